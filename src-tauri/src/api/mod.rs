@@ -1,4 +1,7 @@
+pub mod relay;
 pub mod server;
 pub mod routes;
 
-pub use server::ApiServer;
+pub use relay::{RelayClient, RelayStatus};
+pub use routes::AppState;
+pub use server::{ApiServer, DEFAULT_API_PORT};
\ No newline at end of file
@@ -0,0 +1,615 @@
+//! Remote Access Relay
+//!
+//! Keeps an outbound WebSocket open to a relay server so clients outside
+//! the LAN can reach this node behind NAT. The relay forwards HTTP requests
+//! as JSON frames; each must carry the node's current share key, and is
+//! answered by running it through the local API router in-process.
+//!
+//! Bodies are tunnelled as UTF-8 text and streaming (SSE) endpoints are not
+//! relayed: a response has to complete within `RELAY_REQUEST_TIMEOUT`.
+//!
+//! The node registers with its public key and capabilities, sends a signed
+//! heartbeat every `HEARTBEAT_INTERVAL` and signs every response, so the
+//! relay can tell results really came from the registered node.
+//!
+//! Relayed requests other than reads are jobs: they must carry the
+//! orchestrator's signature over `identity::job_message`, issued within
+//! `MAX_JOB_AGE`, or they are refused before anything runs. Each signed job
+//! runs once; a replayed ID is refused. Without an orchestrator key, jobs
+//! are refused unless `relay.allowUnsignedJobs` is set.
+//!
+//! While the node drains because the contributor is using the machine
+//! (`limits.onlyWhenIdle`), it re-registers as unavailable and answers new
+//! jobs with 503; reads still run.
+//!
+//! Private relays may require mutual TLS; the client certificate comes from
+//! `relay.tls` in the config (a PEM pair or a PKCS#12 bundle whose password
+//! is in the keychain).
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use futures_util::{SinkExt, StreamExt};
+use opentelemetry::{trace::SpanKind, KeyValue};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::Connector;
+use tower::ServiceExt;
+
+use super::routes::{create_router, AppState, SignedJob, RELAY_REQUEST_HEADER};
+use crate::config::RelayTlsConfig;
+use crate::logging;
+use crate::telemetry;
+use crate::models::{NodeCapabilities, NodeEvent};
+use crate::services::redact::Secret;
+use crate::services::bandwidth::{BandwidthMeter, Subsystem};
+use crate::services::pricing::Rates;
+use crate::services::{identity, keychain};
+
+/// Keychain entry holding the PKCS#12 bundle's password
+const PKCS12_PASSWORD_KEY: &str = "relay-pkcs12-password";
+
+/// Longest a relayed request may take
+const RELAY_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest response body sent back through the relay
+const MAX_RELAY_BODY: usize = 8 * 1024 * 1024;
+
+/// Upper bound of the reconnect backoff
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// How often a signed heartbeat goes to the relay
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Oldest (or furthest ahead) a signed job may be, against replays
+const MAX_JOB_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// Current state of the relay connection
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayStatus {
+    pub enabled: bool,
+    pub url: String,
+    pub connected: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connected_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// Messages exchanged with the relay server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RelayFrame {
+    /// Node → relay: announce (or re-announce after rotation) the share key
+    #[serde(rename_all = "camelCase")]
+    Register {
+        node_id: String,
+        share_key: Secret,
+        public_key: String,
+        capabilities: Box<NodeCapabilities>,
+    },
+    /// Node → relay: liveness, signed over `identity::heartbeat_message`,
+    /// with the rates currently advertised
+    #[serde(rename_all = "camelCase")]
+    Heartbeat {
+        node_id: String,
+        timestamp: String,
+        signature: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rates: Option<Rates>,
+    },
+    /// Relay → node: an HTTP request from a remote client
+    Request(RelayRequest),
+    /// Node → relay: the answer to a request, signed over
+    /// `identity::result_message`
+    Response {
+        id: String,
+        status: u16,
+        headers: HashMap<String, String>,
+        body: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+    },
+}
+
+/// An HTTP request forwarded by the relay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RelayRequest {
+    id: String,
+    share_key: Secret,
+    method: String,
+    path: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+    /// When the orchestrator issued the job (RFC 3339)
+    #[serde(default)]
+    timestamp: Option<String>,
+    /// Orchestrator signature over `identity::job_message`
+    #[serde(default)]
+    signature: Option<String>,
+}
+
+/// Background connection to the relay server
+pub struct RelayClient {
+    state: Arc<AppState>,
+    task: Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+impl RelayClient {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self {
+            state,
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Connect to `url`, replacing any existing connection. Reconnects
+    /// with backoff until `stop` is called.
+    pub async fn start(&self, url: &str) {
+        self.stop().await;
+
+        {
+            let mut status = self.state.relay_status.write().await;
+            status.enabled = true;
+            status.url = url.to_string();
+            status.last_error = None;
+        }
+
+        let state = Arc::clone(&self.state);
+        let url = url.trim_end_matches('/').to_string();
+        let handle = tokio::spawn(async move {
+            let mut delay = Duration::from_secs(2);
+            loop {
+                match run_connection(&state, &url).await {
+                    Ok(()) => {
+                        log::info!("Relay connection closed");
+                        delay = Duration::from_secs(2);
+                    }
+                    Err(e) => {
+                        log::warn!("Relay connection failed: {}", e);
+                        state.relay_status.write().await.last_error = Some(e);
+                    }
+                }
+                {
+                    let mut status = state.relay_status.write().await;
+                    if std::mem::replace(&mut status.connected, false) {
+                        state.service_status("relay", false);
+                    }
+                    status.connected_at = None;
+                }
+
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        });
+
+        *self.task.lock().unwrap() = Some(handle);
+    }
+
+    pub async fn stop(&self) {
+        let task = self.task.lock().unwrap().take();
+        if let Some(task) = task {
+            task.abort();
+            log::info!("Relay disconnected");
+        }
+
+        let mut status = self.state.relay_status.write().await;
+        status.enabled = false;
+        if std::mem::replace(&mut status.connected, false) {
+            self.state.service_status("relay", false);
+        }
+        status.connected_at = None;
+    }
+}
+
+/// Save or clear the password of the relay client certificate bundle
+pub fn save_pkcs12_password(password: Option<&str>) -> Result<(), String> {
+    match password {
+        Some(password) => keychain::set(PKCS12_PASSWORD_KEY, password),
+        None => keychain::delete(PKCS12_PASSWORD_KEY),
+    }
+}
+
+fn read_file(path: &str, what: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Failed to read {} {}: {}", what, path, e))
+}
+
+/// TLS settings for the relay connection; `None` keeps the defaults
+pub fn tls_connector(tls: &RelayTlsConfig) -> Result<Option<Connector>, String> {
+    if !tls.has_client_cert() && tls.ca_cert.is_none() {
+        return Ok(None);
+    }
+
+    let mut builder = native_tls::TlsConnector::builder();
+    if let Some(path) = &tls.pkcs12 {
+        let bundle = read_file(path, "client certificate bundle")?;
+        let password = keychain::get(PKCS12_PASSWORD_KEY)?.unwrap_or_default();
+        let identity = native_tls::Identity::from_pkcs12(&bundle, &password)
+            .map_err(|e| format!("Invalid client certificate bundle {}: {}", path, e))?;
+        builder.identity(identity);
+    } else if let Some(cert_path) = &tls.client_cert {
+        let key_path = tls
+            .client_key
+            .as_deref()
+            .ok_or("A client certificate needs its private key (relay.tls.clientKey)")?;
+        let cert = read_file(cert_path, "client certificate")?;
+        let key = read_file(key_path, "client key")?;
+        let identity = native_tls::Identity::from_pkcs8(&cert, &key)
+            .map_err(|e| format!("Invalid client certificate or key (the key must be PKCS#8 PEM): {}", e))?;
+        builder.identity(identity);
+    }
+    if let Some(path) = &tls.ca_cert {
+        let ca = native_tls::Certificate::from_pem(&read_file(path, "CA certificate")?)
+            .map_err(|e| format!("Invalid CA certificate {}: {}", path, e))?;
+        builder.add_root_certificate(ca);
+    }
+
+    let connector = builder
+        .build()
+        .map_err(|e| format!("Failed to set up TLS for the relay: {}", e))?;
+    Ok(Some(Connector::NativeTls(connector)))
+}
+
+/// Explain handshake failures caused by client certificates
+fn connect_error(e: tungstenite::Error, has_client_cert: bool) -> String {
+    let message = match &e {
+        // Proxies that check certificates after the handshake answer in HTTP
+        tungstenite::Error::Http(response) => response
+            .body()
+            .as_deref()
+            .map(|body| String::from_utf8_lossy(body).to_lowercase())
+            .unwrap_or_default(),
+        other => other.to_string().to_lowercase(),
+    };
+    let demanded = ["certificate required", "no required ssl certificate", "alert handshake failure"];
+    let rejected = ["bad certificate", "unknown ca", "certificate unknown", "certificate expired", "certificate revoked"];
+
+    if !has_client_cert && demanded.iter().any(|m| message.contains(m)) {
+        "The relay requires a client certificate (mutual TLS), but none is configured".to_string()
+    } else if has_client_cert && rejected.iter().any(|m| message.contains(m)) {
+        format!("The relay rejected the client certificate: {}", e)
+    } else {
+        format!("Failed to connect to relay: {}", e)
+    }
+}
+
+/// Serve one relay connection until it drops
+async fn run_connection(state: &Arc<AppState>, url: &str) -> Result<(), String> {
+    state.bandwidth.check(Subsystem::Relay)?;
+    let node_id = state.node_id.read().await.clone();
+    let endpoint = format!("{}/node/{}", url, node_id);
+    let tls = state.config.read().await.relay.tls.clone();
+    let connector = tls_connector(&tls)?;
+
+    log::info!("Connecting to relay {}", endpoint);
+    let attributes = vec![KeyValue::new("relay.url", url.to_string())];
+    let connected = telemetry::in_span("relay.connect", SpanKind::Client, attributes, async {
+        let result = async {
+            let (ws, _) = tokio_tungstenite::connect_async_tls_with_config(endpoint.as_str(), None, false, connector)
+                .await
+                .map_err(|e| connect_error(e, tls.has_client_cert()))?;
+            let (mut sink, stream) = ws.split();
+            let share_key = state.share_key.read().await.clone();
+            send_frame(&mut sink, &state.bandwidth, &register_frame(state, share_key).await).await?;
+            Ok::<_, String>((sink, stream))
+        }
+        .await;
+        if let Err(e) = &result {
+            telemetry::set_error(e);
+        }
+        result
+    })
+    .await;
+    let outcome = if connected.is_ok() { "connected" } else { "failed" };
+    telemetry::count("otherthing.relay.connections", &[KeyValue::new("outcome", outcome)]);
+    let (mut sink, mut stream) = connected?;
+
+    {
+        let mut status = state.relay_status.write().await;
+        status.connected = true;
+        status.connected_at = Some(chrono::Utc::now().to_rfc3339());
+        status.last_error = None;
+    }
+    state.service_status("relay", true);
+    log::info!("Relay connected");
+
+    // Responses are produced concurrently and funnelled back through here
+    let (response_tx, mut response_rx) = mpsc::channel::<RelayFrame>(64);
+    let mut node_events = state.node_events.subscribe();
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+
+    loop {
+        tokio::select! {
+            message = stream.next() => {
+                let message = match message {
+                    Some(Ok(message)) => message,
+                    Some(Err(e)) => return Err(format!("Relay connection error: {}", e)),
+                    None => return Ok(()),
+                };
+                state.bandwidth.record(Subsystem::Relay, message.len() as u64, 0);
+                match message {
+                    Message::Text(text) => match serde_json::from_str::<RelayFrame>(&text) {
+                        Ok(RelayFrame::Request(request)) => {
+                            let state = Arc::clone(state);
+                            let response_tx = response_tx.clone();
+                            tokio::spawn(async move {
+                                let job_id = request.id.clone();
+                                let parent = telemetry::context_from_map(&request.headers);
+                                let attributes = vec![
+                                    KeyValue::new("job.id", job_id.clone()),
+                                    KeyValue::new("http.request.method", request.method.clone()),
+                                ];
+                                if let Err(e) = state.store.jobs().start(&job_id, &request.method, &request.path) {
+                                    log::warn!("{}", e);
+                                }
+                                let _ = state.node_events.send(NodeEvent::JobStarted {
+                                    job_id: job_id.clone(),
+                                    method: request.method.clone(),
+                                    path: request.path.clone(),
+                                });
+                                let job = telemetry::in_span_with_parent("relay.job", SpanKind::Consumer, attributes, parent, async {
+                                    let started = std::time::Instant::now();
+                                    let response = handle_request(&state, request).await;
+                                    if let RelayFrame::Response { status, .. } = &response {
+                                        let duration_ms = started.elapsed().as_millis() as u64;
+                                        if let Err(e) = state.store.jobs().finish(&job_id, *status, duration_ms) {
+                                            log::warn!("{}", e);
+                                        }
+                                        let _ = state.node_events.send(NodeEvent::JobFinished {
+                                            job_id: job_id.clone(),
+                                            status: *status,
+                                            duration_ms,
+                                        });
+                                        telemetry::set_attribute(KeyValue::new("http.response.status_code", i64::from(*status)));
+                                        if *status >= 500 {
+                                            telemetry::set_error(&format!("Job failed with status {}", status));
+                                        }
+                                        telemetry::record_duration(
+                                            "otherthing.job.duration",
+                                            started.elapsed(),
+                                            &[KeyValue::new("http.response.status_code", i64::from(*status))],
+                                        );
+                                    }
+                                    response
+                                });
+                                let response = logging::with_context(&[("job_id", &job_id)], job).await;
+                                let _ = response_tx.send(response).await;
+                            });
+                        }
+                        Ok(_) => {}
+                        Err(e) => log::warn!("Ignoring malformed relay frame: {}", e),
+                    },
+                    Message::Ping(data) => {
+                        sink.send(Message::Pong(data)).await
+                            .map_err(|e| format!("Relay connection error: {}", e))?;
+                    }
+                    Message::Close(_) => return Ok(()),
+                    _ => {}
+                }
+            }
+            Some(response) = response_rx.recv() => {
+                send_frame(&mut sink, &state.bandwidth, &sign_response(state, response)).await?;
+            }
+            _ = heartbeat.tick() => {
+                // Over the cap: drop the connection; reconnecting is refused until the month turns
+                state.bandwidth.check(Subsystem::Relay)?;
+                send_frame(&mut sink, &state.bandwidth, &heartbeat_frame(state, &node_id).await).await?;
+            }
+            event = node_events.recv() => {
+                match event {
+                    Ok(NodeEvent::ShareKeyRotated { share_key, .. }) => {
+                        // The old key stops working at the relay too
+                        send_frame(&mut sink, &state.bandwidth, &register_frame(state, share_key).await).await?;
+                    }
+                    Ok(NodeEvent::PricingChanged { .. }) => {
+                        // Advertise the new rates without waiting for the next beat
+                        send_frame(&mut sink, &state.bandwidth, &heartbeat_frame(state, &node_id).await).await?;
+                    }
+                    Ok(NodeEvent::AvailabilityChanged { .. }) => {
+                        // Re-announce so the orchestrator stops or resumes sending jobs
+                        let share_key = state.share_key.read().await.clone();
+                        send_frame(&mut sink, &state.bandwidth, &register_frame(state, share_key).await).await?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+async fn register_frame(state: &AppState, share_key: String) -> RelayFrame {
+    let capabilities = state.capabilities().await;
+    RelayFrame::Register {
+        node_id: capabilities.node_id.clone(),
+        share_key: Secret::new(share_key),
+        public_key: capabilities.public_key.clone(),
+        capabilities: Box::new(capabilities),
+    }
+}
+
+async fn heartbeat_frame(state: &AppState, node_id: &str) -> RelayFrame {
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    RelayFrame::Heartbeat {
+        signature: state.identity.sign(&identity::heartbeat_message(node_id, &timestamp)),
+        node_id: node_id.to_string(),
+        timestamp,
+        rates: Some(state.pricing.rates().await),
+    }
+}
+
+/// Sign a response with the node's identity
+fn sign_response(state: &AppState, frame: RelayFrame) -> RelayFrame {
+    match frame {
+        RelayFrame::Response { id, status, headers, body, .. } => RelayFrame::Response {
+            signature: Some(state.identity.sign(&identity::result_message(&id, status, body.as_bytes()))),
+            id,
+            status,
+            headers,
+            body,
+        },
+        frame => frame,
+    }
+}
+
+async fn send_frame<S>(sink: &mut S, bandwidth: &BandwidthMeter, frame: &RelayFrame) -> Result<(), String>
+where
+    S: SinkExt<Message> + Unpin,
+    S::Error: std::fmt::Display,
+{
+    let text = serde_json::to_string(frame).map_err(|e| e.to_string())?;
+    bandwidth.record(Subsystem::Relay, 0, text.len() as u64);
+    sink.send(Message::Text(text))
+        .await
+        .map_err(|e| format!("Relay connection error: {}", e))
+}
+
+/// IDs of signed jobs already accepted, with when they can be forgotten
+fn seen_jobs() -> &'static Mutex<HashMap<String, Instant>> {
+    static SEEN: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    SEEN.get_or_init(Default::default)
+}
+
+/// Remember a job ID; false if it was seen before. A signature stays valid
+/// until `MAX_JOB_AGE` past a timestamp at most `MAX_JOB_AGE` ahead, so IDs
+/// are kept for twice that.
+fn first_seen(id: &str) -> bool {
+    let now = Instant::now();
+    let mut seen = seen_jobs().lock().unwrap();
+    seen.retain(|_, forget_at| *forget_at > now);
+    if seen.contains_key(id) {
+        return false;
+    }
+    seen.insert(id.to_string(), now + 2 * MAX_JOB_AGE);
+    true
+}
+
+/// Check a job's signature against the orchestrator key, and that it has
+/// not run before
+fn verify_job(orchestrator_key: &str, request: &RelayRequest) -> Result<(), &'static str> {
+    let (Some(timestamp), Some(signature)) = (&request.timestamp, &request.signature) else {
+        return Err("Job is not signed by the orchestrator");
+    };
+    let issued = chrono::DateTime::parse_from_rfc3339(timestamp).map_err(|_| "Invalid job timestamp")?;
+    let age = (chrono::Utc::now() - issued.with_timezone(&chrono::Utc)).num_seconds().unsigned_abs();
+    if age > MAX_JOB_AGE.as_secs() {
+        return Err("Job signature has expired");
+    }
+    let body = request.body.as_deref().unwrap_or_default();
+    let message = identity::job_message(&request.id, &request.method, &request.path, timestamp, body.as_bytes());
+    if !identity::verify(orchestrator_key, &message, signature) {
+        return Err("Invalid job signature");
+    }
+    if !first_seen(&request.id) {
+        return Err("Job has already been run");
+    }
+    Ok(())
+}
+
+/// Run a relayed request against the local router
+async fn handle_request(state: &Arc<AppState>, request: RelayRequest) -> RelayFrame {
+    let id = request.id.clone();
+    let error = |status: StatusCode, message: &str| RelayFrame::Response {
+        id: id.clone(),
+        status: status.as_u16(),
+        headers: HashMap::from([("content-type".to_string(), "application/json".to_string())]),
+        body: serde_json::json!({ "error": message }).to_string(),
+        signature: None,
+    };
+
+    if !identity::secrets_match(request.share_key.expose(), &state.share_key.read().await) {
+        return error(StatusCode::UNAUTHORIZED, "Invalid share key");
+    }
+    if !request.path.starts_with('/') {
+        return error(StatusCode::BAD_REQUEST, "Invalid path");
+    }
+
+    // Reads run unsigned; anything that could start work needs the orchestrator's signature
+    let is_read = ["GET", "HEAD", "OPTIONS"].contains(&request.method.to_uppercase().as_str());
+    // Draining: jobs already running finish, new ones go elsewhere
+    if !is_read && !state.idle.is_available().await {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Node is busy; the contributor is using the machine");
+    }
+    let (orchestrator_key, allow_unsigned) = {
+        let config = state.config.read().await;
+        (config.orchestrator_public_key.clone(), config.relay.allow_unsigned_jobs)
+    };
+    let signed = match (is_read, orchestrator_key) {
+        (true, _) => false,
+        (false, Some(key)) => {
+            if let Err(reason) = verify_job(&key, &request) {
+                log::warn!("Refused relayed {} {}: {}", request.method, request.path, reason);
+                return error(StatusCode::UNAUTHORIZED, reason);
+            }
+            true
+        }
+        (false, None) if allow_unsigned => {
+            log::warn!(
+                "Running unsigned relayed {} {}: no orchestrator key is set and relay.allowUnsignedJobs is on",
+                request.method, request.path
+            );
+            false
+        }
+        (false, None) => {
+            log::warn!("Refused relayed {} {}: no orchestrator key is set", request.method, request.path);
+            return error(
+                StatusCode::UNAUTHORIZED,
+                "Jobs must be signed, and this node has no orchestrator key to check them with",
+            );
+        }
+    };
+
+    let mut builder = Request::builder().method(request.method.as_str()).uri(request.path.as_str());
+    for (name, value) in &request.headers {
+        if !name.eq_ignore_ascii_case(RELAY_REQUEST_HEADER) && !telemetry::is_context_header(name) {
+            builder = builder.header(name, value);
+        }
+    }
+    // The handler's span continues this job's trace
+    for (name, value) in telemetry::current_context_headers() {
+        builder = builder.header(name, value);
+    }
+    // Marks the request as a job in the audit log
+    builder = builder.header(RELAY_REQUEST_HEADER, request.id.as_str());
+    if signed {
+        builder = builder.extension(SignedJob);
+    }
+    let request = match builder.body(Body::from(request.body.unwrap_or_default())) {
+        Ok(request) => request,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+
+    // The deadline covers the handler as well as reading its body
+    let run = async {
+        let response = match create_router(Arc::clone(state)).oneshot(request).await {
+            Ok(response) => response,
+            Err(e) => match e {},
+        };
+        let (parts, body) = response.into_parts();
+        axum::body::to_bytes(body, MAX_RELAY_BODY).await.map(|bytes| (parts, bytes))
+    };
+    let (parts, bytes) = match tokio::time::timeout(RELAY_REQUEST_TIMEOUT, run).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => return error(StatusCode::BAD_GATEWAY, &e.to_string()),
+        Err(_) => return error(StatusCode::GATEWAY_TIMEOUT, "Request timed out (streaming endpoints are not relayed)"),
+    };
+
+    RelayFrame::Response {
+        id,
+        status: parts.status.as_u16(),
+        headers: parts
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body: String::from_utf8_lossy(&bytes).into_owned(),
+        signature: None,
+    }
+}
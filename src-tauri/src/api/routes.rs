@@ -1,124 +1,667 @@
 use axum::{
-    extract::{Path, State},
+    extract::{ConnectInfo, Path, State},
+    handler::Handler,
     http::StatusCode,
-    response::IntoResponse,
-    routing::{get, post, delete},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    routing::{get, post, put, delete},
     Json, Router,
 };
+use futures_util::stream::{self, Stream, StreamExt};
+use opentelemetry::{trace::SpanKind, KeyValue};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, RwLock};
+
+use super::relay::RelayStatus;
+use crate::config::{BandwidthConfig, LimitsConfig, NodeConfig, PaymentsConfig, PricingConfig, StorageConfig};
+use crate::error::NodeError;
+use crate::pagination::ListQuery;
+use crate::models::{ClusterStatus, IpfsProgress, NodeCapabilities, NodeEvent, PullProgress, ResourceLimits, TokenUsage};
+use crate::services::agent::AgentStatus;
+use crate::services::container::ContainerError;
+use crate::notifications;
+use crate::telemetry;
 
 use crate::services::{
-    AgentManager, CreateAgentRequest,
-    ContainerManager, CreateContainerRequest,
-    HardwareDetector, IpfsManager, OllamaManager,
+    AgentManager, Availability, CreateAgentRequest,
+    AppDeployment, AppSpec, BuildImageRequest, ContainerManager, CreateContainerRequest, LogLine, PruneRequest, RuntimeInfo, RuntimeType, UpdateContainerRequest,
+    gpu_credentials, gpu_provider, GpuOfferCache, GpuProvider, GpuSpendTracker, OfferFilter, OfferSort, RentRequest, SpendSummary,
+    agent_templates, state_store, AuditKind, AuditLog, AuditQuery, ClusterFollower, Fleet, IdleMonitor, RemoteQuery, AddRemoteNodeRequest, EarningsLedger, EarningsQuery, ExecutionQuery, ExportFormat, NewEarning, HardwareDetector, ImagePolicy, IpfsManager, NodeIdentity, OllamaManager, PaymentMonitor, ToolRegistry,
+    MAX_CAT_SIZE, MAX_GET_SIZE, backup, discovery, identity, redact, resources, Discovery,
+    fleet::SHARE_KEY_HEADER, mesh::{MESH_WORKSPACE, NODE_ID_HEADER}, DispatchRequest, Mesh, MeshWork, Pubsub,
+    ProviderConfig, ProviderRegistry, ProvisionRequest, RegistryCredential, RemoteComputeManager,
+    CreateWorkspaceRequest, UpdateWorkspaceRequest, WorkspaceManager,
+    DocumentIndex, IngestRequest, RagQuery, SearchDocumentsTool,
+    Settings, StateStore, Challenge, PinRequest, Storage, BandwidthMeter, BandwidthReport, bandwidth::Subsystem,
+    DemandSignal, Pricing, PricingStatus, SandboxConfig,
 };
 
 /// Shared application state
 pub struct AppState {
     pub ollama: Arc<OllamaManager>,
     pub ipfs: Arc<IpfsManager>,
+    pub cluster: Arc<ClusterFollower>,
     pub containers: Arc<ContainerManager>,
-    pub agents: AgentManager,
+    pub apps: Arc<AppDeployment>,
+    pub agents: Arc<AgentManager>,
+    pub providers: Arc<ProviderRegistry>,
+    pub workspaces: Arc<WorkspaceManager>,
     pub node_id: Arc<RwLock<String>>,
+    /// Keypair heartbeats and results are signed with
+    pub identity: Arc<NodeIdentity>,
     pub share_key: Arc<RwLock<String>>,
     pub node_running: Arc<RwLock<bool>>,
+    pub started_at: Arc<RwLock<Option<chrono::DateTime<chrono::Utc>>>>,
+    pub node_events: broadcast::Sender<NodeEvent>,
+    pub config: Arc<RwLock<NodeConfig>>,
+    pub relay_status: Arc<RwLock<RelayStatus>>,
+    /// Tokens served by the generate/chat proxy, keyed by model
+    pub token_usage: Arc<RwLock<HashMap<String, TokenUsage>>>,
+    /// Accrued cost of rented GPUs against the monthly budget
+    pub gpu_spend: Arc<GpuSpendTracker>,
+    /// Recently fetched GPU offers, by provider
+    pub gpu_offers: Arc<GpuOfferCache>,
+    /// Rented GPUs attached as agent compute
+    pub remote_compute: Arc<RemoteComputeManager>,
+    /// What the node has been paid, per job
+    pub earnings: Arc<EarningsLedger>,
+    /// On-chain payments received, checked against the earnings
+    pub payments: Arc<PaymentMonitor>,
+    /// Hash-chained record of the work run on this machine
+    pub audit: Arc<AuditLog>,
+    /// Node ID, share key, settings, agent executions and job history
+    pub store: Arc<StateStore>,
+    /// Whether the contributor is away and the node may take jobs
+    pub idle: Arc<IdleMonitor>,
+    /// The user's other nodes, watched from this one
+    pub fleet: Arc<Fleet>,
+    /// Other nodes found on the local network
+    pub discovery: Arc<Discovery>,
+    /// Jobs exchanged with paired nodes on the LAN
+    pub mesh: Arc<Mesh>,
+    /// Node announcements and job offers over IPFS pubsub
+    pub pubsub: Arc<Pubsub>,
+    /// Data pinned for the orchestrator as contributed storage
+    pub storage: Arc<Storage>,
+    /// Bytes moved per subsystem this month, against the caps
+    pub bandwidth: Arc<BandwidthMeter>,
+    /// Rates advertised to the orchestrator, moved with demand when enabled
+    pub pricing: Arc<Pricing>,
+    /// Documents chunked and embedded for retrieval
+    pub documents: Arc<DocumentIndex>,
+    /// Wrong share keys offered to `/health`, per caller
+    pub key_checks: Arc<KeyCheckLimiter>,
 }
 
 impl AppState {
     pub async fn new() -> Self {
-        let ollama = Arc::new(OllamaManager::new());
-        let ipfs = Arc::new(IpfsManager::new());
-        let containers = Arc::new(ContainerManager::new().await);
+        let config = NodeConfig::load();
+        let ollama = Arc::new(OllamaManager::with_config(config.ollama.clone()));
+        let ipfs = Arc::new(IpfsManager::with_config(config.ipfs.clone()));
+        let cluster = Arc::new(ClusterFollower::new(Arc::clone(&ipfs), config.cluster.clone()));
+        let audit = Arc::new(AuditLog::open_default().unwrap_or_else(|e| {
+            log::error!("{}; the audit log will not survive a restart", e);
+            AuditLog::in_memory().expect("in-memory SQLite log")
+        }));
+        let node_events = broadcast::channel(256).0;
+        let store = Arc::new(StateStore::open_default().unwrap_or_else(|e| {
+            log::error!("{}; node state will not survive a restart", e);
+            StateStore::in_memory().expect("in-memory SQLite store")
+        }));
+        let bandwidth = Arc::new(BandwidthMeter::new(store.bandwidth(), config.bandwidth.clone()));
+        let containers = Arc::new(
+            ContainerManager::new(
+                config.container.preferred_runtime,
+                config.container.image_policy.clone(),
+                Arc::clone(&audit),
+                Arc::clone(&bandwidth),
+                node_events.clone(),
+            )
+            .await,
+        );
+
+        let resource_limits = config.limits.resources.clone();
+        ollama.set_resource_limits(resource_limits.clone());
+        ipfs.set_resource_limits(resource_limits.clone());
+        containers.set_resource_limits(resource_limits).await;
+
+        if let Ok(Some(path)) = store.settings().get(state_store::OLLAMA_PATH) {
+            if !ollama.set_path(std::path::PathBuf::from(&path)) {
+                log::warn!("Saved Ollama path {} no longer exists", path);
+            }
+        }
 
         // Generate persistent node ID and share key
-        let node_id = generate_or_load_node_id();
-        let share_key = generate_share_key();
+        let settings = store.settings();
+        let node_id = generate_or_load_node_id(&settings);
+        let share_key = load_or_generate_share_key(&settings);
+        let identity = NodeIdentity::load_or_generate().unwrap_or_else(|e| {
+            log::error!("{}; signing with a temporary identity", e);
+            NodeIdentity::ephemeral()
+        });
+
+        let identity = Arc::new(identity);
+        let earnings = EarningsLedger::open_default().unwrap_or_else(|e| {
+            log::error!("{}; earnings will not survive a restart", e);
+            EarningsLedger::in_memory().expect("in-memory SQLite ledger")
+        });
+
+        let providers = Arc::new(ProviderRegistry::load(Arc::clone(&ollama)));
+        let workspaces = Arc::new(WorkspaceManager::load());
+
+        let sandbox = SandboxConfig {
+            host_fallback: config.agent.allow_host_shell,
+            ..SandboxConfig::default()
+        };
+        let config = Arc::new(RwLock::new(config));
+        let idle = Arc::new(IdleMonitor::new(Arc::clone(&config), node_events.clone()));
+        let gpu_spend = Arc::new(GpuSpendTracker::load(Arc::clone(&config), node_events.clone()));
+        let remote_compute = Arc::new(RemoteComputeManager::load(Arc::clone(&providers), Arc::clone(&config)));
+        let earnings = Arc::new(earnings);
+        let payments = PaymentMonitor::open_default(Arc::clone(&earnings), Arc::clone(&config), node_events.clone())
+            .unwrap_or_else(|e| {
+                log::error!("{}; received payments will be rescanned after a restart", e);
+                PaymentMonitor::in_memory(Arc::clone(&earnings), Arc::clone(&config), node_events.clone())
+                    .expect("in-memory SQLite store")
+            });
+
+        let documents = Arc::new(DocumentIndex::new(store.documents(), Arc::clone(&ollama), Arc::clone(&ipfs)));
+        let mut tools = ToolRegistry::with_defaults(Arc::clone(&ipfs), Arc::clone(&containers), sandbox)
+            .with_audit(Arc::clone(&audit));
+        tools.register(Arc::new(SearchDocumentsTool::new(Arc::clone(&documents))));
+        let agents = Arc::new(AgentManager::new(
+            Arc::clone(&ollama),
+            Arc::clone(&ipfs),
+            Arc::clone(&providers),
+            Arc::clone(&workspaces),
+            Arc::new(tools),
+            Arc::new(store.agents()),
+            Arc::new(store.agent_memory()),
+            node_events.clone(),
+        ));
+        let fleet = Arc::new(Fleet::new(store.remote_nodes()));
+        let pubsub = Arc::new(Pubsub::new(
+            Arc::clone(&ipfs),
+            Arc::clone(&identity),
+            Arc::clone(&config),
+            node_events.clone(),
+        ));
+        let mesh = Arc::new(Mesh::new(
+            store.mesh_jobs(),
+            Arc::clone(&fleet),
+            Arc::clone(&agents),
+            Arc::clone(&containers),
+            Arc::clone(&config),
+        ));
+        let pricing = Arc::new(Pricing::new(
+            Arc::clone(&config),
+            store.settings(),
+            Arc::clone(&idle),
+            node_events.clone(),
+        ));
+        let storage = Arc::new(Storage::new(
+            Arc::clone(&ipfs),
+            store.storage_pins(),
+            Arc::clone(&earnings),
+            Arc::clone(&identity),
+            Arc::clone(&config),
+        ));
 
         Self {
-            agents: AgentManager::new(Arc::clone(&ollama)),
+            agents,
             ollama,
             ipfs,
+            cluster,
+            apps: Arc::new(AppDeployment::new(Arc::clone(&containers))),
             containers,
+            providers,
+            workspaces,
             node_id: Arc::new(RwLock::new(node_id)),
+            identity,
             share_key: Arc::new(RwLock::new(share_key)),
-            node_running: Arc::new(RwLock::new(true)), // Running by default
+            node_running: Arc::new(RwLock::new(false)),
+            started_at: Arc::new(RwLock::new(None)),
+            node_events,
+            config,
+            gpu_spend,
+            gpu_offers: Arc::new(GpuOfferCache::new()),
+            remote_compute,
+            earnings,
+            payments: Arc::new(payments),
+            audit,
+            fleet,
+            mesh,
+            pubsub,
+            storage,
+            bandwidth,
+            pricing,
+            documents,
+            key_checks: Arc::new(KeyCheckLimiter::default()),
+            discovery: Arc::new(Discovery::new()),
+            store,
+            idle,
+            relay_status: Arc::new(RwLock::new(RelayStatus::default())),
+            token_usage: Arc::new(RwLock::new(HashMap::new())),
         }
     }
-}
 
-fn generate_or_load_node_id() -> String {
-    // Try to load from config, or generate new
-    let config_dir = dirs::config_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join("otherthing-node");
+    /// Mark the node as running and start the uptime clock
+    pub async fn mark_started(&self) {
+        *self.node_running.write().await = true;
+        *self.started_at.write().await = Some(chrono::Utc::now());
+        self.service_status("node", true);
+    }
+
+    pub async fn mark_stopped(&self) {
+        *self.node_running.write().await = false;
+        *self.started_at.write().await = None;
+        self.service_status("node", false);
+    }
+
+    /// Tell subscribers a service came up or went down
+    pub fn service_status(&self, service: &str, running: bool) {
+        let _ = self.node_events.send(NodeEvent::ServiceStatus {
+            service: service.to_string(),
+            running,
+        });
+    }
+
+    /// Replace the share key with a fresh one, invalidating the old key,
+    /// and tell connected clients about it
+    pub async fn rotate_share_key(&self) -> Result<String, String> {
+        let mut share_key = self.share_key.write().await;
+        let mut key = new_share_key();
+        while key == *share_key {
+            key = new_share_key();
+        }
+        save_share_key(&self.store.settings(), &key)?;
+        *share_key = key.clone();
+        drop(share_key);
+
+        log::info!("Share key rotated");
+        let _ = self.node_events.send(NodeEvent::ShareKeyRotated {
+            share_key: key.clone(),
+            rotated_at: chrono::Utc::now().to_rfc3339(),
+        });
+        Ok(key)
+    }
+
+    /// Save the cluster follower settings and start or stop following
+    pub async fn configure_cluster(
+        &self,
+        enabled: bool,
+        config_cid: Option<String>,
+        name: Option<String>,
+    ) -> Result<ClusterStatus, String> {
+        let mut cluster = self.cluster.config();
+        cluster.enabled = enabled;
+        if let Some(cid) = config_cid {
+            let cid = cid.trim().trim_start_matches("/ipfs/").to_string();
+            cluster.config_cid = (!cid.is_empty()).then_some(cid);
+        }
+        if let Some(name) = name {
+            cluster.name = name.trim().to_string();
+        }
+        if enabled && cluster.config_cid.is_none() {
+            return Err("A cluster configuration CID is required".to_string());
+        }
+
+        {
+            let mut config = self.config.write().await;
+            config.cluster = cluster.clone();
+            config.save()?;
+        }
+        self.cluster.set_config(cluster);
+
+        if enabled && self.ipfs.is_running() {
+            self.cluster.start().await?;
+        }
+        Ok(self.cluster.get_status())
+    }
+
+    /// Save the preferred container backend and switch to it
+    pub async fn set_container_runtime(&self, runtime: Option<RuntimeType>) -> Result<RuntimeInfo, String> {
+        {
+            let mut config = self.config.write().await;
+            config.container.preferred_runtime = runtime;
+            config.save()?;
+        }
+        self.containers.set_preferred_runtime(runtime).await.map_err(|e| e.to_string())
+    }
+
+    /// Save the image policy and apply it to the next pull or create
+    pub async fn set_image_policy(&self, policy: ImagePolicy) -> Result<ImagePolicy, String> {
+        {
+            let mut config = self.config.write().await;
+            config.container.image_policy = policy.clone();
+            config.save()?;
+        }
+        self.containers.set_image_policy(policy.clone()).await;
+        log::info!("Image policy updated");
+        Ok(policy)
+    }
 
-    let node_id_file = config_dir.join("node_id");
+    /// Save the monthly GPU budget and apply it on the next poll
+    pub async fn set_gpu_budget(&self, monthly_budget: Option<f64>, auto_destroy: bool) -> Result<SpendSummary, String> {
+        if monthly_budget.is_some_and(|b| !b.is_finite() || b <= 0.0) {
+            return Err("Budget must be a positive amount".to_string());
+        }
+        {
+            let mut config = self.config.write().await;
+            config.gpu.monthly_budget = monthly_budget;
+            config.gpu.auto_destroy = auto_destroy;
+            config.save()?;
+        }
+        Ok(self.gpu_spend.summary().await)
+    }
 
-    if node_id_file.exists() {
-        if let Ok(id) = std::fs::read_to_string(&node_id_file) {
-            let id = id.trim().to_string();
-            if !id.is_empty() {
-                return id;
+    /// Update where the node is paid; a new wallet or contract starts a fresh scan
+    pub async fn set_payments_config(&self, payments: PaymentsConfig) -> Result<PaymentsConfig, String> {
+        for (name, value) in [
+            ("wallet address", payments.wallet_address.as_deref()),
+            ("USDC contract", Some(payments.usdc_contract.as_str())),
+        ] {
+            if value.is_some_and(|v| !crate::services::payments::is_address(v)) {
+                return Err(format!("Invalid {}", name));
             }
         }
+        if !payments.rpc_url.starts_with("http://") && !payments.rpc_url.starts_with("https://") {
+            return Err("RPC URL must be an http(s) URL".to_string());
+        }
+        let mut config = self.config.write().await;
+        config.payments = payments;
+        config.save()?;
+        Ok(config.payments.clone())
     }
 
-    // Generate new node ID
-    let node_id = uuid::Uuid::new_v4().to_string();
+    /// Save the storage contribution settings. With a drive set, the IPFS
+    /// repo is moved onto it when contribution is on and it lives elsewhere.
+    pub async fn set_storage_config(&self, storage: StorageConfig) -> Result<StorageConfig, NodeError> {
+        if !storage.storage_gb_hour_cents.is_finite() || storage.storage_gb_hour_cents < 0.0 {
+            return Err(NodeError::Invalid("storageGbHourCents cannot be negative".to_string()));
+        }
+        if storage.currency.trim().is_empty() {
+            return Err(NodeError::Invalid("currency is required".to_string()));
+        }
+        if storage.budget_gb == 0 {
+            return Err(NodeError::Invalid("budgetGb must be at least 1".to_string()));
+        }
+        if storage.proof_interval_secs < 60 {
+            return Err(NodeError::Invalid("proofIntervalSecs must be at least 60".to_string()));
+        }
 
-    // Save it
-    let _ = std::fs::create_dir_all(&config_dir);
-    let _ = std::fs::write(&node_id_file, &node_id);
+        let repo = self.ipfs.get_repo_path();
+        if let Some(mount) = &storage.drive {
+            let drive = HardwareDetector::get_drives()
+                .into_iter()
+                .find(|drive| &drive.mount == mount)
+                .ok_or_else(|| NodeError::NotFound(format!("No drive mounted at {}", mount)))?;
+            // Pins already on the drive count as space it has
+            let on_drive = if repo.starts_with(&drive.mount) { self.storage.status().await?.used_bytes } else { 0 };
+            let budget = storage.budget_gb.saturating_mul(1 << 30);
+            if budget > drive.available.saturating_add(on_drive) {
+                return Err(NodeError::Invalid(format!(
+                    "{} has {} bytes free, less than the {} GB budget",
+                    mount, drive.available, storage.budget_gb
+                )));
+            }
+            if storage.enabled && !repo.starts_with(&drive.mount) {
+                let dest = std::path::Path::new(&drive.mount).join("otherthing-node").join("ipfs");
+                self.ipfs.relocate_repo(&dest).await?;
+            }
+        }
 
-    node_id
-}
+        let mut config = self.config.write().await;
+        config.ipfs = self.ipfs.config();
+        config.storage = storage;
+        config.save()?;
+        Ok(config.storage.clone())
+    }
 
-fn generate_share_key() -> String {
-    // Try to load from config, or generate new
-    let config_dir = dirs::config_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join("otherthing-node");
+    /// Save the rates and the bounds dynamic pricing keeps them in, and
+    /// advertise the result
+    pub async fn set_pricing_config(&self, pricing: PricingConfig) -> Result<PricingStatus, NodeError> {
+        let rates = [
+            pricing.cpu_core_hour_cents,
+            pricing.memory_gb_hour_cents,
+            pricing.gpu_hour_cents,
+            pricing.inference_1k_tokens_cents,
+        ];
+        if rates.iter().any(|rate| !rate.is_finite() || *rate < 0.0) {
+            return Err(NodeError::Invalid("Rates cannot be negative".to_string()));
+        }
+        if pricing.currency.trim().is_empty() {
+            return Err(NodeError::Invalid("currency is required".to_string()));
+        }
+        let dynamic = &pricing.dynamic;
+        if !dynamic.floor_percent.is_finite() || dynamic.floor_percent <= 0.0 {
+            return Err(NodeError::Invalid("floorPercent must be positive".to_string()));
+        }
+        if !dynamic.ceiling_percent.is_finite() || dynamic.ceiling_percent < dynamic.floor_percent {
+            return Err(NodeError::Invalid("ceilingPercent cannot be below floorPercent".to_string()));
+        }
+        if !dynamic.max_step_percent.is_finite() || dynamic.max_step_percent <= 0.0 {
+            return Err(NodeError::Invalid("maxStepPercent must be positive".to_string()));
+        }
+        if dynamic.interval_secs < 30 {
+            return Err(NodeError::Invalid("intervalSecs must be at least 30".to_string()));
+        }
+        {
+            let mut config = self.config.write().await;
+            config.pricing = pricing;
+            config.save()?;
+        }
+        self.pricing.readvertise().await;
+        Ok(self.pricing.status().await)
+    }
 
-    let share_key_file = config_dir.join("share_key");
+    /// Save the monthly bandwidth caps and apply them to the next transfer
+    pub async fn set_bandwidth_config(&self, caps: BandwidthConfig) -> Result<BandwidthReport, NodeError> {
+        let values = [caps.total_gb, caps.ipfs_gb, caps.image_pulls_gb, caps.uploads_gb, caps.relay_gb];
+        if values.into_iter().flatten().any(|gb| !gb.is_finite() || gb <= 0.0) {
+            return Err(NodeError::Invalid("Bandwidth caps must be positive".to_string()));
+        }
+        {
+            let mut config = self.config.write().await;
+            config.bandwidth = caps.clone();
+            config.save()?;
+        }
+        self.bandwidth.set_caps(caps);
+        Ok(self.bandwidth.report())
+    }
 
-    if share_key_file.exists() {
-        if let Ok(key) = std::fs::read_to_string(&share_key_file) {
-            let key = key.trim().to_string();
-            if !key.is_empty() {
-                return key;
+    /// Announce this node on IPFS pubsub every interval, and as soon as its
+    /// availability changes, for the life of the app
+    pub async fn announce_on_pubsub(self: Arc<Self>) {
+        let mut events = self.node_events.subscribe();
+        loop {
+            let pubsub = self.config.read().await.ipfs.pubsub.clone();
+            if pubsub.enabled && self.ipfs.is_running() {
+                if let Err(e) = self.pubsub.announce(self.capabilities().await).await {
+                    log::debug!("{}", e);
+                }
             }
+            let interval = Duration::from_secs(pubsub.announce_interval_secs.max(10));
+            let _ = tokio::time::timeout(interval, async {
+                loop {
+                    match events.recv().await {
+                        Ok(NodeEvent::AvailabilityChanged { .. }) | Err(broadcast::error::RecvError::Closed) => break,
+                        _ => continue,
+                    }
+                }
+            })
+            .await;
         }
     }
 
-    // Generate new share key (8 char alphanumeric, easy to type)
-    use std::collections::hash_map::RandomState;
-    use std::hash::{BuildHasher, Hasher};
+    /// Announce this node on the LAN and look for others, or refresh the
+    /// announcement when already running
+    pub async fn start_discovery(&self) -> Result<(), String> {
+        let announcement = discovery::Announcement {
+            port: super::server::DEFAULT_API_PORT,
+            capabilities: self.capabilities().await,
+        };
+        self.discovery.start(announcement)
+    }
 
-    let s = RandomState::new();
-    let mut hasher = s.build_hasher();
-    hasher.write_u64(std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_nanos() as u64);
+    /// Save the idle gating settings and re-sample with them
+    pub async fn set_limits(&self, limits: LimitsConfig) -> Result<Availability, String> {
+        if !limits.busy_cpu_percent.is_finite() || !(0.0..=100.0).contains(&limits.busy_cpu_percent) {
+            return Err("CPU threshold must be between 0 and 100".to_string());
+        }
+        {
+            let mut config = self.config.write().await;
+            // Resource limits have their own setter
+            config.limits = LimitsConfig { resources: config.limits.resources.clone(), ..limits };
+            config.save()?;
+        }
+        Ok(self.idle.sample().await)
+    }
 
-    let chars: Vec<char> = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789".chars().collect();
-    let hash = hasher.finish();
-    let mut key = String::new();
+    /// Save the share of the machine the node may use and apply it to
+    /// everything it runs
+    pub async fn set_resource_limits(&self, limits: ResourceLimits) -> Result<ResourceLimits, String> {
+        resources::check(&limits)?;
+        {
+            let mut config = self.config.write().await;
+            config.limits.resources = limits.clone();
+            config.save()?;
+        }
+        self.ollama.set_resource_limits(limits.clone());
+        self.ipfs.set_resource_limits(limits.clone());
+        self.containers.set_resource_limits(limits.clone()).await;
+        log::info!("Resource limits updated");
+        Ok(limits)
+    }
+
+    /// This node and, with `include_remote`, every registered remote node,
+    /// as `/api/v1/my-nodes` lists them
+    pub async fn fleet_overview(&self, include_remote: bool) -> Result<Vec<serde_json::Value>, NodeError> {
+        let hardware = HardwareDetector::detect();
+        let running = *self.node_running.read().await;
+        let local = serde_json::json!({
+            "id": self.node_id.read().await.clone(),
+            "shareKey": self.share_key.read().await.clone(),
+            "name": "Local Node",
+            "remote": false,
+            "status": if running { "online" } else { "offline" },
+            "hardware": {
+                "cpuCores": hardware.cpu.cores,
+                "memoryMb": hardware.memory.total / (1024 * 1024),
+                "gpuCount": hardware.gpu.len(),
+            },
+            "uptimeSecs": self.uptime_secs().await,
+            "addedAt": self.started_at.read().await.unwrap_or_else(chrono::Utc::now).to_rfc3339(),
+        });
+        let mut nodes = vec![local];
+        if include_remote {
+            nodes.extend(self.fleet.summaries().await?);
+        }
+        Ok(nodes)
+    }
+
+    /// Peers on the LAN, each marked with whether it is already paired
+    pub async fn discovery_peers(&self) -> Result<Vec<serde_json::Value>, NodeError> {
+        let paired: Vec<String> = self.fleet.list()?.into_iter().map(|node| node.id).collect();
+        Ok(self
+            .discovery
+            .peers()
+            .await
+            .into_iter()
+            .map(|peer| {
+                let mut value = serde_json::json!(peer);
+                value["paired"] = paired.contains(&peer.node_id).into();
+                value
+            })
+            .collect())
+    }
+
+    /// What the node offers, including the key it signs with
+    pub async fn capabilities(&self) -> NodeCapabilities {
+        let hardware = HardwareDetector::detect();
+        let models = self
+            .ollama
+            .list_models()
+            .await
+            .map(|models| models.into_iter().map(|m| m.name).collect())
+            .unwrap_or_default();
+
+        NodeCapabilities {
+            node_id: self.node_id.read().await.clone(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            public_key: self.identity.public_key(),
+            cpu_cores: hardware.cpu.cores,
+            memory_mb: hardware.memory.total / (1024 * 1024),
+            gpus: hardware.gpu,
+            models,
+            container_runtime: self.containers.is_available().await,
+            available: self.idle.is_available().await,
+        }
+    }
 
-    for i in 0..8 {
-        let idx = ((hash >> (i * 5)) & 0x1F) as usize % chars.len();
-        key.push(chars[idx]);
+    /// Seconds since the node was started, 0 when stopped
+    pub async fn uptime_secs(&self) -> u64 {
+        self.started_at
+            .read()
+            .await
+            .map(|t| (chrono::Utc::now() - t).num_seconds().max(0) as u64)
+            .unwrap_or(0)
     }
+}
+
+fn generate_or_load_node_id(settings: &Settings) -> String {
+    match settings.get(state_store::NODE_ID) {
+        Ok(Some(id)) if !id.trim().is_empty() => return id.trim().to_string(),
+        Ok(_) => {}
+        Err(e) => log::warn!("{}", e),
+    }
+
+    let node_id = uuid::Uuid::new_v4().to_string();
+    if let Err(e) = settings.set(state_store::NODE_ID, &node_id) {
+        log::warn!("{}", e);
+    }
+    node_id
+}
 
-    // Save it
-    let _ = std::fs::create_dir_all(&config_dir);
-    let _ = std::fs::write(&share_key_file, &key);
+fn load_or_generate_share_key(settings: &Settings) -> String {
+    match settings.get(state_store::SHARE_KEY) {
+        Ok(Some(key)) if !key.trim().is_empty() => {
+            let key = key.trim().to_string();
+            redact::register(&key);
+            return key;
+        }
+        Ok(_) => {}
+        Err(e) => log::warn!("{}", e),
+    }
 
+    let key = new_share_key();
+    if let Err(e) = save_share_key(settings, &key) {
+        log::warn!("{}", e);
+    }
     key
 }
 
+/// Generate a share key (8 char alphanumeric, easy to type)
+fn new_share_key() -> String {
+    let chars: Vec<char> = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789".chars().collect();
+    let random = uuid::Uuid::new_v4().as_u128();
+
+    (0..8)
+        .map(|i| chars[((random >> (i * 5)) & 0x1F) as usize % chars.len()])
+        .collect()
+}
+
+fn save_share_key(settings: &Settings, key: &str) -> Result<(), String> {
+    redact::register(key);
+    settings.set(state_store::SHARE_KEY, key)
+}
+
 // ============ Response Types ============
 
 #[derive(Serialize)]
@@ -150,15 +693,118 @@ pub struct AddContentRequest {
     pub content: String,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddPathRequest {
+    pub path: String,
+    #[serde(default = "default_true")]
+    pub recursive: bool,
+    #[serde(default)]
+    pub wrap_with_directory: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatQuery {
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetContentRequest {
+    pub cid: String,
+    /// Directory the content is saved into, under its CID
+    pub dest: String,
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinQuery {
+    /// `recursive` (default), `direct`, `indirect` or `all`
+    #[serde(rename = "type")]
+    pub pin_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct IpnsPublishRequest {
+    pub cid: String,
+    /// Key to publish under; the node's own key when omitted
+    pub key: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct KeyGenRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterFollowRequest {
+    pub enabled: bool,
+    pub config_cid: Option<String>,
+    pub name: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
 // ============ Routes ============
 
 pub fn create_router(state: Arc<AppState>) -> Router {
+    let gpu_local_only = axum::middleware::from_fn_with_state("GPU rentals and credentials", local_only);
+    let provider_local_only = axum::middleware::from_fn_with_state("LLM provider changes", local_only);
+    let registry_local_only = axum::middleware::from_fn_with_state("Registry credential changes", local_only);
+    // Adds whatever path it is given, so only the owner may pick one
+    let add_path_local_only = axum::middleware::from_fn_with_state("Imports from local paths", local_only);
+
     Router::new()
         // Health
         .route("/health", get(health))
         // Node
         .route("/api/v1/node/status", get(node_status))
-        .route("/api/v1/my-nodes", get(my_nodes))
+        .route("/api/v1/node/capabilities", get(node_capabilities))
+        .route("/api/v1/node/availability", get(node_availability))
+        .route("/api/v1/node/limits", get(node_limits).put(node_set_limits))
+        .route("/api/v1/node/resource-limits", get(node_resource_limits).put(node_set_resource_limits))
+        .route("/api/v1/stats", get(node_stats))
+        .route("/metrics", get(prometheus_metrics))
+        .route("/api/v1/notifications", get(notification_settings).put(notification_set_settings))
+        .route("/api/v1/pricing", get(pricing_status))
+        .route("/api/v1/pricing/config", get(pricing_config).put(pricing_set_config))
+        .route("/api/v1/pricing/demand", post(pricing_demand))
+        .route("/api/v1/bandwidth", get(bandwidth_usage))
+        .route("/api/v1/bandwidth/config", get(bandwidth_config).put(bandwidth_set_config))
+        .route("/api/v1/jobs", get(list_jobs))
+        .route("/api/v1/earnings", get(list_earnings).post(record_earning))
+        .route("/api/v1/earnings/summary", get(earnings_summary))
+        .route("/api/v1/earnings/export", get(export_earnings))
+        .route("/api/v1/payments", get(list_payments))
+        .route("/api/v1/payments/reconciliation", get(payments_reconciliation))
+        .route("/api/v1/payments/sync", post(payments_sync))
+        .route(
+            "/api/v1/payments/config",
+            get(payments_config).put(
+                payments_set_config.layer(axum::middleware::from_fn_with_state("Payment settings changes", local_only)),
+            ),
+        )
+        .route("/api/v1/node/share-key/rotate", post(rotate_share_key))
+        .route("/api/v1/node/events", get(node_events))
+        .route("/ws/events", get(events_ws))
+        .route("/api/v1/relay/status", get(relay_status))
+        .route("/api/v1/discovery/peers", get(discovery_peers))
+        .route("/api/v1/mesh/dispatch", post(mesh_dispatch))
+        .route("/api/v1/mesh/jobs", get(mesh_jobs))
+        .route("/api/v1/mesh/jobs/:id", get(mesh_job).delete(mesh_cancel))
+        .route("/api/v1/mesh/jobs/:id/stream", get(mesh_stream))
+        .route("/api/v1/mesh/usage", get(mesh_usage))
+        .route("/api/v1/mesh/peer/jobs", post(mesh_peer_submit))
+        .route("/api/v1/mesh/peer/jobs/:id", get(mesh_peer_job).delete(mesh_peer_cancel))
+        .route("/api/v1/mesh/peer/jobs/:id/stream", get(mesh_peer_stream))
+        .route("/api/v1/my-nodes", get(my_nodes).post(add_remote_node))
+        .route("/api/v1/my-nodes/:node_id", delete(remove_remote_node))
+        .route("/api/v1/my-nodes/:node_id/:query", get(query_remote_node))
         // Hardware
         .route("/api/v1/hardware", get(get_hardware))
         .route("/api/v1/drives", get(get_drives))
@@ -168,63 +814,504 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/api/v1/ollama/stop", post(ollama_stop))
         .route("/api/v1/ollama/models", get(ollama_models))
         .route("/api/v1/ollama/pull", post(ollama_pull))
+        .route("/api/v1/ollama/pull/stream", get(ollama_pull_stream))
         .route("/api/v1/ollama/models/:name", delete(ollama_delete_model))
+        .route("/api/v1/ollama/generate", post(ollama_generate))
+        .route("/api/v1/ollama/chat", post(ollama_chat))
+        .route("/api/v1/ollama/usage", get(ollama_usage))
+        // OpenAI-compatible inference (fronts the local Ollama)
+        .route("/v1/models", get(openai_models))
+        .route("/v1/chat/completions", post(openai_chat_completions))
         // IPFS
         .route("/api/v1/ipfs/status", get(ipfs_status))
         .route("/api/v1/ipfs/start", post(ipfs_start))
         .route("/api/v1/ipfs/stop", post(ipfs_stop))
         .route("/api/v1/ipfs/add", post(ipfs_add))
+        .route("/api/v1/ipfs/add-path", post(ipfs_add_path.layer(add_path_local_only.clone())))
+        .route("/api/v1/ipfs/add-path/stream", get(ipfs_add_path_stream.layer(add_path_local_only.clone())))
+        .route("/api/v1/ipfs/cat/:cid", get(ipfs_cat))
+        // Writes wherever the caller asks
+        .route(
+            "/api/v1/ipfs/get",
+            post(ipfs_get.layer(axum::middleware::from_fn_with_state("Downloads to local paths", local_only))),
+        )
+        .route("/api/v1/ipfs/pins", get(ipfs_pins))
+        .route("/api/v1/ipfs/gc", post(ipfs_gc))
+        .route("/api/v1/ipfs/ipns/publish", post(ipns_publish))
+        .route("/api/v1/ipfs/ipns/resolve/:name", get(ipns_resolve))
+        .route("/api/v1/ipfs/keys", get(ipfs_keys))
+        .route("/api/v1/ipfs/keys", post(ipfs_key_gen))
         .route("/api/v1/ipfs/pin/:cid", post(ipfs_pin))
+        .route("/api/v1/ipfs/pin/:cid/stream", get(ipfs_pin_stream))
         .route("/api/v1/ipfs/pin/:cid", delete(ipfs_unpin))
         .route("/api/v1/ipfs/download", post(ipfs_download_binary))
+        .route("/api/v1/ipfs/pubsub/nodes", get(ipfs_pubsub_nodes))
+        .route("/api/v1/ipfs/pubsub/offers", get(ipfs_pubsub_offers))
+        // Contributed storage
+        .route("/api/v1/storage", get(storage_status))
+        .route("/api/v1/storage/config", get(storage_config).put(storage_set_config))
+        .route("/api/v1/storage/pins", get(storage_pins).post(storage_pin))
+        .route("/api/v1/storage/pins/:cid", get(storage_get_pin).delete(storage_release))
+        .route("/api/v1/storage/challenges", post(storage_challenge))
+        .route("/api/v1/ipfs/cluster", get(ipfs_cluster_status))
+        .route("/api/v1/ipfs/cluster", post(ipfs_cluster_follow))
+        // Workspaces
+        .route("/api/v1/workspaces", get(list_workspaces))
+        .route("/api/v1/workspaces", post(create_workspace))
+        .route("/api/v1/workspaces/:workspace_id", get(get_workspace))
+        .route("/api/v1/workspaces/:workspace_id", put(update_workspace))
+        .route("/api/v1/workspaces/:workspace_id", delete(delete_workspace))
         // Agents
+        .route("/api/v1/agent-templates", get(agent_templates))
         .route("/api/v1/workspaces/:workspace_id/agents", get(list_agents))
         .route("/api/v1/workspaces/:workspace_id/agents", post(create_agent))
         .route("/api/v1/workspaces/:workspace_id/agents/:execution_id", get(get_agent))
         .route("/api/v1/workspaces/:workspace_id/agents/:execution_id", delete(cancel_agent))
+        .route("/api/v1/workspaces/:workspace_id/agents/:execution_id/stream", get(stream_agent))
+        .route("/api/v1/workspaces/:workspace_id/memory", get(list_agent_memory))
+        .route("/api/v1/workspaces/:workspace_id/memory", delete(clear_agent_memory))
+        // Document retrieval
+        .route("/api/v1/rag/documents", get(rag_documents).post(rag_ingest))
+        .route("/api/v1/rag/documents/:id", get(rag_get_document).delete(rag_remove_document))
+        .route("/api/v1/rag/query", post(rag_query))
+        // LLM providers
+        .route("/api/v1/providers", get(list_providers))
+        .route("/api/v1/providers", post(save_provider.layer(provider_local_only.clone())))
+        .route("/api/v1/providers/health", get(providers_health))
+        .route("/api/v1/providers/:id", delete(remove_provider.layer(provider_local_only.clone())))
         // Cloud GPU proxy (bypasses CORS)
+        .route("/api/v1/gpu/providers", get(gpu_providers))
+        .route("/api/v1/gpu/spend", get(gpu_spend))
+        // Spending and credentials use the owner's stored keys: this machine only
+        .route("/api/v1/gpu/budget", put(gpu_set_budget.layer(gpu_local_only.clone())))
+        .route("/api/v1/gpu/credentials", post(gpu_save_credentials.layer(gpu_local_only.clone())))
+        .route(
+            "/api/v1/gpu/credentials/:provider",
+            delete(gpu_remove_credentials.layer(gpu_local_only.clone())),
+        )
         .route("/api/v1/gpu/offers", get(gpu_offers))
         .route("/api/v1/gpu/instances", get(gpu_instances))
         .route("/api/v1/gpu/user", get(gpu_user))
-        .route("/api/v1/gpu/rent/:offer_id", post(gpu_rent))
-        .route("/api/v1/gpu/destroy/:instance_id", delete(gpu_destroy))
+        .route("/api/v1/gpu/rent/:offer_id", post(gpu_rent.layer(gpu_local_only.clone())))
+        .route(
+            "/api/v1/gpu/destroy/:instance_id",
+            delete(gpu_destroy.layer(gpu_local_only.clone())),
+        )
+        .route(
+            "/api/v1/gpu/compute",
+            get(gpu_compute_list).post(gpu_compute_provision.layer(gpu_local_only.clone())),
+        )
+        .route(
+            "/api/v1/gpu/compute/:id",
+            get(gpu_compute_get).delete(gpu_compute_detach.layer(gpu_local_only.clone())),
+        )
         // Containers
-        .route("/api/v1/containers/runtime", get(container_runtime_info))
+        .route("/api/v1/containers/runtime", get(container_runtime_info).put(container_set_runtime))
         .route("/api/v1/containers/runtime/detect", post(container_detect_runtime))
+        .route("/api/v1/containers/policy", get(container_image_policy).put(container_set_image_policy))
         .route("/api/v1/containers", get(container_list))
         .route("/api/v1/containers", post(container_create))
         .route("/api/v1/containers/images", get(container_list_images))
         .route("/api/v1/containers/images/pull", post(container_pull_image))
+        .route("/api/v1/containers/images/build", post(container_build_image))
+        .route("/api/v1/containers/images/push", post(container_push_image))
+        .route("/api/v1/containers/registries", get(container_list_registries))
+        .route("/api/v1/containers/registries", post(container_save_registry.layer(registry_local_only.clone())))
+        .route(
+            "/api/v1/containers/registries/:registry",
+            delete(container_remove_registry.layer(registry_local_only.clone())),
+        )
+        .route("/api/v1/containers/disk-usage", get(container_disk_usage))
+        .route("/api/v1/containers/prune", post(container_prune))
+        .route("/api/v1/containers/networks", get(container_list_networks))
+        .route("/api/v1/containers/networks", post(container_create_network))
+        .route("/api/v1/containers/networks/:name", delete(container_remove_network))
+        .route("/api/v1/containers/networks/:name/connect", post(container_connect_network))
+        .route("/api/v1/containers/networks/:name/disconnect", post(container_disconnect_network))
         .route("/api/v1/containers/:id", get(container_inspect))
         .route("/api/v1/containers/:id", delete(container_remove))
         .route("/api/v1/containers/:id/start", post(container_start))
         .route("/api/v1/containers/:id/stop", post(container_stop))
+        .route("/api/v1/containers/:id/restart", post(container_restart))
+        .route("/api/v1/containers/:id/rename", post(container_rename))
+        .route("/api/v1/containers/:id/update", post(container_update))
         .route("/api/v1/containers/:id/logs", get(container_logs))
+        .route("/api/v1/containers/:id/logs/stream", get(container_logs_stream))
         .route("/api/v1/containers/:id/exec", post(container_exec))
+        // App deployments
+        .route("/api/v1/apps", get(app_list))
+        .route("/api/v1/apps", post(app_deploy))
+        .route("/api/v1/apps/:name", get(app_status))
+        .route("/api/v1/apps/:name", delete(app_teardown))
+        // Audit log
+        .route("/api/v1/audit", get(audit_list))
+        .route("/api/v1/audit/export", get(audit_export))
+        .route("/api/v1/audit/verify", get(audit_verify))
+        // Backups
+        .route("/api/v1/backup", get(backup_status).post(create_backup))
+        .route(
+            "/api/v1/backup/restore",
+            post(restore_backup).layer(axum::extract::DefaultBodyLimit::max(backup::MAX_BACKUP_SIZE)),
+        )
+        .layer(axum::middleware::from_fn_with_state(Arc::clone(&state), audit_mutations))
+        .layer(axum::middleware::from_fn(redact_errors))
+        .layer(axum::middleware::from_fn(trace_requests))
         .with_state(state)
 }
 
+/// Run each request in a span named after its route, continuing the trace
+/// of the caller (the relay, or the orchestrator through it), and record
+/// how long it took
+async fn trace_requests(request: axum::extract::Request, next: axum::middleware::Next) -> axum::response::Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<axum::extract::MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let parent = telemetry::context_from_headers(request.headers());
+    let attributes = vec![
+        KeyValue::new("http.request.method", method.clone()),
+        KeyValue::new("http.route", route.clone()),
+    ];
+
+    let started = std::time::Instant::now();
+    telemetry::in_span_with_parent(format!("{} {}", method, route), SpanKind::Server, attributes, parent, async {
+        let response = next.run(request).await;
+        let status = response.status();
+        telemetry::set_attribute(KeyValue::new("http.response.status_code", i64::from(status.as_u16())));
+        if status.is_server_error() {
+            telemetry::set_error(status.canonical_reason().unwrap_or("Server error"));
+        }
+        telemetry::record_duration(
+            "http.server.request.duration",
+            started.elapsed(),
+            &[
+                KeyValue::new("http.request.method", method.clone()),
+                KeyValue::new("http.route", route.clone()),
+                KeyValue::new("http.response.status_code", i64::from(status.as_u16())),
+            ],
+        );
+        response
+    })
+    .await
+}
+
+/// Largest error body rewritten by `redact_errors`
+const MAX_REDACTED_ERROR: usize = 1024 * 1024;
+
+/// Mask secrets in error responses, which often carry upstream error text
+async fn redact_errors(request: axum::extract::Request, next: axum::middleware::Next) -> axum::response::Response {
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !(response.status().is_client_error() || response.status().is_server_error()) || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let text = match axum::body::to_bytes(body, MAX_REDACTED_ERROR).await {
+        Ok(bytes) => redact::redact(&String::from_utf8_lossy(&bytes)),
+        Err(_) => serde_json::json!({ "error": "Error response too large" }).to_string(),
+    };
+    parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+    axum::response::Response::from_parts(parts, axum::body::Body::from(text))
+}
+
+/// Set by the relay on requests it forwards, to the relay request's ID
+pub const RELAY_REQUEST_HEADER: &str = "x-otherthing-relay-request";
+
+/// Request extension the relay adds to jobs whose orchestrator signature
+/// it checked; unlike a header, no HTTP client can set it
+#[derive(Debug, Clone, Copy)]
+pub struct SignedJob;
+
+/// Record every state-changing request once it has been answered;
+/// requests forwarded by the relay are recorded as jobs
+async fn audit_mutations(
+    State(state): State<Arc<AppState>>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let method = request.method().clone();
+    if [axum::http::Method::GET, axum::http::Method::HEAD, axum::http::Method::OPTIONS].contains(&method) {
+        return next.run(request).await;
+    }
+    let path = request.uri().path().to_string();
+    let relay_request = request
+        .headers()
+        .get(RELAY_REQUEST_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    let response = next.run(request).await;
+    let kind = if relay_request.is_some() { AuditKind::Job } else { AuditKind::ApiMutation };
+    state.audit.log(
+        kind,
+        &format!("{} {}", method, path),
+        serde_json::json!({
+            "status": response.status().as_u16(),
+            "relayRequestId": relay_request,
+        }),
+    );
+    response
+}
+
+// ============ Audit Handlers ============
+
+async fn audit_list(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<AuditQuery>,
+    axum::extract::Query(page): axum::extract::Query<ListQuery>,
+) -> impl IntoResponse {
+    match state.audit.list(&query, &page) {
+        Ok(entries) => (StatusCode::OK, Json(entries.to_json("entries"))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// The whole log as JSON Lines, with the chain check in a header
+async fn audit_export(State(state): State<Arc<AppState>>) -> axum::response::Response {
+    let (body, verification) = match (state.audit.export_jsonl(), state.audit.verify()) {
+        (Ok(body), Ok(verification)) => (body, verification),
+        (Err(e), _) | (_, Err(e)) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e }))).into_response()
+        }
+    };
+    let filename = format!("audit-{}.jsonl", chrono::Utc::now().format("%Y-%m-%d"));
+    (
+        StatusCode::OK,
+        [
+            (axum::http::header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+            (
+                axum::http::HeaderName::from_static("x-audit-chain"),
+                if verification.valid { "valid" } else { "broken" }.to_string(),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+// ============ Backup Handlers ============
+
+/// Header carrying the passphrase of a backup being restored
+const BACKUP_PASSPHRASE_HEADER: &str = "x-backup-passphrase";
+
+#[derive(Deserialize)]
+struct CreateBackupRequest {
+    passphrase: String,
+}
+
+/// Refuse what the orchestrator may not do through the relay; the fleet,
+/// for one, reaches into the user's other nodes
+fn refuse_relayed(headers: &axum::http::HeaderMap, what: &str) -> Option<axum::response::Response> {
+    headers.contains_key(RELAY_REQUEST_HEADER).then(|| {
+        NodeError::Policy(format!("{} are not available through the relay", what)).into_response()
+    })
+}
+
+/// Whether a request comes from this machine: over loopback and not
+/// forwarded by the relay
+fn is_local(peer: Option<&ConnectInfo<SocketAddr>>, headers: &axum::http::HeaderMap) -> bool {
+    !headers.contains_key(RELAY_REQUEST_HEADER)
+        && peer.is_some_and(|ConnectInfo(addr)| addr.ip().to_canonical().is_loopback())
+}
+
+/// Refuse what only callers on this machine may do. The API listens on
+/// every interface without authentication, so whatever hands out the
+/// node's secrets, replaces its state or spends the owner's money stays
+/// with local callers.
+fn refuse_remote(
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: &axum::http::HeaderMap,
+    what: &str,
+) -> Option<axum::response::Response> {
+    (!is_local(peer.as_ref(), headers)).then(|| {
+        NodeError::Policy(format!("{} are only available from this machine", what)).into_response()
+    })
+}
+
+/// `refuse_remote` as a route layer, with what is refused as its state
+async fn local_only(
+    State(what): State<&'static str>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().cloned();
+    if let Some(response) = refuse_remote(peer, request.headers(), what) {
+        return response;
+    }
+    next.run(request).await
+}
+
+async fn backup_status() -> impl IntoResponse {
+    Json(serde_json::json!({ "restorePending": backup::restore_pending() }))
+}
+
+/// Encrypted archive of the node's identity, config and records
+async fn create_backup(
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<CreateBackupRequest>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_remote(peer, &headers, "Backups") {
+        return response;
+    }
+    let data = match tokio::task::spawn_blocking(move || backup::create(&req.passphrase)).await {
+        Ok(Ok(data)) => data,
+        Ok(Err(e)) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))).into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e.to_string() })))
+                .into_response()
+        }
+    };
+    let filename = format!("otherthing-node-{}.backup", chrono::Utc::now().format("%Y-%m-%d"));
+    (
+        StatusCode::OK,
+        [
+            (axum::http::header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        data,
+    )
+        .into_response()
+}
+
+/// Stage a backup (the request body) to replace this node's state at the
+/// next start
+async fn restore_backup(
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+    body: axum::body::Bytes,
+) -> axum::response::Response {
+    if let Some(response) = refuse_remote(peer, &headers, "Backups") {
+        return response;
+    }
+    let Some(passphrase) = headers
+        .get(BACKUP_PASSPHRASE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+    else {
+        let error = format!("The passphrase goes in the {} header", BACKUP_PASSPHRASE_HEADER);
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": error }))).into_response();
+    };
+    match tokio::task::spawn_blocking(move || backup::restore(&body, &passphrase)).await {
+        Ok(Ok(manifest)) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "success": true, "manifest": manifest, "restartRequired": true })),
+        )
+            .into_response(),
+        Ok(Err(e)) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "success": false, "error": e }))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "success": false, "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+async fn audit_verify(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.audit.verify() {
+        Ok(verification) => (StatusCode::OK, Json(serde_json::json!(verification))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e }))),
+    }
+}
+
 // ============ Health Handlers ============
 
-async fn health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+/// Wrong share keys a caller may offer per window before its checks are refused
+const KEY_CHECK_FAILURES: u32 = 10;
+const KEY_CHECK_WINDOW: Duration = Duration::from_secs(60);
+
+/// Counts wrong share keys offered per caller address, so the key can't be
+/// guessed through `/health`. Relayed callers share one count.
+#[derive(Default)]
+pub struct KeyCheckLimiter {
+    failures: std::sync::Mutex<HashMap<Option<std::net::IpAddr>, (std::time::Instant, u32)>>,
+}
+
+impl KeyCheckLimiter {
+    /// Whether the caller may check a key now
+    fn allows(&self, caller: Option<std::net::IpAddr>) -> bool {
+        let mut failures = self.failures.lock().unwrap();
+        failures.retain(|_, (since, _)| since.elapsed() < KEY_CHECK_WINDOW);
+        failures.get(&caller).map_or(true, |(_, count)| *count < KEY_CHECK_FAILURES)
+    }
+
+    fn record_failure(&self, caller: Option<std::net::IpAddr>) {
+        let mut failures = self.failures.lock().unwrap();
+        failures.entry(caller).or_insert_with(|| (std::time::Instant::now(), 0)).1 += 1;
+    }
+}
+
+/// Callers on this machine get the share key; anyone else may only check
+/// a key they already have, sent in the share key header, and only a few
+/// wrong ones a minute
+async fn health(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+) -> axum::response::Response {
     let share_key = state.share_key.read().await.clone();
     let node_id = state.node_id.read().await.clone();
+    let local = is_local(peer.as_ref(), &headers);
 
-    Json(serde_json::json!({
+    let mut health = serde_json::json!({
         "status": "ok",
         "version": "1.0.0",
         "mode": "local",
-        "shareKey": share_key,
         "nodeId": node_id,
-    }))
+        "publicKey": state.identity.public_key(),
+    });
+    if let Some(offered) = headers.get(SHARE_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+        // Relayed requests carry no peer address of their own
+        let caller = peer.filter(|_| !headers.contains_key(RELAY_REQUEST_HEADER)).map(|ConnectInfo(addr)| addr.ip());
+        if !local && !state.key_checks.allows(caller) {
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(serde_json::json!({ "success": false, "error": "Too many wrong share keys; try again later" })),
+            )
+                .into_response();
+        }
+        let valid = identity::secrets_match(offered, &share_key);
+        if !valid && !local {
+            state.key_checks.record_failure(caller);
+        }
+        health["shareKeyValid"] = serde_json::json!(valid);
+    }
+    if local {
+        health["shareKey"] = serde_json::json!(share_key);
+    }
+    Json(health).into_response()
 }
 
 // ============ Node Handlers ============
 
-async fn node_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn node_status(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+) -> impl IntoResponse {
     let running = *state.node_running.read().await;
     let node_id = state.node_id.read().await.clone();
-    let share_key = state.share_key.read().await.clone();
+    // The share key admits mesh jobs, so only callers on this machine see it
+    let share_key = if is_local(peer.as_ref(), &headers) {
+        Some(state.share_key.read().await.clone())
+    } else {
+        None
+    };
+    let uptime_secs = state.uptime_secs().await;
 
     // Get hardware for additional info
     let hardware = HardwareDetector::detect();
@@ -234,6 +1321,7 @@ async fn node_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
         "connected": running,
         "node_id": node_id,
         "share_key": share_key,
+        "uptime_secs": uptime_secs,
         "hardware": {
             "cpuCores": hardware.cpu.cores,
             "memoryMb": hardware.memory.total / (1024 * 1024),
@@ -242,657 +1330,2173 @@ async fn node_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     }))
 }
 
-async fn my_nodes(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let node_id = state.node_id.read().await.clone();
-    let share_key = state.share_key.read().await.clone();
-    let running = *state.node_running.read().await;
+async fn node_capabilities(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!(state.capabilities().await))
+}
 
-    // Get hardware info
-    let hardware = HardwareDetector::detect();
+async fn node_availability(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.idle.status().await)
+}
 
-    // Return this node's info
-    Json(serde_json::json!({
-        "nodes": [{
-            "id": node_id,
-            "shareKey": share_key,
-            "name": "Local Node",
-            "status": if running { "online" } else { "offline" },
-            "hardware": {
-                "cpuCores": hardware.cpu.cores,
-                "memoryMb": hardware.memory.total / (1024 * 1024),
-                "gpuCount": hardware.gpu.len(),
-            },
-            "addedAt": chrono::Utc::now().to_rfc3339(),
-        }]
-    }))
+async fn node_limits(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.config.read().await.limits.clone())
 }
 
-// ============ Hardware Handlers ============
+/// Save when the node takes jobs and re-check availability right away
+async fn node_set_limits(State(state): State<Arc<AppState>>, Json(limits): Json<LimitsConfig>) -> impl IntoResponse {
+    match state.set_limits(limits).await {
+        Ok(availability) => (StatusCode::OK, Json(serde_json::json!(availability))),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))),
+    }
+}
 
-async fn get_hardware() -> impl IntoResponse {
-    let hardware = HardwareDetector::detect();
-    Json(hardware)
+async fn node_resource_limits(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.config.read().await.limits.resources.clone())
 }
 
-async fn get_drives() -> impl IntoResponse {
-    let drives = HardwareDetector::get_drives();
-    Json(serde_json::json!({ "drives": drives }))
+async fn node_set_resource_limits(
+    State(state): State<Arc<AppState>>,
+    Json(limits): Json<ResourceLimits>,
+) -> impl IntoResponse {
+    match state.set_resource_limits(limits).await {
+        Ok(limits) => (StatusCode::OK, Json(serde_json::json!(limits))),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))),
+    }
 }
 
-// ============ Ollama Handlers ============
+/// Headline numbers for the dashboard
+async fn node_stats(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let earnings = match state.earnings.summary() {
+        Ok(summary) => summary,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e })),
+            )
+        }
+    };
+    let completed_jobs = state.earnings.job_count().unwrap_or(0);
+
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "activeJobs": state.agents.running_count().await,
+            "completedJobs": completed_jobs,
+            "uptimeSecs": state.uptime_secs().await,
+            "earnings": earnings,
+            "bandwidth": state.bandwidth.report(),
+        })),
+    )
+}
 
-async fn ollama_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let status = state.ollama.get_status().await;
-    Json(status)
+async fn notification_settings(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(notifications::load(&state.store.settings()))
 }
 
-async fn ollama_start(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.ollama.start().await {
-        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "success": false, "error": e })),
-        ),
+async fn notification_set_settings(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Json(toggles): Json<notifications::NotificationSettings>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "Notification settings") {
+        return response;
+    }
+    match notifications::save(&state.store.settings(), &toggles) {
+        Ok(()) => Json(toggles).into_response(),
+        Err(e) => NodeError::from(e).into_response(),
     }
 }
 
-async fn ollama_stop(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.ollama.stop().await {
-        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "success": false, "error": e })),
-        ),
-    }
+async fn pricing_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.pricing.status().await)
 }
 
-async fn ollama_models(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.ollama.list_models().await {
-        Ok(models) => (StatusCode::OK, Json(serde_json::json!({ "models": models }))),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": e })),
-        ),
+async fn pricing_config(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.config.read().await.pricing.clone())
+}
+
+async fn pricing_set_config(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Json(pricing): Json<PricingConfig>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "Pricing settings") {
+        return response;
+    }
+    match state.set_pricing_config(pricing).await {
+        Ok(status) => Json(status).into_response(),
+        Err(e) => e.into_response(),
     }
 }
 
-async fn ollama_pull(
+/// Demand signal from the orchestrator, through the relay
+async fn pricing_demand(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<PullModelRequest>,
+    Json(signal): Json<DemandSignal>,
+) -> axum::response::Response {
+    match state.pricing.set_demand(signal).await {
+        Ok(status) => Json(status).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Bandwidth counters in the Prometheus text format
+async fn prometheus_metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.bandwidth.prometheus(),
+    )
+}
+
+async fn bandwidth_usage(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.bandwidth.report())
+}
+
+async fn bandwidth_config(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.config.read().await.bandwidth.clone())
+}
+
+async fn bandwidth_set_config(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Json(caps): Json<BandwidthConfig>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "Bandwidth caps") {
+        return response;
+    }
+    match state.set_bandwidth_config(caps).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+// ============ Earnings Handlers ============
+
+async fn list_earnings(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<EarningsQuery>,
+    axum::extract::Query(page): axum::extract::Query<ListQuery>,
 ) -> impl IntoResponse {
-    // Pull without progress for now (could add WebSocket for progress)
-    match state.ollama.pull_model(&req.name, None).await {
-        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "success": false, "error": e })),
-        ),
+    match state.earnings.page(&query, &page) {
+        Ok(entries) => (StatusCode::OK, Json(entries.to_json("entries"))).into_response(),
+        Err(e) => e.into_response(),
     }
 }
 
-async fn ollama_delete_model(
+/// Jobs relayed from the orchestrator, newest first
+async fn list_jobs(
     State(state): State<Arc<AppState>>,
-    axum::extract::Path(name): axum::extract::Path<String>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
 ) -> impl IntoResponse {
-    match state.ollama.delete_model(&name).await {
-        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+    match state.store.jobs().list(&query) {
+        Ok(page) => (StatusCode::OK, Json(page.to_json("jobs"))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Record a job payment; reported by the orchestrator through the relay
+/// Entries are permanent and drive payment reconciliation, so only the
+/// orchestrator (through a signed job) or this machine may add them
+async fn record_earning(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    signed: Option<axum::Extension<SignedJob>>,
+    headers: axum::http::HeaderMap,
+    Json(entry): Json<NewEarning>,
+) -> axum::response::Response {
+    if signed.is_none() {
+        if let Some(response) = refuse_remote(peer, &headers, "Earnings entries") {
+            return response;
+        }
+    }
+    match state.earnings.record(entry) {
+        Ok(entry) => {
+            let _ = state.node_events.send(NodeEvent::EarningAdded {
+                job_id: entry.job_id.clone(),
+                amount_cents: entry.amount_cents,
+                currency: entry.currency.clone(),
+            });
+            (StatusCode::CREATED, Json(serde_json::json!(entry))).into_response()
+        }
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::BAD_REQUEST,
             Json(serde_json::json!({ "success": false, "error": e })),
-        ),
+        )
+            .into_response(),
     }
 }
 
-// ============ IPFS Handlers ============
+async fn earnings_summary(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.earnings.summary() {
+        Ok(summary) => (StatusCode::OK, Json(serde_json::json!(summary))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e }))),
+    }
+}
 
-async fn ipfs_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let status = state.ipfs.get_status().await;
-    Json(status)
+#[derive(Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+    /// Timestamp or `YYYY-MM-DD`
+    from: Option<String>,
+    /// Timestamp or `YYYY-MM-DD`, inclusive for a date
+    to: Option<String>,
 }
 
-async fn ipfs_start(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.ipfs.start().await {
-        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "success": false, "error": e })),
+/// Per-job rows for bookkeeping, downloaded as a file
+async fn export_earnings(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<ExportQuery>,
+) -> axum::response::Response {
+    // Without a reconciliation, rows are exported as unverified
+    let statuses = state.payments.job_statuses().await.unwrap_or_else(|e| {
+        log::warn!("Exporting earnings without payment status: {}", e);
+        HashMap::new()
+    });
+    let rows = match state.earnings.export(query.from.as_deref(), query.to.as_deref(), &statuses) {
+        Ok(rows) => rows,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))).into_response()
+        }
+    };
+
+    let (content_type, extension, body) = match query.format {
+        ExportFormat::Csv => ("text/csv; charset=utf-8", "csv", crate::services::earnings::to_csv(&rows)),
+        ExportFormat::Json => (
+            "application/json",
+            "json",
+            serde_json::to_string_pretty(&rows).unwrap_or_default(),
         ),
+    };
+    let filename = format!("earnings-{}.{}", chrono::Utc::now().format("%Y-%m-%d"), extension);
+    (
+        StatusCode::OK,
+        [
+            (axum::http::header::CONTENT_TYPE, content_type.to_string()),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+// ============ Payment Handlers ============
+
+async fn list_payments(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+) -> impl IntoResponse {
+    match state.payments.received().await {
+        Ok(payments) => (StatusCode::OK, Json(query.apply(payments).to_json("payments"))).into_response(),
+        Err(e) => NodeError::from(e).into_response(),
     }
 }
 
-async fn ipfs_stop(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.ipfs.stop().await {
-        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "success": false, "error": e })),
-        ),
+async fn payments_reconciliation(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.payments.reconcile().await {
+        Ok(report) => (StatusCode::OK, Json(serde_json::json!(report))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e }))),
     }
 }
 
-async fn ipfs_add(
-    State(state): State<Arc<AppState>>,
-    Json(req): Json<AddContentRequest>,
-) -> impl IntoResponse {
-    match state.ipfs.add_content(&req.content).await {
-        Ok(cid) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "cid": cid }))),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
+/// Scan the chain now instead of waiting for the next poll
+async fn payments_sync(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    if let Err(e) = state.payments.sync().await {
+        return (
+            StatusCode::BAD_GATEWAY,
             Json(serde_json::json!({ "success": false, "error": e })),
-        ),
+        );
+    }
+    match state.payments.reconcile().await {
+        Ok(report) => (StatusCode::OK, Json(serde_json::json!(report))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e }))),
     }
 }
 
-async fn ipfs_pin(
+async fn payments_config(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.config.read().await.payments.clone())
+}
+
+async fn payments_set_config(
     State(state): State<Arc<AppState>>,
-    axum::extract::Path(cid): axum::extract::Path<String>,
+    Json(payments): Json<PaymentsConfig>,
 ) -> impl IntoResponse {
-    match state.ipfs.pin(&cid).await {
-        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+    match state.set_payments_config(payments).await {
+        Ok(payments) => (StatusCode::OK, Json(serde_json::json!(payments))),
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::BAD_REQUEST,
             Json(serde_json::json!({ "success": false, "error": e })),
         ),
     }
 }
 
-async fn ipfs_unpin(
+async fn rotate_share_key(
     State(state): State<Arc<AppState>>,
-    axum::extract::Path(cid): axum::extract::Path<String>,
-) -> impl IntoResponse {
-    match state.ipfs.unpin(&cid).await {
-        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+) -> axum::response::Response {
+    if let Some(response) = refuse_remote(peer, &headers, "Share key changes") {
+        return response;
+    }
+    match state.rotate_share_key().await {
+        Ok(key) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "shareKey": key })))
+            .into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({ "success": false, "error": e })),
-        ),
+        )
+            .into_response(),
     }
 }
 
-async fn ipfs_download_binary(State(_state): State<Arc<AppState>>) -> impl IntoResponse {
-    // Download Kubo (IPFS) binary
-    match download_ipfs_binary().await {
-        Ok(path) => {
-            log::info!("IPFS binary downloaded to: {:?}", path);
-            (StatusCode::OK, Json(serde_json::json!({ "success": true, "path": path.to_string_lossy() })))
-        }
-        Err(e) => {
-            log::error!("Failed to download IPFS: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "success": false, "error": e })))
+/// Stream node events (e.g. share key rotations) over SSE
+async fn node_events(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let rx = state.node_events.subscribe();
+    let local = is_local(peer.as_ref(), &headers);
+
+    let events = stream::unfold(rx, move |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let sse = Event::default().event("node").json_data(event_for(event, local));
+                    return Some((sse, rx));
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
         }
-    }
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
 }
 
-async fn download_ipfs_binary() -> Result<std::path::PathBuf, String> {
-    let config_dir = dirs::config_dir()
-        .ok_or("Could not find config directory")?
-        .join("otherthing-node")
-        .join("ipfs");
+#[derive(Deserialize)]
+struct EventStreamQuery {
+    /// Comma-separated event types, e.g. `job_started,job_finished`; all when absent
+    types: Option<String>,
+}
 
-    std::fs::create_dir_all(&config_dir)
-        .map_err(|e| format!("Failed to create directory: {}", e))?;
+/// Stream node events over a WebSocket, one JSON object per message
+async fn events_ws(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Query(query): axum::extract::Query<EventStreamQuery>,
+    ws: axum::extract::ws::WebSocketUpgrade,
+) -> axum::response::Response {
+    let local = is_local(peer.as_ref(), &headers);
+    let types: Option<Vec<String>> = query.types.map(|types| {
+        types
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect()
+    });
+    let rx = state.node_events.subscribe();
+    ws.on_upgrade(move |socket| forward_events(socket, rx, types, local))
+}
 
-    // Determine platform and architecture
-    let version = "v0.32.1";
+/// An event as a subscriber may see it: callers elsewhere learn that the
+/// share key was rotated, not the new key
+fn event_for(event: NodeEvent, local: bool) -> NodeEvent {
+    match event {
+        NodeEvent::ShareKeyRotated { rotated_at, .. } if !local => NodeEvent::ShareKeyRotated {
+            share_key: String::new(),
+            rotated_at,
+        },
+        event => event,
+    }
+}
 
-    #[cfg(target_os = "windows")]
-    let (os, arch, archive_ext, bin_ext) = (
-        "windows",
-        if cfg!(target_arch = "x86_64") { "amd64" } else { "386" },
-        "zip",
-        ".exe"
-    );
+async fn forward_events(
+    mut socket: axum::extract::ws::WebSocket,
+    mut rx: broadcast::Receiver<NodeEvent>,
+    types: Option<Vec<String>>,
+    local: bool,
+) {
+    use axum::extract::ws::Message;
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Ok(event) => {
+                    let Ok(value) = serde_json::to_value(event_for(event, local)) else {
+                        continue;
+                    };
+                    let kind = value.get("type").and_then(|t| t.as_str()).unwrap_or_default();
+                    if types.as_ref().is_some_and(|types| !types.iter().any(|t| t == kind)) {
+                        continue;
+                    }
+                    if socket.send(Message::Text(value.to_string())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => {}
+            },
+        }
+    }
+}
 
-    #[cfg(target_os = "macos")]
-    let (os, arch, archive_ext, bin_ext) = (
-        "darwin",
-        if cfg!(target_arch = "aarch64") { "arm64" } else { "amd64" },
-        "tar.gz",
-        ""
-    );
+async fn relay_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.relay_status.read().await.clone())
+}
 
-    #[cfg(target_os = "linux")]
-    let (os, arch, archive_ext, bin_ext) = (
-        "linux",
-        if cfg!(target_arch = "x86_64") { "amd64" } else { "arm64" },
-        "tar.gz",
-        ""
-    );
+// ============ LAN Mesh Handlers ============
 
-    // Correct URL format: kubo_v0.32.1_windows-amd64.zip
-    let filename = format!("kubo_{}_{}-{}", version, os, arch);
-    let download_url = format!(
-        "https://dist.ipfs.tech/kubo/{}/{}.{}",
-        version, filename, archive_ext
-    );
+/// Send an agent execution or container job to an idle paired node
+async fn mesh_dispatch(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<DispatchRequest>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "LAN jobs") {
+        return response;
+    }
+    let local_id = state.node_id.read().await.clone();
+    match state.mesh.dispatch(request, &local_id).await {
+        Ok(job) => (StatusCode::CREATED, Json(serde_json::json!({ "job": job }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Jobs sent to and received from other nodes, newest first
+async fn mesh_jobs(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "LAN jobs") {
+        return response;
+    }
+    match state.mesh.list(&query) {
+        Ok(page) => Json(page.to_json("jobs")).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-    log::info!("Downloading IPFS from: {}", download_url);
+async fn mesh_job(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Path(id): Path<String>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "LAN jobs") {
+        return response;
+    }
+    match state.mesh.get(&id) {
+        Ok(job) => Json(serde_json::json!({ "job": job })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(300))
-        .build()
-        .map_err(|e| format!("Failed to create client: {}", e))?;
+async fn mesh_cancel(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Path(id): Path<String>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "LAN jobs") {
+        return response;
+    }
+    let local_id = state.node_id.read().await.clone();
+    match state.mesh.cancel(&id, &local_id).await {
+        Ok(()) => Json(serde_json::json!({ "success": true })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-    let response = client
-        .get(&download_url)
-        .send()
-        .await
-        .map_err(|e| format!("Download failed: {}", e))?;
+/// The output of a sent job, passed on from the node running it
+async fn mesh_stream(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Path(id): Path<String>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "LAN jobs") {
+        return response;
+    }
+    let local_id = state.node_id.read().await.clone();
+    match state.mesh.stream(&id, &local_id).await {
+        Ok(upstream) => (
+            [(axum::http::header::CONTENT_TYPE, "text/event-stream"), (axum::http::header::CACHE_CONTROL, "no-cache")],
+            axum::body::Body::from_stream(upstream.bytes_stream()),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-    if !response.status().is_success() {
-        return Err(format!("Download failed with status: {}", response.status()));
+/// Jobs, tokens and run time exchanged with each peer
+async fn mesh_usage(State(state): State<Arc<AppState>>, headers: axum::http::HeaderMap) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "LAN jobs") {
+        return response;
+    }
+    match state.mesh.usage() {
+        Ok(usage) => Json(serde_json::json!({ "usage": usage })).into_response(),
+        Err(e) => e.into_response(),
     }
+}
 
-    let bytes = response.bytes().await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
+/// The node ID of a peer calling with this node's share key
+async fn mesh_peer(state: &AppState, headers: &axum::http::HeaderMap) -> Result<String, NodeError> {
+    if headers.contains_key(RELAY_REQUEST_HEADER) {
+        return Err(NodeError::Policy("LAN jobs are not available through the relay".to_string()));
+    }
+    let share_key = headers.get(SHARE_KEY_HEADER).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    if share_key.is_empty() || !identity::secrets_match(share_key, &state.share_key.read().await) {
+        return Err(NodeError::Policy("Invalid share key".to_string()));
+    }
+    match headers.get(NODE_ID_HEADER).and_then(|v| v.to_str().ok()).unwrap_or_default().trim() {
+        "" => Err(NodeError::Invalid(format!("The {} header is required", NODE_ID_HEADER))),
+        node_id => Ok(node_id.to_string()),
+    }
+}
 
-    log::info!("Downloaded {} bytes", bytes.len());
+/// Take a job from a paired node
+async fn mesh_peer_submit(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Json(work): Json<MeshWork>,
+) -> axum::response::Response {
+    let peer_id = match mesh_peer(&state, &headers).await {
+        Ok(peer_id) => peer_id,
+        Err(e) => return e.into_response(),
+    };
+    // Draining: peers get the same answer as the orchestrator
+    if !state.idle.is_available().await {
+        return NodeError::NotRunning("Node is busy; the contributor is using the machine".to_string()).into_response();
+    }
+    match state.mesh.accept(work, &peer_id).await {
+        Ok(job) => (StatusCode::CREATED, Json(serde_json::json!({ "job": job }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-    let archive_path = config_dir.join(format!("{}.{}", filename, archive_ext));
-    std::fs::write(&archive_path, &bytes)
-        .map_err(|e| format!("Failed to write archive: {}", e))?;
+async fn mesh_peer_job(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Path(id): Path<String>,
+) -> axum::response::Response {
+    match mesh_peer(&state, &headers).await.and_then(|peer_id| state.mesh.received(&id, &peer_id)) {
+        Ok(job) => Json(serde_json::json!({ "job": job })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-    // Extract based on archive type
-    #[cfg(target_os = "windows")]
-    {
-        // Use zip extraction for Windows
-        let file = std::fs::File::open(&archive_path)
-            .map_err(|e| format!("Failed to open archive: {}", e))?;
-        let mut archive = zip::ZipArchive::new(file)
-            .map_err(|e| format!("Failed to read zip: {}", e))?;
+async fn mesh_peer_cancel(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Path(id): Path<String>,
+) -> axum::response::Response {
+    let job = match mesh_peer(&state, &headers).await.and_then(|peer_id| state.mesh.received(&id, &peer_id)) {
+        Ok(job) => job,
+        Err(e) => return e.into_response(),
+    };
+    match state.mesh.stop_received(&job).await {
+        Ok(()) => Json(serde_json::json!({ "success": true })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)
-                .map_err(|e| format!("Failed to read zip entry: {}", e))?;
+/// Stream a received job's output: the agent stream for agents, the
+/// container's log lines for containers, ending with a `done` event
+async fn mesh_peer_stream(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Path(id): Path<String>,
+) -> axum::response::Response {
+    let job = match mesh_peer(&state, &headers).await.and_then(|peer_id| state.mesh.received(&id, &peer_id)) {
+        Ok(job) => job,
+        Err(e) => return e.into_response(),
+    };
+    if job.kind == "agent" {
+        return stream_agent(State(state), Path((MESH_WORKSPACE.to_string(), id))).await;
+    }
+    let Some(container_id) = state.mesh.container_of(&id) else {
+        let done = stream::once(async move { Event::default().event("done").json_data(serde_json::json!({ "job": job })) });
+        return Sse::new(done).into_response();
+    };
+    container_logs_stream(
+        State(state),
+        Path(container_id),
+        axum::extract::Query(ContainerLogsStreamQuery { tail: None }),
+    )
+    .await
+    .into_response()
+}
 
-            let outpath = match file.enclosed_name() {
-                Some(path) => config_dir.join(path),
-                None => continue,
-            };
+/// Nodes found on the local network; `paired` ones are registered remote nodes
+async fn discovery_peers(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "LAN peers") {
+        return response;
+    }
+    match state.discovery_peers().await {
+        Ok(peers) => {
+            let mut body = query.apply(peers).to_json("peers");
+            body["enabled"] = state.discovery.is_running().into();
+            Json(body).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
 
-            if file.name().ends_with('/') {
-                std::fs::create_dir_all(&outpath).ok();
-            } else {
-                if let Some(p) = outpath.parent() {
-                    std::fs::create_dir_all(p).ok();
+/// This node and the user's remote nodes, with their current status;
+/// through the relay only this node
+async fn my_nodes(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+) -> axum::response::Response {
+    let include_remote = !headers.contains_key(RELAY_REQUEST_HEADER);
+    let local = is_local(peer.as_ref(), &headers);
+    match state.fleet_overview(include_remote).await {
+        Ok(mut nodes) => {
+            if !local {
+                for node in &mut nodes {
+                    if let Some(node) = node.as_object_mut() {
+                        node.remove("shareKey");
+                    }
                 }
-                let mut outfile = std::fs::File::create(&outpath)
-                    .map_err(|e| format!("Failed to create file: {}", e))?;
-                std::io::copy(&mut file, &mut outfile)
-                    .map_err(|e| format!("Failed to extract file: {}", e))?;
             }
+            Json(query.apply(nodes).to_json("nodes")).into_response()
         }
+        Err(e) => e.into_response(),
     }
+}
 
-    #[cfg(not(target_os = "windows"))]
-    {
-        // Use tar.gz extraction for Unix
-        let tar_gz = std::fs::File::open(&archive_path)
-            .map_err(|e| format!("Failed to open archive: {}", e))?;
-        let tar = flate2::read::GzDecoder::new(tar_gz);
-        let mut archive = tar::Archive::new(tar);
-        archive.unpack(&config_dir)
-            .map_err(|e| format!("Failed to extract archive: {}", e))?;
+/// Register a remote node by address and share key
+async fn add_remote_node(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<AddRemoteNodeRequest>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "Remote nodes") {
+        return response;
+    }
+    let local_id = state.node_id.read().await.clone();
+    match state.fleet.add(req, &local_id).await {
+        Ok(node) => (StatusCode::CREATED, Json(serde_json::json!({ "success": true, "node": node }))).into_response(),
+        Err(e) => e.into_response(),
     }
+}
 
-    // The binary is in kubo/ipfs
-    let binary_path = config_dir.join("kubo").join(format!("ipfs{}", bin_ext));
+async fn remove_remote_node(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Path(node_id): Path<String>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "Remote nodes") {
+        return response;
+    }
+    match state.fleet.remove(&node_id) {
+        Ok(()) => Json(serde_json::json!({ "success": true })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-    if !binary_path.exists() {
-        return Err(format!("IPFS binary not found at {:?} after extraction", binary_path));
+/// Status, hardware or Ollama state of a remote node, fetched from it
+async fn query_remote_node(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Path((node_id, query)): Path<(String, String)>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "Remote nodes") {
+        return response;
     }
+    let query = match query.as_str() {
+        "status" => RemoteQuery::Status,
+        "hardware" => RemoteQuery::Hardware,
+        "ollama-status" => RemoteQuery::OllamaStatus,
+        "ollama-models" => RemoteQuery::OllamaModels,
+        other => return NodeError::NotFound(format!("Unknown remote query {}", other)).into_response(),
+    };
+    match state.fleet.query(&node_id, query).await {
+        Ok(answer) => Json(answer).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-    log::info!("IPFS binary extracted to: {:?}", binary_path);
+// ============ Hardware Handlers ============
 
-    // Make executable on Unix
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        std::fs::set_permissions(&binary_path, std::fs::Permissions::from_mode(0o755))
-            .map_err(|e| format!("Failed to set permissions: {}", e))?;
-    }
+async fn get_hardware() -> impl IntoResponse {
+    let hardware = HardwareDetector::detect();
+    Json(hardware)
+}
+
+async fn get_drives() -> impl IntoResponse {
+    let drives = HardwareDetector::get_drives();
+    Json(serde_json::json!({ "drives": drives }))
+}
 
-    // Clean up archive
-    let _ = std::fs::remove_file(&archive_path);
+// ============ Ollama Handlers ============
 
-    Ok(binary_path)
+async fn ollama_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let status = state.ollama.get_status().await;
+    Json(status)
 }
 
-// ============ Agent Handlers ============
+async fn ollama_start(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.ollama.start().await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-async fn list_agents(
+async fn ollama_stop(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.ollama.stop().await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ollama_models(
     State(state): State<Arc<AppState>>,
-    Path(workspace_id): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
 ) -> impl IntoResponse {
-    let executions = state.agents.list_executions(&workspace_id).await;
-    Json(serde_json::json!({ "executions": executions }))
+    match state.ollama.list_models().await {
+        Ok(models) => (StatusCode::OK, Json(query.apply(models).to_json("models"))).into_response(),
+        Err(e) => e.into_response(),
+    }
 }
 
-async fn get_agent(
+/// Pull a model, answering once it is done; `ollama_pull_stream` reports progress
+async fn ollama_pull(
     State(state): State<Arc<AppState>>,
-    Path((_workspace_id, execution_id)): Path<(String, String)>,
+    Json(req): Json<PullModelRequest>,
 ) -> impl IntoResponse {
-    match state.agents.get_execution(&execution_id).await {
-        Some(exec) => (StatusCode::OK, Json(serde_json::json!({ "execution": exec }))),
-        None => (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Execution not found" })),
-        ),
+    match state.ollama.pull_model(&req.name, None).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PullStreamQuery {
+    pub name: String,
+}
+
+/// Pull a model, streaming `progress` events and a final `done` event over SSE
+async fn ollama_pull_stream(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(params): axum::extract::Query<PullStreamQuery>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let (tx, rx) = mpsc::channel::<PullProgress>(32);
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+
+    let ollama = Arc::clone(&state.ollama);
+    tokio::spawn(async move {
+        let result = ollama.pull_model(&params.name, Some(tx)).await;
+        let _ = done_tx.send(result);
+    });
+
+    let progress = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|p| (Event::default().event("progress").json_data(p), rx))
+    });
+
+    let done = stream::once(async move {
+        let payload = match done_rx.await {
+            Ok(Ok(())) => serde_json::json!({ "success": true }),
+            Ok(Err(e)) => serde_json::json!({ "success": false, "error": e.to_string(), "code": e.code() }),
+            Err(_) => serde_json::json!({ "success": false, "error": "Pull task aborted" }),
+        };
+        Event::default().event("done").json_data(payload)
+    });
+
+    Sse::new(progress.chain(done)).keep_alive(KeepAlive::default())
+}
+
+async fn ollama_delete_model(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    match state.ollama.delete_model(&name).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))).into_response(),
+        Err(e) => e.into_response(),
     }
 }
 
-async fn create_agent(
+// ============ IPFS Handlers ============
+
+async fn ipfs_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let status = state.ipfs.get_status().await;
+    Json(status)
+}
+
+async fn ipfs_start(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    if let Err(e) = state.bandwidth.check(Subsystem::Ipfs) {
+        return e.into_response();
+    }
+    match state.ipfs.start().await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipfs_stop(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.ipfs.stop().await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipfs_add(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<AddContentRequest>,
+) -> impl IntoResponse {
+    match state.ipfs.add_content(&req.content).await {
+        Ok(cid) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "cid": cid }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipfs_add_path(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<AddPathRequest>,
+) -> impl IntoResponse {
+    let path = std::path::PathBuf::from(&req.path);
+    match state.ipfs.add_path(&path, req.recursive, req.wrap_with_directory, None).await {
+        Ok(cid) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "cid": cid }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Add a path, streaming `progress` events and a final `done` with the CID
+async fn ipfs_add_path_stream(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(req): axum::extract::Query<AddPathRequest>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let (tx, rx) = mpsc::channel::<IpfsProgress>(32);
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+
+    let ipfs = Arc::clone(&state.ipfs);
+    tokio::spawn(async move {
+        let path = std::path::PathBuf::from(&req.path);
+        let result = ipfs.add_path(&path, req.recursive, req.wrap_with_directory, Some(tx)).await;
+        let _ = done_tx.send(result);
+    });
+
+    let progress = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|p| (Event::default().event("progress").json_data(p), rx))
+    });
+
+    let done = stream::once(async move {
+        let payload = match done_rx.await {
+            Ok(Ok(cid)) => serde_json::json!({ "success": true, "cid": cid }),
+            Ok(Err(e)) => serde_json::json!({ "success": false, "error": e.to_string(), "code": e.code() }),
+            Err(_) => serde_json::json!({ "success": false, "error": "Add task aborted" }),
+        };
+        Event::default().event("done").json_data(payload)
+    });
+
+    Sse::new(progress.chain(done)).keep_alive(KeepAlive::default())
+}
+
+/// Pin a CID, streaming `progress` events (blocks fetched) and a final `done`
+async fn ipfs_pin_stream(
+    State(state): State<Arc<AppState>>,
+    Path(cid): Path<String>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let (tx, rx) = mpsc::channel::<IpfsProgress>(32);
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+
+    let ipfs = Arc::clone(&state.ipfs);
+    tokio::spawn(async move {
+        let result = ipfs.pin_with_progress(&cid, Some(tx)).await;
+        let _ = done_tx.send(result);
+    });
+
+    let progress = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|p| (Event::default().event("progress").json_data(p), rx))
+    });
+
+    let done = stream::once(async move {
+        let payload = match done_rx.await {
+            Ok(Ok(())) => serde_json::json!({ "success": true }),
+            Ok(Err(e)) => serde_json::json!({ "success": false, "error": e.to_string(), "code": e.code() }),
+            Err(_) => serde_json::json!({ "success": false, "error": "Pin task aborted" }),
+        };
+        Event::default().event("done").json_data(payload)
+    });
+
+    Sse::new(progress.chain(done)).keep_alive(KeepAlive::default())
+}
+
+/// Stream content back, refusing anything over `maxBytes`
+async fn ipfs_cat(
+    State(state): State<Arc<AppState>>,
+    Path(cid): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<CatQuery>,
+) -> axum::response::Response {
+    let max_bytes = query.max_bytes.unwrap_or(MAX_CAT_SIZE);
+    match state.ipfs.cat_stream(&cid, max_bytes).await {
+        Ok((size, response)) => (
+            StatusCode::OK,
+            [
+                (axum::http::header::CONTENT_TYPE, "application/octet-stream".to_string()),
+                (axum::http::header::CONTENT_LENGTH, size.to_string()),
+            ],
+            axum::body::Body::from_stream(response.bytes_stream()),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipfs_get(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<GetContentRequest>,
+) -> impl IntoResponse {
+    let dest = std::path::PathBuf::from(&req.dest);
+    match state.ipfs.get(&req.cid, &dest, req.max_bytes.unwrap_or(MAX_GET_SIZE)).await {
+        Ok(path) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "success": true, "path": path })),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipfs_pins(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<PinQuery>,
+    axum::extract::Query(page): axum::extract::Query<ListQuery>,
+) -> impl IntoResponse {
+    match state.ipfs.list_pins(query.pin_type.as_deref(), &page).await {
+        Ok(pins) => (StatusCode::OK, Json(pins.to_json("pins"))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipfs_gc(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.ipfs.gc().await {
+        Ok(result) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "success": true, "result": result })),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipns_publish(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<IpnsPublishRequest>,
+) -> impl IntoResponse {
+    match state.ipfs.ipns_publish(&req.cid, req.key.as_deref()).await {
+        Ok(record) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "success": true, "name": record.name, "value": record.value })),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipns_resolve(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match state.ipfs.ipns_resolve(&name).await {
+        Ok(path) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "path": path }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipfs_keys(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+) -> impl IntoResponse {
+    match state.ipfs.key_list().await {
+        Ok(keys) => (StatusCode::OK, Json(query.apply(keys).to_json("keys"))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipfs_key_gen(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<KeyGenRequest>,
+) -> impl IntoResponse {
+    match state.ipfs.key_gen(&req.name).await {
+        Ok(key) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "key": key }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipfs_pin(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(cid): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    match state.ipfs.pin(&cid).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipfs_unpin(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(cid): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    match state.ipfs.unpin(&cid).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipfs_download_binary(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    // Download Kubo (IPFS) binary
+    match state.ipfs.download_binary(None).await {
+        Ok(path) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "path": path.to_string_lossy() }))).into_response(),
+        Err(e) => {
+            log::error!("Failed to download IPFS: {}", e);
+            NodeError::from(e).into_response()
+        }
+    }
+}
+
+/// Other nodes announcing themselves on IPFS pubsub
+async fn ipfs_pubsub_nodes(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!({ "nodes": state.pubsub.nodes().await }))
+}
+
+/// Jobs the orchestrator offered on IPFS pubsub that have not expired
+async fn ipfs_pubsub_offers(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!({ "offers": state.pubsub.offers().await }))
+}
+
+async fn storage_status(State(state): State<Arc<AppState>>) -> axum::response::Response {
+    match state.storage.status().await {
+        Ok(status) => Json(status).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn storage_config(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.config.read().await.storage.clone())
+}
+
+async fn storage_set_config(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Json(storage): Json<StorageConfig>,
+) -> axum::response::Response {
+    if let Some(response) = refuse_relayed(&headers, "Storage settings") {
+        return response;
+    }
+    match state.set_storage_config(storage).await {
+        Ok(storage) => Json(storage).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn storage_pins(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+) -> axum::response::Response {
+    match state.storage.list(&query) {
+        Ok(page) => Json(page.to_json("pins")).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Pin request from the orchestrator; answers once accepted, before the
+/// data has arrived
+async fn storage_pin(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<PinRequest>,
+) -> axum::response::Response {
+    match state.storage.request(req).await {
+        Ok(pin) => (StatusCode::ACCEPTED, Json(pin)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn storage_get_pin(State(state): State<Arc<AppState>>, Path(cid): Path<String>) -> axum::response::Response {
+    match state.storage.get(&cid) {
+        Ok(pin) => Json(pin).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn storage_release(State(state): State<Arc<AppState>>, Path(cid): Path<String>) -> axum::response::Response {
+    match state.storage.release(&cid).await {
+        Ok(()) => Json(serde_json::json!({ "success": true })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Prove possession of pinned data
+async fn storage_challenge(
+    State(state): State<Arc<AppState>>,
+    Json(challenge): Json<Challenge>,
+) -> axum::response::Response {
+    match state.storage.respond(challenge).await {
+        Ok(proof) => Json(proof).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn ipfs_cluster_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.cluster.get_status())
+}
+
+/// Start or stop following a cluster's pinset
+async fn ipfs_cluster_follow(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ClusterFollowRequest>,
+) -> impl IntoResponse {
+    match state.configure_cluster(req.enabled, req.config_cid, req.name).await {
+        Ok(status) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "cluster": status }))),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "success": false, "error": e })),
+        ),
+    }
+}
+
+// ============ OpenAI-Compatible Handlers ============
+
+/// Error body in the shape OpenAI clients expect
+fn openai_error(status: StatusCode, message: impl Into<String>) -> axum::response::Response {
+    (
+        status,
+        Json(serde_json::json!({
+            "error": { "message": message.into(), "type": "server_error" }
+        })),
+    )
+        .into_response()
+}
+
+async fn openai_models(State(state): State<Arc<AppState>>) -> axum::response::Response {
+    match state.ollama.list_models().await {
+        Ok(models) => {
+            let data: Vec<_> = models
+                .iter()
+                .map(|m| {
+                    let created = chrono::DateTime::parse_from_rfc3339(&m.modified_at)
+                        .map(|t| t.timestamp())
+                        .unwrap_or(0);
+                    serde_json::json!({
+                        "id": m.name,
+                        "object": "model",
+                        "created": created,
+                        "owned_by": "ollama",
+                    })
+                })
+                .collect();
+            Json(serde_json::json!({ "object": "list", "data": data })).into_response()
+        }
+        Err(e) => openai_error(e.status(), e),
+    }
+}
+
+/// Forward a chat completion to Ollama's OpenAI-compatible endpoint,
+/// passing streamed (SSE) responses straight through
+async fn openai_chat_completions(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<serde_json::Value>,
+) -> axum::response::Response {
+    if body["model"].as_str().map_or(true, str::is_empty) {
+        return openai_error(StatusCode::BAD_REQUEST, "'model' is required");
+    }
+
+    let url = format!("{}/v1/chat/completions", state.ollama.get_host());
+    let response = match reqwest::Client::new().post(&url).json(&body).send().await {
+        Ok(r) => r,
+        Err(e) => {
+            return openai_error(
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to connect to Ollama: {}", e),
+            );
+        }
+    };
+
+    let status = StatusCode::from_u16(response.status().as_u16())
+        .unwrap_or(StatusCode::BAD_GATEWAY);
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/json")
+        .to_string();
+
+    (
+        status,
+        [(axum::http::header::CONTENT_TYPE, content_type)],
+        axum::body::Body::from_stream(response.bytes_stream()),
+    )
+        .into_response()
+}
+
+/// Default deadline for a proxied generate/chat request
+const DEFAULT_INFERENCE_TIMEOUT: Duration = Duration::from_secs(300);
+
+async fn ollama_generate(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<serde_json::Value>,
+) -> axum::response::Response {
+    proxy_inference(state, "generate", body).await
+}
+
+async fn ollama_chat(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<serde_json::Value>,
+) -> axum::response::Response {
+    proxy_inference(state, "chat", body).await
+}
+
+async fn ollama_usage(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!({ "usage": *state.token_usage.read().await }))
+}
+
+/// Forward a request to Ollama's `/api/{endpoint}`. Streamed responses
+/// (Ollama's default) are re-sent as SSE, one event per NDJSON chunk. An
+/// optional `timeout` field (seconds) bounds the whole request.
+async fn proxy_inference(
+    state: Arc<AppState>,
+    endpoint: &'static str,
+    mut body: serde_json::Value,
+) -> axum::response::Response {
+    let model = match body["model"].as_str() {
+        Some(model) if !model.is_empty() => model.to_string(),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "'model' is required" })),
+            )
+                .into_response();
+        }
+    };
+    let timeout = body
+        .as_object_mut()
+        .and_then(|b| b.remove("timeout"))
+        .and_then(|t| t.as_u64())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_INFERENCE_TIMEOUT);
+    let streaming = body["stream"].as_bool().unwrap_or(true);
+    let deadline = tokio::time::Instant::now() + timeout;
+
+    let url = format!("{}/api/{}", state.ollama.get_host(), endpoint);
+    let send = reqwest::Client::new().post(&url).json(&body).send();
+    let response = match tokio::time::timeout_at(deadline, send).await {
+        Ok(Ok(r)) => r,
+        Ok(Err(e)) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({ "error": format!("Failed to connect to Ollama: {}", e) })),
+            )
+                .into_response();
+        }
+        Err(_) => {
+            return (
+                StatusCode::GATEWAY_TIMEOUT,
+                Json(serde_json::json!({ "error": "Request timed out" })),
+            )
+                .into_response();
+        }
+    };
+
+    if !response.status().is_success() {
+        let status = StatusCode::from_u16(response.status().as_u16())
+            .unwrap_or(StatusCode::BAD_GATEWAY);
+        let error = response.text().await.unwrap_or_default();
+        return (status, Json(serde_json::json!({ "error": error }))).into_response();
+    }
+
+    if !streaming {
+        return match tokio::time::timeout_at(deadline, response.json::<serde_json::Value>()).await {
+            Ok(Ok(data)) => {
+                record_token_usage(&state, &model, &data).await;
+                Json(data).into_response()
+            }
+            Ok(Err(e)) => (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({ "error": format!("Failed to parse response: {}", e) })),
+            )
+                .into_response(),
+            Err(_) => (
+                StatusCode::GATEWAY_TIMEOUT,
+                Json(serde_json::json!({ "error": "Request timed out" })),
+            )
+                .into_response(),
+        };
+    }
+
+    struct ProxyStream<S> {
+        chunks: S,
+        buffer: Vec<u8>,
+        finished: bool,
+    }
+
+    let init = ProxyStream {
+        chunks: response.bytes_stream(),
+        buffer: Vec::new(),
+        finished: false,
+    };
+    let events = stream::unfold(init, move |mut ps| {
+        let state = Arc::clone(&state);
+        let model = model.clone();
+        async move {
+            loop {
+                if let Some(pos) = ps.buffer.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = ps.buffer.drain(..=pos).collect();
+                    let line = String::from_utf8_lossy(&line).trim().to_string();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    if let Ok(chunk) = serde_json::from_str::<serde_json::Value>(&line) {
+                        if chunk["done"].as_bool() == Some(true) {
+                            record_token_usage(&state, &model, &chunk).await;
+                        }
+                    }
+                    return Some((Ok::<_, axum::Error>(Event::default().data(line)), ps));
+                }
+                if ps.finished {
+                    return None;
+                }
+
+                match tokio::time::timeout_at(deadline, ps.chunks.next()).await {
+                    Ok(Some(Ok(bytes))) => ps.buffer.extend_from_slice(&bytes),
+                    Ok(Some(Err(e))) => {
+                        ps.finished = true;
+                        ps.buffer.clear();
+                        let error = Event::default().event("error").data(e.to_string());
+                        return Some((Ok(error), ps));
+                    }
+                    Ok(None) => {
+                        // Flush a final line without a trailing newline
+                        ps.finished = true;
+                        ps.buffer.push(b'\n');
+                    }
+                    Err(_) => {
+                        ps.finished = true;
+                        ps.buffer.clear();
+                        let error = Event::default().event("error").data("Request timed out");
+                        return Some((Ok(error), ps));
+                    }
+                }
+            }
+        }
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+/// Add the token counts from a final Ollama response to the usage totals
+async fn record_token_usage(state: &AppState, model: &str, response: &serde_json::Value) {
+    let mut usage = state.token_usage.write().await;
+    let entry = usage.entry(model.to_string()).or_default();
+    entry.requests += 1;
+    entry.prompt_tokens += response["prompt_eval_count"].as_u64().unwrap_or(0);
+    entry.completion_tokens += response["eval_count"].as_u64().unwrap_or(0);
+}
+
+// ============ Workspace Handlers ============
+
+async fn list_workspaces(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+) -> impl IntoResponse {
+    Json(query.apply(state.workspaces.list().await).to_json("workspaces"))
+}
+
+async fn get_workspace(
+    State(state): State<Arc<AppState>>,
+    Path(workspace_id): Path<String>,
+) -> impl IntoResponse {
+    match state.workspaces.get(&workspace_id).await {
+        Some(workspace) => (StatusCode::OK, Json(serde_json::json!({ "workspace": workspace }))),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Workspace not found" })),
+        ),
+    }
+}
+
+async fn create_workspace(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateWorkspaceRequest>,
+) -> impl IntoResponse {
+    match state.workspaces.create(req).await {
+        Ok(workspace) => (StatusCode::OK, Json(serde_json::json!({ "workspace": workspace }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn update_workspace(
+    State(state): State<Arc<AppState>>,
+    Path(workspace_id): Path<String>,
+    Json(req): Json<UpdateWorkspaceRequest>,
+) -> impl IntoResponse {
+    match state.workspaces.update(&workspace_id, req).await {
+        Ok(workspace) => (StatusCode::OK, Json(serde_json::json!({ "workspace": workspace }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn delete_workspace(
+    State(state): State<Arc<AppState>>,
+    Path(workspace_id): Path<String>,
+) -> impl IntoResponse {
+    match state.workspaces.delete(&workspace_id).await {
+        Ok(()) => {
+            if let Err(e) = state.agents.clear_memory(&workspace_id) {
+                log::warn!("Failed to clear agent memory of workspace {}: {}", workspace_id, e);
+            }
+            (StatusCode::OK, Json(serde_json::json!({ "success": true }))).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+// ============ Agent Handlers ============
+
+/// Kinds of agent `agentType` can pick
+async fn agent_templates() -> impl IntoResponse {
+    Json(serde_json::json!({ "templates": agent_templates::TEMPLATES }))
+}
+
+async fn list_agents(
+    State(state): State<Arc<AppState>>,
+    Path(workspace_id): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<ExecutionQuery>,
+    axum::extract::Query(page): axum::extract::Query<ListQuery>,
+) -> impl IntoResponse {
+    match state.agents.list_executions(&workspace_id, &query, &page).await {
+        Ok(page) => (StatusCode::OK, Json(page.to_json("executions"))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn get_agent(
+    State(state): State<Arc<AppState>>,
+    Path((_workspace_id, execution_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match state.agents.get_execution(&execution_id).await {
+        Some(exec) => (StatusCode::OK, Json(serde_json::json!({ "execution": exec }))),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Execution not found" })),
+        ),
+    }
+}
+
+/// Stream an execution over SSE: a `snapshot` of the execution so far,
+/// then `token` events as output arrives and a final `done` event
+async fn stream_agent(
+    State(state): State<Arc<AppState>>,
+    Path((_workspace_id, execution_id)): Path<(String, String)>,
+) -> axum::response::Response {
+    // Subscribe before taking the snapshot so no tokens fall in between
+    let rx = state.agents.subscribe_output();
+
+    let exec = match state.agents.get_execution(&execution_id).await {
+        Some(exec) => exec,
+        None => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "error": "Execution not found" })),
+            )
+                .into_response();
+        }
+    };
+
+    let finished = !matches!(
+        exec.status,
+        AgentStatus::Pending | AgentStatus::Running | AgentStatus::PullingModel
+    );
+
+    let snapshot = stream::once(async move { Event::default().event("snapshot").json_data(&exec) });
+
+    let tokens = stream::unfold((rx, execution_id, finished), |(mut rx, id, finished)| async move {
+        if finished {
+            return None;
+        }
+        loop {
+            match rx.recv().await {
+                Ok(chunk) if chunk.execution_id == id => {
+                    let done = chunk.done;
+                    let event = Event::default()
+                        .event(if done { "done" } else { "token" })
+                        .json_data(&chunk);
+                    return Some((event, (rx, id, done)));
+                }
+                Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(snapshot.chain(tokens))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+async fn create_agent(
+    State(state): State<Arc<AppState>>,
+    Path(workspace_id): Path<String>,
+    Json(req): Json<CreateAgentRequest>,
+) -> impl IntoResponse {
+    match state.agents.create_execution(&workspace_id, req).await {
+        Ok(exec) => (StatusCode::OK, Json(serde_json::json!({ "execution": exec }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn cancel_agent(
+    State(state): State<Arc<AppState>>,
+    Path((_workspace_id, execution_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match state.agents.cancel_execution(&execution_id).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// What agents remember in a workspace and recall into later prompts
+async fn list_agent_memory(
+    State(state): State<Arc<AppState>>,
+    Path(workspace_id): Path<String>,
+    axum::extract::Query(page): axum::extract::Query<ListQuery>,
+) -> impl IntoResponse {
+    match state.agents.list_memory(&workspace_id, &page) {
+        Ok(page) => (StatusCode::OK, Json(page.to_json("memory"))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn clear_agent_memory(
+    State(state): State<Arc<AppState>>,
+    Path(workspace_id): Path<String>,
+) -> impl IntoResponse {
+    match state.agents.clear_memory(&workspace_id) {
+        Ok(removed) => Json(serde_json::json!({ "success": true, "removed": removed })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+// ============ Document Retrieval Handlers ============
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentsQuery {
+    workspace_id: Option<String>,
+}
+
+async fn rag_documents(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<DocumentsQuery>,
+    axum::extract::Query(page): axum::extract::Query<ListQuery>,
+) -> axum::response::Response {
+    match state.documents.list(query.workspace_id.as_deref()) {
+        Ok(documents) => Json(page.apply(documents).to_json("documents")).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Chunk and embed a file or CID; reading local files is not offered
+/// through the relay
+async fn rag_ingest(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<IngestRequest>,
+) -> axum::response::Response {
+    if req.path.is_some() {
+        if let Some(response) = refuse_relayed(&headers, "Local files") {
+            return response;
+        }
+    }
+    match state.documents.ingest(req).await {
+        Ok(document) => (StatusCode::CREATED, Json(document)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn rag_get_document(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> axum::response::Response {
+    match state.documents.get(&id) {
+        Ok(document) => Json(document).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn rag_remove_document(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> axum::response::Response {
+    match state.documents.remove(&id) {
+        Ok(()) => Json(serde_json::json!({ "success": true })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Passages of the indexed documents closest to a query
+async fn rag_query(State(state): State<Arc<AppState>>, Json(query): Json<RagQuery>) -> axum::response::Response {
+    match state.documents.query(&query).await {
+        Ok(results) => Json(serde_json::json!({ "results": results })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+// ============ LLM Provider Handlers ============
+
+async fn list_providers(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+) -> impl IntoResponse {
+    Json(query.apply(state.providers.list().await).to_json("providers"))
+}
+
+async fn providers_health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!({ "backends": state.providers.check_health().await }))
+}
+
+async fn save_provider(
+    State(state): State<Arc<AppState>>,
+    Json(config): Json<ProviderConfig>,
+) -> impl IntoResponse {
+    match state.providers.save(config).await {
+        Ok(provider) => (StatusCode::OK, Json(serde_json::json!({ "provider": provider }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn remove_provider(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.providers.remove(&id).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+// ============ Cloud GPU Proxy Handlers ============
+
+/// API keys are not accepted here; they are saved once through
+/// `POST /api/v1/gpu/credentials` and looked up server-side
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GpuQuery {
+    /// `vast` (default), `runpod`, `lambda` or the ID of a custom provider
+    #[serde(default)]
+    provider: Option<String>,
+    #[serde(default)]
+    max_price: Option<f64>,
+    #[serde(default)]
+    gpu_type: Option<String>,
+    /// Per GPU
+    #[serde(default)]
+    min_vram_gb: Option<f64>,
+    #[serde(default)]
+    min_gpus: Option<u32>,
+    #[serde(default)]
+    min_reliability: Option<f64>,
+    #[serde(default)]
+    region: Option<String>,
+    #[serde(default)]
+    min_cuda: Option<f64>,
+    /// `value` (price per TFLOP, default), `price` or `performance`
+    #[serde(default)]
+    sort: OfferSort,
+    /// Only the best offer per GPU model
+    #[serde(default)]
+    distinct: bool,
+    #[serde(default)]
+    limit: Option<usize>,
+    /// Instances only, with `limit`; see `ListQuery`
+    #[serde(default)]
+    offset: Option<u32>,
+    /// Instances only; see `ListQuery`
+    #[serde(default)]
+    filter: Option<String>,
+    /// Bypass the offer cache
+    #[serde(default)]
+    refresh: bool,
+}
+
+fn gpu_error(e: String) -> (StatusCode, Json<serde_json::Value>) {
+    log::error!("[GPU] {}", e);
+    (StatusCode::BAD_GATEWAY, Json(serde_json::json!({ "error": e })))
+}
+
+/// Client for the requested provider, or the error response
+async fn gpu_client(
+    state: &AppState,
+    provider: Option<&str>,
+) -> Result<Box<dyn GpuProvider>, (StatusCode, Json<serde_json::Value>)> {
+    let config = state.config.read().await;
+    gpu_provider::provider(
+        provider.unwrap_or(gpu_provider::DEFAULT_PROVIDER),
+        &config.gpu.custom_providers,
+    )
+    .map_err(|e| (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))))
+}
+
+async fn gpu_providers(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let config = state.config.read().await;
+    Json(serde_json::json!({
+        "providers": gpu_provider::list_providers(&config.gpu.custom_providers)
+    }))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpuCredentialRequest {
+    provider: String,
+    api_key: String,
+}
+
+async fn gpu_save_credentials(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<GpuCredentialRequest>,
+) -> impl IntoResponse {
+    let known = {
+        let config = state.config.read().await;
+        gpu_provider::list_providers(&config.gpu.custom_providers)
+            .iter()
+            .any(|p| p.id == req.provider)
+    };
+    if !known {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "success": false, "error": format!("Unknown GPU provider: {}", req.provider) })),
+        );
+    }
+    match gpu_credentials::save(&req.provider, &req.api_key) {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "success": false, "error": e })),
+        ),
+    }
+}
+
+async fn gpu_remove_credentials(Path(provider): Path<String>) -> impl IntoResponse {
+    match gpu_credentials::remove(&provider) {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "success": false, "error": e })),
+        ),
+    }
+}
+
+async fn gpu_spend(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!(state.gpu_spend.summary().await))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpuBudgetRequest {
+    /// USD per month; null removes the budget
+    monthly_budget: Option<f64>,
+    #[serde(default)]
+    auto_destroy: bool,
+}
+
+async fn gpu_set_budget(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<GpuBudgetRequest>,
+) -> impl IntoResponse {
+    match state.set_gpu_budget(req.monthly_budget, req.auto_destroy).await {
+        Ok(summary) => (StatusCode::OK, Json(serde_json::json!(summary))),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "success": false, "error": e })),
+        ),
+    }
+}
+
+async fn gpu_offers(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(params): axum::extract::Query<GpuQuery>,
+) -> impl IntoResponse {
+    let provider = match gpu_client(&state, params.provider.as_deref()).await {
+        Ok(provider) => provider,
+        Err(e) => return e,
+    };
+    let filter = OfferFilter {
+        max_price: params.max_price,
+        gpu_type: params.gpu_type,
+        min_vram_gb: params.min_vram_gb,
+        min_gpus: params.min_gpus,
+        min_reliability: params.min_reliability,
+        region: params.region,
+        min_cuda: params.min_cuda,
+        sort: params.sort,
+        distinct: params.distinct,
+        limit: params.limit,
+    };
+    match state.gpu_offers.offers(provider.as_ref(), &filter, params.refresh).await {
+        Ok(offers) => (StatusCode::OK, Json(serde_json::json!({ "offers": offers }))),
+        Err(e) => gpu_error(e),
+    }
+}
+
+async fn gpu_instances(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(params): axum::extract::Query<GpuQuery>,
+) -> impl IntoResponse {
+    let provider = match gpu_client(&state, params.provider.as_deref()).await {
+        Ok(provider) => provider,
+        Err(e) => return e,
+    };
+    // `sort` here orders offers, so instances keep the provider's order
+    let query = ListQuery {
+        limit: params.limit.map(|limit| u32::try_from(limit).unwrap_or(u32::MAX)),
+        offset: params.offset,
+        sort: None,
+        filter: params.filter,
+    };
+    match provider.list_instances().await {
+        Ok(instances) => (StatusCode::OK, Json(query.apply(instances).to_json("instances"))),
+        Err(e) => gpu_error(e),
+    }
+}
+
+async fn gpu_user(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(params): axum::extract::Query<GpuQuery>,
+) -> impl IntoResponse {
+    let provider = match gpu_client(&state, params.provider.as_deref()).await {
+        Ok(provider) => provider,
+        Err(e) => return e,
+    };
+    match provider.balance().await {
+        Ok(credit) => (StatusCode::OK, Json(serde_json::json!({ "credit": credit }))),
+        Err(e) => gpu_error(e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GpuRentRequest {
+    #[serde(default)]
+    provider: Option<String>,
+    #[serde(flatten)]
+    rent: RentRequest,
+}
+
+async fn gpu_rent(
+    State(state): State<Arc<AppState>>,
+    Path(offer_id): Path<String>,
+    Json(req): Json<GpuRentRequest>,
+) -> impl IntoResponse {
+    let provider = match gpu_client(&state, req.provider.as_deref()).await {
+        Ok(provider) => provider,
+        Err(e) => return e,
+    };
+    match provider.rent(&offer_id, &req.rent).await {
+        Ok(instance_id) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "success": true, "instanceId": instance_id })),
+        ),
+        Err(e) => gpu_error(e),
+    }
+}
+
+async fn gpu_destroy(
+    State(state): State<Arc<AppState>>,
+    Path(instance_id): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<GpuQuery>,
+) -> impl IntoResponse {
+    let provider = match gpu_client(&state, params.provider.as_deref()).await {
+        Ok(provider) => provider,
+        Err(e) => return e,
+    };
+    match provider.destroy(&instance_id).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => gpu_error(e),
+    }
+}
+
+async fn gpu_compute_list(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+) -> impl IntoResponse {
+    Json(query.apply(state.remote_compute.list().await).to_json("computes"))
+}
+
+/// Rent an offer and attach it as agent compute once its model is pulled
+async fn gpu_compute_provision(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ProvisionRequest>,
+) -> impl IntoResponse {
+    match state.remote_compute.provision(req).await {
+        Ok(compute) => (StatusCode::ACCEPTED, Json(serde_json::json!(compute))),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "success": false, "error": e })),
+        ),
+    }
+}
+
+async fn gpu_compute_get(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.remote_compute.get(&id).await {
+        Some(compute) => (StatusCode::OK, Json(serde_json::json!(compute))),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Remote compute not found" })),
+        ),
+    }
+}
+
+/// Unregister an attached GPU and destroy its instance
+async fn gpu_compute_detach(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.remote_compute.detach(&id, "Detached by user").await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => gpu_error(e),
+    }
+}
+
+// ============ Container Handlers ============
+
+async fn container_runtime_info(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.containers.get_runtime_info().await {
+        Some(info) => Json(serde_json::json!(info)),
+        None => Json(serde_json::json!({ "available": false })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SetRuntimeRequest {
+    /// `docker`, `podman`, `native`, or null to pick automatically
+    runtime: Option<RuntimeType>,
+}
+
+async fn container_set_runtime(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SetRuntimeRequest>,
+) -> impl IntoResponse {
+    match state.set_container_runtime(req.runtime).await {
+        Ok(info) => (StatusCode::OK, Json(serde_json::json!(info))),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "available": false, "error": e })),
+        ),
+    }
+}
+
+async fn container_detect_runtime(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.containers.detect_runtime().await {
+        Ok(info) => (StatusCode::OK, Json(serde_json::json!(info))),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "available": false, "error": e.to_string() })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ContainerListQuery {
+    #[serde(default)]
+    all: bool,
+}
+
+async fn container_list(
     State(state): State<Arc<AppState>>,
-    Path(workspace_id): Path<String>,
-    Json(req): Json<CreateAgentRequest>,
+    axum::extract::Query(params): axum::extract::Query<ContainerListQuery>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
 ) -> impl IntoResponse {
-    match state.agents.create_execution(&workspace_id, req).await {
-        Ok(exec) => (StatusCode::OK, Json(serde_json::json!({ "execution": exec }))),
+    match state.containers.list_containers(params.all).await {
+        Ok(containers) => (StatusCode::OK, Json(query.apply(containers).to_json("containers"))),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": e })),
+            Json(serde_json::json!({ "error": e.to_string() })),
         ),
     }
 }
 
-async fn cancel_agent(
+async fn container_list_images(
     State(state): State<Arc<AppState>>,
-    Path((_workspace_id, execution_id)): Path<(String, String)>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
 ) -> impl IntoResponse {
-    match state.agents.cancel_execution(&execution_id).await {
-        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+    match state.containers.list_images().await {
+        Ok(images) => (StatusCode::OK, Json(query.apply(images).to_json("images"))),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "success": false, "error": e })),
+            Json(serde_json::json!({ "error": e.to_string() })),
         ),
     }
 }
 
-// ============ Cloud GPU Proxy Handlers ============
-
 #[derive(Deserialize)]
-pub struct GpuQuery {
-    api_key: String,
-    #[serde(default)]
-    max_price: Option<f64>,
-    #[serde(default)]
-    gpu_type: Option<String>,
+pub struct ContainerPullImageRequest {
+    image: String,
 }
 
-async fn gpu_offers(
-    axum::extract::Query(params): axum::extract::Query<GpuQuery>,
-) -> impl IntoResponse {
-    use axum::http::header;
-
-    let client = reqwest::Client::new();
-
-    // Build Vast API query
-    let mut query = serde_json::json!({
-        "rentable": {"eq": true},
-        "rented": {"eq": false},
-        "type": "on-demand",
-        "order": [["dph_total", "asc"]]
-    });
-
-    if let Some(max_price) = params.max_price {
-        if max_price < 10.0 {
-            query["dph_total"] = serde_json::json!({"lte": max_price});
-        }
-    }
-
-    if let Some(ref gpu_type) = params.gpu_type {
-        if gpu_type != "any" {
-            query["gpu_name"] = serde_json::json!({"eq": gpu_type});
-        }
+/// Policy rejections are the caller's fault, not the runtime's
+fn container_error_status(e: &ContainerError) -> StatusCode {
+    match e {
+        ContainerError::PolicyViolation(_) => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
     }
+}
 
-    let url = format!(
-        "https://console.vast.ai/api/v0/bundles/?q={}",
-        urlencoding::encode(&query.to_string())
-    );
+async fn container_image_policy(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.containers.image_policy().await)
+}
 
-    log::info!("[GPU] Fetching offers from: {}", url);
-
-    match client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .send()
-        .await
-    {
-        Ok(resp) => {
-            let status = resp.status();
-            match resp.text().await {
-                Ok(body) => {
-                    log::info!("[GPU] Got response: {} bytes, status: {}", body.len(), status);
-                    (
-                        StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::OK),
-                        [(header::CONTENT_TYPE, "application/json")],
-                        body
-                    )
-                }
-                Err(e) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    [(header::CONTENT_TYPE, "application/json")],
-                    format!("{{\"error\":\"{}\"}}", e)
-                ),
-            }
-        }
-        Err(e) => {
-            log::error!("[GPU] Request failed: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                [(header::CONTENT_TYPE, "application/json")],
-                format!("{{\"error\":\"{}\"}}", e)
-            )
-        }
+async fn container_set_image_policy(
+    State(state): State<Arc<AppState>>,
+    Json(policy): Json<ImagePolicy>,
+) -> impl IntoResponse {
+    match state.set_image_policy(policy).await {
+        Ok(policy) => (StatusCode::OK, Json(serde_json::json!(policy))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "success": false, "error": e })),
+        ),
     }
 }
 
-async fn gpu_instances(
-    axum::extract::Query(params): axum::extract::Query<GpuQuery>,
+async fn container_pull_image(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ContainerPullImageRequest>,
 ) -> impl IntoResponse {
-    use axum::http::header;
-    let client = reqwest::Client::new();
-
-    match client
-        .get("https://console.vast.ai/api/v0/instances/")
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .send()
-        .await
-    {
-        Ok(resp) => {
-            let status = resp.status();
-            match resp.text().await {
-                Ok(body) => (
-                    StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::OK),
-                    [(header::CONTENT_TYPE, "application/json")],
-                    body
-                ),
-                Err(e) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    [(header::CONTENT_TYPE, "application/json")],
-                    format!("{{\"error\":\"{}\"}}", e)
-                ),
-            }
-        }
+    match state.containers.pull_image(&req.image).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            [(header::CONTENT_TYPE, "application/json")],
-            format!("{{\"error\":\"{}\"}}", e)
+            container_error_status(&e),
+            Json(serde_json::json!({ "success": false, "error": e.to_string() })),
         ),
     }
 }
 
-async fn gpu_user(
-    axum::extract::Query(params): axum::extract::Query<GpuQuery>,
+async fn container_push_image(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ContainerPullImageRequest>,
 ) -> impl IntoResponse {
-    use axum::http::header;
-    let client = reqwest::Client::new();
-
-    match client
-        .get("https://console.vast.ai/api/v0/users/current/")
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .send()
-        .await
-    {
-        Ok(resp) => {
-            let status = resp.status();
-            match resp.text().await {
-                Ok(body) => (
-                    StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::OK),
-                    [(header::CONTENT_TYPE, "application/json")],
-                    body
-                ),
-                Err(e) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    [(header::CONTENT_TYPE, "application/json")],
-                    format!("{{\"error\":\"{}\"}}", e)
-                ),
-            }
-        }
+    match state.containers.push_image(&req.image).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
-            [(header::CONTENT_TYPE, "application/json")],
-            format!("{{\"error\":\"{}\"}}", e)
+            Json(serde_json::json!({ "success": false, "error": e.to_string() })),
         ),
     }
 }
 
-#[derive(Deserialize)]
-pub struct GpuRentRequest {
-    api_key: String,
-    image: Option<String>,
-    disk: Option<u32>,
+async fn container_list_registries() -> impl IntoResponse {
+    Json(serde_json::json!({ "registries": crate::services::registry_auth::list() }))
 }
 
-async fn gpu_rent(
-    Path(offer_id): Path<u64>,
-    Json(req): Json<GpuRentRequest>,
-) -> impl IntoResponse {
-    use axum::http::header;
-    let client = reqwest::Client::new();
-
-    let payload = serde_json::json!({
-        "client_id": "me",
-        "image": req.image.unwrap_or_else(|| "ollama/ollama".to_string()),
-        "disk": req.disk.unwrap_or(20),
-        "label": "otherthing-workspace",
-        "onstart": "#!/bin/bash\nollama serve &\nsleep 5\necho 'Ollama ready on port 11434'",
-        "runtype": "ssh_direc ssh_proxy",
-        "env": {
-            "OLLAMA_HOST": "0.0.0.0"
-        }
-    });
+async fn container_save_registry(Json(req): Json<RegistryCredential>) -> impl IntoResponse {
+    match crate::services::registry_auth::save(req) {
+        Ok(info) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "registry": info }))),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "success": false, "error": e })),
+        ),
+    }
+}
 
-    let url = format!("https://console.vast.ai/api/v0/asks/{}/", offer_id);
-    log::info!("[GPU] Renting offer {} with payload: {:?}", offer_id, payload);
-
-    match client
-        .put(&url)
-        .header("Authorization", format!("Bearer {}", req.api_key))
-        .json(&payload)
-        .send()
-        .await
-    {
-        Ok(resp) => {
-            let status = resp.status();
-            match resp.text().await {
-                Ok(body) => {
-                    log::info!("[GPU] Rent response: {} - {}", status, body);
-                    (
-                        StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::OK),
-                        [(header::CONTENT_TYPE, "application/json")],
-                        body
-                    )
-                }
-                Err(e) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    [(header::CONTENT_TYPE, "application/json")],
-                    format!("{{\"error\":\"{}\"}}", e)
-                ),
-            }
-        }
-        Err(e) => {
-            log::error!("[GPU] Rent failed: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                [(header::CONTENT_TYPE, "application/json")],
-                format!("{{\"error\":\"{}\"}}", e)
-            )
-        }
+async fn container_remove_registry(Path(registry): Path<String>) -> impl IntoResponse {
+    match crate::services::registry_auth::remove(&registry) {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "success": false, "error": e })),
+        ),
     }
 }
 
-async fn gpu_destroy(
-    Path(instance_id): Path<u64>,
-    axum::extract::Query(params): axum::extract::Query<GpuQuery>,
-) -> impl IntoResponse {
-    use axum::http::header;
-    let client = reqwest::Client::new();
-
-    let url = format!("https://console.vast.ai/api/v0/instances/{}/", instance_id);
-    log::info!("[GPU] Destroying instance {}", instance_id);
-
-    match client
-        .delete(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .send()
-        .await
-    {
-        Ok(resp) => {
-            let status = resp.status();
-            match resp.text().await {
-                Ok(body) => (
-                    StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::OK),
-                    [(header::CONTENT_TYPE, "application/json")],
-                    body
-                ),
-                Err(e) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    [(header::CONTENT_TYPE, "application/json")],
-                    format!("{{\"error\":\"{}\"}}", e)
-                ),
-            }
-        }
+/// Build an image, streaming build output as `output` events and finishing
+/// with a `done` event carrying the image ID
+async fn container_build_image(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<BuildImageRequest>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let (tx, rx) = mpsc::channel::<String>(64);
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+
+    let containers = Arc::clone(&state.containers);
+    tokio::spawn(async move {
+        let result = containers.build_image(req, Some(tx)).await;
+        let _ = done_tx.send(result);
+    });
+
+    let output = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|line| (Ok::<_, axum::Error>(Event::default().event("output").data(line)), rx))
+    });
+
+    let done = stream::once(async move {
+        let payload = match done_rx.await {
+            Ok(Ok(id)) => serde_json::json!({ "success": true, "id": id }),
+            Ok(Err(e)) => serde_json::json!({ "success": false, "error": e.to_string() }),
+            Err(_) => serde_json::json!({ "success": false, "error": "Build task aborted" }),
+        };
+        Event::default().event("done").json_data(payload)
+    });
+
+    Sse::new(output.chain(done)).keep_alive(KeepAlive::default())
+}
+
+async fn container_disk_usage(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.containers.disk_usage().await {
+        Ok(usage) => (StatusCode::OK, Json(serde_json::json!(usage))),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
-            [(header::CONTENT_TYPE, "application/json")],
-            format!("{{\"error\":\"{}\"}}", e)
+            Json(serde_json::json!({ "error": e.to_string() })),
         ),
     }
 }
 
-// ============ Container Handlers ============
-
-async fn container_runtime_info(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.containers.get_runtime_info().await {
-        Some(info) => Json(serde_json::json!(info)),
-        None => Json(serde_json::json!({ "available": false })),
+async fn container_prune(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<PruneRequest>,
+) -> impl IntoResponse {
+    match state.containers.prune(req).await {
+        Ok(result) => (StatusCode::OK, Json(serde_json::json!(result))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
     }
 }
 
-async fn container_detect_runtime(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.containers.detect_runtime().await {
-        Ok(info) => (StatusCode::OK, Json(serde_json::json!(info))),
+async fn container_list_networks(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+) -> impl IntoResponse {
+    match state.containers.list_networks().await {
+        Ok(networks) => (StatusCode::OK, Json(query.apply(networks).to_json("networks"))),
         Err(e) => (
-            StatusCode::SERVICE_UNAVAILABLE,
-            Json(serde_json::json!({ "available": false, "error": e.to_string() })),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
         ),
     }
 }
 
 #[derive(Deserialize)]
-pub struct ContainerListQuery {
+pub struct CreateNetworkRequest {
+    name: String,
     #[serde(default)]
-    all: bool,
+    internal: bool,
+    #[serde(default)]
+    labels: HashMap<String, String>,
 }
 
-async fn container_list(
+async fn container_create_network(
     State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<ContainerListQuery>,
+    Json(req): Json<CreateNetworkRequest>,
 ) -> impl IntoResponse {
-    match state.containers.list_containers(params.all).await {
-        Ok(containers) => (StatusCode::OK, Json(serde_json::json!({ "containers": containers }))),
+    match state.containers.create_network(&req.name, req.internal, req.labels).await {
+        Ok(id) => (StatusCode::OK, Json(serde_json::json!({ "id": id }))),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({ "error": e.to_string() })),
@@ -900,26 +3504,46 @@ async fn container_list(
     }
 }
 
-async fn container_list_images(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.containers.list_images().await {
-        Ok(images) => (StatusCode::OK, Json(serde_json::json!({ "images": images }))),
+async fn container_remove_network(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match state.containers.remove_network(&name).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": e.to_string() })),
+            Json(serde_json::json!({ "success": false, "error": e.to_string() })),
         ),
     }
 }
 
 #[derive(Deserialize)]
-pub struct ContainerPullImageRequest {
-    image: String,
+pub struct NetworkConnectRequest {
+    container: String,
+    #[serde(default)]
+    force: bool,
 }
 
-async fn container_pull_image(
+async fn container_connect_network(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<ContainerPullImageRequest>,
+    Path(name): Path<String>,
+    Json(req): Json<NetworkConnectRequest>,
 ) -> impl IntoResponse {
-    match state.containers.pull_image(&req.image).await {
+    match state.containers.connect_network(&name, &req.container).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "success": false, "error": e.to_string() })),
+        ),
+    }
+}
+
+async fn container_disconnect_network(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(req): Json<NetworkConnectRequest>,
+) -> impl IntoResponse {
+    match state.containers.disconnect_network(&name, &req.container, req.force).await {
         Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -935,7 +3559,7 @@ async fn container_create(
     match state.containers.create_container(req).await {
         Ok(id) => (StatusCode::OK, Json(serde_json::json!({ "id": id }))),
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            container_error_status(&e),
             Json(serde_json::json!({ "error": e.to_string() })),
         ),
     }
@@ -987,6 +3611,53 @@ async fn container_stop(
     }
 }
 
+async fn container_restart(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(req): Json<StopContainerPayload>,
+) -> impl IntoResponse {
+    match state.containers.restart_container(&id, req.timeout).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "success": false, "error": e.to_string() })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RenameContainerPayload {
+    name: String,
+}
+
+async fn container_rename(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(req): Json<RenameContainerPayload>,
+) -> impl IntoResponse {
+    match state.containers.rename_container(&id, &req.name).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "success": false, "error": e.to_string() })),
+        ),
+    }
+}
+
+async fn container_update(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateContainerRequest>,
+) -> impl IntoResponse {
+    match state.containers.update_container(&id, req).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "success": false, "error": e.to_string() })),
+        ),
+    }
+}
+
 #[derive(Deserialize)]
 pub struct RemoveContainerQuery {
     #[serde(default)]
@@ -1031,6 +3702,43 @@ async fn container_logs(
     }
 }
 
+#[derive(Deserialize)]
+pub struct ContainerLogsStreamQuery {
+    tail: Option<usize>,
+}
+
+/// Tail a container's output over SSE: one `log` event per line (with
+/// stream and timestamp) and a final `done` once the container stops
+async fn container_logs_stream(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<ContainerLogsStreamQuery>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let (tx, rx) = mpsc::channel::<LogLine>(256);
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+
+    let containers = Arc::clone(&state.containers);
+    tokio::spawn(async move {
+        let result = containers.follow_logs(&id, params.tail, tx).await;
+        let _ = done_tx.send(result);
+    });
+
+    let lines = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|line| (Event::default().event("log").json_data(line), rx))
+    });
+
+    let done = stream::once(async move {
+        let payload = match done_rx.await {
+            Ok(Ok(())) => serde_json::json!({ "success": true }),
+            Ok(Err(e)) => serde_json::json!({ "success": false, "error": e.to_string() }),
+            Err(_) => serde_json::json!({ "success": false, "error": "Log task aborted" }),
+        };
+        Event::default().event("done").json_data(payload)
+    });
+
+    Sse::new(lines.chain(done)).keep_alive(KeepAlive::default())
+}
+
 #[derive(Deserialize)]
 pub struct ExecRequest {
     cmd: Vec<String>,
@@ -1049,3 +3757,57 @@ async fn container_exec(
         ),
     }
 }
+
+// ============ App Deployment Handlers ============
+
+async fn app_list(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+) -> impl IntoResponse {
+    match state.apps.list().await {
+        Ok(apps) => (StatusCode::OK, Json(query.apply(apps).to_json("apps"))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e })),
+        ),
+    }
+}
+
+async fn app_deploy(
+    State(state): State<Arc<AppState>>,
+    Json(spec): Json<AppSpec>,
+) -> impl IntoResponse {
+    match state.apps.deploy(spec).await {
+        Ok(status) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "app": status }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "success": false, "error": e })),
+        ),
+    }
+}
+
+async fn app_status(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match state.apps.status(&name).await {
+        Ok(status) => (StatusCode::OK, Json(serde_json::json!(status))),
+        Err(e) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": e })),
+        ),
+    }
+}
+
+async fn app_teardown(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match state.apps.teardown(&name).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "success": false, "error": e })),
+        ),
+    }
+}
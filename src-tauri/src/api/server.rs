@@ -1,47 +1,125 @@
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use axum::http::{header, Method};
-use tower_http::cors::{Any, CorsLayer};
+use axum::http::{header, HeaderValue, Method};
+use tokio::sync::oneshot;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
 use super::routes::{create_router, AppState};
+use crate::config::ApiConfig;
+
+/// Port the local API server listens on (the renderer expects this)
+pub const DEFAULT_API_PORT: u16 = 8080;
+
+/// Whether an origin is a page served from this machine
+fn is_localhost_origin(origin: &str) -> bool {
+    let Some(host) = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"))
+    else {
+        return false;
+    };
+    let host = if host.starts_with('[') {
+        host.split_inclusive(']').next().unwrap_or(host)
+    } else {
+        host.split(':').next().unwrap_or(host)
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+/// Browsers may only call the API from the configured origins
+fn cors_layer(config: &ApiConfig) -> CorsLayer {
+    let cors = CorsLayer::new()
+        .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::PUT, Method::OPTIONS])
+        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION]);
+
+    if config.allow_any_origin {
+        log::warn!("API accepts requests from any website (allowAnyOrigin is set)");
+        return cors.allow_origin(Any);
+    }
+
+    let origins: Vec<String> = config
+        .cors_origins
+        .iter()
+        .map(|o| o.trim().trim_end_matches('/').to_lowercase())
+        .collect();
+    let allow_localhost = config.allow_localhost;
+    cors.allow_origin(AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+        let Ok(origin) = origin.to_str() else {
+            return false;
+        };
+        let origin = origin.to_lowercase();
+        origins.contains(&origin) || (allow_localhost && is_localhost_origin(&origin))
+    }))
+}
 
 pub struct ApiServer {
-    state: Option<Arc<AppState>>,
+    state: Arc<AppState>,
+    shutdown_tx: Arc<Mutex<Option<oneshot::Sender<()>>>>,
 }
 
 impl ApiServer {
-    pub fn new() -> Self {
+    pub fn new(state: Arc<AppState>) -> Self {
         Self {
-            state: None,
+            state,
+            shutdown_tx: Arc::new(Mutex::new(None)),
         }
     }
 
+    /// Shared state served by this API server
+    pub fn state(&self) -> Arc<AppState> {
+        Arc::clone(&self.state)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.shutdown_tx.lock().unwrap().is_some()
+    }
+
+    /// Bind the listener and serve in the background until `stop` is called
     pub async fn start(&self, port: u16) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // Initialize state asynchronously
-        let state = Arc::new(AppState::new().await);
-        // Create CORS layer
-        let cors = CorsLayer::new()
-            .allow_origin(Any)
-            .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::PUT, Method::OPTIONS])
-            .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION]);
+        if self.is_running() {
+            log::info!("API server already running");
+            return Ok(());
+        }
+
+        let cors = cors_layer(&self.state.config.read().await.api);
 
         // Build the router
-        let app = create_router(state)
+        let app = create_router(Arc::clone(&self.state))
             .layer(cors);
 
         let addr = SocketAddr::from(([0, 0, 0, 0], port));
+        let listener = tokio::net::TcpListener::bind(addr).await?;
         log::info!("Rust API server listening on http://{}", addr);
 
-        let listener = tokio::net::TcpListener::bind(addr).await?;
-        axum::serve(listener, app).await?;
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        *self.shutdown_tx.lock().unwrap() = Some(shutdown_tx);
+
+        let running = Arc::clone(&self.shutdown_tx);
+        tokio::spawn(async move {
+            // Peer addresses let handlers keep some routes to this machine
+            let result = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .with_graceful_shutdown(async {
+                    let _ = shutdown_rx.await;
+                })
+                .await;
+
+            match result {
+                Ok(()) => log::info!("Rust API server stopped"),
+                Err(e) => {
+                    log::error!("API server error: {}", e);
+                    running.lock().unwrap().take();
+                }
+            }
+        });
 
         Ok(())
     }
-}
 
-impl Default for ApiServer {
-    fn default() -> Self {
-        Self::new()
+    /// Signal the server to finish in-flight requests and stop accepting new ones
+    pub fn stop(&self) {
+        if let Some(tx) = self.shutdown_tx.lock().unwrap().take() {
+            let _ = tx.send(());
+        }
     }
 }
@@ -0,0 +1,51 @@
+//! Launch at Login
+//!
+//! Registers the app as a login item (a Windows Run key, a macOS launch
+//! agent or an XDG autostart entry) when the `autostart` setting is on.
+//! Login launches pass `--minimized`, so the node starts earning from the
+//! tray without opening its window.
+
+use tauri::{AppHandle, Manager};
+use tauri_plugin_autostart::ManagerExt;
+
+/// Argument added to login launches
+pub const MINIMIZED_ARG: &str = "--minimized";
+
+/// Whether this process was started at login
+pub fn launched_minimized() -> bool {
+    std::env::args().any(|arg| arg == MINIMIZED_ARG)
+}
+
+/// Whether the app is currently registered to launch at login
+pub fn is_enabled(app: &AppHandle) -> Result<bool, String> {
+    app.autolaunch()
+        .is_enabled()
+        .map_err(|e| format!("Failed to read launch at login: {}", e))
+}
+
+/// Register or unregister the login item
+pub fn set(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    let launcher = app.autolaunch();
+    let result = if enabled { launcher.enable() } else { launcher.disable() };
+    result.map_err(|e| format!("Failed to update launch at login: {}", e))
+}
+
+/// Bring the login item in line with the saved setting, e.g. after the app
+/// was moved or the entry removed by hand
+pub fn sync(app: &AppHandle, enabled: bool) {
+    match is_enabled(app) {
+        Ok(registered) if registered == enabled => {}
+        Ok(_) => {
+            if let Err(e) = set(app, enabled) {
+                log::warn!("{}", e);
+            }
+        }
+        Err(e) => log::warn!("{}", e),
+    }
+
+    if launched_minimized() {
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.hide();
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Command Line
+//!
+//! Subcommands that print something and exit instead of starting the app:
+//!
+//! ```text
+//! otherthing-node earnings [--days N] [--json]
+//! otherthing-node earnings --export csv|json [--from DATE] [--to DATE] [--output FILE]
+//! otherthing-node backup create FILE
+//! otherthing-node backup restore FILE
+//! ```
+//!
+//! Backups are encrypted with a passphrase read from
+//! `OTHERTHING_BACKUP_PASSPHRASE`, or asked for on the terminal.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::config::NodeConfig;
+use crate::services::earnings::{self, CurrencyTotal, EarningsLedger, ExportFormat};
+use crate::services::{backup, PaymentMonitor};
+
+const PASSPHRASE_VAR: &str = "OTHERTHING_BACKUP_PASSPHRASE";
+
+fn format_totals(totals: &[CurrencyTotal]) -> String {
+    if totals.is_empty() {
+        return "0.00".to_string();
+    }
+    totals
+        .iter()
+        .map(|t| format!("{:.2} {} ({} jobs)", t.amount_cents as f64 / 100.0, t.currency, t.jobs))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Value following `flag`, if the flag was given
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a str>, String> {
+    match args.iter().position(|a| a == flag) {
+        Some(i) => args
+            .get(i + 1)
+            .map(|v| Some(v.as_str()))
+            .ok_or_else(|| format!("{} needs a value", flag)),
+        None => Ok(None),
+    }
+}
+
+/// Per-job rows for bookkeeping, with payment status where the node's
+/// wallet has been reconciled
+fn export(ledger: EarningsLedger, format: ExportFormat, args: &[String]) -> Result<(), String> {
+    let ledger = Arc::new(ledger);
+    let config = Arc::new(tokio::sync::RwLock::new(NodeConfig::load()));
+    let events = tokio::sync::broadcast::channel(1).0;
+    let statuses = match PaymentMonitor::open_default(Arc::clone(&ledger), config, events) {
+        Ok(monitor) => tauri::async_runtime::block_on(monitor.job_statuses())?,
+        Err(e) => {
+            eprintln!("{}; payment status left unverified", e);
+            HashMap::new()
+        }
+    };
+
+    let rows = ledger.export(flag_value(args, "--from")?, flag_value(args, "--to")?, &statuses)?;
+    let data = match format {
+        ExportFormat::Csv => earnings::to_csv(&rows),
+        ExportFormat::Json => serde_json::to_string_pretty(&rows).map_err(|e| e.to_string())? + "\n",
+    };
+    match flag_value(args, "--output")? {
+        Some(path) => {
+            std::fs::write(path, data).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+            eprintln!("Exported {} jobs to {}", rows.len(), path);
+        }
+        None => print!("{}", data),
+    }
+    Ok(())
+}
+
+fn earnings(args: &[String]) -> Result<(), String> {
+    let json = args.iter().any(|a| a == "--json");
+    let days = match flag_value(args, "--days")? {
+        Some(d) => d.parse::<u32>().map_err(|_| "--days needs a number")?,
+        None => 7,
+    };
+
+    let ledger = EarningsLedger::open_default()?;
+    if let Some(format) = flag_value(args, "--export")? {
+        return export(ledger, format.parse()?, args);
+    }
+
+    let summary = ledger.summary()?;
+    if json {
+        let data = serde_json::to_string_pretty(&summary).map_err(|e| e.to_string())?;
+        println!("{}", data);
+        return Ok(());
+    }
+
+    println!("Total:        {}", format_totals(&summary.total));
+    println!("Last 24h:     {}", format_totals(&summary.last_24h));
+    println!("Last 7 days:  {}", format_totals(&summary.last_7d));
+    println!("Last 30 days: {}", format_totals(&summary.last_30d));
+
+    let daily = ledger.daily(days)?;
+    if !daily.is_empty() {
+        println!();
+        for day in daily {
+            println!(
+                "{}  {:>10.2} {}  {} jobs",
+                day.day,
+                day.amount_cents as f64 / 100.0,
+                day.currency,
+                day.jobs
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Backup passphrase from the environment, or typed in
+fn passphrase() -> Result<String, String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_VAR) {
+        return Ok(passphrase);
+    }
+    eprint!("Backup passphrase: ");
+    let mut line = String::new();
+    std::io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| format!("Failed to read passphrase: {}", e))?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn backup_command(args: &[String]) -> Result<(), String> {
+    const USAGE: &str = "Usage: otherthing-node backup create|restore FILE";
+    let (action, path) = match args {
+        [action, path, ..] => (action.as_str(), path.as_str()),
+        _ => return Err(USAGE.to_string()),
+    };
+    match action {
+        "create" => {
+            let data = backup::create(&passphrase()?)?;
+            std::fs::write(path, &data).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+            eprintln!("Backup written to {}", path);
+        }
+        "restore" => {
+            let data = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let manifest = backup::restore(&data, &passphrase()?)?;
+            eprintln!(
+                "Restored backup from {} ({}); it takes effect the next time the node starts",
+                manifest.created_at,
+                manifest.files.join(", ")
+            );
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+    Ok(())
+}
+
+/// Run the subcommand named in `args` (program name first). Returns the
+/// exit code, or `None` when there is no subcommand and the app should start.
+pub fn run(args: &[String]) -> Option<i32> {
+    let result = match args.get(1).map(String::as_str) {
+        Some("earnings") => earnings(&args[2..]),
+        Some("backup") => backup_command(&args[2..]),
+        _ => return None,
+    };
+    Some(match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    })
+}
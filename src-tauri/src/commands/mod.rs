@@ -1,47 +1,90 @@
+use crate::config::{BandwidthConfig, IpfsConfig, LimitsConfig, OllamaConfig, PricingConfig, RelayTlsConfig, StorageConfig, UpdateConfig};
+use crate::api::{self, ApiServer, RelayClient, RelayStatus, DEFAULT_API_PORT};
+use crate::error::NodeError;
+use crate::notifications::{self, NotificationSettings};
+use crate::pagination::{ListQuery, Page};
 use crate::models::*;
 use crate::services::{
-    ContainerManager, ContainerInfo, CreateContainerRequest, RuntimeInfo, ExecResult,
-    HardwareDetector, IpfsManager, OllamaManager,
+    AgentExecution, AgentManager, AgentTemplate, AppDeployment, AppSpec, AppStatus, BuildImageRequest, CreateAgentRequest, ExecutionQuery, ContainerManager, ContainerInfo, CreateContainerRequest, DiskUsage, LogLine, PruneRequest, PruneResult, UpdateContainerRequest, NetworkInfo, RuntimeInfo, RuntimeType, ExecOutput,
+    BackendStatus, ClusterFollower, HardwareDetector, IpfsManager, OllamaManager, MAX_GET_SIZE, generate_swarm_key, parse_swarm_key, ProviderConfig, ProviderInfo, ProviderRegistry, RegistryCredential, RegistryInfo,
+    CreateWorkspaceRequest, UpdateWorkspaceRequest, Workspace, WorkspaceManager,
+    state_store, AddRemoteNodeRequest, AnnouncedNode, Availability, DispatchRequest, JobOffer, JobRecord, MeshJobRecord, MeshUsage, RemoteNode, RemoteQuery, StoragePin, StorageStatus,
+    bandwidth::Subsystem, BandwidthReport, PricingStatus,
 };
+use std::collections::HashMap;
 use std::sync::Arc;
-use tauri::State;
+use tauri::{Emitter, State};
 use tokio::sync::RwLock;
 
+/// Tauri-side handles onto the same state the local API server serves
 #[derive(Clone)]
 pub struct AppState {
     pub ollama: Arc<OllamaManager>,
     pub ipfs: Arc<IpfsManager>,
+    pub cluster: Arc<ClusterFollower>,
     pub containers: Arc<ContainerManager>,
+    pub apps: Arc<AppDeployment>,
+    pub agents: Arc<AgentManager>,
+    pub providers: Arc<ProviderRegistry>,
+    pub workspaces: Arc<WorkspaceManager>,
     pub node_running: Arc<RwLock<bool>>,
-    pub node_id: Arc<RwLock<Option<String>>>,
-    pub share_key: Arc<RwLock<Option<String>>>,
+    pub node_id: Arc<RwLock<String>>,
+    pub share_key: Arc<RwLock<String>>,
+    pub api: Arc<ApiServer>,
+    pub relay: Arc<RelayClient>,
+    /// Running `container_follow_logs` tasks, by container ID
+    pub log_followers: Arc<std::sync::Mutex<HashMap<String, tauri::async_runtime::JoinHandle<()>>>>,
 }
 
 impl AppState {
     pub async fn new() -> Self {
+        let shared = Arc::new(api::AppState::new().await);
+
         Self {
-            ollama: Arc::new(OllamaManager::new()),
-            ipfs: Arc::new(IpfsManager::new()),
-            containers: Arc::new(ContainerManager::new().await),
-            node_running: Arc::new(RwLock::new(false)),
-            node_id: Arc::new(RwLock::new(None)),
-            share_key: Arc::new(RwLock::new(None)),
+            ollama: Arc::clone(&shared.ollama),
+            ipfs: Arc::clone(&shared.ipfs),
+            cluster: Arc::clone(&shared.cluster),
+            containers: Arc::clone(&shared.containers),
+            apps: Arc::clone(&shared.apps),
+            agents: Arc::clone(&shared.agents),
+            providers: Arc::clone(&shared.providers),
+            workspaces: Arc::clone(&shared.workspaces),
+            node_running: Arc::clone(&shared.node_running),
+            node_id: Arc::clone(&shared.node_id),
+            share_key: Arc::clone(&shared.share_key),
+            relay: Arc::new(RelayClient::new(Arc::clone(&shared))),
+            api: Arc::new(ApiServer::new(shared)),
+            log_followers: Arc::new(std::sync::Mutex::new(HashMap::new())),
         }
     }
-}
 
-// Note: AppState now requires async initialization, so Default uses a sync fallback
-impl Default for AppState {
-    fn default() -> Self {
-        // This is a sync fallback - prefer using AppState::new().await
-        Self {
-            ollama: Arc::new(OllamaManager::new()),
-            ipfs: Arc::new(IpfsManager::new()),
-            containers: Arc::new(futures::executor::block_on(ContainerManager::new())),
-            node_running: Arc::new(RwLock::new(false)),
-            node_id: Arc::new(RwLock::new(None)),
-            share_key: Arc::new(RwLock::new(None)),
+    /// Start the node: bring up the local API server and mark the node running
+    pub async fn start_node(&self) -> Result<(), String> {
+        self.api.start(DEFAULT_API_PORT).await
+            .map_err(|e| format!("Failed to start API server: {}", e))?;
+        self.api.state().mark_started().await;
+        log::info!("Node started in local mode");
+
+        let relay = self.api.state().config.read().await.relay.clone();
+        if relay.enabled && !relay.url.is_empty() {
+            self.relay.start(&relay.url).await;
         }
+
+        if self.api.state().config.read().await.discovery.enabled {
+            if let Err(e) = self.api.state().start_discovery().await {
+                log::warn!("{}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop the node and the API server together
+    pub async fn stop_node(&self) {
+        self.relay.stop().await;
+        self.api.state().discovery.stop().await;
+        self.api.state().mark_stopped().await;
+        self.api.stop();
+        log::info!("Node stopped");
     }
 }
 
@@ -66,88 +109,393 @@ pub async fn get_node_status(state: State<'_, AppState>) -> Result<NodeStatus, S
     Ok(NodeStatus {
         running,
         connected: false, // Network connection status
-        node_id,
-        share_key,
+        node_id: Some(node_id),
+        share_key: Some(share_key),
+        uptime_secs: state.api.state().uptime_secs().await,
     })
 }
 
 #[tauri::command]
 pub async fn start_node(state: State<'_, AppState>) -> Result<CommandResult, String> {
-    // Generate node ID if not set
-    let mut node_id = state.node_id.write().await;
-    if node_id.is_none() {
-        *node_id = Some(uuid::Uuid::new_v4().to_string());
+    match state.start_node().await {
+        Ok(()) => Ok(CommandResult::ok()),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Whether the node is taking jobs, with the idle sample behind it
+#[tauri::command]
+pub async fn get_availability(state: State<'_, AppState>) -> Result<Availability, String> {
+    Ok(state.api.state().idle.status().await)
+}
+
+#[tauri::command]
+pub async fn get_limits_config(state: State<'_, AppState>) -> Result<LimitsConfig, String> {
+    Ok(state.api.state().config.read().await.limits.clone())
+}
+
+/// Switch idle gating on or off, or change its thresholds
+#[tauri::command]
+pub async fn set_limits_config(state: State<'_, AppState>, config: LimitsConfig) -> Result<Availability, String> {
+    state.api.state().set_limits(config).await
+}
+
+#[tauri::command]
+pub async fn get_resource_limits(state: State<'_, AppState>) -> Result<ResourceLimits, String> {
+    Ok(state.api.state().config.read().await.limits.resources.clone())
+}
+
+/// Cap the CPU, memory and storage the node uses for everything it runs
+#[tauri::command]
+pub async fn set_resource_limits(state: State<'_, AppState>, limits: ResourceLimits) -> Result<ResourceLimits, String> {
+    state.api.state().set_resource_limits(limits).await
+}
+
+/// This node and the user's remote nodes, with their current status
+#[tauri::command]
+pub async fn fleet_list(state: State<'_, AppState>) -> Result<Vec<serde_json::Value>, NodeError> {
+    state.api.state().fleet_overview(true).await
+}
+
+/// Register a remote node by address and share key
+#[tauri::command]
+pub async fn fleet_add(state: State<'_, AppState>, request: AddRemoteNodeRequest) -> Result<RemoteNode, NodeError> {
+    let local_id = state.node_id.read().await.clone();
+    state.api.state().fleet.add(request, &local_id).await
+}
+
+#[tauri::command]
+pub async fn fleet_remove(state: State<'_, AppState>, node_id: String) -> Result<(), NodeError> {
+    state.api.state().fleet.remove(&node_id)
+}
+
+/// Status, hardware or Ollama state of a remote node
+#[tauri::command]
+pub async fn fleet_query(state: State<'_, AppState>, node_id: String, query: RemoteQuery) -> Result<serde_json::Value, NodeError> {
+    state.api.state().fleet.query(&node_id, query).await
+}
+
+/// Send an agent execution or container job to an idle paired node
+#[tauri::command]
+pub async fn mesh_dispatch(state: State<'_, AppState>, request: DispatchRequest) -> Result<MeshJobRecord, NodeError> {
+    let api = state.api.state();
+    let local_id = api.node_id.read().await.clone();
+    api.mesh.dispatch(request, &local_id).await
+}
+
+/// Jobs sent to and received from other nodes
+#[tauri::command]
+pub async fn mesh_jobs(state: State<'_, AppState>, query: Option<ListQuery>) -> Result<Page<MeshJobRecord>, NodeError> {
+    state.api.state().mesh.list(&query.unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn mesh_job(state: State<'_, AppState>, id: String) -> Result<MeshJobRecord, NodeError> {
+    state.api.state().mesh.get(&id)
+}
+
+#[tauri::command]
+pub async fn mesh_cancel(state: State<'_, AppState>, id: String) -> Result<(), NodeError> {
+    let api = state.api.state();
+    let local_id = api.node_id.read().await.clone();
+    api.mesh.cancel(&id, &local_id).await
+}
+
+/// Jobs, tokens and run time exchanged with each peer
+#[tauri::command]
+pub async fn mesh_usage(state: State<'_, AppState>) -> Result<Vec<MeshUsage>, NodeError> {
+    state.api.state().mesh.usage()
+}
+
+/// Nodes found on the local network, ready to pair with `fleet_add`
+#[tauri::command]
+pub async fn discovery_peers(state: State<'_, AppState>) -> Result<Vec<serde_json::Value>, NodeError> {
+    state.api.state().discovery_peers().await
+}
+
+/// Zip up logs, crash reports, redacted config and hardware info for
+/// support; returns where the bundle was saved
+#[tauri::command]
+pub async fn export_diagnostics(app: tauri::AppHandle, state: State<'_, AppState>, path: Option<String>) -> Result<String, String> {
+    let path = crate::diagnostics::export(&app, &state, path.map(std::path::PathBuf::from)).await?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Newer release on the configured update channel, if any
+#[tauri::command]
+pub async fn check_for_updates(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<Option<crate::updater::UpdateInfo>, String> {
+    crate::updater::check(&app, &state).await
+}
+
+/// Download the update, drain running jobs, install and restart
+#[tauri::command]
+pub async fn install_update(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    crate::updater::install(&app, &state).await
+}
+
+#[tauri::command]
+pub async fn get_update_config(state: State<'_, AppState>) -> Result<UpdateConfig, String> {
+    Ok(state.api.state().config.read().await.update.clone())
+}
+
+/// Switch release channel or background checks and remember the choice
+#[tauri::command]
+pub async fn set_update_config(state: State<'_, AppState>, config: UpdateConfig) -> Result<UpdateConfig, String> {
+    let shared = state.api.state();
+    let mut node_config = shared.config.write().await;
+    node_config.update = config.clone();
+    node_config.save()?;
+    Ok(config)
+}
+
+/// Whether the node is registered to launch at login
+#[tauri::command]
+pub fn get_autostart(app: tauri::AppHandle) -> Result<bool, String> {
+    crate::autostart::is_enabled(&app)
+}
+
+/// Turn launch at login on or off and remember the choice
+#[tauri::command]
+pub async fn set_autostart(app: tauri::AppHandle, state: State<'_, AppState>, enabled: bool) -> Result<bool, String> {
+    crate::autostart::set(&app, enabled)?;
+    let shared = state.api.state();
+    let mut config = shared.config.write().await;
+    config.autostart = enabled;
+    config.save()?;
+    log::info!("Launch at login {}", if enabled { "enabled" } else { "disabled" });
+    Ok(enabled)
+}
+
+#[tauri::command]
+pub async fn rotate_share_key(state: State<'_, AppState>) -> Result<String, String> {
+    state.api.state().rotate_share_key().await
+}
+
+#[tauri::command]
+pub async fn relay_status(state: State<'_, AppState>) -> Result<RelayStatus, String> {
+    Ok(state.api.state().relay_status.read().await.clone())
+}
+
+/// Enable or disable remote access through a relay server. `tls` replaces
+/// the client certificate settings when given; an empty `pkcs12Password`
+/// removes the stored one.
+#[tauri::command]
+pub async fn relay_configure(
+    state: State<'_, AppState>,
+    enabled: bool,
+    url: String,
+    tls: Option<RelayTlsConfig>,
+    pkcs12_password: Option<String>,
+) -> Result<RelayStatus, String> {
+    let url = url.trim().to_string();
+    if enabled && !(url.starts_with("ws://") || url.starts_with("wss://")) {
+        return Err("Relay URL must start with ws:// or wss://".to_string());
+    }
+    if let Some(password) = pkcs12_password {
+        let password = Some(password).filter(|p| !p.is_empty());
+        api::relay::save_pkcs12_password(password.as_deref())?;
     }
 
-    // Generate share key
-    let mut share_key = state.share_key.write().await;
-    if share_key.is_none() {
-        *share_key = Some(generate_share_key());
+    let shared = state.api.state();
+    {
+        let mut config = shared.config.write().await;
+        if let Some(tls) = tls {
+            if tls.has_client_cert() && url.starts_with("ws://") {
+                return Err("A client certificate needs a wss:// relay URL".to_string());
+            }
+            // Refuse certificates that can't be loaded rather than failing on every reconnect
+            api::relay::tls_connector(&tls)?;
+            config.relay.tls = tls;
+        }
+        config.relay.enabled = enabled;
+        config.relay.url = url.clone();
+        config.save()?;
     }
 
-    *state.node_running.write().await = true;
+    if enabled && *state.node_running.read().await {
+        state.relay.start(&url).await;
+    } else {
+        state.relay.stop().await;
+    }
 
-    Ok(CommandResult::ok())
+    let status = shared.relay_status.read().await.clone();
+    Ok(status)
+}
+
+/// Set or clear the orchestrator key relayed jobs must be signed with
+#[tauri::command]
+pub async fn set_orchestrator_key(state: State<'_, AppState>, public_key: Option<String>) -> Result<(), String> {
+    let public_key = public_key.map(|k| k.trim().to_lowercase()).filter(|k| !k.is_empty());
+    if public_key.as_deref().is_some_and(|k| !crate::services::identity::is_public_key(k)) {
+        return Err("Orchestrator key must be a hex-encoded Ed25519 public key".to_string());
+    }
+    let shared = state.api.state();
+    let mut config = shared.config.write().await;
+    config.orchestrator_public_key = public_key;
+    config.save()
 }
 
 #[tauri::command]
 pub async fn stop_node(state: State<'_, AppState>) -> Result<CommandResult, String> {
-    *state.node_running.write().await = false;
+    state.stop_node().await;
     Ok(CommandResult::ok())
 }
 
 // Ollama commands
 #[tauri::command]
-pub async fn ollama_status(state: State<'_, AppState>) -> Result<OllamaStatus, String> {
+pub async fn ollama_status(state: State<'_, AppState>) -> Result<OllamaStatus, NodeError> {
     Ok(state.ollama.get_status().await)
 }
 
 #[tauri::command]
-pub async fn ollama_start(state: State<'_, AppState>) -> Result<CommandResult, String> {
-    state.ollama.start().await.map(|_| CommandResult::ok())
-        .map_err(|e| e)
+pub async fn ollama_start(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<CommandResult, NodeError> {
+    state.ollama.start().await?;
+    spawn_model_bootstrap(app, state.inner().clone());
+    Ok(CommandResult::ok())
 }
 
 #[tauri::command]
-pub async fn ollama_stop(state: State<'_, AppState>) -> Result<CommandResult, String> {
+pub async fn ollama_stop(state: State<'_, AppState>) -> Result<CommandResult, NodeError> {
     state.ollama.stop().await.map(|_| CommandResult::ok())
-        .map_err(|e| e)
 }
 
 #[tauri::command]
-pub async fn ollama_models(state: State<'_, AppState>) -> Result<Vec<OllamaModel>, String> {
-    state.ollama.list_models().await
+pub async fn ollama_models(
+    state: State<'_, AppState>,
+    query: Option<ListQuery>,
+) -> Result<Page<OllamaModel>, NodeError> {
+    let models = state.ollama.list_models().await?;
+    Ok(query.unwrap_or_default().apply(models))
 }
 
 #[tauri::command]
 pub async fn ollama_pull_model(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
     name: String,
-) -> Result<CommandResult, String> {
-    state.ollama.pull_model(&name, None).await
+) -> Result<CommandResult, NodeError> {
+    state.ollama.pull_model(&name, Some(forward_pull_progress(app))).await
         .map(|_| CommandResult::ok())
-        .map_err(|e| e)
+}
+
+/// Forward pull progress to the frontend as `ollama://pull-progress` events
+fn forward_pull_progress(app: tauri::AppHandle) -> tokio::sync::mpsc::Sender<PullProgress> {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<PullProgress>(32);
+    tauri::async_runtime::spawn(async move {
+        while let Some(progress) = rx.recv().await {
+            let _ = app.emit("ollama://pull-progress", progress);
+        }
+    });
+    tx
+}
+
+/// Pull the recommended model in the background when bootstrapping is
+/// enabled and Ollama has no models yet
+pub fn spawn_model_bootstrap(app: tauri::AppHandle, state: AppState) {
+    tauri::async_runtime::spawn(async move {
+        if !state.api.state().config.read().await.ollama.bootstrap_model {
+            return;
+        }
+
+        let hardware = HardwareDetector::detect();
+        match state.ollama.bootstrap_model(&hardware, Some(forward_pull_progress(app.clone()))).await {
+            Ok(Some(model)) => {
+                log::info!("Bootstrapped Ollama model {}", model);
+                let _ = app.emit("ollama://model-bootstrapped", model);
+            }
+            Ok(None) => {}
+            Err(e) => log::error!("Failed to bootstrap Ollama model: {}", e),
+        }
+    });
+}
+
+#[tauri::command]
+pub fn ollama_get_config(state: State<'_, AppState>) -> OllamaConfig {
+    state.ollama.config()
+}
+
+/// Save Ollama settings. Returns whether the running server must be
+/// restarted for them to take effect.
+#[tauri::command]
+pub async fn ollama_set_config(state: State<'_, AppState>, config: OllamaConfig) -> Result<bool, NodeError> {
+    if config.port == 0 {
+        return Err(NodeError::Invalid("Invalid Ollama port".to_string()));
+    }
+    if let Some(dir) = &config.models_dir {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| NodeError::Invalid(format!("Cannot use models directory {}: {}", dir, e)))?;
+    }
+
+    {
+        let shared = state.api.state();
+        let mut node_config = shared.config.write().await;
+        node_config.ollama = config.clone();
+        node_config.save()?;
+    }
+
+    Ok(state.ollama.set_config(config))
+}
+
+#[tauri::command]
+pub fn ollama_recommended_model() -> String {
+    OllamaManager::recommended_model(&HardwareDetector::detect()).to_string()
+}
+
+/// Turn the first-start model bootstrap on or off
+#[tauri::command]
+pub async fn ollama_set_bootstrap(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<CommandResult, NodeError> {
+    {
+        let shared = state.api.state();
+        let mut config = shared.config.write().await;
+        config.ollama.bootstrap_model = enabled;
+        config.save()?;
+        state.ollama.set_config(config.ollama.clone());
+    }
+
+    if enabled && state.ollama.is_running() {
+        spawn_model_bootstrap(app, state.inner().clone());
+    }
+    Ok(CommandResult::ok())
 }
 
 #[tauri::command]
 pub async fn ollama_delete_model(
     state: State<'_, AppState>,
     name: String,
-) -> Result<CommandResult, String> {
+) -> Result<CommandResult, NodeError> {
     state.ollama.delete_model(&name).await
         .map(|_| CommandResult::ok())
-        .map_err(|e| e)
 }
 
 #[tauri::command]
 pub fn ollama_set_path(state: State<'_, AppState>, path: String) -> CommandResult {
     if state.ollama.set_path(std::path::PathBuf::from(&path)) {
-        CommandResult::ok()
+        // Kept for the next launch
+        match state.api.state().store.settings().set(state_store::OLLAMA_PATH, &path) {
+            Ok(()) => CommandResult::ok(),
+            Err(e) => CommandResult::err(e),
+        }
     } else {
         CommandResult::err("Invalid path - file not found")
     }
 }
 
+/// Download and verify an Ollama release and use it as the managed binary
+#[tauri::command]
+pub async fn ollama_install(state: State<'_, AppState>, version: Option<String>) -> Result<String, NodeError> {
+    state.ollama.install(version.as_deref()).await
+}
+
+/// Upgrade the managed Ollama; `None` when it is already the latest release
+#[tauri::command]
+pub async fn ollama_upgrade(state: State<'_, AppState>) -> Result<Option<String>, NodeError> {
+    state.ollama.upgrade().await
+}
+
 #[tauri::command]
 pub fn ollama_get_path(state: State<'_, AppState>) -> String {
     state.ollama.get_ollama_path().to_string_lossy().to_string()
@@ -155,40 +503,301 @@ pub fn ollama_get_path(state: State<'_, AppState>) -> String {
 
 // IPFS commands
 #[tauri::command]
-pub async fn ipfs_status(state: State<'_, AppState>) -> Result<IpfsStatus, String> {
+pub async fn ipfs_status(state: State<'_, AppState>) -> Result<IpfsStatus, NodeError> {
     Ok(state.ipfs.get_status().await)
 }
 
 #[tauri::command]
-pub async fn ipfs_start(state: State<'_, AppState>) -> Result<CommandResult, String> {
-    state.ipfs.start().await.map(|_| CommandResult::ok())
-        .map_err(|e| e)
+pub async fn ipfs_start(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<CommandResult, NodeError> {
+    state.api.state().bandwidth.check(Subsystem::Ipfs)?;
+    state.ipfs.start_with_progress(Some(forward_ipfs_progress(app))).await.map(|_| CommandResult::ok())
 }
 
 #[tauri::command]
-pub async fn ipfs_stop(state: State<'_, AppState>) -> Result<CommandResult, String> {
+pub async fn ipfs_stop(state: State<'_, AppState>) -> Result<CommandResult, NodeError> {
     state.ipfs.stop().await.map(|_| CommandResult::ok())
-        .map_err(|e| e)
 }
 
 #[tauri::command]
 pub async fn ipfs_add_content(
     state: State<'_, AppState>,
     content: String,
-) -> Result<String, String> {
+) -> Result<String, NodeError> {
     state.ipfs.add_content(&content).await
 }
 
+/// Add a file or directory picked with the file dialog, emitting
+/// `ipfs://progress` events while it is hashed
+#[tauri::command]
+pub async fn ipfs_add_path(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    recursive: Option<bool>,
+    wrap_with_directory: Option<bool>,
+) -> Result<String, NodeError> {
+    state.ipfs
+        .add_path(
+            std::path::Path::new(&path),
+            recursive.unwrap_or(true),
+            wrap_with_directory.unwrap_or(false),
+            Some(forward_ipfs_progress(app)),
+        )
+        .await
+}
+
+/// Forward IPFS add/pin progress to the frontend as `ipfs://progress` events
+fn forward_ipfs_progress(app: tauri::AppHandle) -> tokio::sync::mpsc::Sender<IpfsProgress> {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<IpfsProgress>(32);
+    tauri::async_runtime::spawn(async move {
+        while let Some(progress) = rx.recv().await {
+            let _ = app.emit("ipfs://progress", progress);
+        }
+    });
+    tx
+}
+
+#[tauri::command]
+pub async fn ipfs_cat(state: State<'_, AppState>, cid: String) -> Result<String, NodeError> {
+    state.ipfs.cat(&cid).await
+}
+
+/// Save content into `dest` (under its CID); returns the written path
+#[tauri::command]
+pub async fn ipfs_get(
+    state: State<'_, AppState>,
+    cid: String,
+    dest: String,
+    max_bytes: Option<u64>,
+) -> Result<String, NodeError> {
+    state.ipfs
+        .get(&cid, std::path::Path::new(&dest), max_bytes.unwrap_or(MAX_GET_SIZE))
+        .await
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+pub async fn ipfs_list_pins(
+    state: State<'_, AppState>,
+    pin_type: Option<String>,
+    query: Option<ListQuery>,
+) -> Result<Page<PinInfo>, NodeError> {
+    state.ipfs.list_pins(pin_type.as_deref(), &query.unwrap_or_default()).await
+}
+
+#[tauri::command]
+pub async fn ipfs_gc(state: State<'_, AppState>) -> Result<GcResult, NodeError> {
+    state.ipfs.gc().await
+}
+
+#[tauri::command]
+pub fn ipfs_get_config(state: State<'_, AppState>) -> IpfsConfig {
+    state.ipfs.config()
+}
+
+/// Save IPFS settings and apply a lowered repo budget right away. Returns
+/// whether the running daemon must be restarted for new ports to apply.
+#[tauri::command]
+pub async fn ipfs_set_config(state: State<'_, AppState>, mut config: IpfsConfig) -> Result<bool, NodeError> {
+    if config.api_port == 0 || config.gateway_port == 0 || config.api_port == config.gateway_port {
+        return Err(NodeError::Invalid("Invalid IPFS ports".to_string()));
+    }
+    if config.api_port == DEFAULT_API_PORT || config.gateway_port == DEFAULT_API_PORT {
+        return Err(NodeError::Conflict(format!("Port {} is used by the node API", DEFAULT_API_PORT)));
+    }
+    if let (Some(low), Some(high)) = (config.limits.conn_low_water, config.limits.conn_high_water) {
+        if low >= high {
+            return Err(NodeError::Invalid("Connection low water must be below high water".to_string()));
+        }
+    }
+    if config.pubsub.announce_topic.trim().is_empty() || config.pubsub.offer_topic.trim().is_empty() {
+        return Err(NodeError::Invalid("Pubsub topics must not be empty".to_string()));
+    }
+    config.swarm_key = match config.swarm_key.as_deref().map(str::trim) {
+        Some(key) if !key.is_empty() => Some(parse_swarm_key(key).map_err(NodeError::Invalid)?),
+        _ => None,
+    };
+
+    let needs_restart = state.ipfs.set_config(config);
+    {
+        let shared = state.api.state();
+        let mut node_config = shared.config.write().await;
+        node_config.ipfs = state.ipfs.config();
+        node_config.save()?;
+    }
+
+    if state.ipfs.is_running() {
+        let ipfs = Arc::clone(&state.ipfs);
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = ipfs.enforce_repo_budget().await {
+                log::warn!("IPFS repo budget check failed: {}", e);
+            }
+        });
+    }
+    Ok(needs_restart)
+}
+
+#[tauri::command]
+pub async fn ipfs_version_info(state: State<'_, AppState>) -> Result<KuboVersionInfo, NodeError> {
+    Ok(state.ipfs.version_info().await)
+}
+
+/// Upgrade the app-installed Kubo (to the newest supported release when no
+/// version is given), migrating the repo; returns the installed version
+#[tauri::command]
+pub async fn ipfs_upgrade(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    version: Option<String>,
+) -> Result<String, NodeError> {
+    Ok(state.ipfs.upgrade(version.as_deref(), Some(forward_ipfs_progress(app))).await?)
+}
+
+/// New random key for setting up a private network; share it with the
+/// other nodes out of band
+#[tauri::command]
+pub fn ipfs_generate_swarm_key() -> String {
+    generate_swarm_key()
+}
+
+/// Move the IPFS repo, e.g. to a drive picked from `get_drives`
+#[tauri::command]
+pub async fn ipfs_relocate_repo(state: State<'_, AppState>, path: String) -> Result<String, NodeError> {
+    let dest = std::path::PathBuf::from(&path);
+    if !dest.is_absolute() {
+        return Err(NodeError::Invalid("Repo path must be absolute".to_string()));
+    }
+
+    let moved = state.ipfs.relocate_repo(&dest).await?;
+    {
+        let shared = state.api.state();
+        let mut node_config = shared.config.write().await;
+        node_config.ipfs = state.ipfs.config();
+        node_config.save()?;
+    }
+    Ok(moved.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+pub async fn ipns_publish(
+    state: State<'_, AppState>,
+    cid: String,
+    key: Option<String>,
+) -> Result<IpnsRecord, NodeError> {
+    state.ipfs.ipns_publish(&cid, key.as_deref()).await
+}
+
+#[tauri::command]
+pub async fn ipns_resolve(state: State<'_, AppState>, name: String) -> Result<String, NodeError> {
+    state.ipfs.ipns_resolve(&name).await
+}
+
+#[tauri::command]
+pub async fn ipfs_key_list(state: State<'_, AppState>, query: Option<ListQuery>) -> Result<Page<IpnsKey>, NodeError> {
+    Ok(query.unwrap_or_default().apply(state.ipfs.key_list().await?))
+}
+
+#[tauri::command]
+pub async fn ipfs_key_gen(state: State<'_, AppState>, name: String) -> Result<IpnsKey, NodeError> {
+    state.ipfs.key_gen(&name).await
+}
+
 #[tauri::command]
-pub async fn ipfs_pin(state: State<'_, AppState>, cid: String) -> Result<CommandResult, String> {
-    state.ipfs.pin(&cid).await.map(|_| CommandResult::ok())
-        .map_err(|e| e)
+pub async fn ipfs_pin(app: tauri::AppHandle, state: State<'_, AppState>, cid: String) -> Result<CommandResult, NodeError> {
+    state.ipfs.pin_with_progress(&cid, Some(forward_ipfs_progress(app))).await.map(|_| CommandResult::ok())
 }
 
 #[tauri::command]
-pub async fn ipfs_unpin(state: State<'_, AppState>, cid: String) -> Result<CommandResult, String> {
+pub async fn ipfs_unpin(state: State<'_, AppState>, cid: String) -> Result<CommandResult, NodeError> {
     state.ipfs.unpin(&cid).await.map(|_| CommandResult::ok())
-        .map_err(|e| e)
+}
+
+/// Other nodes announcing themselves on IPFS pubsub
+#[tauri::command]
+pub async fn ipfs_pubsub_nodes(state: State<'_, AppState>) -> Result<Vec<AnnouncedNode>, NodeError> {
+    Ok(state.api.state().pubsub.nodes().await)
+}
+
+/// Jobs the orchestrator offered on IPFS pubsub that have not expired
+#[tauri::command]
+pub async fn ipfs_pubsub_offers(state: State<'_, AppState>) -> Result<Vec<JobOffer>, NodeError> {
+    Ok(state.api.state().pubsub.offers().await)
+}
+
+#[tauri::command]
+pub async fn storage_status(state: State<'_, AppState>) -> Result<StorageStatus, NodeError> {
+    state.api.state().storage.status().await
+}
+
+/// Save the storage contribution settings, moving the IPFS repo onto the
+/// chosen drive when needed
+#[tauri::command]
+pub async fn storage_set_config(state: State<'_, AppState>, config: StorageConfig) -> Result<StorageConfig, NodeError> {
+    state.api.state().set_storage_config(config).await
+}
+
+#[tauri::command]
+pub async fn storage_pins(state: State<'_, AppState>, query: Option<ListQuery>) -> Result<Page<StoragePin>, NodeError> {
+    state.api.state().storage.list(&query.unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn storage_release(state: State<'_, AppState>, cid: String) -> Result<(), NodeError> {
+    state.api.state().storage.release(&cid).await
+}
+
+/// Which desktop notifications are shown
+#[tauri::command]
+pub fn notification_settings(state: State<'_, AppState>) -> NotificationSettings {
+    notifications::load(&state.api.state().store.settings())
+}
+
+#[tauri::command]
+pub fn notification_set_settings(
+    state: State<'_, AppState>,
+    settings: NotificationSettings,
+) -> Result<NotificationSettings, NodeError> {
+    notifications::save(&state.api.state().store.settings(), &settings)?;
+    Ok(settings)
+}
+
+/// Advertised rates, and where dynamic pricing is taking them
+#[tauri::command]
+pub async fn pricing_status(state: State<'_, AppState>) -> Result<PricingStatus, NodeError> {
+    Ok(state.api.state().pricing.status().await)
+}
+
+#[tauri::command]
+pub async fn pricing_set_config(state: State<'_, AppState>, config: PricingConfig) -> Result<PricingStatus, NodeError> {
+    state.api.state().set_pricing_config(config).await
+}
+
+/// Bytes moved per subsystem this month, against the caps
+#[tauri::command]
+pub fn bandwidth_usage(state: State<'_, AppState>) -> BandwidthReport {
+    state.api.state().bandwidth.report()
+}
+
+#[tauri::command]
+pub async fn bandwidth_set_config(state: State<'_, AppState>, config: BandwidthConfig) -> Result<BandwidthReport, NodeError> {
+    state.api.state().set_bandwidth_config(config).await
+}
+
+#[tauri::command]
+pub fn ipfs_cluster_status(state: State<'_, AppState>) -> ClusterStatus {
+    state.cluster.get_status()
+}
+
+/// Follow the cluster whose follower configuration is at `config_cid`,
+/// or stop following when `enabled` is false
+#[tauri::command]
+pub async fn ipfs_cluster_follow(
+    state: State<'_, AppState>,
+    enabled: bool,
+    config_cid: Option<String>,
+    name: Option<String>,
+) -> Result<ClusterStatus, String> {
+    state.api.state().configure_cluster(enabled, config_cid, name).await
 }
 
 // Window commands
@@ -234,16 +843,32 @@ pub async fn container_detect_runtime(state: State<'_, AppState>) -> Result<Runt
         .map_err(|e| e.to_string())
 }
 
+/// Switch container backend (`docker`, `podman`, `native`, or null for
+/// automatic) and remember the choice
 #[tauri::command]
-pub async fn container_list(state: State<'_, AppState>, all: bool) -> Result<Vec<ContainerInfo>, String> {
-    state.containers.list_containers(all).await
-        .map_err(|e| e.to_string())
+pub async fn container_set_runtime(state: State<'_, AppState>, runtime: Option<RuntimeType>) -> Result<RuntimeInfo, String> {
+    state.api.state().set_container_runtime(runtime).await
 }
 
 #[tauri::command]
-pub async fn container_list_images(state: State<'_, AppState>) -> Result<Vec<crate::services::container::ImageInfo>, String> {
-    state.containers.list_images().await
-        .map_err(|e| e.to_string())
+pub async fn container_list(
+    state: State<'_, AppState>,
+    all: bool,
+    query: Option<ListQuery>,
+) -> Result<Page<ContainerInfo>, String> {
+    let containers = state.containers.list_containers(all).await
+        .map_err(|e| e.to_string())?;
+    Ok(query.unwrap_or_default().apply(containers))
+}
+
+#[tauri::command]
+pub async fn container_list_images(
+    state: State<'_, AppState>,
+    query: Option<ListQuery>,
+) -> Result<Page<crate::services::container::ImageInfo>, String> {
+    let images = state.containers.list_images().await
+        .map_err(|e| e.to_string())?;
+    Ok(query.unwrap_or_default().apply(images))
 }
 
 #[tauri::command]
@@ -253,6 +878,109 @@ pub async fn container_pull_image(state: State<'_, AppState>, image: String) ->
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn container_push_image(state: State<'_, AppState>, image: String) -> Result<CommandResult, String> {
+    state.containers.push_image(&image).await
+        .map(|_| CommandResult::ok())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn container_list_registries() -> Vec<RegistryInfo> {
+    crate::services::registry_auth::list()
+}
+
+/// Save a registry login; the password or token goes to the OS keychain
+#[tauri::command]
+pub fn container_save_registry(credential: RegistryCredential) -> Result<RegistryInfo, String> {
+    crate::services::registry_auth::save(credential)
+}
+
+#[tauri::command]
+pub fn container_remove_registry(registry: String) -> Result<CommandResult, String> {
+    crate::services::registry_auth::remove(&registry)
+        .map(|_| CommandResult::ok())
+}
+
+/// Build an image, forwarding build output to the frontend as
+/// `container://build-output` events
+#[tauri::command]
+pub async fn container_build_image(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    request: BuildImageRequest,
+) -> Result<String, String> {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(64);
+    let tag = request.tag.clone();
+    tauri::async_runtime::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            let _ = app.emit("container://build-output", serde_json::json!({ "tag": tag, "line": line }));
+        }
+    });
+
+    state.containers.build_image(request, Some(tx)).await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn container_disk_usage(state: State<'_, AppState>) -> Result<DiskUsage, String> {
+    state.containers.disk_usage().await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn container_prune(state: State<'_, AppState>, request: PruneRequest) -> Result<PruneResult, String> {
+    state.containers.prune(request).await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn container_list_networks(
+    state: State<'_, AppState>,
+    query: Option<ListQuery>,
+) -> Result<Page<NetworkInfo>, String> {
+    let networks = state.containers.list_networks().await
+        .map_err(|e| e.to_string())?;
+    Ok(query.unwrap_or_default().apply(networks))
+}
+
+#[tauri::command]
+pub async fn container_create_network(
+    state: State<'_, AppState>,
+    name: String,
+    internal: Option<bool>,
+    labels: Option<HashMap<String, String>>,
+) -> Result<String, String> {
+    state.containers.create_network(&name, internal.unwrap_or(false), labels.unwrap_or_default()).await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn container_remove_network(state: State<'_, AppState>, name: String) -> Result<CommandResult, String> {
+    state.containers.remove_network(&name).await
+        .map(|_| CommandResult::ok())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn container_connect_network(state: State<'_, AppState>, network: String, container_id: String) -> Result<CommandResult, String> {
+    state.containers.connect_network(&network, &container_id).await
+        .map(|_| CommandResult::ok())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn container_disconnect_network(
+    state: State<'_, AppState>,
+    network: String,
+    container_id: String,
+    force: Option<bool>,
+) -> Result<CommandResult, String> {
+    state.containers.disconnect_network(&network, &container_id, force.unwrap_or(false)).await
+        .map(|_| CommandResult::ok())
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn container_create(state: State<'_, AppState>, request: CreateContainerRequest) -> Result<String, String> {
     state.containers.create_container(request).await
@@ -273,6 +1001,31 @@ pub async fn container_stop(state: State<'_, AppState>, container_id: String, ti
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn container_restart(state: State<'_, AppState>, container_id: String, timeout: Option<i64>) -> Result<CommandResult, String> {
+    state.containers.restart_container(&container_id, timeout).await
+        .map(|_| CommandResult::ok())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn container_rename(state: State<'_, AppState>, container_id: String, name: String) -> Result<CommandResult, String> {
+    state.containers.rename_container(&container_id, &name).await
+        .map(|_| CommandResult::ok())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn container_update(
+    state: State<'_, AppState>,
+    container_id: String,
+    request: UpdateContainerRequest,
+) -> Result<CommandResult, String> {
+    state.containers.update_container(&container_id, request).await
+        .map(|_| CommandResult::ok())
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn container_remove(state: State<'_, AppState>, container_id: String, force: bool) -> Result<CommandResult, String> {
     state.containers.remove_container(&container_id, force).await
@@ -286,8 +1039,50 @@ pub async fn container_logs(state: State<'_, AppState>, container_id: String, ta
         .map_err(|e| e.to_string())
 }
 
+/// Tail a container's output, emitting each line as a
+/// `container://logs/<id>` event until the container stops or
+/// `container_unfollow_logs` is called
 #[tauri::command]
-pub async fn container_exec(state: State<'_, AppState>, container_id: String, cmd: Vec<String>) -> Result<ExecResult, String> {
+pub fn container_follow_logs(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    container_id: String,
+    tail: Option<usize>,
+) -> CommandResult {
+    let mut followers = state.log_followers.lock().unwrap();
+    if let Some(previous) = followers.remove(&container_id) {
+        previous.abort();
+    }
+
+    let containers = Arc::clone(&state.containers);
+    let id = container_id.clone();
+    let task = tauri::async_runtime::spawn(async move {
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<LogLine>(256);
+        let event = format!("container://logs/{}", id);
+        let forward = async {
+            while let Some(line) = rx.recv().await {
+                let _ = app.emit(&event, line);
+            }
+        };
+        let (result, _) = tokio::join!(containers.follow_logs(&id, tail, tx), forward);
+        if let Err(e) = result {
+            log::warn!("Log stream for container {} ended: {}", id, e);
+        }
+    });
+    followers.insert(container_id, task);
+    CommandResult::ok()
+}
+
+#[tauri::command]
+pub fn container_unfollow_logs(state: State<'_, AppState>, container_id: String) -> CommandResult {
+    if let Some(task) = state.log_followers.lock().unwrap().remove(&container_id) {
+        task.abort();
+    }
+    CommandResult::ok()
+}
+
+#[tauri::command]
+pub async fn container_exec(state: State<'_, AppState>, container_id: String, cmd: Vec<String>) -> Result<ExecOutput, String> {
     state.containers.exec_in_container(&container_id, cmd).await
         .map_err(|e| e.to_string())
 }
@@ -298,26 +1093,125 @@ pub async fn container_inspect(state: State<'_, AppState>, container_id: String)
         .map_err(|e| e.to_string())
 }
 
-// Helper function
-fn generate_share_key() -> String {
-    use std::collections::hash_map::RandomState;
-    use std::hash::{BuildHasher, Hasher};
+// App deployment commands
+#[tauri::command]
+pub async fn app_list(state: State<'_, AppState>, query: Option<ListQuery>) -> Result<Page<AppStatus>, String> {
+    let apps = state.apps.list().await?;
+    Ok(query.unwrap_or_default().apply(apps))
+}
 
-    let s = RandomState::new();
-    let mut hasher = s.build_hasher();
-    hasher.write_u64(std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_nanos() as u64);
+#[tauri::command]
+pub async fn app_deploy(state: State<'_, AppState>, spec: AppSpec) -> Result<AppStatus, String> {
+    state.apps.deploy(spec).await
+}
 
-    let chars: Vec<char> = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789".chars().collect();
-    let hash = hasher.finish();
-    let mut key = String::new();
+#[tauri::command]
+pub async fn app_status(state: State<'_, AppState>, name: String) -> Result<AppStatus, String> {
+    state.apps.status(&name).await
+}
 
-    for i in 0..8 {
-        let idx = ((hash >> (i * 5)) & 0x1F) as usize % chars.len();
-        key.push(chars[idx]);
-    }
+#[tauri::command]
+pub async fn app_teardown(state: State<'_, AppState>, name: String) -> Result<CommandResult, String> {
+    state.apps.teardown(&name).await
+        .map(|_| CommandResult::ok())
+}
 
-    key
+// Workspace commands
+#[tauri::command]
+pub async fn workspace_list(state: State<'_, AppState>, query: Option<ListQuery>) -> Result<Page<Workspace>, NodeError> {
+    Ok(query.unwrap_or_default().apply(state.workspaces.list().await))
+}
+
+#[tauri::command]
+pub async fn workspace_get(state: State<'_, AppState>, workspace_id: String) -> Result<Workspace, NodeError> {
+    state.workspaces.get(&workspace_id).await
+        .ok_or_else(|| NodeError::NotFound("Workspace not found".to_string()))
+}
+
+#[tauri::command]
+pub async fn workspace_create(state: State<'_, AppState>, request: CreateWorkspaceRequest) -> Result<Workspace, NodeError> {
+    state.workspaces.create(request).await
+}
+
+#[tauri::command]
+pub async fn workspace_update(
+    state: State<'_, AppState>,
+    workspace_id: String,
+    request: UpdateWorkspaceRequest,
+) -> Result<Workspace, NodeError> {
+    state.workspaces.update(&workspace_id, request).await
+}
+
+#[tauri::command]
+pub async fn workspace_delete(state: State<'_, AppState>, workspace_id: String) -> Result<CommandResult, NodeError> {
+    state.workspaces.delete(&workspace_id).await
+        .map(|_| CommandResult::ok())
+}
+
+// Agent commands
+#[tauri::command]
+pub fn agent_templates() -> Vec<AgentTemplate> {
+    crate::services::agent_templates::TEMPLATES.to_vec()
+}
+
+#[tauri::command]
+pub async fn agent_create(
+    state: State<'_, AppState>,
+    workspace_id: String,
+    request: CreateAgentRequest,
+) -> Result<AgentExecution, NodeError> {
+    state.agents.create_execution(&workspace_id, request).await
+}
+
+#[tauri::command]
+pub async fn agent_list(
+    state: State<'_, AppState>,
+    workspace_id: String,
+    query: Option<ExecutionQuery>,
+    page: Option<ListQuery>,
+) -> Result<Page<AgentExecution>, NodeError> {
+    state.agents
+        .list_executions(&workspace_id, &query.unwrap_or_default(), &page.unwrap_or_default())
+        .await
+}
+
+/// Jobs relayed from the orchestrator, newest first unless sorted otherwise
+#[tauri::command]
+pub async fn job_list(state: State<'_, AppState>, query: Option<ListQuery>) -> Result<Page<JobRecord>, NodeError> {
+    state.api.state().store.jobs().list(&query.unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn agent_get(state: State<'_, AppState>, execution_id: String) -> Result<AgentExecution, NodeError> {
+    state.agents.get_execution(&execution_id).await
+        .ok_or_else(|| NodeError::NotFound("Execution not found".to_string()))
+}
+
+#[tauri::command]
+pub async fn agent_cancel(state: State<'_, AppState>, execution_id: String) -> Result<CommandResult, NodeError> {
+    state.agents.cancel_execution(&execution_id).await
+        .map(|_| CommandResult::ok())
+}
+
+// LLM provider commands
+#[tauri::command]
+pub async fn provider_list(state: State<'_, AppState>, query: Option<ListQuery>) -> Result<Page<ProviderInfo>, NodeError> {
+    Ok(query.unwrap_or_default().apply(state.providers.list().await))
+}
+
+#[tauri::command]
+pub async fn provider_save(state: State<'_, AppState>, config: ProviderConfig) -> Result<ProviderInfo, NodeError> {
+    state.providers.save(config).await
+}
+
+#[tauri::command]
+pub async fn provider_remove(state: State<'_, AppState>, id: String) -> Result<CommandResult, NodeError> {
+    state.providers.remove(&id).await
+        .map(|_| CommandResult::ok())
+}
+
+/// Health-check every backend and list the models each serves
+#[tauri::command]
+pub async fn provider_health(state: State<'_, AppState>) -> Result<Vec<BackendStatus>, NodeError> {
+    Ok(state.providers.check_health().await)
 }
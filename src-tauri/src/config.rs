@@ -0,0 +1,800 @@
+//! Node Configuration
+//!
+//! User-editable node settings, persisted as `config.json` under the
+//! config dir. Missing fields fall back to their defaults so older files
+//! keep loading as settings are added.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::logging::LogFormat;
+use crate::models::ResourceLimits;
+use crate::services::redact::Secret;
+use crate::services::{CustomGpuProviderConfig, ImagePolicy, RuntimeType};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeConfig {
+    /// Launch the node at login
+    #[serde(default)]
+    pub autostart: bool,
+    /// Log line layout; `--log-format` overrides it
+    #[serde(default)]
+    pub log_format: LogFormat,
+    #[serde(default)]
+    pub relay: RelayConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub ollama: OllamaConfig,
+    #[serde(default)]
+    pub ipfs: IpfsConfig,
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+    #[serde(default)]
+    pub container: ContainerConfig,
+    #[serde(default)]
+    pub update: UpdateConfig,
+    #[serde(default)]
+    pub gpu: GpuConfig,
+    #[serde(default)]
+    pub payments: PaymentsConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub discovery: DiscoveryConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub bandwidth: BandwidthConfig,
+    #[serde(default)]
+    pub pricing: PricingConfig,
+    #[serde(default)]
+    pub agent: AgentConfig,
+    /// Hex Ed25519 key of the orchestrator. When set, relayed requests
+    /// that change anything must be signed with it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orchestrator_public_key: Option<String>,
+}
+
+/// Finding other nodes on the local network
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveryConfig {
+    /// Announce this node over mDNS while it runs, and browse for others
+    #[serde(default = "default_discovery_enabled")]
+    pub enabled: bool,
+    /// Let container jobs from paired nodes use this machine's GPUs
+    #[serde(default)]
+    pub share_gpus: bool,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_discovery_enabled(),
+            share_gpus: false,
+        }
+    }
+}
+
+fn default_discovery_enabled() -> bool {
+    true
+}
+
+/// Rates the node advertises to the orchestrator for its work
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricingConfig {
+    #[serde(default = "default_pricing_currency")]
+    pub currency: String,
+    #[serde(default)]
+    pub cpu_core_hour_cents: f64,
+    #[serde(default)]
+    pub memory_gb_hour_cents: f64,
+    #[serde(default)]
+    pub gpu_hour_cents: f64,
+    /// Inference, per thousand tokens
+    #[serde(default)]
+    pub inference_1k_tokens_cents: f64,
+    #[serde(default)]
+    pub dynamic: DynamicPricing,
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        Self {
+            currency: default_pricing_currency(),
+            cpu_core_hour_cents: 0.0,
+            memory_gb_hour_cents: 0.0,
+            gpu_hour_cents: 0.0,
+            inference_1k_tokens_cents: 0.0,
+            dynamic: DynamicPricing::default(),
+        }
+    }
+}
+
+fn default_pricing_currency() -> String {
+    "USD".to_string()
+}
+
+/// Moving the rates with demand: every interval the rates step toward a
+/// target between the floor and ceiling set by how busy the network
+/// (the orchestrator's demand signal) and this machine are
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DynamicPricing {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Lowest rates, as a percent of the configured ones
+    #[serde(default = "default_pricing_floor_percent")]
+    pub floor_percent: f64,
+    /// Highest rates, as a percent of the configured ones
+    #[serde(default = "default_pricing_ceiling_percent")]
+    pub ceiling_percent: f64,
+    /// Most the rates move in one step, in percentage points
+    #[serde(default = "default_pricing_max_step_percent")]
+    pub max_step_percent: f64,
+    #[serde(default = "default_pricing_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for DynamicPricing {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            floor_percent: default_pricing_floor_percent(),
+            ceiling_percent: default_pricing_ceiling_percent(),
+            max_step_percent: default_pricing_max_step_percent(),
+            interval_secs: default_pricing_interval_secs(),
+        }
+    }
+}
+
+fn default_pricing_floor_percent() -> f64 {
+    50.0
+}
+
+fn default_pricing_ceiling_percent() -> f64 {
+    150.0
+}
+
+fn default_pricing_max_step_percent() -> f64 {
+    10.0
+}
+
+fn default_pricing_interval_secs() -> u64 {
+    300
+}
+
+/// Monthly transfer caps in GB, for contributors on metered connections.
+/// A subsystem over its cap, or every one once `totalGb` is reached, stops
+/// transferring until the month (UTC) turns. Unset caps don't apply.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_gb: Option<f64>,
+    /// IPFS daemon traffic, both ways
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipfs_gb: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_pulls_gb: Option<f64>,
+    /// Images pushed to registries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uploads_gb: Option<f64>,
+    /// The orchestrator connection through the relay
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relay_gb: Option<f64>,
+}
+
+/// Disk space contributed to the network: data the orchestrator asks
+/// the node to pin, paid per GB-hour held
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageConfig {
+    /// Accept pin requests
+    #[serde(default)]
+    pub enabled: bool,
+    /// Disk space offered, in GB
+    #[serde(default = "default_storage_budget_gb")]
+    pub budget_gb: u64,
+    /// Mount point of the drive to keep the data on; the IPFS repo is
+    /// moved there when it lives elsewhere
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drive: Option<String>,
+    /// Rate earned per GB held for an hour
+    #[serde(default)]
+    pub storage_gb_hour_cents: f64,
+    #[serde(default = "default_storage_currency")]
+    pub currency: String,
+    /// How often pins are checked for possession and storage-hours recorded
+    #[serde(default = "default_storage_proof_interval_secs")]
+    pub proof_interval_secs: u64,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            budget_gb: default_storage_budget_gb(),
+            drive: None,
+            storage_gb_hour_cents: 0.0,
+            currency: default_storage_currency(),
+            proof_interval_secs: default_storage_proof_interval_secs(),
+        }
+    }
+}
+
+fn default_storage_budget_gb() -> u64 {
+    50
+}
+
+fn default_storage_currency() -> String {
+    "USD".to_string()
+}
+
+fn default_storage_proof_interval_secs() -> u64 {
+    3600
+}
+
+/// When the node takes work from the orchestrator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LimitsConfig {
+    /// Only advertise availability while the contributor is away from
+    /// the machine; activity drains the node until it is idle again
+    #[serde(default)]
+    pub only_when_idle: bool,
+    /// Seconds without keyboard or mouse input before the machine counts as idle
+    #[serde(default = "default_idle_after_secs")]
+    pub idle_after_secs: u64,
+    /// CPU load (percent, all cores) above which an idle machine is still busy
+    #[serde(default = "default_busy_cpu_percent")]
+    pub busy_cpu_percent: f32,
+    /// CPU, memory and storage the node may use for everything it runs
+    #[serde(default)]
+    pub resources: ResourceLimits,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            only_when_idle: false,
+            idle_after_secs: default_idle_after_secs(),
+            busy_cpu_percent: default_busy_cpu_percent(),
+            resources: ResourceLimits::default(),
+        }
+    }
+}
+
+fn default_idle_after_secs() -> u64 {
+    5 * 60
+}
+
+fn default_busy_cpu_percent() -> f32 {
+    50.0
+}
+
+/// Where the node is paid and how payments are checked on chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentsConfig {
+    /// Address orchestrator payments go to; reconciliation is off without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wallet_address: Option<String>,
+    /// JSON-RPC endpoint of the chain payments are made on
+    #[serde(default = "default_payments_rpc_url")]
+    pub rpc_url: String,
+    /// USDC token contract on that chain
+    #[serde(default = "default_usdc_contract")]
+    pub usdc_contract: String,
+    /// Block to start scanning from; defaults to about a day back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_block: Option<u64>,
+    /// Hours after completion before an uncovered job counts as unpaid
+    #[serde(default = "default_payment_grace_hours")]
+    pub grace_hours: u32,
+}
+
+impl Default for PaymentsConfig {
+    fn default() -> Self {
+        Self {
+            wallet_address: None,
+            rpc_url: default_payments_rpc_url(),
+            usdc_contract: default_usdc_contract(),
+            start_block: None,
+            grace_hours: default_payment_grace_hours(),
+        }
+    }
+}
+
+// Base mainnet
+fn default_payments_rpc_url() -> String {
+    "https://mainnet.base.org".to_string()
+}
+
+fn default_usdc_contract() -> String {
+    "0x833589fCD6eDb6E08f4c7C32D61A85Dd2Ae2913".to_string()
+}
+
+fn default_payment_grace_hours() -> u32 {
+    24
+}
+
+/// What agents may do on this machine
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentConfig {
+    /// Run agent shell commands directly on the host when no container
+    /// runtime is available; otherwise the shell tool refuses to run
+    #[serde(default)]
+    pub allow_host_shell: bool,
+}
+
+/// Cloud GPU rental
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpuConfig {
+    /// Marketplaces besides the built-in Vast.ai, RunPod and Lambda Cloud
+    #[serde(default)]
+    pub custom_providers: Vec<CustomGpuProviderConfig>,
+    /// USD per calendar month across rented instances; alerts at 80% and 100%
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_budget: Option<f64>,
+    /// Destroy running instances once the monthly budget is spent
+    #[serde(default)]
+    pub auto_destroy: bool,
+    /// Minutes a GPU attached as agent compute may sit unused before it is
+    /// destroyed (30 unless set; 0 never)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_idle_timeout_minutes: Option<u64>,
+}
+
+/// Release channel the updater follows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseChannel {
+    #[default]
+    Stable,
+    /// Pre-releases as well as stable releases
+    Beta,
+}
+
+/// How the app keeps itself up to date
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateConfig {
+    #[serde(default)]
+    pub channel: ReleaseChannel,
+    /// Check for updates in the background and tell the frontend
+    #[serde(default = "default_auto_check")]
+    pub auto_check: bool,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            channel: ReleaseChannel::default(),
+            auto_check: default_auto_check(),
+        }
+    }
+}
+
+fn default_auto_check() -> bool {
+    true
+}
+
+/// Which container backend runs the node's containers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerConfig {
+    /// Backend used when it is available (`docker`, `podman` or `native`);
+    /// unset prefers native on Linux, then Docker/Podman
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_runtime: Option<RuntimeType>,
+    /// Images containers may be pulled and created from
+    #[serde(default)]
+    pub image_policy: ImagePolicy,
+}
+
+/// How the managed IPFS node is run and stores data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpfsConfig {
+    /// Repo location; defaults to `~/.otherthing-node/ipfs/repo`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo_path: Option<String>,
+    #[serde(default = "default_ipfs_api_port")]
+    pub api_port: u16,
+    #[serde(default = "default_ipfs_gateway_port")]
+    pub gateway_port: u16,
+    /// Use the in-process store instead of Kubo (builds with the
+    /// `embedded-ipfs` feature only)
+    #[serde(default)]
+    pub embedded: bool,
+    /// Profile applied when the repo is created, e.g. `lowpower` or `server`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Pre-shared key (64 hex chars) of a private network; the node only
+    /// talks to peers holding the same key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub swarm_key: Option<String>,
+    /// Bootstrap peers (multiaddrs) used instead of the public ones in a
+    /// private network
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bootstrap_peers: Vec<String>,
+    /// Connection and resource limits for the daemon
+    #[serde(default)]
+    pub limits: IpfsLimits,
+    /// Repo size (bytes) above which garbage collection runs automatically
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_repo_size: Option<u64>,
+    /// When GC alone does not get under the budget, unpin the oldest pins
+    /// made through the app until it does
+    #[serde(default)]
+    pub unpin_oldest: bool,
+    /// Pins never removed to make room
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protected_pins: Vec<String>,
+    /// Node announcements and job offers over pubsub
+    #[serde(default)]
+    pub pubsub: IpfsPubsub,
+}
+
+/// Topics the node takes part in when Kubo runs with pubsub
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpfsPubsub {
+    /// Start Kubo with pubsub, announce this node and listen for offers
+    #[serde(default)]
+    pub enabled: bool,
+    /// Signed node summaries are published and read here
+    #[serde(default = "default_announce_topic")]
+    pub announce_topic: String,
+    /// Jobs offered by the orchestrator are read here
+    #[serde(default = "default_offer_topic")]
+    pub offer_topic: String,
+    #[serde(default = "default_announce_interval_secs")]
+    pub announce_interval_secs: u64,
+}
+
+impl Default for IpfsPubsub {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            announce_topic: default_announce_topic(),
+            offer_topic: default_offer_topic(),
+            announce_interval_secs: default_announce_interval_secs(),
+        }
+    }
+}
+
+fn default_announce_topic() -> String {
+    "otherthing/nodes/v1".to_string()
+}
+
+fn default_offer_topic() -> String {
+    "otherthing/job-offers/v1".to_string()
+}
+
+fn default_announce_interval_secs() -> u64 {
+    60
+}
+
+/// How the managed Ollama server is run and reached
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OllamaConfig {
+    /// Pull a recommended model when Ollama starts without any installed
+    #[serde(default)]
+    pub bootstrap_model: bool,
+    /// Address `ollama serve` listens on (`0.0.0.0` exposes it to the LAN)
+    #[serde(default = "default_ollama_host")]
+    pub host: String,
+    #[serde(default = "default_ollama_port")]
+    pub port: u16,
+    /// Where models are stored (`OLLAMA_MODELS`), e.g. on a larger drive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub models_dir: Option<String>,
+    /// How long models stay loaded after a request (`OLLAMA_KEEP_ALIVE`), e.g. `5m`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<String>,
+    /// Number of layers to offload to the GPU (0 forces CPU)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_gpu: Option<i32>,
+    /// GPUs Ollama may use, e.g. `0,1` (`CUDA_VISIBLE_DEVICES`/`HIP_VISIBLE_DEVICES`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu_devices: Option<String>,
+    /// Additional environment for `ollama serve`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    /// Model used to embed agent memory and indexed documents
+    #[serde(default = "default_embedding_model")]
+    pub embedding_model: String,
+}
+
+impl Default for OllamaConfig {
+    fn default() -> Self {
+        Self {
+            bootstrap_model: false,
+            host: default_ollama_host(),
+            port: default_ollama_port(),
+            models_dir: None,
+            keep_alive: None,
+            num_gpu: None,
+            gpu_devices: None,
+            env: HashMap::new(),
+            embedding_model: default_embedding_model(),
+        }
+    }
+}
+
+impl OllamaConfig {
+    /// URL the app uses to talk to Ollama
+    pub fn base_url(&self) -> String {
+        let host = match self.host.as_str() {
+            "" | "0.0.0.0" => "127.0.0.1",
+            "::" => "::1",
+            host => host,
+        };
+        if host.contains(':') {
+            format!("http://[{}]:{}", host, self.port)
+        } else {
+            format!("http://{}:{}", host, self.port)
+        }
+    }
+}
+
+fn default_ollama_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_ollama_port() -> u16 {
+    11434
+}
+
+fn default_embedding_model() -> String {
+    "nomic-embed-text".to_string()
+}
+
+/// Kubo resource limits; unset values keep Kubo's defaults. Kubo has no
+/// bandwidth throttle, so traffic is bounded through connection counts and
+/// by not serving the DHT.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpfsLimits {
+    /// Peers at which the connection manager starts trimming
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conn_high_water: Option<u32>,
+    /// Peers the connection manager trims down to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conn_low_water: Option<u32>,
+    /// Memory the libp2p resource manager may use, e.g. `512MB`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_descriptors: Option<u32>,
+    /// Query the DHT without answering other peers' lookups, which
+    /// saves most of the idle upload traffic
+    #[serde(default)]
+    pub dht_client_only: bool,
+}
+
+impl Default for IpfsConfig {
+    fn default() -> Self {
+        Self {
+            repo_path: None,
+            api_port: default_ipfs_api_port(),
+            gateway_port: default_ipfs_gateway_port(),
+            embedded: false,
+            profile: None,
+            swarm_key: None,
+            bootstrap_peers: Vec::new(),
+            limits: IpfsLimits::default(),
+            max_repo_size: None,
+            unpin_oldest: false,
+            protected_pins: Vec::new(),
+            pubsub: IpfsPubsub::default(),
+        }
+    }
+}
+
+impl IpfsConfig {
+    /// Base URL of the RPC API
+    pub fn api_url(&self) -> String {
+        format!("http://127.0.0.1:{}/api/v0", self.api_port)
+    }
+}
+
+fn default_ipfs_api_port() -> u16 {
+    5001
+}
+
+// 8080 is taken by the node API
+fn default_ipfs_gateway_port() -> u16 {
+    8088
+}
+
+/// IPFS Cluster whose pinset the node follows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Local name of the followed cluster (its state directory)
+    #[serde(default = "default_cluster_name")]
+    pub name: String,
+    /// CID of the cluster's follower configuration (`service.json`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_cid: Option<String>,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            name: default_cluster_name(),
+            config_cid: None,
+        }
+    }
+}
+
+fn default_cluster_name() -> String {
+    "otherthing".to_string()
+}
+
+/// Which browser origins may call the local API. Applied when the API
+/// server starts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiConfig {
+    /// Origins allowed besides localhost, e.g. `https://dashboard.example.com`
+    #[serde(default = "default_cors_origins")]
+    pub cors_origins: Vec<String>,
+    /// Allow pages served from localhost or 127.0.0.1 on any port
+    #[serde(default = "default_allow_localhost")]
+    pub allow_localhost: bool,
+    /// Let any website call the node from a browser. Only for nodes that
+    /// are not reachable from untrusted networks.
+    #[serde(default)]
+    pub allow_any_origin: bool,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            cors_origins: default_cors_origins(),
+            allow_localhost: true,
+            allow_any_origin: false,
+        }
+    }
+}
+
+fn default_allow_localhost() -> bool {
+    true
+}
+
+// The desktop app's own origins: macOS/Linux, then Windows
+fn default_cors_origins() -> Vec<String> {
+    ["tauri://localhost", "http://tauri.localhost", "https://tauri.localhost"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+/// Export of traces and metrics to an OpenTelemetry collector
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP endpoint of the collector, e.g. `http://collector:4318`;
+    /// nothing is exported without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+    /// Extra headers sent to the collector, e.g. an API key
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, Secret>,
+    /// `service.name` the node reports under
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// Seconds between metric exports
+    #[serde(default = "default_metrics_interval")]
+    pub metrics_interval_secs: u64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            headers: HashMap::new(),
+            service_name: default_service_name(),
+            metrics_interval_secs: default_metrics_interval(),
+        }
+    }
+}
+
+fn default_service_name() -> String {
+    "otherthing-node".to_string()
+}
+
+fn default_metrics_interval() -> u64 {
+    60
+}
+
+/// Outbound relay connection for reaching the node from outside the LAN
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// WebSocket URL of the relay server, e.g. `wss://relay.example.com`
+    #[serde(default)]
+    pub url: String,
+    /// Client certificate for relays that require mutual TLS
+    #[serde(default)]
+    pub tls: RelayTlsConfig,
+    /// Run relayed requests that change anything without an orchestrator
+    /// signature when no `orchestratorPublicKey` is set; refused otherwise
+    #[serde(default)]
+    pub allow_unsigned_jobs: bool,
+}
+
+/// Client certificate presented to the relay, either as a PEM certificate
+/// and PKCS#8 key or as a PKCS#12 bundle. The bundle's password is kept
+/// in the OS keychain.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayTlsConfig {
+    /// PEM certificate chain, leaf first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    /// PEM PKCS#8 private key (`BEGIN PRIVATE KEY`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<String>,
+    /// PKCS#12 (`.p12`/`.pfx`) bundle, used instead of the PEM pair
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pkcs12: Option<String>,
+    /// Extra root CA (PEM) for relays with a private certificate authority
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+}
+
+impl RelayTlsConfig {
+    /// Whether a client certificate is configured
+    pub fn has_client_cert(&self) -> bool {
+        self.pkcs12.is_some() || self.client_cert.is_some()
+    }
+}
+
+impl NodeConfig {
+    /// Load the config, falling back to defaults when missing or unreadable
+    pub fn load() -> Self {
+        let path = Self::path();
+        match std::fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let data = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, data).map_err(|e| format!("Failed to save config: {}", e))
+    }
+
+    pub fn path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("otherthing-node")
+            .join("config.json")
+    }
+}
@@ -0,0 +1,136 @@
+//! Diagnostics
+//!
+//! Panics are logged and written to `crash-<time>.log` in the log
+//! directory. `export` bundles the logs, crash reports, the config with
+//! secrets blanked out and the hardware summary into a zip for support.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Manager};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::commands::AppState;
+use crate::services::HardwareDetector;
+
+/// Config keys containing any of these are replaced before export
+const SECRET_MARKERS: &[&str] = &["key", "token", "secret", "password", "credential"];
+
+/// Log files larger than this are left out of the bundle
+const MAX_BUNDLED_FILE: u64 = 50 * 1024 * 1024;
+
+/// Log panics and keep a crash report for each, then carry on with the
+/// default hook
+pub fn install_panic_hook(log_dir: PathBuf) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        let thread = std::thread::current();
+        let report = format!(
+            "OtherThing Node {} crashed on thread '{}'\n{}\n\n{}\n",
+            env!("CARGO_PKG_VERSION"),
+            thread.name().unwrap_or("<unnamed>"),
+            info,
+            backtrace
+        );
+        log::error!("{}", report);
+
+        let path = log_dir.join(format!("crash-{}.log", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+        if std::fs::create_dir_all(&log_dir).is_ok() {
+            let _ = std::fs::write(path, &report);
+        }
+        previous(info);
+    }));
+}
+
+/// Blank out every value whose key looks like a secret
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if SECRET_MARKERS.iter().any(|m| key.contains(m)) && !value.is_null() {
+                    *value = serde_json::Value::String("[redacted]".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn add_json(zip: &mut ZipWriter<File>, name: &str, value: &serde_json::Value, options: SimpleFileOptions) -> Result<(), String> {
+    let data = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+    zip.start_file(name, options).map_err(|e| e.to_string())?;
+    zip.write_all(&data).map_err(|e| e.to_string())
+}
+
+fn add_logs(zip: &mut ZipWriter<File>, log_dir: &Path, options: SimpleFileOptions) -> Result<(), String> {
+    let Ok(entries) = std::fs::read_dir(log_dir) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if !meta.is_file() || meta.len() > MAX_BUNDLED_FILE {
+            continue;
+        }
+        let Ok(data) = std::fs::read(&path) else {
+            continue;
+        };
+        let name = format!("logs/{}", entry.file_name().to_string_lossy());
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(&data).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Write the diagnostics bundle to `dest`, or to the downloads folder when
+/// none is given. Returns the path of the zip.
+pub async fn export(app: &AppHandle, state: &AppState, dest: Option<PathBuf>) -> Result<PathBuf, String> {
+    let dest = match dest {
+        Some(dest) => dest,
+        None => {
+            let dir = dirs::download_dir()
+                .or_else(dirs::home_dir)
+                .ok_or("No folder to save diagnostics to")?;
+            dir.join(format!(
+                "otherthing-diagnostics-{}.zip",
+                chrono::Local::now().format("%Y%m%d-%H%M%S")
+            ))
+        }
+    };
+
+    let mut config = serde_json::to_value(&*state.api.state().config.read().await).map_err(|e| e.to_string())?;
+    redact(&mut config);
+    let system = serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "nodeRunning": *state.node_running.read().await,
+        "ollamaRunning": state.ollama.is_running(),
+        "ipfsRunning": state.ipfs.is_running(),
+        "containerRuntime": state.containers.is_available().await,
+        "exportedAt": chrono::Utc::now().to_rfc3339(),
+    });
+    let hardware = serde_json::to_value(HardwareDetector::detect()).map_err(|e| e.to_string())?;
+    let log_dir = app.path().app_log_dir().map_err(|e| e.to_string())?;
+
+    let file = File::create(&dest).map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    add_json(&mut zip, "system.json", &system, options)?;
+    add_json(&mut zip, "config.json", &config, options)?;
+    add_json(&mut zip, "hardware.json", &hardware, options)?;
+    add_logs(&mut zip, &log_dir, options)?;
+    zip.finish().map_err(|e| format!("Failed to write diagnostics: {}", e))?;
+
+    log::info!("Diagnostics exported to {}", dest.display());
+    Ok(dest)
+}
@@ -0,0 +1,141 @@
+//! Error Model
+//!
+//! Services the UI needs to react to (Ollama, IPFS, agents, containers)
+//! fail with a `NodeError`, whose category survives to the caller: API
+//! responses carry it as a stable `code` next to the message, with a
+//! matching HTTP status, and Tauri commands reject with
+//! `{ "code", "message" }`. Codes never change once shipped; new
+//! categories get new codes.
+//!
+//! Code still returning `Result<_, String>` can `?` a `NodeError` (it
+//! becomes its message), and a `String` error becomes `Internal`.
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+use crate::services::container::ContainerError;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum NodeError {
+    /// A binary or model the operation needs is not installed
+    #[error("{0}")]
+    NotInstalled(String),
+    /// The service is installed but not running, or not answering
+    #[error("{0}")]
+    NotRunning(String),
+    #[error("{0}")]
+    Timeout(String),
+    /// The operation clashes with the current state, e.g. already in progress
+    #[error("{0}")]
+    Conflict(String),
+    /// Refused by a node policy (image policy, budgets, relay restrictions)
+    #[error("{0}")]
+    Policy(String),
+    /// A service the node talks to answered with an error
+    #[error("{0}")]
+    Upstream(String),
+    #[error("{0}")]
+    NotFound(String),
+    /// The request itself is wrong
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl NodeError {
+    /// Stable identifier of the category
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotInstalled(_) => "not_installed",
+            Self::NotRunning(_) => "not_running",
+            Self::Timeout(_) => "timeout",
+            Self::Conflict(_) => "conflict",
+            Self::Policy(_) => "policy",
+            Self::Upstream(_) => "upstream",
+            Self::NotFound(_) => "not_found",
+            Self::Invalid(_) => "invalid",
+            Self::Internal(_) => "internal",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::NotInstalled(_) | Self::NotRunning(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Policy(_) => StatusCode::FORBIDDEN,
+            Self::Upstream(_) => StatusCode::BAD_GATEWAY,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Classify a failed HTTP request to a local service: refused
+    /// connections mean it is not running
+    pub fn request(context: &str, e: reqwest::Error) -> Self {
+        let message = format!("{}: {}", context, e);
+        if e.is_timeout() {
+            Self::Timeout(message)
+        } else if e.is_connect() {
+            Self::NotRunning(message)
+        } else {
+            Self::Upstream(message)
+        }
+    }
+}
+
+impl Serialize for NodeError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("NodeError", 2)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+/// Same shape as the API's other errors, plus the code
+impl IntoResponse for NodeError {
+    fn into_response(self) -> Response {
+        (
+            self.status(),
+            Json(serde_json::json!({ "success": false, "error": self.to_string(), "code": self.code() })),
+        )
+            .into_response()
+    }
+}
+
+impl From<String> for NodeError {
+    fn from(message: String) -> Self {
+        Self::Internal(message)
+    }
+}
+
+impl From<&str> for NodeError {
+    fn from(message: &str) -> Self {
+        Self::Internal(message.to_string())
+    }
+}
+
+impl From<NodeError> for String {
+    fn from(e: NodeError) -> Self {
+        e.to_string()
+    }
+}
+
+impl From<ContainerError> for NodeError {
+    fn from(e: ContainerError) -> Self {
+        let message = e.to_string();
+        match e {
+            ContainerError::RuntimeNotAvailable(_) => Self::NotRunning(message),
+            ContainerError::NotFound(_) | ContainerError::ImageNotFound(_) => Self::NotFound(message),
+            ContainerError::FeatureNotEnabled => Self::NotInstalled(message),
+            ContainerError::PolicyViolation(_) => Self::Policy(message),
+            ContainerError::OperationFailed(_) => Self::Upstream(message),
+        }
+    }
+}
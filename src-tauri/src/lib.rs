@@ -1,72 +1,308 @@
 mod api;
+mod autostart;
+pub mod cli;
 mod commands;
+mod config;
+mod diagnostics;
+mod error;
+mod logging;
 mod models;
+mod notifications;
+mod pagination;
 mod services;
+mod telemetry;
+mod tray;
+mod updater;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-use api::ApiServer;
 use commands::AppState;
-use tauri::Manager;
-
-// Global API server handle
-static API_SERVER_RUNNING: std::sync::Mutex<bool> = std::sync::Mutex::new(false);
-
-async fn start_api_server() {
-    // Check if already running
-    {
-        let mut running = API_SERVER_RUNNING.lock().unwrap();
-        if *running {
-            log::info!("API server already running");
-            return;
+use tauri::{Emitter, Manager, RunEvent, WindowEvent};
+
+/// Size at which the log file is rotated
+const LOG_FILE_SIZE: u128 = 5 * 1024 * 1024;
+
+/// Rotated log files kept besides the current one
+const LOG_FILES_KEPT: usize = 5;
+
+/// Time running agents get to cancel and clean up their sandboxes
+const AGENT_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Seconds each managed container gets to stop before it is killed
+const CONTAINER_STOP_TIMEOUT: i64 = 10;
+
+/// Set when the shutdown sequence starts, so repeated exit requests do not run it twice
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Set once the shutdown sequence has run, so the exit it requests goes through
+static SHUT_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Stop everything the app started, users first: agents, so they stop
+/// using containers and models, then the containers, then Ollama and IPFS,
+/// and the API server last
+async fn shutdown(state: AppState) {
+    log::info!("Shutting down");
+
+    let remaining = state.agents.shutdown(AGENT_DRAIN_TIMEOUT).await;
+    if remaining > 0 {
+        log::warn!("{} agent executions did not finish cancelling in time", remaining);
+    }
+
+    if state.containers.is_available().await {
+        match state.containers.list_containers(false).await {
+            Ok(containers) => {
+                let stops = containers
+                    .into_iter()
+                    .filter(|c| c.labels.get("managed_by").map(String::as_str) == Some("otherthing-node"))
+                    .map(|c| {
+                        let containers = state.containers.clone();
+                        async move {
+                            log::info!("Stopping container {}", c.name);
+                            if let Err(e) = containers.stop_container(&c.id, Some(CONTAINER_STOP_TIMEOUT)).await {
+                                log::warn!("Failed to stop container {}: {}", c.name, e);
+                            }
+                        }
+                    });
+                // Stops time out on their own; this guards against a hung runtime
+                let limit = Duration::from_secs(CONTAINER_STOP_TIMEOUT as u64 + 5);
+                if tokio::time::timeout(limit, futures_util::future::join_all(stops)).await.is_err() {
+                    log::warn!("Timed out stopping containers");
+                }
+            }
+            Err(e) => log::warn!("Failed to list containers: {}", e),
         }
-        *running = true;
     }
 
-    log::info!("Starting Rust API server...");
+    state.cluster.stop();
+    if let Err(e) = state.ollama.stop().await {
+        log::warn!("{}", e);
+    }
+    if let Err(e) = state.ipfs.stop().await {
+        log::warn!("{}", e);
+    }
+    state.stop_node().await;
+    if let Err(e) = state.api.state().bandwidth.flush() {
+        log::warn!("{}", e);
+    }
+    telemetry::shutdown().await;
+
+    log::info!("Shutdown complete");
+}
 
-    let server = ApiServer::new();
-    if let Err(e) = server.start(8080).await {
-        log::error!("API server error: {}", e);
-        *API_SERVER_RUNNING.lock().unwrap() = false;
+/// Run the shutdown sequence unless it already ran or is running. Returns
+/// whether this call ran it; exits requested afterwards go through.
+pub(crate) async fn shutdown_once(state: AppState) -> bool {
+    if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+        return false;
     }
+    shutdown(state).await;
+    SHUT_DOWN.store(true, Ordering::SeqCst);
+    true
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
+        // Must come first: a second launch hands over to this process and
+        // exits before it starts another node
+        .plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
+            // Deep links and other arguments, minus the executable
+            let args: Vec<String> = argv
+                .into_iter()
+                .skip(1)
+                .filter(|arg| arg != autostart::MINIMIZED_ARG)
+                .collect();
+            log::info!("Second launch handed over to the running node");
+            tray::show_window(app);
+            if !args.is_empty() {
+                let _ = app.emit("app://open-args", args);
+            }
+        }))
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_notification::init())
-        .manage(AppState::default())
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            Some(vec![autostart::MINIMIZED_ARG]),
+        ))
         .setup(|app| {
+            // Log to rotating files in the log dir, and to stdout in dev builds
+            use tauri_plugin_log::{RotationStrategy, Target, TargetKind};
+            let mut targets = vec![Target::new(TargetKind::LogDir { file_name: None })];
             if cfg!(debug_assertions) {
-                app.handle().plugin(
-                    tauri_plugin_log::Builder::default()
-                        .level(log::LevelFilter::Info)
-                        .build(),
-                )?;
+                targets.push(Target::new(TargetKind::Stdout));
+            }
+            let args: Vec<String> = std::env::args().collect();
+            let log_format = logging::format_from_args(&args).unwrap_or_else(|| config::NodeConfig::load().log_format);
+            app.handle().plugin(
+                tauri_plugin_log::Builder::default()
+                    .clear_targets()
+                    .targets(targets)
+                    .level(log::LevelFilter::Info)
+                    // Text in the default layout or JSON, with secrets masked
+                    .format(move |out, message, record| {
+                        let message = services::redact::redact(&message.to_string());
+                        out.finish(format_args!("{}", logging::format_line(log_format, record, &message)))
+                    })
+                    .max_file_size(LOG_FILE_SIZE)
+                    .rotation_strategy(RotationStrategy::KeepSome(LOG_FILES_KEPT))
+                    .build(),
+            )?;
+            diagnostics::install_panic_hook(app.path().app_log_dir()?);
+
+            // A backup restored last session replaces the state before anything opens it
+            if let Err(e) = services::backup::apply_pending() {
+                log::error!("Failed to restore backup: {}", e);
             }
 
-            // Start the Rust API server
-            tauri::async_runtime::spawn(async {
-                start_api_server().await;
+            // Shared state for Tauri commands and the local API server
+            let state = tauri::async_runtime::block_on(AppState::new());
+            app.manage(state.clone());
+
+            // Export traces and metrics when a collector is configured
+            tauri::async_runtime::block_on(async {
+                let api_state = state.api.state();
+                let telemetry = api_state.config.read().await.telemetry.clone();
+                let node_id = api_state.node_id.read().await.clone();
+                if let Err(e) = telemetry::init(&telemetry, &node_id) {
+                    log::warn!("{}", e);
+                }
+            });
+
+            // Forward streamed agent output to the frontend
+            let mut agent_output = state.agents.subscribe_output();
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                use tokio::sync::broadcast::error::RecvError;
+                loop {
+                    match agent_output.recv().await {
+                        Ok(chunk) => {
+                            let _ = handle.emit("agent://output", chunk);
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Forward node events (jobs, agent progress, container and
+            // service changes, share key rotation, ...) to the frontend,
+            // and raise the ones switched on as desktop notifications
+            let mut node_events = state.api.state().node_events.subscribe();
+            let handle = app.handle().clone();
+            let mut notifier = notifications::Notifier::new(handle.clone(), state.api.state());
+            tauri::async_runtime::spawn(async move {
+                use tokio::sync::broadcast::error::RecvError;
+                loop {
+                    match node_events.recv().await {
+                        Ok(event) => {
+                            notifier.on_event(&event);
+                            let _ = handle.emit("node://event", event);
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Supervise Ollama and forward its health changes to the frontend
+            let mut ollama_health = state.ollama.subscribe_health();
+            let ollama = state.ollama.clone();
+            tauri::async_runtime::spawn(async move { ollama.supervise().await });
+            let handle = app.handle().clone();
+            let api_state = state.api.state();
+            let notifier = notifications::Notifier::new(handle.clone(), state.api.state());
+            tauri::async_runtime::spawn(async move {
+                use models::OllamaHealth;
+                use tokio::sync::broadcast::error::RecvError;
+                loop {
+                    match ollama_health.recv().await {
+                        Ok(event) => {
+                            match event.health {
+                                OllamaHealth::Running => api_state.service_status("ollama", true),
+                                OllamaHealth::Restarting => {}
+                                _ => api_state.service_status("ollama", false),
+                            }
+                            notifier.on_ollama_health(&event);
+                            let _ = handle.emit("ollama://health", event);
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    }
+                }
             });
 
-            // Auto-start node in local mode
-            let state: tauri::State<AppState> = app.state();
-            let state_clone = (*state).clone();
+            // Keep the IPFS repo within its configured size budget
+            let ipfs = state.ipfs.clone();
+            tauri::async_runtime::spawn(async move { ipfs.watch_repo_budget().await });
+
+            // Follow the configured IPFS Cluster pinset while IPFS is up
+            let cluster = state.cluster.clone();
+            tauri::async_runtime::spawn(async move { cluster.watch().await });
+
+            // Keep the login item in line with the saved setting
+            let enabled = tauri::async_runtime::block_on(state.api.state().config.read()).autostart;
+            autostart::sync(app.handle(), enabled);
+
+            // Tray icon with node status and controls
+            tray::init(app.handle(), state.clone())?;
+
+            // Accrue the cost of rented GPUs and enforce the monthly budget
+            let gpu_spend = state.api.state().gpu_spend.clone();
+            tauri::async_runtime::spawn(async move { gpu_spend.watch().await });
+            let remote_compute = state.api.state().remote_compute.clone();
+            tauri::async_runtime::spawn(async move { remote_compute.watch().await });
+            let payments = state.api.state().payments.clone();
+            tauri::async_runtime::spawn(async move { payments.watch().await });
+
+            // Take jobs only while the contributor is away, when configured
+            let idle = state.api.state().idle.clone();
+            tauri::async_runtime::spawn(async move { idle.watch().await });
+
+            // Announce the node and read job offers on IPFS pubsub, when enabled
+            let pubsub = state.api.state().pubsub.clone();
+            tauri::async_runtime::spawn(async move { pubsub.listen().await });
+            tauri::async_runtime::spawn(state.api.state().announce_on_pubsub());
+
+            // Follow jobs exchanged with other nodes to their end
+            let mesh = state.api.state().mesh.clone();
+            let node_id = state.api.state().node_id.clone();
+            tauri::async_runtime::spawn(async move { mesh.watch(node_id).await });
+
+            // Move advertised rates with demand, when dynamic pricing is on
+            let pricing = state.api.state().pricing.clone();
+            tauri::async_runtime::spawn(async move { pricing.watch().await });
+
+            // Meter IPFS traffic and save bandwidth counters
+            let bandwidth = state.api.state().bandwidth.clone();
+            let ipfs = state.ipfs.clone();
+            tauri::async_runtime::spawn(async move { bandwidth.watch(ipfs).await });
+
+            // Check contributed storage is still held and record storage-hours
+            let storage = state.api.state().storage.clone();
+            tauri::async_runtime::spawn(async move { storage.watch().await });
+
+            // Look for updates on the configured release channel
+            tauri::async_runtime::spawn(updater::watch(app.handle().clone()));
+
+            // Auto-start node in local mode (brings up the Rust API server)
+            let handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
-                // Initialize node
-                let mut running = state_clone.node_running.write().await;
-                *running = true;
-                let mut node_id = state_clone.node_id.write().await;
-                *node_id = Some(uuid::Uuid::new_v4().to_string());
-                log::info!("Node started in local mode");
+                if state.ollama.is_running() {
+                    commands::spawn_model_bootstrap(handle, state.clone());
+                }
+
+                if let Err(e) = state.start_node().await {
+                    log::error!("{}", e);
+                }
 
                 // Detect container runtime
-                if let Ok(runtime) = state_clone.containers.detect_runtime().await {
+                if let Ok(runtime) = state.containers.detect_runtime().await {
                     log::info!("Container runtime detected: {} v{}", runtime.runtime_type, runtime.version);
                 } else {
                     log::info!("No container runtime detected - container features disabled");
@@ -75,6 +311,15 @@ pub fn run() {
 
             Ok(())
         })
+        .on_window_event(|window, event| {
+            // Keep running in the tray; quitting is done from the tray menu
+            if let WindowEvent::CloseRequested { api, .. } = event {
+                if window.label() == "main" {
+                    api.prevent_close();
+                    let _ = window.hide();
+                }
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             // Hardware
             commands::get_hardware,
@@ -83,6 +328,33 @@ pub fn run() {
             commands::get_node_status,
             commands::start_node,
             commands::stop_node,
+            commands::rotate_share_key,
+            commands::relay_status,
+            commands::relay_configure,
+            commands::set_orchestrator_key,
+            commands::get_availability,
+            commands::get_limits_config,
+            commands::set_limits_config,
+            commands::get_resource_limits,
+            commands::set_resource_limits,
+            commands::fleet_list,
+            commands::fleet_add,
+            commands::fleet_remove,
+            commands::fleet_query,
+            commands::discovery_peers,
+            commands::mesh_dispatch,
+            commands::mesh_jobs,
+            commands::mesh_job,
+            commands::mesh_cancel,
+            commands::mesh_usage,
+            commands::export_diagnostics,
+            // Updates
+            commands::check_for_updates,
+            commands::install_update,
+            commands::get_update_config,
+            commands::set_update_config,
+            commands::get_autostart,
+            commands::set_autostart,
             // Ollama
             commands::ollama_status,
             commands::ollama_start,
@@ -90,15 +362,50 @@ pub fn run() {
             commands::ollama_models,
             commands::ollama_pull_model,
             commands::ollama_delete_model,
+            commands::ollama_get_config,
+            commands::ollama_set_config,
+            commands::ollama_recommended_model,
+            commands::ollama_set_bootstrap,
             commands::ollama_set_path,
             commands::ollama_get_path,
+            commands::ollama_install,
+            commands::ollama_upgrade,
             // IPFS
             commands::ipfs_status,
             commands::ipfs_start,
             commands::ipfs_stop,
             commands::ipfs_add_content,
+            commands::ipfs_add_path,
+            commands::ipfs_cat,
+            commands::ipfs_get,
+            commands::ipfs_list_pins,
+            commands::ipfs_gc,
+            commands::ipfs_get_config,
+            commands::ipfs_set_config,
+            commands::ipfs_relocate_repo,
+            commands::ipfs_generate_swarm_key,
+            commands::ipfs_version_info,
+            commands::ipfs_upgrade,
+            commands::ipns_publish,
+            commands::ipns_resolve,
+            commands::ipfs_key_list,
+            commands::ipfs_key_gen,
             commands::ipfs_pin,
             commands::ipfs_unpin,
+            commands::ipfs_pubsub_nodes,
+            commands::ipfs_pubsub_offers,
+            commands::storage_status,
+            commands::storage_set_config,
+            commands::storage_pins,
+            commands::storage_release,
+            commands::bandwidth_usage,
+            commands::pricing_status,
+            commands::notification_settings,
+            commands::notification_set_settings,
+            commands::pricing_set_config,
+            commands::bandwidth_set_config,
+            commands::ipfs_cluster_status,
+            commands::ipfs_cluster_follow,
             // Window
             commands::window_minimize,
             commands::window_maximize,
@@ -108,17 +415,75 @@ pub fn run() {
             // Containers
             commands::container_runtime_info,
             commands::container_detect_runtime,
+            commands::container_set_runtime,
             commands::container_list,
             commands::container_list_images,
             commands::container_pull_image,
+            commands::container_build_image,
+            commands::container_push_image,
+            commands::container_list_registries,
+            commands::container_save_registry,
+            commands::container_remove_registry,
+            commands::container_disk_usage,
+            commands::container_prune,
+            commands::container_list_networks,
+            commands::container_create_network,
+            commands::container_remove_network,
+            commands::container_connect_network,
+            commands::container_disconnect_network,
             commands::container_create,
             commands::container_start,
             commands::container_stop,
+            commands::container_restart,
+            commands::container_rename,
+            commands::container_update,
             commands::container_remove,
             commands::container_logs,
+            commands::container_follow_logs,
+            commands::container_unfollow_logs,
             commands::container_exec,
             commands::container_inspect,
+            commands::app_list,
+            commands::app_deploy,
+            commands::app_status,
+            commands::app_teardown,
+            // Workspaces
+            commands::workspace_list,
+            commands::workspace_get,
+            commands::workspace_create,
+            commands::workspace_update,
+            commands::workspace_delete,
+            // Agents
+            commands::agent_templates,
+            commands::agent_create,
+            commands::agent_list,
+            commands::job_list,
+            commands::agent_get,
+            commands::agent_cancel,
+            // LLM providers
+            commands::provider_list,
+            commands::provider_save,
+            commands::provider_remove,
+            commands::provider_health,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Quitting from the tray lands here; hold the exit
+            // until managed processes are down
+            if let RunEvent::ExitRequested { api, .. } = event {
+                if SHUT_DOWN.load(Ordering::SeqCst) {
+                    return;
+                }
+                api.prevent_exit();
+
+                let state = app.state::<AppState>().inner().clone();
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    if shutdown_once(state).await {
+                        app.exit(0);
+                    }
+                });
+            }
+        });
 }
@@ -0,0 +1,96 @@
+//! Log Output
+//!
+//! Log lines are plain text by default, or one JSON object per line with
+//! `--log-format json` (or `logFormat: "json"` in the config) for log
+//! aggregators such as Loki or Elasticsearch. Work that runs in a task —
+//! an agent execution, a relayed job, a container operation — wraps itself
+//! in `with_context`, and every line logged inside carries its IDs
+//! (`execution_id`, `job_id`, `container_id`) as fields.
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!("Unknown log format: {} (expected text or json)", other)),
+        }
+    }
+}
+
+/// `--log-format json` or `--log-format=json` among the program arguments
+pub fn format_from_args(args: &[String]) -> Option<LogFormat> {
+    let value = args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == "--log-format" {
+            args.get(i + 1).map(String::as_str)
+        } else {
+            arg.strip_prefix("--log-format=")
+        }
+    })?;
+    match value.parse() {
+        Ok(format) => Some(format),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    }
+}
+
+tokio::task_local! {
+    static CONTEXT: Vec<(&'static str, String)>;
+}
+
+/// Run `future` with `fields` attached to every line it logs, on top of
+/// those of the enclosing context
+pub async fn with_context<F: Future>(fields: &[(&'static str, &str)], future: F) -> F::Output {
+    let mut context = CONTEXT.try_with(Clone::clone).unwrap_or_default();
+    for (key, value) in fields {
+        context.retain(|(k, _)| k != key);
+        context.push((key, value.to_string()));
+    }
+    CONTEXT.scope(context, future).await
+}
+
+/// Format one log record; `message` has already had secrets masked
+pub fn format_line(format: LogFormat, record: &log::Record, message: &str) -> String {
+    let context = CONTEXT.try_with(Clone::clone).unwrap_or_default();
+    match format {
+        LogFormat::Text => {
+            let mut line = format!(
+                "[{}][{}][{}] {}",
+                chrono::Local::now().format("%Y-%m-%d][%H:%M:%S"),
+                record.target(),
+                record.level(),
+                message
+            );
+            if !context.is_empty() {
+                let fields: Vec<String> = context.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                line.push_str(&format!(" [{}]", fields.join(" ")));
+            }
+            line
+        }
+        LogFormat::Json => {
+            let mut object = serde_json::Map::new();
+            object.insert("timestamp".into(), chrono::Utc::now().to_rfc3339().into());
+            object.insert("level".into(), record.level().as_str().to_lowercase().into());
+            object.insert("target".into(), record.target().into());
+            object.insert("message".into(), message.into());
+            for (key, value) in context {
+                object.insert(key.to_string(), value.into());
+            }
+            serde_json::Value::Object(object).to_string()
+        }
+    }
+}
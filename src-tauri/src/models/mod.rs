@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::services::agent::AgentStatus;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Hardware {
     pub cpu: CpuInfo,
@@ -45,6 +47,25 @@ pub struct NodeStatus {
     pub connected: bool,
     pub node_id: Option<String>,
     pub share_key: Option<String>,
+    pub uptime_secs: u64,
+}
+
+/// What the node offers, as advertised to clients and the orchestrator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeCapabilities {
+    pub node_id: String,
+    pub version: String,
+    /// Hex Ed25519 key heartbeats and results are signed with
+    pub public_key: String,
+    pub cpu_cores: u32,
+    pub memory_mb: u64,
+    pub gpus: Vec<GpuInfo>,
+    /// Models the local Ollama serves
+    pub models: Vec<String>,
+    pub container_runtime: bool,
+    /// Taking new jobs; false while draining because the machine is in use
+    pub available: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,6 +80,96 @@ pub struct OllamaModel {
     pub name: String,
     pub size: u64,
     pub modified_at: String,
+    #[serde(default)]
+    pub digest: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>,
+    /// e.g. `8.0B`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameter_size: Option<String>,
+    /// e.g. `Q4_K_M`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantization: Option<String>,
+    /// Prompt template from the modelfile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Rough memory needed to run the model (weights plus context)
+    #[serde(default)]
+    pub estimated_memory: u64,
+    #[serde(default)]
+    pub fit: ModelFit,
+}
+
+/// Where a model is expected to fit on this machine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelFit {
+    /// Fits in the largest GPU's VRAM
+    Gpu,
+    /// Fits in system RAM only, runs on CPU (slow)
+    Cpu,
+    /// Larger than both VRAM and RAM
+    TooLarge,
+    #[default]
+    Unknown,
+}
+
+/// Tokens processed through the node's inference proxy, per model
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// What the node is doing, as shown in the system tray
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeActivity {
+    /// Running and serving work: agents in flight or inference requests
+    Earning,
+    /// Running with nothing to do
+    Idle,
+    /// Stopped by the user
+    Paused,
+}
+
+/// Health of the Ollama server as seen by the supervisor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OllamaHealth {
+    Running,
+    Stopped,
+    /// The managed process exited unexpectedly
+    Crashed,
+    /// The managed process is alive but the API stopped answering
+    Unresponsive,
+    Restarting,
+    /// A restart attempt failed; the next one follows after a backoff
+    RestartFailed,
+}
+
+/// Emitted when the supervised Ollama server changes health
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OllamaHealthEvent {
+    pub health: OllamaHealth,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Restarts performed by the supervisor so far
+    pub restarts: u32,
+    pub timestamp: String,
+}
+
+/// Progress update emitted while a model is being pulled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullProgress {
+    pub model: String,
+    pub status: String,
+    pub percent: Option<f64>,
+    pub completed: Option<u64>,
+    pub total: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,6 +178,9 @@ pub struct IpfsStatus {
     pub has_binary: bool,
     pub peer_id: Option<String>,
     pub stats: Option<IpfsStats>,
+    /// Running with a swarm key, isolated from the public network
+    #[serde(default)]
+    pub private_network: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,9 +191,170 @@ pub struct IpfsStats {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinInfo {
+    pub cid: String,
+    /// `recursive`, `direct` or `indirect`
+    pub pin_type: String,
+    /// Size including all linked blocks, when it could be determined
+    pub cumulative_size: Option<u64>,
+}
+
+/// Installed Kubo release versus what the app supports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KuboVersionInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installed: Option<String>,
+    /// Oldest supported version (inclusive)
+    pub min_supported: String,
+    /// First version not yet supported (exclusive)
+    pub max_supported: String,
+    pub supported: bool,
+    /// Newest release within the supported range
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest: Option<String>,
+    pub upgrade_available: bool,
+    /// Whether the binary was installed by the app (and can be upgraded by it)
+    pub managed: bool,
+}
+
+/// State of the IPFS Cluster follower
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterStatus {
+    pub enabled: bool,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config_cid: Option<String>,
+    pub running: bool,
+    /// Whether ipfs-cluster-follow has been downloaded
+    pub installed: bool,
+    /// Why the follower last failed to start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// Progress of a long-running IPFS add or pin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpfsProgress {
+    /// `add` or `pin`
+    pub operation: String,
+    /// Path being added or CID being pinned
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_file: Option<String>,
+    /// Bytes hashed so far (adds)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    /// Blocks fetched so far (pins; the total is not known up front)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
+}
+
+/// Keypair an IPNS name is published under
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpnsKey {
+    pub name: String,
+    /// The IPNS name (`k51...`) derived from the key
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpnsRecord {
+    /// IPNS name that was published
+    pub name: String,
+    /// Path it points to, e.g. `/ipfs/bafy...`
+    pub value: String,
+}
+
+/// Outcome of an IPFS garbage collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GcResult {
+    /// Blocks removed
+    pub removed: usize,
+    pub repo_size_before: u64,
+    pub repo_size_after: u64,
+    /// Pins dropped to get under the repo budget
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unpinned: Vec<String>,
+}
+
+/// Node-level change pushed to connected clients: the Tauri frontend
+/// (`node://event`), `/api/v1/node/events` (SSE) and `/ws/events`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NodeEvent {
+    /// A job relayed from the orchestrator started running
+    JobStarted { job_id: String, method: String, path: String },
+    /// A relayed job was answered
+    JobFinished { job_id: String, status: u16, duration_ms: u64 },
+    /// An agent execution changed status or made progress
+    AgentProgress {
+        execution_id: String,
+        status: AgentStatus,
+        progress: u8,
+        message: String,
+    },
+    /// A container was created, started, stopped, restarted or removed
+    ContainerStateChanged {
+        container_id: String,
+        /// `created`, `started`, `stopped`, `restarted` or `removed`
+        state: String,
+    },
+    /// A service came up or went down: `node`, `relay` or `ollama`
+    ServiceStatus { service: String, running: bool },
+    /// A job payment was recorded in the earnings ledger
+    EarningAdded { job_id: String, amount_cents: i64, currency: String },
+    /// The share key was rotated; the previous key no longer works
+    ShareKeyRotated { share_key: String, rotated_at: String },
+    /// Rented GPUs crossed 80% of the monthly budget, or all of it
+    GpuBudgetAlert {
+        month: String,
+        spent: f64,
+        budget: f64,
+        exceeded: bool,
+        /// Instances destroyed because the budget was reached
+        destroyed: Vec<String>,
+    },
+    /// The node started or stopped taking jobs because the contributor
+    /// came back to the machine or left it (`limits.onlyWhenIdle`)
+    AvailabilityChanged {
+        available: bool,
+        /// Why it stopped; missing when it became available
+        reason: Option<String>,
+    },
+    /// The orchestrator offered a job on IPFS pubsub
+    JobOffered { offer_id: String, kind: String },
+    /// Completed jobs passed their grace period without being paid
+    PaymentsOverdue {
+        /// Jobs newly flagged
+        jobs: Vec<String>,
+        /// Outstanding across all unpaid jobs
+        unpaid_cents: i64,
+    },
+    /// Dynamic pricing moved the advertised rates
+    PricingChanged {
+        /// Rates are the base rates times this
+        multiplier: f64,
+    },
+}
+
+/// Share of the machine the node may use; 0 leaves a resource unlimited
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct ResourceLimits {
+    /// Percent of all cores together
+    #[serde(default)]
     pub max_cpu_percent: u32,
+    #[serde(default)]
     pub max_memory_mb: u64,
+    #[serde(default)]
     pub max_storage_gb: u64,
 }
 
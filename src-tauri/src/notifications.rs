@@ -0,0 +1,230 @@
+//! Desktop Notifications
+//!
+//! Node events the contributor would want to hear about while the window
+//! is hidden are raised as native notifications, each category behind its
+//! own toggle. The toggles live in the settings store under
+//! `notifications` and are read at every event, so changes apply at once.
+//!
+//! Relayed jobs only count when they change something (not `GET`): the
+//! orchestrator also polls status through the relay. Earnings milestones
+//! fire when a currency's lifetime total crosses one of `MILESTONES_CENTS`,
+//! then every further 1,000.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+
+use crate::api::AppState;
+use crate::models::{NodeEvent, OllamaHealth, OllamaHealthEvent};
+use crate::services::agent::AgentStatus;
+use crate::services::state_store::Settings;
+
+/// Settings key the toggles are stored under
+const SETTINGS_KEY: &str = "notifications";
+
+/// Lifetime totals worth a notification, below the recurring 1,000 steps
+const MILESTONES_CENTS: &[i64] = &[100, 1_000, 5_000, 10_000, 50_000, 100_000];
+
+/// Recurring milestone step past the last of `MILESTONES_CENTS`
+const MILESTONE_STEP_CENTS: i64 = 100_000;
+
+/// Agent executions remembered to avoid notifying twice
+const MAX_FINISHED_AGENTS: usize = 256;
+
+/// Which notifications are shown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationSettings {
+    #[serde(default)]
+    pub job_assigned: bool,
+    #[serde(default)]
+    pub job_completed: bool,
+    #[serde(default = "enabled")]
+    pub job_failed: bool,
+    #[serde(default = "enabled")]
+    pub agent_finished: bool,
+    #[serde(default = "enabled")]
+    pub earnings_milestones: bool,
+    /// Ollama crashing or failing to restart
+    #[serde(default = "enabled")]
+    pub service_crashes: bool,
+    #[serde(default = "enabled")]
+    pub gpu_budget: bool,
+    #[serde(default = "enabled")]
+    pub unpaid_jobs: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            job_assigned: false,
+            job_completed: false,
+            job_failed: true,
+            agent_finished: true,
+            earnings_milestones: true,
+            service_crashes: true,
+            gpu_budget: true,
+            unpaid_jobs: true,
+        }
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// Saved toggles, or the defaults
+pub fn load(settings: &Settings) -> NotificationSettings {
+    settings
+        .get(SETTINGS_KEY)
+        .ok()
+        .flatten()
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(settings: &Settings, toggles: &NotificationSettings) -> Result<(), String> {
+    let value = serde_json::to_string(toggles).map_err(|e| e.to_string())?;
+    settings.set(SETTINGS_KEY, &value)
+}
+
+/// Turns node events into notifications; lives in the event forwarding task
+pub struct Notifier {
+    handle: AppHandle,
+    state: Arc<AppState>,
+    /// Method and path of relayed jobs still running
+    jobs: HashMap<String, (String, String)>,
+    finished_agents: HashSet<String>,
+}
+
+impl Notifier {
+    pub fn new(handle: AppHandle, state: Arc<AppState>) -> Self {
+        Self {
+            handle,
+            state,
+            jobs: HashMap::new(),
+            finished_agents: HashSet::new(),
+        }
+    }
+
+    pub fn on_event(&mut self, event: &NodeEvent) {
+        let toggles = load(&self.state.store.settings());
+        match event {
+            NodeEvent::JobStarted { job_id, method, path } => {
+                if method.eq_ignore_ascii_case("GET") {
+                    return;
+                }
+                self.jobs.insert(job_id.clone(), (method.clone(), path.clone()));
+                if toggles.job_assigned {
+                    self.show("Job assigned", &format!("{} {}", method, path));
+                }
+            }
+            NodeEvent::JobFinished { job_id, status, duration_ms } => {
+                let Some((method, path)) = self.jobs.remove(job_id) else {
+                    return;
+                };
+                let secs = *duration_ms as f64 / 1000.0;
+                if *status >= 500 {
+                    if toggles.job_failed {
+                        self.show("Job failed", &format!("{} {} failed with status {} after {:.1}s", method, path, status, secs));
+                    }
+                } else if toggles.job_completed {
+                    self.show("Job completed", &format!("{} {} finished in {:.1}s", method, path, secs));
+                }
+            }
+            NodeEvent::AgentProgress { execution_id, status, message, .. } => {
+                let title = match status {
+                    AgentStatus::Completed => "Agent finished",
+                    AgentStatus::Failed => "Agent failed",
+                    AgentStatus::Blocked => "Agent stopped at its budget",
+                    _ => return,
+                };
+                if !self.finished_agents.insert(execution_id.clone()) {
+                    return;
+                }
+                if self.finished_agents.len() > MAX_FINISHED_AGENTS {
+                    self.finished_agents.clear();
+                }
+                if toggles.agent_finished {
+                    let body = if message.is_empty() { format!("Execution {}", execution_id) } else { message.clone() };
+                    self.show(title, &body);
+                }
+            }
+            NodeEvent::EarningAdded { amount_cents, currency, .. } => {
+                if !toggles.earnings_milestones {
+                    return;
+                }
+                let Ok(totals) = self.state.earnings.totals(None) else {
+                    return;
+                };
+                let Some(total) = totals.iter().find(|total| &total.currency == currency) else {
+                    return;
+                };
+                if let Some(milestone) = crossed_milestone(total.amount_cents - amount_cents, total.amount_cents) {
+                    self.show(
+                        "Earnings milestone",
+                        &format!("You have earned {:.2} {} with this node.", milestone as f64 / 100.0, currency),
+                    );
+                }
+            }
+            NodeEvent::GpuBudgetAlert { spent, budget, exceeded, destroyed, .. } => {
+                if !toggles.gpu_budget {
+                    return;
+                }
+                let title = if *exceeded { "GPU budget reached" } else { "GPU budget almost spent" };
+                let mut body = format!("${:.2} of your ${:.2} monthly GPU budget is spent.", spent, budget);
+                if !destroyed.is_empty() {
+                    body.push_str(&format!(" Destroyed {} running instance(s).", destroyed.len()));
+                }
+                self.show(title, &body);
+            }
+            NodeEvent::PaymentsOverdue { jobs, unpaid_cents } => {
+                if toggles.unpaid_jobs {
+                    let body = format!(
+                        "{} job(s) are past their payment grace period; ${:.2} is outstanding.",
+                        jobs.len(),
+                        *unpaid_cents as f64 / 100.0
+                    );
+                    self.show("Unpaid jobs", &body);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn on_ollama_health(&self, event: &OllamaHealthEvent) {
+        let title = match event.health {
+            OllamaHealth::Crashed => "Ollama crashed",
+            OllamaHealth::RestartFailed => "Ollama failed to restart",
+            _ => return,
+        };
+        if !load(&self.state.store.settings()).service_crashes {
+            return;
+        }
+        let body = event.message.clone().unwrap_or_else(|| match event.health {
+            OllamaHealth::Crashed => "Restarting it.".to_string(),
+            _ => "Trying again shortly.".to_string(),
+        });
+        self.show(title, &body);
+    }
+
+    fn show(&self, title: &str, body: &str) {
+        if let Err(e) = self.handle.notification().builder().title(title).body(body).show() {
+            log::warn!("Failed to show notification: {}", e);
+        }
+    }
+}
+
+/// Highest milestone passed going from `before` to `after`
+fn crossed_milestone(before: i64, after: i64) -> Option<i64> {
+    let last = *MILESTONES_CENTS.last()?;
+    let stepped = (after / MILESTONE_STEP_CENTS) * MILESTONE_STEP_CENTS;
+    let candidate = if stepped > last {
+        stepped
+    } else {
+        MILESTONES_CENTS.iter().copied().filter(|m| *m <= after).max()?
+    };
+    (candidate > before).then_some(candidate)
+}
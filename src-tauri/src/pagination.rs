@@ -0,0 +1,196 @@
+//! List Pagination
+//!
+//! Every list endpoint and command takes the same `limit`, `offset`, `sort`
+//! and `filter` parameters and answers with a page: the items plus the
+//! total that matched, so the UI can page through containers, models or
+//! job history without loading all of it.
+//!
+//! Lists held in memory (containers, models, workspaces) are paged with
+//! `ListQuery::apply`; lists read from SQLite (agent executions, jobs) page
+//! in the query, with `ListQuery::order` and `ListQuery::condition`
+//! mapping sort and filter fields onto columns.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+use crate::error::NodeError;
+
+/// Page size when the request gives none
+pub const DEFAULT_LIMIT: u32 = 100;
+/// Largest page a request may ask for
+pub const MAX_LIMIT: u32 = 1000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    /// Field to sort by, named as in the response; a leading `-` sorts
+    /// descending, e.g. `-createdAt`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    /// `field:text` keeps items whose field contains the text; bare text
+    /// matches any text field. Case-insensitive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+}
+
+/// One page of a list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Items matching the filter, across all pages
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl<T: Serialize> Page<T> {
+    /// The page as an API response body, with the items under `key`
+    pub fn to_json(&self, key: &str) -> Value {
+        let mut body = serde_json::Map::new();
+        body.insert(key.to_string(), serde_json::to_value(&self.items).unwrap_or_default());
+        body.insert("total".into(), self.total.into());
+        body.insert("offset".into(), self.offset.into());
+        body.insert("limit".into(), self.limit.into());
+        Value::Object(body)
+    }
+}
+
+impl ListQuery {
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset.unwrap_or(0) as usize
+    }
+
+    /// Sort field and whether it is descending
+    pub fn sort_field(&self) -> Option<(&str, bool)> {
+        let sort = self.sort.as_deref()?.trim();
+        let (field, descending) = match sort.strip_prefix('-') {
+            Some(field) => (field, true),
+            None => (sort, false),
+        };
+        (!field.is_empty()).then_some((field, descending))
+    }
+
+    /// Field (if one was named) and text to look for
+    pub fn filter_term(&self) -> Option<(Option<&str>, &str)> {
+        let filter = self.filter.as_deref()?.trim();
+        if filter.is_empty() {
+            return None;
+        }
+        Some(match filter.split_once(':') {
+            Some((field, text)) if !field.is_empty() && !field.contains(' ') => (Some(field), text),
+            _ => (None, filter),
+        })
+    }
+
+    /// `ORDER BY` clause for a table, from the columns that may be sorted
+    /// on (response field, SQL expression); `default` applies without `sort`
+    pub fn order(&self, columns: &[(&str, &str)], default: &str) -> Result<String, NodeError> {
+        let Some((field, descending)) = self.sort_field() else {
+            return Ok(default.to_string());
+        };
+        let column = column(columns, field, "sort")?;
+        Ok(format!("ORDER BY {} {}", column, if descending { "DESC" } else { "ASC" }))
+    }
+
+    /// SQL condition for the filter over the columns that may be filtered
+    /// on, reading the text from parameter `?{param}`, and the text to bind
+    /// there; the condition holds for every row without a filter
+    pub fn condition(&self, columns: &[(&str, &str)], param: usize) -> Result<(String, Option<String>), NodeError> {
+        let Some((field, text)) = self.filter_term() else {
+            return Ok((format!("?{} IS NULL", param), None));
+        };
+        let expressions = match field {
+            Some(field) => vec![column(columns, field, "filter")?],
+            None => columns.iter().map(|(_, expression)| *expression).collect(),
+        };
+        let matches: Vec<String> = expressions
+            .iter()
+            .map(|e| format!("CAST({} AS TEXT) LIKE '%' || ?{} || '%'", e, param))
+            .collect();
+        Ok((format!("({})", matches.join(" OR ")), Some(text.to_string())))
+    }
+
+    /// Filter, sort and cut a page out of a list held in memory
+    pub fn apply<T: Serialize>(&self, items: Vec<T>) -> Page<T> {
+        let mut items: Vec<(Value, T)> = items
+            .into_iter()
+            .map(|item| (serde_json::to_value(&item).unwrap_or_default(), item))
+            .collect();
+
+        if let Some((field, text)) = self.filter_term() {
+            let text = text.to_lowercase();
+            items.retain(|(value, _)| match field {
+                Some(field) => field_value(value, field).is_some_and(|v| contains(v, &text)),
+                None => value
+                    .as_object()
+                    .is_some_and(|fields| fields.values().any(|v| v.is_string() && contains(v, &text))),
+            });
+        }
+
+        if let Some((field, descending)) = self.sort_field() {
+            items.sort_by(|(a, _), (b, _)| {
+                compare(field_value(a, field), field_value(b, field), descending)
+            });
+        }
+
+        let total = items.len();
+        let (offset, limit) = (self.offset(), self.limit());
+        let items = items.into_iter().skip(offset).take(limit).map(|(_, item)| item).collect();
+        Page { items, total, offset, limit }
+    }
+}
+
+fn column<'a>(columns: &[(&str, &'a str)], field: &str, action: &str) -> Result<&'a str, NodeError> {
+    columns
+        .iter()
+        .find(|(name, _)| *name == field)
+        .map(|(_, column)| *column)
+        .ok_or_else(|| {
+            let names: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+            NodeError::Invalid(format!("Cannot {} by {} (expected one of: {})", action, field, names.join(", ")))
+        })
+}
+
+/// A field of an item; dots reach into nested objects, e.g. `state.status`
+fn field_value<'a>(value: &'a Value, field: &str) -> Option<&'a Value> {
+    field.split('.').try_fold(value, |value, key| value.get(key))
+}
+
+fn contains(value: &Value, text: &str) -> bool {
+    match value {
+        Value::String(s) => s.to_lowercase().contains(text),
+        Value::Number(_) | Value::Bool(_) => value.to_string().contains(text),
+        _ => false,
+    }
+}
+
+/// Items without the field go last either way
+fn compare(a: Option<&Value>, b: Option<&Value>, descending: bool) -> Ordering {
+    let (a, b) = match (a.filter(|v| !v.is_null()), b.filter(|v| !v.is_null())) {
+        (None, None) => return Ordering::Equal,
+        (None, Some(_)) => return Ordering::Greater,
+        (Some(_), None) => return Ordering::Less,
+        (Some(a), Some(b)) => (a, b),
+    };
+    let ordering = match (a, b) {
+        (Value::Number(x), Value::Number(y)) => {
+            x.as_f64().unwrap_or(0.0).total_cmp(&y.as_f64().unwrap_or(0.0))
+        }
+        (Value::String(x), Value::String(y)) => x.to_lowercase().cmp(&y.to_lowercase()),
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        _ => a.to_string().cmp(&b.to_string()),
+    };
+    if descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
@@ -1,11 +1,34 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
 use uuid::Uuid;
 use chrono::Utc;
+use opentelemetry::{trace::SpanKind, KeyValue};
 
-use super::OllamaManager;
+use super::llm_provider::{LlmProvider, ProviderPrice, ProviderRegistry, AUTO_PROVIDER_ID, OLLAMA_PROVIDER_ID};
+use super::remote_compute::REMOTE_COMPUTE_PREFIX;
+use super::agent_memory::MemoryEntry;
+use super::agent_templates::{self, AgentTemplate};
+use super::{AgentMemory, AgentStore, ExecutionQuery, IpfsManager, OllamaManager, MAX_GET_SIZE, ToolContext, ToolRegistry, WorkspaceManager};
+use crate::error::NodeError;
+use crate::pagination::{ListQuery, Page};
+use crate::models::NodeEvent;
+use crate::telemetry;
+
+/// Reason/act/observe cycles an agent gets unless the request says otherwise
+pub const DEFAULT_MAX_ITERATIONS: u32 = 8;
+/// Total prompt + completion tokens an agent may spend unless the request says otherwise
+pub const DEFAULT_TOKEN_BUDGET: u32 = 32_000;
+/// Memory snippets of earlier executions recalled into a new one's prompt
+const RECALLED_MEMORIES: usize = 8;
+/// Longest goal, observation or result kept in memory
+const MAX_MEMORY_CHARS: usize = 1_000;
+/// `agent_type` of an execution that runs sub-agents in turn
+pub const PIPELINE_AGENT_TYPE: &str = "pipeline";
+/// How often a pipeline folds its running step's progress into its own
+const PIPELINE_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentAction {
@@ -29,6 +52,49 @@ pub enum AgentStatus {
     PullingModel,
 }
 
+/// Part a sub-agent plays in a pipeline
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentRole {
+    Planner,
+    Coder,
+    Reviewer,
+}
+
+impl AgentRole {
+    /// The default pipeline, in order
+    pub const ALL: [AgentRole; 3] = [AgentRole::Planner, AgentRole::Coder, AgentRole::Reviewer];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AgentRole::Planner => "planner",
+            AgentRole::Coder => "coder",
+            AgentRole::Reviewer => "reviewer",
+        }
+    }
+
+    /// Added to the system prompt of a sub-agent in this role
+    fn instructions(&self) -> &'static str {
+        match self {
+            AgentRole::Planner => "You are the planner of a team of agents. Break the goal down into concrete, ordered steps \
+                for the coder that follows you, noting files to create and how to check the work. \
+                Do not carry out the plan yourself; your final answer is the plan.",
+            AgentRole::Coder => "You are the coder of a team of agents. Carry out the plan you are given: write the files \
+                in your working directory and run them to check they work. \
+                Your final answer says what you built, where it is, and how you tested it.",
+            AgentRole::Reviewer => "You are the reviewer of a team of agents. Check the work in your working directory \
+                against the goal and the plan, run it, and fix small problems yourself. \
+                Your final answer is your verdict and any issues that remain.",
+        }
+    }
+}
+
+impl std::fmt::Display for AgentRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentExecution {
@@ -49,6 +115,9 @@ pub struct AgentExecution {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub security_alerts: Option<Vec<String>>,
     pub tokens_used: u32,
+    /// What the model calls cost on paid providers
+    #[serde(default)]
+    pub cost_cents: f64,
     pub iterations: u32,
     pub created_at: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -57,19 +126,34 @@ pub struct AgentExecution {
     pub compute_source: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub task_category: Option<String>,
+    /// Snapshot of the working directory, added to IPFS when the execution ended
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sandbox_cid: Option<String>,
+    /// Execution whose snapshot the working directory started from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resumed_from: Option<String>,
+    /// Pipeline this execution is a step of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<AgentRole>,
+    /// Steps of a pipeline, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<String>,
+    /// Container the agent's shell commands ran in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox_container_id: Option<String>,
 }
 
 impl AgentExecution {
-    pub fn new(workspace_id: &str, goal: &str, model: &str) -> Self {
+    pub fn new(workspace_id: &str, goal: &str, model: &str, provider: &str) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
             workspace_id: workspace_id.to_string(),
             goal: goal.to_string(),
-            agent_type: "react".to_string(),
+            agent_type: agent_templates::DEFAULT_TEMPLATE.to_string(),
             model: model.to_string(),
-            provider: "ollama".to_string(),
+            provider: provider.to_string(),
             status: AgentStatus::Pending,
             progress: 0,
             progress_message: "Initializing...".to_string(),
@@ -78,12 +162,25 @@ impl AgentExecution {
             error: None,
             security_alerts: None,
             tokens_used: 0,
+            cost_cents: 0.0,
             iterations: 0,
             created_at: Utc::now().to_rfc3339(),
             completed_at: None,
-            compute_source: Some("local".to_string()),
+            // A rented GPU is named by its attachment id
+            compute_source: Some(if provider == OLLAMA_PROVIDER_ID {
+                "local".to_string()
+            } else if provider.starts_with(REMOTE_COMPUTE_PREFIX) {
+                provider.to_string()
+            } else {
+                "remote".to_string()
+            }),
             task_category: None,
             sandbox_cid: None,
+            resumed_from: None,
+            parent_id: None,
+            role: None,
+            children: Vec::new(),
+            sandbox_container_id: None,
         }
     }
 }
@@ -93,55 +190,231 @@ pub struct CreateAgentRequest {
     pub goal: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
+    /// Template to run (`research`, `code-runner`, ...); `react` when unset
     #[serde(skip_serializing_if = "Option::is_none")]
     pub agent_type: Option<String>,
+    /// Configured LLM provider to run on (defaults to the local Ollama;
+    /// `auto` picks a backend serving the model)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Upper bound on reason/act/observe cycles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_iterations: Option<u32>,
+    /// Upper bound on tokens spent across all model calls
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Upper bound on what model calls may cost, priced by the provider;
+    /// unbounded when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cost_cents: Option<f64>,
+    /// Earlier execution in the workspace to continue from: its snapshot
+    /// becomes the new working directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_from: Option<String>,
+    /// Run the goal as a pipeline of sub-agents with these roles, in order;
+    /// empty for planner, coder and reviewer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<Vec<AgentRole>>,
+}
+
+/// A piece of streamed agent output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentOutputChunk {
+    pub execution_id: String,
+    /// Model call the chunk belongs to (starts at 1)
+    pub iteration: u32,
+    pub content: String,
+    pub done: bool,
 }
 
 pub struct AgentManager {
+    /// Executions still in flight; finished ones live only in the store
     executions: Arc<RwLock<HashMap<String, AgentExecution>>>,
+    /// Cancellation signals of running agent tasks
+    cancels: Arc<std::sync::Mutex<HashMap<String, oneshot::Sender<()>>>>,
+    store: Arc<AgentStore>,
+    memory: Arc<AgentMemory>,
     ollama: Arc<OllamaManager>,
+    ipfs: Arc<IpfsManager>,
+    providers: Arc<ProviderRegistry>,
+    workspaces: Arc<WorkspaceManager>,
+    tools: Arc<ToolRegistry>,
+    output_tx: broadcast::Sender<AgentOutputChunk>,
+    events: broadcast::Sender<NodeEvent>,
 }
 
 impl AgentManager {
-    pub fn new(ollama: Arc<OllamaManager>) -> Self {
+    pub fn new(
+        ollama: Arc<OllamaManager>,
+        ipfs: Arc<IpfsManager>,
+        providers: Arc<ProviderRegistry>,
+        workspaces: Arc<WorkspaceManager>,
+        tools: Arc<ToolRegistry>,
+        store: Arc<AgentStore>,
+        memory: Arc<AgentMemory>,
+        events: broadcast::Sender<NodeEvent>,
+    ) -> Self {
+        match store.fail_interrupted() {
+            Ok(0) => {}
+            Ok(n) => log::warn!("Marked {} interrupted agent executions as failed", n),
+            Err(e) => log::error!("Failed to clean up interrupted agent executions: {}", e),
+        }
+
+        let (output_tx, _) = broadcast::channel(1024);
         Self {
             executions: Arc::new(RwLock::new(HashMap::new())),
+            cancels: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            store,
+            memory,
             ollama,
+            ipfs,
+            providers,
+            workspaces,
+            tools,
+            output_tx,
+            events,
         }
     }
 
-    pub async fn list_executions(&self, workspace_id: &str) -> Vec<AgentExecution> {
-        let executions = self.executions.read().await;
-        executions
-            .values()
-            .filter(|e| e.workspace_id == workspace_id)
-            .cloned()
-            .collect()
+    /// Subscribe to streamed output of all executions
+    pub fn subscribe_output(&self) -> broadcast::Receiver<AgentOutputChunk> {
+        self.output_tx.subscribe()
+    }
+
+    /// A page of a workspace's executions, newest first unless sorted otherwise
+    pub async fn list_executions(
+        &self,
+        workspace_id: &str,
+        query: &ExecutionQuery,
+        page: &ListQuery,
+    ) -> Result<Page<AgentExecution>, NodeError> {
+        let mut stored = self.store.list(workspace_id, query, page)?;
+
+        // In-flight executions carry output that is not persisted yet
+        let live = self.executions.read().await;
+        for exec in stored.items.iter_mut() {
+            if let Some(current) = live.get(&exec.id) {
+                *exec = current.clone();
+            }
+        }
+        Ok(stored)
     }
 
     pub async fn get_execution(&self, execution_id: &str) -> Option<AgentExecution> {
-        let executions = self.executions.read().await;
-        executions.get(execution_id).cloned()
+        if let Some(exec) = self.executions.read().await.get(execution_id) {
+            return Some(exec.clone());
+        }
+        self.store.get(execution_id).unwrap_or_else(|e| {
+            log::error!("Failed to load execution {}: {}", execution_id, e);
+            None
+        })
     }
 
     pub async fn create_execution(
         &self,
         workspace_id: &str,
         req: CreateAgentRequest,
-    ) -> Result<AgentExecution, String> {
-        // Determine model to use
-        let model = match &req.model {
-            Some(m) if !m.is_empty() && m != "auto" => m.clone(),
+    ) -> Result<AgentExecution, NodeError> {
+        if let Some(roles) = req.pipeline.clone() {
+            return self.create_pipeline(workspace_id, req, roles).await;
+        }
+
+        let template = agent_templates::find(req.agent_type.as_deref())?;
+        let (provider_id, provider, model) = self.resolve(workspace_id, &req, template).await?;
+        let resume = self.resume_snapshot(workspace_id, &req).await?;
+        let mut execution = AgentExecution::new(workspace_id, &req.goal, &model, &provider_id);
+        execution.agent_type = template.id.to_string();
+        execution.resumed_from = resume.as_ref().map(|(previous, _)| previous.clone());
+        let task = self.prepare(&execution, &req, template, provider, resume.map(|(_, cid)| cid)).await?;
+
+        let execution_id = execution.id.clone();
+        self.spawn(&execution_id, &model, move |cancel_rx| run_agent(task, cancel_rx));
+
+        // Return current state
+        let executions = self.executions.read().await;
+        Ok(executions.get(&execution.id).cloned().unwrap_or(execution))
+    }
+
+    /// Run the goal through sub-agents with the given roles in turn, each
+    /// handed the results and working directory of the steps before it.
+    /// The returned execution is the pipeline itself; its steps are its children.
+    async fn create_pipeline(
+        &self,
+        workspace_id: &str,
+        req: CreateAgentRequest,
+        roles: Vec<AgentRole>,
+    ) -> Result<AgentExecution, NodeError> {
+        let roles = if roles.is_empty() { AgentRole::ALL.to_vec() } else { roles };
+        let template = agent_templates::find(req.agent_type.as_deref())?;
+        let (provider_id, provider, model) = self.resolve(workspace_id, &req, template).await?;
+        let resume = self.resume_snapshot(workspace_id, &req).await?;
+
+        let mut pipeline = AgentExecution::new(workspace_id, &req.goal, &model, &provider_id);
+        pipeline.agent_type = PIPELINE_AGENT_TYPE.to_string();
+        pipeline.resumed_from = resume.as_ref().map(|(previous, _)| previous.clone());
+
+        let mut steps = Vec::with_capacity(roles.len());
+        for (i, role) in roles.into_iter().enumerate() {
+            let mut step = AgentExecution::new(workspace_id, &req.goal, &model, &provider_id);
+            step.agent_type = template.id.to_string();
+            step.parent_id = Some(pipeline.id.clone());
+            step.role = Some(role);
+            let snapshot = if i == 0 { resume.as_ref().map(|(_, cid)| cid.clone()) } else { None };
+            pipeline.children.push(step.id.clone());
+            steps.push(self.prepare(&step, &req, template, Arc::clone(&provider), snapshot).await?);
+        }
+        let parent = self.prepare(&pipeline, &req, template, provider, None).await?;
+
+        let execution_id = pipeline.id.clone();
+        self.spawn(&execution_id, &model, move |cancel_rx| run_pipeline(parent, steps, cancel_rx));
+
+        let executions = self.executions.read().await;
+        Ok(executions.get(&pipeline.id).cloned().unwrap_or(pipeline))
+    }
+
+    /// Provider and model to run a request on: the request's, then the
+    /// workspace default, then a sensible pick for the template
+    async fn resolve(
+        &self,
+        workspace_id: &str,
+        req: &CreateAgentRequest,
+        template: &AgentTemplate,
+    ) -> Result<(String, Arc<dyn LlmProvider>, String), NodeError> {
+        let requested = match req.model.as_deref() {
+            Some(m) if !m.is_empty() && m != "auto" => Some(m.to_string()),
+            _ => self.workspaces.get(workspace_id).await.and_then(|w| w.default_model),
+        };
+
+        let provider_id = match req.provider.as_deref() {
+            // Route to whichever backend serves the model and is least busy
+            Some(AUTO_PROVIDER_ID) => match &requested {
+                Some(model) => self.providers.route(model).await?,
+                None => OLLAMA_PROVIDER_ID.to_string(),
+            },
+            Some(p) if !p.is_empty() => p.to_string(),
+            _ => OLLAMA_PROVIDER_ID.to_string(),
+        };
+        let provider = self.providers.get(&provider_id).await?;
+        let model = match requested {
+            Some(m) => m,
+            _ if provider_id != OLLAMA_PROVIDER_ID => {
+                self.providers.get_config(&provider_id).await
+                    .and_then(|c| c.default_model)
+                    .ok_or_else(|| NodeError::Invalid(format!("No model given and provider {} has no default model", provider_id)))?
+            }
             _ => {
                 // Auto-select: try to find a good model
-                let models = self.ollama.list_models().await.map_err(|e| e.to_string())?;
+                let models = self.ollama.list_models().await?;
                 if models.is_empty() {
-                    return Err("No Ollama models available. Please pull a model first.".to_string());
+                    return Err(NodeError::NotInstalled("No Ollama models available. Please pull a model first.".to_string()));
                 }
-                // Prefer llama3.2, mistral, or first available
-                models
+                // Prefer what suits the template, then llama3.2, mistral, or first available
+                template
+                    .model_hints
                     .iter()
-                    .find(|m| m.name.contains("llama3"))
+                    .find_map(|hint| models.iter().find(|m| m.name.starts_with(hint)))
+                    .or_else(|| models.iter().find(|m| m.name.contains("llama3")))
                     .or_else(|| models.iter().find(|m| m.name.contains("mistral")))
                     .or_else(|| models.first())
                     .map(|m| m.name.clone())
@@ -149,161 +422,940 @@ impl AgentManager {
             }
         };
 
-        let execution = AgentExecution::new(workspace_id, &req.goal, &model);
-        let execution_id = execution.id.clone();
+        Ok((provider_id, provider, model))
+    }
 
-        // Store execution
-        {
-            let mut executions = self.executions.write().await;
-            executions.insert(execution_id.clone(), execution.clone());
-        }
+    /// Execution to resume from and its snapshot, when the request names
+    /// one; it must be of the same workspace
+    async fn resume_snapshot(
+        &self,
+        workspace_id: &str,
+        req: &CreateAgentRequest,
+    ) -> Result<Option<(String, String)>, NodeError> {
+        Ok(match req.resume_from.as_deref() {
+            Some(previous) => {
+                let exec = self
+                    .get_execution(previous)
+                    .await
+                    .filter(|exec| exec.workspace_id == workspace_id)
+                    .ok_or_else(|| NodeError::NotFound(format!("Execution {} not found in this workspace", previous)))?;
+                let cid = exec
+                    .sandbox_cid
+                    .ok_or_else(|| NodeError::Invalid(format!("Execution {} has no snapshot to resume from", previous)))?;
+                Some((previous.to_string(), cid))
+            }
+            None => None,
+        })
+    }
 
-        // Run agent in background
-        let executions = Arc::clone(&self.executions);
-        let goal = req.goal.clone();
+    /// Store a new execution and build the task that runs it
+    async fn prepare(
+        &self,
+        execution: &AgentExecution,
+        req: &CreateAgentRequest,
+        template: &'static AgentTemplate,
+        provider: Arc<dyn LlmProvider>,
+        snapshot: Option<String>,
+    ) -> Result<AgentTask, NodeError> {
+        self.store.save(execution)?;
+        self.executions.write().await.insert(execution.id.clone(), execution.clone());
 
+        Ok(AgentTask {
+            executions: Arc::clone(&self.executions),
+            store: Arc::clone(&self.store),
+            memory: Arc::clone(&self.memory),
+            ollama: Arc::clone(&self.ollama),
+            ipfs: Arc::clone(&self.ipfs),
+            snapshot,
+            output_tx: self.output_tx.clone(),
+            events: self.events.clone(),
+            provider,
+            tools: match template.tools {
+                Some(names) => Arc::new(self.tools.only(names)),
+                None => Arc::clone(&self.tools),
+            },
+            instructions: template.system_prompt,
+            execution_id: execution.id.clone(),
+            workspace_id: execution.workspace_id.clone(),
+            goal: execution.goal.clone(),
+            role: execution.role,
+            model: execution.model.clone(),
+            max_iterations: req.max_iterations.or(template.max_iterations).unwrap_or(DEFAULT_MAX_ITERATIONS).max(1),
+            token_budget: req.max_tokens.unwrap_or(DEFAULT_TOKEN_BUDGET),
+            cost_budget_cents: req.max_cost_cents,
+            price: self.providers.get_config(&execution.provider).await.map(|c| c.price).unwrap_or_default(),
+        })
+    }
+
+    /// Run an execution in the background until it finishes or is cancelled
+    fn spawn<F, Fut>(&self, execution_id: &str, model: &str, run: F)
+    where
+        F: FnOnce(oneshot::Receiver<()>) -> Fut,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
         log::info!("Spawning agent task for execution {} with model {}", execution_id, model);
 
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        self.cancels.lock().unwrap().insert(execution_id.to_string(), cancel_tx);
+
+        let cancels = Arc::clone(&self.cancels);
+        let attributes = vec![
+            KeyValue::new("agent.execution_id", execution_id.to_string()),
+            KeyValue::new("gen_ai.request.model", model.to_string()),
+        ];
+        let execution_id = execution_id.to_string();
+        let run = run(cancel_rx);
         tokio::spawn(async move {
-            run_agent(executions, execution_id, goal, model).await;
+            let run = telemetry::in_span("agent.execution", SpanKind::Internal, attributes, run);
+            crate::logging::with_context(&[("execution_id", &execution_id)], run).await;
+            cancels.lock().unwrap().remove(&execution_id);
         });
-
-        // Return current state
-        let executions = self.executions.read().await;
-        Ok(executions.get(&execution.id).cloned().unwrap_or(execution))
     }
 
-    pub async fn cancel_execution(&self, execution_id: &str) -> Result<(), String> {
-        let mut executions = self.executions.write().await;
-        if let Some(exec) = executions.get_mut(execution_id) {
-            if exec.status == AgentStatus::Running || exec.status == AgentStatus::Pending {
-                exec.status = AgentStatus::Failed;
-                exec.error = Some("Cancelled by user".to_string());
-                exec.completed_at = Some(Utc::now().to_rfc3339());
-            }
+    /// Abort a running execution. The agent task stops its model request and
+    /// tools, cleans up, and then marks the execution as cancelled.
+    pub async fn cancel_execution(&self, execution_id: &str) -> Result<(), NodeError> {
+        let cancel_tx = self.cancels.lock().unwrap().remove(execution_id);
+        // Steps of a pipeline stop with it
+        let parent_id = self.executions.read().await.get(execution_id).and_then(|exec| exec.parent_id.clone());
+        let cancel_tx = cancel_tx.or_else(|| self.cancels.lock().unwrap().remove(parent_id.as_deref()?));
+        if let Some(tx) = cancel_tx {
+            let _ = tx.send(());
+            Ok(())
+        } else if self.get_execution(execution_id).await.is_some() {
+            // Already finished
             Ok(())
         } else {
-            Err("Execution not found".to_string())
+            Err(NodeError::NotFound("Execution not found".to_string()))
+        }
+    }
+
+    /// A page of what agents remember in a workspace, newest first unless sorted otherwise
+    pub fn list_memory(&self, workspace_id: &str, page: &ListQuery) -> Result<Page<MemoryEntry>, NodeError> {
+        self.memory.list(workspace_id, page)
+    }
+
+    /// Forget what agents learned in a workspace; returns how many entries
+    pub fn clear_memory(&self, workspace_id: &str) -> Result<usize, NodeError> {
+        Ok(self.memory.clear(workspace_id)?)
+    }
+
+    /// Number of executions currently in flight
+    pub async fn running_count(&self) -> usize {
+        self.executions.read().await.len()
+    }
+
+    /// Cancel every running execution and wait up to `timeout` for them to
+    /// clean up. Returns how many were still running when time ran out.
+    pub async fn shutdown(&self, timeout: Duration) -> usize {
+        let cancels: Vec<_> = self.cancels.lock().unwrap().drain().collect();
+        for (execution_id, tx) in cancels {
+            log::info!("Cancelling agent execution {}", execution_id);
+            let _ = tx.send(());
+        }
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let remaining = self.executions.read().await.len();
+            if remaining == 0 || tokio::time::Instant::now() >= deadline {
+                return remaining;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
         }
     }
 }
 
-async fn run_agent(
+/// Everything a background agent run needs
+struct AgentTask {
     executions: Arc<RwLock<HashMap<String, AgentExecution>>>,
+    store: Arc<AgentStore>,
+    memory: Arc<AgentMemory>,
+    ollama: Arc<OllamaManager>,
+    ipfs: Arc<IpfsManager>,
+    /// Snapshot the working directory is restored from before the run
+    snapshot: Option<String>,
+    output_tx: broadcast::Sender<AgentOutputChunk>,
+    events: broadcast::Sender<NodeEvent>,
+    provider: Arc<dyn LlmProvider>,
+    tools: Arc<ToolRegistry>,
+    /// Template instructions ahead of the tool descriptions
+    instructions: Option<&'static str>,
     execution_id: String,
+    workspace_id: String,
     goal: String,
+    /// Part played in a pipeline, if a step of one
+    role: Option<AgentRole>,
     model: String,
-) {
-    log::info!("Starting agent execution {} with model {}", execution_id, model);
+    max_iterations: u32,
+    token_budget: u32,
+    cost_budget_cents: Option<f64>,
+    /// What the provider charges per call
+    price: ProviderPrice,
+}
 
-    // Update status to running
-    {
-        let mut execs = executions.write().await;
-        if let Some(exec) = execs.get_mut(&execution_id) {
-            exec.status = AgentStatus::Running;
-            exec.progress = 10;
-            exec.progress_message = "Starting agent...".to_string();
+impl AgentTask {
+    /// Apply a change to the execution and persist it
+    async fn update(&self, f: impl FnOnce(&mut AgentExecution)) {
+        let mut execs = self.executions.write().await;
+        if let Some(exec) = execs.get_mut(&self.execution_id) {
+            f(exec);
+            if let Err(e) = self.store.save(exec) {
+                log::error!("Failed to persist execution {}: {}", self.execution_id, e);
+            }
+            self.progress(exec);
         }
     }
 
-    // Simple ReAct-style agent loop
-    let system_prompt = r#"You are a helpful AI assistant. Answer the user's question directly and concisely.
+    /// Tell subscribers where the execution stands
+    fn progress(&self, exec: &AgentExecution) {
+        let _ = self.events.send(NodeEvent::AgentProgress {
+            execution_id: exec.id.clone(),
+            status: exec.status.clone(),
+            progress: exec.progress,
+            message: exec.progress_message.clone(),
+        });
+    }
+
+    /// Apply a change to the in-memory execution only (streamed output)
+    async fn update_live(&self, f: impl FnOnce(&mut AgentExecution)) {
+        let mut execs = self.executions.write().await;
+        if let Some(exec) = execs.get_mut(&self.execution_id) {
+            f(exec);
+        }
+    }
+
+    /// Persist the execution one last time and drop it from memory
+    async fn finish(&self) {
+        let mut execs = self.executions.write().await;
+        if let Some(exec) = execs.remove(&self.execution_id) {
+            if let Err(e) = self.store.save(&exec) {
+                log::error!("Failed to persist execution {}: {}", self.execution_id, e);
+            }
+            self.progress(&exec);
+        }
+    }
+
+    /// Embedding of `text` for memory, or `None` when no embedding model is
+    /// available and recall falls back to recency
+    async fn embed(&self, text: &str) -> Option<Vec<f32>> {
+        match self.ollama.embed(&[text.to_string()]).await {
+            Ok(mut embeddings) => embeddings.pop(),
+            Err(e) => {
+                log::debug!("Not embedding agent memory: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Keep a goal, observation or result for later executions in the workspace
+    async fn remember(&self, kind: &str, content: &str, embedding: Option<Vec<f32>>) {
+        let content = clip(content, MAX_MEMORY_CHARS);
+        let embedding = match embedding {
+            Some(embedding) => Some(embedding),
+            None => self.embed(&content).await,
+        };
+        if let Err(e) = self.memory.record(&self.workspace_id, &self.execution_id, kind, &content, embedding.as_deref()) {
+            log::warn!("Failed to remember {} of execution {}: {}", kind, self.execution_id, e);
+        }
+    }
+
+    /// Unpack the snapshot to resume from into the working directory
+    async fn restore(&self, dir: &std::path::Path) -> Result<(), NodeError> {
+        let Some(cid) = &self.snapshot else {
+            return Ok(());
+        };
+        let parent = dir.parent().ok_or_else(|| NodeError::Invalid(format!("Invalid working directory {}", dir.display())))?;
+        // Unpack next to the working directory, then move it into place
+        let staging = parent.join(format!(".restore-{}", self.execution_id));
+        let result = async {
+            let restored = self.ipfs.get(cid, &staging, MAX_GET_SIZE).await?;
+            if !restored.is_dir() {
+                return Err(NodeError::Invalid(format!("Snapshot {} is not a directory", cid)));
+            }
+            let _ = std::fs::remove_dir_all(dir);
+            std::fs::rename(&restored, dir)
+                .map_err(|e| NodeError::Internal(format!("Failed to restore snapshot {}: {}", cid, e)))
+        }
+        .await;
+        let _ = std::fs::remove_dir_all(&staging);
+        result
+    }
+
+    /// Add the working directory to IPFS; `None` when the agent left nothing
+    async fn snapshot(&self, dir: &std::path::Path) -> Option<String> {
+        let empty = std::fs::read_dir(dir).map_or(true, |mut entries| entries.next().is_none());
+        if empty {
+            return None;
+        }
+        match self.ipfs.add_path(dir, true, false, None).await {
+            Ok(cid) => Some(cid),
+            Err(e) => {
+                log::warn!("Failed to snapshot the working directory of execution {}: {}", self.execution_id, e);
+                None
+            }
+        }
+    }
+
+    /// Start streaming a new model call into the execution's result
+    async fn begin_stream(&self, iteration: u32) -> OutputSink<'_> {
+        self.update_live(|exec| exec.result = None).await;
+        OutputSink { task: self, iteration }
+    }
+
+    fn finish_stream(&self, iteration: u32) {
+        let _ = self.output_tx.send(AgentOutputChunk {
+            execution_id: self.execution_id.clone(),
+            iteration,
+            content: String::new(),
+            done: true,
+        });
+    }
+}
+
+/// Delivers streamed tokens to the stored execution and to subscribers
+struct OutputSink<'a> {
+    task: &'a AgentTask,
+    iteration: u32,
+}
+
+impl OutputSink<'_> {
+    async fn push(&self, token: &str) {
+        self.task
+            .update_live(|exec| exec.result.get_or_insert_with(String::new).push_str(token))
+            .await;
+
+        let _ = self.task.output_tx.send(AgentOutputChunk {
+            execution_id: self.task.execution_id.clone(),
+            iteration: self.iteration,
+            content: token.to_string(),
+            done: false,
+        });
+    }
+}
+
+/// What the model asked for in one reply
+#[derive(Debug, Clone, PartialEq)]
+enum AgentReply {
+    /// Call a tool with the given JSON input
+    Tool { thought: String, tool: String, input: serde_json::Value },
+    /// The model is done
+    Final { thought: String, answer: String },
+}
+
+/// Prompt section with what earlier executions in the workspace learned,
+/// empty when there is nothing to recall
+fn memory_prompt(entries: &[MemoryEntry]) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+    let mut prompt = "Relevant memory from earlier tasks in this workspace:\n".to_string();
+    for entry in entries {
+        prompt.push_str(&format!("- [{} {}] {}\n", entry.created_at, entry.kind, entry.content));
+    }
+    prompt.push('\n');
+    prompt
+}
+
+/// `text` cut to at most `max` bytes on a character boundary
+fn clip(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut cut = max;
+    while !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    format!("{}...", &text[..cut])
+}
+
+/// Parse a model reply. Replies are expected to be a JSON object with either
+/// `tool`/`input` or `final_answer`; anything else is treated as a final answer.
+fn parse_reply(text: &str) -> AgentReply {
+    let json = text
+        .find('{')
+        .zip(text.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .and_then(|(start, end)| serde_json::from_str::<serde_json::Value>(&text[start..=end]).ok());
+
+    if let Some(json) = json {
+        let thought = json["thought"].as_str().unwrap_or("").to_string();
+        if let Some(tool) = json["tool"].as_str() {
+            return AgentReply::Tool {
+                thought,
+                tool: tool.to_string(),
+                input: json.get("input").cloned().unwrap_or(serde_json::Value::Null),
+            };
+        }
+        if let Some(answer) = json["final_answer"].as_str() {
+            return AgentReply::Final { thought, answer: answer.to_string() };
+        }
+    }
+
+    AgentReply::Final {
+        thought: String::new(),
+        answer: text.trim().to_string(),
+    }
+}
+
+fn build_system_prompt(tools: &ToolRegistry) -> String {
+    if tools.is_empty() {
+        return r#"You are a helpful AI assistant. Answer the user's question directly and concisely.
 If you need to think through the problem, explain your reasoning briefly.
-Provide a clear, actionable answer."#;
+Provide a clear, actionable answer."#.to_string();
+    }
 
-    let user_prompt = format!("Goal: {}\n\nPlease help me accomplish this goal.", goal);
+    format!(
+        r#"You are an autonomous agent running on an OtherThing node. Work towards the user's goal.
 
-    // Update progress
-    {
-        let mut execs = executions.write().await;
-        if let Some(exec) = execs.get_mut(&execution_id) {
-            exec.progress = 30;
-            exec.progress_message = format!("Sending request to {}...", model);
+You can use these tools (parameters are JSON schemas):
+{}
+
+To use a tool, reply with ONLY a JSON object:
+{{"thought": "<why you need the tool>", "tool": "<tool name>", "input": {{ ... }}}}
+
+When you can answer the goal, reply with ONLY a JSON object:
+{{"thought": "<short reasoning>", "final_answer": "<your answer>"}}"#,
+        serde_json::to_string_pretty(&tools.describe()).unwrap_or_default()
+    )
+}
+
+async fn run_agent(task: AgentTask, cancel_rx: oneshot::Receiver<()>) {
+    let execution_id = task.execution_id.clone();
+    let started = std::time::Instant::now();
+
+    log::info!("Starting agent execution {} with model {}", execution_id, task.model);
+
+    // Update status to running
+    task.update(|exec| {
+        exec.status = AgentStatus::Running;
+        exec.progress = 10;
+        exec.progress_message = "Starting agent...".to_string();
+    }).await;
+
+    let ctx = ToolContext::new(&execution_id, &task.workspace_id);
+
+    if task.snapshot.is_some() {
+        task.update(|exec| exec.progress_message = "Restoring snapshot...".to_string()).await;
+        if let Err(e) = task.restore(&ctx.workspace_dir).await {
+            log::error!("Agent {} failed: {}", execution_id, e);
+            task.update_live(|exec| {
+                exec.status = AgentStatus::Failed;
+                exec.progress = 100;
+                exec.progress_message = "Failed".to_string();
+                exec.error = Some(e.to_string());
+                exec.completed_at = Some(Utc::now().to_rfc3339());
+            }).await;
+            task.finish().await;
+            return;
         }
     }
 
-    log::info!("Calling Ollama API for execution {}", execution_id);
+    // What earlier executions in the workspace learned, by relevance to the goal
+    let goal_embedding = task.embed(&task.goal).await;
+    let recalled = task
+        .memory
+        .recall(&task.workspace_id, goal_embedding.as_deref(), RECALLED_MEMORIES)
+        .unwrap_or_else(|e| {
+            log::warn!("Failed to recall agent memory: {}", e);
+            Vec::new()
+        });
+    if !recalled.is_empty() {
+        log::info!("Recalled {} memories for execution {}", recalled.len(), execution_id);
+    }
+    let memory = memory_prompt(&recalled);
+
+    // Cancelling drops the loop future, which aborts the in-flight model
+    // request or tool invocation
+    let outcome = tokio::select! {
+        outcome = react_loop(&task, &ctx, &memory) => Some(outcome),
+        Ok(()) = cancel_rx => None,
+    };
+
+    // Tear down tool sandboxes before reporting the final state
+    task.tools.cleanup(&ctx).await;
+
+    if let Some(cid) = task.snapshot(&ctx.workspace_dir).await {
+        log::info!("Snapshot of execution {} is {}", execution_id, cid);
+        task.update_live(|exec| exec.sandbox_cid = Some(cid)).await;
+    }
+
+    let result = match &outcome {
+        Some(Ok(_)) => "completed",
+        Some(Err(Stop::Budget(_))) => "blocked",
+        Some(Err(Stop::Failed(e))) => {
+            telemetry::set_error(e);
+            "failed"
+        }
+        None => "cancelled",
+    };
+    telemetry::record_duration(
+        "otherthing.agent.execution.duration",
+        started.elapsed(),
+        &[
+            KeyValue::new("gen_ai.request.model", task.model.clone()),
+            KeyValue::new("outcome", result),
+        ],
+    );
+
+    task.remember("goal", &task.goal, goal_embedding).await;
+    match &outcome {
+        Some(Ok(response)) => task.remember("result", response, None).await,
+        Some(Err(Stop::Budget(e))) => task.remember("result", &format!("Stopped: {}", e), None).await,
+        Some(Err(Stop::Failed(e))) => task.remember("result", &format!("Failed: {}", e), None).await,
+        None => {}
+    }
 
-    // Call Ollama
-    match call_ollama(&model, &system_prompt, &user_prompt).await {
-        Ok((response, tokens)) => {
-            log::info!("Agent {} completed successfully with {} tokens", execution_id, tokens);
-            let mut execs = executions.write().await;
-            if let Some(exec) = execs.get_mut(&execution_id) {
+    match outcome {
+        Some(Ok(response)) => {
+            log::info!("Agent {} completed successfully", execution_id);
+            task.update_live(|exec| {
                 exec.status = AgentStatus::Completed;
                 exec.progress = 100;
                 exec.progress_message = "Completed".to_string();
-                exec.result = Some(response.clone());
-                exec.tokens_used = tokens;
-                exec.iterations = 1;
+                exec.result = Some(response);
                 exec.completed_at = Some(Utc::now().to_rfc3339());
-                exec.actions.push(AgentAction {
-                    thought: "Processing the goal and generating response".to_string(),
-                    tool: None,
-                    input: None,
-                    output: Some(response),
-                });
-            }
+            }).await;
+        }
+        Some(Err(Stop::Budget(e))) => {
+            log::warn!("Agent {} stopped: {}", execution_id, e);
+            task.update_live(|exec| {
+                exec.status = AgentStatus::Blocked;
+                exec.progress = 100;
+                exec.progress_message = "Budget exceeded".to_string();
+                exec.error = Some(e);
+                exec.completed_at = Some(Utc::now().to_rfc3339());
+            }).await;
         }
-        Err(e) => {
+        Some(Err(Stop::Failed(e))) => {
             log::error!("Agent {} failed: {}", execution_id, e);
-            let mut execs = executions.write().await;
-            if let Some(exec) = execs.get_mut(&execution_id) {
+            task.update_live(|exec| {
                 exec.status = AgentStatus::Failed;
                 exec.progress = 100;
                 exec.progress_message = "Failed".to_string();
                 exec.error = Some(e);
                 exec.completed_at = Some(Utc::now().to_rfc3339());
-            }
+            }).await;
+        }
+        None => {
+            log::info!("Agent {} cancelled", execution_id);
+            let mut iteration = 0;
+            task.update_live(|exec| {
+                iteration = exec.iterations;
+                exec.status = AgentStatus::Failed;
+                exec.progress = 100;
+                exec.progress_message = "Cancelled".to_string();
+                exec.error = Some("Cancelled by user".to_string());
+                exec.completed_at = Some(Utc::now().to_rfc3339());
+            }).await;
+            // The interrupted stream never got its `done` chunk
+            task.finish_stream(iteration);
         }
     }
+
+    task.finish().await;
 }
 
-async fn call_ollama(
-    model: &str,
-    system: &str,
-    prompt: &str,
-) -> Result<(String, u32), String> {
-    let client = reqwest::Client::new();
+/// Run the steps of a pipeline in turn. Each step's goal carries the
+/// results of those before it and starts from the previous step's working
+/// directory; the pipeline's progress combines its steps', and its result
+/// gathers theirs. The pipeline's token and cost budgets are shared, each
+/// step getting what the steps before it left over.
+async fn run_pipeline(pipeline: AgentTask, steps: Vec<AgentTask>, mut cancel_rx: oneshot::Receiver<()>) {
+    let execution_id = pipeline.execution_id.clone();
+    let total = steps.len() as u32;
+    log::info!("Starting pipeline {} with {} steps", execution_id, total);
+
+    pipeline.update(|exec| {
+        exec.status = AgentStatus::Running;
+        exec.progress = 0;
+    }).await;
+
+    let mut outputs: Vec<(AgentRole, String)> = Vec::new();
+    let mut snapshot: Option<String> = None;
+    let mut tokens_used = 0u32;
+    let mut cost_cents = 0.0f64;
+    let mut failure: Option<Stop> = None;
+    let mut cancel_pending = true;
+    let mut cancelled = false;
+
+    let mut steps = steps.into_iter();
+    for (index, mut step) in steps.by_ref().enumerate() {
+        let index = index as u32;
+        let role = step.role.unwrap_or(AgentRole::Coder);
+        step.goal = step_goal(&pipeline.goal, role, &outputs);
+        if snapshot.is_some() {
+            step.snapshot = snapshot.clone();
+        }
+        step.token_budget = pipeline.token_budget.saturating_sub(tokens_used);
+        step.cost_budget_cents = pipeline.cost_budget_cents.map(|budget| (budget - cost_cents).max(0.0));
+        let goal = step.goal.clone();
+        step.update(|exec| exec.goal = goal).await;
+        pipeline.update(|exec| {
+            exec.progress_message = format!("Step {}/{}: {}", index + 1, total, role);
+        }).await;
 
-    let ollama_host = std::env::var("OLLAMA_HOST").unwrap_or_else(|_| "http://localhost:11434".to_string());
-    let url = format!("{}/api/generate", ollama_host);
+        let step_id = step.execution_id.clone();
+        let (step_cancel_tx, step_cancel_rx) = oneshot::channel();
+        let mut step_cancel_tx = Some(step_cancel_tx);
+        let run = run_agent(step, step_cancel_rx);
+        tokio::pin!(run);
+        let mut ticker = tokio::time::interval(PIPELINE_PROGRESS_INTERVAL);
+        loop {
+            tokio::select! {
+                () = &mut run => break,
+                received = &mut cancel_rx, if cancel_pending => {
+                    cancel_pending = false;
+                    if received.is_ok() {
+                        cancelled = true;
+                        if let Some(tx) = step_cancel_tx.take() {
+                            let _ = tx.send(());
+                        }
+                    }
+                }
+                _ = ticker.tick() => {
+                    let step_progress = pipeline.executions.read().await.get(&step_id).map_or(0, |exec| exec.progress);
+                    let progress = ((index * 100 + u32::from(step_progress)) / total) as u8;
+                    let changed = pipeline.executions.read().await.get(&execution_id).is_some_and(|exec| exec.progress != progress);
+                    if changed {
+                        pipeline.update(|exec| exec.progress = progress).await;
+                    }
+                }
+            }
+        }
 
-    log::info!("Calling Ollama at {} with model {}", url, model);
+        let Some(finished) = pipeline.store.get(&step_id).ok().flatten() else {
+            failure = Some(Stop::Failed(format!("The {} step failed: unknown error", role)));
+            break;
+        };
+        tokens_used += finished.tokens_used;
+        cost_cents += finished.cost_cents;
+        pipeline.update(|exec| {
+            exec.tokens_used = tokens_used;
+            exec.cost_cents = cost_cents;
+        }).await;
+        if finished.status != AgentStatus::Completed {
+            let error = finished.error.unwrap_or_else(|| "unknown error".to_string());
+            failure = Some(match finished.status {
+                AgentStatus::Blocked => Stop::Budget(format!("The {} step stopped: {}", role, error)),
+                _ => Stop::Failed(format!("The {} step failed: {}", role, error)),
+            });
+            break;
+        }
+        if finished.sandbox_cid.is_some() {
+            snapshot = finished.sandbox_cid.clone();
+        }
+        let result = finished.result.unwrap_or_default();
+        pipeline.update(|exec| {
+            exec.progress = ((index + 1) * 100 / total) as u8;
+            exec.iterations = index + 1;
+            exec.actions.push(AgentAction {
+                thought: format!("The {} finished", role),
+                tool: None,
+                input: None,
+                output: Some(result.clone()),
+            });
+        }).await;
+        outputs.push((role, result));
+        if cancelled {
+            break;
+        }
+        let spent = tokens_used >= pipeline.token_budget
+            || pipeline.cost_budget_cents.is_some_and(|budget| cost_cents >= budget);
+        if spent && index + 1 < total {
+            failure = Some(Stop::Budget(format!("Budget exhausted after the {} step", role)));
+            break;
+        }
+    }
+
+    // Steps that never ran
+    for step in steps {
+        let reason = match &failure {
+            _ if cancelled => "Cancelled by user",
+            Some(Stop::Budget(_)) => "Skipped once the pipeline's budget was spent",
+            _ => "Skipped after an earlier step failed",
+        };
+        step.update_live(|exec| {
+            exec.status = AgentStatus::Failed;
+            exec.progress = 100;
+            exec.progress_message = "Skipped".to_string();
+            exec.error = Some(reason.to_string());
+            exec.completed_at = Some(Utc::now().to_rfc3339());
+        }).await;
+        step.finish().await;
+    }
 
-    let payload = serde_json::json!({
-        "model": model,
-        "prompt": prompt,
-        "system": system,
-        "stream": false,
+    let result = (!outputs.is_empty()).then(|| {
+        outputs
+            .iter()
+            .map(|(role, output)| format!("## {}\n\n{}", role, output))
+            .collect::<Vec<_>>()
+            .join("\n\n")
     });
+    pipeline.update_live(|exec| {
+        exec.progress = 100;
+        exec.result = result;
+        exec.sandbox_cid = snapshot;
+        exec.completed_at = Some(Utc::now().to_rfc3339());
+        if cancelled {
+            exec.status = AgentStatus::Failed;
+            exec.progress_message = "Cancelled".to_string();
+            exec.error = Some("Cancelled by user".to_string());
+        } else if let Some(Stop::Budget(e)) = failure {
+            exec.status = AgentStatus::Blocked;
+            exec.progress_message = "Budget exceeded".to_string();
+            exec.error = Some(e);
+        } else if let Some(Stop::Failed(e)) = failure {
+            exec.status = AgentStatus::Failed;
+            exec.progress_message = "Failed".to_string();
+            exec.error = Some(e);
+        } else {
+            exec.status = AgentStatus::Completed;
+            exec.progress_message = "Completed".to_string();
+        }
+    }).await;
+    log::info!("Pipeline {} finished", execution_id);
+    pipeline.finish().await;
+}
 
-    let response = client
-        .post(&url)
-        .json(&payload)
-        .timeout(std::time::Duration::from_secs(120))
-        .send()
-        .await
-        .map_err(|e| format!("Failed to connect to Ollama: {}", e))?;
+/// Goal of a pipeline step: the pipeline's, with what earlier steps produced
+fn step_goal(goal: &str, role: AgentRole, outputs: &[(AgentRole, String)]) -> String {
+    let mut step = format!("{}\n\nYou are the {} in this pipeline.", goal, role);
+    for (earlier, output) in outputs {
+        step.push_str(&format!("\n\nResult of the {} step:\n{}", earlier, output));
+    }
+    step
+}
+
+/// Why an agent loop ended without an answer
+#[derive(Debug)]
+enum Stop {
+    Failed(String),
+    /// A token, iteration or cost limit was reached
+    Budget(String),
+}
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let text = response.text().await.unwrap_or_default();
-        return Err(format!("Ollama returned error {}: {}", status, text));
+impl From<String> for Stop {
+    fn from(e: String) -> Self {
+        Stop::Failed(e)
+    }
+}
+
+impl std::fmt::Display for Stop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stop::Failed(e) | Stop::Budget(e) => f.write_str(e),
+        }
     }
+}
+
+/// Most tokens the next model call may generate: what is left of the token
+/// budget, and of the cost budget where the provider charges per token
+fn output_headroom(
+    token_budget: u32,
+    tokens_used: u32,
+    cost_budget_cents: Option<f64>,
+    cost_cents: f64,
+    price: &ProviderPrice,
+) -> u32 {
+    let tokens_left = token_budget.saturating_sub(tokens_used);
+    cost_budget_cents
+        .and_then(|budget| price.tokens_for(budget - cost_cents))
+        .map_or(tokens_left, |affordable| tokens_left.min(affordable))
+}
 
-    let data: serde_json::Value = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
+/// Reason/act/observe until the model gives a final answer or a limit is hit
+async fn react_loop(task: &AgentTask, ctx: &ToolContext, memory: &str) -> Result<String, Stop> {
+    let execution_id = &task.execution_id;
+    let model = &task.model;
+    let mut system_prompt = build_system_prompt(&task.tools);
+    if let Some(instructions) = task.instructions {
+        system_prompt = format!("{}\n\n{}", instructions, system_prompt);
+    }
+    if let Some(role) = task.role {
+        system_prompt = format!("{}\n\n{}", role.instructions(), system_prompt);
+    }
+    let mut transcript = format!("{}Goal: {}\n", memory, task.goal);
+    let mut tokens_used = 0u32;
+    let mut cost_cents = 0.0f64;
 
-    let response_text = data["response"]
-        .as_str()
-        .unwrap_or("No response")
-        .to_string();
+    for iteration in 1..=task.max_iterations {
+        if tokens_used >= task.token_budget {
+            return Err(Stop::Budget(format!(
+                "Token budget exhausted ({} of {} tokens used)",
+                tokens_used, task.token_budget
+            )));
+        }
+        if let Some(budget) = task.cost_budget_cents.filter(|budget| cost_cents >= *budget) {
+            return Err(Stop::Budget(format!(
+                "Cost budget exhausted ({:.2} of {:.2} cents spent)",
+                cost_cents, budget
+            )));
+        }
 
-    let tokens = data["eval_count"].as_u64().unwrap_or(0) as u32
-        + data["prompt_eval_count"].as_u64().unwrap_or(0) as u32;
+        let last = iteration == task.max_iterations;
+        task.update(|exec| {
+            exec.progress = (10 + 85 * (iteration - 1) / task.max_iterations) as u8;
+            exec.progress_message = format!(
+                "Iteration {}/{}: waiting for {}...",
+                iteration, task.max_iterations, model
+            );
+            exec.iterations = iteration;
+        }).await;
 
-    Ok((response_text, tokens))
+        let prompt = if last && iteration > 1 {
+            format!("{}\nThis is your last step. Give your final answer now.\n", transcript)
+        } else {
+            transcript.clone()
+        };
+
+        log::info!(
+            "Calling {} for execution {} (iteration {})",
+            task.provider.id(), execution_id, iteration
+        );
+
+        // Call the model, streaming tokens into the execution as they arrive
+        let sink = task.begin_stream(iteration).await;
+        let (token_tx, mut token_rx) = mpsc::channel::<String>(256);
+        let forward = async {
+            while let Some(token) = token_rx.recv().await {
+                sink.push(&token).await;
+            }
+        };
+        let attributes = vec![
+            KeyValue::new("gen_ai.system", task.provider.id().to_string()),
+            KeyValue::new("gen_ai.request.model", model.clone()),
+            KeyValue::new("agent.iteration", i64::from(iteration)),
+        ];
+        // The checks above run between calls; this keeps a single call within them
+        let max_tokens = output_headroom(task.token_budget, tokens_used, task.cost_budget_cents, cost_cents, &task.price);
+        let call = async {
+            let (response, ()) = tokio::join!(
+                task.provider.complete(model, &system_prompt, &prompt, Some(max_tokens), Some(token_tx)),
+                forward
+            );
+            match &response {
+                Ok(completion) => telemetry::set_attribute(KeyValue::new(
+                    "gen_ai.usage.total_tokens",
+                    i64::from(completion.tokens),
+                )),
+                Err(e) => telemetry::set_error(e),
+            }
+            response
+        };
+        let called = std::time::Instant::now();
+        let response = telemetry::in_span(format!("agent.llm {}", model), SpanKind::Client, attributes, call).await;
+        task.finish_stream(iteration);
+
+        let completion = response?;
+        let (text, tokens) = (completion.text, completion.tokens);
+        tokens_used += tokens;
+        cost_cents += task.price.cost_cents(tokens, called.elapsed());
+        task.update(|exec| {
+            exec.tokens_used = tokens_used;
+            exec.cost_cents = cost_cents;
+        }).await;
+
+        match parse_reply(&text) {
+            AgentReply::Tool { thought, tool, input } => {
+                task.update(|exec| {
+                    exec.progress_message = format!("Iteration {}: running {}", iteration, tool);
+                }).await;
+
+                let observation = match task.tools.execute(&tool, ctx, &input).await {
+                    Ok(output) => output,
+                    Err(e) => format!("Error: {}", e),
+                };
+
+                transcript.push_str(&format!(
+                    "\nThought: {}\nAction: {} {}\nObservation: {}\n",
+                    thought, tool, input, observation
+                ));
+                task.remember("observation", &format!("{} {}: {}", tool, input, observation), None).await;
+
+                let sandbox = ctx.sandbox_container_id();
+                task.update(|exec| {
+                    if sandbox.is_some() {
+                        exec.sandbox_container_id = sandbox;
+                    }
+                    exec.actions.push(AgentAction {
+                        thought,
+                        tool: Some(tool),
+                        input: Some(input.to_string()),
+                        output: Some(observation),
+                    });
+                }).await;
+            }
+            AgentReply::Final { thought, answer } => {
+                task.update(|exec| {
+                    exec.actions.push(AgentAction {
+                        thought: if thought.is_empty() {
+                            "Processing the goal and generating response".to_string()
+                        } else {
+                            thought
+                        },
+                        tool: None,
+                        input: None,
+                        output: Some(answer.clone()),
+                    });
+                }).await;
+                return Ok(answer);
+            }
+        }
+    }
+
+
+    Err(Stop::Budget(format!("No final answer after {} iterations", task.max_iterations)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::llm_provider::Completion;
+    use async_trait::async_trait;
+
+    /// Generates a long answer, or as much of it as it is allowed
+    struct Verbose;
+
+    #[async_trait]
+    impl LlmProvider for Verbose {
+        fn id(&self) -> &str {
+            "verbose"
+        }
+
+        async fn complete(
+            &self,
+            _model: &str,
+            _system: &str,
+            _prompt: &str,
+            max_tokens: Option<u32>,
+            _token_tx: Option<mpsc::Sender<String>>,
+        ) -> Result<Completion, String> {
+            Ok(Completion { text: "Final Answer: done".to_string(), tokens: max_tokens.unwrap_or(u32::MAX).min(5_000) })
+        }
+    }
+
+    #[tokio::test]
+    async fn a_call_that_would_overrun_the_token_budget_is_capped() {
+        let (budget, used) = (1_000, 900);
+        let max_tokens = output_headroom(budget, used, None, 0.0, &ProviderPrice::default());
+        let completion = Verbose.complete("model", "", "", Some(max_tokens), None).await.unwrap();
+        assert_eq!(max_tokens, 100);
+        assert!(used + completion.tokens <= budget);
+    }
+
+    #[tokio::test]
+    async fn a_call_that_would_overrun_the_cost_budget_is_capped() {
+        let price = ProviderPrice { per_1k_tokens_cents: 10.0, per_hour_cents: 0.0 };
+        let (budget, spent) = (5.0, 4.0);
+        let max_tokens = output_headroom(10_000, 0, Some(budget), spent, &price);
+        let completion = Verbose.complete("model", "", "", Some(max_tokens), None).await.unwrap();
+        assert_eq!(max_tokens, 100);
+        assert!(spent + price.cost_cents(completion.tokens, Duration::ZERO) <= budget + 1e-9);
+    }
+
+    #[test]
+    fn only_the_token_budget_caps_calls_not_charged_per_token() {
+        let price = ProviderPrice { per_1k_tokens_cents: 0.0, per_hour_cents: 100.0 };
+        assert_eq!(output_headroom(10_000, 2_500, Some(5.0), 4.0, &price), 7_500);
+    }
 }
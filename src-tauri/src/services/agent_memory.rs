@@ -0,0 +1,187 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::error::NodeError;
+use crate::pagination::{ListQuery, Page};
+
+/// Entries of a workspace considered when recalling, newest first
+const RECALL_CANDIDATES: usize = 200;
+/// Age at which an entry's recency score has halved
+const RECENCY_HALF_LIFE_HOURS: f64 = 72.0;
+/// Share of the score given to embedding similarity; the rest is recency
+const SIMILARITY_WEIGHT: f64 = 0.7;
+
+const SORT: &[(&str, &str)] = &[("createdAt", "created_at"), ("kind", "kind")];
+const FILTER: &[(&str, &str)] = &[("kind", "kind"), ("executionId", "execution_id"), ("content", "content")];
+
+/// What an agent learned while working in a workspace: its goal, a tool
+/// observation, or the final result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryEntry {
+    pub id: i64,
+    pub workspace_id: String,
+    pub execution_id: String,
+    /// `goal`, `observation` or `result`
+    pub kind: String,
+    pub content: String,
+    pub created_at: String,
+    #[serde(skip)]
+    pub embedding: Option<Vec<f32>>,
+}
+
+/// Persistent per-workspace memory of agent executions; a table of the
+/// node's state store
+pub struct AgentMemory {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl AgentMemory {
+    pub(super) fn new(conn: Arc<Mutex<Connection>>) -> Self {
+        Self { conn }
+    }
+
+    pub fn record(
+        &self,
+        workspace_id: &str,
+        execution_id: &str,
+        kind: &str,
+        content: &str,
+        embedding: Option<&[f32]>,
+    ) -> Result<(), String> {
+        let blob = embedding.map(vector_to_blob);
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO agent_memory (workspace_id, execution_id, kind, content, embedding, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![workspace_id, execution_id, kind, content, blob, Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| format!("Failed to record agent memory: {}", e))?;
+        Ok(())
+    }
+
+    /// Up to `limit` snippets of the workspace most worth remembering for a
+    /// task, scored by similarity to `query` (when embedded) and recency.
+    /// Returned oldest first, the order they are told to the model.
+    pub fn recall(&self, workspace_id: &str, query: Option<&[f32]>, limit: usize) -> Result<Vec<MemoryEntry>, String> {
+        let now = Utc::now();
+        let mut scored: Vec<(f64, MemoryEntry)> = self
+            .recent(workspace_id, RECALL_CANDIDATES)?
+            .into_iter()
+            .map(|entry| {
+                let age_hours = DateTime::parse_from_rfc3339(&entry.created_at)
+                    .map(|t| (now - t.with_timezone(&Utc)).num_seconds().max(0) as f64 / 3600.0)
+                    .unwrap_or(f64::MAX);
+                let recency = 0.5f64.powf(age_hours / RECENCY_HALF_LIFE_HOURS);
+                let score = match (query, entry.embedding.as_deref()) {
+                    (Some(q), Some(e)) if q.len() == e.len() => {
+                        SIMILARITY_WEIGHT * cosine(q, e) + (1.0 - SIMILARITY_WEIGHT) * recency
+                    }
+                    // Without embeddings, fall back to recency alone
+                    _ => (1.0 - SIMILARITY_WEIGHT) * recency,
+                };
+                (score, entry)
+            })
+            .collect();
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let mut recalled: Vec<MemoryEntry> = scored.into_iter().take(limit).map(|(_, entry)| entry).collect();
+        recalled.sort_by_key(|entry| entry.id);
+        Ok(recalled)
+    }
+
+    /// A page of a workspace's memory, newest first unless sorted otherwise
+    pub fn list(&self, workspace_id: &str, page: &ListQuery) -> Result<Page<MemoryEntry>, NodeError> {
+        let order = page.order(SORT, "ORDER BY id DESC")?;
+        let (condition, filter) = page.condition(FILTER, 2)?;
+        let selection = format!("FROM agent_memory WHERE workspace_id = ?1 AND {}", condition);
+
+        let conn = self.conn.lock().unwrap();
+        let total: i64 = conn
+            .query_row(&format!("SELECT COUNT(*) {}", selection), params![workspace_id, filter], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(&format!("SELECT {} {} {} LIMIT ?3 OFFSET ?4", COLUMNS, selection, order))
+            .map_err(|e| e.to_string())?;
+
+        let (limit, offset) = (page.limit(), page.offset());
+        let rows = stmt
+            .query_map(params![workspace_id, filter, limit as i64, offset as i64], entry_from_row)
+            .map_err(|e| e.to_string())?;
+        let items = rows
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read agent memory: {}", e))?;
+        Ok(Page { items, total: total.max(0) as usize, offset, limit })
+    }
+
+    /// Forget everything remembered in a workspace; returns how many entries
+    pub fn clear(&self, workspace_id: &str) -> Result<usize, String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM agent_memory WHERE workspace_id = ?1", params![workspace_id])
+            .map_err(|e| format!("Failed to clear agent memory: {}", e))
+    }
+
+    fn recent(&self, workspace_id: &str, limit: usize) -> Result<Vec<MemoryEntry>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM agent_memory WHERE workspace_id = ?1 ORDER BY id DESC LIMIT ?2",
+                COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![workspace_id, limit as i64], entry_from_row)
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to read agent memory: {}", e))
+    }
+}
+
+const COLUMNS: &str = "id, workspace_id, execution_id, kind, content, created_at, embedding";
+
+fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<MemoryEntry> {
+    let embedding: Option<Vec<u8>> = row.get(6)?;
+    Ok(MemoryEntry {
+        id: row.get(0)?,
+        workspace_id: row.get(1)?,
+        execution_id: row.get(2)?,
+        kind: row.get(3)?,
+        content: row.get(4)?,
+        created_at: row.get(5)?,
+        embedding: embedding.as_deref().map(vector_from_blob),
+    })
+}
+
+/// Embedding as stored in SQLite: little-endian `f32`s
+pub(super) fn vector_to_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+pub(super) fn vector_from_blob(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+/// Cosine similarity of two vectors of the same length
+pub fn cosine(a: &[f32], b: &[f32]) -> f64 {
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f64, 0.0f64, 0.0f64);
+    for (x, y) in a.iter().zip(b) {
+        let (x, y) = (f64::from(*x), f64::from(*y));
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a.sqrt() * norm_b.sqrt())
+    }
+}
@@ -0,0 +1,162 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use super::agent::{AgentExecution, AgentStatus};
+use crate::error::NodeError;
+use crate::pagination::{ListQuery, Page};
+
+/// Execution fields that can be sorted on, with their columns
+const SORT: &[(&str, &str)] = &[
+    ("createdAt", "created_at"),
+    ("status", "status"),
+    ("model", "json_extract(data, '$.model')"),
+    ("tokensUsed", "json_extract(data, '$.tokensUsed')"),
+];
+/// Execution fields that can be filtered on
+const FILTER: &[(&str, &str)] = &[
+    ("status", "status"),
+    ("goal", "json_extract(data, '$.goal')"),
+    ("model", "json_extract(data, '$.model')"),
+    ("provider", "json_extract(data, '$.provider')"),
+    ("agentType", "json_extract(data, '$.agentType')"),
+    ("parentId", "json_extract(data, '$.parentId')"),
+    ("role", "json_extract(data, '$.role')"),
+];
+
+/// Time range of stored executions to list; paging, sorting and other
+/// filters come in a `ListQuery`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecutionQuery {
+    /// Only executions created at or after this RFC 3339 timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// Only executions created before this RFC 3339 timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+}
+
+/// SQLite-backed record of agent executions, including their actions and
+/// results; a table of the node's state store
+pub struct AgentStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl AgentStore {
+    pub(super) fn new(conn: Arc<Mutex<Connection>>) -> Self {
+        Self { conn }
+    }
+
+    /// Insert or replace an execution
+    pub fn save(&self, exec: &AgentExecution) -> Result<(), String> {
+        let data = serde_json::to_string(exec).map_err(|e| e.to_string())?;
+        let status = serde_json::to_value(&exec.status)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default();
+
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO agent_executions (id, workspace_id, status, created_at, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![exec.id, exec.workspace_id, status, exec.created_at, data],
+            )
+            .map_err(|e| format!("Failed to save execution {}: {}", exec.id, e))?;
+        Ok(())
+    }
+
+    pub fn get(&self, execution_id: &str) -> Result<Option<AgentExecution>, String> {
+        let data: Option<String> = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT data FROM agent_executions WHERE id = ?1",
+                params![execution_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+
+        data.map(|d| serde_json::from_str(&d).map_err(|e| e.to_string()))
+            .transpose()
+    }
+
+    /// A page of a workspace's executions, newest first unless sorted otherwise
+    pub fn list(
+        &self,
+        workspace_id: &str,
+        query: &ExecutionQuery,
+        page: &ListQuery,
+    ) -> Result<Page<AgentExecution>, NodeError> {
+        let order = page.order(SORT, "ORDER BY created_at DESC")?;
+        let (condition, filter) = page.condition(FILTER, 4)?;
+        let selection = format!(
+            "FROM agent_executions
+             WHERE workspace_id = ?1
+               AND (?2 IS NULL OR created_at >= ?2)
+               AND (?3 IS NULL OR created_at < ?3)
+               AND {}",
+            condition
+        );
+
+        let conn = self.conn.lock().unwrap();
+        let total: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) {}", selection),
+                params![workspace_id, query.since, query.until, filter],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(&format!("SELECT data {} {} LIMIT ?5 OFFSET ?6", selection, order))
+            .map_err(|e| e.to_string())?;
+
+        let (limit, offset) = (page.limit(), page.offset());
+        let rows = stmt
+            .query_map(
+                params![workspace_id, query.since, query.until, filter, limit as i64, offset as i64],
+                |row| row.get::<_, String>(0),
+            )
+            .map_err(|e| e.to_string())?;
+
+        let mut executions = Vec::new();
+        for data in rows {
+            let data = data.map_err(|e| e.to_string())?;
+            match serde_json::from_str(&data) {
+                Ok(exec) => executions.push(exec),
+                Err(e) => log::warn!("Skipping unreadable agent execution: {}", e),
+            }
+        }
+        Ok(Page { items: executions, total: total.max(0) as usize, offset, limit })
+    }
+
+    /// Mark executions left running by a previous process as failed
+    pub fn fail_interrupted(&self) -> Result<usize, String> {
+        let ids: Vec<String> = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT id FROM agent_executions WHERE status IN ('pending', 'running', 'pulling_model')")
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(|e| e.to_string())?;
+            rows.filter_map(Result::ok).collect()
+        };
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut count = 0;
+        for id in ids {
+            if let Some(mut exec) = self.get(&id)? {
+                exec.status = AgentStatus::Failed;
+                exec.error = Some("Interrupted by node restart".to_string());
+                exec.completed_at = Some(now.clone());
+                self.save(&exec)?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
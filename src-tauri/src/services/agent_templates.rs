@@ -0,0 +1,110 @@
+//! Agent Templates
+//!
+//! Predefined kinds of agent, picked with `agent_type` when creating an
+//! execution. A template adds instructions to the system prompt, narrows
+//! the tools the agent may call and names models suited to the work; the
+//! default `react` template leaves all tools available.
+
+use serde::Serialize;
+
+use crate::error::NodeError;
+
+/// Template used when a request names none
+pub const DEFAULT_TEMPLATE: &str = "react";
+
+/// A kind of agent with its own instructions, tools and preferred models
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentTemplate {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Added to the system prompt ahead of the tool descriptions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<&'static str>,
+    /// Tools the agent may call; all when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<&'static [&'static str]>,
+    /// Installed models to prefer when none is requested, best first,
+    /// matched by name prefix
+    pub model_hints: &'static [&'static str],
+    /// Iterations unless the request says otherwise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_iterations: Option<u32>,
+}
+
+pub static TEMPLATES: &[AgentTemplate] = &[
+    AgentTemplate {
+        id: DEFAULT_TEMPLATE,
+        name: "General agent",
+        description: "Reasons and acts towards any goal with every tool available",
+        system_prompt: None,
+        tools: None,
+        model_hints: &[],
+        max_iterations: None,
+    },
+    AgentTemplate {
+        id: "research",
+        name: "Research",
+        description: "Gathers information from the web, IPFS and indexed documents and writes up what it found",
+        system_prompt: Some(
+            "You are a research assistant. Collect information relevant to the goal from indexed documents, \
+             web pages and IPFS content before answering. Cite where each fact came from, say when sources \
+             disagree or information is missing, and write longer findings to a file in the workspace.",
+        ),
+        tools: Some(&["search_documents", "http_fetch", "ipfs_cat", "write_file", "read_file"]),
+        model_hints: &["llama3.1", "qwen2.5", "mistral"],
+        max_iterations: Some(12),
+    },
+    AgentTemplate {
+        id: "summarize-cid",
+        name: "Summarize CID",
+        description: "Reads the content behind an IPFS CID and summarizes it",
+        system_prompt: Some(
+            "You summarize content stored on IPFS. The goal names a CID: read it with ipfs_cat, then give a \
+             concise summary covering its purpose, main points and anything notable. If the content is not \
+             text, say what it appears to be instead.",
+        ),
+        tools: Some(&["ipfs_cat"]),
+        model_hints: &["llama3.2", "qwen2.5", "mistral"],
+        max_iterations: Some(3),
+    },
+    AgentTemplate {
+        id: "code-runner",
+        name: "Code runner",
+        description: "Writes programs in its sandboxed working directory, runs them and reports the output",
+        system_prompt: Some(
+            "You write and run code to accomplish the goal. Put source files in the working directory, run them \
+             with the shell tool, and fix errors until they work. Report the final output and the files you \
+             created.",
+        ),
+        tools: Some(&["shell", "read_file", "write_file"]),
+        model_hints: &["qwen2.5-coder", "deepseek-coder", "codellama", "llama3.1"],
+        max_iterations: Some(12),
+    },
+    AgentTemplate {
+        id: "system-diagnostics",
+        name: "System diagnostics",
+        description: "Inspects the machine's hardware and environment and explains problems it finds",
+        system_prompt: Some(
+            "You diagnose the machine this node runs on. Start from system_info, run read-only commands to dig \
+             deeper, and do not change or delete anything. Explain what you found, what looks wrong, and what \
+             the user could do about it.",
+        ),
+        tools: Some(&["system_info", "shell", "read_file"]),
+        model_hints: &["llama3.1", "qwen2.5", "llama3.2"],
+        max_iterations: Some(8),
+    },
+];
+
+/// The template `agent_type` names, or the default
+pub fn find(agent_type: Option<&str>) -> Result<&'static AgentTemplate, NodeError> {
+    let id = match agent_type {
+        Some(id) if !id.is_empty() => id,
+        _ => DEFAULT_TEMPLATE,
+    };
+    TEMPLATES.iter().find(|t| t.id == id).ok_or_else(|| {
+        let known: Vec<&str> = TEMPLATES.iter().map(|t| t.id).collect();
+        NodeError::Invalid(format!("Unknown agent type {}; expected one of {}", id, known.join(", ")))
+    })
+}
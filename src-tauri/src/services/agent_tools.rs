@@ -0,0 +1,768 @@
+//! Agent Tools
+//!
+//! Tools an agent can invoke while working towards a goal. Every tool
+//! describes its input with a JSON schema; the descriptions are injected
+//! into the agent's system prompt and the tool output is fed back to the
+//! model as an observation.
+
+use async_trait::async_trait;
+use opentelemetry::{trace::SpanKind, KeyValue};
+use serde_json::{json, Value};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::audit::{AuditKind, AuditLog};
+use super::{resources, ContainerManager, CreateContainerRequest, HardwareDetector, IpfsManager};
+use crate::telemetry;
+
+/// Maximum number of characters of tool output handed back to the model
+const MAX_OUTPUT_CHARS: usize = 16 * 1024;
+
+/// Timeout for shell commands run by the agent
+const SHELL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Mount point of the workspace inside the sandbox container
+const SANDBOX_WORKDIR: &str = "/workspace";
+
+/// Execution context passed to every tool invocation
+#[derive(Debug, Clone)]
+pub struct ToolContext {
+    pub execution_id: String,
+    pub workspace_id: String,
+    /// Working directory of the execution, inside the workspace's; the
+    /// agent may read and write here and shell commands run here
+    pub workspace_dir: PathBuf,
+    /// Sandbox container created for this execution, if any
+    sandbox: Arc<Mutex<Option<String>>>,
+}
+
+impl ToolContext {
+    pub fn new(execution_id: &str, workspace_id: &str) -> Self {
+        Self {
+            execution_id: execution_id.to_string(),
+            workspace_id: workspace_id.to_string(),
+            workspace_dir: execution_dir(workspace_id, execution_id),
+            sandbox: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// ID of the sandbox container shell commands of this execution run in
+    pub fn sandbox_container_id(&self) -> Option<String> {
+        self.sandbox.lock().unwrap().clone()
+    }
+
+    /// Resolve a path relative to the workspace, refusing to escape it.
+    /// File tools run on the host while sandboxed shell commands can create
+    /// symbolic links in the workspace, so no part of the path may be one.
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(path);
+        let escapes = relative.components().any(|c| {
+            matches!(c, Component::ParentDir | Component::RootDir | Component::Prefix(_))
+        });
+        if escapes {
+            return Err(format!("Path must stay inside the workspace: {}", path));
+        }
+
+        let mut prefix = self.workspace_dir.clone();
+        for component in relative.components() {
+            prefix.push(component);
+            match std::fs::symlink_metadata(&prefix) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    return Err(format!("Path must not go through a symbolic link: {}", path));
+                }
+                Ok(_) => {}
+                // The rest of the path does not exist yet
+                Err(_) => break,
+            }
+        }
+        Ok(self.workspace_dir.join(relative))
+    }
+}
+
+/// Directory holding the files of a workspace
+pub fn workspace_dir(workspace_id: &str) -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+        .join("workspaces")
+        .join(workspace_id)
+}
+
+/// Working directory of one execution, snapshotted to IPFS when it ends
+pub fn execution_dir(workspace_id: &str, execution_id: &str) -> PathBuf {
+    workspace_dir(workspace_id).join("executions").join(execution_id)
+}
+
+/// A capability the agent can call
+#[async_trait]
+pub trait AgentTool: Send + Sync {
+    /// Name the model uses to call the tool
+    fn name(&self) -> &'static str;
+
+    /// One-line description shown to the model
+    fn description(&self) -> &'static str;
+
+    /// JSON schema of the tool input
+    fn parameters(&self) -> Value;
+
+    /// Run the tool and return the observation text
+    async fn execute(&self, ctx: &ToolContext, input: &Value) -> Result<String, String>;
+
+    /// Release anything the tool set up for an execution once it has finished
+    async fn cleanup(&self, _ctx: &ToolContext) {}
+}
+
+/// Limits of the throwaway container agent shell commands run in
+#[derive(Debug, Clone)]
+pub struct SandboxConfig {
+    pub image: String,
+    /// Memory limit in bytes
+    pub memory_limit: i64,
+    /// CPU limit in units of 1e-9 CPUs
+    pub nano_cpus: i64,
+    /// Give the sandbox network access
+    pub network: bool,
+    /// Run commands on the host when no container runtime is available
+    /// (`agent.allowHostShell`); off unless the owner opts in
+    pub host_fallback: bool,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            image: "python:3.12-alpine".to_string(),
+            memory_limit: 512 * 1024 * 1024,
+            nano_cpus: 1_000_000_000,
+            network: false,
+            host_fallback: false,
+        }
+    }
+}
+
+/// Set of tools available to agents
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn AgentTool>>,
+    audit: Option<Arc<AuditLog>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self { tools: Vec::new(), audit: None }
+    }
+
+    /// Record every invocation in the audit log
+    pub fn with_audit(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Registry with the built-in shell, file, HTTP, IPFS, container and
+    /// system information tools
+    pub fn with_defaults(ipfs: Arc<IpfsManager>, containers: Arc<ContainerManager>, sandbox: SandboxConfig) -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(ShellTool {
+            containers: Arc::clone(&containers),
+            sandbox,
+        }));
+        registry.register(Arc::new(ReadFileTool));
+        registry.register(Arc::new(WriteFileTool));
+        registry.register(Arc::new(HttpFetchTool));
+        registry.register(Arc::new(IpfsAddTool { ipfs: Arc::clone(&ipfs) }));
+        registry.register(Arc::new(IpfsCatTool { ipfs }));
+        registry.register(Arc::new(ContainerExecTool { containers }));
+        registry.register(Arc::new(SystemInfoTool));
+        registry
+    }
+
+    pub fn register(&mut self, tool: Arc<dyn AgentTool>) {
+        self.tools.retain(|t| t.name() != tool.name());
+        self.tools.push(tool);
+    }
+
+    /// Registry with just the named tools, audited the same way
+    pub fn only(&self, names: &[&str]) -> Self {
+        Self {
+            tools: self.tools.iter().filter(|t| names.contains(&t.name())).cloned().collect(),
+            audit: self.audit.clone(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn AgentTool>> {
+        self.tools.iter().find(|t| t.name() == name).cloned()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// JSON descriptions of all tools, for the system prompt
+    pub fn describe(&self) -> Value {
+        Value::Array(
+            self.tools
+                .iter()
+                .map(|t| {
+                    json!({
+                        "name": t.name(),
+                        "description": t.description(),
+                        "parameters": t.parameters(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Run a tool by name; the returned text is truncated for the prompt
+    pub async fn execute(&self, name: &str, ctx: &ToolContext, input: &Value) -> Result<String, String> {
+        let tool = self.get(name).ok_or_else(|| format!("Unknown tool: {}", name))?;
+        log::info!("Agent {} (workspace {}) invoking tool {}", ctx.execution_id, ctx.workspace_id, name);
+        let attributes = vec![
+            KeyValue::new("agent.tool", name.to_string()),
+            KeyValue::new("agent.execution_id", ctx.execution_id.clone()),
+        ];
+        let started = std::time::Instant::now();
+        let result = telemetry::in_span(format!("agent.tool {}", name), SpanKind::Internal, attributes, async {
+            let result = tool.execute(ctx, input).await.map(truncate);
+            if let Err(e) = &result {
+                telemetry::set_error(e);
+            }
+            result
+        })
+        .await;
+        telemetry::record_duration(
+            "otherthing.agent.tool.duration",
+            started.elapsed(),
+            &[
+                KeyValue::new("agent.tool", name.to_string()),
+                KeyValue::new("outcome", if result.is_ok() { "ok" } else { "error" }),
+            ],
+        );
+        if let Some(audit) = &self.audit {
+            audit.log(
+                AuditKind::AgentTool,
+                name,
+                json!({
+                    "executionId": ctx.execution_id,
+                    "workspaceId": ctx.workspace_id,
+                    "input": input,
+                    "success": result.is_ok(),
+                    "error": result.as_ref().err(),
+                }),
+            );
+        }
+        result
+    }
+
+    /// Let every tool release what it set up for a finished execution
+    pub async fn cleanup(&self, ctx: &ToolContext) {
+        for tool in &self.tools {
+            tool.cleanup(ctx).await;
+        }
+    }
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn truncate(mut text: String) -> String {
+    if text.len() > MAX_OUTPUT_CHARS {
+        let mut cut = MAX_OUTPUT_CHARS;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        text.truncate(cut);
+        text.push_str("\n... [output truncated]");
+    }
+    text
+}
+
+pub(super) fn required_str<'a>(input: &'a Value, key: &str) -> Result<&'a str, String> {
+    input[key]
+        .as_str()
+        .ok_or_else(|| format!("Missing required string parameter '{}'", key))
+}
+
+// ============ Built-in Tools ============
+
+/// Run a shell command against the workspace, inside an ephemeral sandbox
+/// container when a container runtime is available
+pub struct ShellTool {
+    containers: Arc<ContainerManager>,
+    sandbox: SandboxConfig,
+}
+
+impl ShellTool {
+    /// Sandbox container of the execution, created on first use
+    async fn sandbox_for(&self, ctx: &ToolContext) -> Result<String, String> {
+        if let Some(id) = ctx.sandbox_container_id() {
+            return Ok(id);
+        }
+
+        let request = CreateContainerRequest {
+            name: format!("otherthing-agent-{}", ctx.execution_id),
+            image: self.sandbox.image.clone(),
+            cmd: Some(vec!["sleep".to_string(), "infinity".to_string()]),
+            volumes: Some(vec![format!("{}:{}", ctx.workspace_dir.display(), SANDBOX_WORKDIR)]),
+            labels: Some(
+                [
+                    ("otherthing.agent.execution".to_string(), ctx.execution_id.clone()),
+                    ("otherthing.agent.workspace".to_string(), ctx.workspace_id.clone()),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+            memory_limit: Some(self.sandbox.memory_limit),
+            nano_cpus: Some(self.sandbox.nano_cpus),
+            working_dir: Some(SANDBOX_WORKDIR.to_string()),
+            network_disabled: Some(!self.sandbox.network),
+            ..Default::default()
+        };
+
+        let id = match self.containers.create_container(request.clone()).await {
+            Ok(id) => id,
+            Err(_) => {
+                // Most likely the image is missing locally
+                self.containers.pull_image(&self.sandbox.image).await
+                    .map_err(|e| format!("Failed to pull sandbox image {}: {}", self.sandbox.image, e))?;
+                self.containers.create_container(request).await
+                    .map_err(|e| format!("Failed to create sandbox: {}", e))?
+            }
+        };
+
+        if let Err(e) = self.containers.start_container(&id).await {
+            let _ = self.containers.remove_container(&id, true).await;
+            return Err(format!("Failed to start sandbox: {}", e));
+        }
+
+        log::info!("Agent {} sandbox container {}", ctx.execution_id, id);
+        *ctx.sandbox.lock().unwrap() = Some(id.clone());
+        Ok(id)
+    }
+
+    async fn run_in_sandbox(&self, ctx: &ToolContext, command: &str) -> Result<String, String> {
+        let id = self.sandbox_for(ctx).await?;
+        let cmd = vec!["sh".to_string(), "-c".to_string(), command.to_string()];
+
+        let result = match tokio::time::timeout(SHELL_TIMEOUT, self.containers.exec_in_container(&id, cmd)).await {
+            Ok(result) => result.map_err(|e| format!("Failed to run command: {}", e))?,
+            Err(_) => {
+                // The exec keeps running in the container; throw the sandbox away
+                self.cleanup(ctx).await;
+                return Err(format!("Command timed out after {}s", SHELL_TIMEOUT.as_secs()));
+            }
+        };
+
+        Ok(format!(
+            "exit_code: {}\nstdout:\n{}\nstderr:\n{}",
+            result.exit_code, result.stdout, result.stderr,
+        ))
+    }
+
+    async fn run_on_host(&self, ctx: &ToolContext, command: &str) -> Result<String, String> {
+        // Held to the node's limits like the sandboxes it stands in for
+        let limits = self.containers.resource_limits().await;
+        #[cfg(target_os = "windows")]
+        let mut cmd = {
+            let mut c = resources::command(Path::new("cmd"), &limits);
+            c.arg("/C").arg(command);
+            tokio::process::Command::from(c)
+        };
+        #[cfg(not(target_os = "windows"))]
+        let mut cmd = {
+            let mut c = resources::command(Path::new("sh"), &limits);
+            c.arg("-c").arg(command);
+            tokio::process::Command::from(c)
+        };
+
+        cmd.current_dir(&ctx.workspace_dir).kill_on_drop(true);
+
+        let output = tokio::time::timeout(SHELL_TIMEOUT, cmd.output())
+            .await
+            .map_err(|_| format!("Command timed out after {}s", SHELL_TIMEOUT.as_secs()))?
+            .map_err(|e| format!("Failed to run command: {}", e))?;
+
+        Ok(format!(
+            "exit_code: {}\nstdout:\n{}\nstderr:\n{}",
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr),
+        ))
+    }
+}
+
+#[async_trait]
+impl AgentTool for ShellTool {
+    fn name(&self) -> &'static str {
+        "shell"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run a shell command in the workspace directory and return its exit code, stdout and stderr"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": { "type": "string", "description": "Command line to execute" }
+            },
+            "required": ["command"]
+        })
+    }
+
+    async fn execute(&self, ctx: &ToolContext, input: &Value) -> Result<String, String> {
+        let command = required_str(input, "command")?;
+
+        std::fs::create_dir_all(&ctx.workspace_dir)
+            .map_err(|e| format!("Failed to create workspace: {}", e))?;
+
+        if self.containers.is_available().await {
+            self.run_in_sandbox(ctx, command).await
+        } else if self.sandbox.host_fallback {
+            log::warn!("No container runtime available; agent {} runs shell commands on the host", ctx.execution_id);
+            self.run_on_host(ctx, command).await
+        } else {
+            Err("Shell commands require a container runtime (or agent.allowHostShell in the config)".to_string())
+        }
+    }
+
+    async fn cleanup(&self, ctx: &ToolContext) {
+        let id = ctx.sandbox.lock().unwrap().take();
+        if let Some(id) = id {
+            if let Err(e) = self.containers.remove_container(&id, true).await {
+                log::warn!("Failed to remove sandbox container {}: {}", id, e);
+            }
+        }
+    }
+}
+
+/// Read a text file from the workspace
+pub struct ReadFileTool;
+
+#[async_trait]
+impl AgentTool for ReadFileTool {
+    fn name(&self) -> &'static str {
+        "read_file"
+    }
+
+    fn description(&self) -> &'static str {
+        "Read a text file from the workspace"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Path relative to the workspace" }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn execute(&self, ctx: &ToolContext, input: &Value) -> Result<String, String> {
+        let path = ctx.resolve(required_str(input, "path")?)?;
+        tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    }
+}
+
+/// Write a text file into the workspace
+pub struct WriteFileTool;
+
+#[async_trait]
+impl AgentTool for WriteFileTool {
+    fn name(&self) -> &'static str {
+        "write_file"
+    }
+
+    fn description(&self) -> &'static str {
+        "Create or overwrite a text file in the workspace"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Path relative to the workspace" },
+                "content": { "type": "string", "description": "File content" }
+            },
+            "required": ["path", "content"]
+        })
+    }
+
+    async fn execute(&self, ctx: &ToolContext, input: &Value) -> Result<String, String> {
+        let path = ctx.resolve(required_str(input, "path")?)?;
+        let content = required_str(input, "content")?;
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("Failed to create directory: {}", e))?;
+        }
+
+        tokio::fs::write(&path, content)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        Ok(format!("Wrote {} bytes to {}", content.len(), path.display()))
+    }
+}
+
+/// Fetch a URL over HTTP(S). The fetch runs on the host, so only public
+/// addresses are reachable: not this node's API, the LAN, or cloud metadata.
+pub struct HttpFetchTool;
+
+#[async_trait]
+impl AgentTool for HttpFetchTool {
+    fn name(&self) -> &'static str {
+        "http_fetch"
+    }
+
+    fn description(&self) -> &'static str {
+        "Fetch a URL with an HTTP GET request and return the status and body"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string", "description": "http:// or https:// URL" }
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn execute(&self, _ctx: &ToolContext, input: &Value) -> Result<String, String> {
+        let url = required_str(input, "url")?;
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err("Only http and https URLs are supported".to_string());
+        }
+        let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+        let host = parsed.host_str().ok_or("URL has no host")?.to_string();
+        let port = parsed.port_or_known_default().unwrap_or(80);
+
+        // Resolve once and connect to what was checked, so the name cannot
+        // resolve somewhere else for the request itself
+        let mut builder = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .redirect(reqwest::redirect::Policy::none());
+        match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(ip) if is_public(ip) => {}
+            Ok(_) => return Err(format!("{} is not a public address", host)),
+            Err(_) => {
+                let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+                    .await
+                    .map_err(|e| format!("Failed to resolve {}: {}", host, e))?
+                    .collect();
+                if addrs.is_empty() || !addrs.iter().all(|addr| is_public(addr.ip())) {
+                    return Err(format!("{} is not a public address", host));
+                }
+                builder = builder.resolve_to_addrs(&host, &addrs);
+            }
+        }
+        let client = builder
+            .build()
+            .map_err(|e| format!("Failed to create client: {}", e))?;
+
+        let response = client
+            .get(parsed)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        let status = response.status();
+        // Redirects are not followed: their target has to pass the same check
+        if let Some(location) = response.headers().get(reqwest::header::LOCATION) {
+            return Ok(format!(
+                "status: {}\nRedirected to {}; fetch that URL to follow it",
+                status,
+                location.to_str().unwrap_or_default()
+            ));
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+
+        Ok(format!("status: {}\n{}", status, body))
+    }
+}
+
+/// Whether an address is on the public internet: not loopback, private,
+/// link-local (cloud metadata lives there), shared or otherwise reserved
+fn is_public(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Add text content to IPFS
+pub struct IpfsAddTool {
+    ipfs: Arc<IpfsManager>,
+}
+
+#[async_trait]
+impl AgentTool for IpfsAddTool {
+    fn name(&self) -> &'static str {
+        "ipfs_add"
+    }
+
+    fn description(&self) -> &'static str {
+        "Add text content to IPFS and return its CID"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "content": { "type": "string", "description": "Content to store" }
+            },
+            "required": ["content"]
+        })
+    }
+
+    async fn execute(&self, _ctx: &ToolContext, input: &Value) -> Result<String, String> {
+        let content = required_str(input, "content")?;
+        Ok(self.ipfs.add_content(content).await?)
+    }
+}
+
+/// Read content from IPFS
+pub struct IpfsCatTool {
+    ipfs: Arc<IpfsManager>,
+}
+
+#[async_trait]
+impl AgentTool for IpfsCatTool {
+    fn name(&self) -> &'static str {
+        "ipfs_cat"
+    }
+
+    fn description(&self) -> &'static str {
+        "Read the content stored under an IPFS CID"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "cid": { "type": "string", "description": "CID to read" }
+            },
+            "required": ["cid"]
+        })
+    }
+
+    async fn execute(&self, _ctx: &ToolContext, input: &Value) -> Result<String, String> {
+        let cid = required_str(input, "cid")?;
+        Ok(self.ipfs.cat(cid).await?)
+    }
+}
+
+/// Run a command in the execution's own sandbox container; other
+/// containers on the node (other jobs', other agents') are off limits
+pub struct ContainerExecTool {
+    containers: Arc<ContainerManager>,
+}
+
+#[async_trait]
+impl AgentTool for ContainerExecTool {
+    fn name(&self) -> &'static str {
+        "container_exec"
+    }
+
+    fn description(&self) -> &'static str {
+        "Execute a command inside this execution's sandbox container, created by the shell tool"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "container_id": { "type": "string", "description": "ID of the sandbox container; it is the default" },
+                "cmd": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Command and arguments"
+                }
+            },
+            "required": ["cmd"]
+        })
+    }
+
+    async fn execute(&self, ctx: &ToolContext, input: &Value) -> Result<String, String> {
+        let sandbox = ctx
+            .sandbox_container_id()
+            .ok_or("This execution has no sandbox container yet; run a shell command first")?;
+        if let Some(requested) = input["container_id"].as_str() {
+            if requested != sandbox {
+                return Err(format!("Only this execution's sandbox container ({}) can be used", sandbox));
+            }
+        }
+        let cmd: Vec<String> = input["cmd"]
+            .as_array()
+            .ok_or("Missing required array parameter 'cmd'")?
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect();
+
+        let result = self.containers
+            .exec_in_container(&sandbox, cmd)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(format!(
+            "exit_code: {}\nstdout:\n{}\nstderr:\n{}",
+            result.exit_code, result.stdout, result.stderr
+        ))
+    }
+}
+
+/// Report the hardware of the machine the node runs on
+pub struct SystemInfoTool;
+
+#[async_trait]
+impl AgentTool for SystemInfoTool {
+    fn name(&self) -> &'static str {
+        "system_info"
+    }
+
+    fn description(&self) -> &'static str {
+        "Describe this machine: operating system, CPU, memory, GPUs and drives"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    async fn execute(&self, _ctx: &ToolContext, _input: &Value) -> Result<String, String> {
+        let hardware = tokio::task::spawn_blocking(HardwareDetector::detect)
+            .await
+            .map_err(|e| format!("Failed to detect hardware: {}", e))?;
+        serde_json::to_string_pretty(&hardware).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,416 @@
+//! Audit Log
+//!
+//! Append-only record of the work run on this machine: relayed jobs and
+//! other API mutations, container execs and agent tool invocations. Each
+//! entry stores the SHA-256 of the previous entry's hash and its own
+//! contents, so editing, removing or reordering rows in `audit.db` breaks
+//! the chain and `verify` points at the first entry that no longer fits.
+//! As in the earnings ledger, triggers refuse updates and deletes.
+
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::NodeError;
+use crate::pagination::{ListQuery, Page};
+
+/// `prev_hash` of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Longest string kept in an entry's detail; longer ones are cut with a note
+const MAX_DETAIL_STRING: usize = 4096;
+
+/// Fields entries can be sorted by, and their columns
+const AUDIT_SORT: &[(&str, &str)] = &[("id", "id"), ("recordedAt", "recorded_at"), ("kind", "kind"), ("action", "action")];
+/// Fields entries can be filtered on, and their columns
+const AUDIT_FILTER: &[(&str, &str)] = &[("kind", "kind"), ("action", "action"), ("detail", "detail")];
+
+const AUDIT_COLUMNS: &str = "id, recorded_at, kind, action, detail, prev_hash, hash";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditKind {
+    /// A request relayed from the orchestrator
+    Job,
+    /// A state-changing local API request
+    ApiMutation,
+    ContainerExec,
+    AgentTool,
+}
+
+impl AuditKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Job => "job",
+            Self::ApiMutation => "api_mutation",
+            Self::ContainerExec => "container_exec",
+            Self::AgentTool => "agent_tool",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "job" => Some(Self::Job),
+            "api_mutation" => Some(Self::ApiMutation),
+            "container_exec" => Some(Self::ContainerExec),
+            "agent_tool" => Some(Self::AgentTool),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub id: i64,
+    pub recorded_at: String,
+    pub kind: AuditKind,
+    /// What happened, e.g. `POST /api/v1/containers` or `shell`
+    pub action: String,
+    pub detail: Value,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<AuditKind>,
+}
+
+/// Outcome of walking the hash chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainVerification {
+    pub valid: bool,
+    pub entries: u64,
+    /// Hash of the newest entry; publishing it pins the whole history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head: Option<String>,
+    /// First entry whose hash does not match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub broken_at: Option<i64>,
+}
+
+fn entry_hash(prev_hash: &str, id: i64, recorded_at: &str, kind: &str, action: &str, detail: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [prev_hash, &id.to_string(), recorded_at, kind, action, detail] {
+        hasher.update(part.as_bytes());
+        hasher.update([0u8]);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Cut long strings so a large file write or response does not bloat the log
+fn clip(value: Value) -> Value {
+    match value {
+        Value::String(s) if s.len() > MAX_DETAIL_STRING => {
+            let mut end = MAX_DETAIL_STRING;
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+            Value::String(format!("{}… ({} bytes)", &s[..end], s.len()))
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(clip).collect()),
+        Value::Object(map) => Value::Object(map.into_iter().map(|(k, v)| (k, clip(v))).collect()),
+        other => other,
+    }
+}
+
+pub struct AuditLog {
+    conn: Mutex<Connection>,
+}
+
+impl AuditLog {
+    /// Open the log at its default location under the config dir
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&default_path())
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open audit log: {}", e))?;
+        Self::init(conn)
+    }
+
+    /// Log that lives only as long as the process (used when the file can't be opened)
+    pub fn in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory().map_err(|e| format!("Failed to open audit log: {}", e))?;
+        Self::init(conn)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                recorded_at TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                action TEXT NOT NULL,
+                detail TEXT NOT NULL,
+                prev_hash TEXT NOT NULL,
+                hash TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_audit_kind ON audit_log (kind);
+            CREATE TRIGGER IF NOT EXISTS audit_no_update
+                BEFORE UPDATE ON audit_log
+                BEGIN SELECT RAISE(ABORT, 'the audit log is append-only'); END;
+            CREATE TRIGGER IF NOT EXISTS audit_no_delete
+                BEFORE DELETE ON audit_log
+                BEGIN SELECT RAISE(ABORT, 'the audit log is append-only'); END;",
+        )
+        .map_err(|e| format!("Failed to initialize audit log: {}", e))?;
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Append an entry, chained to the newest one
+    pub fn record(&self, kind: AuditKind, action: &str, detail: Value) -> Result<AuditEntry, String> {
+        let detail = clip(detail);
+        let detail_text = detail.to_string();
+        let recorded_at = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let last: Option<(i64, String)> = tx
+            .query_row("SELECT id, hash FROM audit_log ORDER BY id DESC LIMIT 1", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let (id, prev_hash) = match last {
+            Some((id, hash)) => (id + 1, hash),
+            None => (1, GENESIS_HASH.to_string()),
+        };
+        let hash = entry_hash(&prev_hash, id, &recorded_at, kind.as_str(), action, &detail_text);
+
+        tx.execute(
+            "INSERT INTO audit_log (id, recorded_at, kind, action, detail, prev_hash, hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![id, recorded_at, kind.as_str(), action, detail_text, prev_hash, hash],
+        )
+        .map_err(|e| format!("Failed to write audit entry: {}", e))?;
+        tx.commit().map_err(|e| format!("Failed to write audit entry: {}", e))?;
+
+        Ok(AuditEntry {
+            id,
+            recorded_at,
+            kind,
+            action: action.to_string(),
+            detail,
+            prev_hash,
+            hash,
+        })
+    }
+
+    /// Record an entry, logging instead of failing the work it describes
+    pub fn log(&self, kind: AuditKind, action: &str, detail: Value) {
+        if let Err(e) = self.record(kind, action, detail) {
+            log::error!("Audit log: {}", e);
+        }
+    }
+
+    /// A page of entries, newest first unless sorted otherwise
+    pub fn list(&self, query: &AuditQuery, page: &ListQuery) -> Result<Page<AuditEntry>, NodeError> {
+        let order = page.order(AUDIT_SORT, "ORDER BY id DESC")?;
+        let (condition, filter) = page.condition(AUDIT_FILTER, 1)?;
+        let kind = query.kind.map(|k| k.as_str());
+        let conn = self.conn.lock().unwrap();
+        let total: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM audit_log WHERE {} AND (?2 IS NULL OR kind = ?2)", condition),
+                params![filter, kind],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM audit_log WHERE {} AND (?2 IS NULL OR kind = ?2) {} LIMIT ?3 OFFSET ?4",
+                AUDIT_COLUMNS, condition, order
+            ))
+            .map_err(|e| e.to_string())?;
+        let (limit, offset) = (page.limit(), page.offset());
+        let rows = stmt
+            .query_map(params![filter, kind, limit as i64, offset as i64], Self::row_to_entry)
+            .map_err(|e| e.to_string())?;
+        let items = rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+        Ok(Page { items, total: total.max(0) as usize, offset, limit })
+    }
+
+    fn row_to_entry(row: &rusqlite::Row) -> rusqlite::Result<AuditEntry> {
+        let kind: String = row.get(2)?;
+        let detail: String = row.get(4)?;
+        Ok(AuditEntry {
+            id: row.get(0)?,
+            recorded_at: row.get(1)?,
+            kind: AuditKind::parse(&kind).unwrap_or(AuditKind::ApiMutation),
+            action: row.get(3)?,
+            detail: serde_json::from_str(&detail).unwrap_or(Value::String(detail)),
+            prev_hash: row.get(5)?,
+            hash: row.get(6)?,
+        })
+    }
+
+    /// The whole log as JSON Lines, oldest first, for inspection elsewhere
+    pub fn export_jsonl(&self) -> Result<String, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!("SELECT {} FROM audit_log ORDER BY id", AUDIT_COLUMNS))
+            .map_err(|e| e.to_string())?;
+        let entries: Vec<AuditEntry> = stmt
+            .query_map([], Self::row_to_entry)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        let mut out = String::new();
+        for entry in entries {
+            out.push_str(&serde_json::to_string(&entry).map_err(|e| e.to_string())?);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// Recompute every hash from the stored rows
+    pub fn verify(&self) -> Result<ChainVerification, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT id, recorded_at, kind, action, detail, prev_hash, hash FROM audit_log ORDER BY id")
+            .map_err(|e| e.to_string())?;
+        let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+
+        let mut expected_prev = GENESIS_HASH.to_string();
+        let mut entries = 0;
+        while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+            let id: i64 = row.get(0).map_err(|e| e.to_string())?;
+            let fields: Vec<String> = (1..=6)
+                .map(|i| row.get::<_, String>(i))
+                .collect::<Result<_, _>>()
+                .map_err(|e| e.to_string())?;
+            let (recorded_at, kind, action, detail, prev_hash, hash) =
+                (&fields[0], &fields[1], &fields[2], &fields[3], &fields[4], &fields[5]);
+
+            if *prev_hash != expected_prev
+                || *hash != entry_hash(prev_hash, id, recorded_at, kind, action, detail)
+            {
+                return Ok(ChainVerification {
+                    valid: false,
+                    entries,
+                    head: None,
+                    broken_at: Some(id),
+                });
+            }
+            expected_prev = hash.clone();
+            entries += 1;
+        }
+
+        Ok(ChainVerification {
+            valid: true,
+            entries,
+            head: (entries > 0).then_some(expected_prev),
+            broken_at: None,
+        })
+    }
+}
+
+/// Location of the audit database
+pub fn default_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+        .join("audit.db")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_with_entries() -> AuditLog {
+        let log = AuditLog::in_memory().unwrap();
+        log.record(AuditKind::Job, "POST /api/v1/agents", serde_json::json!({ "status": 200 })).unwrap();
+        log.record(AuditKind::ContainerExec, "exec", serde_json::json!({ "cmd": ["ls"] })).unwrap();
+        log.record(AuditKind::AgentTool, "shell", serde_json::json!({ "command": "echo hi" })).unwrap();
+        log
+    }
+
+    /// Change rows behind the log's back, as someone editing `audit.db` would
+    fn tamper(log: &AuditLog, sql: &str) {
+        let conn = log.conn.lock().unwrap();
+        conn.execute_batch("DROP TRIGGER audit_no_update; DROP TRIGGER audit_no_delete;").unwrap();
+        conn.execute_batch(sql).unwrap();
+    }
+
+    #[test]
+    fn intact_chain_verifies() {
+        let log = log_with_entries();
+        let head = log.record(AuditKind::Job, "GET /health", Value::Null).unwrap().hash;
+
+        let verification = log.verify().unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.entries, 4);
+        assert_eq!(verification.head, Some(head));
+        assert_eq!(verification.broken_at, None);
+    }
+
+    #[test]
+    fn entries_chain_to_the_previous_hash() {
+        let log = AuditLog::in_memory().unwrap();
+        let first = log.record(AuditKind::Job, "a", Value::Null).unwrap();
+        let second = log.record(AuditKind::Job, "b", Value::Null).unwrap();
+        assert_eq!(first.prev_hash, GENESIS_HASH);
+        assert_eq!(second.prev_hash, first.hash);
+    }
+
+    #[test]
+    fn edited_row_is_caught() {
+        let log = log_with_entries();
+        tamper(&log, r#"UPDATE audit_log SET detail = '{"cmd":["rm"]}' WHERE id = 2"#);
+
+        let verification = log.verify().unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.broken_at, Some(2));
+        assert_eq!(verification.entries, 1);
+    }
+
+    #[test]
+    fn reordered_rows_are_caught() {
+        let log = log_with_entries();
+        tamper(
+            &log,
+            "UPDATE audit_log SET id = 100 WHERE id = 2;
+             UPDATE audit_log SET id = 2 WHERE id = 3;
+             UPDATE audit_log SET id = 3 WHERE id = 100;",
+        );
+
+        let verification = log.verify().unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.broken_at, Some(2));
+    }
+
+    #[test]
+    fn removed_row_is_caught() {
+        let log = log_with_entries();
+        tamper(&log, "DELETE FROM audit_log WHERE id = 2");
+
+        let verification = log.verify().unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.broken_at, Some(3));
+    }
+
+    #[test]
+    fn rows_cannot_be_changed_through_sql() {
+        let log = log_with_entries();
+        let conn = log.conn.lock().unwrap();
+        assert!(conn.execute("UPDATE audit_log SET action = 'x' WHERE id = 1", []).is_err());
+        assert!(conn.execute("DELETE FROM audit_log WHERE id = 1", []).is_err());
+    }
+}
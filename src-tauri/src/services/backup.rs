@@ -0,0 +1,354 @@
+//! Node Backups
+//!
+//! A backup holds what a node needs to carry on elsewhere: its identity
+//! key (and with it the reputation tied to its public key), the config,
+//! the state store (node ID, share key, agent history, jobs), the earnings
+//! ledger, received payments and the audit log. It is a gzipped tar
+//! encrypted with XChaCha20-Poly1305 under a key derived from a passphrase
+//! with Argon2id:
+//!
+//! ```text
+//! OTNBACKUP1 | salt (16 bytes) | nonce (24 bytes) | ciphertext
+//! ```
+//!
+//! Databases are copied with `VACUUM INTO`, so a backup can be taken while
+//! the node runs. Restoring cannot replace databases the running node has
+//! open, so it stages the files under `restore-pending/` and they are put
+//! in place the next time the node starts, before anything opens them.
+
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::{params, Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use super::{audit, earnings, identity, payments, state_store};
+use crate::config::NodeConfig;
+
+const MAGIC: &[u8] = b"OTNBACKUP1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const FORMAT_VERSION: u32 = 1;
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// Largest backup accepted for restore
+pub const MAX_BACKUP_SIZE: usize = 1024 * 1024 * 1024;
+
+const MANIFEST: &str = "manifest.json";
+const IDENTITY: &str = "identity.key";
+const CONFIG: &str = "config.json";
+
+/// What a backup contains
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupManifest {
+    pub version: u32,
+    pub created_at: String,
+    pub app_version: String,
+    /// Public key of the backed-up identity, when there was one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    pub files: Vec<String>,
+}
+
+/// Databases in a backup, by file name
+fn databases() -> Vec<(&'static str, PathBuf)> {
+    vec![
+        ("node.db", state_store::default_path()),
+        ("earnings.db", earnings::default_path()),
+        ("payments.db", payments::default_path()),
+        ("audit.db", audit::default_path()),
+    ]
+}
+
+fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+}
+
+fn pending_dir() -> PathBuf {
+    config_dir().join("restore-pending")
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!("Backup passphrase must be at least {} characters", MIN_PASSPHRASE_LEN));
+    }
+    Ok(())
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive backup key: {}", e))?;
+    XChaCha20Poly1305::new_from_slice(&key).map_err(|e| e.to_string())
+}
+
+/// A consistent copy of a live SQLite database
+fn snapshot(path: &Path) -> Result<Vec<u8>, String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let copy = std::env::temp_dir().join(format!("otherthing-backup-{}.db", uuid::Uuid::new_v4()));
+    let result = conn
+        .execute("VACUUM INTO ?1", params![copy.to_string_lossy()])
+        .map_err(|e| format!("Failed to copy {}: {}", path.display(), e))
+        .and_then(|_| std::fs::read(&copy).map_err(|e| e.to_string()));
+    let _ = std::fs::remove_file(&copy);
+    result
+}
+
+fn append(builder: &mut tar::Builder<GzEncoder<Vec<u8>>>, name: &str, data: &[u8]) -> Result<(), String> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, name, data)
+        .map_err(|e| format!("Failed to write {} to backup: {}", name, e))
+}
+
+/// Build an encrypted backup of this node
+pub fn create(passphrase: &str) -> Result<Vec<u8>, String> {
+    check_passphrase(passphrase)?;
+
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let secret = identity::stored_secret()?;
+    if let Some(secret) = &secret {
+        files.push((IDENTITY.to_string(), secret.as_bytes().to_vec()));
+    }
+    if let Ok(config) = std::fs::read(NodeConfig::path()) {
+        files.push((CONFIG.to_string(), config));
+    }
+    for (name, path) in databases() {
+        if path.exists() {
+            files.push((name.to_string(), snapshot(&path)?));
+        }
+    }
+
+    let manifest = BackupManifest {
+        version: FORMAT_VERSION,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        public_key: secret.as_deref().map(identity::public_key_of).transpose()?,
+        files: files.iter().map(|(name, _)| name.clone()).collect(),
+    };
+
+    let out = seal(passphrase, &manifest, &files)?;
+    log::info!("Created backup of {} files", manifest.files.len());
+    Ok(out)
+}
+
+/// Pack the manifest and files and encrypt them under the passphrase
+fn seal(passphrase: &str, manifest: &BackupManifest, files: &[(String, Vec<u8>)]) -> Result<Vec<u8>, String> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    let manifest_json = serde_json::to_vec_pretty(manifest).map_err(|e| e.to_string())?;
+    append(&mut builder, MANIFEST, &manifest_json)?;
+    for (name, data) in files {
+        append(&mut builder, name, data)?;
+    }
+    let archive = builder
+        .into_inner()
+        .and_then(|gz| gz.finish())
+        .map_err(|e| format!("Failed to write backup: {}", e))?;
+
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    getrandom::getrandom(&mut salt).map_err(|e| e.to_string())?;
+    getrandom::getrandom(&mut nonce).map_err(|e| e.to_string())?;
+    let ciphertext = cipher(passphrase, &salt)?
+        .encrypt(XNonce::from_slice(&nonce), archive.as_slice())
+        .map_err(|_| "Failed to encrypt backup".to_string())?;
+
+    let mut out = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypt a backup and read its files
+fn open(backup: &[u8], passphrase: &str) -> Result<(BackupManifest, HashMap<String, Vec<u8>>), String> {
+    let header_len = MAGIC.len() + SALT_LEN + NONCE_LEN;
+    if backup.len() < header_len || !backup.starts_with(MAGIC) {
+        return Err("Not a node backup".to_string());
+    }
+    let salt = &backup[MAGIC.len()..MAGIC.len() + SALT_LEN];
+    let nonce = &backup[MAGIC.len() + SALT_LEN..header_len];
+    let archive = cipher(passphrase, salt)?
+        .decrypt(XNonce::from_slice(nonce), &backup[header_len..])
+        .map_err(|_| "Wrong passphrase, or the backup is damaged".to_string())?;
+
+    let mut files = HashMap::new();
+    let mut tar = tar::Archive::new(GzDecoder::new(archive.as_slice()));
+    for entry in tar.entries().map_err(|e| format!("Unreadable backup: {}", e))? {
+        let mut entry = entry.map_err(|e| format!("Unreadable backup: {}", e))?;
+        let name = entry
+            .path()
+            .map_err(|e| format!("Unreadable backup: {}", e))?
+            .to_string_lossy()
+            .into_owned();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).map_err(|e| format!("Unreadable backup: {}", e))?;
+        files.insert(name, data);
+    }
+
+    let manifest: BackupManifest = files
+        .remove(MANIFEST)
+        .ok_or("Backup has no manifest")
+        .and_then(|m| serde_json::from_slice(&m).map_err(|_| "Backup manifest is unreadable"))?;
+    if manifest.version > FORMAT_VERSION {
+        return Err(format!(
+            "Backup format {} is newer than this build understands ({})",
+            manifest.version, FORMAT_VERSION
+        ));
+    }
+    Ok((manifest, files))
+}
+
+/// Check a backup and stage its files; they replace this node's state the
+/// next time it starts
+pub fn restore(backup: &[u8], passphrase: &str) -> Result<BackupManifest, String> {
+    let (manifest, files) = open(backup, passphrase)?;
+    if let Some(secret) = files.get(IDENTITY) {
+        identity::public_key_of(&String::from_utf8_lossy(secret))?;
+    }
+
+    let known: Vec<&str> = databases()
+        .into_iter()
+        .map(|(name, _)| name)
+        .chain([IDENTITY, CONFIG])
+        .collect();
+    let dir = pending_dir();
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).map_err(|e| format!("Failed to clear {}: {}", dir.display(), e))?;
+    }
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    // The identity key waits here unencrypted
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700));
+    }
+    for (name, data) in &files {
+        // Names come from the archive; only the files a backup is made of are taken
+        if !known.contains(&name.as_str()) {
+            log::warn!("Ignoring unexpected file {} in backup", name);
+            continue;
+        }
+        std::fs::write(dir.join(name), data).map_err(|e| format!("Failed to stage {}: {}", name, e))?;
+    }
+
+    log::info!(
+        "Staged backup from {} ({} files); it is restored at the next start",
+        manifest.created_at,
+        manifest.files.len()
+    );
+    Ok(manifest)
+}
+
+/// Whether a restored backup is waiting for the next start
+pub fn restore_pending() -> bool {
+    pending_dir().exists()
+}
+
+/// Put a staged backup in place. Runs at startup, before the stores open;
+/// returns whether there was one.
+pub fn apply_pending() -> Result<bool, String> {
+    let dir = pending_dir();
+    if !dir.exists() {
+        return Ok(false);
+    }
+
+    let identity_file = dir.join(IDENTITY);
+    if let Ok(secret) = std::fs::read_to_string(&identity_file) {
+        identity::restore_secret(&secret)?;
+    }
+
+    let targets = databases().into_iter().chain([(CONFIG, NodeConfig::path())]);
+    for (name, target) in targets {
+        let staged = dir.join(name);
+        if !staged.exists() {
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        // Journals of the database being replaced would be replayed into it
+        for suffix in ["-wal", "-shm", "-journal"] {
+            let _ = std::fs::remove_file(format!("{}{}", target.display(), suffix));
+        }
+        std::fs::copy(&staged, &target).map_err(|e| format!("Failed to restore {}: {}", name, e))?;
+    }
+
+    std::fs::remove_dir_all(&dir).map_err(|e| format!("Failed to clear {}: {}", dir.display(), e))?;
+    log::info!("Restored node state from backup");
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sealed(passphrase: &str) -> Vec<u8> {
+        let files = vec![
+            (CONFIG.to_string(), br#"{"nodeName":"test"}"#.to_vec()),
+            ("node.db".to_string(), vec![0u8, 1, 2, 3]),
+        ];
+        let manifest = BackupManifest {
+            version: FORMAT_VERSION,
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            app_version: "test".to_string(),
+            public_key: None,
+            files: files.iter().map(|(name, _)| name.clone()).collect(),
+        };
+        seal(passphrase, &manifest, &files).unwrap()
+    }
+
+    #[test]
+    fn round_trips_with_the_passphrase() {
+        let backup = sealed("correct horse battery");
+        assert!(backup.starts_with(MAGIC));
+
+        let (manifest, files) = open(&backup, "correct horse battery").unwrap();
+        assert_eq!(manifest.files, vec![CONFIG.to_string(), "node.db".to_string()]);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[CONFIG], br#"{"nodeName":"test"}"#.to_vec());
+        assert_eq!(files["node.db"], vec![0u8, 1, 2, 3]);
+    }
+
+    #[test]
+    fn rejects_a_wrong_passphrase() {
+        let backup = sealed("correct horse battery");
+        let e = open(&backup, "wrong horse battery").unwrap_err();
+        assert_eq!(e, "Wrong passphrase, or the backup is damaged");
+    }
+
+    #[test]
+    fn rejects_a_damaged_backup() {
+        let mut backup = sealed("correct horse battery");
+        let last = backup.len() - 1;
+        backup[last] ^= 1;
+        assert!(open(&backup, "correct horse battery").is_err());
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert_eq!(open(b"not a backup", "correct horse battery").unwrap_err(), "Not a node backup");
+    }
+
+    #[test]
+    fn requires_a_long_passphrase() {
+        assert!(check_passphrase("short").is_err());
+        assert!(check_passphrase("long enough").is_ok());
+    }
+}
@@ -0,0 +1,352 @@
+//! Bandwidth Metering
+//!
+//! Counts the bytes each subsystem moves over the network, per calendar
+//! month (UTC), and holds them to the caps in `bandwidth`:
+//!
+//! - `ipfs`: daemon traffic, from Kubo's own counters, polled every minute
+//! - `imagePulls` / `uploads`: images pulled or pushed, counted at their
+//!   stored size once the transfer completes
+//! - `relay`: frames exchanged with the orchestrator
+//!
+//! Totals are kept in memory and written to the state store every minute,
+//! so a crash loses at most that much. A subsystem over its cap is refused
+//! with a `Policy` error until the month turns; the IPFS daemon is stopped.
+//! Counters are exported as `otherthing.bandwidth` over OTLP and at
+//! `/metrics` for Prometheus.
+
+use chrono::{Datelike, Utc};
+use opentelemetry::KeyValue;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::state_store::BandwidthUsage;
+use super::IpfsManager;
+use crate::config::BandwidthConfig;
+use crate::error::NodeError;
+use crate::telemetry;
+
+const GB: f64 = 1_000_000_000.0;
+
+/// How often IPFS is polled and totals are saved
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Subsystem {
+    Ipfs,
+    ImagePulls,
+    Uploads,
+    Relay,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 4] = [Self::Ipfs, Self::ImagePulls, Self::Uploads, Self::Relay];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ipfs => "ipfs",
+            Self::ImagePulls => "imagePulls",
+            Self::Uploads => "uploads",
+            Self::Relay => "relay",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Ipfs => "IPFS",
+            Self::ImagePulls => "image pulls",
+            Self::Uploads => "uploads",
+            Self::Relay => "the orchestrator connection",
+        }
+    }
+
+    fn cap(self, caps: &BandwidthConfig) -> Option<f64> {
+        match self {
+            Self::Ipfs => caps.ipfs_gb,
+            Self::ImagePulls => caps.image_pulls_gb,
+            Self::Uploads => caps.uploads_gb,
+            Self::Relay => caps.relay_gb,
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|subsystem| subsystem.as_str() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Transfer {
+    rx: u64,
+    tx: u64,
+}
+
+impl Transfer {
+    fn total(self) -> u64 {
+        self.rx.saturating_add(self.tx)
+    }
+}
+
+/// One subsystem's transfer this month
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubsystemUsage {
+    pub subsystem: &'static str,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap_bytes: Option<u64>,
+    pub capped: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthReport {
+    /// `YYYY-MM`
+    pub month: String,
+    pub subsystems: Vec<SubsystemUsage>,
+    pub total_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_cap_bytes: Option<u64>,
+}
+
+struct Month {
+    month: String,
+    bytes: HashMap<Subsystem, Transfer>,
+    dirty: bool,
+}
+
+pub struct BandwidthMeter {
+    store: BandwidthUsage,
+    caps: Mutex<BandwidthConfig>,
+    current: Mutex<Month>,
+    /// Kubo's counters at the last poll; they restart with the daemon
+    ipfs_seen: Mutex<Option<(u64, u64)>>,
+}
+
+impl BandwidthMeter {
+    pub fn new(store: BandwidthUsage, caps: BandwidthConfig) -> Self {
+        let month = current_month();
+        let mut bytes = HashMap::new();
+        match store.month(&month) {
+            Ok(rows) => {
+                for (name, rx, tx) in rows {
+                    if let Some(subsystem) = Subsystem::parse(&name) {
+                        bytes.insert(subsystem, Transfer { rx, tx });
+                    }
+                }
+            }
+            Err(e) => log::warn!("{}", e),
+        }
+        Self {
+            store,
+            caps: Mutex::new(caps),
+            current: Mutex::new(Month { month, bytes, dirty: false }),
+            ipfs_seen: Mutex::new(None),
+        }
+    }
+
+    /// Caps apply from the next transfer
+    pub fn set_caps(&self, caps: BandwidthConfig) {
+        *self.caps.lock().unwrap() = caps;
+    }
+
+    /// Count bytes received and sent by `subsystem`
+    pub fn record(&self, subsystem: Subsystem, rx: u64, tx: u64) {
+        if rx == 0 && tx == 0 {
+            return;
+        }
+        {
+            let mut current = self.current.lock().unwrap();
+            self.roll(&mut current);
+            let transfer = current.bytes.entry(subsystem).or_default();
+            transfer.rx = transfer.rx.saturating_add(rx);
+            transfer.tx = transfer.tx.saturating_add(tx);
+            current.dirty = true;
+        }
+        for (direction, bytes) in [("rx", rx), ("tx", tx)] {
+            if bytes > 0 {
+                telemetry::count_bytes(
+                    "otherthing.bandwidth",
+                    bytes,
+                    &[
+                        KeyValue::new("subsystem", subsystem.as_str()),
+                        KeyValue::new("direction", direction),
+                    ],
+                );
+            }
+        }
+    }
+
+    /// Refuse when `subsystem`, or the node as a whole, is over its cap
+    pub fn check(&self, subsystem: Subsystem) -> Result<(), NodeError> {
+        let caps = self.caps.lock().unwrap().clone();
+        let mut current = self.current.lock().unwrap();
+        self.roll(&mut current);
+        let used = current.bytes.get(&subsystem).copied().unwrap_or_default().total();
+        let total: u64 = current.bytes.values().map(|transfer| transfer.total()).sum();
+
+        let over = |used: u64, cap: Option<f64>| cap.filter(|&cap| used as f64 >= cap * GB);
+        let exceeded = over(used, subsystem.cap(&caps))
+            .map(|cap| (format!("for {}", subsystem.label()), used, cap))
+            .or_else(|| over(total, caps.total_gb).map(|cap| ("in total".to_string(), total, cap)));
+        match exceeded {
+            Some((scope, used, cap)) => Err(NodeError::Policy(format!(
+                "Monthly bandwidth cap {} reached ({:.2} of {} GB); it resets on {}",
+                scope,
+                used as f64 / GB,
+                cap,
+                next_month()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn report(&self) -> BandwidthReport {
+        let caps = self.caps.lock().unwrap().clone();
+        let mut current = self.current.lock().unwrap();
+        self.roll(&mut current);
+        let total_bytes: u64 = current.bytes.values().map(|transfer| transfer.total()).sum();
+        let total_cap = caps.total_gb.map(cap_bytes);
+        let subsystems = Subsystem::ALL
+            .into_iter()
+            .map(|subsystem| {
+                let transfer = current.bytes.get(&subsystem).copied().unwrap_or_default();
+                let cap = subsystem.cap(&caps).map(cap_bytes);
+                SubsystemUsage {
+                    subsystem: subsystem.as_str(),
+                    rx_bytes: transfer.rx,
+                    tx_bytes: transfer.tx,
+                    cap_bytes: cap,
+                    capped: cap.is_some_and(|cap| transfer.total() >= cap)
+                        || total_cap.is_some_and(|cap| total_bytes >= cap),
+                }
+            })
+            .collect();
+        BandwidthReport {
+            month: current.month.clone(),
+            subsystems,
+            total_bytes,
+            total_cap_bytes: total_cap,
+        }
+    }
+
+    /// Counters in the Prometheus text format
+    pub fn prometheus(&self) -> String {
+        let report = self.report();
+        let mut out = String::new();
+        out.push_str("# HELP otherthing_bandwidth_bytes Bytes transferred this month, by subsystem and direction\n");
+        out.push_str("# TYPE otherthing_bandwidth_bytes gauge\n");
+        for usage in &report.subsystems {
+            for (direction, bytes) in [("rx", usage.rx_bytes), ("tx", usage.tx_bytes)] {
+                out.push_str(&format!(
+                    "otherthing_bandwidth_bytes{{subsystem=\"{}\",direction=\"{}\"}} {}\n",
+                    usage.subsystem, direction, bytes
+                ));
+            }
+        }
+        out.push_str("# HELP otherthing_bandwidth_cap_bytes Monthly cap, by subsystem\n");
+        out.push_str("# TYPE otherthing_bandwidth_cap_bytes gauge\n");
+        for usage in &report.subsystems {
+            if let Some(cap) = usage.cap_bytes {
+                out.push_str(&format!(
+                    "otherthing_bandwidth_cap_bytes{{subsystem=\"{}\"}} {}\n",
+                    usage.subsystem, cap
+                ));
+            }
+        }
+        if let Some(cap) = report.total_cap_bytes {
+            out.push_str(&format!("otherthing_bandwidth_cap_bytes{{subsystem=\"total\"}} {}\n", cap));
+        }
+        out.push_str("# HELP otherthing_bandwidth_capped Whether a subsystem is over its cap\n");
+        out.push_str("# TYPE otherthing_bandwidth_capped gauge\n");
+        for usage in &report.subsystems {
+            out.push_str(&format!(
+                "otherthing_bandwidth_capped{{subsystem=\"{}\"}} {}\n",
+                usage.subsystem,
+                u8::from(usage.capped)
+            ));
+        }
+        out
+    }
+
+    /// Save this month's totals
+    pub fn flush(&self) -> Result<(), String> {
+        let mut current = self.current.lock().unwrap();
+        self.roll(&mut current);
+        save(&self.store, &mut current)
+    }
+
+    /// Poll IPFS and save totals every minute for the life of the app,
+    /// stopping the daemon while IPFS is over its cap
+    pub async fn watch(self: Arc<Self>, ipfs: Arc<IpfsManager>) {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            if ipfs.is_running() {
+                self.poll_ipfs(&ipfs).await;
+                if let Err(e) = self.check(Subsystem::Ipfs) {
+                    log::warn!("{}; stopping IPFS", e);
+                    if let Err(e) = ipfs.stop().await {
+                        log::warn!("Failed to stop IPFS: {}", e);
+                    }
+                }
+            } else {
+                *self.ipfs_seen.lock().unwrap() = None;
+            }
+            if let Err(e) = self.flush() {
+                log::warn!("{}", e);
+            }
+        }
+    }
+
+    async fn poll_ipfs(&self, ipfs: &IpfsManager) {
+        let Ok((rx, tx)) = ipfs.bandwidth_totals().await else {
+            return;
+        };
+        let seen = self.ipfs_seen.lock().unwrap().replace((rx, tx));
+        // Lower than last time: the daemon restarted and counts from zero
+        let (delta_rx, delta_tx) = match seen {
+            Some((seen_rx, seen_tx)) if rx >= seen_rx && tx >= seen_tx => (rx - seen_rx, tx - seen_tx),
+            _ => (rx, tx),
+        };
+        self.record(Subsystem::Ipfs, delta_rx, delta_tx);
+    }
+
+    /// Start over when the month has turned, saving the one that ended
+    fn roll(&self, current: &mut Month) {
+        let month = current_month();
+        if current.month == month {
+            return;
+        }
+        if let Err(e) = save(&self.store, current) {
+            log::warn!("{}", e);
+        }
+        *current = Month { month, bytes: HashMap::new(), dirty: false };
+    }
+}
+
+fn save(store: &BandwidthUsage, current: &mut Month) -> Result<(), String> {
+    if !current.dirty {
+        return Ok(());
+    }
+    for (subsystem, transfer) in &current.bytes {
+        store.save(&current.month, subsystem.as_str(), transfer.rx, transfer.tx)?;
+    }
+    current.dirty = false;
+    Ok(())
+}
+
+fn cap_bytes(gb: f64) -> u64 {
+    (gb.max(0.0) * GB) as u64
+}
+
+fn current_month() -> String {
+    Utc::now().format("%Y-%m").to_string()
+}
+
+/// First day of next month, `YYYY-MM-DD`
+fn next_month() -> String {
+    let now = Utc::now();
+    let (year, month) = if now.month() == 12 { (now.year() + 1, 1) } else { (now.year(), now.month() + 1) };
+    format!("{:04}-{:02}-01", year, month)
+}
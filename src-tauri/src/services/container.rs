@@ -1,29 +1,38 @@
 //! Container Runtime Service
 //!
-//! Provides container orchestration capabilities using Docker/Podman.
-//! This is the foundation for ZLayer integration - once ZLayer's dependencies
-//! align with our stack, we can add native libcontainer support on Linux.
+//! Front for whichever container backend `RuntimeSelector` picks: Docker or
+//! Podman through their API, the native libcontainer runtime on Linux, or
+//! the in-memory mock built with the `mock-runtime` feature.
+//! Commands and API handlers only talk to the `ContainerManager`, so they
+//! work the same on every backend. It also enforces the image policy, so
+//! nothing pulls or runs an image the policy rejects, and the node's
+//! resource limits (see `resources`), so no container gets more than the
+//! contributor shares.
 
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, mpsc, RwLock};
+use opentelemetry::{trace::SpanKind, KeyValue};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[cfg(feature = "container-runtime")]
-use bollard::{
-    Docker,
-    container::{
-        Config, CreateContainerOptions, ListContainersOptions,
-        LogsOptions, RemoveContainerOptions, StartContainerOptions,
-        StopContainerOptions,
-    },
-    image::{CreateImageOptions, ListImagesOptions},
-    exec::{CreateExecOptions, StartExecResults},
+use super::container_runtime::{
+    ContainerRuntime, ContainerSpec, HealthCheck, Mount, MountType, ResourceLimits, RestartPolicy,
+    RuntimeError, RuntimeSelector, RuntimeType,
+};
+use super::audit::{AuditKind, AuditLog};
+use super::bandwidth::{BandwidthMeter, Subsystem};
+use super::image_policy::ImagePolicy;
+use super::resources;
+use crate::models;
+use crate::logging::with_context;
+use crate::models::NodeEvent;
+use crate::telemetry;
+pub use super::container_runtime::{
+    BuildImageRequest, ContainerInfo, ContainerState, ContainerStats, DiskUsage, ExecOutput, ImageInfo,
+    LogLine, LogStream, NetworkInfo, PortMapping, PruneRequest, PruneResult, UsageSummary,
 };
-
-#[cfg(feature = "container-runtime")]
-use futures_util::StreamExt;
 
 #[derive(Error, Debug)]
 pub enum ContainerError {
@@ -39,80 +48,27 @@ pub enum ContainerError {
     #[error("Container operation failed: {0}")]
     OperationFailed(String),
 
-    #[error("Docker API error: {0}")]
-    DockerError(String),
-
     #[error("Feature not enabled")]
     FeatureNotEnabled,
-}
 
-#[cfg(feature = "container-runtime")]
-impl From<bollard::errors::Error> for ContainerError {
-    fn from(err: bollard::errors::Error) -> Self {
-        ContainerError::DockerError(err.to_string())
-    }
+    #[error("Image policy: {0}")]
+    PolicyViolation(String),
 }
 
-/// Container status
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[serde(rename_all = "lowercase")]
-pub enum ContainerStatus {
-    Created,
-    Running,
-    Paused,
-    Restarting,
-    Removing,
-    Exited,
-    Dead,
-    Unknown,
-}
-
-impl From<&str> for ContainerStatus {
-    fn from(s: &str) -> Self {
-        match s.to_lowercase().as_str() {
-            "created" => ContainerStatus::Created,
-            "running" => ContainerStatus::Running,
-            "paused" => ContainerStatus::Paused,
-            "restarting" => ContainerStatus::Restarting,
-            "removing" => ContainerStatus::Removing,
-            "exited" => ContainerStatus::Exited,
-            "dead" => ContainerStatus::Dead,
-            _ => ContainerStatus::Unknown,
+impl From<RuntimeError> for ContainerError {
+    fn from(err: RuntimeError) -> Self {
+        match err {
+            RuntimeError::NotAvailable(msg) => ContainerError::RuntimeNotAvailable(msg),
+            RuntimeError::ContainerNotFound(id) => ContainerError::NotFound(id),
+            RuntimeError::ImageNotFound(image) => ContainerError::ImageNotFound(image),
+            RuntimeError::OperationFailed(msg) | RuntimeError::Config(msg) => ContainerError::OperationFailed(msg),
+            RuntimeError::Io(e) => ContainerError::OperationFailed(e.to_string()),
         }
     }
 }
 
-/// Container information
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ContainerInfo {
-    pub id: String,
-    pub name: String,
-    pub image: String,
-    pub status: ContainerStatus,
-    pub created: i64,
-    pub ports: Vec<PortMapping>,
-    pub labels: HashMap<String, String>,
-}
-
-/// Port mapping
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PortMapping {
-    pub container_port: u16,
-    pub host_port: Option<u16>,
-    pub protocol: String,
-}
-
-/// Image information
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ImageInfo {
-    pub id: String,
-    pub repo_tags: Vec<String>,
-    pub size: i64,
-    pub created: i64,
-}
-
 /// Container creation request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CreateContainerRequest {
     pub name: String,
     pub image: String,
@@ -123,15 +79,127 @@ pub struct CreateContainerRequest {
     pub labels: Option<HashMap<String, String>>,
     pub memory_limit: Option<i64>,
     pub cpu_shares: Option<i64>,
+    /// Hard CPU limit in units of 1e-9 CPUs
+    #[serde(default)]
+    pub nano_cpus: Option<i64>,
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    /// Run without any network access
+    #[serde(default)]
+    pub network_disabled: Option<bool>,
+    /// Network to attach to instead of the default bridge
+    #[serde(default)]
+    pub network: Option<String>,
+    /// Extra DNS names for the container on `network`
+    #[serde(default)]
+    pub network_aliases: Option<Vec<String>>,
+    /// Give the container the host's NVIDIA GPUs
     pub gpu: Option<bool>,
+    /// GPU indices or UUIDs to expose instead of all of them
+    #[serde(default)]
+    pub gpu_devices: Option<Vec<String>>,
+    #[serde(default)]
+    pub healthcheck: Option<HealthCheck>,
+    #[serde(default)]
+    pub restart_policy: Option<RestartPolicy>,
 }
 
-/// Container execution result
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ExecResult {
-    pub exit_code: i64,
-    pub stdout: String,
-    pub stderr: String,
+impl CreateContainerRequest {
+    /// The backend-neutral spec for this request. `cmd` overrides the
+    /// image's CMD and keeps its entrypoint, as with `docker run`.
+    fn into_spec(self) -> ContainerSpec {
+        let env = self.env.map(|vars| {
+            vars.iter()
+                .map(|var| {
+                    let (key, value) = var.split_once('=').unwrap_or((var, ""));
+                    (key.to_string(), value.to_string())
+                })
+                .collect()
+        });
+
+        let mounts = self.volumes.map(|volumes| {
+            volumes.iter().map(String::as_str).filter_map(Self::parse_volume).collect()
+        });
+
+        let resources = (self.memory_limit.is_some() || self.cpu_shares.is_some() || self.nano_cpus.is_some())
+            .then(|| ResourceLimits {
+                memory: self.memory_limit,
+                cpu_shares: self.cpu_shares,
+                cpus: self.nano_cpus.map(|n| n as f64 / 1e9),
+                ..Default::default()
+            });
+
+        let network_mode = match self.network_disabled {
+            Some(true) => Some("none".to_string()),
+            _ => self.network,
+        };
+
+        ContainerSpec {
+            name: self.name,
+            image: self.image,
+            command: None,
+            args: self.cmd,
+            env,
+            workdir: self.working_dir,
+            ports: self.ports,
+            mounts,
+            resources,
+            labels: self.labels,
+            user: None,
+            hostname: None,
+            network_mode,
+            privileged: None,
+            readonly_rootfs: None,
+            healthcheck: self.healthcheck,
+            restart_policy: self.restart_policy,
+            network_aliases: self.network_aliases,
+            gpu: self.gpu,
+            gpu_devices: self.gpu_devices,
+        }
+    }
+
+    /// A bind mount given as `host:container[:ro|:rw]`. The host path is
+    /// split off at the last colon so Windows drive letters survive.
+    fn parse_volume(volume: &str) -> Option<Mount> {
+        let (rest, readonly) = match volume.rsplit_once(':') {
+            Some((rest, "ro")) => (rest, true),
+            Some((rest, "rw")) => (rest, false),
+            _ => (volume, false),
+        };
+        let (source, target) = rest.rsplit_once(':')?;
+        Some(Mount {
+            source: source.to_string(),
+            target: target.to_string(),
+            mount_type: MountType::Bind,
+            readonly,
+        })
+    }
+}
+
+/// New resource limits for an existing container; unset fields are left
+/// as they are
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateContainerRequest {
+    pub memory_limit: Option<i64>,
+    /// Memory plus swap; -1 for unlimited swap
+    pub memory_swap: Option<i64>,
+    pub cpu_shares: Option<i64>,
+    /// Hard CPU limit in units of 1e-9 CPUs
+    pub nano_cpus: Option<i64>,
+    pub pids_limit: Option<i64>,
+}
+
+impl From<UpdateContainerRequest> for ResourceLimits {
+    fn from(request: UpdateContainerRequest) -> Self {
+        ResourceLimits {
+            memory: request.memory_limit,
+            memory_swap: request.memory_swap,
+            cpu_shares: request.cpu_shares,
+            cpus: request.nano_cpus.map(|n| n as f64 / 1e9),
+            pids_limit: request.pids_limit,
+            ..Default::default()
+        }
+    }
 }
 
 /// Runtime information
@@ -143,22 +211,74 @@ pub struct RuntimeInfo {
     pub api_version: String,
     pub os: String,
     pub arch: String,
+    /// Backend chosen in the settings; `runtime_type` differs from it when
+    /// that backend is not available
+    pub preferred_runtime: Option<RuntimeType>,
+}
+
+/// Run an operation on a container with its ID in the log context, in a
+/// trace span, recording how long it took
+async fn container_op<T, F>(operation: &'static str, container_id: &str, future: F) -> Result<T, ContainerError>
+where
+    F: Future<Output = Result<T, ContainerError>>,
+{
+    let attributes = vec![
+        KeyValue::new("container.operation", operation),
+        KeyValue::new("container.id", container_id.to_string()),
+    ];
+    let started = std::time::Instant::now();
+    let span = telemetry::in_span(format!("container.{}", operation), SpanKind::Internal, attributes, async {
+        let result = future.await;
+        if let Err(e) = &result {
+            telemetry::set_error(&e.to_string());
+        }
+        result
+    });
+    let result = with_context(&[("container_id", container_id)], span).await;
+    telemetry::record_duration(
+        "otherthing.container.operation.duration",
+        started.elapsed(),
+        &[
+            KeyValue::new("container.operation", operation),
+            KeyValue::new("outcome", if result.is_ok() { "ok" } else { "error" }),
+        ],
+    );
+    result
 }
 
 /// Container runtime manager
 pub struct ContainerManager {
-    #[cfg(feature = "container-runtime")]
-    docker: Option<Docker>,
+    runtime: RwLock<Option<Arc<dyn ContainerRuntime>>>,
     runtime_info: Arc<RwLock<Option<RuntimeInfo>>>,
+    preferred_runtime: RwLock<Option<RuntimeType>>,
+    image_policy: RwLock<ImagePolicy>,
+    /// Caps on what any container may use
+    resource_limits: RwLock<models::ResourceLimits>,
+    /// Where execs into containers are recorded
+    audit: Arc<AuditLog>,
+    /// Counts image pulls and pushes against the monthly caps
+    bandwidth: Arc<BandwidthMeter>,
+    events: broadcast::Sender<NodeEvent>,
 }
 
 impl ContainerManager {
-    /// Create a new container manager
-    pub async fn new() -> Self {
+    /// Create a new container manager, using `preferred` when available
+    pub async fn new(
+        preferred: Option<RuntimeType>,
+        image_policy: ImagePolicy,
+        audit: Arc<AuditLog>,
+        bandwidth: Arc<BandwidthMeter>,
+        events: broadcast::Sender<NodeEvent>,
+    ) -> Self {
         let manager = Self {
-            #[cfg(feature = "container-runtime")]
-            docker: Docker::connect_with_local_defaults().ok(),
+            runtime: RwLock::new(None),
             runtime_info: Arc::new(RwLock::new(None)),
+            preferred_runtime: RwLock::new(preferred),
+            image_policy: RwLock::new(image_policy),
+            resource_limits: RwLock::new(models::ResourceLimits::default()),
+            audit,
+            bandwidth,
+            events,
         };
 
         // Initialize runtime info
@@ -167,40 +287,145 @@ impl ContainerManager {
         manager
     }
 
-    /// Detect available container runtime
+    /// Tell subscribers a container changed state
+    fn changed(&self, container_id: &str, state: &str) {
+        let _ = self.events.send(NodeEvent::ContainerStateChanged {
+            container_id: container_id.to_string(),
+            state: state.to_string(),
+        });
+    }
+
+    /// Select the container runtime, the preferred one when available
     pub async fn detect_runtime(&self) -> Result<RuntimeInfo, ContainerError> {
-        #[cfg(feature = "container-runtime")]
+        let preferred = *self.preferred_runtime.read().await;
+        let Some(runtime) = RuntimeSelector::detect(preferred).await else {
+            *self.runtime.write().await = None;
+            *self.runtime_info.write().await = None;
+            return Err(ContainerError::RuntimeNotAvailable("No container runtime available".to_string()));
+        };
+
+        let details = runtime.info().await
+            .map_err(|e| ContainerError::RuntimeNotAvailable(e.to_string()))?;
+        let info = RuntimeInfo {
+            available: true,
+            runtime_type: details.runtime_type.to_string(),
+            version: details.version,
+            api_version: details.api_version.unwrap_or_default(),
+            os: details.os,
+            arch: details.arch,
+            preferred_runtime: preferred,
+        };
+
+        *self.runtime.write().await = Some(Arc::from(runtime));
+        *self.runtime_info.write().await = Some(info.clone());
+        Ok(info)
+    }
+
+    /// Switch to another backend (None: pick automatically)
+    pub async fn set_preferred_runtime(&self, preferred: Option<RuntimeType>) -> Result<RuntimeInfo, ContainerError> {
+        *self.preferred_runtime.write().await = preferred;
+        let info = self.detect_runtime().await?;
+        log::info!("Container runtime is now {}", info.runtime_type);
+        Ok(info)
+    }
+
+    pub async fn image_policy(&self) -> ImagePolicy {
+        self.image_policy.read().await.clone()
+    }
+
+    pub async fn set_image_policy(&self, policy: ImagePolicy) {
+        *self.image_policy.write().await = policy;
+    }
+
+    pub async fn resource_limits(&self) -> models::ResourceLimits {
+        self.resource_limits.read().await.clone()
+    }
+
+    /// Apply new limits to containers created from now on, and bring the
+    /// running containers of this node under them
+    pub async fn set_resource_limits(&self, limits: models::ResourceLimits) {
+        *self.resource_limits.write().await = limits.clone();
+        let Some(update) = resources::update(&limits) else {
+            return;
+        };
+        if !self.is_available().await {
+            return;
+        }
+        let containers = match self.list_containers(false).await {
+            Ok(containers) => containers,
+            Err(e) => {
+                log::warn!("Failed to list containers to apply resource limits: {}", e);
+                return;
+            }
+        };
+        let Ok(runtime) = self.runtime().await else {
+            return;
+        };
+        for container in containers
+            .iter()
+            .filter(|c| c.labels.get("managed_by").map(String::as_str) == Some("otherthing-node"))
         {
-            if let Some(ref docker) = self.docker {
-                match docker.version().await {
-                    Ok(version) => {
-                        let info = RuntimeInfo {
-                            available: true,
-                            runtime_type: "docker".to_string(),
-                            version: version.version.unwrap_or_default(),
-                            api_version: version.api_version.unwrap_or_default(),
-                            os: version.os.unwrap_or_default(),
-                            arch: version.arch.unwrap_or_default(),
-                        };
-
-                        let mut cached = self.runtime_info.write().await;
-                        *cached = Some(info.clone());
-
-                        return Ok(info);
-                    }
-                    Err(e) => {
-                        return Err(ContainerError::RuntimeNotAvailable(e.to_string()));
-                    }
-                }
+            if let Err(e) = runtime.update_resources(&container.id, &update).await {
+                log::warn!("Failed to apply resource limits to {}: {}", container.name, e);
             }
-
-            Err(ContainerError::RuntimeNotAvailable("Docker not connected".to_string()))
         }
+    }
 
-        #[cfg(not(feature = "container-runtime"))]
-        {
-            Err(ContainerError::FeatureNotEnabled)
+    /// Refuse to take more disk once the runtime uses the storage limit
+    async fn check_storage(&self) -> Result<(), ContainerError> {
+        let Some(max) = resources::storage_bytes(&*self.resource_limits.read().await) else {
+            return Ok(());
+        };
+        // Usage the runtime can't report does not block work
+        let Ok(usage) = self.disk_usage().await else {
+            return Ok(());
+        };
+        let used = [usage.images, usage.containers, usage.volumes, usage.build_cache]
+            .iter()
+            .map(|u| u.size.max(0) as u64)
+            .sum::<u64>();
+        if used >= max {
+            return Err(ContainerError::PolicyViolation(format!(
+                "Storage limit of {} GB reached ({:.1} GB in use)",
+                max / (1024 * 1024 * 1024),
+                used as f64 / (1024.0 * 1024.0 * 1024.0)
+            )));
         }
+        Ok(())
+    }
+
+    /// Refuse transfers once the monthly cap for `subsystem` is reached
+    fn check_bandwidth(&self, subsystem: Subsystem) -> Result<(), ContainerError> {
+        self.bandwidth
+            .check(subsystem)
+            .map_err(|e| ContainerError::PolicyViolation(e.to_string()))
+    }
+
+    /// Stored size of a local image, matched by reference
+    async fn image_size(&self, image: &str) -> Option<u64> {
+        let name = image.rsplit('/').next().unwrap_or(image);
+        let reference = if name.contains(':') || image.contains('@') {
+            image.to_string()
+        } else {
+            format!("{}:latest", image)
+        };
+        let matches = |tag: &String| {
+            *tag == reference || tag.ends_with(&format!("/{}", reference)) || reference.ends_with(&format!("/{}", tag))
+        };
+        self.list_images()
+            .await
+            .ok()?
+            .into_iter()
+            .find(|info| info.repo_tags.iter().any(matches) || info.repo_digests.iter().any(matches))
+            .map(|info| info.size.max(0) as u64)
+    }
+
+    /// Refuse images the policy does not allow
+    pub async fn check_image(&self, image: &str) -> Result<(), ContainerError> {
+        self.image_policy.read().await.check(image).map_err(|e| {
+            log::warn!("Rejected image {}: {}", image, e);
+            ContainerError::PolicyViolation(e)
+        })
     }
 
     /// Check if runtime is available
@@ -215,336 +440,253 @@ impl ContainerManager {
         cached.clone()
     }
 
-    /// List all containers
-    #[cfg(feature = "container-runtime")]
-    pub async fn list_containers(&self, all: bool) -> Result<Vec<ContainerInfo>, ContainerError> {
-        let docker = self.docker.as_ref()
-            .ok_or_else(|| ContainerError::RuntimeNotAvailable("Docker not connected".to_string()))?;
-
-        let options = ListContainersOptions::<String> {
-            all,
-            ..Default::default()
-        };
-
-        let containers = docker.list_containers(Some(options)).await?;
-
-        Ok(containers.into_iter().map(|c| {
-            let ports = c.ports.unwrap_or_default().into_iter().map(|p| {
-                PortMapping {
-                    container_port: p.private_port as u16,
-                    host_port: p.public_port.map(|hp| hp as u16),
-                    protocol: p.typ.map(|t| format!("{:?}", t).to_lowercase()).unwrap_or_else(|| "tcp".to_string()),
-                }
-            }).collect();
-
-            ContainerInfo {
-                id: c.id.unwrap_or_default(),
-                name: c.names.and_then(|n| n.first().cloned()).unwrap_or_default()
-                    .trim_start_matches('/').to_string(),
-                image: c.image.unwrap_or_default(),
-                status: c.state.as_deref().map(ContainerStatus::from).unwrap_or(ContainerStatus::Unknown),
-                created: c.created.unwrap_or(0),
-                ports,
-                labels: c.labels.unwrap_or_default(),
-            }
-        }).collect())
+    /// The selected backend. Operations hold their own handle, so a switch
+    /// of backend does not cut off one in progress.
+    async fn runtime(&self) -> Result<Arc<dyn ContainerRuntime>, ContainerError> {
+        if let Some(runtime) = self.runtime.read().await.as_ref() {
+            return Ok(Arc::clone(runtime));
+        }
+        if cfg!(not(any(
+            feature = "container-runtime",
+            feature = "mock-runtime",
+            all(target_os = "linux", feature = "native-containers")
+        ))) {
+            return Err(ContainerError::FeatureNotEnabled);
+        }
+        // A daemon may have been started since the last detection
+        self.detect_runtime().await?;
+        self.runtime.read().await.as_ref().map(Arc::clone)
+            .ok_or_else(|| ContainerError::RuntimeNotAvailable("No container runtime available".to_string()))
     }
 
-    #[cfg(not(feature = "container-runtime"))]
-    pub async fn list_containers(&self, _all: bool) -> Result<Vec<ContainerInfo>, ContainerError> {
-        Err(ContainerError::FeatureNotEnabled)
+    /// List all containers
+    pub async fn list_containers(&self, all: bool) -> Result<Vec<ContainerInfo>, ContainerError> {
+        Ok(self.runtime().await?.list_containers(all).await?)
     }
 
     /// List images
-    #[cfg(feature = "container-runtime")]
-    pub async fn list_images(&self) -> Result<Vec<ImageInfo>, ContainerError> {
-        let docker = self.docker.as_ref()
-            .ok_or_else(|| ContainerError::RuntimeNotAvailable("Docker not connected".to_string()))?;
-
-        let images = docker.list_images(Some(ListImagesOptions::<String> {
-            all: false,
-            ..Default::default()
-        })).await?;
-
-        Ok(images.into_iter().map(|i| {
-            ImageInfo {
-                id: i.id,
-                repo_tags: i.repo_tags,
-                size: i.size,
-                created: i.created,
-            }
-        }).collect())
-    }
-
-    #[cfg(not(feature = "container-runtime"))]
     pub async fn list_images(&self) -> Result<Vec<ImageInfo>, ContainerError> {
-        Err(ContainerError::FeatureNotEnabled)
+        Ok(self.runtime().await?.list_images().await?)
     }
 
     /// Pull an image
-    #[cfg(feature = "container-runtime")]
     pub async fn pull_image(&self, image: &str) -> Result<(), ContainerError> {
-        let docker = self.docker.as_ref()
-            .ok_or_else(|| ContainerError::RuntimeNotAvailable("Docker not connected".to_string()))?;
-
-        let options = CreateImageOptions {
-            from_image: image,
-            ..Default::default()
-        };
-
-        let mut stream = docker.create_image(Some(options), None, None);
-
-        while let Some(result) = stream.next().await {
-            match result {
-                Ok(_info) => {
-                    // Progress update - could emit events here
-                }
-                Err(e) => {
-                    return Err(ContainerError::OperationFailed(format!("Pull failed: {}", e)));
-                }
+        self.check_image(image).await?;
+        self.check_storage().await?;
+        self.check_bandwidth(Subsystem::ImagePulls)?;
+        let had = self.image_size(image).await.is_some();
+        self.runtime().await?.pull_image(image).await
+            .map_err(|e| ContainerError::OperationFailed(format!("Pull failed: {}", e)))?;
+        if !had {
+            if let Some(size) = self.image_size(image).await {
+                self.bandwidth.record(Subsystem::ImagePulls, size, 0);
             }
         }
+        Ok(())
+    }
 
+    /// Push an image to its registry, using the saved login for it
+    pub async fn push_image(&self, image: &str) -> Result<(), ContainerError> {
+        self.check_bandwidth(Subsystem::Uploads)?;
+        self.runtime().await?.push_image(image).await?;
+        if let Some(size) = self.image_size(image).await {
+            self.bandwidth.record(Subsystem::Uploads, 0, size);
+        }
         Ok(())
     }
 
-    #[cfg(not(feature = "container-runtime"))]
-    pub async fn pull_image(&self, _image: &str) -> Result<(), ContainerError> {
-        Err(ContainerError::FeatureNotEnabled)
+    /// Build an image, sending build output lines to `progress` as they
+    /// arrive. Returns the ID of the built image.
+    pub async fn build_image(
+        &self,
+        request: BuildImageRequest,
+        progress: Option<mpsc::Sender<String>>,
+    ) -> Result<String, ContainerError> {
+        Ok(self.runtime().await?.build_image(&request, progress).await?)
     }
 
     /// Create a container
-    #[cfg(feature = "container-runtime")]
     pub async fn create_container(&self, request: CreateContainerRequest) -> Result<String, ContainerError> {
-        let docker = self.docker.as_ref()
-            .ok_or_else(|| ContainerError::RuntimeNotAvailable("Docker not connected".to_string()))?;
-
-        let mut labels = request.labels.unwrap_or_default();
-        labels.insert("managed_by".to_string(), "otherthing-node".to_string());
-
-        let config = Config {
-            image: Some(request.image.clone()),
-            cmd: request.cmd,
-            env: request.env,
-            labels: Some(labels),
-            host_config: Some(bollard::models::HostConfig {
-                memory: request.memory_limit,
-                cpu_shares: request.cpu_shares,
-                binds: request.volumes,
-                ..Default::default()
-            }),
-            ..Default::default()
-        };
-
-        let options = CreateContainerOptions {
-            name: request.name,
-            platform: None,
-        };
-
-        let response = docker.create_container(Some(options), config).await?;
-
-        Ok(response.id)
-    }
-
-    #[cfg(not(feature = "container-runtime"))]
-    pub async fn create_container(&self, _request: CreateContainerRequest) -> Result<String, ContainerError> {
-        Err(ContainerError::FeatureNotEnabled)
+        self.check_image(&request.image).await?;
+        self.check_storage().await?;
+        // Runtimes pull missing images as part of creating the container
+        let pulled = self.image_size(&request.image).await.is_none();
+        if pulled {
+            self.check_bandwidth(Subsystem::ImagePulls)?;
+        }
+        let image = request.image.clone();
+        let mut spec = request.into_spec();
+        spec.resources = resources::cap(spec.resources, &*self.resource_limits.read().await);
+        let attributes = vec![KeyValue::new("container.image", spec.image.clone())];
+        let container_id = telemetry::in_span("container.create", SpanKind::Internal, attributes, async {
+            let result = async {
+                Ok::<_, ContainerError>(self.runtime().await?.create_container(&spec).await?)
+            }
+            .await;
+            if let Err(e) = &result {
+                telemetry::set_error(&e.to_string());
+            }
+            result
+        })
+        .await?;
+        if pulled {
+            if let Some(size) = self.image_size(&image).await {
+                self.bandwidth.record(Subsystem::ImagePulls, size, 0);
+            }
+        }
+        self.changed(&container_id, "created");
+        Ok(container_id)
     }
 
     /// Start a container
-    #[cfg(feature = "container-runtime")]
     pub async fn start_container(&self, container_id: &str) -> Result<(), ContainerError> {
-        let docker = self.docker.as_ref()
-            .ok_or_else(|| ContainerError::RuntimeNotAvailable("Docker not connected".to_string()))?;
-
-        docker.start_container(container_id, None::<StartContainerOptions<String>>).await?;
-
+        container_op("start", container_id, async {
+            Ok(self.runtime().await?.start_container(container_id).await?)
+        })
+        .await?;
+        self.changed(container_id, "started");
         Ok(())
     }
 
-    #[cfg(not(feature = "container-runtime"))]
-    pub async fn start_container(&self, _container_id: &str) -> Result<(), ContainerError> {
-        Err(ContainerError::FeatureNotEnabled)
-    }
-
     /// Stop a container
-    #[cfg(feature = "container-runtime")]
     pub async fn stop_container(&self, container_id: &str, timeout: Option<i64>) -> Result<(), ContainerError> {
-        let docker = self.docker.as_ref()
-            .ok_or_else(|| ContainerError::RuntimeNotAvailable("Docker not connected".to_string()))?;
-
-        let options = StopContainerOptions {
-            t: timeout.unwrap_or(10) as i64,
-        };
-
-        docker.stop_container(container_id, Some(options)).await?;
-
+        let timeout = timeout.map(|t| t.max(0) as u32);
+        container_op("stop", container_id, async {
+            Ok(self.runtime().await?.stop_container(container_id, timeout).await?)
+        })
+        .await?;
+        self.changed(container_id, "stopped");
         Ok(())
     }
 
-    #[cfg(not(feature = "container-runtime"))]
-    pub async fn stop_container(&self, _container_id: &str, _timeout: Option<i64>) -> Result<(), ContainerError> {
-        Err(ContainerError::FeatureNotEnabled)
-    }
-
     /// Remove a container
-    #[cfg(feature = "container-runtime")]
     pub async fn remove_container(&self, container_id: &str, force: bool) -> Result<(), ContainerError> {
-        let docker = self.docker.as_ref()
-            .ok_or_else(|| ContainerError::RuntimeNotAvailable("Docker not connected".to_string()))?;
-
-        let options = RemoveContainerOptions {
-            force,
-            ..Default::default()
-        };
-
-        docker.remove_container(container_id, Some(options)).await?;
+        container_op("remove", container_id, async {
+            Ok(self.runtime().await?.remove_container(container_id, force).await?)
+        })
+        .await?;
+        self.changed(container_id, "removed");
+        Ok(())
+    }
 
+    /// Restart a container, giving it `timeout` seconds to stop
+    pub async fn restart_container(&self, container_id: &str, timeout: Option<i64>) -> Result<(), ContainerError> {
+        let timeout = timeout.map(|t| t.max(0) as u32);
+        container_op("restart", container_id, async {
+            Ok(self.runtime().await?.restart_container(container_id, timeout).await?)
+        })
+        .await?;
+        self.changed(container_id, "restarted");
         Ok(())
     }
 
-    #[cfg(not(feature = "container-runtime"))]
-    pub async fn remove_container(&self, _container_id: &str, _force: bool) -> Result<(), ContainerError> {
-        Err(ContainerError::FeatureNotEnabled)
+    /// Rename a container
+    pub async fn rename_container(&self, container_id: &str, name: &str) -> Result<(), ContainerError> {
+        Ok(self.runtime().await?.rename_container(container_id, name).await?)
+    }
+
+    /// Change the CPU and memory limits of a container, running or not
+    pub async fn update_container(&self, container_id: &str, request: UpdateContainerRequest) -> Result<(), ContainerError> {
+        let limits = resources::cap(Some(ResourceLimits::from(request)), &*self.resource_limits.read().await)
+            .unwrap_or_default();
+        Ok(self.runtime().await?.update_resources(container_id, &limits).await?)
     }
 
     /// Get container logs
-    #[cfg(feature = "container-runtime")]
     pub async fn get_logs(&self, container_id: &str, tail: Option<usize>) -> Result<String, ContainerError> {
-        let docker = self.docker.as_ref()
-            .ok_or_else(|| ContainerError::RuntimeNotAvailable("Docker not connected".to_string()))?;
+        self.runtime().await?.logs(container_id, tail, false).await
+            .map_err(|e| ContainerError::OperationFailed(format!("Log fetch failed: {}", e)))
+    }
 
-        let options = LogsOptions::<String> {
-            stdout: true,
-            stderr: true,
-            tail: tail.map(|t| t.to_string()).unwrap_or_else(|| "100".to_string()),
-            ..Default::default()
-        };
+    /// Send the last `tail` lines, then keep sending new output as it is
+    /// written, until the container stops or the receiver is dropped
+    pub async fn follow_logs(
+        &self,
+        container_id: &str,
+        tail: Option<usize>,
+        tx: mpsc::Sender<LogLine>,
+    ) -> Result<(), ContainerError> {
+        Ok(self.runtime().await?.follow_logs(container_id, tail, tx).await?)
+    }
 
-        let mut stream = docker.logs(container_id, Some(options));
-        let mut output = String::new();
-
-        while let Some(result) = stream.next().await {
-            match result {
-                Ok(log) => {
-                    output.push_str(&log.to_string());
-                }
-                Err(e) => {
-                    return Err(ContainerError::OperationFailed(format!("Log fetch failed: {}", e)));
-                }
-            }
-        }
+    /// Current resource usage of a running container
+    pub async fn stats(&self, container_id: &str) -> Result<ContainerStats, ContainerError> {
+        Ok(self.runtime().await?.stats(container_id).await?)
+    }
 
-        Ok(output)
+    /// Execute command in container
+    pub async fn exec_in_container(&self, container_id: &str, cmd: Vec<String>) -> Result<ExecOutput, ContainerError> {
+        let result = container_op("exec", container_id, async {
+            self.runtime().await?.exec(container_id, &cmd, false).await
+                .map_err(|e| ContainerError::OperationFailed(format!("Exec failed: {}", e)))
+        })
+        .await;
+        self.audit.log(
+            AuditKind::ContainerExec,
+            container_id,
+            serde_json::json!({
+                "cmd": cmd,
+                "exitCode": result.as_ref().ok().map(|o| o.exit_code),
+                "error": result.as_ref().err().map(|e| e.to_string()),
+            }),
+        );
+        result
     }
 
-    #[cfg(not(feature = "container-runtime"))]
-    pub async fn get_logs(&self, _container_id: &str, _tail: Option<usize>) -> Result<String, ContainerError> {
-        Err(ContainerError::FeatureNotEnabled)
+    /// Inspect a container
+    pub async fn inspect_container(&self, container_id: &str) -> Result<ContainerInfo, ContainerError> {
+        Ok(self.runtime().await?.inspect_container(container_id).await?)
     }
 
-    /// Execute command in container
-    #[cfg(feature = "container-runtime")]
-    pub async fn exec_in_container(&self, container_id: &str, cmd: Vec<String>) -> Result<ExecResult, ContainerError> {
-        let docker = self.docker.as_ref()
-            .ok_or_else(|| ContainerError::RuntimeNotAvailable("Docker not connected".to_string()))?;
-
-        let exec_options = CreateExecOptions {
-            attach_stdout: Some(true),
-            attach_stderr: Some(true),
-            cmd: Some(cmd),
-            ..Default::default()
-        };
+    /// Disk space used by images, containers, volumes and the build cache
+    pub async fn disk_usage(&self) -> Result<DiskUsage, ContainerError> {
+        Ok(self.runtime().await?.disk_usage().await?)
+    }
 
-        let exec = docker.create_exec(container_id, exec_options).await?;
-
-        let mut stdout = String::new();
-        let mut stderr = String::new();
-
-        if let StartExecResults::Attached { mut output, .. } = docker.start_exec(&exec.id, None).await? {
-            while let Some(result) = output.next().await {
-                match result {
-                    Ok(log) => {
-                        match log {
-                            bollard::container::LogOutput::StdOut { message } => {
-                                stdout.push_str(&String::from_utf8_lossy(&message));
-                            }
-                            bollard::container::LogOutput::StdErr { message } => {
-                                stderr.push_str(&String::from_utf8_lossy(&message));
-                            }
-                            _ => {}
-                        }
-                    }
-                    Err(e) => {
-                        return Err(ContainerError::OperationFailed(format!("Exec failed: {}", e)));
-                    }
-                }
-            }
-        }
+    /// Remove unused images, stopped containers and unused volumes. Only
+    /// containers created by this node are pruned.
+    pub async fn prune(&self, request: PruneRequest) -> Result<PruneResult, ContainerError> {
+        let result = self.runtime().await?.prune(&request).await?;
 
-        // Get exit code
-        let inspect = docker.inspect_exec(&exec.id).await?;
-        let exit_code = inspect.exit_code.unwrap_or(-1);
+        log::info!(
+            "Pruned {} containers, {} images, {} volumes ({} bytes)",
+            result.containers_deleted.len(),
+            result.images_deleted.len(),
+            result.volumes_deleted.len(),
+            result.space_reclaimed,
+        );
 
-        Ok(ExecResult {
-            exit_code,
-            stdout,
-            stderr,
-        })
+        Ok(result)
     }
 
-    #[cfg(not(feature = "container-runtime"))]
-    pub async fn exec_in_container(&self, _container_id: &str, _cmd: Vec<String>) -> Result<ExecResult, ContainerError> {
-        Err(ContainerError::FeatureNotEnabled)
+    /// Create a bridge network for a group of containers. An `internal`
+    /// network has no route to the outside world.
+    pub async fn create_network(
+        &self,
+        name: &str,
+        internal: bool,
+        labels: HashMap<String, String>,
+    ) -> Result<String, ContainerError> {
+        Ok(self.runtime().await?.create_network(name, internal, &labels).await?)
     }
 
-    /// Inspect a container
-    #[cfg(feature = "container-runtime")]
-    pub async fn inspect_container(&self, container_id: &str) -> Result<ContainerInfo, ContainerError> {
-        let docker = self.docker.as_ref()
-            .ok_or_else(|| ContainerError::RuntimeNotAvailable("Docker not connected".to_string()))?;
-
-        let inspect = docker.inspect_container(container_id, None).await?;
-
-        let ports = inspect.network_settings
-            .and_then(|ns| ns.ports)
-            .map(|ports| {
-                ports.into_iter()
-                    .filter_map(|(port_str, bindings)| {
-                        let parts: Vec<&str> = port_str.split('/').collect();
-                        let container_port = parts.first()?.parse().ok()?;
-                        let protocol = parts.get(1).unwrap_or(&"tcp").to_string();
-                        let host_port = bindings
-                            .and_then(|b| b.first().cloned())
-                            .and_then(|b| b.host_port)
-                            .and_then(|p| p.parse().ok());
-
-                        Some(PortMapping {
-                            container_port,
-                            host_port,
-                            protocol,
-                        })
-                    })
-                    .collect()
-            })
-            .unwrap_or_default();
+    /// List networks
+    pub async fn list_networks(&self) -> Result<Vec<NetworkInfo>, ContainerError> {
+        Ok(self.runtime().await?.list_networks().await?)
+    }
 
-        Ok(ContainerInfo {
-            id: inspect.id.unwrap_or_default(),
-            name: inspect.name.unwrap_or_default().trim_start_matches('/').to_string(),
-            image: inspect.config.and_then(|c| c.image).unwrap_or_default(),
-            status: inspect.state
-                .and_then(|s| s.status)
-                .map(|s| ContainerStatus::from(format!("{:?}", s).to_lowercase().as_str()))
-                .unwrap_or(ContainerStatus::Unknown),
-            created: 0, // Would need to parse the timestamp
-            ports,
-            labels: HashMap::new(),
-        })
+    /// Remove a network; it must have no containers attached
+    pub async fn remove_network(&self, name: &str) -> Result<(), ContainerError> {
+        Ok(self.runtime().await?.remove_network(name).await?)
+    }
+
+    /// Attach a container to a network
+    pub async fn connect_network(&self, network: &str, container_id: &str) -> Result<(), ContainerError> {
+        Ok(self.runtime().await?.connect_network(network, container_id).await?)
     }
 
-    #[cfg(not(feature = "container-runtime"))]
-    pub async fn inspect_container(&self, _container_id: &str) -> Result<ContainerInfo, ContainerError> {
-        Err(ContainerError::FeatureNotEnabled)
+    /// Detach a container from a network
+    pub async fn disconnect_network(&self, network: &str, container_id: &str, force: bool) -> Result<(), ContainerError> {
+        Ok(self.runtime().await?.disconnect_network(network, container_id, force).await?)
     }
 }
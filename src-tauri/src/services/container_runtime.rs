@@ -4,11 +4,13 @@
 //! implemented by different backends:
 //! - Docker/Podman via bollard (cross-platform)
 //! - Native libcontainer/youki (Linux only, no daemon required)
+//! - An in-memory mock for tests and demos (`mock-runtime` feature)
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
+use tokio::sync::mpsc;
 
 /// Container runtime error
 #[derive(Debug, thiserror::Error)]
@@ -41,6 +43,8 @@ pub enum RuntimeType {
     Docker,
     Podman,
     Native,
+    /// In-memory stand-in, with the `mock-runtime` feature
+    Mock,
     Unknown,
 }
 
@@ -50,6 +54,7 @@ impl std::fmt::Display for RuntimeType {
             RuntimeType::Docker => write!(f, "docker"),
             RuntimeType::Podman => write!(f, "podman"),
             RuntimeType::Native => write!(f, "native"),
+            RuntimeType::Mock => write!(f, "mock"),
             RuntimeType::Unknown => write!(f, "unknown"),
         }
     }
@@ -102,14 +107,97 @@ pub struct ContainerSpec {
     pub privileged: Option<bool>,
     /// Read-only root filesystem
     pub readonly_rootfs: Option<bool>,
+    /// Health check run inside the container
+    #[serde(default)]
+    pub healthcheck: Option<HealthCheck>,
+    /// What to do when the container exits
+    #[serde(default)]
+    pub restart_policy: Option<RestartPolicy>,
+    /// Extra DNS names for the container on its network
+    #[serde(default)]
+    pub network_aliases: Option<Vec<String>>,
+    /// Give the container the host's NVIDIA GPUs
+    #[serde(default)]
+    pub gpu: Option<bool>,
+    /// GPU indices or UUIDs to expose instead of all of them
+    #[serde(default)]
+    pub gpu_devices: Option<Vec<String>>,
+}
+
+/// When the runtime restarts a container after it exits
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Never restart
+    #[default]
+    No,
+    /// Restart after a non-zero exit, at most `max_retries` times
+    OnFailure { max_retries: Option<u32> },
+    /// Always restart; Docker also starts it again when the daemon restarts
+    Always,
+    /// Like `Always`, but stays down across daemon restarts once stopped
+    UnlessStopped,
+}
+
+impl RestartPolicy {
+    /// Whether a container that exited with `exit_code` after `restarts`
+    /// earlier restarts is started again. Explicit stops are not exits.
+    pub fn should_restart(&self, exit_code: i32, restarts: u32) -> bool {
+        match self {
+            RestartPolicy::No => false,
+            RestartPolicy::OnFailure { max_retries } => {
+                exit_code != 0 && max_retries.map_or(true, |max| restarts < max)
+            }
+            RestartPolicy::Always | RestartPolicy::UnlessStopped => true,
+        }
+    }
+}
+
+/// Command the runtime runs periodically to decide whether a container
+/// is healthy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheck {
+    /// Command to run; a single element is run through the shell. Exit
+    /// code 0 means healthy.
+    pub command: Vec<String>,
+    /// Seconds between checks
+    pub interval_secs: Option<u64>,
+    /// Seconds a check may take before it counts as failed
+    pub timeout_secs: Option<u64>,
+    /// Consecutive failures before the container is unhealthy
+    pub retries: Option<u32>,
+    /// Seconds after start during which failures are not counted
+    pub start_period_secs: Option<u64>,
+}
+
+impl HealthCheck {
+    /// Docker's `Test` form of the command
+    pub fn test(&self) -> Vec<String> {
+        match self.command.as_slice() {
+            [shell] => vec!["CMD-SHELL".to_string(), shell.clone()],
+            args => std::iter::once("CMD".to_string()).chain(args.iter().cloned()).collect(),
+        }
+    }
+}
+
+/// Result of a container's health check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    /// Within the start period, no verdict yet
+    Starting,
+    Healthy,
+    Unhealthy,
 }
 
 /// Port mapping
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortMapping {
-    pub host_port: u16,
+    /// None when the port is only exposed, not published on the host
+    pub host_port: Option<u16>,
     pub container_port: u16,
     pub protocol: String, // tcp, udp
+    #[serde(default)]
     pub host_ip: Option<String>,
 }
 
@@ -131,7 +219,7 @@ pub enum MountType {
 }
 
 /// Resource limits
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ResourceLimits {
     /// Memory limit in bytes
     pub memory: Option<i64>,
@@ -155,6 +243,7 @@ pub struct ContainerInfo {
     pub id: String,
     pub name: String,
     pub image: String,
+    #[serde(rename = "status")]
     pub state: ContainerState,
     pub created: i64,
     pub started: Option<i64>,
@@ -164,6 +253,9 @@ pub struct ContainerInfo {
     pub ports: Vec<PortMapping>,
     pub mounts: Vec<Mount>,
     pub labels: HashMap<String, String>,
+    /// Set when the container has a health check
+    #[serde(default)]
+    pub health: Option<HealthStatus>,
 }
 
 /// Image information
@@ -176,6 +268,108 @@ pub struct ImageInfo {
     pub created: i64,
 }
 
+/// Network information
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkInfo {
+    pub id: String,
+    pub name: String,
+    pub driver: String,
+    /// No route to the outside world
+    pub internal: bool,
+    pub labels: HashMap<String, String>,
+    /// IDs of attached containers
+    pub containers: Vec<String>,
+}
+
+/// Resource usage of a running container
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContainerStats {
+    /// CPU time used since the container started, in microseconds
+    pub cpu_usage_usec: u64,
+    /// Current CPU use; 100 is one full core
+    pub cpu_percent: f64,
+    pub memory_usage: u64,
+    /// None when the memory is not limited
+    pub memory_limit: Option<u64>,
+    pub io_read_bytes: u64,
+    pub io_write_bytes: u64,
+    pub pids: u64,
+}
+
+/// Output stream a log line was written to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+/// One line of container output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogLine {
+    pub stream: LogStream,
+    /// RFC 3339 time the runtime recorded the line
+    pub timestamp: Option<String>,
+    pub message: String,
+}
+
+/// Image build request. The build context is either a directory, which is
+/// packed into a tarball, or an existing tarball (optionally gzipped).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BuildImageRequest {
+    pub context_path: Option<String>,
+    pub tarball_path: Option<String>,
+    /// Tag for the built image, e.g. `my-job:latest`
+    pub tag: String,
+    /// Dockerfile path relative to the context root
+    #[serde(default)]
+    pub dockerfile: Option<String>,
+    #[serde(default)]
+    pub build_args: HashMap<String, String>,
+}
+
+/// Space used by one kind of runtime object
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageSummary {
+    pub count: usize,
+    /// Bytes on disk
+    pub size: i64,
+    /// Bytes a prune could free
+    pub reclaimable: i64,
+}
+
+/// Disk space used by the container runtime, like `docker system df`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub images: UsageSummary,
+    pub containers: UsageSummary,
+    pub volumes: UsageSummary,
+    pub build_cache: UsageSummary,
+}
+
+/// What to remove in a prune
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PruneRequest {
+    /// Dangling (untagged) images
+    #[serde(default)]
+    pub images: bool,
+    /// Stopped containers created by this node
+    #[serde(default)]
+    pub containers: bool,
+    /// Volumes no container uses
+    #[serde(default)]
+    pub volumes: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PruneResult {
+    pub images_deleted: Vec<String>,
+    pub containers_deleted: Vec<String>,
+    pub volumes_deleted: Vec<String>,
+    /// Bytes freed
+    pub space_reclaimed: i64,
+}
+
 /// Exec result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecOutput {
@@ -230,15 +424,32 @@ pub trait ContainerRuntime: Send + Sync {
     /// Unpause a container
     async fn unpause_container(&self, id: &str) -> Result<()>;
 
+    /// Stop a container, waiting up to `timeout` seconds, then start it again
+    async fn restart_container(&self, id: &str, timeout: Option<u32>) -> Result<()>;
+
+    /// Rename a container
+    async fn rename_container(&self, id: &str, name: &str) -> Result<()>;
+
+    /// Change the resource limits of a container, running or not. Limits
+    /// left unset keep their current value.
+    async fn update_resources(&self, id: &str, resources: &ResourceLimits) -> Result<()>;
+
     /// Get container information
     async fn inspect_container(&self, id: &str) -> Result<ContainerInfo>;
 
     /// List containers
     async fn list_containers(&self, all: bool) -> Result<Vec<ContainerInfo>>;
 
-    /// Get container logs
+    /// Get container logs; with `follow`, keeps reading until the container exits
     async fn logs(&self, id: &str, tail: Option<usize>, follow: bool) -> Result<String>;
 
+    /// Send the last `tail` lines, then keep sending new output as it is
+    /// written, until the container stops or the receiver is dropped
+    async fn follow_logs(&self, id: &str, tail: Option<usize>, tx: mpsc::Sender<LogLine>) -> Result<()>;
+
+    /// Current resource usage of a running container
+    async fn stats(&self, id: &str) -> Result<ContainerStats>;
+
     /// Execute a command in a container
     async fn exec(&self, id: &str, cmd: &[String], tty: bool) -> Result<ExecOutput>;
 
@@ -258,14 +469,58 @@ pub trait ContainerRuntime: Send + Sync {
 
     /// Check if image exists
     async fn image_exists(&self, reference: &str) -> Result<bool>;
+
+    /// Push an image to its registry, using the saved login for it
+    async fn push_image(&self, reference: &str) -> Result<()>;
+
+    /// Build an image, sending build output lines to `progress` as they
+    /// arrive. Returns the ID of the built image.
+    async fn build_image(&self, request: &BuildImageRequest, progress: Option<mpsc::Sender<String>>) -> Result<String>;
+
+    // ============ Maintenance ============
+
+    /// Disk space used by images, containers, volumes and the build cache
+    async fn disk_usage(&self) -> Result<DiskUsage>;
+
+    /// Remove unused images, stopped containers and unused volumes. Only
+    /// containers created by this node are pruned.
+    async fn prune(&self, request: &PruneRequest) -> Result<PruneResult>;
+
+    // ============ Network Operations ============
+
+    /// Create a bridge network, returning its ID
+    async fn create_network(&self, name: &str, internal: bool, labels: &HashMap<String, String>) -> Result<String>;
+
+    /// List networks
+    async fn list_networks(&self) -> Result<Vec<NetworkInfo>>;
+
+    /// Remove a network
+    async fn remove_network(&self, name: &str) -> Result<()>;
+
+    /// Attach a container to a network
+    async fn connect_network(&self, network: &str, container: &str) -> Result<()>;
+
+    /// Detach a container from a network
+    async fn disconnect_network(&self, network: &str, container: &str, force: bool) -> Result<()>;
 }
 
 /// Runtime detection and selection
 pub struct RuntimeSelector;
 
 impl RuntimeSelector {
-    /// Detect available runtimes and return the best one
-    pub async fn detect() -> Option<Box<dyn ContainerRuntime>> {
+    /// Detect available runtimes and return the preferred one if it is
+    /// available, otherwise the best one
+    pub async fn detect(preferred: Option<RuntimeType>) -> Option<Box<dyn ContainerRuntime>> {
+        if let Some(preferred) = preferred {
+            if let Some(runtime) = Self::get(preferred).await {
+                if runtime.is_available().await {
+                    log::info!("Using preferred {} container runtime", preferred);
+                    return Some(runtime);
+                }
+            }
+            log::warn!("Preferred container runtime {} is not available", preferred);
+        }
+
         // Try native runtime first on Linux (if feature enabled)
         #[cfg(all(target_os = "linux", feature = "native-containers"))]
         {
@@ -288,6 +543,18 @@ impl RuntimeSelector {
             }
         }
 
+        Self::fallback()
+    }
+
+    /// Last resort, so tests and demos run without any runtime
+    #[cfg(feature = "mock-runtime")]
+    fn fallback() -> Option<Box<dyn ContainerRuntime>> {
+        log::warn!("No container runtime available, using the mock runtime");
+        Some(Box::new(super::mock_runtime::MockRuntime::demo()))
+    }
+
+    #[cfg(not(feature = "mock-runtime"))]
+    fn fallback() -> Option<Box<dyn ContainerRuntime>> {
         log::warn!("No container runtime available");
         None
     }
@@ -307,6 +574,8 @@ impl RuntimeSelector {
                     .await
                     .map(|r| Box::new(r) as Box<dyn ContainerRuntime>)
             }
+            #[cfg(feature = "mock-runtime")]
+            RuntimeType::Mock => Some(Box::new(super::mock_runtime::MockRuntime::demo())),
             _ => None,
         }
     }
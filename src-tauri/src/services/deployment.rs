@@ -0,0 +1,308 @@
+//! Application Deployments
+//!
+//! A small docker-compose: an app is a set of services, each one container,
+//! deployed onto a network of its own where services reach each other by
+//! name. Everything belonging to an app carries the `otherthing.app` label,
+//! so status and teardown work from what the runtime reports and nothing
+//! needs to be stored by the node.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use super::container::{
+    ContainerInfo, ContainerManager, ContainerState, CreateContainerRequest, PortMapping,
+};
+use super::container_runtime::HealthStatus;
+
+pub const APP_LABEL: &str = "otherthing.app";
+pub const SERVICE_LABEL: &str = "otherthing.app.service";
+
+/// Seconds a service gets to exit on teardown before it is killed
+const STOP_TIMEOUT: i64 = 10;
+
+/// One service of an app
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceSpec {
+    pub image: String,
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub ports: Vec<PortMapping>,
+    /// Bind mounts as `host_path:container_path[:ro]`
+    #[serde(default)]
+    pub volumes: Vec<String>,
+    /// Services that are started before this one
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub memory_limit: Option<i64>,
+}
+
+/// A multi-service application
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSpec {
+    pub name: String,
+    pub services: BTreeMap<String, ServiceSpec>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceStatus {
+    pub name: String,
+    pub container_id: String,
+    pub image: String,
+    pub status: ContainerState,
+    pub health: Option<HealthStatus>,
+    pub ports: Vec<PortMapping>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppStatus {
+    pub name: String,
+    pub network: String,
+    /// True when every service is running and none is unhealthy
+    pub running: bool,
+    pub services: Vec<ServiceStatus>,
+}
+
+pub struct AppDeployment {
+    containers: Arc<ContainerManager>,
+}
+
+impl AppDeployment {
+    pub fn new(containers: Arc<ContainerManager>) -> Self {
+        Self { containers }
+    }
+
+    fn network_name(app: &str) -> String {
+        format!("otherthing-app-{}", app)
+    }
+
+    fn container_name(app: &str, service: &str) -> String {
+        format!("otherthing-app-{}-{}", app, service)
+    }
+
+    fn valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= 63
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+
+    /// Services in an order where each comes after everything it depends on
+    fn start_order(spec: &AppSpec) -> Result<Vec<String>, String> {
+        fn visit(
+            name: &str,
+            spec: &AppSpec,
+            visiting: &mut HashSet<String>,
+            done: &mut HashSet<String>,
+            order: &mut Vec<String>,
+        ) -> Result<(), String> {
+            if done.contains(name) {
+                return Ok(());
+            }
+            if !visiting.insert(name.to_string()) {
+                return Err(format!("Dependency cycle involving service {}", name));
+            }
+            for dep in &spec.services[name].depends_on {
+                if !spec.services.contains_key(dep) {
+                    return Err(format!("Service {} depends on unknown service {}", name, dep));
+                }
+                visit(dep, spec, visiting, done, order)?;
+            }
+            visiting.remove(name);
+            done.insert(name.to_string());
+            order.push(name.to_string());
+            Ok(())
+        }
+
+        let mut order = Vec::new();
+        let mut visiting = HashSet::new();
+        let mut done = HashSet::new();
+        for name in spec.services.keys() {
+            visit(name, spec, &mut visiting, &mut done, &mut order)?;
+        }
+        Ok(order)
+    }
+
+    fn validate(spec: &AppSpec) -> Result<Vec<String>, String> {
+        if !Self::valid_name(&spec.name) {
+            return Err(format!("Invalid app name: {}", spec.name));
+        }
+        if spec.services.is_empty() {
+            return Err("An app needs at least one service".to_string());
+        }
+        for (name, service) in &spec.services {
+            if !Self::valid_name(name) {
+                return Err(format!("Invalid service name: {}", name));
+            }
+            if service.image.trim().is_empty() {
+                return Err(format!("Service {} has no image", name));
+            }
+        }
+        Self::start_order(spec)
+    }
+
+    /// Deploy an app, replacing any running deployment of the same name.
+    /// If a service fails to come up, everything created so far is removed.
+    pub async fn deploy(&self, spec: AppSpec) -> Result<AppStatus, String> {
+        let order = Self::validate(&spec)?;
+        // Refuse the whole app up front rather than after some services started
+        for (name, service) in &spec.services {
+            self.containers
+                .check_image(&service.image)
+                .await
+                .map_err(|e| format!("Service {}: {}", name, e))?;
+        }
+
+        self.teardown(&spec.name).await?;
+
+        let network = Self::network_name(&spec.name);
+        let labels = HashMap::from([(APP_LABEL.to_string(), spec.name.clone())]);
+        self.containers
+            .create_network(&network, false, labels.clone())
+            .await
+            .map_err(|e| format!("Failed to create network: {}", e))?;
+
+        log::info!("Deploying app {} ({} services)", spec.name, order.len());
+        if let Err(e) = self.start_services(&spec, &order, &network, &labels).await {
+            let _ = self.teardown(&spec.name).await;
+            return Err(e);
+        }
+
+        self.status(&spec.name).await
+    }
+
+    async fn start_services(
+        &self,
+        spec: &AppSpec,
+        order: &[String],
+        network: &str,
+        labels: &HashMap<String, String>,
+    ) -> Result<(), String> {
+        let images = self.containers.list_images().await.map_err(|e| e.to_string())?;
+
+        for name in order {
+            let service = &spec.services[name];
+
+            let present = images.iter().any(|i| i.repo_tags.iter().any(|t| t == &service.image));
+            if !present {
+                self.containers
+                    .pull_image(&service.image)
+                    .await
+                    .map_err(|e| format!("Failed to pull {} for service {}: {}", service.image, name, e))?;
+            }
+
+            let mut labels = labels.clone();
+            labels.insert(SERVICE_LABEL.to_string(), name.clone());
+
+            let request = CreateContainerRequest {
+                name: Self::container_name(&spec.name, name),
+                image: service.image.clone(),
+                cmd: service.command.clone(),
+                env: Some(service.env.iter().map(|(k, v)| format!("{}={}", k, v)).collect()),
+                ports: Some(service.ports.clone()),
+                volumes: Some(service.volumes.clone()),
+                labels: Some(labels),
+                memory_limit: service.memory_limit,
+                network: Some(network.to_string()),
+                network_aliases: Some(vec![name.clone()]),
+                ..Default::default()
+            };
+
+            let id = self.containers
+                .create_container(request)
+                .await
+                .map_err(|e| format!("Failed to create service {}: {}", name, e))?;
+            self.containers
+                .start_container(&id)
+                .await
+                .map_err(|e| format!("Failed to start service {}: {}", name, e))?;
+        }
+
+        Ok(())
+    }
+
+    async fn app_containers(&self, app: &str) -> Result<Vec<ContainerInfo>, String> {
+        let containers = self.containers.list_containers(true).await.map_err(|e| e.to_string())?;
+        Ok(containers
+            .into_iter()
+            .filter(|c| c.labels.get(APP_LABEL).map(String::as_str) == Some(app))
+            .collect())
+    }
+
+    /// Stop and remove every service of an app and its network
+    pub async fn teardown(&self, app: &str) -> Result<(), String> {
+        for container in self.app_containers(app).await? {
+            let _ = self.containers.stop_container(&container.id, Some(STOP_TIMEOUT)).await;
+            self.containers
+                .remove_container(&container.id, true)
+                .await
+                .map_err(|e| format!("Failed to remove {}: {}", container.name, e))?;
+        }
+
+        let network = Self::network_name(app);
+        let networks = self.containers.list_networks().await.map_err(|e| e.to_string())?;
+        if networks.iter().any(|n| n.name == network) {
+            self.containers
+                .remove_network(&network)
+                .await
+                .map_err(|e| format!("Failed to remove network: {}", e))?;
+            log::info!("Tore down app {}", app);
+        }
+
+        Ok(())
+    }
+
+    /// Current state of an app's services
+    pub async fn status(&self, app: &str) -> Result<AppStatus, String> {
+        let containers = self.app_containers(app).await?;
+        if containers.is_empty() {
+            return Err(format!("App not found: {}", app));
+        }
+
+        let mut services: Vec<ServiceStatus> = containers
+            .into_iter()
+            .map(|c| ServiceStatus {
+                name: c.labels.get(SERVICE_LABEL).cloned().unwrap_or_else(|| c.name.clone()),
+                container_id: c.id,
+                image: c.image,
+                status: c.state,
+                health: c.health,
+                ports: c.ports,
+            })
+            .collect();
+        services.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(AppStatus {
+            name: app.to_string(),
+            network: Self::network_name(app),
+            running: services.iter().all(|s| {
+                s.status == ContainerState::Running && s.health != Some(HealthStatus::Unhealthy)
+            }),
+            services,
+        })
+    }
+
+    /// Every deployed app
+    pub async fn list(&self) -> Result<Vec<AppStatus>, String> {
+        let containers = self.containers.list_containers(true).await.map_err(|e| e.to_string())?;
+        let mut names: Vec<String> = containers
+            .iter()
+            .filter_map(|c| c.labels.get(APP_LABEL).cloned())
+            .collect();
+        names.sort();
+        names.dedup();
+
+        let mut apps = Vec::with_capacity(names.len());
+        for name in names {
+            apps.push(self.status(&name).await?);
+        }
+        Ok(apps)
+    }
+}
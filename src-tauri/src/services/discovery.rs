@@ -0,0 +1,194 @@
+//! LAN Discovery
+//!
+//! While the node runs it announces itself over mDNS as
+//! `_otherthing._tcp.local.` and browses for other nodes doing the same,
+//! so the user can pair with a node on the same network by picking it from
+//! a list instead of typing its address. Pairing still takes the share key
+//! (see `fleet`).
+//!
+//! The announcement carries the node ID, API port, version and a summary
+//! of the hardware. Nothing derived from the share key goes out: the key is
+//! short enough that any hash of it could be reversed offline.
+
+use chrono::Utc;
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+
+use crate::models::NodeCapabilities;
+
+const SERVICE_TYPE: &str = "_otherthing._tcp.local.";
+
+/// Another node seen on the local network
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Peer {
+    pub node_id: String,
+    /// Host name of the machine
+    pub name: String,
+    /// Base URL of its API
+    pub address: String,
+    pub version: String,
+    pub cpu_cores: u32,
+    pub memory_mb: u64,
+    pub gpu_count: usize,
+    pub models: usize,
+    pub container_runtime: bool,
+    pub last_seen: String,
+}
+
+/// What this node announces
+pub struct Announcement {
+    pub port: u16,
+    pub capabilities: NodeCapabilities,
+}
+
+pub struct Discovery {
+    daemon: Mutex<Option<ServiceDaemon>>,
+    /// Full name of the registered service, while announced
+    fullname: Mutex<Option<String>>,
+    /// Peers by their service full name
+    peers: Arc<RwLock<HashMap<String, Peer>>>,
+}
+
+impl Discovery {
+    pub fn new() -> Self {
+        Self {
+            daemon: Mutex::new(None),
+            fullname: Mutex::new(None),
+            peers: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.daemon.lock().unwrap().is_some()
+    }
+
+    /// Nodes seen on the network, by name
+    pub async fn peers(&self) -> Vec<Peer> {
+        let mut peers: Vec<Peer> = self.peers.read().await.values().cloned().collect();
+        peers.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.node_id.cmp(&b.node_id)));
+        peers
+    }
+
+    /// Announce this node and browse for others; announcing again while
+    /// running replaces the announcement
+    pub fn start(&self, announcement: Announcement) -> Result<(), String> {
+        let mut daemon = self.daemon.lock().unwrap();
+        if daemon.is_none() {
+            let started = ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS: {}", e))?;
+            let receiver = started
+                .browse(SERVICE_TYPE)
+                .map_err(|e| format!("Failed to browse for nodes: {}", e))?;
+            let peers = Arc::clone(&self.peers);
+            let own_id = announcement.capabilities.node_id.clone();
+            tokio::spawn(async move {
+                while let Ok(event) = receiver.recv_async().await {
+                    match event {
+                        ServiceEvent::ServiceResolved(info) => {
+                            if let Some(peer) = peer_from(&info).filter(|peer| peer.node_id != own_id) {
+                                if !peers.read().await.contains_key(info.get_fullname()) {
+                                    log::info!("Found node {} on the LAN at {}", peer.node_id, peer.address);
+                                }
+                                peers.write().await.insert(info.get_fullname().to_string(), peer);
+                            }
+                        }
+                        ServiceEvent::ServiceRemoved(_, fullname) => {
+                            peers.write().await.remove(&fullname);
+                        }
+                        _ => {}
+                    }
+                }
+            });
+            *daemon = Some(started);
+        }
+        let daemon = daemon.as_ref().expect("mDNS daemon started above");
+
+        let info = service_info(&announcement)?;
+        let fullname = info.get_fullname().to_string();
+        daemon
+            .register(info)
+            .map_err(|e| format!("Failed to announce node: {}", e))?;
+        *self.fullname.lock().unwrap() = Some(fullname);
+        log::info!("Announcing node on the LAN");
+        Ok(())
+    }
+
+    /// Withdraw the announcement and stop browsing
+    pub async fn stop(&self) {
+        let daemon = self.daemon.lock().unwrap().take();
+        let Some(daemon) = daemon else {
+            return;
+        };
+        if let Some(fullname) = self.fullname.lock().unwrap().take() {
+            let _ = daemon.unregister(&fullname);
+        }
+        let _ = daemon.shutdown();
+        self.peers.write().await.clear();
+        log::info!("Stopped LAN discovery");
+    }
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn service_info(announcement: &Announcement) -> Result<ServiceInfo, String> {
+    let capabilities = &announcement.capabilities;
+    let host = sysinfo::System::host_name().unwrap_or_else(|| "otherthing-node".to_string());
+    let instance = format!("otherthing-{}", capabilities.node_id.chars().take(8).collect::<String>());
+    let properties: HashMap<String, String> = [
+        ("id", capabilities.node_id.clone()),
+        ("name", host.clone()),
+        ("version", capabilities.version.clone()),
+        ("cpu", capabilities.cpu_cores.to_string()),
+        ("mem", capabilities.memory_mb.to_string()),
+        ("gpus", capabilities.gpus.len().to_string()),
+        ("models", capabilities.models.len().to_string()),
+        ("containers", capabilities.container_runtime.to_string()),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value))
+    .collect();
+
+    ServiceInfo::new(
+        SERVICE_TYPE,
+        &instance,
+        &format!("{}.local.", host),
+        "",
+        announcement.port,
+        properties,
+    )
+    .map(ServiceInfo::enable_addr_auto)
+    .map_err(|e| format!("Invalid mDNS announcement: {}", e))
+}
+
+fn peer_from(info: &ServiceInfo) -> Option<Peer> {
+    let property = |key: &str| info.get_property_val_str(key).map(str::to_string);
+    let number = |key: &str| -> u64 { property(key).and_then(|v| v.parse().ok()).unwrap_or_default() };
+    let addresses = info.get_addresses();
+    // IPv4 where there is one; link-local IPv6 addresses need a scope to be reachable
+    let ip: IpAddr = addresses
+        .iter()
+        .find(|ip| ip.is_ipv4())
+        .or_else(|| addresses.iter().next())
+        .copied()?;
+
+    Some(Peer {
+        node_id: property("id")?,
+        name: property("name").unwrap_or_else(|| info.get_hostname().trim_end_matches(".local.").to_string()),
+        address: format!("http://{}", SocketAddr::new(ip, info.get_port())),
+        version: property("version").unwrap_or_default(),
+        cpu_cores: number("cpu") as u32,
+        memory_mb: number("mem"),
+        gpu_count: number("gpus") as usize,
+        models: number("models") as usize,
+        container_runtime: property("containers").as_deref() == Some("true"),
+        last_seen: Utc::now().to_rfc3339(),
+    })
+}
@@ -7,20 +7,209 @@
 use async_trait::async_trait;
 use bollard::container::{
     Config, CreateContainerOptions, InspectContainerOptions, KillContainerOptions,
-    ListContainersOptions, LogsOptions, RemoveContainerOptions, StartContainerOptions,
-    StopContainerOptions, WaitContainerOptions,
+    ListContainersOptions, LogOutput, LogsOptions, NetworkingConfig, PruneContainersOptions,
+    RemoveContainerOptions, RenameContainerOptions, RestartContainerOptions, StartContainerOptions,
+    StatsOptions, StopContainerOptions, UpdateContainerOptions, WaitContainerOptions,
 };
 use bollard::exec::{CreateExecOptions, StartExecResults};
-use bollard::image::{CreateImageOptions, ListImagesOptions, RemoveImageOptions};
-use bollard::models::{HostConfig, PortBinding};
+use bollard::image::{
+    BuildImageOptions, CreateImageOptions, ListImagesOptions, PruneImagesOptions, PushImageOptions,
+    RemoveImageOptions,
+};
+use bollard::network::{
+    ConnectNetworkOptions, CreateNetworkOptions, DisconnectNetworkOptions, ListNetworksOptions,
+};
+use bollard::models::{
+    DeviceRequest, EndpointSettings, HealthConfig, HealthStatusEnum, HostConfig, PortBinding,
+    RestartPolicyNameEnum,
+};
+use bollard::volume::PruneVolumesOptions;
+use bollard::auth::DockerCredentials;
 use bollard::Docker;
 use futures_util::StreamExt;
 use std::collections::HashMap;
+use tokio::sync::mpsc;
 
 use super::container_runtime::{
-    ContainerInfo, ContainerRuntime, ContainerSpec, ContainerState, ExecOutput, ImageInfo, Mount,
-    PortMapping, Result, RuntimeError, RuntimeInfo, RuntimeType,
+    BuildImageRequest, ContainerInfo, ContainerRuntime, ContainerSpec, ContainerState, ContainerStats,
+    DiskUsage, ExecOutput, HealthCheck, HealthStatus, ImageInfo, LogLine, LogStream, Mount, NetworkInfo,
+    PortMapping, PruneRequest, PruneResult, ResourceLimits, RestartPolicy, Result, RuntimeError,
+    RuntimeInfo, RuntimeType,
 };
+use super::registry_auth::{self, RegistryCredential};
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+/// Seconds to wait on each candidate socket while looking for a daemon
+const CONNECT_TIMEOUT: u64 = 2;
+
+/// Daemon sockets to try after `DOCKER_HOST` and the platform default, in
+/// order: rootless Podman, rootful Podman, Docker Desktop's per-user
+/// socket and Podman machine
+fn fallback_sockets() -> Vec<String> {
+    let mut sockets = Vec::new();
+
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+            sockets.push(std::path::Path::new(&runtime_dir).join("podman/podman.sock").display().to_string());
+        }
+        let uid = current_uid();
+        sockets.push(format!("/run/user/{}/podman/podman.sock", uid));
+        sockets.push("/run/podman/podman.sock".to_string());
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        if let Some(home) = dirs::home_dir() {
+            sockets.push(home.join(".docker/run/docker.sock").display().to_string());
+            sockets.push(home.join(".local/share/containers/podman/machine/podman.sock").display().to_string());
+            sockets.push(home.join(".local/share/containers/podman/machine/qemu/podman.sock").display().to_string());
+        }
+        if let Some(tmp) = std::env::var_os("TMPDIR") {
+            sockets.push(std::path::Path::new(&tmp).join("podman/podman-machine-default-api.sock").display().to_string());
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        sockets.push(r"\\.\pipe\podman-machine-default".to_string());
+    }
+
+    sockets
+}
+
+#[cfg(target_os = "linux")]
+fn current_uid() -> u32 {
+    // Owner of our own /proc entry
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata("/proc/self").map(|m| m.uid()).unwrap_or(0)
+}
+
+async fn responds(docker: Docker) -> Option<Docker> {
+    let ping = tokio::time::timeout(std::time::Duration::from_secs(CONNECT_TIMEOUT), docker.ping());
+    matches!(ping.await, Ok(Ok(_))).then_some(docker)
+}
+
+/// Connect to the first Docker-compatible daemon that answers: the one
+/// `DOCKER_HOST` names, the platform default, then the Podman and Docker
+/// Desktop sockets from `fallback_sockets`
+pub(crate) async fn connect_local() -> Option<Docker> {
+    if std::env::var_os("DOCKER_HOST").is_some() {
+        match Docker::connect_with_defaults() {
+            Ok(docker) => {
+                if let Some(docker) = responds(docker).await {
+                    return Some(docker);
+                }
+                log::warn!("DOCKER_HOST is set but the daemon there does not respond");
+            }
+            Err(e) => log::warn!("Invalid DOCKER_HOST: {}", e),
+        }
+    }
+
+    if let Ok(docker) = Docker::connect_with_local_defaults() {
+        if let Some(docker) = responds(docker).await {
+            return Some(docker);
+        }
+    }
+
+    for socket in fallback_sockets() {
+        #[cfg(unix)]
+        if !std::path::Path::new(&socket).exists() {
+            continue;
+        }
+        #[cfg(unix)]
+        let docker = Docker::connect_with_unix(&socket, 120, bollard::API_DEFAULT_VERSION);
+        #[cfg(windows)]
+        let docker = Docker::connect_with_named_pipe(&socket, 120, bollard::API_DEFAULT_VERSION);
+
+        if let Ok(docker) = docker {
+            if let Some(docker) = responds(docker).await {
+                log::info!("Connected to container daemon at {}", socket);
+                return Some(docker);
+            }
+        }
+    }
+
+    None
+}
+
+/// Docker health check settings for `check`; durations are nanoseconds
+pub(crate) fn health_config(check: &HealthCheck) -> HealthConfig {
+    let nanos = |secs: Option<u64>| secs.map(|s| s as i64 * NANOS_PER_SEC);
+    HealthConfig {
+        test: Some(check.test()),
+        interval: nanos(check.interval_secs),
+        timeout: nanos(check.timeout_secs),
+        retries: check.retries.map(i64::from),
+        start_period: nanos(check.start_period_secs),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn restart_policy(policy: &RestartPolicy) -> bollard::models::RestartPolicy {
+    let (name, maximum_retry_count) = match policy {
+        RestartPolicy::No => (RestartPolicyNameEnum::NO, None),
+        RestartPolicy::OnFailure { max_retries } => {
+            (RestartPolicyNameEnum::ON_FAILURE, max_retries.map(i64::from))
+        }
+        RestartPolicy::Always => (RestartPolicyNameEnum::ALWAYS, None),
+        RestartPolicy::UnlessStopped => (RestartPolicyNameEnum::UNLESS_STOPPED, None),
+    };
+    bollard::models::RestartPolicy {
+        name: Some(name),
+        maximum_retry_count,
+    }
+}
+
+/// Registry login in the form the Docker API takes
+pub(crate) fn docker_credentials(credential: RegistryCredential) -> DockerCredentials {
+    let serveraddress = if credential.registry == registry_auth::DOCKER_HUB {
+        "https://index.docker.io/v1/".to_string()
+    } else {
+        credential.registry
+    };
+    DockerCredentials {
+        username: credential.username,
+        password: credential.password,
+        identitytoken: credential.token,
+        serveraddress: Some(serveraddress),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn health_from_inspect(status: Option<HealthStatusEnum>) -> Option<HealthStatus> {
+    match status? {
+        HealthStatusEnum::STARTING => Some(HealthStatus::Starting),
+        HealthStatusEnum::HEALTHY => Some(HealthStatus::Healthy),
+        HealthStatusEnum::UNHEALTHY => Some(HealthStatus::Unhealthy),
+        _ => None,
+    }
+}
+
+pub(crate) fn network_info(network: bollard::models::Network) -> NetworkInfo {
+    NetworkInfo {
+        id: network.id.unwrap_or_default(),
+        name: network.name.unwrap_or_default(),
+        driver: network.driver.unwrap_or_default(),
+        internal: network.internal.unwrap_or(false),
+        labels: network.labels.unwrap_or_default(),
+        containers: network.containers.unwrap_or_default().into_keys().collect(),
+    }
+}
+
+/// Health from a container list status such as `Up 5 minutes (healthy)`
+pub(crate) fn health_from_status(status: &str) -> Option<HealthStatus> {
+    if status.contains("(health: starting)") {
+        Some(HealthStatus::Starting)
+    } else if status.contains("(unhealthy)") {
+        Some(HealthStatus::Unhealthy)
+    } else if status.contains("(healthy)") {
+        Some(HealthStatus::Healthy)
+    } else {
+        None
+    }
+}
 
 /// Docker/Podman runtime implementation
 pub struct DockerRuntime {
@@ -31,7 +220,7 @@ pub struct DockerRuntime {
 impl DockerRuntime {
     /// Create a new Docker runtime
     pub async fn new() -> Option<Self> {
-        let docker = Docker::connect_with_local_defaults().ok()?;
+        let docker = connect_local().await?;
 
         // Detect if it's Docker or Podman
         let runtime_type = match docker.version().await {
@@ -56,15 +245,25 @@ impl DockerRuntime {
         let mut bindings = HashMap::new();
         for port in ports {
             let key = format!("{}/{}", port.container_port, port.protocol);
-            let binding = PortBinding {
-                host_ip: port.host_ip.clone(),
-                host_port: Some(port.host_port.to_string()),
-            };
-            bindings.insert(key, Some(vec![binding]));
+            // Exposed-only ports get no binding
+            let binding = port.host_port.map(|host_port| {
+                vec![PortBinding {
+                    host_ip: port.host_ip.clone(),
+                    host_port: Some(host_port.to_string()),
+                }]
+            });
+            bindings.insert(key, binding);
         }
         bindings
     }
 
+    fn convert_exposed_ports(ports: &[PortMapping]) -> HashMap<String, HashMap<(), ()>> {
+        ports
+            .iter()
+            .map(|p| (format!("{}/{}", p.container_port, p.protocol), HashMap::new()))
+            .collect()
+    }
+
     fn convert_mounts(mounts: &[Mount]) -> Vec<String> {
         mounts
             .iter()
@@ -79,6 +278,34 @@ impl DockerRuntime {
         env.iter().map(|(k, v)| format!("{}={}", k, v)).collect()
     }
 
+    /// GPU requests need the NVIDIA Container Toolkit, which registers the
+    /// `nvidia` runtime with the daemon and installs `nvidia-container-cli`.
+    /// Docker Desktop on Windows passes GPUs through WSL 2 without it.
+    async fn check_gpu_support(&self) -> Result<()> {
+        if cfg!(target_os = "windows") {
+            return Ok(());
+        }
+
+        let info = self.docker.info().await
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+        let has_runtime = info.runtimes
+            .map(|runtimes| runtimes.contains_key("nvidia"))
+            .unwrap_or(false);
+        let has_cli = std::env::var_os("PATH")
+            .map(|path| std::env::split_paths(&path).any(|dir| dir.join("nvidia-container-cli").is_file()))
+            .unwrap_or(false);
+
+        if has_runtime || has_cli {
+            Ok(())
+        } else {
+            Err(RuntimeError::OperationFailed(
+                "GPU requested but the NVIDIA Container Toolkit is not installed. Install it and restart Docker: \
+                 https://docs.nvidia.com/datacenter/cloud-native/container-toolkit/latest/install-guide.html"
+                    .to_string(),
+            ))
+        }
+    }
+
     fn parse_state(state: &str) -> ContainerState {
         match state.to_lowercase().as_str() {
             "creating" => ContainerState::Creating,
@@ -119,6 +346,7 @@ impl ContainerRuntime for DockerRuntime {
         let mut host_config = HostConfig::default();
 
         // Port bindings
+        let exposed_ports = spec.ports.as_deref().map(Self::convert_exposed_ports);
         if let Some(ports) = &spec.ports {
             host_config.port_bindings = Some(Self::convert_port_bindings(ports));
         }
@@ -145,6 +373,36 @@ impl ContainerRuntime for DockerRuntime {
         if let Some(network_mode) = &spec.network_mode {
             host_config.network_mode = Some(network_mode.clone());
         }
+        let networking_config = match (&spec.network_mode, &spec.network_aliases) {
+            (Some(network), Some(aliases)) if network != "none" => Some(NetworkingConfig {
+                endpoints_config: HashMap::from([(
+                    network.clone(),
+                    EndpointSettings {
+                        aliases: Some(aliases.clone()),
+                        ..Default::default()
+                    },
+                )]),
+            }),
+            _ => None,
+        };
+
+        // GPUs
+        if spec.gpu == Some(true) || spec.gpu_devices.is_some() {
+            self.check_gpu_support().await?;
+            host_config.device_requests = Some(vec![DeviceRequest {
+                driver: Some("nvidia".to_string()),
+                // -1 asks for every GPU
+                count: if spec.gpu_devices.is_some() { None } else { Some(-1) },
+                device_ids: spec.gpu_devices.clone(),
+                capabilities: Some(vec![vec!["gpu".to_string()]]),
+                ..Default::default()
+            }]);
+        }
+
+        // Restart policy
+        if let Some(policy) = &spec.restart_policy {
+            host_config.restart_policy = Some(restart_policy(policy));
+        }
 
         // Privileged
         if let Some(privileged) = spec.privileged {
@@ -174,7 +432,11 @@ impl ContainerRuntime for DockerRuntime {
             user: spec.user.clone(),
             hostname: spec.hostname.clone(),
             labels: Some(labels),
+            network_disabled: (spec.network_mode.as_deref() == Some("none")).then_some(true),
+            exposed_ports,
+            healthcheck: spec.healthcheck.as_ref().map(health_config),
             host_config: Some(host_config),
+            networking_config,
             ..Default::default()
         };
 
@@ -244,6 +506,41 @@ impl ContainerRuntime for DockerRuntime {
             .map_err(|e| RuntimeError::OperationFailed(e.to_string()))
     }
 
+    async fn restart_container(&self, id: &str, timeout: Option<u32>) -> Result<()> {
+        let options = RestartContainerOptions {
+            t: timeout.unwrap_or(10) as isize,
+        };
+        self.docker
+            .restart_container(id, Some(options))
+            .await
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))
+    }
+
+    async fn rename_container(&self, id: &str, name: &str) -> Result<()> {
+        let options = RenameContainerOptions { name };
+        self.docker
+            .rename_container(id, options)
+            .await
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))
+    }
+
+    async fn update_resources(&self, id: &str, resources: &ResourceLimits) -> Result<()> {
+        let options = UpdateContainerOptions::<String> {
+            memory: resources.memory,
+            memory_swap: resources.memory_swap,
+            cpu_shares: resources.cpu_shares.map(|s| s as isize),
+            cpu_quota: resources.cpu_quota,
+            cpu_period: resources.cpu_period,
+            nano_cpus: resources.cpus.map(|cpus| (cpus * NANOS_PER_SEC as f64) as i64),
+            pids_limit: resources.pids_limit,
+            ..Default::default()
+        };
+        self.docker
+            .update_container(id, options)
+            .await
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))
+    }
+
     async fn inspect_container(&self, id: &str) -> Result<ContainerInfo> {
         let inspect = self.docker
             .inspect_container(id, None::<InspectContainerOptions>)
@@ -268,31 +565,42 @@ impl ContainerRuntime for DockerRuntime {
                         let parts: Vec<&str> = port_str.split('/').collect();
                         let container_port = parts.first()?.parse().ok()?;
                         let protocol = parts.get(1).unwrap_or(&"tcp").to_string();
-                        let binding = bindings.as_ref()?.first()?;
+                        let binding = bindings.as_ref().and_then(|b| b.first());
                         Some(PortMapping {
                             container_port,
-                            host_port: binding.host_port.as_ref()?.parse().ok()?,
+                            host_port: binding
+                                .and_then(|b| b.host_port.as_ref())
+                                .and_then(|p| p.parse().ok()),
                             protocol,
-                            host_ip: binding.host_ip.clone(),
+                            host_ip: binding.and_then(|b| b.host_ip.clone()),
                         })
                     })
                     .collect()
             })
             .unwrap_or_default();
 
+        let timestamp = |t: Option<&String>| {
+            t.and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                // Docker reports never as 0001-01-01
+                .map(|t| t.timestamp())
+                .filter(|&t| t > 0)
+        };
+        let config = inspect.config.unwrap_or_default();
+
         Ok(ContainerInfo {
             id: inspect.id.unwrap_or_default(),
             name: inspect.name.unwrap_or_default().trim_start_matches('/').to_string(),
-            image: inspect.config.and_then(|c| c.image).unwrap_or_default(),
+            image: config.image.unwrap_or_default(),
             state: container_state,
-            created: 0, // Would need to parse timestamp
-            started: None,
-            finished: None,
+            created: timestamp(inspect.created.as_ref()).unwrap_or(0),
+            started: timestamp(state.and_then(|s| s.started_at.as_ref())),
+            finished: timestamp(state.and_then(|s| s.finished_at.as_ref())),
             exit_code: state.and_then(|s| s.exit_code).map(|c| c as i32),
             pid: state.and_then(|s| s.pid).map(|p| p as u32),
             ports,
             mounts: vec![],
-            labels: HashMap::new(),
+            labels: config.labels.unwrap_or_default(),
+            health: health_from_inspect(state.and_then(|s| s.health.as_ref()).and_then(|h| h.status)),
         })
     }
 
@@ -312,13 +620,11 @@ impl ContainerRuntime for DockerRuntime {
             .map(|c| {
                 let ports = c.ports.unwrap_or_default()
                     .into_iter()
-                    .filter_map(|p| {
-                        Some(PortMapping {
-                            container_port: p.private_port as u16,
-                            host_port: p.public_port.map(|hp| hp as u16)?,
-                            protocol: p.typ.map(|t| format!("{:?}", t).to_lowercase()).unwrap_or_else(|| "tcp".to_string()),
-                            host_ip: p.ip,
-                        })
+                    .map(|p| PortMapping {
+                        container_port: p.private_port as u16,
+                        host_port: p.public_port.map(|hp| hp as u16),
+                        protocol: p.typ.map(|t| format!("{:?}", t).to_lowercase()).unwrap_or_else(|| "tcp".to_string()),
+                        host_ip: p.ip,
                     })
                     .collect();
 
@@ -340,13 +646,15 @@ impl ContainerRuntime for DockerRuntime {
                     ports,
                     mounts: vec![],
                     labels: c.labels.unwrap_or_default(),
+                    health: c.status.as_deref().and_then(health_from_status),
                 }
             })
             .collect())
     }
 
-    async fn logs(&self, id: &str, tail: Option<usize>, _follow: bool) -> Result<String> {
+    async fn logs(&self, id: &str, tail: Option<usize>, follow: bool) -> Result<String> {
         let options = LogsOptions::<String> {
+            follow,
             stdout: true,
             stderr: true,
             tail: tail.map(|t| t.to_string()).unwrap_or_else(|| "100".to_string()),
@@ -366,6 +674,88 @@ impl ContainerRuntime for DockerRuntime {
         Ok(output)
     }
 
+    async fn follow_logs(&self, id: &str, tail: Option<usize>, tx: mpsc::Sender<LogLine>) -> Result<()> {
+        let options = LogsOptions::<String> {
+            follow: true,
+            stdout: true,
+            stderr: true,
+            timestamps: true,
+            tail: tail.map(|t| t.to_string()).unwrap_or_else(|| "100".to_string()),
+            ..Default::default()
+        };
+
+        let mut stream = self.docker.logs(id, Some(options));
+        while let Some(result) = stream.next().await {
+            let (stream, message) = match result {
+                Ok(LogOutput::StdErr { message }) => (LogStream::Stderr, message),
+                Ok(LogOutput::StdOut { message }) | Ok(LogOutput::Console { message }) => {
+                    (LogStream::Stdout, message)
+                }
+                Ok(_) => continue,
+                Err(e) => {
+                    return Err(RuntimeError::OperationFailed(format!("Log stream failed: {}", e)));
+                }
+            };
+
+            // With timestamps on, each line starts with `<RFC 3339> `
+            let text = String::from_utf8_lossy(&message);
+            for line in text.lines() {
+                let (timestamp, message) = match line.split_once(' ') {
+                    Some((ts, rest)) if chrono::DateTime::parse_from_rfc3339(ts).is_ok() => {
+                        (Some(ts.to_string()), rest.to_string())
+                    }
+                    _ => (None, line.to_string()),
+                };
+                if tx.send(LogLine { stream, timestamp, message }).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn stats(&self, id: &str) -> Result<ContainerStats> {
+        let options = StatsOptions {
+            stream: false,
+            one_shot: false,
+        };
+        let stats = self.docker
+            .stats(id, Some(options))
+            .next()
+            .await
+            .ok_or_else(|| RuntimeError::ContainerNotFound(id.to_string()))?
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+
+        // Same formula as `docker stats`: share of the host's CPU time since
+        // the previous sample, times the number of CPUs
+        let cpu_delta = stats.cpu_stats.cpu_usage.total_usage
+            .saturating_sub(stats.precpu_stats.cpu_usage.total_usage);
+        let system_delta = stats.cpu_stats.system_cpu_usage.unwrap_or(0)
+            .saturating_sub(stats.precpu_stats.system_cpu_usage.unwrap_or(0));
+        let cpus = stats.cpu_stats.online_cpus.unwrap_or(1);
+        let cpu_percent = if system_delta > 0 {
+            cpu_delta as f64 / system_delta as f64 * cpus as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        let io = stats.blkio_stats.io_service_bytes_recursive.unwrap_or_default();
+        let io_bytes = |op: &str| -> u64 {
+            io.iter().filter(|e| e.op.eq_ignore_ascii_case(op)).map(|e| e.value).sum()
+        };
+
+        Ok(ContainerStats {
+            cpu_usage_usec: stats.cpu_stats.cpu_usage.total_usage / 1000,
+            cpu_percent,
+            memory_usage: stats.memory_stats.usage.unwrap_or(0),
+            memory_limit: stats.memory_stats.limit,
+            io_read_bytes: io_bytes("read"),
+            io_write_bytes: io_bytes("write"),
+            pids: stats.pids_stats.current.unwrap_or(0),
+        })
+    }
+
     async fn exec(&self, id: &str, cmd: &[String], tty: bool) -> Result<ExecOutput> {
         let exec_options = CreateExecOptions {
             attach_stdout: Some(true),
@@ -391,10 +781,10 @@ impl ContainerRuntime for DockerRuntime {
             while let Some(result) = output.next().await {
                 match result {
                     Ok(log) => match log {
-                        bollard::container::LogOutput::StdOut { message } => {
+                        LogOutput::StdOut { message } => {
                             stdout.push_str(&String::from_utf8_lossy(&message));
                         }
-                        bollard::container::LogOutput::StdErr { message } => {
+                        LogOutput::StdErr { message } => {
                             stderr.push_str(&String::from_utf8_lossy(&message));
                         }
                         _ => {}
@@ -440,7 +830,8 @@ impl ContainerRuntime for DockerRuntime {
             ..Default::default()
         };
 
-        let mut stream = self.docker.create_image(Some(options), None, None);
+        let credentials = registry_auth::credentials_for(reference).map(docker_credentials);
+        let mut stream = self.docker.create_image(Some(options), None, credentials);
 
         while let Some(result) = stream.next().await {
             match result {
@@ -496,4 +887,234 @@ impl ContainerRuntime for DockerRuntime {
             Err(e) => Err(RuntimeError::OperationFailed(e.to_string())),
         }
     }
+
+    async fn push_image(&self, reference: &str) -> Result<()> {
+        // A colon after the last slash separates the tag
+        let (name, tag) = match reference.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => (name, tag),
+            _ => (reference, "latest"),
+        };
+        let options = PushImageOptions { tag };
+        let credentials = registry_auth::credentials_for(reference).map(docker_credentials);
+
+        let mut stream = self.docker.push_image(name, Some(options), credentials);
+        while let Some(result) = stream.next().await {
+            let info = result
+                .map_err(|e| RuntimeError::OperationFailed(format!("Push failed: {}", e)))?;
+            if let Some(error) = info.error {
+                return Err(RuntimeError::OperationFailed(format!("Push failed: {}", error)));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn build_image(&self, request: &BuildImageRequest, progress: Option<mpsc::Sender<String>>) -> Result<String> {
+        if request.tag.trim().is_empty() {
+            return Err(RuntimeError::Config("An image tag is required".to_string()));
+        }
+
+        let tarball = match (&request.context_path, &request.tarball_path) {
+            (Some(dir), None) => {
+                let dir = std::path::PathBuf::from(dir);
+                tokio::task::spawn_blocking(move || -> std::io::Result<Vec<u8>> {
+                    let mut builder = tar::Builder::new(Vec::new());
+                    builder.follow_symlinks(false);
+                    builder.append_dir_all(".", &dir)?;
+                    builder.into_inner()
+                })
+                .await
+                .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?
+                .map_err(|e| RuntimeError::OperationFailed(format!("Failed to pack build context: {}", e)))?
+            }
+            (None, Some(path)) => tokio::fs::read(path).await
+                .map_err(|e| RuntimeError::OperationFailed(format!("Failed to read build context: {}", e)))?,
+            _ => {
+                return Err(RuntimeError::Config(
+                    "Set exactly one of context_path or tarball_path".to_string(),
+                ));
+            }
+        };
+
+        let options = BuildImageOptions {
+            dockerfile: request.dockerfile.clone().unwrap_or_else(|| "Dockerfile".to_string()),
+            t: request.tag.clone(),
+            buildargs: request.build_args.clone(),
+            labels: HashMap::from([("managed_by".to_string(), "otherthing-node".to_string())]),
+            rm: true,
+            ..Default::default()
+        };
+
+        // Base images may come from any saved registry
+        let credentials = registry_auth::all()
+            .into_iter()
+            .map(|c| {
+                let credentials = docker_credentials(c);
+                (credentials.serveraddress.clone().unwrap_or_default(), credentials)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut stream = self.docker.build_image(options, Some(credentials), Some(tarball.into()));
+        let mut image_id = None;
+
+        while let Some(result) = stream.next().await {
+            let info = result
+                .map_err(|e| RuntimeError::OperationFailed(format!("Build failed: {}", e)))?;
+            if let Some(error) = info.error {
+                return Err(RuntimeError::OperationFailed(format!("Build failed: {}", error)));
+            }
+            if let Some(id) = info.aux.and_then(|aux| aux.id) {
+                image_id = Some(id);
+            }
+            if let (Some(tx), Some(output)) = (&progress, info.stream) {
+                for line in output.lines().filter(|l| !l.trim().is_empty()) {
+                    let _ = tx.send(line.to_string()).await;
+                }
+            }
+        }
+
+        Ok(image_id.unwrap_or_else(|| request.tag.clone()))
+    }
+
+    async fn disk_usage(&self) -> Result<DiskUsage> {
+        let df = self.docker.df().await
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+        let mut usage = DiskUsage::default();
+
+        for image in df.images.unwrap_or_default() {
+            // Shared layers are counted once, under layers_size
+            let size = image.size - image.shared_size.max(0);
+            usage.images.count += 1;
+            usage.images.size += size;
+            if image.containers == 0 {
+                usage.images.reclaimable += size;
+            }
+        }
+        if let Some(layers_size) = df.layers_size {
+            usage.images.size = layers_size;
+        }
+
+        for container in df.containers.unwrap_or_default() {
+            let size = container.size_rw.unwrap_or(0);
+            usage.containers.count += 1;
+            usage.containers.size += size;
+            if container.state.as_deref() != Some("running") {
+                usage.containers.reclaimable += size;
+            }
+        }
+
+        for volume in df.volumes.unwrap_or_default() {
+            let (size, refs) = volume.usage_data
+                .map(|u| (u.size.max(0), u.ref_count))
+                .unwrap_or((0, 0));
+            usage.volumes.count += 1;
+            usage.volumes.size += size;
+            if refs == 0 {
+                usage.volumes.reclaimable += size;
+            }
+        }
+
+        for cache in df.build_cache.unwrap_or_default() {
+            let size = cache.size.unwrap_or(0);
+            usage.build_cache.count += 1;
+            usage.build_cache.size += size;
+            if !cache.in_use.unwrap_or(false) {
+                usage.build_cache.reclaimable += size;
+            }
+        }
+
+        Ok(usage)
+    }
+
+    async fn prune(&self, request: &PruneRequest) -> Result<PruneResult> {
+        let mut result = PruneResult::default();
+
+        // Containers first so the images and volumes they held become unused
+        if request.containers {
+            let options = PruneContainersOptions {
+                filters: HashMap::from([("label", vec!["managed_by=otherthing-node"])]),
+            };
+            let response = self.docker.prune_containers(Some(options)).await
+                .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+            result.containers_deleted = response.containers_deleted.unwrap_or_default();
+            result.space_reclaimed += response.space_reclaimed.unwrap_or(0);
+        }
+
+        if request.images {
+            let options = PruneImagesOptions {
+                filters: HashMap::from([("dangling", vec!["true"])]),
+            };
+            let response = self.docker.prune_images(Some(options)).await
+                .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+            result.images_deleted = response.images_deleted
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|item| item.deleted.or(item.untagged))
+                .collect();
+            result.space_reclaimed += response.space_reclaimed.unwrap_or(0);
+        }
+
+        if request.volumes {
+            let response = self.docker.prune_volumes(None::<PruneVolumesOptions<String>>).await
+                .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+            result.volumes_deleted = response.volumes_deleted.unwrap_or_default();
+            result.space_reclaimed += response.space_reclaimed.unwrap_or(0);
+        }
+
+        Ok(result)
+    }
+
+    async fn create_network(&self, name: &str, internal: bool, labels: &HashMap<String, String>) -> Result<String> {
+        let mut labels = labels.clone();
+        labels.insert("managed_by".to_string(), "otherthing-node".to_string());
+
+        let options = CreateNetworkOptions {
+            name: name.to_string(),
+            driver: "bridge".to_string(),
+            internal,
+            labels,
+            ..Default::default()
+        };
+        let response = self.docker
+            .create_network(options)
+            .await
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+
+        Ok(response.id)
+    }
+
+    async fn list_networks(&self) -> Result<Vec<NetworkInfo>> {
+        let networks = self.docker
+            .list_networks(None::<ListNetworksOptions<String>>)
+            .await
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+
+        Ok(networks.into_iter().map(network_info).collect())
+    }
+
+    async fn remove_network(&self, name: &str) -> Result<()> {
+        self.docker
+            .remove_network(name)
+            .await
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))
+    }
+
+    async fn connect_network(&self, network: &str, container: &str) -> Result<()> {
+        let options = ConnectNetworkOptions {
+            container,
+            endpoint_config: Default::default(),
+        };
+        self.docker
+            .connect_network(network, options)
+            .await
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))
+    }
+
+    async fn disconnect_network(&self, network: &str, container: &str, force: bool) -> Result<()> {
+        let options = DisconnectNetworkOptions { container, force };
+        self.docker
+            .disconnect_network(network, options)
+            .await
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))
+    }
 }
@@ -0,0 +1,154 @@
+//! Helpers for installing managed binaries: download, checksum
+//! verification and archive extraction.
+
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::Duration;
+
+/// Download a file into memory
+pub async fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(600))
+        .user_agent("otherthing-node")
+        .build()
+        .map_err(|e| format!("Failed to create client: {}", e))?;
+
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Download failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Download of {} failed with status: {}", url, response.status()));
+    }
+
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+
+    log::info!("Downloaded {} bytes from {}", bytes.len(), url);
+    Ok(bytes.to_vec())
+}
+
+/// Download a file into memory, reporting `(received, total)` as chunks
+/// arrive
+pub async fn fetch_with_progress(
+    url: &str,
+    mut on_progress: impl FnMut(u64, Option<u64>),
+) -> Result<Vec<u8>, String> {
+    use futures_util::StreamExt;
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(600))
+        .user_agent("otherthing-node")
+        .build()
+        .map_err(|e| format!("Failed to create client: {}", e))?;
+
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Download failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Download of {} failed with status: {}", url, response.status()));
+    }
+
+    let total = response.content_length();
+    let mut data = Vec::with_capacity(total.unwrap_or(0) as usize);
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("Failed to read response: {}", e))?;
+        data.extend_from_slice(&chunk);
+        on_progress(data.len() as u64, total);
+    }
+
+    log::info!("Downloaded {} bytes from {}", data.len(), url);
+    Ok(data)
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Find the checksum of `filename` in a `sha256sum`-style listing
+/// (`<hex>  <name>` per line, names optionally prefixed with `./` or `*`)
+pub fn checksum_for(listing: &str, filename: &str) -> Option<String> {
+    listing.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let hash = parts.next()?;
+        let name = parts.next()?.trim_start_matches('*').trim_start_matches("./");
+        (name == filename).then(|| hash.to_lowercase())
+    })
+}
+
+pub fn verify_sha256(data: &[u8], expected: &str) -> Result<(), String> {
+    let actual = sha256_hex(data);
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        Ok(())
+    } else {
+        Err(format!("Checksum mismatch: expected {}, got {}", expected.trim(), actual))
+    }
+}
+
+/// Unpack a `.zip`, `.tar.gz` or `.tgz` archive into `dest`
+pub fn extract_archive(archive_path: &Path, dest: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dest).map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let name = archive_path.to_string_lossy();
+    let file = std::fs::File::open(archive_path)
+        .map_err(|e| format!("Failed to open archive: {}", e))?;
+
+    if name.ends_with(".zip") {
+        let mut archive = zip::ZipArchive::new(file)
+            .map_err(|e| format!("Failed to read zip: {}", e))?;
+
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)
+                .map_err(|e| format!("Failed to read zip entry: {}", e))?;
+
+            let outpath = match entry.enclosed_name() {
+                Some(path) => dest.join(path),
+                None => continue,
+            };
+
+            if entry.name().ends_with('/') {
+                std::fs::create_dir_all(&outpath).ok();
+            } else {
+                if let Some(p) = outpath.parent() {
+                    std::fs::create_dir_all(p).ok();
+                }
+                let mut outfile = std::fs::File::create(&outpath)
+                    .map_err(|e| format!("Failed to create file: {}", e))?;
+                std::io::copy(&mut entry, &mut outfile)
+                    .map_err(|e| format!("Failed to extract file: {}", e))?;
+            }
+        }
+        Ok(())
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        let tar = flate2::read::GzDecoder::new(file);
+        tar::Archive::new(tar)
+            .unpack(dest)
+            .map_err(|e| format!("Failed to extract archive: {}", e))
+    } else {
+        Err(format!("Unsupported archive format: {}", name))
+    }
+}
+
+/// Mark a file executable on Unix
+pub fn make_executable(path: &Path) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Failed to set permissions: {}", e))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
@@ -0,0 +1,532 @@
+//! Earnings Ledger
+//!
+//! Append-only SQLite record of what the node was paid for each job: the
+//! amount in minor units (cents) and its currency, when the job ran, and
+//! the orchestrator's payment reference. Rows cannot be updated or deleted;
+//! a job is recorded at most once. Totals for `/api/v1/stats`, the
+//! `earnings` CLI and the dashboard are aggregated from it, and per-job
+//! exports for bookkeeping are drawn from it.
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::payments::JobPaymentStatus;
+use crate::error::NodeError;
+use crate::pagination::{ListQuery, Page};
+
+/// Fields entries can be sorted by, and their columns
+const EARNINGS_SORT: &[(&str, &str)] = &[
+    ("completedAt", "completed_at"),
+    ("startedAt", "started_at"),
+    ("recordedAt", "recorded_at"),
+    ("amountCents", "amount_cents"),
+    ("currency", "currency"),
+    ("jobKind", "job_kind"),
+];
+/// Fields entries can be filtered on, and their columns
+const EARNINGS_FILTER: &[(&str, &str)] = &[
+    ("jobId", "job_id"),
+    ("jobKind", "job_kind"),
+    ("currency", "currency"),
+    ("paymentRef", "payment_ref"),
+    ("resources", "resources"),
+];
+
+/// A recorded job payment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EarningEntry {
+    pub id: i64,
+    pub job_id: String,
+    /// What ran, e.g. `agent`, `inference` or `container`
+    pub job_kind: String,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub started_at: String,
+    pub completed_at: String,
+    pub recorded_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment_ref: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<String>,
+}
+
+/// A job payment to record
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewEarning {
+    pub job_id: String,
+    #[serde(default = "default_job_kind")]
+    pub job_kind: String,
+    pub amount_cents: i64,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    pub started_at: String,
+    pub completed_at: String,
+    #[serde(default)]
+    pub payment_ref: Option<String>,
+    /// What the job used, e.g. `4 vCPU, 8 GB RAM, 1x RTX 4090`
+    #[serde(default)]
+    pub resources: Option<String>,
+}
+
+fn default_job_kind() -> String {
+    "job".to_string()
+}
+
+fn default_currency() -> String {
+    "USD".to_string()
+}
+
+/// Filters for listing entries
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EarningsQuery {
+    /// Only jobs completed at or after this RFC 3339 timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// Only jobs completed before this RFC 3339 timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+}
+
+/// Earnings in one currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrencyTotal {
+    pub currency: String,
+    pub amount_cents: i64,
+    pub jobs: u64,
+}
+
+/// Earnings of one UTC day in one currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyEarnings {
+    /// `YYYY-MM-DD`
+    pub day: String,
+    pub currency: String,
+    pub amount_cents: i64,
+    pub jobs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EarningsSummary {
+    pub total: Vec<CurrencyTotal>,
+    pub last_24h: Vec<CurrencyTotal>,
+    pub last_7d: Vec<CurrencyTotal>,
+    pub last_30d: Vec<CurrencyTotal>,
+    /// The last 30 days that had earnings, oldest first
+    pub daily: Vec<DailyEarnings>,
+}
+
+/// Normalize a timestamp so stored values compare as text
+fn normalize_time(value: &str, field: &str) -> Result<String, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Millis, true))
+        .map_err(|_| format!("{} must be an RFC 3339 timestamp", field))
+}
+
+/// Normalize an export bound given as a timestamp or a `YYYY-MM-DD` date.
+/// A date as the upper bound includes that whole day.
+fn normalize_bound(value: &str, field: &str, upper: bool) -> Result<String, String> {
+    match chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => {
+            let date = if upper { date.succ_opt().unwrap_or(date) } else { date };
+            Ok(date
+                .and_hms_opt(0, 0, 0)
+                .unwrap_or_default()
+                .and_utc()
+                .to_rfc3339_opts(SecondsFormat::Millis, true))
+        }
+        Err(_) => normalize_time(value, field),
+    }
+}
+
+fn ago(duration: Duration) -> String {
+    (Utc::now() - duration).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            other => Err(format!("Unknown export format: {} (expected csv or json)", other)),
+        }
+    }
+}
+
+/// One job in an export
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportRow {
+    pub job_id: String,
+    pub job_type: String,
+    pub started_at: String,
+    pub completed_at: String,
+    pub duration_secs: i64,
+    pub resources: String,
+    /// Major units, e.g. `12.50`
+    pub cost: String,
+    pub currency: String,
+    /// `paid`, `covered`, `pending` or `unpaid` once reconciled against
+    /// on-chain payments, `unverified` otherwise
+    pub payment_status: String,
+    pub payment_ref: String,
+}
+
+const CSV_HEADER: &str =
+    "job_id,job_type,started_at,completed_at,duration_secs,resources,cost,currency,payment_status,payment_ref";
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Rows as CSV, header first
+pub fn to_csv(rows: &[ExportRow]) -> String {
+    let mut out = String::from(CSV_HEADER);
+    out.push('\n');
+    for row in rows {
+        let fields = [
+            csv_field(&row.job_id),
+            csv_field(&row.job_type),
+            row.started_at.clone(),
+            row.completed_at.clone(),
+            row.duration_secs.to_string(),
+            csv_field(&row.resources),
+            row.cost.clone(),
+            csv_field(&row.currency),
+            row.payment_status.clone(),
+            csv_field(&row.payment_ref),
+        ];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+pub struct EarningsLedger {
+    conn: Mutex<Connection>,
+}
+
+impl EarningsLedger {
+    /// Open the ledger at its default location under the config dir
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&default_path())
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open earnings ledger: {}", e))?;
+        Self::init(conn)
+    }
+
+    /// Ledger that lives only as long as the process (used when the file can't be opened)
+    pub fn in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open earnings ledger: {}", e))?;
+        Self::init(conn)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS earnings (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                job_id TEXT NOT NULL UNIQUE,
+                job_kind TEXT NOT NULL,
+                amount_cents INTEGER NOT NULL,
+                currency TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                completed_at TEXT NOT NULL,
+                recorded_at TEXT NOT NULL,
+                payment_ref TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_earnings_completed
+                ON earnings (completed_at);
+            CREATE TRIGGER IF NOT EXISTS earnings_no_update
+                BEFORE UPDATE ON earnings
+                BEGIN SELECT RAISE(ABORT, 'earnings are append-only'); END;
+            CREATE TRIGGER IF NOT EXISTS earnings_no_delete
+                BEFORE DELETE ON earnings
+                BEGIN SELECT RAISE(ABORT, 'earnings are append-only'); END;",
+        )
+        .map_err(|e| format!("Failed to initialize earnings ledger: {}", e))?;
+
+        // Ledgers created before resources were recorded
+        let has_resources = conn.prepare("SELECT resources FROM earnings LIMIT 0").is_ok();
+        if !has_resources {
+            conn.execute("ALTER TABLE earnings ADD COLUMN resources TEXT", [])
+                .map_err(|e| format!("Failed to migrate earnings ledger: {}", e))?;
+        }
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Append a job payment
+    pub fn record(&self, entry: NewEarning) -> Result<EarningEntry, String> {
+        let job_id = entry.job_id.trim();
+        if job_id.is_empty() {
+            return Err("jobId is required".to_string());
+        }
+        if entry.amount_cents < 0 {
+            return Err("amountCents cannot be negative".to_string());
+        }
+        let currency = entry.currency.trim().to_uppercase();
+        if currency.is_empty() {
+            return Err("currency is required".to_string());
+        }
+        let started_at = normalize_time(&entry.started_at, "startedAt")?;
+        let completed_at = normalize_time(&entry.completed_at, "completedAt")?;
+        let recorded_at = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        let payment_ref = entry.payment_ref.filter(|r| !r.trim().is_empty());
+        let resources = entry.resources.filter(|r| !r.trim().is_empty());
+
+        let conn = self.conn.lock().unwrap();
+        let exists: Option<i64> = conn
+            .query_row("SELECT id FROM earnings WHERE job_id = ?1", params![job_id], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if exists.is_some() {
+            return Err(format!("Job {} is already recorded", job_id));
+        }
+
+        conn.execute(
+            "INSERT INTO earnings (job_id, job_kind, amount_cents, currency, started_at, completed_at, recorded_at, payment_ref, resources)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![job_id, entry.job_kind, entry.amount_cents, currency, started_at, completed_at, recorded_at, payment_ref, resources],
+        )
+        .map_err(|e| format!("Failed to record earnings for job {}: {}", job_id, e))?;
+
+        log::info!("Recorded {} {} for job {}", entry.amount_cents, currency, job_id);
+        Ok(EarningEntry {
+            id: conn.last_insert_rowid(),
+            job_id: job_id.to_string(),
+            job_kind: entry.job_kind,
+            amount_cents: entry.amount_cents,
+            currency,
+            started_at,
+            completed_at,
+            recorded_at,
+            payment_ref,
+            resources,
+        })
+    }
+
+    /// Every matching entry, newest first
+    pub fn list(&self, query: &EarningsQuery) -> Result<Vec<EarningEntry>, String> {
+        let (_, entries) = self.select(query, "ORDER BY completed_at DESC, id DESC", ("?6 IS NULL".to_string(), None), -1, 0)?;
+        Ok(entries)
+    }
+
+    /// A page of matching entries, newest first unless sorted otherwise
+    pub fn page(&self, query: &EarningsQuery, page: &ListQuery) -> Result<Page<EarningEntry>, NodeError> {
+        let order = page.order(EARNINGS_SORT, "ORDER BY completed_at DESC, id DESC")?;
+        let condition = page.condition(EARNINGS_FILTER, 6)?;
+        let (limit, offset) = (page.limit(), page.offset());
+        let (total, items) = self.select(query, &order, condition, limit as i64, offset as i64)?;
+        Ok(Page { items, total, offset, limit })
+    }
+
+    /// Entries matching `query` and the pagination `condition` on `?6`,
+    /// with how many matched in all
+    fn select(
+        &self,
+        query: &EarningsQuery,
+        order: &str,
+        (condition, filter): (String, Option<String>),
+        limit: i64,
+        offset: i64,
+    ) -> Result<(usize, Vec<EarningEntry>), NodeError> {
+        let since = query.since.as_deref().map(|t| normalize_time(t, "since")).transpose().map_err(NodeError::Invalid)?;
+        let until = query.until.as_deref().map(|t| normalize_time(t, "until")).transpose().map_err(NodeError::Invalid)?;
+        let currency = query.currency.as_ref().map(|c| c.to_uppercase());
+        let matching = format!(
+            "FROM earnings
+             WHERE (?1 IS NULL OR completed_at >= ?1)
+               AND (?2 IS NULL OR completed_at < ?2)
+               AND (?3 IS NULL OR currency = ?3)
+               AND {}",
+            condition
+        );
+
+        let conn = self.conn.lock().unwrap();
+        // Numbered parameters: the count binds `?4`/`?5` without using them
+        let total: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) {}", matching),
+                params![since, until, currency, limit, offset, filter],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT id, job_id, job_kind, amount_cents, currency, started_at, completed_at, recorded_at, payment_ref, resources
+                 {} {} LIMIT ?4 OFFSET ?5",
+                matching, order
+            ))
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map(params![since, until, currency, limit, offset, filter], |row| {
+                Ok(EarningEntry {
+                    id: row.get(0)?,
+                    job_id: row.get(1)?,
+                    job_kind: row.get(2)?,
+                    amount_cents: row.get(3)?,
+                    currency: row.get(4)?,
+                    started_at: row.get(5)?,
+                    completed_at: row.get(6)?,
+                    recorded_at: row.get(7)?,
+                    payment_ref: row.get(8)?,
+                    resources: row.get(9)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        let entries = rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+        Ok((total.max(0) as usize, entries))
+    }
+
+    /// Jobs completed between `from` and `to` (timestamps or dates), oldest
+    /// first, with the payment status of those that were reconciled
+    pub fn export(
+        &self,
+        from: Option<&str>,
+        to: Option<&str>,
+        statuses: &HashMap<String, JobPaymentStatus>,
+    ) -> Result<Vec<ExportRow>, String> {
+        let query = EarningsQuery {
+            since: from.map(|t| normalize_bound(t, "from", false)).transpose()?,
+            until: to.map(|t| normalize_bound(t, "to", true)).transpose()?,
+            ..Default::default()
+        };
+        let mut entries = self.list(&query)?;
+        entries.reverse();
+
+        Ok(entries
+            .into_iter()
+            .map(|entry| {
+                let duration_secs = match (
+                    DateTime::parse_from_rfc3339(&entry.started_at),
+                    DateTime::parse_from_rfc3339(&entry.completed_at),
+                ) {
+                    (Ok(start), Ok(end)) => (end - start).num_seconds().max(0),
+                    _ => 0,
+                };
+                ExportRow {
+                    payment_status: statuses
+                        .get(&entry.job_id)
+                        .map_or("unverified", |s| s.as_str())
+                        .to_string(),
+                    cost: format!("{}.{:02}", entry.amount_cents / 100, entry.amount_cents % 100),
+                    job_id: entry.job_id,
+                    job_type: entry.job_kind,
+                    started_at: entry.started_at,
+                    completed_at: entry.completed_at,
+                    duration_secs,
+                    resources: entry.resources.unwrap_or_default(),
+                    currency: entry.currency,
+                    payment_ref: entry.payment_ref.unwrap_or_default(),
+                }
+            })
+            .collect())
+    }
+
+    /// Totals per currency of jobs completed since `since`, or ever
+    pub fn totals(&self, since: Option<&str>) -> Result<Vec<CurrencyTotal>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT currency, SUM(amount_cents), COUNT(*) FROM earnings
+                 WHERE (?1 IS NULL OR completed_at >= ?1)
+                 GROUP BY currency ORDER BY currency",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![since], |row| {
+                Ok(CurrencyTotal {
+                    currency: row.get(0)?,
+                    amount_cents: row.get(1)?,
+                    jobs: row.get::<_, i64>(2)? as u64,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    /// Per-day totals of the last `days` days, oldest first
+    pub fn daily(&self, days: u32) -> Result<Vec<DailyEarnings>, String> {
+        let since = ago(Duration::days(i64::from(days)));
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT substr(completed_at, 1, 10) AS day, currency, SUM(amount_cents), COUNT(*)
+                 FROM earnings WHERE completed_at >= ?1
+                 GROUP BY day, currency ORDER BY day, currency",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![since], |row| {
+                Ok(DailyEarnings {
+                    day: row.get(0)?,
+                    currency: row.get(1)?,
+                    amount_cents: row.get(2)?,
+                    jobs: row.get::<_, i64>(3)? as u64,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    pub fn summary(&self) -> Result<EarningsSummary, String> {
+        Ok(EarningsSummary {
+            total: self.totals(None)?,
+            last_24h: self.totals(Some(&ago(Duration::hours(24))))?,
+            last_7d: self.totals(Some(&ago(Duration::days(7))))?,
+            last_30d: self.totals(Some(&ago(Duration::days(30))))?,
+            daily: self.daily(30)?,
+        })
+    }
+
+    /// Number of jobs recorded
+    pub fn job_count(&self) -> Result<u64, String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM earnings", [], |row| row.get::<_, i64>(0))
+            .map(|n| n as u64)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Location of the earnings database
+pub fn default_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+        .join("earnings.db")
+}
@@ -0,0 +1,226 @@
+//! Embedded IPFS Store (`embedded-ipfs` feature)
+//!
+//! In-process replacement for the Kubo daemon on platforms where shipping
+//! and supervising an external binary is impractical. Covers the surface
+//! `IpfsManager` exposes: add, cat, pin/unpin, peer id and stats.
+//!
+//! Content is stored as a single raw block addressed by a CIDv1
+//! (raw codec, sha2-256). For content up to 1 MiB this is the same CID Kubo
+//! produces with `--cid-version=1 --raw-leaves`; larger content is not
+//! chunked, so its CID differs from Kubo's. Blocks are not exchanged with
+//! other peers (no bitswap): content is only available from this node.
+
+use ed25519_dalek::SigningKey;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::models::{IpfsStats, PinInfo};
+
+/// CIDv1 header of a raw block with a sha2-256 multihash:
+/// version 1, raw codec (0x55), sha2-256 (0x12), 32-byte digest
+const RAW_SHA256_CID_PREFIX: [u8; 4] = [0x01, 0x55, 0x12, 0x20];
+
+/// Identity-multihash prefix of a libp2p ed25519 public key (protobuf
+/// `KeyType::Ed25519`, 32 data bytes) as used in peer IDs
+const ED25519_PEER_ID_PREFIX: [u8; 6] = [0x00, 0x24, 0x08, 0x01, 0x12, 0x20];
+
+pub struct EmbeddedIpfs {
+    root: PathBuf,
+    identity: SigningKey,
+    pins: Mutex<BTreeSet<String>>,
+}
+
+impl EmbeddedIpfs {
+    /// Open (or create) a store in `root`
+    pub fn open(root: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(root.join("blocks"))
+            .map_err(|e| format!("Failed to create embedded IPFS store: {}", e))?;
+
+        let key_path = root.join("identity.key");
+        let identity = match std::fs::read(&key_path) {
+            Ok(bytes) => {
+                let secret: [u8; 32] = bytes
+                    .try_into()
+                    .map_err(|_| "Invalid embedded IPFS identity".to_string())?;
+                SigningKey::from_bytes(&secret)
+            }
+            Err(_) => {
+                let mut secret = [0u8; 32];
+                getrandom::getrandom(&mut secret)
+                    .map_err(|e| format!("Failed to generate identity: {}", e))?;
+                std::fs::write(&key_path, secret)
+                    .map_err(|e| format!("Failed to save identity: {}", e))?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    let _ = std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o600));
+                }
+                SigningKey::from_bytes(&secret)
+            }
+        };
+
+        let pins = std::fs::read_to_string(root.join("pins.json"))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+
+        log::info!("Embedded IPFS store opened at {:?}", root);
+        Ok(Self {
+            root: root.to_path_buf(),
+            identity,
+            pins: Mutex::new(pins),
+        })
+    }
+
+    pub fn peer_id(&self) -> String {
+        let mut bytes = ED25519_PEER_ID_PREFIX.to_vec();
+        bytes.extend_from_slice(self.identity.verifying_key().as_bytes());
+        bs58::encode(bytes).into_string()
+    }
+
+    /// Store `data`, returning its CID. Added content is pinned, as Kubo does.
+    pub fn add(&self, data: &[u8]) -> Result<String, String> {
+        let mut bytes = RAW_SHA256_CID_PREFIX.to_vec();
+        bytes.extend_from_slice(&Sha256::digest(data));
+        let cid = format!("b{}", base32_encode(&bytes));
+        let path = self.block_path(&cid);
+        if !path.exists() {
+            // Write then rename so a crash never leaves a truncated block
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, data).map_err(|e| format!("Failed to store block: {}", e))?;
+            std::fs::rename(&tmp, &path).map_err(|e| format!("Failed to store block: {}", e))?;
+        }
+        self.pin(&cid)?;
+        Ok(cid)
+    }
+
+    pub fn cat(&self, cid: &str, max_bytes: u64) -> Result<Vec<u8>, String> {
+        let (cid, digest) = parse_cid(cid)?;
+        let path = self.block_path(&cid);
+        let size = std::fs::metadata(&path)
+            .map_err(|_| format!("{} not found in the embedded store", cid))?
+            .len();
+        if size > max_bytes {
+            return Err(format!("{} is {} bytes, over the {} byte limit", cid, size, max_bytes));
+        }
+
+        let data = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", cid, e))?;
+        if Sha256::digest(&data).as_slice() != digest {
+            return Err(format!("Block {} is corrupt", cid));
+        }
+        Ok(data)
+    }
+
+    pub fn pin(&self, cid: &str) -> Result<(), String> {
+        let (cid, _) = parse_cid(cid)?;
+        if !self.block_path(&cid).exists() {
+            return Err(format!("{} not found in the embedded store", cid));
+        }
+        let mut pins = self.pins.lock().unwrap();
+        if pins.insert(cid) {
+            self.save_pins(&pins)?;
+        }
+        Ok(())
+    }
+
+    /// Unpin `cid`; unpinned blocks are deleted right away since there is
+    /// no separate garbage collection
+    pub fn unpin(&self, cid: &str) -> Result<(), String> {
+        let (cid, _) = parse_cid(cid)?;
+        let mut pins = self.pins.lock().unwrap();
+        if pins.remove(&cid) {
+            self.save_pins(&pins)?;
+            let _ = std::fs::remove_file(self.block_path(&cid));
+        }
+        Ok(())
+    }
+
+    pub fn list_pins(&self) -> Vec<PinInfo> {
+        self.pins
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|cid| PinInfo {
+                cid: cid.clone(),
+                pin_type: "recursive".to_string(),
+                cumulative_size: std::fs::metadata(self.block_path(cid)).ok().map(|m| m.len()),
+            })
+            .collect()
+    }
+
+    pub fn stats(&self) -> IpfsStats {
+        let (num_objects, repo_size) = std::fs::read_dir(self.root.join("blocks"))
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok()?.metadata().ok())
+                    .filter(|m| m.is_file())
+                    .fold((0, 0), |(n, size), m| (n + 1, size + m.len()))
+            })
+            .unwrap_or((0, 0));
+
+        IpfsStats {
+            repo_size,
+            num_objects,
+            peers: 0,
+        }
+    }
+
+    fn block_path(&self, cid: &str) -> PathBuf {
+        self.root.join("blocks").join(cid)
+    }
+
+    fn save_pins(&self, pins: &BTreeSet<String>) -> Result<(), String> {
+        let data = serde_json::to_string_pretty(pins).map_err(|e| e.to_string())?;
+        std::fs::write(self.root.join("pins.json"), data)
+            .map_err(|e| format!("Failed to save pins: {}", e))
+    }
+}
+
+/// Validate a CID created by this store, returning it in canonical form
+/// (what blocks are stored under) along with its sha2-256 digest
+fn parse_cid(cid: &str) -> Result<(String, Vec<u8>), String> {
+    let cid = cid.trim().trim_start_matches("/ipfs/");
+    let bytes = cid
+        .strip_prefix('b')
+        .and_then(|encoded| base32_decode(&encoded.to_ascii_lowercase()))
+        .filter(|b| b.len() == 36 && b[..4] == RAW_SHA256_CID_PREFIX)
+        .ok_or_else(|| format!("Unsupported CID {} (only CIDv1 raw sha2-256 is stored)", cid))?;
+    Ok((format!("b{}", base32_encode(&bytes)), bytes[4..].to_vec()))
+}
+
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// RFC 4648 base32, lowercase and unpadded, as used by multibase `b`
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in input.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
@@ -0,0 +1,249 @@
+//! Node Fleet
+//!
+//! A user running several nodes can watch all of them from one app: other
+//! nodes are registered by their API address and share key, and their
+//! status, hardware and Ollama state are fetched from their APIs on
+//! request. Registering checks that the node at the address answers with
+//! that share key; every query checks it again, so a node whose key was
+//! rotated shows up as such instead of being queried.
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+use super::redact::Secret;
+use super::state_store::{RemoteNode, RemoteNodes};
+use crate::api::DEFAULT_API_PORT;
+use crate::error::NodeError;
+
+/// Longest a remote node gets to answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Header the share key is sent in, for nodes that check it
+pub const SHARE_KEY_HEADER: &str = "x-share-key";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddRemoteNodeRequest {
+    /// `host`, `host:port` or a URL; the port defaults to the API port
+    pub address: String,
+    pub share_key: String,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// What can be asked of a remote node
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteQuery {
+    Status,
+    Hardware,
+    OllamaStatus,
+    OllamaModels,
+}
+
+impl RemoteQuery {
+    fn path(self) -> &'static str {
+        match self {
+            Self::Status => "/api/v1/node/status",
+            Self::Hardware => "/api/v1/hardware",
+            Self::OllamaStatus => "/api/v1/ollama/status",
+            Self::OllamaModels => "/api/v1/ollama/models",
+        }
+    }
+}
+
+/// The answer of a node's `/health` to a request carrying a share key
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Health {
+    node_id: String,
+    /// Whether the key sent matches the node's
+    #[serde(default)]
+    share_key_valid: bool,
+}
+
+pub struct Fleet {
+    nodes: RemoteNodes,
+    client: reqwest::Client,
+}
+
+impl Fleet {
+    pub fn new(nodes: RemoteNodes) -> Self {
+        Self {
+            nodes,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn list(&self) -> Result<Vec<RemoteNode>, NodeError> {
+        Ok(self.nodes.list()?)
+    }
+
+    pub fn node(&self, id: &str) -> Result<RemoteNode, NodeError> {
+        self.nodes
+            .get(id)?
+            .ok_or_else(|| NodeError::NotFound(format!("Remote node {} not found", id)))
+    }
+
+    /// A request to a registered node, carrying its share key
+    pub fn request(&self, node: &RemoteNode, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", node.address, path))
+            .header(SHARE_KEY_HEADER, node.share_key.expose())
+    }
+
+    /// A node's `/health`, checking `share_key` against its own
+    async fn health(&self, address: &str, share_key: &str) -> Result<Health, NodeError> {
+        let url = format!("{}/health", address);
+        let response = self
+            .client
+            .get(&url)
+            .header(SHARE_KEY_HEADER, share_key)
+            .send()
+            .await
+            .map_err(|e| NodeError::request(&format!("Failed to reach {}", address), e))?;
+        if !response.status().is_success() {
+            return Err(NodeError::Upstream(format!("{} answered {}", address, response.status())));
+        }
+        response
+            .json()
+            .await
+            .map_err(|_| NodeError::Upstream(format!("{} is not an OtherThing node", address)))
+    }
+
+    /// Check the node at the address still has the key it was registered with
+    async fn verify(&self, node: &RemoteNode) -> Result<(), NodeError> {
+        let health = self.health(&node.address, node.share_key.expose()).await?;
+        if health.node_id != node.id {
+            return Err(NodeError::Conflict(format!("{} is now a different node", node.address)));
+        }
+        if !health.share_key_valid {
+            return Err(NodeError::Policy(format!("The share key of {} has changed", node.name)));
+        }
+        Ok(())
+    }
+
+    /// Register the node at `address` after checking its share key.
+    /// Registering a known node again updates its address, key and name.
+    pub async fn add(&self, request: AddRemoteNodeRequest, local_id: &str) -> Result<RemoteNode, NodeError> {
+        let address = normalize_address(&request.address)?;
+        let share_key = request.share_key.trim().to_string();
+        if share_key.is_empty() {
+            return Err(NodeError::Invalid("A share key is required".to_string()));
+        }
+
+        let health = self.health(&address, &share_key).await?;
+        if !health.share_key_valid {
+            return Err(NodeError::Policy(format!("Share key does not match the node at {}", address)));
+        }
+        if health.node_id == local_id {
+            return Err(NodeError::Conflict("That is this node".to_string()));
+        }
+
+        let existing = self.nodes.get(&health.node_id)?;
+        let name = request
+            .name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .or_else(|| existing.as_ref().map(|node| node.name.clone()))
+            .unwrap_or_else(|| address.trim_start_matches("http://").trim_start_matches("https://").to_string());
+        let node = RemoteNode {
+            id: health.node_id,
+            name,
+            address,
+            share_key: Secret::new(share_key),
+            added_at: existing
+                .map(|node| node.added_at)
+                .unwrap_or_else(|| chrono::Utc::now().to_rfc3339()),
+        };
+        self.nodes.save(&node)?;
+        log::info!("Registered remote node {} at {}", node.id, node.address);
+        Ok(node)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), NodeError> {
+        if !self.nodes.remove(id)? {
+            return Err(NodeError::NotFound(format!("Remote node {} not found", id)));
+        }
+        log::info!("Removed remote node {}", id);
+        Ok(())
+    }
+
+    /// Ask a remote node for its status, hardware or Ollama state
+    pub async fn query(&self, id: &str, query: RemoteQuery) -> Result<Value, NodeError> {
+        let node = self.node(id)?;
+        self.verify(&node).await?;
+
+        let response = self
+            .request(&node, reqwest::Method::GET, query.path())
+            .send()
+            .await
+            .map_err(|e| NodeError::request(&format!("Failed to reach {}", node.name), e))?;
+        if !response.status().is_success() {
+            return Err(NodeError::Upstream(format!("{} answered {}", node.name, response.status())));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| NodeError::Upstream(format!("Unreadable answer from {}: {}", node.name, e)))
+    }
+
+    /// Every registered node as listed by `/api/v1/my-nodes`, queried
+    /// concurrently; unreachable nodes are `offline`
+    pub async fn summaries(&self) -> Result<Vec<Value>, NodeError> {
+        let nodes = self.list()?;
+        let summaries = nodes.iter().map(|node| async move {
+            let mut summary = serde_json::json!({
+                "id": node.id,
+                "name": node.name,
+                "address": node.address,
+                "remote": true,
+                "addedAt": node.added_at,
+            });
+            match self.query(&node.id, RemoteQuery::Status).await {
+                Ok(status) => {
+                    let running = status.get("running").and_then(Value::as_bool).unwrap_or(false);
+                    summary["status"] = (if running { "online" } else { "offline" }).into();
+                    summary["hardware"] = status.get("hardware").cloned().unwrap_or_default();
+                    summary["uptimeSecs"] = status.get("uptime_secs").cloned().unwrap_or_default();
+                }
+                Err(e) => {
+                    summary["status"] = match &e {
+                        NodeError::Policy(_) | NodeError::Conflict(_) => "unauthorized",
+                        _ => "offline",
+                    }
+                    .into();
+                    summary["error"] = e.to_string().into();
+                }
+            }
+            summary
+        });
+        Ok(futures_util::future::join_all(summaries).await)
+    }
+}
+
+/// Base URL of a node's API from what the user typed
+fn normalize_address(address: &str) -> Result<String, NodeError> {
+    let address = address.trim().trim_end_matches('/');
+    if address.is_empty() {
+        return Err(NodeError::Invalid("An address is required".to_string()));
+    }
+    let with_scheme = if address.contains("://") {
+        address.to_string()
+    } else {
+        format!("http://{}", address)
+    };
+    let mut url = Url::parse(&with_scheme).map_err(|e| NodeError::Invalid(format!("Invalid address {}: {}", address, e)))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(NodeError::Invalid(format!("Invalid address {}", address)));
+    }
+    if url.scheme() == "http" && url.port().is_none() && !address.contains("://") {
+        let _ = url.set_port(Some(DEFAULT_API_PORT));
+    }
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
@@ -0,0 +1,40 @@
+//! Cloud GPU Credentials
+//!
+//! API keys for the GPU providers, one per provider, kept in the OS
+//! keychain. The `/api/v1/gpu` routes look keys up here so they never
+//! travel in URLs or end up in request logs.
+
+use super::keychain;
+
+fn keychain_key(provider: &str) -> String {
+    format!("gpu:{}", provider)
+}
+
+/// Save or replace the API key for a provider
+pub fn save(provider: &str, api_key: &str) -> Result<(), String> {
+    let provider = provider.trim();
+    if provider.is_empty() {
+        return Err("Provider is required".to_string());
+    }
+    if api_key.trim().is_empty() {
+        return Err("API key is required".to_string());
+    }
+    keychain::set(&keychain_key(provider), api_key.trim())?;
+    log::info!("Saved API key for GPU provider {}", provider);
+    Ok(())
+}
+
+pub fn remove(provider: &str) -> Result<(), String> {
+    keychain::delete(&keychain_key(provider))
+}
+
+/// API key saved for a provider
+pub fn get(provider: &str) -> Option<String> {
+    match keychain::get(&keychain_key(provider)) {
+        Ok(key) => key,
+        Err(e) => {
+            log::warn!("{}", e);
+            None
+        }
+    }
+}
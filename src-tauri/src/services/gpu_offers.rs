@@ -0,0 +1,203 @@
+//! Cloud GPU Offers
+//!
+//! Offers are fetched per provider and kept for a short while, so the UI
+//! can refine its filters without hitting the marketplace each time.
+//! Filtering and ranking happen here for every provider alike: offers are
+//! ranked by price per TFLOP, using the provider's own figure when it has
+//! one and the GPU model's FP32 throughput otherwise.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use super::gpu_provider::{GpuOffer, GpuProvider};
+
+/// How long fetched offers are served from the cache
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// FP32 TFLOPS per GPU, matched against lowercased model names in order,
+/// so more specific names come first
+const GPU_TFLOPS: &[(&str, f64)] = &[
+    ("h200", 67.0),
+    ("h100 pcie", 51.2),
+    ("h100", 67.0),
+    ("a100", 19.5),
+    ("l40s", 91.6),
+    ("l40", 90.5),
+    ("rtx 6000 ada", 91.1),
+    ("6000 ada", 91.1),
+    ("a6000", 38.7),
+    ("rtx a6000", 38.7),
+    ("a5000", 27.8),
+    ("a4000", 19.2),
+    ("a40", 37.4),
+    ("a10", 31.2),
+    ("l4", 30.3),
+    ("v100", 15.7),
+    ("t4", 8.1),
+    ("5090", 104.8),
+    ("4090", 82.6),
+    ("4080", 48.7),
+    ("4070", 29.1),
+    ("3090", 35.6),
+    ("3080", 29.8),
+    ("3070", 20.3),
+];
+
+/// Per-GPU FP32 TFLOPS of a model, if it is a known one
+pub fn model_tflops(gpu_name: &str) -> Option<f64> {
+    let name = gpu_name.to_lowercase();
+    GPU_TFLOPS
+        .iter()
+        .find(|(model, _)| name.contains(model))
+        .map(|(_, tflops)| *tflops)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OfferSort {
+    /// Cheapest compute first: lowest price per TFLOP
+    #[default]
+    Value,
+    /// Cheapest machine first
+    Price,
+    /// Fastest machine first
+    Performance,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OfferFilter {
+    #[serde(default)]
+    pub max_price: Option<f64>,
+    /// Substring of the GPU model, e.g. `RTX 4090`
+    #[serde(default)]
+    pub gpu_type: Option<String>,
+    /// Per GPU
+    #[serde(default)]
+    pub min_vram_gb: Option<f64>,
+    #[serde(default)]
+    pub min_gpus: Option<u32>,
+    /// 0 to 1; providers that do not rate hosts never match
+    #[serde(default)]
+    pub min_reliability: Option<f64>,
+    /// Substring of the location, e.g. `US` or `us-east`
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Lowest CUDA version the host must support, e.g. `12.2`; offers
+    /// whose CUDA version is unknown never match
+    #[serde(default)]
+    pub min_cuda: Option<f64>,
+    #[serde(default)]
+    pub sort: OfferSort,
+    /// Keep only the best offer of each GPU model
+    #[serde(default)]
+    pub distinct: bool,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+fn contains_ignore_case(haystack: &str, needle: Option<&str>) -> bool {
+    match needle {
+        None | Some("") | Some("any") => true,
+        Some(needle) => haystack.to_lowercase().contains(&needle.to_lowercase()),
+    }
+}
+
+impl OfferFilter {
+    pub fn matches(&self, offer: &GpuOffer) -> bool {
+        contains_ignore_case(&offer.gpu_name, self.gpu_type.as_deref())
+            && contains_ignore_case(&offer.location, self.region.as_deref())
+            && self.max_price.map_or(true, |max| offer.price_per_hour <= max)
+            && self
+                .min_vram_gb
+                .map_or(true, |min| offer.gpu_memory_mb as f64 / 1024.0 >= min)
+            && self.min_gpus.map_or(true, |min| offer.gpu_count >= min)
+            && self.min_reliability.map_or(true, |min| offer.reliability >= min)
+            && self
+                .min_cuda
+                .map_or(true, |min| offer.cuda_version.is_some_and(|cuda| cuda >= min))
+    }
+
+    /// Filter, sort and trim a list of offers
+    pub fn apply(&self, offers: &[GpuOffer]) -> Vec<GpuOffer> {
+        let mut offers: Vec<GpuOffer> = offers.iter().filter(|o| self.matches(o)).cloned().collect();
+
+        // Offers without a TFLOPS figure rank after those with one
+        let value = |o: &GpuOffer| o.price_per_tflop.unwrap_or(f64::INFINITY);
+        let speed = |o: &GpuOffer| o.tflops.unwrap_or(0.0);
+        match self.sort {
+            OfferSort::Value => offers.sort_by(|a, b| {
+                value(a)
+                    .total_cmp(&value(b))
+                    .then(a.price_per_hour.total_cmp(&b.price_per_hour))
+            }),
+            OfferSort::Price => offers.sort_by(|a, b| a.price_per_hour.total_cmp(&b.price_per_hour)),
+            OfferSort::Performance => offers.sort_by(|a, b| {
+                speed(b)
+                    .total_cmp(&speed(a))
+                    .then(a.price_per_hour.total_cmp(&b.price_per_hour))
+            }),
+        }
+
+        if self.distinct {
+            let mut seen = HashSet::new();
+            offers.retain(|o| seen.insert((o.gpu_name.to_lowercase(), o.gpu_count)));
+        }
+        if let Some(limit) = self.limit {
+            offers.truncate(limit);
+        }
+        offers
+    }
+}
+
+/// Fill in TFLOPS where the provider gave none, and the price per TFLOP
+fn rank(mut offer: GpuOffer) -> GpuOffer {
+    if offer.tflops.is_none() {
+        offer.tflops = model_tflops(&offer.gpu_name).map(|t| t * offer.gpu_count.max(1) as f64);
+    }
+    offer.price_per_tflop = offer
+        .tflops
+        .filter(|t| *t > 0.0)
+        .map(|t| offer.price_per_hour / t);
+    offer
+}
+
+/// Recently fetched offers, by provider
+#[derive(Default)]
+pub struct GpuOfferCache {
+    entries: RwLock<HashMap<String, (Instant, Vec<GpuOffer>)>>,
+}
+
+impl GpuOfferCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Offers of a provider matching `filter`, fetched again when the
+    /// cached list is stale or `refresh` is set
+    pub async fn offers(
+        &self,
+        provider: &dyn GpuProvider,
+        filter: &OfferFilter,
+        refresh: bool,
+    ) -> Result<Vec<GpuOffer>, String> {
+        if !refresh {
+            if let Some((fetched, offers)) = self.entries.read().await.get(provider.id()) {
+                if fetched.elapsed() < CACHE_TTL {
+                    return Ok(filter.apply(offers));
+                }
+            }
+        }
+
+        let offers: Vec<GpuOffer> = provider.list_offers().await?.into_iter().map(rank).collect();
+        log::info!("[GPU] Fetched {} offers from {}", offers.len(), provider.id());
+        let result = filter.apply(&offers);
+        self.entries
+            .write()
+            .await
+            .insert(provider.id().to_string(), (Instant::now(), offers));
+        Ok(result)
+    }
+}
@@ -0,0 +1,1024 @@
+//! Cloud GPU Providers
+//!
+//! Marketplaces the node can rent GPUs from for running larger models.
+//! Each provider maps its own API onto the same offer and instance shapes
+//! (GPU model, per-GPU VRAM, $/hr, ...) so the UI and the `/api/v1/gpu`
+//! routes do not care which one is used. Rented machines run Ollama on
+//! port 11434.
+//!
+//! Vast.ai, RunPod and Lambda Cloud are built in. Other marketplaces can
+//! be added in the config as custom providers: a base URL, the header the
+//! API key goes in, and JSON pointers mapping their responses onto ours.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::{gpu_credentials, redact};
+
+/// Image rented machines run when the request does not name one
+pub const DEFAULT_IMAGE: &str = "ollama/ollama";
+
+/// Port Ollama listens on inside rented machines
+const OLLAMA_PORT: u16 = 11434;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// IDs of the built-in providers
+pub const VAST: &str = "vast";
+pub const RUNPOD: &str = "runpod";
+pub const LAMBDA: &str = "lambda";
+
+/// Provider used when a request does not name one
+pub const DEFAULT_PROVIDER: &str = VAST;
+
+/// A machine that can be rented
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpuOffer {
+    pub id: String,
+    /// ID of the provider it comes from
+    pub provider: String,
+    pub gpu_name: String,
+    pub gpu_count: u32,
+    /// VRAM per GPU
+    pub gpu_memory_mb: u64,
+    pub cpu_cores: f64,
+    pub ram_mb: u64,
+    /// Total price for the machine, in USD
+    pub price_per_hour: f64,
+    pub location: String,
+    /// 0 to 1; 0 when the provider does not rate hosts
+    pub reliability: f64,
+    pub verified: bool,
+    /// Vast's deep learning performance score, 0 elsewhere
+    pub dlperf: f64,
+    /// FP32 TFLOPS of all GPUs together, from the provider or estimated
+    /// from the GPU model
+    #[serde(default)]
+    pub tflops: Option<f64>,
+    /// `price_per_hour / tflops`, the value offers are ranked by
+    #[serde(default)]
+    pub price_per_tflop: Option<f64>,
+    /// Highest CUDA version the host's driver supports
+    #[serde(default)]
+    pub cuda_version: Option<f64>,
+}
+
+/// A rented machine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpuInstance {
+    pub id: String,
+    pub provider: String,
+    /// `running`, `loading`, `exited`, ... as reported by the provider, lowercased
+    pub status: String,
+    pub gpu_name: String,
+    pub gpu_count: u32,
+    pub price_per_hour: f64,
+    pub total_cost: f64,
+    /// Unix seconds
+    pub started_at: Option<i64>,
+    pub ssh_host: Option<String>,
+    pub ssh_port: Option<u16>,
+    pub public_ip: Option<String>,
+    /// URL of the machine's Ollama, when reachable from outside
+    pub ollama_url: Option<String>,
+}
+
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RentRequest {
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Disk size in GB
+    #[serde(default)]
+    pub disk: Option<u32>,
+}
+
+/// A provider the UI can offer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpuProviderInfo {
+    pub id: String,
+    pub name: String,
+    /// Where users get an API key, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_url: Option<String>,
+    pub custom: bool,
+    /// Whether an API key is saved for it
+    pub configured: bool,
+}
+
+/// A GPU marketplace account
+#[async_trait]
+pub trait GpuProvider: Send + Sync {
+    /// ID offers and instances are tagged with
+    fn id(&self) -> &str;
+
+    /// Every machine available to rent; filtering and ranking happen in
+    /// the offer cache
+    async fn list_offers(&self) -> Result<Vec<GpuOffer>, String>;
+
+    /// Rent an offer; returns the ID of the new instance
+    async fn rent(&self, offer_id: &str, request: &RentRequest) -> Result<String, String>;
+
+    async fn list_instances(&self) -> Result<Vec<GpuInstance>, String>;
+
+    async fn destroy(&self, instance_id: &str) -> Result<(), String>;
+
+    /// Account credit in USD, if the provider reports it
+    async fn balance(&self) -> Result<Option<f64>, String>;
+}
+
+/// Built-in providers followed by the custom ones from the config
+pub fn list_providers(custom: &[CustomGpuProviderConfig]) -> Vec<GpuProviderInfo> {
+    let builtin = [
+        (VAST, "Vast.ai", "https://cloud.vast.ai/cli/"),
+        (RUNPOD, "RunPod", "https://www.runpod.io/console/user/settings"),
+        (LAMBDA, "Lambda Cloud", "https://cloud.lambdalabs.com/api-keys"),
+    ];
+    builtin
+        .into_iter()
+        .map(|(id, name, url)| GpuProviderInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            api_key_url: Some(url.to_string()),
+            custom: false,
+            configured: gpu_credentials::get(id).is_some(),
+        })
+        .chain(custom.iter().map(|c| GpuProviderInfo {
+            id: c.id.clone(),
+            name: c.name.clone().unwrap_or_else(|| c.id.clone()),
+            api_key_url: None,
+            custom: true,
+            configured: gpu_credentials::get(&c.id).is_some(),
+        }))
+        .collect()
+}
+
+/// Client for a provider, authenticated with the API key saved for it
+pub fn provider(id: &str, custom: &[CustomGpuProviderConfig]) -> Result<Box<dyn GpuProvider>, String> {
+    let api_key = gpu_credentials::get(id).ok_or_else(|| format!("No API key saved for GPU provider {}", id))?;
+    let api_key = api_key.as_str();
+    match id {
+        VAST => Ok(Box::new(VastProvider::new(api_key))),
+        RUNPOD => Ok(Box::new(RunPodProvider::new(api_key))),
+        LAMBDA => Ok(Box::new(LambdaProvider::new(api_key))),
+        _ => custom
+            .iter()
+            .find(|c| c.id == id)
+            .map(|c| Box::new(CustomGpuProvider::new(c.clone(), api_key)) as Box<dyn GpuProvider>)
+            .ok_or_else(|| format!("Unknown GPU provider: {}", id)),
+    }
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// JSON body of a successful response, or the provider's error
+async fn json_response(resp: reqwest::Response, what: &str) -> Result<Value, String> {
+    let status = resp.status();
+    let body = resp.text().await.map_err(|e| format!("Failed to {}: {}", what, e))?;
+    if !status.is_success() {
+        // Providers sometimes echo the request, credentials included
+        return Err(redact::redact(&format!("Failed to {}: {} {}", what, status, body)));
+    }
+    if body.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_str(&body).map_err(|e| format!("Failed to {}: invalid response: {}", what, e))
+}
+
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+fn f64_field(value: &Value, key: &str) -> f64 {
+    value.get(key).and_then(Value::as_f64).unwrap_or(0.0)
+}
+
+fn u64_field(value: &Value, key: &str) -> u64 {
+    value.get(key).and_then(Value::as_f64).map(|n| n as u64).unwrap_or(0)
+}
+
+/// IDs come back as numbers from some APIs and strings from others
+fn id_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn id_field(value: &Value, key: &str) -> String {
+    value.get(key).map(id_string).unwrap_or_default()
+}
+
+// ============ Vast.ai ============
+
+const VAST_API: &str = "https://console.vast.ai/api/v0";
+
+/// Offers fetched per listing; filters apply to this set
+const VAST_OFFER_LIMIT: u32 = 1000;
+
+pub struct VastProvider {
+    client: reqwest::Client,
+    api_key: String,
+}
+
+impl VastProvider {
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            client: client(),
+            api_key: api_key.to_string(),
+        }
+    }
+
+    fn auth(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        req.header("Authorization", format!("Bearer {}", self.api_key))
+    }
+
+    fn parse_offer(o: &Value) -> GpuOffer {
+        GpuOffer {
+            id: id_field(o, "id"),
+            provider: VAST.to_string(),
+            gpu_name: str_field(o, "gpu_name").unwrap_or_default(),
+            gpu_count: u64_field(o, "num_gpus") as u32,
+            gpu_memory_mb: u64_field(o, "gpu_ram"),
+            cpu_cores: f64_field(o, "cpu_cores_effective"),
+            ram_mb: u64_field(o, "cpu_ram"),
+            price_per_hour: f64_field(o, "dph_total"),
+            location: str_field(o, "geolocation").unwrap_or_else(|| "Unknown".to_string()),
+            reliability: f64_field(o, "reliability"),
+            verified: o.get("verified").and_then(Value::as_bool).unwrap_or(false),
+            dlperf: f64_field(o, "dlperf"),
+            tflops: o.get("total_flops").and_then(Value::as_f64).filter(|f| *f > 0.0),
+            price_per_tflop: None,
+            cuda_version: o.get("cuda_max_good").and_then(Value::as_f64),
+        }
+    }
+
+    fn parse_instance(i: &Value) -> GpuInstance {
+        let public_ip = str_field(i, "public_ipaddr").map(|ip| ip.trim().to_string());
+        // Docker-style port map: {"11434/tcp": [{"HostPort": "41234"}]}
+        let ollama_port = i
+            .get("ports")
+            .and_then(|p| p.get(format!("{}/tcp", OLLAMA_PORT)))
+            .and_then(|bindings| bindings.get(0))
+            .and_then(|b| b.get("HostPort"))
+            .and_then(Value::as_str)
+            .and_then(|p| p.parse::<u16>().ok());
+        let ollama_url = match (&public_ip, ollama_port) {
+            (Some(ip), Some(port)) => Some(format!("http://{}:{}", ip, port)),
+            _ => None,
+        };
+
+        GpuInstance {
+            id: id_field(i, "id"),
+            provider: VAST.to_string(),
+            status: str_field(i, "actual_status").unwrap_or_else(|| "unknown".to_string()).to_lowercase(),
+            gpu_name: str_field(i, "gpu_name").unwrap_or_default(),
+            gpu_count: u64_field(i, "num_gpus") as u32,
+            price_per_hour: f64_field(i, "dph_total"),
+            total_cost: f64_field(i, "total_cost"),
+            started_at: i.get("start_date").and_then(Value::as_f64).map(|t| t as i64),
+            ssh_host: str_field(i, "ssh_host"),
+            ssh_port: i.get("ssh_port").and_then(Value::as_u64).map(|p| p as u16),
+            public_ip,
+            ollama_url,
+        }
+    }
+}
+
+#[async_trait]
+impl GpuProvider for VastProvider {
+    fn id(&self) -> &str {
+        VAST
+    }
+
+    async fn list_offers(&self) -> Result<Vec<GpuOffer>, String> {
+        let query = serde_json::json!({
+            "rentable": {"eq": true},
+            "rented": {"eq": false},
+            "type": "on-demand",
+            "order": [["dph_total", "asc"]],
+            "limit": VAST_OFFER_LIMIT
+        });
+
+        let url = format!("{}/bundles/?q={}", VAST_API, urlencoding::encode(&query.to_string()));
+        let resp = self
+            .auth(self.client.get(&url))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to list offers: {}", e))?;
+        let body = json_response(resp, "list offers").await?;
+
+        Ok(body
+            .get("offers")
+            .and_then(Value::as_array)
+            .map(|offers| offers.iter().map(Self::parse_offer).collect())
+            .unwrap_or_default())
+    }
+
+    async fn rent(&self, offer_id: &str, request: &RentRequest) -> Result<String, String> {
+        let payload = serde_json::json!({
+            "client_id": "me",
+            "image": request.image.clone().unwrap_or_else(|| DEFAULT_IMAGE.to_string()),
+            "disk": request.disk.unwrap_or(20),
+            "label": "otherthing-workspace",
+            "onstart": "#!/bin/bash\nollama serve &\nsleep 5\necho 'Ollama ready on port 11434'",
+            "runtype": "ssh_direc ssh_proxy",
+            "env": {
+                "OLLAMA_HOST": "0.0.0.0",
+                "-p 11434:11434": "1"
+            }
+        });
+
+        log::info!("[GPU] Renting Vast offer {}", offer_id);
+        let resp = self
+            .auth(self.client.put(format!("{}/asks/{}/", VAST_API, offer_id)))
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to rent offer: {}", e))?;
+        let body = json_response(resp, "rent offer").await?;
+
+        body.get("new_contract")
+            .map(id_string)
+            .ok_or_else(|| format!("Failed to rent offer: {}", body))
+    }
+
+    async fn list_instances(&self) -> Result<Vec<GpuInstance>, String> {
+        let resp = self
+            .auth(self.client.get(format!("{}/instances/", VAST_API)))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to list instances: {}", e))?;
+        let body = json_response(resp, "list instances").await?;
+
+        Ok(body
+            .get("instances")
+            .and_then(Value::as_array)
+            .map(|instances| instances.iter().map(Self::parse_instance).collect())
+            .unwrap_or_default())
+    }
+
+    async fn destroy(&self, instance_id: &str) -> Result<(), String> {
+        log::info!("[GPU] Destroying Vast instance {}", instance_id);
+        let resp = self
+            .auth(self.client.delete(format!("{}/instances/{}/", VAST_API, instance_id)))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to destroy instance: {}", e))?;
+        json_response(resp, "destroy instance").await.map(|_| ())
+    }
+
+    async fn balance(&self) -> Result<Option<f64>, String> {
+        let resp = self
+            .auth(self.client.get(format!("{}/users/current/", VAST_API)))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to get account: {}", e))?;
+        let body = json_response(resp, "get account").await?;
+        Ok(body.get("credit").and_then(Value::as_f64))
+    }
+}
+
+// ============ RunPod ============
+
+const RUNPOD_GRAPHQL: &str = "https://api.runpod.io/graphql";
+const RUNPOD_REST: &str = "https://rest.runpod.io/v1";
+
+pub struct RunPodProvider {
+    client: reqwest::Client,
+    api_key: String,
+}
+
+impl RunPodProvider {
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            client: client(),
+            api_key: api_key.to_string(),
+        }
+    }
+
+    fn auth(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        req.header("Authorization", format!("Bearer {}", self.api_key))
+    }
+
+    async fn graphql(&self, query: &str, what: &str) -> Result<Value, String> {
+        let resp = self
+            .auth(self.client.post(RUNPOD_GRAPHQL))
+            .json(&serde_json::json!({ "query": query }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to {}: {}", what, e))?;
+        let body = json_response(resp, what).await?;
+        if let Some(errors) = body.get("errors") {
+            return Err(format!("Failed to {}: {}", what, errors));
+        }
+        Ok(body.get("data").cloned().unwrap_or(Value::Null))
+    }
+
+    fn parse_instance(pod: &Value) -> GpuInstance {
+        let id = id_field(pod, "id");
+        let status = str_field(pod, "desiredStatus").unwrap_or_else(|| "unknown".to_string()).to_lowercase();
+        let gpu = pod.get("gpu").cloned().unwrap_or(Value::Null);
+        let public_ip = str_field(pod, "publicIp").filter(|ip| !ip.is_empty());
+        // {"22": 40123} maps container ports to public ones
+        let ssh_port = pod
+            .get("portMappings")
+            .and_then(|m| m.get("22"))
+            .and_then(Value::as_u64)
+            .map(|p| p as u16);
+        let started_at = str_field(pod, "lastStartedAt")
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(&t).ok())
+            .map(|t| t.timestamp());
+
+        GpuInstance {
+            // HTTP ports are exposed through RunPod's proxy
+            ollama_url: Some(format!("https://{}-{}.proxy.runpod.net", id, OLLAMA_PORT)),
+            id,
+            provider: RUNPOD.to_string(),
+            status,
+            gpu_name: str_field(&gpu, "displayName").or_else(|| str_field(&gpu, "id")).unwrap_or_default(),
+            gpu_count: u64_field(&gpu, "count").max(1) as u32,
+            price_per_hour: f64_field(pod, "costPerHr"),
+            total_cost: 0.0,
+            started_at,
+            ssh_host: ssh_port.and(public_ip.clone()),
+            ssh_port,
+            public_ip,
+        }
+    }
+}
+
+#[async_trait]
+impl GpuProvider for RunPodProvider {
+    fn id(&self) -> &str {
+        RUNPOD
+    }
+
+    async fn list_offers(&self) -> Result<Vec<GpuOffer>, String> {
+        let data = self
+            .graphql(
+                "query { gpuTypes { id displayName memoryInGb secureCloud communityCloud \
+                 lowestPrice(input: { gpuCount: 1 }) { uninterruptablePrice stockStatus } } }",
+                "list offers",
+            )
+            .await?;
+
+        // RunPod rents by GPU type rather than by host
+        let mut offers: Vec<GpuOffer> = data
+            .get("gpuTypes")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|gpu| {
+                let price = gpu.get("lowestPrice")?.get("uninterruptablePrice")?.as_f64()?;
+                let name = str_field(gpu, "displayName").unwrap_or_else(|| id_field(gpu, "id"));
+                let secure = gpu.get("secureCloud").and_then(Value::as_bool).unwrap_or(false);
+                Some(GpuOffer {
+                    id: id_field(gpu, "id"),
+                    provider: RUNPOD.to_string(),
+                    gpu_name: name,
+                    gpu_count: 1,
+                    gpu_memory_mb: u64_field(gpu, "memoryInGb") * 1024,
+                    cpu_cores: 0.0,
+                    ram_mb: 0,
+                    price_per_hour: price,
+                    location: if secure { "Secure Cloud" } else { "Community Cloud" }.to_string(),
+                    reliability: 0.0,
+                    verified: secure,
+                    dlperf: 0.0,
+                    tflops: None,
+                    price_per_tflop: None,
+                    cuda_version: None,
+                })
+            })
+            .collect();
+        offers.sort_by(|a, b| a.price_per_hour.total_cmp(&b.price_per_hour));
+        Ok(offers)
+    }
+
+    async fn rent(&self, offer_id: &str, request: &RentRequest) -> Result<String, String> {
+        let payload = serde_json::json!({
+            "name": "otherthing-workspace",
+            "imageName": request.image.clone().unwrap_or_else(|| DEFAULT_IMAGE.to_string()),
+            "gpuTypeIds": [offer_id],
+            "gpuCount": 1,
+            "containerDiskInGb": request.disk.unwrap_or(20),
+            "ports": [format!("{}/http", OLLAMA_PORT), "22/tcp"],
+            "env": { "OLLAMA_HOST": "0.0.0.0" }
+        });
+
+        log::info!("[GPU] Renting RunPod GPU {}", offer_id);
+        let resp = self
+            .auth(self.client.post(format!("{}/pods", RUNPOD_REST)))
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to rent offer: {}", e))?;
+        let pod = json_response(resp, "rent offer").await?;
+        Ok(id_field(&pod, "id"))
+    }
+
+    async fn list_instances(&self) -> Result<Vec<GpuInstance>, String> {
+        let resp = self
+            .auth(self.client.get(format!("{}/pods", RUNPOD_REST)))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to list instances: {}", e))?;
+        let body = json_response(resp, "list instances").await?;
+
+        Ok(body
+            .as_array()
+            .map(|pods| pods.iter().map(Self::parse_instance).collect())
+            .unwrap_or_default())
+    }
+
+    async fn destroy(&self, instance_id: &str) -> Result<(), String> {
+        log::info!("[GPU] Terminating RunPod pod {}", instance_id);
+        let resp = self
+            .auth(self.client.delete(format!("{}/pods/{}", RUNPOD_REST, instance_id)))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to destroy instance: {}", e))?;
+        json_response(resp, "destroy instance").await.map(|_| ())
+    }
+
+    async fn balance(&self) -> Result<Option<f64>, String> {
+        let data = self.graphql("query { myself { clientBalance } }", "get account").await?;
+        Ok(data
+            .get("myself")
+            .and_then(|m| m.get("clientBalance"))
+            .and_then(Value::as_f64))
+    }
+}
+
+// ============ Lambda Cloud ============
+
+/// Per-GPU VRAM from descriptions like `A100 (40 GB PCIe)`
+fn vram_from_description(description: &str) -> u64 {
+    let Some((before, _)) = description.split_once(" GB") else {
+        return 0;
+    };
+    before
+        .rsplit(|c: char| !c.is_ascii_digit())
+        .next()
+        .and_then(|n| n.parse::<u64>().ok())
+        .map(|gb| gb * 1024)
+        .unwrap_or(0)
+}
+
+const LAMBDA_API: &str = "https://cloud.lambdalabs.com/api/v1";
+
+/// Installs Ollama on first boot; Lambda machines are plain VMs
+const LAMBDA_USER_DATA: &str = "#cloud-config\nruncmd:\n  - curl -fsSL https://ollama.com/install.sh | sh\n";
+
+pub struct LambdaProvider {
+    client: reqwest::Client,
+    api_key: String,
+}
+
+impl LambdaProvider {
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            client: client(),
+            api_key: api_key.to_string(),
+        }
+    }
+
+    fn auth(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        req.header("Authorization", format!("Bearer {}", self.api_key))
+    }
+
+    async fn get(&self, path: &str, what: &str) -> Result<Value, String> {
+        let resp = self
+            .auth(self.client.get(format!("{}{}", LAMBDA_API, path)))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to {}: {}", what, e))?;
+        let body = json_response(resp, what).await?;
+        Ok(body.get("data").cloned().unwrap_or(Value::Null))
+    }
+
+    async fn post(&self, path: &str, payload: &Value, what: &str) -> Result<Value, String> {
+        let resp = self
+            .auth(self.client.post(format!("{}{}", LAMBDA_API, path)))
+            .json(payload)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to {}: {}", what, e))?;
+        let body = json_response(resp, what).await?;
+        Ok(body.get("data").cloned().unwrap_or(Value::Null))
+    }
+
+    fn parse_instance(i: &Value) -> GpuInstance {
+        let instance_type = i.get("instance_type").cloned().unwrap_or(Value::Null);
+        let public_ip = str_field(i, "ip");
+        GpuInstance {
+            id: id_field(i, "id"),
+            provider: LAMBDA.to_string(),
+            status: str_field(i, "status").unwrap_or_else(|| "unknown".to_string()).to_lowercase(),
+            gpu_name: str_field(&instance_type, "gpu_description").unwrap_or_default(),
+            gpu_count: instance_type
+                .get("specs")
+                .map(|s| u64_field(s, "gpus"))
+                .unwrap_or(1) as u32,
+            price_per_hour: f64_field(&instance_type, "price_cents_per_hour") / 100.0,
+            total_cost: 0.0,
+            started_at: None,
+            ssh_host: public_ip.clone(),
+            ssh_port: public_ip.as_ref().map(|_| 22),
+            public_ip,
+            // Only SSH is open by default; Ollama is reached through a tunnel
+            ollama_url: None,
+        }
+    }
+}
+
+#[async_trait]
+impl GpuProvider for LambdaProvider {
+    fn id(&self) -> &str {
+        LAMBDA
+    }
+
+    async fn list_offers(&self) -> Result<Vec<GpuOffer>, String> {
+        let data = self.get("/instance-types", "list offers").await?;
+        let Some(types) = data.as_object() else {
+            return Ok(Vec::new());
+        };
+
+        // One offer per instance type and region with capacity; the ID
+        // carries both as `type@region`
+        let mut offers = Vec::new();
+        for entry in types.values() {
+            let Some(instance_type) = entry.get("instance_type") else {
+                continue;
+            };
+            let name = str_field(instance_type, "name").unwrap_or_default();
+            let gpu_name = str_field(instance_type, "gpu_description").unwrap_or_else(|| name.clone());
+            let price = f64_field(instance_type, "price_cents_per_hour") / 100.0;
+            let specs = instance_type.get("specs").cloned().unwrap_or(Value::Null);
+            let regions = entry
+                .get("regions_with_capacity_available")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            for region in regions {
+                let region_name = str_field(&region, "name").unwrap_or_default();
+                offers.push(GpuOffer {
+                    id: format!("{}@{}", name, region_name),
+                    provider: LAMBDA.to_string(),
+                    gpu_name: gpu_name.clone(),
+                    gpu_count: u64_field(&specs, "gpus") as u32,
+                    // Lambda does not report VRAM; the GPU description names it
+                    gpu_memory_mb: vram_from_description(&gpu_name),
+                    cpu_cores: f64_field(&specs, "vcpus"),
+                    ram_mb: u64_field(&specs, "memory_gib") * 1024,
+                    price_per_hour: price,
+                    location: str_field(&region, "description").unwrap_or(region_name),
+                    reliability: 0.0,
+                    verified: true,
+                    dlperf: 0.0,
+                    tflops: None,
+                    price_per_tflop: None,
+                    cuda_version: None,
+                });
+            }
+        }
+        offers.sort_by(|a, b| a.price_per_hour.total_cmp(&b.price_per_hour));
+        Ok(offers)
+    }
+
+    async fn rent(&self, offer_id: &str, _request: &RentRequest) -> Result<String, String> {
+        let (instance_type, region) = offer_id
+            .split_once('@')
+            .ok_or_else(|| format!("Invalid Lambda offer: {}", offer_id))?;
+
+        // Lambda requires an SSH key on the account to launch anything
+        let keys = self.get("/ssh-keys", "list SSH keys").await?;
+        let key_name = keys
+            .as_array()
+            .and_then(|keys| keys.first())
+            .and_then(|k| str_field(k, "name"))
+            .ok_or("Add an SSH key to your Lambda Cloud account before renting")?;
+
+        let payload = serde_json::json!({
+            "region_name": region,
+            "instance_type_name": instance_type,
+            "ssh_key_names": [key_name],
+            "quantity": 1,
+            "name": "otherthing-workspace",
+            "user_data": LAMBDA_USER_DATA,
+        });
+        log::info!("[GPU] Launching Lambda {} in {}", instance_type, region);
+        let data = self.post("/instance-operations/launch", &payload, "rent offer").await?;
+        data.get("instance_ids")
+            .and_then(|ids| ids.get(0))
+            .map(id_string)
+            .ok_or_else(|| format!("Failed to rent offer: {}", data))
+    }
+
+    async fn list_instances(&self) -> Result<Vec<GpuInstance>, String> {
+        let data = self.get("/instances", "list instances").await?;
+        Ok(data
+            .as_array()
+            .map(|instances| instances.iter().map(Self::parse_instance).collect())
+            .unwrap_or_default())
+    }
+
+    async fn destroy(&self, instance_id: &str) -> Result<(), String> {
+        log::info!("[GPU] Terminating Lambda instance {}", instance_id);
+        let payload = serde_json::json!({ "instance_ids": [instance_id] });
+        self.post("/instance-operations/terminate", &payload, "destroy instance")
+            .await
+            .map(|_| ())
+    }
+
+    async fn balance(&self) -> Result<Option<f64>, String> {
+        // Lambda bills by invoice and has no credit balance
+        Ok(None)
+    }
+}
+
+// ============ Custom providers ============
+
+/// A marketplace described entirely in the config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomGpuProviderConfig {
+    /// ID requests select the provider by, e.g. `acme`
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// e.g. `https://api.acme.cloud/v1`
+    pub base_url: String,
+    /// Header the API key is sent in
+    #[serde(default = "default_auth_header")]
+    pub auth_header: String,
+    /// Put before the key in the header
+    #[serde(default = "default_auth_prefix")]
+    pub auth_prefix: String,
+    pub offers: ListMapping,
+    pub instances: ListMapping,
+    pub rent: RentMapping,
+    /// Path of the DELETE that destroys an instance; `{id}` is replaced
+    /// with the instance ID
+    pub destroy_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance: Option<FieldMapping>,
+}
+
+/// A GET returning a list, and how its items map onto offers or instances
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListMapping {
+    /// Relative to the base URL
+    pub path: String,
+    /// JSON pointer to the array in the response; empty for the root
+    #[serde(default)]
+    pub items: String,
+    /// Our field name (`id`, `gpuName`, `pricePerHour`, ...) to a JSON
+    /// pointer within each item, e.g. `"gpuName": "/gpu/model"`. Fields
+    /// not listed are read from a key of the same name.
+    #[serde(default)]
+    pub fields: HashMap<String, String>,
+}
+
+/// The POST that rents an offer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RentMapping {
+    /// Relative to the base URL; `{id}` is replaced with the offer ID
+    pub path: String,
+    /// Request body; `{id}`, `{image}` and `{disk}` in string values are
+    /// filled in, and a value that is exactly `{disk}` becomes a number
+    #[serde(default)]
+    pub body: Value,
+    /// JSON pointer to the new instance's ID in the response
+    pub instance_id: String,
+}
+
+/// A GET and the JSON pointer of the value wanted from it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldMapping {
+    pub path: String,
+    pub field: String,
+}
+
+fn default_auth_header() -> String {
+    "Authorization".to_string()
+}
+
+fn default_auth_prefix() -> String {
+    "Bearer ".to_string()
+}
+
+pub struct CustomGpuProvider {
+    client: reqwest::Client,
+    config: CustomGpuProviderConfig,
+    api_key: String,
+}
+
+/// Reads our fields out of one item of a mapped list
+struct MappedItem<'a> {
+    item: &'a Value,
+    fields: &'a HashMap<String, String>,
+}
+
+impl MappedItem<'_> {
+    fn get(&self, field: &str) -> Option<&Value> {
+        match self.fields.get(field) {
+            Some(pointer) => self.item.pointer(pointer),
+            None => self.item.get(field),
+        }
+    }
+
+    fn string(&self, field: &str) -> Option<String> {
+        self.get(field).map(|v| match v {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+    }
+
+    fn f64(&self, field: &str) -> f64 {
+        self.get(field)
+            .and_then(|v| v.as_f64().or_else(|| v.as_str()?.parse().ok()))
+            .unwrap_or(0.0)
+    }
+
+    fn bool(&self, field: &str) -> bool {
+        self.get(field).and_then(Value::as_bool).unwrap_or(false)
+    }
+}
+
+/// Fill the placeholders of a request body template
+fn fill_template(value: &Value, id: &str, image: &str, disk: u32) -> Value {
+    match value {
+        Value::String(s) if s == "{disk}" => Value::from(disk),
+        Value::String(s) => Value::String(
+            s.replace("{id}", id)
+                .replace("{image}", image)
+                .replace("{disk}", &disk.to_string()),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(|v| fill_template(v, id, image, disk)).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), fill_template(v, id, image, disk)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+impl CustomGpuProvider {
+    pub fn new(config: CustomGpuProviderConfig, api_key: &str) -> Self {
+        Self {
+            client: client(),
+            config,
+            api_key: api_key.to_string(),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.config.base_url.trim_end_matches('/'), path.trim_start_matches('/'))
+    }
+
+    fn auth(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        req.header(
+            self.config.auth_header.as_str(),
+            format!("{}{}", self.config.auth_prefix, self.api_key),
+        )
+    }
+
+    async fn get(&self, path: &str, what: &str) -> Result<Value, String> {
+        let resp = self
+            .auth(self.client.get(self.url(path)))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to {}: {}", what, e))?;
+        json_response(resp, what).await
+    }
+
+    async fn list(&self, mapping: &ListMapping, what: &str) -> Result<Vec<Value>, String> {
+        let body = self.get(&mapping.path, what).await?;
+        let items = if mapping.items.is_empty() {
+            Some(&body)
+        } else {
+            body.pointer(&mapping.items)
+        };
+        Ok(items.and_then(Value::as_array).cloned().unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl GpuProvider for CustomGpuProvider {
+    fn id(&self) -> &str {
+        &self.config.id
+    }
+
+    async fn list_offers(&self) -> Result<Vec<GpuOffer>, String> {
+        let fields = &self.config.offers.fields;
+        let mut offers: Vec<GpuOffer> = self
+            .list(&self.config.offers, "list offers")
+            .await?
+            .iter()
+            .map(|item| {
+                let o = MappedItem { item, fields };
+                GpuOffer {
+                    id: o.string("id").unwrap_or_default(),
+                    provider: self.config.id.clone(),
+                    gpu_name: o.string("gpuName").unwrap_or_default(),
+                    gpu_count: (o.f64("gpuCount") as u32).max(1),
+                    gpu_memory_mb: o.f64("gpuMemoryMb") as u64,
+                    cpu_cores: o.f64("cpuCores"),
+                    ram_mb: o.f64("ramMb") as u64,
+                    price_per_hour: o.f64("pricePerHour"),
+                    location: o.string("location").unwrap_or_else(|| "Unknown".to_string()),
+                    reliability: o.f64("reliability"),
+                    verified: o.bool("verified"),
+                    dlperf: o.f64("dlperf"),
+                    tflops: Some(o.f64("tflops")).filter(|f| *f > 0.0),
+                    price_per_tflop: None,
+                    cuda_version: Some(o.f64("cudaVersion")).filter(|v| *v > 0.0),
+                }
+            })
+            .collect();
+        offers.sort_by(|a, b| a.price_per_hour.total_cmp(&b.price_per_hour));
+        Ok(offers)
+    }
+
+    async fn rent(&self, offer_id: &str, request: &RentRequest) -> Result<String, String> {
+        let rent = &self.config.rent;
+        let image = request.image.clone().unwrap_or_else(|| DEFAULT_IMAGE.to_string());
+        let body = fill_template(&rent.body, offer_id, &image, request.disk.unwrap_or(20));
+
+        log::info!("[GPU] Renting {} offer {}", self.config.id, offer_id);
+        let resp = self
+            .auth(self.client.post(self.url(&rent.path.replace("{id}", offer_id))))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to rent offer: {}", e))?;
+        let body = json_response(resp, "rent offer").await?;
+        body.pointer(&rent.instance_id)
+            .map(id_string)
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| format!("Failed to rent offer: no instance ID in {}", body))
+    }
+
+    async fn list_instances(&self) -> Result<Vec<GpuInstance>, String> {
+        let fields = &self.config.instances.fields;
+        Ok(self
+            .list(&self.config.instances, "list instances")
+            .await?
+            .iter()
+            .map(|item| {
+                let i = MappedItem { item, fields };
+                GpuInstance {
+                    id: i.string("id").unwrap_or_default(),
+                    provider: self.config.id.clone(),
+                    status: i.string("status").unwrap_or_else(|| "unknown".to_string()).to_lowercase(),
+                    gpu_name: i.string("gpuName").unwrap_or_default(),
+                    gpu_count: (i.f64("gpuCount") as u32).max(1),
+                    price_per_hour: i.f64("pricePerHour"),
+                    total_cost: i.f64("totalCost"),
+                    started_at: i.get("startedAt").and_then(Value::as_f64).map(|t| t as i64),
+                    ssh_host: i.string("sshHost"),
+                    ssh_port: i.get("sshPort").and_then(Value::as_u64).map(|p| p as u16),
+                    public_ip: i.string("publicIp"),
+                    ollama_url: i.string("ollamaUrl"),
+                }
+            })
+            .collect())
+    }
+
+    async fn destroy(&self, instance_id: &str) -> Result<(), String> {
+        log::info!("[GPU] Destroying {} instance {}", self.config.id, instance_id);
+        let resp = self
+            .auth(self.client.delete(self.url(&self.config.destroy_path.replace("{id}", instance_id))))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to destroy instance: {}", e))?;
+        json_response(resp, "destroy instance").await.map(|_| ())
+    }
+
+    async fn balance(&self) -> Result<Option<f64>, String> {
+        let Some(mapping) = &self.config.balance else {
+            return Ok(None);
+        };
+        let body = self.get(&mapping.path, "get account").await?;
+        Ok(body.pointer(&mapping.field).and_then(Value::as_f64))
+    }
+}
@@ -0,0 +1,300 @@
+//! Cloud GPU Spend
+//!
+//! Polls the rented instances of every provider with a saved API key and
+//! accrues their cost (price per hour x time seen running) into a ledger
+//! kept in `gpu_spend.json` under the config dir. With a monthly budget
+//! set, crossing 80% and 100% of it raises a `GpuBudgetAlert` node event,
+//! and reaching it can destroy every running instance.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+
+use super::gpu_provider;
+use crate::config::NodeConfig;
+use crate::models::NodeEvent;
+
+/// How often instances are polled
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Share of the budget at which the first alert goes out
+const WARNING_RATIO: f64 = 0.8;
+
+/// Cost of one instance as seen by the node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendEntry {
+    pub instance_id: String,
+    pub provider: String,
+    pub gpu_name: String,
+    pub price_per_hour: f64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// When it was last seen running; unset while stopped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub running_since_sample: Option<DateTime<Utc>>,
+    /// USD accrued per calendar month (`YYYY-MM`)
+    #[serde(default)]
+    pub monthly: BTreeMap<String, f64>,
+}
+
+impl SpendEntry {
+    pub fn total(&self) -> f64 {
+        self.monthly.values().sum()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpendLedger {
+    /// By `provider:instance_id`
+    #[serde(default)]
+    entries: HashMap<String, SpendEntry>,
+    /// Highest alert level sent, by month: 1 for the warning, 2 for the cap
+    #[serde(default)]
+    alerts: BTreeMap<String, u8>,
+}
+
+/// An instance's spend as reported by `GET /api/v1/gpu/spend`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceSpend {
+    pub instance_id: String,
+    pub provider: String,
+    pub gpu_name: String,
+    pub price_per_hour: f64,
+    pub running: bool,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub month_cost: f64,
+    pub total_cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendSummary {
+    pub month: String,
+    /// Spent this month across all instances
+    pub spent: f64,
+    pub budget: Option<f64>,
+    pub auto_destroy: bool,
+    /// Spent since tracking began
+    pub total: f64,
+    pub instances: Vec<InstanceSpend>,
+}
+
+fn ledger_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+        .join("gpu_spend.json")
+}
+
+fn month_of(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m").to_string()
+}
+
+pub struct GpuSpendTracker {
+    ledger: RwLock<SpendLedger>,
+    path: PathBuf,
+    config: Arc<RwLock<NodeConfig>>,
+    events: broadcast::Sender<NodeEvent>,
+}
+
+impl GpuSpendTracker {
+    pub fn load(config: Arc<RwLock<NodeConfig>>, events: broadcast::Sender<NodeEvent>) -> Self {
+        let path = ledger_path();
+        let ledger = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+
+        Self {
+            ledger: RwLock::new(ledger),
+            path,
+            config,
+            events,
+        }
+    }
+
+    fn persist(&self, ledger: &SpendLedger) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let data = serde_json::to_string_pretty(ledger).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, data).map_err(|e| format!("Failed to save GPU spend: {}", e))
+    }
+
+    /// This month's spend, the budget and every tracked instance
+    pub async fn summary(&self) -> SpendSummary {
+        let month = month_of(Utc::now());
+        let gpu = self.config.read().await.gpu.clone();
+        let ledger = self.ledger.read().await;
+
+        let mut instances: Vec<InstanceSpend> = ledger
+            .entries
+            .values()
+            .map(|e| InstanceSpend {
+                instance_id: e.instance_id.clone(),
+                provider: e.provider.clone(),
+                gpu_name: e.gpu_name.clone(),
+                price_per_hour: e.price_per_hour,
+                running: e.running_since_sample.is_some(),
+                first_seen: e.first_seen,
+                last_seen: e.last_seen,
+                month_cost: e.monthly.get(&month).copied().unwrap_or(0.0),
+                total_cost: e.total(),
+            })
+            .collect();
+        instances.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+
+        SpendSummary {
+            spent: instances.iter().map(|i| i.month_cost).sum(),
+            total: instances.iter().map(|i| i.total_cost).sum(),
+            month,
+            budget: gpu.monthly_budget,
+            auto_destroy: gpu.auto_destroy,
+            instances,
+        }
+    }
+
+    /// Poll every configured provider once and accrue what ran since the
+    /// last poll
+    pub async fn sample(&self) {
+        let custom = self.config.read().await.gpu.custom_providers.clone();
+        let now = Utc::now();
+        let month = month_of(now);
+        let mut seen = Vec::new();
+
+        for info in gpu_provider::list_providers(&custom).into_iter().filter(|p| p.configured) {
+            let provider = match gpu_provider::provider(&info.id, &custom) {
+                Ok(provider) => provider,
+                Err(e) => {
+                    log::warn!("[GPU] {}", e);
+                    continue;
+                }
+            };
+            match provider.list_instances().await {
+                Ok(instances) => seen.push((info.id, instances)),
+                Err(e) => log::warn!("[GPU] Spend tracking skipped {}: {}", info.id, e),
+            }
+        }
+        if seen.is_empty() {
+            return;
+        }
+
+        let mut ledger = self.ledger.write().await;
+        for (provider, instances) in &seen {
+            // Instances a provider no longer lists are gone
+            let listed: Vec<&str> = instances.iter().map(|i| i.id.as_str()).collect();
+            for entry in ledger.entries.values_mut().filter(|e| &e.provider == provider) {
+                if !listed.contains(&entry.instance_id.as_str()) {
+                    entry.running_since_sample = None;
+                }
+            }
+
+            for instance in instances {
+                let key = format!("{}:{}", provider, instance.id);
+                let entry = ledger.entries.entry(key).or_insert_with(|| SpendEntry {
+                    instance_id: instance.id.clone(),
+                    provider: provider.clone(),
+                    gpu_name: instance.gpu_name.clone(),
+                    price_per_hour: instance.price_per_hour,
+                    first_seen: now,
+                    last_seen: now,
+                    running_since_sample: None,
+                    monthly: BTreeMap::new(),
+                });
+                if instance.status != "running" {
+                    entry.running_since_sample = None;
+                    continue;
+                }
+                // Providers bill while an instance runs, so time between
+                // two sightings counts even if the node was not polling
+                if let Some(since) = entry.running_since_sample {
+                    let hours = (now - since).num_milliseconds().max(0) as f64 / 3_600_000.0;
+                    *entry.monthly.entry(month.clone()).or_insert(0.0) += hours * instance.price_per_hour;
+                }
+                entry.running_since_sample = Some(now);
+                entry.price_per_hour = instance.price_per_hour;
+                entry.last_seen = now;
+            }
+        }
+        if let Err(e) = self.persist(&ledger) {
+            log::warn!("{}", e);
+        }
+        drop(ledger);
+
+        self.enforce_budget(&month).await;
+    }
+
+    /// Alert on crossing the warning and the cap, and destroy running
+    /// instances at the cap when auto-destroy is on
+    async fn enforce_budget(&self, month: &str) {
+        let gpu = self.config.read().await.gpu.clone();
+        let Some(budget) = gpu.monthly_budget.filter(|b| *b > 0.0) else {
+            return;
+        };
+        let summary = self.summary().await;
+        let level = if summary.spent >= budget {
+            2
+        } else if summary.spent >= budget * WARNING_RATIO {
+            1
+        } else {
+            0
+        };
+
+        let mut destroyed = Vec::new();
+        if level == 2 && gpu.auto_destroy {
+            for instance in summary.instances.iter().filter(|i| i.running) {
+                let result = match gpu_provider::provider(&instance.provider, &gpu.custom_providers) {
+                    Ok(provider) => provider.destroy(&instance.instance_id).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(()) => {
+                        log::warn!(
+                            "[GPU] Budget reached; destroyed {} instance {}",
+                            instance.provider,
+                            instance.instance_id
+                        );
+                        destroyed.push(instance.instance_id.clone());
+                    }
+                    Err(e) => log::error!("[GPU] Budget reached but failed to destroy {}: {}", instance.instance_id, e),
+                }
+            }
+        }
+
+        let mut ledger = self.ledger.write().await;
+        let sent = ledger.alerts.get(month).copied().unwrap_or(0);
+        if level <= sent && destroyed.is_empty() {
+            return;
+        }
+        ledger.alerts.insert(month.to_string(), level.max(sent));
+        if let Err(e) = self.persist(&ledger) {
+            log::warn!("{}", e);
+        }
+        drop(ledger);
+
+        log::warn!("[GPU] Spent ${:.2} of the ${:.2} monthly budget", summary.spent, budget);
+        let _ = self.events.send(NodeEvent::GpuBudgetAlert {
+            month: month.to_string(),
+            spent: summary.spent,
+            budget,
+            exceeded: level == 2,
+            destroyed,
+        });
+    }
+
+    /// Poll forever
+    pub async fn watch(self: Arc<Self>) {
+        loop {
+            self.sample().await;
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
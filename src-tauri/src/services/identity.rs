@@ -0,0 +1,220 @@
+//! Node Identity
+//!
+//! An Ed25519 keypair generated at first start. The secret key is kept in
+//! the OS keychain, or in a 0600 `identity.key` file under the config dir
+//! where no keychain is available. The public key is advertised in the
+//! node's capabilities so the orchestrator can check that heartbeats and
+//! results really come from the node it registered.
+//!
+//! Keys and signatures are hex-encoded. Signed messages are UTF-8 strings
+//! built by `heartbeat_message` and `result_message`. Jobs pushed by the
+//! orchestrator are signed the same way, over `job_message`, with the
+//! orchestrator's own key. Messages on IPFS pubsub are signed over
+//! `pubsub_message`: node announcements by the node, job offers by the
+//! orchestrator.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+use super::keychain;
+
+const KEYCHAIN_KEY: &str = "node-identity";
+
+fn key_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+        .join("identity.key")
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_secret(hex: &str) -> Result<SigningKey, String> {
+    let secret: [u8; 32] = from_hex(hex.trim())
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or("Invalid node identity key")?;
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+fn generate() -> Result<SigningKey, String> {
+    let mut secret = [0u8; 32];
+    getrandom::getrandom(&mut secret).map_err(|e| format!("Failed to generate node identity: {}", e))?;
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+/// Keep the secret in a file readable only by the user
+fn save_file(key: &SigningKey) -> Result<(), String> {
+    let path = key_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&path, to_hex(key.as_bytes()))
+        .map_err(|e| format!("Failed to save node identity: {}", e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+    }
+    Ok(())
+}
+
+/// The stored secret key (hex), without generating one; for backups
+pub fn stored_secret() -> Result<Option<String>, String> {
+    if let Ok(hex) = std::fs::read_to_string(key_path()) {
+        return Ok(Some(hex.trim().to_string()));
+    }
+    keychain::get(KEYCHAIN_KEY)
+}
+
+/// Replace the stored secret key with `hex`, e.g. one restored from a
+/// backup. Takes effect at the next start.
+pub fn restore_secret(hex: &str) -> Result<(), String> {
+    let key = parse_secret(hex)?;
+    if key_path().exists() {
+        return save_file(&key);
+    }
+    if let Err(e) = keychain::set(KEYCHAIN_KEY, &to_hex(key.as_bytes())) {
+        log::warn!("{}; keeping the node identity in a file instead", e);
+        save_file(&key)?;
+    }
+    Ok(())
+}
+
+/// Hex public key belonging to a hex secret key
+pub fn public_key_of(secret_hex: &str) -> Result<String, String> {
+    Ok(to_hex(parse_secret(secret_hex)?.verifying_key().as_bytes()))
+}
+
+pub struct NodeIdentity {
+    key: SigningKey,
+}
+
+impl NodeIdentity {
+    /// Load the node's keypair, generating and storing one on first start
+    pub fn load_or_generate() -> Result<Self, String> {
+        // A key written to the fallback file stays there
+        if let Ok(hex) = std::fs::read_to_string(key_path()) {
+            return Ok(Self { key: parse_secret(&hex)? });
+        }
+
+        match keychain::get(KEYCHAIN_KEY) {
+            Ok(Some(hex)) => return Ok(Self { key: parse_secret(&hex)? }),
+            Ok(None) => {}
+            Err(e) => log::warn!("{}", e),
+        }
+
+        let key = generate()?;
+        if let Err(e) = keychain::set(KEYCHAIN_KEY, &to_hex(key.as_bytes())) {
+            log::warn!("{}; keeping the node identity in a file instead", e);
+            save_file(&key)?;
+        }
+        log::info!("Generated node identity {}", to_hex(key.verifying_key().as_bytes()));
+        Ok(Self { key })
+    }
+
+    /// A keypair that lasts only as long as the process, for when none can
+    /// be stored
+    pub fn ephemeral() -> Self {
+        let key = generate().unwrap_or_else(|_| {
+            let seed: [u8; 32] = Sha256::digest(uuid::Uuid::new_v4().as_bytes()).into();
+            SigningKey::from_bytes(&seed)
+        });
+        Self { key }
+    }
+
+    /// Hex-encoded Ed25519 public key
+    pub fn public_key(&self) -> String {
+        to_hex(self.key.verifying_key().as_bytes())
+    }
+
+    /// Hex-encoded signature of `message`
+    pub fn sign(&self, message: &str) -> String {
+        to_hex(&self.key.sign(message.as_bytes()).to_bytes())
+    }
+}
+
+/// What a heartbeat signs: the node and the time it was sent (RFC 3339)
+pub fn heartbeat_message(node_id: &str, timestamp: &str) -> String {
+    format!("otherthing-heartbeat:{}:{}", node_id, timestamp)
+}
+
+/// What a result signs: the request it answers, its status and the SHA-256
+/// of its body
+pub fn result_message(request_id: &str, status: u16, body: &[u8]) -> String {
+    format!("otherthing-result:{}:{}:{}", request_id, status, to_hex(&Sha256::digest(body)))
+}
+
+/// What the orchestrator signs for a job: the request, when it was issued
+/// (RFC 3339) and the SHA-256 of its body
+pub fn job_message(request_id: &str, method: &str, path: &str, timestamp: &str, body: &[u8]) -> String {
+    format!(
+        "otherthing-job:{}:{}:{}:{}:{}",
+        request_id,
+        method.to_uppercase(),
+        path,
+        timestamp,
+        to_hex(&Sha256::digest(body))
+    )
+}
+
+/// What a pubsub message signs: its kind (`announcement` or `offer`) and
+/// the SHA-256 of its payload
+pub fn pubsub_message(kind: &str, payload: &[u8]) -> String {
+    format!("otherthing-{}:{}", kind, to_hex(&Sha256::digest(payload)))
+}
+
+/// Answer to a storage challenge: hex SHA-256 of the nonce followed by the
+/// challenged bytes
+pub fn possession_proof(nonce: &str, data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(nonce.as_bytes());
+    hasher.update(data);
+    to_hex(&hasher.finalize())
+}
+
+/// What a storage proof signs: the content, the challenged range and the
+/// answer
+pub fn storage_proof_message(cid: &str, nonce: &str, offset: u64, length: u64, proof: &str) -> String {
+    format!("otherthing-storage-proof:{}:{}:{}:{}:{}", cid, nonce, offset, length, proof)
+}
+
+/// Whether two secrets are equal, without the time taken depending on
+/// where they differ: their SHA-256 digests are compared in full
+pub fn secrets_match(a: &str, b: &str) -> bool {
+    let (a, b) = (Sha256::digest(a.as_bytes()), Sha256::digest(b.as_bytes()));
+    a.iter().zip(b.iter()).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Whether `key` is a hex-encoded Ed25519 public key
+pub fn is_public_key(key: &str) -> bool {
+    from_hex(key)
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .is_some_and(|bytes| VerifyingKey::from_bytes(&bytes).is_ok())
+}
+
+/// Check a hex signature of `message` against a hex public key
+pub fn verify(public_key: &str, message: &str, signature: &str) -> bool {
+    let key = from_hex(public_key)
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok());
+    let signature = from_hex(signature)
+        .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+        .map(|bytes| Signature::from_bytes(&bytes));
+    match (key, signature) {
+        (Some(key), Some(signature)) => key.verify(message.as_bytes(), &signature).is_ok(),
+        _ => false,
+    }
+}
@@ -0,0 +1,219 @@
+//! Idle Detection
+//!
+//! With `limits.onlyWhenIdle` set, the node only takes work from the
+//! orchestrator while the contributor is away: no keyboard or mouse input
+//! for `limits.idleAfterSecs`, and CPU load under `limits.busyCpuPercent`.
+//! Input is read from the OS (`GetLastInputInfo` on Windows, `HIDIdleTime`
+//! on macOS, Mutter or `xprintidle` on Linux); where it can't be read, CPU
+//! load decides alone.
+//!
+//! Where input can be read, CPU load only gates becoming available: once
+//! the node is working its own jobs load the CPU, so only input takes it
+//! back out. When activity is detected the node drains: it stops
+//! advertising availability and refuses new jobs, and the jobs already
+//! running finish.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use sysinfo::System;
+use tokio::sync::{broadcast, RwLock};
+
+use crate::config::NodeConfig;
+use crate::models::NodeEvent;
+
+/// How often input and CPU load are sampled
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Whether the node is taking work, and why
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Availability {
+    /// Gating on idleness is switched on
+    pub only_when_idle: bool,
+    pub available: bool,
+    /// Why the node is not taking work
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Seconds since the last keyboard or mouse input, where the OS tells
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_idle_secs: Option<u64>,
+    pub cpu_percent: f32,
+    /// When `available` last changed
+    pub since: String,
+}
+
+impl Default for Availability {
+    fn default() -> Self {
+        Self {
+            only_when_idle: false,
+            available: true,
+            reason: None,
+            input_idle_secs: None,
+            cpu_percent: 0.0,
+            since: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Decides whether the node may take work
+pub struct IdleMonitor {
+    config: Arc<RwLock<NodeConfig>>,
+    events: broadcast::Sender<NodeEvent>,
+    system: Mutex<System>,
+    status: RwLock<Availability>,
+}
+
+impl IdleMonitor {
+    pub fn new(config: Arc<RwLock<NodeConfig>>, events: broadcast::Sender<NodeEvent>) -> Self {
+        let mut system = System::new();
+        // Usage is measured between refreshes; this one starts the first interval
+        system.refresh_cpu_usage();
+        Self {
+            config,
+            events,
+            system: Mutex::new(system),
+            status: RwLock::new(Availability::default()),
+        }
+    }
+
+    pub async fn status(&self) -> Availability {
+        self.status.read().await.clone()
+    }
+
+    /// Whether the node should advertise itself and accept new jobs
+    pub async fn is_available(&self) -> bool {
+        self.status.read().await.available
+    }
+
+    fn cpu_percent(&self) -> f32 {
+        let mut system = self.system.lock().unwrap();
+        system.refresh_cpu_usage();
+        system.global_cpu_usage()
+    }
+
+    /// Take a sample and update availability, announcing a change
+    pub async fn sample(&self) -> Availability {
+        let limits = self.config.read().await.limits.clone();
+        let input_idle = tokio::task::spawn_blocking(input_idle_time).await.ok().flatten();
+        let cpu_percent = self.cpu_percent();
+        let input_idle_secs = input_idle.map(|d| d.as_secs());
+
+        let mut status = self.status.write().await;
+        let reason = if !limits.only_when_idle {
+            None
+        } else if let Some(secs) = input_idle_secs.filter(|secs| *secs < limits.idle_after_secs) {
+            Some(format!("Machine in use (input {}s ago)", secs))
+        } else if (!status.available || input_idle_secs.is_none()) && cpu_percent > limits.busy_cpu_percent {
+            Some(format!("CPU busy ({:.0}%)", cpu_percent))
+        } else {
+            None
+        };
+        let available = reason.is_none();
+
+        if available != status.available {
+            status.since = chrono::Utc::now().to_rfc3339();
+            match &reason {
+                Some(reason) => log::info!("Draining: {}", reason),
+                None => log::info!("Machine idle; taking jobs again"),
+            }
+            let _ = self.events.send(NodeEvent::AvailabilityChanged {
+                available,
+                reason: reason.clone(),
+            });
+        }
+        status.only_when_idle = limits.only_when_idle;
+        status.available = available;
+        status.reason = reason;
+        status.input_idle_secs = input_idle_secs;
+        status.cpu_percent = cpu_percent;
+        status.clone()
+    }
+
+    /// Sample in the background for the life of the app
+    pub async fn watch(self: Arc<Self>) {
+        loop {
+            self.sample().await;
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Time since the last keyboard or mouse input, if the OS tells
+#[cfg(target_os = "windows")]
+fn input_idle_time() -> Option<Duration> {
+    use windows_sys::Win32::System::SystemInformation::GetTickCount;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    // SAFETY: `info` is a valid LASTINPUTINFO with its size set
+    if unsafe { GetLastInputInfo(&mut info) } == 0 {
+        return None;
+    }
+    // Both are milliseconds since boot and wrap together
+    let now = unsafe { GetTickCount() };
+    Some(Duration::from_millis(now.wrapping_sub(info.dwTime) as u64))
+}
+
+#[cfg(target_os = "macos")]
+fn input_idle_time() -> Option<Duration> {
+    let output = std::process::Command::new("ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.contains("\"HIDIdleTime\""))
+        .and_then(|line| line.rsplit('=').next())
+        .and_then(|nanos| nanos.trim().parse::<u64>().ok())
+        .map(Duration::from_nanos)
+}
+
+#[cfg(target_os = "linux")]
+fn input_idle_time() -> Option<Duration> {
+    use std::process::Command;
+
+    // GNOME on Wayland or X11
+    let mutter = Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--dest",
+            "org.gnome.Mutter.IdleMonitor",
+            "--object-path",
+            "/org/gnome/Mutter/IdleMonitor/Core",
+            "--method",
+            "org.gnome.Mutter.IdleMonitor.GetIdletime",
+        ])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        // "(uint64 12345,)"
+        .and_then(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .trim()
+                .trim_start_matches("(uint64 ")
+                .trim_end_matches(",)")
+                .parse::<u64>()
+                .ok()
+        });
+    if let Some(millis) = mutter {
+        return Some(Duration::from_millis(millis));
+    }
+
+    // Other X11 sessions
+    Command::new("xprintidle")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8_lossy(&output.stdout).trim().parse::<u64>().ok())
+        .map(Duration::from_millis)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn input_idle_time() -> Option<Duration> {
+    None
+}
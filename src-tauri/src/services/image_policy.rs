@@ -0,0 +1,139 @@
+//! Container Image Policy
+//!
+//! Which images the node agrees to pull and run. References are
+//! normalized the way Docker resolves them (`python:3.12` is
+//! `docker.io/library/python:3.12`), and patterns are matched against both
+//! the reference as written and its normalized form, with `*` matching any
+//! run of characters. Denied patterns win over allowed ones; empty allow
+//! lists allow everything.
+
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_REGISTRY: &str = "docker.io";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImagePolicy {
+    /// Registries images may come from, e.g. `ghcr.io` or `docker.io`
+    #[serde(default)]
+    pub allowed_registries: Vec<String>,
+    /// Images that may run, e.g. `python:*` or `ghcr.io/acme/*`
+    #[serde(default)]
+    pub allowed_images: Vec<String>,
+    /// Images that never run, even when otherwise allowed
+    #[serde(default)]
+    pub denied_images: Vec<String>,
+    /// Only accept references pinned by digest (`image@sha256:...`)
+    #[serde(default)]
+    pub require_digest: bool,
+}
+
+/// An image reference split into its parts
+#[derive(Debug, Clone, PartialEq)]
+struct ImageRef {
+    registry: String,
+    repository: String,
+    /// `:tag` or `@sha256:...`, with its separator
+    suffix: String,
+}
+
+impl ImageRef {
+    fn parse(reference: &str) -> Self {
+        let (name, suffix) = match reference.find('@') {
+            Some(at) => (&reference[..at], reference[at..].to_string()),
+            None => match reference.rfind(':') {
+                // A colon after the last slash is a tag, not a registry port
+                Some(colon) if !reference[colon..].contains('/') => {
+                    (&reference[..colon], reference[colon..].to_string())
+                }
+                _ => (reference, ":latest".to_string()),
+            },
+        };
+
+        let (registry, repository) = match name.split_once('/') {
+            Some((first, rest)) if first.contains('.') || first.contains(':') || first == "localhost" => {
+                (first.to_lowercase(), rest.to_string())
+            }
+            _ => (DEFAULT_REGISTRY.to_string(), name.to_string()),
+        };
+        let repository = if registry == DEFAULT_REGISTRY && !repository.contains('/') {
+            format!("library/{}", repository)
+        } else {
+            repository
+        };
+
+        Self { registry, repository, suffix }
+    }
+
+    fn normalized(&self) -> String {
+        format!("{}/{}{}", self.registry, self.repository, self.suffix)
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.suffix.starts_with("@sha256:")
+    }
+}
+
+/// Match `value` against a pattern where `*` stands for any characters
+fn glob_match(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*` at all
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+impl ImagePolicy {
+    /// Whether the policy restricts anything at all
+    pub fn is_empty(&self) -> bool {
+        self.allowed_registries.is_empty()
+            && self.allowed_images.is_empty()
+            && self.denied_images.is_empty()
+            && !self.require_digest
+    }
+
+    /// Check an image reference, explaining which rule it breaks
+    pub fn check(&self, image: &str) -> Result<(), String> {
+        let image = image.trim();
+        if image.is_empty() {
+            return Err("Image is required".to_string());
+        }
+        let parsed = ImageRef::parse(image);
+        let normalized = parsed.normalized();
+        let matches = |pattern: &String| glob_match(pattern, image) || glob_match(pattern, &normalized);
+
+        if let Some(pattern) = self.denied_images.iter().find(|p| matches(p)) {
+            return Err(format!("Image {} is denied by the image policy ({})", image, pattern));
+        }
+        if self.require_digest && !parsed.is_pinned() {
+            return Err(format!("Image {} must be pinned by digest (image@sha256:...)", image));
+        }
+        if !self.allowed_registries.is_empty()
+            && !self
+                .allowed_registries
+                .iter()
+                .any(|r| r.trim_end_matches('/').eq_ignore_ascii_case(&parsed.registry))
+        {
+            return Err(format!(
+                "Registry {} of image {} is not allowed by the image policy",
+                parsed.registry, image
+            ));
+        }
+        if !self.allowed_images.is_empty() && !self.allowed_images.iter().any(matches) {
+            return Err(format!("Image {} is not allowed by the image policy", image));
+        }
+        Ok(())
+    }
+}
@@ -1,34 +1,115 @@
-use crate::models::{IpfsStats, IpfsStatus};
-use std::path::PathBuf;
+use crate::config::IpfsConfig;
+use crate::error::NodeError;
+use crate::models::{
+    GcResult, IpfsProgress, IpfsStats, IpfsStatus, IpnsKey, IpnsRecord, KuboVersionInfo, PinInfo,
+    ResourceLimits,
+};
+use crate::pagination::{ListQuery, Page};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use super::download;
+use super::resources;
+#[cfg(feature = "embedded-ipfs")]
+use super::embedded_ipfs::EmbeddedIpfs;
+#[cfg(feature = "embedded-ipfs")]
+use std::sync::Arc;
+
+/// Largest content `cat` returns unless a caller asks for more
+pub const MAX_CAT_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Largest content `get` writes to disk unless a caller asks for more
+pub const MAX_GET_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Kubo release downloaded when no binary is installed
+pub const KUBO_VERSION: &str = "v0.32.1";
+
+/// Kubo releases the app works with: from `MIN_KUBO_VERSION` up to, but
+/// not including, `MAX_KUBO_VERSION`
+pub const MIN_KUBO_VERSION: &str = "0.28.0";
+pub const MAX_KUBO_VERSION: &str = "0.40.0";
+
+/// How often the repo size is checked against the budget
+const BUDGET_CHECK_INTERVAL: Duration = Duration::from_secs(600);
 
 pub struct IpfsManager {
     process: Mutex<Option<Child>>,
     binary_path: Mutex<Option<PathBuf>>,
     repo_path: Mutex<Option<PathBuf>>,
+    config: Mutex<IpfsConfig>,
+    /// CPU, memory and storage the daemon is held to
+    resource_limits: Mutex<ResourceLimits>,
+    /// Serializes GC runs
+    gc_lock: tokio::sync::Mutex<()>,
+    /// Pins kept for others (contributed storage), never unpinned to meet the budget
+    held: Mutex<HashSet<String>>,
+    /// In-process store, when running embedded instead of Kubo
+    #[cfg(feature = "embedded-ipfs")]
+    embedded: Mutex<Option<Arc<EmbeddedIpfs>>>,
 }
 
 impl IpfsManager {
     pub fn new() -> Self {
+        Self::with_config(IpfsConfig::default())
+    }
+
+    pub fn with_config(config: IpfsConfig) -> Self {
         Self {
             process: Mutex::new(None),
             binary_path: Mutex::new(None),
             repo_path: Mutex::new(None),
+            config: Mutex::new(config),
+            resource_limits: Mutex::new(ResourceLimits::default()),
+            gc_lock: tokio::sync::Mutex::new(()),
+            held: Mutex::new(HashSet::new()),
+            #[cfg(feature = "embedded-ipfs")]
+            embedded: Mutex::new(None),
         }
     }
 
+    #[cfg(feature = "embedded-ipfs")]
+    fn embedded(&self) -> Option<Arc<EmbeddedIpfs>> {
+        self.embedded.lock().unwrap().clone()
+    }
+
+    pub fn config(&self) -> IpfsConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Limits for the daemon: CPU and memory apply when it next starts,
+    /// storage on the next repo budget check
+    pub fn set_resource_limits(&self, limits: ResourceLimits) {
+        *self.resource_limits.lock().unwrap() = limits;
+    }
+
+    /// Replace the settings, keeping the repo where it is (see
+    /// `relocate_repo`). Ports and network settings apply on the next start;
+    /// returns whether a restart is needed for that.
+    pub fn set_config(&self, mut config: IpfsConfig) -> bool {
+        let mut current = self.config.lock().unwrap();
+        config.repo_path = current.repo_path.clone();
+        let needs_restart = self.process.lock().unwrap().is_some()
+            && (current.api_port != config.api_port
+                || current.gateway_port != config.gateway_port
+                || current.swarm_key != config.swarm_key
+                || current.limits != config.limits
+                || current.bootstrap_peers != config.bootstrap_peers
+                || current.pubsub.enabled != config.pubsub.enabled);
+        *current = config;
+        needs_restart
+    }
+
     pub fn get_ipfs_path(&self) -> PathBuf {
         if let Some(path) = self.binary_path.lock().unwrap().as_ref() {
             return path.clone();
         }
 
         // Check multiple locations
-        let config_dir = dirs::config_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("otherthing-node")
-            .join("ipfs")
-            .join("kubo");
+        let config_dir = Self::managed_dir().join("kubo");
 
         #[cfg(target_os = "windows")]
         let binary_name = "ipfs.exe";
@@ -71,11 +152,221 @@ impl IpfsManager {
         downloaded_path
     }
 
+    /// Directory Kubo is downloaded into
+    fn managed_dir() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("otherthing-node")
+            .join("ipfs")
+    }
+
+    /// Download and unpack the default Kubo release for this platform,
+    /// reporting download progress. Returns the binary path.
+    pub async fn download_binary(
+        &self,
+        progress_tx: Option<mpsc::Sender<IpfsProgress>>,
+    ) -> Result<PathBuf, String> {
+        self.download_version(KUBO_VERSION, progress_tx).await
+    }
+
+    async fn download_version(
+        &self,
+        version: &str,
+        progress_tx: Option<mpsc::Sender<IpfsProgress>>,
+    ) -> Result<PathBuf, String> {
+        let dir = Self::managed_dir();
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+        #[cfg(target_os = "windows")]
+        let (os, arch, archive_ext, bin_ext) = (
+            "windows",
+            if cfg!(target_arch = "x86_64") { "amd64" } else { "386" },
+            "zip",
+            ".exe"
+        );
+
+        #[cfg(target_os = "macos")]
+        let (os, arch, archive_ext, bin_ext) = (
+            "darwin",
+            if cfg!(target_arch = "aarch64") { "arm64" } else { "amd64" },
+            "tar.gz",
+            ""
+        );
+
+        #[cfg(target_os = "linux")]
+        let (os, arch, archive_ext, bin_ext) = (
+            "linux",
+            if cfg!(target_arch = "x86_64") { "amd64" } else { "arm64" },
+            "tar.gz",
+            ""
+        );
+
+        // e.g. kubo_v0.32.1_windows-amd64.zip
+        let filename = format!("kubo_{}_{}-{}.{}", version, os, arch, archive_ext);
+        let url = format!("https://dist.ipfs.tech/kubo/{}/{}", version, filename);
+        log::info!("Downloading IPFS from: {}", url);
+
+        let bytes = download::fetch_with_progress(&url, |received, total| {
+            if let Some(tx) = &progress_tx {
+                let _ = tx.try_send(IpfsProgress {
+                    operation: "download".to_string(),
+                    target: filename.clone(),
+                    current_file: None,
+                    bytes: Some(received),
+                    total,
+                    blocks: None,
+                    percent: total
+                        .filter(|&t| t > 0)
+                        .map(|t| received as f64 / t as f64 * 100.0),
+                });
+            }
+        })
+        .await?;
+
+        let archive_path = dir.join(&filename);
+        std::fs::write(&archive_path, &bytes)
+            .map_err(|e| format!("Failed to write archive: {}", e))?;
+        let extracted = download::extract_archive(&archive_path, &dir);
+        let _ = std::fs::remove_file(&archive_path);
+        extracted?;
+
+        // The binary is in kubo/ipfs
+        let binary_path = dir.join("kubo").join(format!("ipfs{}", bin_ext));
+        if !binary_path.exists() {
+            return Err(format!("IPFS binary not found at {:?} after extraction", binary_path));
+        }
+        download::make_executable(&binary_path)?;
+
+        log::info!("IPFS binary extracted to: {:?}", binary_path);
+        Ok(binary_path)
+    }
+
+    /// Version of the Kubo binary in use, e.g. `0.32.1`
+    pub fn installed_version(&self) -> Option<String> {
+        let path = self.get_ipfs_path();
+        if !path.exists() {
+            return None;
+        }
+        let output = Command::new(path)
+            .args(["version", "--number"])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (!version.is_empty()).then_some(version)
+    }
+
+    fn is_managed_binary(&self) -> bool {
+        self.get_ipfs_path().starts_with(Self::managed_dir())
+    }
+
+    /// Newest stable Kubo release within the supported range
+    pub async fn latest_supported_version() -> Result<String, String> {
+        let listing = download::fetch("https://dist.ipfs.tech/kubo/versions").await?;
+        let min = parse_version(MIN_KUBO_VERSION);
+        let max = parse_version(MAX_KUBO_VERSION);
+
+        String::from_utf8_lossy(&listing)
+            .lines()
+            .map(str::trim)
+            .filter(|v| !v.contains('-'))
+            .filter_map(|v| Some((parse_version(v)?, v.to_string())))
+            .filter(|(parsed, _)| Some(*parsed) >= min && Some(*parsed) < max)
+            .max()
+            .map(|(_, v)| v.trim_start_matches('v').to_string())
+            .ok_or_else(|| "No supported Kubo release found".to_string())
+    }
+
+    pub async fn version_info(&self) -> KuboVersionInfo {
+        let installed = self.installed_version();
+        let latest = Self::latest_supported_version().await.ok();
+        let supported = installed.as_deref().and_then(parse_version).is_some_and(|v| {
+            Some(v) >= parse_version(MIN_KUBO_VERSION) && Some(v) < parse_version(MAX_KUBO_VERSION)
+        });
+        let managed = self.is_managed_binary();
+        let upgrade_available = managed
+            && match (installed.as_deref().and_then(parse_version), latest.as_deref().and_then(parse_version)) {
+                (Some(current), Some(latest)) => latest > current,
+                _ => false,
+            };
+
+        KuboVersionInfo {
+            installed,
+            min_supported: MIN_KUBO_VERSION.to_string(),
+            max_supported: MAX_KUBO_VERSION.to_string(),
+            supported,
+            latest,
+            upgrade_available,
+            managed,
+        }
+    }
+
+    /// Replace the app-installed Kubo with `version` (the newest supported
+    /// release by default), migrate the repo and restart the daemon if it
+    /// was running. Returns the installed version.
+    pub async fn upgrade(
+        &self,
+        version: Option<&str>,
+        progress_tx: Option<mpsc::Sender<IpfsProgress>>,
+    ) -> Result<String, String> {
+        if self.has_binary() && !self.is_managed_binary() {
+            return Err(format!(
+                "IPFS at {} was not installed by the app; upgrade it with its package manager",
+                self.get_ipfs_path().display()
+            ));
+        }
+
+        let version = match version {
+            Some(v) => {
+                let parsed = parse_version(v).ok_or_else(|| format!("Invalid version: {}", v))?;
+                if Some(parsed) < parse_version(MIN_KUBO_VERSION) || Some(parsed) >= parse_version(MAX_KUBO_VERSION) {
+                    return Err(format!(
+                        "Kubo {} is outside the supported range {} - {}",
+                        v, MIN_KUBO_VERSION, MAX_KUBO_VERSION
+                    ));
+                }
+                v.trim_start_matches('v').to_string()
+            }
+            None => Self::latest_supported_version().await?,
+        };
+
+        if self.installed_version().as_deref() == Some(version.as_str()) {
+            return Ok(version);
+        }
+
+        let was_running = self.process.lock().unwrap().is_some();
+        if was_running {
+            self.stop().await?;
+        }
+
+        log::info!("Upgrading Kubo to {}", version);
+        let path = self.download_version(&format!("v{}", version), progress_tx).await?;
+
+        // Newer releases may need a newer repo layout
+        let repo_path = self.get_repo_path();
+        if repo_path.join("config").exists() {
+            log::info!("Migrating IPFS repo at {:?}", repo_path);
+            self.run_ipfs(&path, &repo_path, &["repo", "migrate"])
+                .map_err(|e| format!("Repo migration failed: {}", e))?;
+        }
+
+        if was_running {
+            self.start().await?;
+        }
+        Ok(version)
+    }
+
     pub fn has_binary(&self) -> bool {
         self.get_ipfs_path().exists()
     }
 
     pub fn is_running(&self) -> bool {
+        #[cfg(feature = "embedded-ipfs")]
+        if self.embedded().is_some() {
+            return true;
+        }
+
         if let Ok(mut guard) = self.process.lock() {
             if let Some(ref mut child) = *guard {
                 match child.try_wait() {
@@ -89,77 +380,129 @@ impl IpfsManager {
         }
 
         // Check API
-        Self::check_api_running()
+        self.check_api_running()
     }
 
-    fn check_api_running() -> bool {
-        std::thread::spawn(|| {
-            reqwest::blocking::get("http://localhost:5001/api/v0/id").is_ok()
+    fn check_api_running(&self) -> bool {
+        let url = format!("{}/id", self.api_url());
+        std::thread::spawn(move || {
+            reqwest::blocking::get(url).is_ok()
         })
         .join()
         .unwrap_or(false)
     }
 
-    pub async fn start(&self) -> Result<(), String> {
+    /// Base URL of the RPC API
+    pub fn api_url(&self) -> String {
+        self.config.lock().unwrap().api_url()
+    }
+
+    /// Run `ipfs` against the repo, ignoring output
+    fn run_ipfs(&self, path: &Path, repo_path: &Path, args: &[&str]) -> Result<(), String> {
+        let status = Command::new(path)
+            .args(args)
+            .env("IPFS_PATH", repo_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| format!("Failed to run ipfs {}: {}", args.join(" "), e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("ipfs {} failed", args.join(" ")))
+        }
+    }
+
+    pub async fn start(&self) -> Result<(), NodeError> {
+        self.start_with_progress(None).await
+    }
+
+    /// Start the daemon, first downloading Kubo if it is not installed
+    pub async fn start_with_progress(
+        &self,
+        progress_tx: Option<mpsc::Sender<IpfsProgress>>,
+    ) -> Result<(), NodeError> {
         if self.is_running() {
             return Ok(());
         }
 
-        let path = self.get_ipfs_path();
+        if self.config().embedded {
+            #[cfg(feature = "embedded-ipfs")]
+            {
+                let root = self
+                    .get_repo_path()
+                    .parent()
+                    .map(|p| p.join("embedded"))
+                    .unwrap_or_else(|| PathBuf::from("embedded"));
+                let store = EmbeddedIpfs::open(&root)?;
+                *self.embedded.lock().unwrap() = Some(Arc::new(store));
+                return Ok(());
+            }
+            #[cfg(not(feature = "embedded-ipfs"))]
+            log::warn!("Embedded IPFS requested but not compiled in, using Kubo");
+        }
+
+        let mut path = self.get_ipfs_path();
         if !path.exists() {
-            return Err("IPFS binary not found. Please download it first.".to_string());
+            log::info!("IPFS binary not found, downloading Kubo {}", KUBO_VERSION);
+            path = self.download_binary(progress_tx).await?;
         }
 
         // Initialize IPFS repo if needed
+        let config = self.config();
         let repo_path = self.get_repo_path();
         if !repo_path.join("config").exists() {
             log::info!("Initializing IPFS repo at {:?}", repo_path);
-            let status = Command::new(&path)
-                .arg("init")
-                .env("IPFS_PATH", &repo_path)
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .status()
-                .map_err(|e| format!("Failed to init IPFS: {}", e))?;
-
-            if !status.success() {
-                return Err("IPFS init failed".to_string());
+            std::fs::create_dir_all(&repo_path)
+                .map_err(|e| format!("Failed to create IPFS repo dir: {}", e))?;
+            let mut args = vec!["init"];
+            if let Some(profile) = config.profile.as_deref().filter(|p| !p.is_empty()) {
+                args.extend(["--profile", profile]);
             }
-
-            // Configure gateway to use port 8088 instead of 8080 to avoid conflict
-            log::info!("Configuring IPFS gateway port to 8088");
-            let _ = Command::new(&path)
-                .args(["config", "Addresses.Gateway", "/ip4/127.0.0.1/tcp/8088"])
-                .env("IPFS_PATH", &repo_path)
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .status();
+            self.run_ipfs(&path, &repo_path, &args)
+                .map_err(|_| "IPFS init failed".to_string())?;
 
             // Disable gateway redirect (optional, for security)
-            let _ = Command::new(&path)
-                .args(["config", "--json", "Gateway.NoFetch", "true"])
-                .env("IPFS_PATH", &repo_path)
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .status();
+            let _ = self.run_ipfs(&path, &repo_path, &["config", "--json", "Gateway.NoFetch", "true"]);
         }
 
+        // Applied on every start so port changes reach existing repos
+        log::info!("IPFS API on port {}, gateway on port {}", config.api_port, config.gateway_port);
+        let api_addr = format!("/ip4/127.0.0.1/tcp/{}", config.api_port);
+        let gateway_addr = format!("/ip4/127.0.0.1/tcp/{}", config.gateway_port);
+        self.run_ipfs(&path, &repo_path, &["config", "Addresses.API", &api_addr])?;
+        self.run_ipfs(&path, &repo_path, &["config", "Addresses.Gateway", &gateway_addr])?;
+
+        let private = self.apply_swarm_key(&path, &repo_path, &config)?;
+        self.apply_limits(&path, &repo_path, &config)?;
+
         log::info!("Starting IPFS daemon");
-        let child = Command::new(&path)
-            .arg("daemon")
+        let mut cmd = resources::command(&path, &self.resource_limits.lock().unwrap());
+        cmd.arg("daemon")
             .arg("--enable-gc")
-            .env("IPFS_PATH", &repo_path)
+            .env("IPFS_PATH", &repo_path);
+        if config.pubsub.enabled {
+            cmd.arg("--enable-pubsub-experiment");
+        }
+        if private {
+            // Refuse to start rather than silently join the public network
+            cmd.env("LIBP2P_FORCE_PNET", "1");
+        }
+        let child = cmd
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .spawn()
-            .map_err(|e| format!("Failed to start IPFS: {}", e))?;
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => NodeError::NotInstalled(format!("IPFS not found at {}", path.display())),
+                _ => NodeError::Internal(format!("Failed to start IPFS: {}", e)),
+            })?;
 
         *self.process.lock().unwrap() = Some(child);
 
         // Wait for API
         for i in 0..30 {
             tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-            if Self::check_api_running() {
+            if self.check_api_running() {
                 log::info!("IPFS daemon started successfully");
                 return Ok(());
             }
@@ -168,22 +511,28 @@ impl IpfsManager {
             }
         }
 
-        Err("IPFS started but API not responding after 15 seconds".to_string())
+        Err(NodeError::Timeout("IPFS started but API not responding after 15 seconds".to_string()))
     }
 
-    pub async fn stop(&self) -> Result<(), String> {
+    pub async fn stop(&self) -> Result<(), NodeError> {
+        #[cfg(feature = "embedded-ipfs")]
+        self.embedded.lock().unwrap().take();
+
         if let Ok(mut guard) = self.process.lock() {
             if let Some(mut child) = guard.take() {
-                child.kill().map_err(|e| format!("Failed to stop IPFS: {}", e))?;
+                child.kill().map_err(|e| NodeError::Internal(format!("Failed to stop IPFS: {}", e)))?;
             }
         }
         Ok(())
     }
 
-    fn get_repo_path(&self) -> PathBuf {
+    pub fn get_repo_path(&self) -> PathBuf {
         if let Some(path) = self.repo_path.lock().unwrap().as_ref() {
             return path.clone();
         }
+        if let Some(path) = self.config.lock().unwrap().repo_path.as_ref() {
+            return PathBuf::from(path);
+        }
 
         #[cfg(target_os = "windows")]
         {
@@ -199,8 +548,115 @@ impl IpfsManager {
         }
     }
 
+    /// Write or remove `swarm.key` to match the config and set the
+    /// bootstrap list accordingly. Returns whether the network is private.
+    fn apply_swarm_key(&self, path: &Path, repo_path: &Path, config: &IpfsConfig) -> Result<bool, String> {
+        let key_path = repo_path.join("swarm.key");
+
+        let Some(key) = config.swarm_key.as_deref() else {
+            if key_path.exists() {
+                log::info!("Leaving private IPFS network, restoring public bootstrap peers");
+                std::fs::remove_file(&key_path)
+                    .map_err(|e| format!("Failed to remove swarm key: {}", e))?;
+                self.run_ipfs(path, repo_path, &["bootstrap", "rm", "--all"])?;
+                self.run_ipfs(path, repo_path, &["bootstrap", "add", "--default"])?;
+            }
+            return Ok(false);
+        };
+
+        let key = parse_swarm_key(key)?;
+        let contents = format!("/key/swarm/psk/1.0.0/\n/base16/\n{}\n", key);
+        let unchanged = std::fs::read_to_string(&key_path).is_ok_and(|c| c == contents);
+        if !unchanged {
+            log::info!("Joining private IPFS network");
+            std::fs::write(&key_path, contents)
+                .map_err(|e| format!("Failed to write swarm key: {}", e))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let _ = std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o600));
+            }
+        }
+
+        // Public bootstrap peers are unreachable with a swarm key
+        self.run_ipfs(path, repo_path, &["bootstrap", "rm", "--all"])?;
+        for peer in &config.bootstrap_peers {
+            self.run_ipfs(path, repo_path, &["bootstrap", "add", peer])
+                .map_err(|_| format!("Invalid bootstrap peer: {}", peer))?;
+        }
+        if config.bootstrap_peers.is_empty() {
+            log::warn!("Private IPFS network has no bootstrap peers configured");
+        }
+        Ok(true)
+    }
+
+    /// Write the connection/resource limits into the repo config; `null`
+    /// restores Kubo's default for anything unset
+    fn apply_limits(&self, path: &Path, repo_path: &Path, config: &IpfsConfig) -> Result<(), String> {
+        let limits = &config.limits;
+        let json = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+
+        let settings = [
+            ("Swarm.ConnMgr.HighWater", json(limits.conn_high_water.map(|v| v.to_string()))),
+            ("Swarm.ConnMgr.LowWater", json(limits.conn_low_water.map(|v| v.to_string()))),
+            ("Swarm.ResourceMgr.MaxMemory", json(limits.max_memory.as_ref().map(|v| format!("\"{}\"", v)))),
+            ("Swarm.ResourceMgr.MaxFileDescriptors", json(limits.max_file_descriptors.map(|v| v.to_string()))),
+            ("Routing.Type", json(limits.dht_client_only.then(|| "\"dhtclient\"".to_string()))),
+        ];
+        for (key, value) in &settings {
+            self.run_ipfs(path, repo_path, &["config", "--json", key, value])
+                .map_err(|_| format!("Invalid IPFS setting {} = {}", key, value))?;
+        }
+        Ok(())
+    }
+
+    /// Move the repo to `dest` (e.g. onto a larger drive), restarting the
+    /// daemon around the move if it is running. The caller persists the
+    /// new `repo_path`.
+    pub async fn relocate_repo(&self, dest: &Path) -> Result<PathBuf, NodeError> {
+        let current = self.get_repo_path();
+        if dest == current {
+            return Ok(current);
+        }
+        if dest.join("config").exists() {
+            return Err(NodeError::Conflict(format!("{} already contains an IPFS repo", dest.display())));
+        }
+        if dest.starts_with(&current) {
+            return Err(NodeError::Invalid("Cannot move the repo into itself".to_string()));
+        }
+
+        let was_running = self.process.lock().unwrap().is_some();
+        if was_running {
+            self.stop().await?;
+        }
+
+        if current.exists() {
+            log::info!("Moving IPFS repo from {:?} to {:?}", current, dest);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            let _ = std::fs::remove_dir(dest);
+            if std::fs::rename(&current, dest).is_err() {
+                // Different filesystem: copy, then drop the original
+                copy_dir(&current, dest)
+                    .map_err(|e| format!("Failed to move IPFS repo: {}", e))?;
+                std::fs::remove_dir_all(&current)
+                    .map_err(|e| format!("Repo copied but old copy not removed: {}", e))?;
+            }
+        }
+
+        *self.repo_path.lock().unwrap() = None;
+        self.config.lock().unwrap().repo_path = Some(dest.to_string_lossy().into_owned());
+
+        if was_running {
+            self.start().await?;
+        }
+        Ok(dest.to_path_buf())
+    }
+
     pub async fn get_status(&self) -> IpfsStatus {
-        let has_binary = self.has_binary();
+        let has_binary =
+            self.has_binary() || (cfg!(feature = "embedded-ipfs") && self.config().embedded);
         let running = self.is_running();
         let peer_id = if running {
             self.get_peer_id().await.ok()
@@ -213,54 +669,66 @@ impl IpfsManager {
             None
         };
 
-        IpfsStatus { running, has_binary, peer_id, stats }
+        let private_network = self.get_repo_path().join("swarm.key").exists();
+
+        IpfsStatus { running, has_binary, peer_id, stats, private_network }
     }
 
-    pub async fn get_peer_id(&self) -> Result<String, String> {
+    pub async fn get_peer_id(&self) -> Result<String, NodeError> {
+        #[cfg(feature = "embedded-ipfs")]
+        if let Some(node) = self.embedded() {
+            return Ok(node.peer_id());
+        }
+
         let client = reqwest::Client::new();
         let response = client
-            .post("http://localhost:5001/api/v0/id")
+            .post(format!("{}/id", self.api_url()))
             .send()
             .await
-            .map_err(|e| format!("Failed to get peer ID: {}", e))?;
+            .map_err(|e| NodeError::request("Failed to get peer ID", e))?;
 
         let data: serde_json::Value = response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| NodeError::Upstream(format!("Failed to parse response: {}", e)))?;
 
         data["ID"]
             .as_str()
             .map(|s| s.to_string())
-            .ok_or_else(|| "No peer ID in response".to_string())
+            .ok_or_else(|| NodeError::Upstream("No peer ID in response".to_string()))
     }
 
-    pub async fn get_stats(&self) -> Result<IpfsStats, String> {
+    pub async fn get_stats(&self) -> Result<IpfsStats, NodeError> {
+        #[cfg(feature = "embedded-ipfs")]
+        if let Some(node) = self.embedded() {
+            return Ok(node.stats());
+        }
+
         let client = reqwest::Client::new();
 
         // Get repo stats
         let repo_response = client
-            .post("http://localhost:5001/api/v0/repo/stat")
+            .post(format!("{}/repo/stat", self.api_url()))
             .send()
             .await
-            .map_err(|e| format!("Failed to get repo stats: {}", e))?;
+            .map_err(|e| NodeError::request("Failed to get repo stats", e))?;
 
         let repo_data: serde_json::Value = repo_response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse repo stats: {}", e))?;
+            .map_err(|e| NodeError::Upstream(format!("Failed to parse repo stats: {}", e)))?;
 
         // Get swarm peers
         let peers_response = client
-            .post("http://localhost:5001/api/v0/swarm/peers")
+            .post(format!("{}/swarm/peers", self.api_url()))
             .send()
             .await
-            .map_err(|e| format!("Failed to get peers: {}", e))?;
+            .map_err(|e| NodeError::request("Failed to get peers", e))?;
 
         let peers_data: serde_json::Value = peers_response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse peers: {}", e))?;
+            .map_err(|e| NodeError::Upstream(format!("Failed to parse peers: {}", e)))?;
 
         Ok(IpfsStats {
             repo_size: repo_data["RepoSize"].as_u64().unwrap_or(0),
@@ -272,49 +740,889 @@ impl IpfsManager {
         })
     }
 
-    pub async fn add_content(&self, content: &str) -> Result<String, String> {
+    /// Bytes the daemon has received and sent since it started
+    pub async fn bandwidth_totals(&self) -> Result<(u64, u64), String> {
+        let response = reqwest::Client::new()
+            .post(format!("{}/stats/bw", self.api_url()))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to get bandwidth stats: {}", e))?;
+        let data: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse bandwidth stats: {}", e))?;
+        match (data["TotalIn"].as_u64(), data["TotalOut"].as_u64()) {
+            (Some(rx), Some(tx)) => Ok((rx, tx)),
+            _ => Err("No totals in bandwidth stats".to_string()),
+        }
+    }
+
+    pub async fn add_content(&self, content: &str) -> Result<String, NodeError> {
+        #[cfg(feature = "embedded-ipfs")]
+        if let Some(node) = self.embedded() {
+            return Ok(node.add(content.as_bytes())?);
+        }
+
         let client = reqwest::Client::new();
 
         let form = reqwest::multipart::Form::new()
             .text("file", content.to_string());
 
         let response = client
-            .post("http://localhost:5001/api/v0/add")
+            .post(format!("{}/add", self.api_url()))
             .multipart(form)
             .send()
             .await
-            .map_err(|e| format!("Failed to add content: {}", e))?;
+            .map_err(|e| NodeError::request("Failed to add content", e))?;
 
         let data: serde_json::Value = response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| NodeError::Upstream(format!("Failed to parse response: {}", e)))?;
 
         data["Hash"]
             .as_str()
             .map(|s| s.to_string())
-            .ok_or_else(|| "No CID in response".to_string())
+            .ok_or_else(|| NodeError::Upstream("No CID in response".to_string()))
     }
 
-    pub async fn pin(&self, cid: &str) -> Result<(), String> {
+    /// Add a file, or a directory when `recursive` is set, streaming its
+    /// contents from disk. Returns the CID of the root (the wrapping
+    /// directory when `wrap_with_directory` is set).
+    pub async fn add_path(
+        &self,
+        path: &Path,
+        recursive: bool,
+        wrap_with_directory: bool,
+        progress_tx: Option<mpsc::Sender<IpfsProgress>>,
+    ) -> Result<String, NodeError> {
+        let metadata = std::fs::metadata(path)
+            .map_err(|e| NodeError::NotFound(format!("Cannot read {}: {}", path.display(), e)))?;
+        if metadata.is_dir() && !recursive {
+            return Err(NodeError::Invalid(format!("{} is a directory, add it recursively", path.display())));
+        }
+
+        let root = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .ok_or_else(|| NodeError::Invalid(format!("Invalid path: {}", path.display())))?;
+
+        // Parts are named by their path relative to the added root; kubo
+        // rebuilds the tree from them
+        let mut entries = vec![(root.clone(), path.to_path_buf(), metadata.is_dir())];
+        if metadata.is_dir() {
+            collect_dir_entries(path, &root, &mut entries)?;
+        }
+
+        let mut form = reqwest::multipart::Form::new();
+        let mut total = 0u64;
+        for (name, full_path, is_dir) in entries {
+            let name = name
+                .split('/')
+                .map(|c| urlencoding::encode(c).into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+            let part = if is_dir {
+                reqwest::multipart::Part::bytes(Vec::new())
+                    .file_name(name)
+                    .mime_str("application/x-directory")
+                    .map_err(|e| e.to_string())?
+            } else {
+                let file = tokio::fs::File::open(&full_path)
+                    .await
+                    .map_err(|e| format!("Cannot open {}: {}", full_path.display(), e))?;
+                let len = file.metadata().await.map_err(|e| e.to_string())?.len();
+                total += len;
+                reqwest::multipart::Part::stream_with_length(reqwest::Body::from(file), len)
+                    .file_name(name)
+                    .mime_str("application/octet-stream")
+                    .map_err(|e| e.to_string())?
+            };
+            form = form.part("file", part);
+        }
+
         let client = reqwest::Client::new();
-        client
-            .post(format!("http://localhost:5001/api/v0/pin/add?arg={}", cid))
+        let response = client
+            .post(format!("{}/add", self.api_url()))
+            .query(&[
+                ("wrap-with-directory", wrap_with_directory.to_string()),
+                ("progress", progress_tx.is_some().to_string()),
+            ])
+            .multipart(form)
+            .send()
+            .await
+            .map_err(|e| NodeError::request(&format!("Failed to add {}", path.display()), e))?;
+
+        if !response.status().is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(NodeError::Upstream(format!("Failed to add {}: {}", path.display(), text)));
+        }
+
+        // One JSON object per added entry (the root comes last), with
+        // `Bytes` updates per file in between when progress is on
+        let target = path.display().to_string();
+        let mut hashed: HashMap<String, u64> = HashMap::new();
+        let mut root = None;
+        read_json_lines(response, progress_tx.as_ref(), |entry| {
+            let name = entry["Name"].as_str().unwrap_or_default().to_string();
+            if let Some(hash) = entry["Hash"].as_str() {
+                root = Some(hash.to_string());
+                return None;
+            }
+            let bytes = entry["Bytes"].as_u64()?;
+            hashed.insert(name.clone(), bytes);
+            let done: u64 = hashed.values().sum();
+            Some(IpfsProgress {
+                operation: "add".to_string(),
+                target: target.clone(),
+                current_file: Some(name),
+                bytes: Some(done),
+                total: Some(total),
+                blocks: None,
+                percent: (total > 0).then(|| (done as f64 / total as f64 * 100.0).min(100.0)),
+            })
+        })
+        .await?;
+
+        root.ok_or_else(|| NodeError::Upstream("No CID in response".to_string()))
+    }
+
+    /// Total size of the content behind `cid`, including any children
+    pub async fn content_size(&self, cid: &str) -> Result<u64, NodeError> {
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/files/stat", self.api_url()))
+            .query(&[("arg", format!("/ipfs/{}", cid))])
+            .send()
+            .await
+            .map_err(|e| NodeError::request(&format!("Failed to stat {}", cid), e))?;
+
+        if !response.status().is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(NodeError::Upstream(format!("Failed to stat {}: {}", cid, text)));
+        }
+
+        let data: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| NodeError::Upstream(format!("Failed to parse response: {}", e)))?;
+
+        // Size is the file size; directories only report a cumulative size
+        data["Size"]
+            .as_u64()
+            .filter(|&size| size > 0)
+            .or_else(|| data["CumulativeSize"].as_u64())
+            .ok_or_else(|| NodeError::Upstream("No size in response".to_string()))
+    }
+
+    /// Whether every block of `cid` is in the local repo, without
+    /// fetching any from the network
+    pub async fn has_locally(&self, cid: &str) -> Result<bool, NodeError> {
+        let response = reqwest::Client::new()
+            .post(format!("{}/dag/stat", self.api_url()))
+            .query(&[("arg", cid), ("progress", "false"), ("offline", "true")])
+            .send()
+            .await
+            .map_err(|e| NodeError::request(&format!("Failed to stat {}", cid), e))?;
+        Ok(response.status().is_success())
+    }
+
+    /// Read `length` bytes of the file behind `cid` from `offset`, from
+    /// the local repo only
+    pub async fn read_range(&self, cid: &str, offset: u64, length: u64) -> Result<Vec<u8>, NodeError> {
+        let response = reqwest::Client::new()
+            .post(format!("{}/cat", self.api_url()))
+            .query(&[
+                ("arg", cid.to_string()),
+                ("offset", offset.to_string()),
+                ("length", length.to_string()),
+                ("offline", "true".to_string()),
+            ])
             .send()
             .await
-            .map_err(|e| format!("Failed to pin: {}", e))?;
+            .map_err(|e| NodeError::request(&format!("Failed to read {}", cid), e))?;
+
+        if !response.status().is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(NodeError::Upstream(format!("Failed to read {}: {}", cid, text)));
+        }
+
+        response
+            .bytes()
+            .await
+            .map(|bytes| bytes.to_vec())
+            .map_err(|e| NodeError::request(&format!("Failed to read {}", cid), e))
+    }
+
+    /// Open the content behind `cid` for streaming, refusing anything
+    /// larger than `max_bytes`. Returns the size and the response to read.
+    pub async fn cat_stream(&self, cid: &str, max_bytes: u64) -> Result<(u64, reqwest::Response), NodeError> {
+        let size = self.content_size(cid).await?;
+        if size > max_bytes {
+            return Err(NodeError::Policy(format!("{} is {} bytes, over the {} byte limit", cid, size, max_bytes)));
+        }
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/cat", self.api_url()))
+            // Caps the read even if the stat was off
+            .query(&[("arg", cid.to_string()), ("length", max_bytes.to_string())])
+            .send()
+            .await
+            .map_err(|e| NodeError::request("Failed to read content", e))?;
+
+        if !response.status().is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(NodeError::Upstream(format!("Failed to read content: {}", text)));
+        }
+
+        Ok((size, response))
+    }
+
+    /// Read content as text, up to `MAX_CAT_SIZE`
+    pub async fn cat(&self, cid: &str) -> Result<String, NodeError> {
+        #[cfg(feature = "embedded-ipfs")]
+        if let Some(node) = self.embedded() {
+            let data = node.cat(cid, MAX_CAT_SIZE)?;
+            return String::from_utf8(data).map_err(|_| NodeError::Invalid(format!("{} is not text", cid)));
+        }
+
+        let (_, response) = self.cat_stream(cid, MAX_CAT_SIZE).await?;
+        response
+            .text()
+            .await
+            .map_err(|e| NodeError::request("Failed to read response", e))
+    }
+
+    /// Download the file or directory behind `cid` into `dest_dir`, where
+    /// it is saved under its CID. Returns the written path.
+    pub async fn get(&self, cid: &str, dest_dir: &Path, max_bytes: u64) -> Result<PathBuf, NodeError> {
+        use futures_util::StreamExt;
+        use tokio::io::AsyncWriteExt;
+
+        let size = self.content_size(cid).await?;
+        if size > max_bytes {
+            return Err(NodeError::Policy(format!("{} is {} bytes, over the {} byte limit", cid, size, max_bytes)));
+        }
+
+        std::fs::create_dir_all(dest_dir)
+            .map_err(|e| format!("Failed to create {}: {}", dest_dir.display(), e))?;
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/get", self.api_url()))
+            .query(&[("arg", cid)])
+            .send()
+            .await
+            .map_err(|e| NodeError::request(&format!("Failed to get {}", cid), e))?;
+
+        if !response.status().is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(NodeError::Upstream(format!("Failed to get {}: {}", cid, text)));
+        }
+
+        // Kubo answers with a tar of the content; spool it to disk, then unpack
+        let archive_path = dest_dir.join(format!(".{}.tar", cid));
+        let result = async {
+            let mut file = tokio::fs::File::create(&archive_path)
+                .await
+                .map_err(|e| format!("Failed to create {}: {}", archive_path.display(), e))?;
+            let mut stream = response.bytes_stream();
+            let mut written = 0u64;
+            // Tar headers add some overhead on top of the content itself
+            let limit = max_bytes.saturating_add(max_bytes / 10 + 1024 * 1024);
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.map_err(|e| format!("Download of {} interrupted: {}", cid, e))?;
+                written += chunk.len() as u64;
+                if written > limit {
+                    return Err(format!("{} exceeds the {} byte limit", cid, max_bytes));
+                }
+                file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+            }
+            file.flush().await.map_err(|e| e.to_string())?;
+
+            let archive = std::fs::File::open(&archive_path).map_err(|e| e.to_string())?;
+            tar::Archive::new(archive)
+                .unpack(dest_dir)
+                .map_err(|e| format!("Failed to unpack {}: {}", cid, e))
+        }
+        .await;
+        let _ = std::fs::remove_file(&archive_path);
+        result?;
+
+        Ok(dest_dir.join(cid))
+    }
+
+    pub async fn pin(&self, cid: &str) -> Result<(), NodeError> {
+        self.pin_with_progress(cid, None).await
+    }
+
+    /// Pin `cid`, reporting how many blocks have been fetched so far
+    pub async fn pin_with_progress(
+        &self,
+        cid: &str,
+        progress_tx: Option<mpsc::Sender<IpfsProgress>>,
+    ) -> Result<(), NodeError> {
+        #[cfg(feature = "embedded-ipfs")]
+        if let Some(node) = self.embedded() {
+            return Ok(node.pin(cid)?);
+        }
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/pin/add", self.api_url()))
+            .query(&[("arg", cid), ("progress", if progress_tx.is_some() { "true" } else { "false" })])
+            .send()
+            .await
+            .map_err(|e| NodeError::request("Failed to pin", e))?;
+
+        if !response.status().is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(NodeError::Upstream(format!("Failed to pin: {}", text)));
+        }
+
+        read_json_lines(response, progress_tx.as_ref(), |entry| {
+            Some(IpfsProgress {
+                operation: "pin".to_string(),
+                target: cid.to_string(),
+                current_file: None,
+                bytes: None,
+                total: None,
+                blocks: Some(entry["Progress"].as_u64()?),
+                percent: None,
+            })
+        })
+        .await?;
+
+        record_pin(cid, true);
         Ok(())
     }
 
-    pub async fn unpin(&self, cid: &str) -> Result<(), String> {
+    /// List pins of `pin_type` (`recursive` by default, or `direct`,
+    /// `indirect`, `all`) sorted by CID unless sorted otherwise, with sizes
+    /// for the requested page
+    pub async fn list_pins(&self, pin_type: Option<&str>, query: &ListQuery) -> Result<Page<PinInfo>, NodeError> {
+        #[cfg(feature = "embedded-ipfs")]
+        if let Some(node) = self.embedded() {
+            return Ok(query.apply(node.list_pins()));
+        }
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/pin/ls", self.api_url()))
+            .query(&[("type", pin_type.unwrap_or("recursive"))])
+            .send()
+            .await
+            .map_err(|e| NodeError::request("Failed to list pins", e))?;
+
+        if !response.status().is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(NodeError::Upstream(format!("Failed to list pins: {}", text)));
+        }
+
+        let data: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| NodeError::Upstream(format!("Failed to parse response: {}", e)))?;
+
+        let mut pins: Vec<PinInfo> = data["Keys"]
+            .as_object()
+            .map(|keys| {
+                keys.iter()
+                    .map(|(cid, info)| PinInfo {
+                        cid: cid.clone(),
+                        pin_type: info["Type"].as_str().unwrap_or("unknown").to_string(),
+                        cumulative_size: None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        pins.sort_by(|a, b| a.cid.cmp(&b.cid));
+
+        // Sizes need a lookup per pin, so only fetch them for this page
+        let mut page = query.apply(pins);
+        let sizes = futures_util::future::join_all(
+            page.items.iter().map(|pin| self.cumulative_size(&pin.cid)),
+        )
+        .await;
+        for (pin, size) in page.items.iter_mut().zip(sizes) {
+            pin.cumulative_size = size;
+        }
+        Ok(page)
+    }
+
+    async fn cumulative_size(&self, cid: &str) -> Option<u64> {
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/files/stat", self.api_url()))
+            .query(&[("arg", format!("/ipfs/{}", cid))])
+            .send()
+            .await
+            .ok()?;
+        let data: serde_json::Value = response.json().await.ok()?;
+        data["CumulativeSize"].as_u64()
+    }
+
+    /// Keep `cid` out of the pins `enforce_repo_budget` may drop, or let it back in
+    pub fn hold(&self, cid: &str, held: bool) {
+        let mut pins = self.held.lock().unwrap();
+        if held {
+            pins.insert(cid.to_string());
+        } else {
+            pins.remove(cid);
+        }
+    }
+
+    pub async fn unpin(&self, cid: &str) -> Result<(), NodeError> {
+        #[cfg(feature = "embedded-ipfs")]
+        if let Some(node) = self.embedded() {
+            return Ok(node.unpin(cid)?);
+        }
+
         let client = reqwest::Client::new();
         client
-            .post(format!("http://localhost:5001/api/v0/pin/rm?arg={}", cid))
+            .post(format!("{}/pin/rm?arg={}", self.api_url(), cid))
             .send()
             .await
-            .map_err(|e| format!("Failed to unpin: {}", e))?;
+            .map_err(|e| NodeError::request("Failed to unpin", e))?;
+        record_pin(cid, false);
         Ok(())
     }
+
+    /// Publish `data` on a pubsub topic; the daemon must run with pubsub
+    pub async fn pubsub_publish(&self, topic: &str, data: &[u8]) -> Result<(), NodeError> {
+        let form = reqwest::multipart::Form::new()
+            .part("data", reqwest::multipart::Part::bytes(data.to_vec()).file_name("data"));
+        let response = reqwest::Client::new()
+            .post(format!("{}/pubsub/pub", self.api_url()))
+            .query(&[("arg", multibase(topic.as_bytes()))])
+            .multipart(form)
+            .send()
+            .await
+            .map_err(|e| NodeError::request("Failed to publish on IPFS pubsub", e))?;
+        if !response.status().is_success() {
+            let data: serde_json::Value = response.json().await.unwrap_or_default();
+            let message = data["Message"].as_str().unwrap_or("unknown error");
+            return Err(NodeError::Upstream(format!("Failed to publish on {}: {}", topic, message)));
+        }
+        Ok(())
+    }
+
+    /// Messages on a pubsub topic as they arrive, one JSON object per
+    /// line, with `data` multibase-encoded (see `multibase_decode`)
+    pub async fn pubsub_subscribe(&self, topic: &str) -> Result<reqwest::Response, NodeError> {
+        let response = reqwest::Client::new()
+            .post(format!("{}/pubsub/sub", self.api_url()))
+            .query(&[("arg", multibase(topic.as_bytes()))])
+            .send()
+            .await
+            .map_err(|e| NodeError::request("Failed to subscribe on IPFS pubsub", e))?;
+        if !response.status().is_success() {
+            let data: serde_json::Value = response.json().await.unwrap_or_default();
+            let message = data["Message"].as_str().unwrap_or("unknown error");
+            return Err(NodeError::Upstream(format!("Failed to subscribe to {}: {}", topic, message)));
+        }
+        Ok(response)
+    }
+
+    /// Point the IPNS name of `key` (`self` by default) at `cid`
+    pub async fn ipns_publish(&self, cid: &str, key: Option<&str>) -> Result<IpnsRecord, NodeError> {
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/name/publish", self.api_url()))
+            .query(&[
+                ("arg", format!("/ipfs/{}", cid.trim_start_matches("/ipfs/"))),
+                ("key", key.unwrap_or("self").to_string()),
+            ])
+            .send()
+            .await
+            .map_err(|e| NodeError::request("Failed to publish", e))?;
+
+        let data = api_json(response, "publish").await?;
+        Ok(IpnsRecord {
+            name: data["Name"].as_str().unwrap_or_default().to_string(),
+            value: data["Value"].as_str().unwrap_or_default().to_string(),
+        })
+    }
+
+    /// Resolve an IPNS name to the path it currently points to
+    pub async fn ipns_resolve(&self, name: &str) -> Result<String, NodeError> {
+        let name = if name.starts_with("/ipns/") {
+            name.to_string()
+        } else {
+            format!("/ipns/{}", name)
+        };
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/name/resolve", self.api_url()))
+            .query(&[("arg", name.as_str())])
+            .send()
+            .await
+            .map_err(|e| NodeError::request(&format!("Failed to resolve {}", name), e))?;
+
+        let data = api_json(response, "resolve").await?;
+        data["Path"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| NodeError::Upstream("No path in response".to_string()))
+    }
+
+    /// Create a new ed25519 key for publishing under a separate IPNS name
+    pub async fn key_gen(&self, name: &str) -> Result<IpnsKey, NodeError> {
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/key/gen", self.api_url()))
+            .query(&[("arg", name), ("type", "ed25519")])
+            .send()
+            .await
+            .map_err(|e| NodeError::request("Failed to generate key", e))?;
+
+        let data = api_json(response, "generate key").await?;
+        Ok(IpnsKey {
+            name: data["Name"].as_str().unwrap_or(name).to_string(),
+            id: data["Id"].as_str().unwrap_or_default().to_string(),
+        })
+    }
+
+    pub async fn key_list(&self) -> Result<Vec<IpnsKey>, NodeError> {
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/key/list", self.api_url()))
+            .query(&[("l", "true")])
+            .send()
+            .await
+            .map_err(|e| NodeError::request("Failed to list keys", e))?;
+
+        let data = api_json(response, "list keys").await?;
+        Ok(data["Keys"]
+            .as_array()
+            .map(|keys| {
+                keys.iter()
+                    .filter_map(|k| {
+                        Some(IpnsKey {
+                            name: k["Name"].as_str()?.to_string(),
+                            id: k["Id"].as_str()?.to_string(),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn repo_size(&self) -> Result<u64, NodeError> {
+        let client = reqwest::Client::new();
+        let data: serde_json::Value = client
+            .post(format!("{}/repo/stat", self.api_url()))
+            .query(&[("size-only", "true")])
+            .send()
+            .await
+            .map_err(|e| NodeError::request("Failed to get repo stats", e))?
+            .json()
+            .await
+            .map_err(|e| NodeError::Upstream(format!("Failed to parse repo stats: {}", e)))?;
+        data["RepoSize"]
+            .as_u64()
+            .ok_or_else(|| NodeError::Upstream("No repo size in response".to_string()))
+    }
+
+    /// Remove unpinned blocks from the repo
+    pub async fn gc(&self) -> Result<GcResult, NodeError> {
+        let _guard = self.gc_lock.lock().await;
+        self.run_gc(Vec::new()).await
+    }
+
+    async fn run_gc(&self, unpinned: Vec<String>) -> Result<GcResult, NodeError> {
+        let repo_size_before = self.repo_size().await?;
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/repo/gc", self.api_url()))
+            .send()
+            .await
+            .map_err(|e| NodeError::request("Failed to run GC", e))?;
+
+        if !response.status().is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(NodeError::Upstream(format!("Failed to run GC: {}", text)));
+        }
+
+        // One JSON object per removed block
+        let text = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read GC output: {}", e))?;
+        let mut removed = 0;
+        for line in text.lines() {
+            let Ok(entry) = serde_json::from_str::<serde_json::Value>(line) else {
+                continue;
+            };
+            match entry["Error"].as_str() {
+                Some(error) if !error.is_empty() => log::warn!("IPFS GC: {}", error),
+                _ => removed += 1,
+            }
+        }
+
+        let repo_size_after = self.repo_size().await.unwrap_or(repo_size_before);
+        log::info!(
+            "IPFS GC removed {} blocks ({} -> {} bytes)",
+            removed, repo_size_before, repo_size_after
+        );
+
+        Ok(GcResult { removed, repo_size_before, repo_size_after, unpinned })
+    }
+
+    /// Bring the repo under `max_repo_size`, or the node's storage limit
+    /// if that is lower: GC first, then, if enabled, unpin the oldest
+    /// unprotected app pins one at a time; held pins are never dropped.
+    /// Returns `None` when no budget is set or the repo is already within it.
+    pub async fn enforce_repo_budget(&self) -> Result<Option<GcResult>, NodeError> {
+        let config = self.config();
+        let storage_limit = resources::storage_bytes(&self.resource_limits.lock().unwrap());
+        let Some(budget) = [config.max_repo_size, storage_limit].into_iter().flatten().min() else {
+            return Ok(None);
+        };
+
+        let _guard = self.gc_lock.lock().await;
+        if self.repo_size().await? <= budget {
+            return Ok(None);
+        }
+
+        log::info!("IPFS repo over its {} byte budget, collecting garbage", budget);
+        let mut result = self.run_gc(Vec::new()).await?;
+        if result.repo_size_after <= budget || !config.unpin_oldest {
+            return Ok(Some(result));
+        }
+
+        let held = self.held.lock().unwrap().clone();
+        let mut candidates: Vec<(String, String)> = load_pin_ledger()
+            .into_iter()
+            .filter(|(cid, _)| !config.protected_pins.contains(cid) && !held.contains(cid))
+            .collect();
+        candidates.sort_by(|a, b| a.1.cmp(&b.1));
+
+        let size_before = result.repo_size_before;
+        let mut unpinned = Vec::new();
+        for (cid, _) in candidates {
+            log::info!("Unpinning {} to stay within the repo budget", cid);
+            self.unpin(&cid).await?;
+            unpinned.push(cid);
+
+            result = self.run_gc(unpinned.clone()).await?;
+            if result.repo_size_after <= budget {
+                break;
+            }
+        }
+        result.repo_size_before = size_before;
+
+        if result.repo_size_after > budget {
+            log::warn!("IPFS repo still over budget after unpinning all app pins");
+        }
+        Ok(Some(result))
+    }
+
+    /// Check the repo budget periodically until the app exits
+    pub async fn watch_repo_budget(&self) {
+        loop {
+            tokio::time::sleep(BUDGET_CHECK_INTERVAL).await;
+            if !self.is_running() {
+                continue;
+            }
+            if let Err(e) = self.enforce_repo_budget().await {
+                log::warn!("IPFS repo budget check failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Parse `v0.32.1` / `0.32.1` into comparable parts
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = version.trim().trim_start_matches('v').split('.');
+    Some((
+        parts.next()?.parse().ok()?,
+        parts.next()?.parse().ok()?,
+        parts.next().unwrap_or("0").parse().ok()?,
+    ))
+}
+
+/// Accept a bare 64-char hex key or the contents of a `swarm.key` file
+pub fn parse_swarm_key(input: &str) -> Result<String, String> {
+    let key = input
+        .lines()
+        .map(str::trim)
+        .rfind(|l| !l.is_empty())
+        .unwrap_or_default()
+        .to_lowercase();
+    if key.len() == 64 && key.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(key)
+    } else {
+        Err("Swarm key must be 64 hex characters".to_string())
+    }
+}
+
+/// Random key for starting a new private network
+pub fn generate_swarm_key() -> String {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    for _ in 0..4 {
+        hasher.update(uuid::Uuid::new_v4().as_bytes());
+    }
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Read a streamed NDJSON response, sending whatever progress `on_entry`
+/// derives from each object
+async fn read_json_lines(
+    response: reqwest::Response,
+    progress_tx: Option<&mpsc::Sender<IpfsProgress>>,
+    mut on_entry: impl FnMut(&serde_json::Value) -> Option<IpfsProgress>,
+) -> Result<(), NodeError> {
+    use futures_util::StreamExt;
+
+    let mut stream = response.bytes_stream();
+    let mut buffer = Vec::new();
+    let mut finished = false;
+    while !finished {
+        match stream.next().await {
+            Some(chunk) => {
+                let chunk = chunk.map_err(|e| NodeError::Upstream(format!("IPFS stream interrupted: {}", e)))?;
+                buffer.extend_from_slice(&chunk);
+            }
+            None => {
+                finished = true;
+                buffer.push(b'\n');
+            }
+        }
+
+        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            let Ok(entry) = serde_json::from_slice::<serde_json::Value>(&line) else {
+                continue;
+            };
+            if entry["Type"].as_str() == Some("error") {
+                let message = entry["Message"].as_str().unwrap_or("unknown error");
+                return Err(NodeError::Upstream(format!("IPFS error: {}", message)));
+            }
+            if let Some(progress) = on_entry(&entry) {
+                if let Some(tx) = progress_tx {
+                    let _ = tx.send(progress).await;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse an RPC response, turning kubo's error body into the message
+/// Base64url multibase, as the pubsub API takes topics and gives data
+fn multibase(bytes: &[u8]) -> String {
+    use base64::Engine;
+    format!("u{}", base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Bytes of a multibase string in one of the base64 encodings Kubo uses
+pub fn multibase_decode(text: &str) -> Option<Vec<u8>> {
+    use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+    use base64::Engine;
+    let rest = text.get(1..)?;
+    match text.get(..1)? {
+        "u" => URL_SAFE_NO_PAD.decode(rest).ok(),
+        "U" => URL_SAFE.decode(rest).ok(),
+        "m" => STANDARD_NO_PAD.decode(rest).ok(),
+        "M" => STANDARD.decode(rest).ok(),
+        _ => None,
+    }
+}
+
+async fn api_json(response: reqwest::Response, action: &str) -> Result<serde_json::Value, NodeError> {
+    let success = response.status().is_success();
+    let data: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| NodeError::Upstream(format!("Failed to parse response: {}", e)))?;
+    if !success {
+        let message = data["Message"].as_str().unwrap_or("unknown error");
+        return Err(NodeError::Upstream(format!("Failed to {}: {}", action, message)));
+    }
+    Ok(data)
+}
+
+fn copy_dir(src: &Path, dest: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Pins made through the app and when, so the oldest can be dropped first
+fn pin_ledger_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+        .join("ipfs-pins.json")
+}
+
+/// CID -> RFC 3339 time it was pinned
+fn load_pin_ledger() -> HashMap<String, String> {
+    std::fs::read_to_string(pin_ledger_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn record_pin(cid: &str, pinned: bool) {
+    let mut ledger = load_pin_ledger();
+    if pinned {
+        ledger.insert(cid.to_string(), chrono::Utc::now().to_rfc3339());
+    } else if ledger.remove(cid).is_none() {
+        return;
+    }
+
+    let path = pin_ledger_path();
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    if let Err(e) = serde_json::to_string_pretty(&ledger)
+        .map_err(|e| e.to_string())
+        .and_then(|data| std::fs::write(&path, data).map_err(|e| e.to_string()))
+    {
+        log::warn!("Failed to record pin {}: {}", cid, e);
+    }
+}
+
+/// Collect `(relative name, path, is_dir)` for everything under `dir`,
+/// parents before children. Symlinks are skipped.
+fn collect_dir_entries(
+    dir: &Path,
+    prefix: &str,
+    entries: &mut Vec<(String, PathBuf, bool)>,
+) -> Result<(), String> {
+    let mut children: Vec<_> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Cannot read {}: {}", dir.display(), e))?
+        .filter_map(|e| e.ok())
+        .collect();
+    children.sort_by_key(|e| e.file_name());
+
+    for child in children {
+        let file_type = match child.file_type() {
+            Ok(t) if !t.is_symlink() => t,
+            _ => continue,
+        };
+        let name = format!("{}/{}", prefix, child.file_name().to_string_lossy());
+        let path = child.path();
+        entries.push((name.clone(), path.clone(), file_type.is_dir()));
+        if file_type.is_dir() {
+            collect_dir_entries(&path, &name, entries)?;
+        }
+    }
+    Ok(())
 }
 
 impl Default for IpfsManager {
@@ -0,0 +1,248 @@
+//! IPFS Cluster Follower
+//!
+//! Runs `ipfs-cluster-follow` next to the managed Kubo daemon so the node
+//! tracks a cluster's pinset: the follower pins and unpins on the local
+//! daemon as the cluster's pinset changes. The cluster is identified by
+//! the CID of its follower configuration (`service.json`).
+
+use crate::config::ClusterConfig;
+use crate::models::ClusterStatus;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::download;
+use super::ipfs::IpfsManager;
+
+/// ipfs-cluster-follow release downloaded when no binary is installed
+pub const CLUSTER_FOLLOW_VERSION: &str = "v1.1.2";
+
+/// How often the follower is checked and restarted if it exited
+const WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+pub struct ClusterFollower {
+    ipfs: Arc<IpfsManager>,
+    config: Mutex<ClusterConfig>,
+    process: Mutex<Option<Child>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl ClusterFollower {
+    pub fn new(ipfs: Arc<IpfsManager>, config: ClusterConfig) -> Self {
+        Self {
+            ipfs,
+            config: Mutex::new(config),
+            process: Mutex::new(None),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> ClusterConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Replace the settings, stopping the follower when it was disabled or
+    /// now follows a different cluster (`watch` starts it again)
+    pub fn set_config(&self, config: ClusterConfig) {
+        let mut current = self.config.lock().unwrap();
+        if !config.enabled || current.config_cid != config.config_cid || current.name != config.name {
+            self.kill();
+        }
+        *current = config;
+    }
+
+    /// Directory holding the binary and each followed cluster's state
+    fn managed_dir() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("otherthing-node")
+            .join("ipfs-cluster-follow")
+    }
+
+    fn binary_path() -> PathBuf {
+        #[cfg(target_os = "windows")]
+        let binary_name = "ipfs-cluster-follow.exe";
+        #[cfg(not(target_os = "windows"))]
+        let binary_name = "ipfs-cluster-follow";
+
+        Self::managed_dir().join("ipfs-cluster-follow").join(binary_name)
+    }
+
+    /// Download and unpack ipfs-cluster-follow for this platform
+    async fn download_binary() -> Result<PathBuf, String> {
+        let dir = Self::managed_dir();
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+        let os = if cfg!(target_os = "windows") {
+            "windows"
+        } else if cfg!(target_os = "macos") {
+            "darwin"
+        } else {
+            "linux"
+        };
+        let arch = if cfg!(target_arch = "aarch64") { "arm64" } else { "amd64" };
+        let ext = if cfg!(target_os = "windows") { "zip" } else { "tar.gz" };
+
+        let filename = format!(
+            "ipfs-cluster-follow_{}_{}-{}.{}",
+            CLUSTER_FOLLOW_VERSION, os, arch, ext
+        );
+        let url = format!(
+            "https://dist.ipfs.tech/ipfs-cluster-follow/{}/{}",
+            CLUSTER_FOLLOW_VERSION, filename
+        );
+        log::info!("Downloading ipfs-cluster-follow from: {}", url);
+
+        let bytes = download::fetch(&url).await?;
+        let archive_path = dir.join(&filename);
+        std::fs::write(&archive_path, &bytes)
+            .map_err(|e| format!("Failed to write archive: {}", e))?;
+        let extracted = download::extract_archive(&archive_path, &dir);
+        let _ = std::fs::remove_file(&archive_path);
+        extracted?;
+
+        let path = Self::binary_path();
+        if !path.exists() {
+            return Err(format!("ipfs-cluster-follow not found at {:?} after extraction", path));
+        }
+        download::make_executable(&path)?;
+        Ok(path)
+    }
+
+    pub fn is_running(&self) -> bool {
+        let mut guard = self.process.lock().unwrap();
+        match guard.as_mut().map(|child| child.try_wait()) {
+            Some(Ok(None)) => true,
+            Some(_) => {
+                *guard = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Initialize the follower for the configured cluster if needed and
+    /// start following it. The Kubo daemon must be running.
+    pub async fn start(&self) -> Result<(), String> {
+        if self.is_running() {
+            return Ok(());
+        }
+
+        let config = self.config();
+        let cid = config
+            .config_cid
+            .as_deref()
+            .map(|c| c.trim().trim_start_matches("/ipfs/"))
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| "No cluster configuration CID set".to_string())?
+            .to_string();
+        let name = config.name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Invalid cluster name: {}", config.name));
+        }
+        if self.ipfs.config().embedded {
+            return Err("Cluster following needs the Kubo daemon, not the embedded store".to_string());
+        }
+        if !self.ipfs.is_running() {
+            return Err("IPFS is not running".to_string());
+        }
+
+        let mut path = Self::binary_path();
+        if !path.exists() {
+            path = Self::download_binary().await?;
+        }
+
+        // Keep the configuration itself pinned so the local gateway (which
+        // does not fetch from the network) can serve it to the follower
+        self.ipfs.pin(&cid).await?;
+
+        let ipfs_config = self.ipfs.config();
+        let state_dir = Self::managed_dir().join("clusters");
+        let cluster_dir = state_dir.join(name);
+        let source = format!("http://127.0.0.1:{}/ipfs/{}", ipfs_config.gateway_port, cid);
+        let initialized_from = std::fs::read_to_string(cluster_dir.join(".source")).ok();
+        if initialized_from.as_deref() != Some(source.as_str()) {
+            log::info!("Initializing cluster follower {} from {}", name, cid);
+            let _ = std::fs::remove_dir_all(&cluster_dir);
+            let status = Command::new(&path)
+                .arg("--config")
+                .arg(&state_dir)
+                .args([name, "init", &source])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map_err(|e| format!("Failed to run ipfs-cluster-follow: {}", e))?;
+            if !status.success() {
+                return Err(format!("Failed to initialize cluster follower from {}", cid));
+            }
+            let _ = std::fs::write(cluster_dir.join(".source"), &source);
+        }
+
+        log::info!("Following IPFS cluster {} ({})", name, cid);
+        let child = Command::new(&path)
+            .arg("--config")
+            .arg(&state_dir)
+            .args([name, "run"])
+            .env(
+                "CLUSTER_IPFSHTTP_NODEMULTIADDRESS",
+                format!("/ip4/127.0.0.1/tcp/{}", ipfs_config.api_port),
+            )
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start ipfs-cluster-follow: {}", e))?;
+
+        *self.process.lock().unwrap() = Some(child);
+        *self.last_error.lock().unwrap() = None;
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        self.kill();
+    }
+
+    fn kill(&self) {
+        if let Some(mut child) = self.process.lock().unwrap().take() {
+            let _ = child.kill();
+            let _ = child.wait();
+            log::info!("Stopped following IPFS cluster");
+        }
+    }
+
+    pub fn get_status(&self) -> ClusterStatus {
+        let config = self.config();
+        ClusterStatus {
+            enabled: config.enabled,
+            name: config.name,
+            config_cid: config.config_cid,
+            running: self.is_running(),
+            installed: Self::binary_path().exists(),
+            last_error: self.last_error.lock().unwrap().clone(),
+        }
+    }
+
+    /// Keep the follower running while it is enabled and IPFS is up,
+    /// until the app exits
+    pub async fn watch(&self) {
+        loop {
+            tokio::time::sleep(WATCH_INTERVAL).await;
+            let config = self.config();
+            if !config.enabled || config.config_cid.is_none() {
+                continue;
+            }
+            if !self.ipfs.is_running() {
+                self.kill();
+                continue;
+            }
+            if self.is_running() {
+                continue;
+            }
+            if let Err(e) = self.start().await {
+                log::warn!("IPFS cluster follower not running: {}", e);
+                *self.last_error.lock().unwrap() = Some(e);
+            }
+        }
+    }
+}
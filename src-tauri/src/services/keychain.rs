@@ -0,0 +1,38 @@
+//! OS Keychain
+//!
+//! Secrets the node needs to keep (registry passwords, API keys) go to the
+//! platform credential store — Keychain on macOS, Credential Manager on
+//! Windows, the Secret Service on Linux — instead of plain config files.
+
+const SERVICE: &str = "otherthing-node";
+
+fn entry(key: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(SERVICE, key).map_err(|e| format!("Keychain unavailable: {}", e))
+}
+
+/// Read a secret; `None` when nothing is stored under `key`
+pub fn get(key: &str) -> Result<Option<String>, String> {
+    match entry(key)?.get_password() {
+        Ok(secret) => {
+            super::redact::register(&secret);
+            Ok(Some(secret))
+        }
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read {} from keychain: {}", key, e)),
+    }
+}
+
+pub fn set(key: &str, secret: &str) -> Result<(), String> {
+    super::redact::register(secret);
+    entry(key)?
+        .set_password(secret)
+        .map_err(|e| format!("Failed to save {} to keychain: {}", key, e))
+}
+
+/// Delete a secret; deleting one that does not exist is not an error
+pub fn delete(key: &str) -> Result<(), String> {
+    match entry(key)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Failed to delete {} from keychain: {}", key, e)),
+    }
+}
@@ -0,0 +1,689 @@
+//! LLM Providers
+//!
+//! Backends agents can run against: the local Ollama daemon or any
+//! OpenAI-compatible chat completions endpoint (OpenAI, vLLM, a llama.cpp
+//! server, a rented Vast instance, ...). Provider settings, including API
+//! keys, are kept in `providers.json` under the config dir.
+//!
+//! Several Ollama hosts can be registered side by side (another LAN node,
+//! a cloud instance); the registry health-checks them, tracks the models
+//! each serves and how busy it is, and can route a model to the least
+//! loaded backend that has it.
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, RwLock};
+
+use super::redact::Secret;
+use super::OllamaManager;
+use crate::error::NodeError;
+
+/// ID of the built-in local Ollama provider
+pub const OLLAMA_PROVIDER_ID: &str = "ollama";
+
+/// Provider id that picks a backend by model availability and load
+pub const AUTO_PROVIDER_ID: &str = "auto";
+
+/// Timeout for a single completion request
+const COMPLETION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Timeout for a backend health check
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long health results are reused when routing
+const HEALTH_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    Ollama,
+    #[serde(rename = "openai")]
+    OpenAi,
+}
+
+/// Stored provider settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderConfig {
+    pub id: String,
+    pub kind: ProviderKind,
+    /// Base URL, e.g. `http://localhost:11434` or `https://api.openai.com/v1`
+    pub base_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<Secret>,
+    /// Model used when a request does not name one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_model: Option<String>,
+    /// What the backend charges, counted against agent cost budgets
+    #[serde(default)]
+    pub price: ProviderPrice,
+}
+
+/// What a backend charges for completions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderPrice {
+    /// Cents per 1,000 prompt and completion tokens
+    #[serde(default)]
+    pub per_1k_tokens_cents: f64,
+    /// Cents per hour spent completing, for rented machines
+    #[serde(default)]
+    pub per_hour_cents: f64,
+}
+
+impl ProviderPrice {
+    /// Cents one completion cost
+    pub fn cost_cents(&self, tokens: u32, elapsed: Duration) -> f64 {
+        f64::from(tokens) / 1000.0 * self.per_1k_tokens_cents + elapsed.as_secs_f64() / 3600.0 * self.per_hour_cents
+    }
+
+    /// Tokens `cents` buy, or `None` when tokens are not charged for
+    pub fn tokens_for(&self, cents: f64) -> Option<u32> {
+        (self.per_1k_tokens_cents > 0.0).then(|| (cents.max(0.0) / self.per_1k_tokens_cents * 1000.0) as u32)
+    }
+}
+
+/// Provider settings as shown to the UI; the API key itself is never returned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderInfo {
+    pub id: String,
+    pub kind: ProviderKind,
+    pub base_url: String,
+    pub has_api_key: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_model: Option<String>,
+    pub price: ProviderPrice,
+}
+
+impl From<&ProviderConfig> for ProviderInfo {
+    fn from(config: &ProviderConfig) -> Self {
+        Self {
+            id: config.id.clone(),
+            kind: config.kind,
+            base_url: config.base_url.clone(),
+            has_api_key: config.api_key.as_ref().is_some_and(|k| !k.is_empty()),
+            default_model: config.default_model.clone(),
+            price: config.price,
+        }
+    }
+}
+
+/// Health of a configured backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendStatus {
+    pub id: String,
+    pub kind: ProviderKind,
+    pub base_url: String,
+    pub healthy: bool,
+    /// Models the backend currently serves
+    pub models: Vec<String>,
+    /// Completions currently running on it through this node
+    pub active_requests: usize,
+    pub checked_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of a completion request
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub text: String,
+    /// Prompt + completion tokens, when the backend reports them
+    pub tokens: u32,
+}
+
+/// A backend that can complete a prompt
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    fn id(&self) -> &str;
+
+    /// Complete `prompt`, generating at most `max_tokens` and sending the
+    /// text to `token_tx` as it streams in
+    async fn complete(
+        &self,
+        model: &str,
+        system: &str,
+        prompt: &str,
+        max_tokens: Option<u32>,
+        token_tx: Option<mpsc::Sender<String>>,
+    ) -> Result<Completion, String>;
+}
+
+/// Ollama server (`/api/generate`)
+pub struct OllamaProvider {
+    base_url: String,
+    /// Model options sent with every request (e.g. `num_gpu`)
+    options: Option<serde_json::Value>,
+}
+
+impl OllamaProvider {
+    pub fn new(base_url: &str, options: Option<serde_json::Value>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            options,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn id(&self) -> &str {
+        OLLAMA_PROVIDER_ID
+    }
+
+    async fn complete(
+        &self,
+        model: &str,
+        system: &str,
+        prompt: &str,
+        max_tokens: Option<u32>,
+        token_tx: Option<mpsc::Sender<String>>,
+    ) -> Result<Completion, String> {
+        let url = format!("{}/api/generate", self.base_url);
+
+        log::info!("Calling Ollama at {} with model {}", url, model);
+
+        let mut payload = serde_json::json!({
+            "model": model,
+            "prompt": prompt,
+            "system": system,
+            "stream": true,
+        });
+        if let Some(options) = &self.options {
+            payload["options"] = options.clone();
+        }
+        if let Some(max_tokens) = max_tokens {
+            payload["options"]["num_predict"] = max_tokens.into();
+        }
+
+        let response = reqwest::Client::new()
+            .post(&url)
+            .json(&payload)
+            .timeout(COMPLETION_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to connect to Ollama: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("Ollama returned error {}: {}", status, text));
+        }
+
+        let mut stream = response.bytes_stream();
+
+        // Streamed responses are newline-delimited JSON objects
+        let mut buffer = String::new();
+        let mut text = String::new();
+        let mut tokens = 0u32;
+
+        while let Some(chunk) = stream.next().await {
+            let bytes = chunk.map_err(|e| format!("Ollama stream interrupted: {}", e))?;
+            buffer.push_str(&String::from_utf8_lossy(&bytes));
+
+            while let Some(pos) = buffer.find('\n') {
+                let line: String = buffer.drain(..=pos).collect();
+                let data = match serde_json::from_str::<serde_json::Value>(line.trim()) {
+                    Ok(data) => data,
+                    Err(_) => continue,
+                };
+
+                if let Some(error) = data["error"].as_str() {
+                    return Err(format!("Ollama error: {}", error));
+                }
+
+                if let Some(token) = data["response"].as_str() {
+                    if !token.is_empty() {
+                        text.push_str(token);
+                        if let Some(tx) = &token_tx {
+                            let _ = tx.send(token.to_string()).await;
+                        }
+                    }
+                }
+
+                if data["done"].as_bool().unwrap_or(false) {
+                    tokens = data["eval_count"].as_u64().unwrap_or(0) as u32
+                        + data["prompt_eval_count"].as_u64().unwrap_or(0) as u32;
+                }
+            }
+        }
+
+        if text.is_empty() {
+            text = "No response".to_string();
+        }
+
+        Ok(Completion { text, tokens })
+    }
+}
+
+/// Any endpoint speaking the OpenAI chat completions API
+pub struct OpenAiProvider {
+    id: String,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiProvider {
+    pub fn new(id: &str, base_url: &str, api_key: Option<String>) -> Self {
+        Self {
+            id: id.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiProvider {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn complete(
+        &self,
+        model: &str,
+        system: &str,
+        prompt: &str,
+        max_tokens: Option<u32>,
+        token_tx: Option<mpsc::Sender<String>>,
+    ) -> Result<Completion, String> {
+        let url = format!("{}/chat/completions", self.base_url);
+
+        log::info!("Calling {} at {} with model {}", self.id, url, model);
+
+        let mut payload = serde_json::json!({
+            "model": model,
+            "messages": [
+                { "role": "system", "content": system },
+                { "role": "user", "content": prompt },
+            ],
+            "stream": true,
+            "stream_options": { "include_usage": true },
+        });
+        if let Some(max_tokens) = max_tokens {
+            payload["max_tokens"] = max_tokens.into();
+        }
+
+        let mut request = reqwest::Client::new()
+            .post(&url)
+            .json(&payload)
+            .timeout(COMPLETION_TIMEOUT);
+        if let Some(key) = self.api_key.as_deref().filter(|k| !k.is_empty()) {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to connect to {}: {}", self.id, e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("{} returned error {}: {}", self.id, status, text));
+        }
+
+        let mut stream = response.bytes_stream();
+
+        // Server-sent events: `data: {...}` lines, terminated by `data: [DONE]`
+        let mut buffer = String::new();
+        let mut text = String::new();
+        let mut tokens = 0u32;
+
+        'stream: while let Some(chunk) = stream.next().await {
+            let bytes = chunk.map_err(|e| format!("{} stream interrupted: {}", self.id, e))?;
+            buffer.push_str(&String::from_utf8_lossy(&bytes));
+
+            while let Some(pos) = buffer.find('\n') {
+                let line: String = buffer.drain(..=pos).collect();
+                let Some(data) = line.trim().strip_prefix("data:") else {
+                    continue;
+                };
+                let data = data.trim();
+                if data == "[DONE]" {
+                    break 'stream;
+                }
+                let data = match serde_json::from_str::<serde_json::Value>(data) {
+                    Ok(data) => data,
+                    Err(_) => continue,
+                };
+
+                if let Some(error) = data["error"]["message"].as_str() {
+                    return Err(format!("{} error: {}", self.id, error));
+                }
+
+                if let Some(token) = data["choices"][0]["delta"]["content"].as_str() {
+                    if !token.is_empty() {
+                        text.push_str(token);
+                        if let Some(tx) = &token_tx {
+                            let _ = tx.send(token.to_string()).await;
+                        }
+                    }
+                }
+
+                if let Some(total) = data["usage"]["total_tokens"].as_u64() {
+                    tokens = total as u32;
+                }
+            }
+        }
+
+        if text.is_empty() {
+            text = "No response".to_string();
+        }
+
+        Ok(Completion { text, tokens })
+    }
+}
+
+/// Counts in-flight completions of the wrapped provider and notes when
+/// the last one finished
+struct TrackedProvider {
+    /// Registry id, which Ollama backends do not report themselves
+    id: String,
+    inner: Arc<dyn LlmProvider>,
+    in_flight: Arc<AtomicUsize>,
+    last_used: Arc<std::sync::Mutex<HashMap<String, Instant>>>,
+}
+
+#[async_trait]
+impl LlmProvider for TrackedProvider {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    async fn complete(
+        &self,
+        model: &str,
+        system: &str,
+        prompt: &str,
+        max_tokens: Option<u32>,
+        token_tx: Option<mpsc::Sender<String>>,
+    ) -> Result<Completion, String> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let result = self.inner.complete(model, system, prompt, max_tokens, token_tx).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.last_used
+            .lock()
+            .unwrap()
+            .insert(self.id.clone(), Instant::now());
+        result
+    }
+}
+
+/// Configured providers, persisted to disk
+pub struct ProviderRegistry {
+    configs: RwLock<Vec<ProviderConfig>>,
+    path: PathBuf,
+    /// The built-in provider follows the managed Ollama's settings
+    ollama: Arc<OllamaManager>,
+    /// Last health check of every backend and when it ran
+    health: RwLock<Option<(Instant, Vec<BackendStatus>)>>,
+    in_flight: std::sync::Mutex<HashMap<String, Arc<AtomicUsize>>>,
+    /// When a completion last finished, by provider
+    last_used: Arc<std::sync::Mutex<HashMap<String, Instant>>>,
+}
+
+impl ProviderRegistry {
+    /// Load providers from the config dir; the local Ollama provider always exists
+    pub fn load(ollama: Arc<OllamaManager>) -> Self {
+        let path = config_path();
+        let mut configs: Vec<ProviderConfig> = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+
+        if !configs.iter().any(|c| c.id == OLLAMA_PROVIDER_ID) {
+            configs.insert(0, ProviderConfig {
+                id: OLLAMA_PROVIDER_ID.to_string(),
+                kind: ProviderKind::Ollama,
+                base_url: ollama.get_host(),
+                api_key: None,
+                default_model: None,
+                price: ProviderPrice::default(),
+            });
+        }
+
+        Self {
+            configs: RwLock::new(configs),
+            path,
+            ollama,
+            health: RwLock::new(None),
+            in_flight: std::sync::Mutex::new(HashMap::new()),
+            last_used: Arc::new(std::sync::Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn list(&self) -> Vec<ProviderInfo> {
+        self.configs
+            .read()
+            .await
+            .iter()
+            .map(|config| {
+                let mut info = ProviderInfo::from(config);
+                if config.id == OLLAMA_PROVIDER_ID {
+                    info.base_url = self.ollama.get_host();
+                }
+                info
+            })
+            .collect()
+    }
+
+    /// Add or update a provider. An update without an API key keeps the stored one.
+    pub async fn save(&self, mut config: ProviderConfig) -> Result<ProviderInfo, NodeError> {
+        if config.id.trim().is_empty() {
+            return Err(NodeError::Invalid("Provider id is required".to_string()));
+        }
+        if config.id == AUTO_PROVIDER_ID {
+            return Err(NodeError::Invalid(format!("'{}' is reserved for automatic routing", AUTO_PROVIDER_ID)));
+        }
+        if config.id == OLLAMA_PROVIDER_ID && config.kind != ProviderKind::Ollama {
+            return Err(NodeError::Invalid("The ollama provider cannot change kind".to_string()));
+        }
+
+        let mut configs = self.configs.write().await;
+        if let Some(existing) = configs.iter_mut().find(|c| c.id == config.id) {
+            if config.api_key.is_none() {
+                config.api_key = existing.api_key.clone();
+            }
+            *existing = config.clone();
+        } else {
+            configs.push(config.clone());
+        }
+        self.persist(&configs)?;
+
+        Ok(ProviderInfo::from(&config))
+    }
+
+    pub async fn remove(&self, id: &str) -> Result<(), NodeError> {
+        if id == OLLAMA_PROVIDER_ID {
+            return Err(NodeError::Invalid("The ollama provider cannot be removed".to_string()));
+        }
+
+        let mut configs = self.configs.write().await;
+        let before = configs.len();
+        configs.retain(|c| c.id != id);
+        if configs.len() == before {
+            return Err(NodeError::NotFound(format!("Unknown provider: {}", id)));
+        }
+        Ok(self.persist(&configs)?)
+    }
+
+    pub async fn get_config(&self, id: &str) -> Option<ProviderConfig> {
+        self.configs.read().await.iter().find(|c| c.id == id).cloned()
+    }
+
+    /// Instantiate a provider by id
+    pub async fn get(&self, id: &str) -> Result<Arc<dyn LlmProvider>, String> {
+        let config = self
+            .get_config(id)
+            .await
+            .ok_or_else(|| format!("Unknown provider: {}", id))?;
+
+        let inner: Arc<dyn LlmProvider> = if config.id == OLLAMA_PROVIDER_ID {
+            Arc::new(OllamaProvider::new(
+                &self.ollama.get_host(),
+                self.ollama.request_options(),
+            ))
+        } else {
+            match config.kind {
+                ProviderKind::Ollama => Arc::new(OllamaProvider::new(&config.base_url, None)),
+                ProviderKind::OpenAi => Arc::new(OpenAiProvider::new(&config.id, &config.base_url, config.api_key.map(|k| k.expose().to_string()))),
+            }
+        };
+
+        Ok(Arc::new(TrackedProvider {
+            id: id.to_string(),
+            inner,
+            in_flight: self.in_flight_counter(id),
+            last_used: Arc::clone(&self.last_used),
+        }))
+    }
+
+    fn in_flight_counter(&self, id: &str) -> Arc<AtomicUsize> {
+        Arc::clone(self.in_flight.lock().unwrap().entry(id.to_string()).or_default())
+    }
+
+    /// Whether a completion is running on the provider
+    pub fn is_busy(&self, id: &str) -> bool {
+        self.in_flight_counter(id).load(Ordering::SeqCst) > 0
+    }
+
+    /// When a completion on the provider last finished, if one has since
+    /// the app started
+    pub fn last_used(&self, id: &str) -> Option<Instant> {
+        self.last_used.lock().unwrap().get(id).copied()
+    }
+
+    /// Probe every backend for reachability and its model list
+    pub async fn check_health(&self) -> Vec<BackendStatus> {
+        let configs = self.configs.read().await.clone();
+        let checks = configs.iter().map(|config| async move {
+            let base_url = if config.id == OLLAMA_PROVIDER_ID {
+                self.ollama.get_host()
+            } else {
+                config.base_url.trim_end_matches('/').to_string()
+            };
+            let result = list_backend_models(config.kind, &base_url, config.api_key.as_ref().map(Secret::expose)).await;
+            if let Err(e) = &result {
+                log::debug!("Backend {} unhealthy: {}", config.id, e);
+            }
+
+            BackendStatus {
+                id: config.id.clone(),
+                kind: config.kind,
+                base_url,
+                healthy: result.is_ok(),
+                active_requests: self.in_flight_counter(&config.id).load(Ordering::SeqCst),
+                checked_at: chrono::Utc::now().to_rfc3339(),
+                error: result.as_ref().err().cloned(),
+                models: result.unwrap_or_default(),
+            }
+        });
+        let statuses = futures_util::future::join_all(checks).await;
+
+        *self.health.write().await = Some((Instant::now(), statuses.clone()));
+        statuses
+    }
+
+    /// Backend health, re-checked when older than `HEALTH_TTL`
+    pub async fn health(&self) -> Vec<BackendStatus> {
+        if let Some((checked, statuses)) = self.health.read().await.as_ref() {
+            if checked.elapsed() < HEALTH_TTL {
+                return statuses.clone();
+            }
+        }
+        self.check_health().await
+    }
+
+    /// Pick the healthy backend serving `model` with the fewest running
+    /// completions, preferring the local Ollama on a tie
+    pub async fn route(&self, model: &str) -> Result<String, String> {
+        let statuses = self.health().await;
+        statuses
+            .iter()
+            .filter(|s| s.healthy && s.models.iter().any(|m| model_matches(m, model)))
+            .min_by_key(|s| {
+                let load = self.in_flight_counter(&s.id).load(Ordering::SeqCst);
+                (load, s.id != OLLAMA_PROVIDER_ID)
+            })
+            .map(|s| s.id.clone())
+            .ok_or_else(|| format!("No healthy backend serves model {}", model))
+    }
+
+    fn persist(&self, configs: &[ProviderConfig]) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let data = serde_json::to_string_pretty(configs).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, data)
+            .map_err(|e| format!("Failed to save providers: {}", e))?;
+
+        // The file holds API keys
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(&self.path, std::fs::Permissions::from_mode(0o600));
+        }
+
+        Ok(())
+    }
+}
+
+fn config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+        .join("providers.json")
+}
+
+/// Ollama names models `name:tag` and treats a bare name as `:latest`
+fn model_matches(available: &str, wanted: &str) -> bool {
+    available == wanted
+        || available.strip_suffix(":latest") == Some(wanted)
+        || wanted.strip_suffix(":latest") == Some(available)
+}
+
+/// Models served by a backend; an error means it is unreachable
+async fn list_backend_models(
+    kind: ProviderKind,
+    base_url: &str,
+    api_key: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let client = reqwest::Client::builder()
+        .timeout(HEALTH_CHECK_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let (url, field) = match kind {
+        ProviderKind::Ollama => (format!("{}/api/tags", base_url), ("models", "name")),
+        ProviderKind::OpenAi => (format!("{}/models", base_url), ("data", "id")),
+    };
+    let mut request = client.get(&url);
+    if let Some(key) = api_key.filter(|k| !k.is_empty()) {
+        request = request.bearer_auth(key);
+    }
+
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("{} returned {}", url, response.status()));
+    }
+    let data: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+
+    Ok(data[field.0]
+        .as_array()
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| m[field.1].as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default())
+}
@@ -0,0 +1,539 @@
+//! LAN Clustering
+//!
+//! Paired nodes (see `fleet`) can run each other's work without the
+//! orchestrator. An agent execution or container job dispatched here goes
+//! to an idle peer, which runs it and streams the output back. The peer
+//! checks the share key it was paired with, and only takes jobs while it
+//! would take them from the orchestrator (see `idle`).
+//!
+//! Both ends account the job in `mesh_jobs`: the sender as `sent`, with
+//! the result and tokens the peer reports, the peer as `received`. A
+//! background watch follows jobs to their end on both sides, so the record
+//! is completed whether or not anyone streams the output.
+
+use chrono::Utc;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::agent::AgentStatus;
+use super::state_store::{MeshJobRecord, MeshJobs, MeshUsage, RemoteNode};
+use super::{AgentManager, Availability, ContainerManager, ContainerState, CreateAgentRequest, CreateContainerRequest, Fleet};
+use crate::config::NodeConfig;
+use crate::error::NodeError;
+use crate::pagination::{ListQuery, Page};
+
+/// Header a peer sends its node ID in, alongside the share key
+pub const NODE_ID_HEADER: &str = "x-node-id";
+
+/// Workspace agent executions received from peers run in
+pub const MESH_WORKSPACE: &str = "mesh";
+
+/// Where peers take jobs
+const PEER_JOBS_PATH: &str = "/api/v1/mesh/peer/jobs";
+
+const SENT: &str = "sent";
+const RECEIVED: &str = "received";
+const RUNNING: &str = "running";
+const COMPLETED: &str = "completed";
+const FAILED: &str = "failed";
+const INTERRUPTED: &str = "interrupted";
+
+/// How often running jobs are checked on
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Run time of a container job that does not set one
+const DEFAULT_CONTAINER_TIMEOUT: Duration = Duration::from_secs(3600);
+const MAX_CONTAINER_TIMEOUT: Duration = Duration::from_secs(24 * 3600);
+
+/// Lines of container output kept as the result
+const MAX_OUTPUT_LINES: usize = 1000;
+
+/// Longest a peer's output stream is followed
+const STREAM_TIMEOUT: Duration = Duration::from_secs(24 * 3600);
+
+/// Work one node can hand another
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MeshWork {
+    Agent {
+        request: CreateAgentRequest,
+    },
+    /// Runs to exit; the output is the result
+    Container {
+        request: CreateContainerRequest,
+        /// Seconds the container may run before it is stopped
+        #[serde(default, rename = "timeoutSecs")]
+        timeout_secs: Option<u64>,
+    },
+}
+
+impl MeshWork {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Agent { .. } => "agent",
+            Self::Container { .. } => "container",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DispatchRequest {
+    #[serde(flatten)]
+    pub work: MeshWork,
+    /// Paired node to run on; the idlest available one when not given
+    #[serde(default)]
+    pub node_id: Option<String>,
+}
+
+/// Container of a received job, while it runs
+struct ContainerRun {
+    container_id: String,
+    deadline: Instant,
+}
+
+pub struct Mesh {
+    jobs: MeshJobs,
+    fleet: Arc<Fleet>,
+    agents: Arc<AgentManager>,
+    containers: Arc<ContainerManager>,
+    config: Arc<RwLock<NodeConfig>>,
+    /// Containers of received jobs, by job ID
+    runs: Mutex<HashMap<String, ContainerRun>>,
+}
+
+impl Mesh {
+    pub fn new(
+        jobs: MeshJobs,
+        fleet: Arc<Fleet>,
+        agents: Arc<AgentManager>,
+        containers: Arc<ContainerManager>,
+        config: Arc<RwLock<NodeConfig>>,
+    ) -> Self {
+        Self {
+            jobs,
+            fleet,
+            agents,
+            containers,
+            config,
+            runs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn list(&self, query: &ListQuery) -> Result<Page<MeshJobRecord>, NodeError> {
+        self.jobs.list(query)
+    }
+
+    pub fn get(&self, id: &str) -> Result<MeshJobRecord, NodeError> {
+        self.jobs
+            .get(id)?
+            .ok_or_else(|| NodeError::NotFound(format!("Mesh job {} not found", id)))
+    }
+
+    pub fn usage(&self) -> Result<Vec<MeshUsage>, NodeError> {
+        Ok(self.jobs.usage()?)
+    }
+
+    // ---- Sending ----
+
+    /// Paired nodes that can take a job now, idlest first
+    async fn candidates(&self, node_id: Option<&str>) -> Result<Vec<RemoteNode>, NodeError> {
+        let nodes = match node_id {
+            Some(id) => vec![self.fleet.node(id)?],
+            None => self.fleet.list()?,
+        };
+        if nodes.is_empty() {
+            return Err(NodeError::NotFound("No paired nodes to send the job to".to_string()));
+        }
+
+        let checks = nodes.into_iter().map(|node| async move {
+            let availability: Option<Availability> = async {
+                let response = self
+                    .fleet
+                    .request(&node, Method::GET, "/api/v1/node/availability")
+                    .send()
+                    .await
+                    .ok()?;
+                response.json().await.ok()
+            }
+            .await;
+            availability
+                .filter(|availability| availability.available)
+                .map(|availability| (node, availability.cpu_percent))
+        });
+        let mut available: Vec<(RemoteNode, f32)> = futures_util::future::join_all(checks)
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+        available.sort_by(|a, b| a.1.total_cmp(&b.1));
+        Ok(available.into_iter().map(|(node, _)| node).collect())
+    }
+
+    /// Send work to a peer, trying the next one if a peer turns it down
+    pub async fn dispatch(&self, request: DispatchRequest, local_id: &str) -> Result<MeshJobRecord, NodeError> {
+        let candidates = self.candidates(request.node_id.as_deref()).await?;
+        let kind = request.work.kind();
+
+        let mut last_error = NodeError::NotRunning("No paired node is available to take the job".to_string());
+        for node in candidates {
+            let remote_id = match self.submit(&node, &request.work, local_id).await {
+                Ok(remote_id) => remote_id,
+                Err(e) => {
+                    log::warn!("{} did not take the {} job: {}", node.name, kind, e);
+                    last_error = e;
+                    continue;
+                }
+            };
+            let job = MeshJobRecord {
+                id: Uuid::new_v4().to_string(),
+                direction: SENT.to_string(),
+                peer_id: node.id.clone(),
+                kind: kind.to_string(),
+                remote_id: Some(remote_id),
+                status: RUNNING.to_string(),
+                tokens_used: 0,
+                result: None,
+                error: None,
+                started_at: Utc::now().to_rfc3339(),
+                finished_at: None,
+                duration_ms: None,
+            };
+            self.jobs.start(&job)?;
+            log::info!("Sent {} job {} to {}", kind, job.id, node.name);
+            return Ok(job);
+        }
+        Err(last_error)
+    }
+
+    async fn submit(&self, node: &RemoteNode, work: &MeshWork, local_id: &str) -> Result<String, NodeError> {
+        let response = self
+            .fleet
+            .request(node, Method::POST, PEER_JOBS_PATH)
+            .header(NODE_ID_HEADER, local_id)
+            .json(work)
+            .send()
+            .await
+            .map_err(|e| NodeError::request(&format!("Failed to reach {}", node.name), e))?;
+        let body = answer(node, response).await?;
+        body["job"]["id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| NodeError::Upstream(format!("{} did not return a job", node.name)))
+    }
+
+    /// A sent job, with the node running it and its ID there
+    fn sent(&self, id: &str) -> Result<(MeshJobRecord, RemoteNode, String), NodeError> {
+        let job = self.get(id)?;
+        let remote_id = match (job.direction.as_str(), &job.remote_id) {
+            (SENT, Some(remote_id)) => remote_id.clone(),
+            _ => return Err(NodeError::Invalid(format!("Mesh job {} was not sent from this node", id))),
+        };
+        let node = self.fleet.node(&job.peer_id)?;
+        Ok((job, node, remote_id))
+    }
+
+    /// The output of a sent job as the peer streams it, as server-sent events
+    pub async fn stream(&self, id: &str, local_id: &str) -> Result<reqwest::Response, NodeError> {
+        let (_, node, remote_id) = self.sent(id)?;
+        let response = self
+            .fleet
+            .request(&node, Method::GET, &format!("{}/{}/stream", PEER_JOBS_PATH, remote_id))
+            .header(NODE_ID_HEADER, local_id)
+            .timeout(STREAM_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| NodeError::request(&format!("Failed to reach {}", node.name), e))?;
+        if !response.status().is_success() {
+            return Err(answer(&node, response).await.err().unwrap_or_else(|| {
+                NodeError::Upstream(format!("{} could not stream the job", node.name))
+            }));
+        }
+        Ok(response)
+    }
+
+    /// Ask the peer to stop a sent job; the watch records how it ended
+    pub async fn cancel(&self, id: &str, local_id: &str) -> Result<(), NodeError> {
+        let (job, node, remote_id) = self.sent(id)?;
+        if job.status != RUNNING {
+            return Err(NodeError::Conflict(format!("Mesh job {} has already finished", id)));
+        }
+        let response = self
+            .fleet
+            .request(&node, Method::DELETE, &format!("{}/{}", PEER_JOBS_PATH, remote_id))
+            .header(NODE_ID_HEADER, local_id)
+            .send()
+            .await
+            .map_err(|e| NodeError::request(&format!("Failed to reach {}", node.name), e))?;
+        answer(&node, response).await.map(|_| ())
+    }
+
+    /// Bring a sent job's record up to date with the peer
+    async fn follow_sent(&self, job: &MeshJobRecord, local_id: &str) -> Result<(), NodeError> {
+        let (_, node, remote_id) = self.sent(&job.id)?;
+        let response = self
+            .fleet
+            .request(&node, Method::GET, &format!("{}/{}", PEER_JOBS_PATH, remote_id))
+            .header(NODE_ID_HEADER, local_id)
+            .send()
+            .await
+            .map_err(|e| NodeError::request(&format!("Failed to reach {}", node.name), e))?;
+        let remote: MeshJobRecord = match answer(&node, response).await {
+            Ok(body) => serde_json::from_value(body["job"].clone())
+                .map_err(|e| NodeError::Upstream(format!("Unreadable job from {}: {}", node.name, e)))?,
+            // The peer lost the job, e.g. its state was reset
+            Err(NodeError::NotFound(_)) => {
+                self.jobs.finish(
+                    &job.id,
+                    INTERRUPTED,
+                    0,
+                    None,
+                    Some("The node running the job no longer knows it"),
+                    elapsed_ms(&job.started_at),
+                )?;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        if remote.status == RUNNING {
+            return Ok(());
+        }
+        self.jobs.finish(
+            &job.id,
+            &remote.status,
+            remote.tokens_used,
+            remote.result.as_deref(),
+            remote.error.as_deref(),
+            elapsed_ms(&job.started_at),
+        )?;
+        log::info!("{} job {} on {} {}", job.kind, job.id, node.name, remote.status);
+        Ok(())
+    }
+
+    // ---- Receiving ----
+
+    /// Run work for a peer whose share key has been checked
+    pub async fn accept(&self, work: MeshWork, peer_id: &str) -> Result<MeshJobRecord, NodeError> {
+        let kind = work.kind();
+        let mut job = MeshJobRecord {
+            id: String::new(),
+            direction: RECEIVED.to_string(),
+            peer_id: peer_id.to_string(),
+            kind: kind.to_string(),
+            remote_id: None,
+            status: RUNNING.to_string(),
+            tokens_used: 0,
+            result: None,
+            error: None,
+            started_at: Utc::now().to_rfc3339(),
+            finished_at: None,
+            duration_ms: None,
+        };
+
+        match work {
+            // The execution ID is the job ID, so the agent endpoints find it too
+            MeshWork::Agent { request } => {
+                job.id = self.agents.create_execution(MESH_WORKSPACE, request).await?.id;
+                self.jobs.start(&job)?;
+            }
+            MeshWork::Container { request, timeout_secs } => {
+                job.id = Uuid::new_v4().to_string();
+                let timeout = timeout_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_CONTAINER_TIMEOUT)
+                    .min(MAX_CONTAINER_TIMEOUT);
+                let container_id = self.start_container(&job.id, request).await?;
+                self.jobs.start(&job)?;
+                self.runs.lock().unwrap().insert(
+                    job.id.clone(),
+                    ContainerRun {
+                        container_id,
+                        deadline: Instant::now() + timeout,
+                    },
+                );
+            }
+        }
+        log::info!("Running {} job {} for node {}", kind, job.id, peer_id);
+        Ok(job)
+    }
+
+    async fn start_container(&self, job_id: &str, mut request: CreateContainerRequest) -> Result<String, NodeError> {
+        // A peer's job gets no access to this machine's files, ports or
+        // networks; the host network would reach the node's own API
+        if request.volumes.as_ref().is_some_and(|volumes| !volumes.is_empty()) {
+            return Err(NodeError::Policy("Jobs from other nodes may not mount host paths".to_string()));
+        }
+        if request.ports.as_ref().is_some_and(|ports| !ports.is_empty()) {
+            return Err(NodeError::Policy("Jobs from other nodes may not publish ports".to_string()));
+        }
+        if request.network.as_deref().is_some_and(|network| !network.is_empty()) {
+            return Err(NodeError::Policy("Jobs from other nodes may not choose a network".to_string()));
+        }
+        request.network = None;
+        request.network_aliases = None;
+        if !self.config.read().await.discovery.share_gpus {
+            request.gpu = None;
+            request.gpu_devices = None;
+        }
+        request.name = format!("otherthing-mesh-{}", &job_id[..8]);
+        request.restart_policy = None;
+        request
+            .labels
+            .get_or_insert_with(HashMap::new)
+            .insert("otherthing.mesh-job".to_string(), job_id.to_string());
+
+        let container_id = self.containers.create_container(request).await?;
+        if let Err(e) = self.containers.start_container(&container_id).await {
+            let _ = self.containers.remove_container(&container_id, true).await;
+            return Err(e.into());
+        }
+        Ok(container_id)
+    }
+
+    /// A received job, if it came from `peer_id`
+    pub fn received(&self, id: &str, peer_id: &str) -> Result<MeshJobRecord, NodeError> {
+        self.jobs
+            .get(id)?
+            .filter(|job| job.direction == RECEIVED && job.peer_id == peer_id)
+            .ok_or_else(|| NodeError::NotFound(format!("Mesh job {} not found", id)))
+    }
+
+    /// Container of a received job that is still running
+    pub fn container_of(&self, id: &str) -> Option<String> {
+        self.runs.lock().unwrap().get(id).map(|run| run.container_id.clone())
+    }
+
+    /// Stop a received job; the watch records how it ended
+    pub async fn stop_received(&self, job: &MeshJobRecord) -> Result<(), NodeError> {
+        if job.status != RUNNING {
+            return Err(NodeError::Conflict(format!("Mesh job {} has already finished", job.id)));
+        }
+        match job.kind.as_str() {
+            "agent" => self.agents.cancel_execution(&job.id).await,
+            _ => match self.container_of(&job.id) {
+                Some(container_id) => Ok(self.containers.stop_container(&container_id, Some(10)).await?),
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Bring a received job's record up to date with its execution or container
+    async fn follow_received(&self, job: &MeshJobRecord) -> Result<(), NodeError> {
+        let duration_ms = elapsed_ms(&job.started_at);
+
+        if job.kind == "agent" {
+            let Some(execution) = self.agents.get_execution(&job.id).await else {
+                self.jobs.finish(&job.id, INTERRUPTED, 0, None, Some("The execution was lost"), duration_ms)?;
+                return Ok(());
+            };
+            let status = match execution.status {
+                AgentStatus::Pending | AgentStatus::Running | AgentStatus::PullingModel => return Ok(()),
+                AgentStatus::Completed => COMPLETED,
+                AgentStatus::Failed | AgentStatus::Blocked => FAILED,
+            };
+            self.jobs.finish(
+                &job.id,
+                status,
+                execution.tokens_used as u64,
+                execution.result.as_deref(),
+                execution.error.as_deref(),
+                duration_ms,
+            )?;
+            log::info!("Agent job {} for node {} {}", job.id, job.peer_id, status);
+            return Ok(());
+        }
+
+        // Container runs do not survive a restart of the node
+        let Some((container_id, deadline)) = self
+            .runs
+            .lock()
+            .unwrap()
+            .get(&job.id)
+            .map(|run| (run.container_id.clone(), run.deadline))
+        else {
+            self.jobs.finish(&job.id, INTERRUPTED, 0, None, Some("The node restarted"), duration_ms)?;
+            return Ok(());
+        };
+
+        let info = self.containers.inspect_container(&container_id).await?;
+        let timed_out = Instant::now() >= deadline;
+        let running = matches!(
+            info.state,
+            ContainerState::Creating | ContainerState::Created | ContainerState::Running | ContainerState::Paused
+        );
+        if running && !timed_out {
+            return Ok(());
+        }
+        if running {
+            let _ = self.containers.stop_container(&container_id, Some(10)).await;
+        }
+
+        let output = self.containers.get_logs(&container_id, Some(MAX_OUTPUT_LINES)).await.ok();
+        let (status, error) = match (timed_out && running, info.exit_code) {
+            (true, _) => (FAILED, Some("Timed out".to_string())),
+            (false, Some(0)) => (COMPLETED, None),
+            (false, Some(code)) => (FAILED, Some(format!("Exited with code {}", code))),
+            (false, None) => (FAILED, Some("Container stopped".to_string())),
+        };
+        self.jobs.finish(&job.id, status, 0, output.as_deref(), error.as_deref(), duration_ms)?;
+        self.runs.lock().unwrap().remove(&job.id);
+        if let Err(e) = self.containers.remove_container(&container_id, true).await {
+            log::warn!("Failed to remove container of mesh job {}: {}", job.id, e);
+        }
+        log::info!("Container job {} for node {} {}", job.id, job.peer_id, status);
+        Ok(())
+    }
+
+    /// Follow running jobs in both directions for the life of the app
+    pub async fn watch(self: Arc<Self>, node_id: Arc<tokio::sync::RwLock<String>>) {
+        loop {
+            tokio::time::sleep(WATCH_INTERVAL).await;
+            let local_id = node_id.read().await.clone();
+            for job in self.jobs.running(SENT).unwrap_or_default() {
+                // Unreachable peers are tried again on the next round
+                if let Err(e) = self.follow_sent(&job, &local_id).await {
+                    log::debug!("Could not check mesh job {}: {}", job.id, e);
+                }
+            }
+            for job in self.jobs.running(RECEIVED).unwrap_or_default() {
+                if let Err(e) = self.follow_received(&job).await {
+                    log::warn!("Could not check mesh job {}: {}", job.id, e);
+                }
+            }
+        }
+    }
+}
+
+/// The JSON answer of a peer, or its error in the peer's own category
+async fn answer(node: &RemoteNode, response: reqwest::Response) -> Result<Value, NodeError> {
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or_default();
+    if status.is_success() {
+        return Ok(body);
+    }
+    let message = format!(
+        "{}: {}",
+        node.name,
+        body["error"].as_str().map(str::to_string).unwrap_or_else(|| status.to_string())
+    );
+    Err(match status {
+        StatusCode::SERVICE_UNAVAILABLE => NodeError::NotRunning(message),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => NodeError::Policy(message),
+        StatusCode::NOT_FOUND => NodeError::NotFound(message),
+        StatusCode::BAD_REQUEST => NodeError::Invalid(message),
+        StatusCode::CONFLICT => NodeError::Conflict(message),
+        _ => NodeError::Upstream(message),
+    })
+}
+
+fn elapsed_ms(started_at: &str) -> u64 {
+    chrono::DateTime::parse_from_rfc3339(started_at)
+        .map(|started| (Utc::now() - started.with_timezone(&Utc)).num_milliseconds().max(0) as u64)
+        .unwrap_or_default()
+}
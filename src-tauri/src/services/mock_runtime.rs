@@ -0,0 +1,625 @@
+//! Mock Container Runtime
+//!
+//! An in-memory `ContainerRuntime` for CI machines without Docker and for
+//! demos, enabled with the `mock-runtime` feature. Nothing is executed:
+//! containers move through their states, write scripted output and exit
+//! with a scripted code after a scripted time.
+//!
+//! A script is registered per image with `MockRuntime::with_script`, or
+//! given per container through labels, which also works through the API:
+//!
+//! - `otherthing.mock.exit-code`: exit code; without it the container runs
+//!   until stopped
+//! - `otherthing.mock.run-ms`: milliseconds before it exits (default 0)
+//! - `otherthing.mock.output`: lines written to stdout, separated by `\n`
+//!
+//! `RuntimeSelector` falls back to it when no real runtime is available.
+
+#![cfg(feature = "mock-runtime")]
+
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use super::container_runtime::{
+    BuildImageRequest, ContainerInfo, ContainerRuntime, ContainerSpec, ContainerState, ContainerStats,
+    DiskUsage, ExecOutput, ImageInfo, LogLine, LogStream, NetworkInfo, PruneRequest, PruneResult,
+    ResourceLimits, Result, RuntimeError, RuntimeInfo, RuntimeType, UsageSummary,
+};
+
+pub const EXIT_CODE_LABEL: &str = "otherthing.mock.exit-code";
+pub const RUN_MS_LABEL: &str = "otherthing.mock.run-ms";
+pub const OUTPUT_LABEL: &str = "otherthing.mock.output";
+
+/// Label marking containers created by this node, the only ones pruned
+const MANAGED_BY: (&str, &str) = ("managed_by", "otherthing-node");
+
+/// Size reported for every pulled image
+const IMAGE_SIZE: i64 = 5 * 1024 * 1024;
+
+/// How often waits and log followers look for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What a container does once started
+#[derive(Debug, Clone, Default)]
+pub struct MockScript {
+    /// Exit code; `None` runs until stopped
+    pub exit_code: Option<i32>,
+    /// Time from start to exit
+    pub run_for: Duration,
+    /// Lines written to stdout while running
+    pub output: Vec<String>,
+}
+
+impl MockScript {
+    /// The image's script with the container's labels applied over it
+    fn with_labels(mut self, labels: &HashMap<String, String>) -> Self {
+        if let Some(code) = labels.get(EXIT_CODE_LABEL).and_then(|c| c.parse().ok()) {
+            self.exit_code = Some(code);
+        }
+        if let Some(ms) = labels.get(RUN_MS_LABEL).and_then(|ms| ms.parse().ok()) {
+            self.run_for = Duration::from_millis(ms);
+        }
+        if let Some(output) = labels.get(OUTPUT_LABEL) {
+            self.output = output.split('\n').map(String::from).collect();
+        }
+        self
+    }
+}
+
+struct MockContainer {
+    info: ContainerInfo,
+    script: MockScript,
+    logs: Vec<LogLine>,
+    /// Bumped on every start, so the exit of an earlier run is not applied
+    /// to a later one
+    run: u64,
+}
+
+impl MockContainer {
+    fn log(&mut self, stream: LogStream, message: impl Into<String>) {
+        self.logs.push(LogLine {
+            stream,
+            timestamp: Some(Utc::now().to_rfc3339()),
+            message: message.into(),
+        });
+    }
+
+    fn is_running(&self) -> bool {
+        matches!(self.info.state, ContainerState::Running | ContainerState::Paused)
+    }
+
+    fn finish(&mut self, exit_code: i32) {
+        self.info.state = ContainerState::Exited;
+        self.info.exit_code = Some(exit_code);
+        self.info.finished = Some(Utc::now().timestamp());
+        self.info.pid = None;
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    containers: HashMap<String, MockContainer>,
+    images: HashMap<String, ImageInfo>,
+    networks: HashMap<String, NetworkInfo>,
+    scripts: HashMap<String, MockScript>,
+    next_pid: u32,
+}
+
+impl MockState {
+    /// Key of a container given by ID, ID prefix or name
+    fn resolve(&self, id: &str) -> Result<String> {
+        if self.containers.contains_key(id) {
+            return Ok(id.to_string());
+        }
+        self.containers
+            .iter()
+            .find(|(key, c)| c.info.name == id || (id.len() >= 12 && key.starts_with(id)))
+            .map(|(key, _)| key.clone())
+            .ok_or_else(|| RuntimeError::ContainerNotFound(id.to_string()))
+    }
+
+    fn container(&mut self, id: &str) -> Result<&mut MockContainer> {
+        let key = self.resolve(id)?;
+        Ok(self.containers.get_mut(&key).expect("resolved container exists"))
+    }
+
+    fn network_key(&self, name: &str) -> Result<String> {
+        self.networks
+            .iter()
+            .find(|(id, n)| *id == name || n.name == name)
+            .map(|(id, _)| id.clone())
+            .ok_or_else(|| RuntimeError::OperationFailed(format!("Network not found: {}", name)))
+    }
+}
+
+/// `name` and `name:latest` are the same image
+fn normalize(reference: &str) -> String {
+    let name = reference.rsplit('/').next().unwrap_or(reference);
+    if name.contains(':') || name.contains('@') {
+        reference.to_string()
+    } else {
+        format!("{}:latest", reference)
+    }
+}
+
+fn new_id() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+pub struct MockRuntime {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl Default for MockRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockRuntime {
+    pub fn new() -> Self {
+        let mut state = MockState { next_pid: 1000, ..Default::default() };
+        let id = new_id();
+        state.networks.insert(
+            id.clone(),
+            NetworkInfo {
+                id,
+                name: "bridge".to_string(),
+                driver: "bridge".to_string(),
+                internal: false,
+                labels: HashMap::new(),
+                containers: Vec::new(),
+            },
+        );
+        Self { state: Arc::new(Mutex::new(state)) }
+    }
+
+    /// The runtime `RuntimeSelector` hands out: `hello-world` is already
+    /// pulled, prints a greeting and exits
+    pub fn demo() -> Self {
+        Self::new().with_image("hello-world").with_script(
+            "hello-world",
+            MockScript {
+                exit_code: Some(0),
+                run_for: Duration::from_millis(500),
+                output: vec!["Hello from the mock container runtime!".to_string()],
+            },
+        )
+    }
+
+    /// Script containers of `image`; labels on a container still apply over it
+    pub fn with_script(self, image: &str, script: MockScript) -> Self {
+        self.state.lock().unwrap().scripts.insert(normalize(image), script);
+        self
+    }
+
+    /// Make `image` available without pulling it
+    pub fn with_image(self, image: &str) -> Self {
+        self.add_image(image);
+        self
+    }
+
+    fn add_image(&self, reference: &str) {
+        let reference = normalize(reference);
+        let mut state = self.state.lock().unwrap();
+        state.images.entry(reference.clone()).or_insert_with(|| ImageInfo {
+            id: format!("sha256:{}", new_id()),
+            repo_tags: vec![reference],
+            repo_digests: Vec::new(),
+            size: IMAGE_SIZE,
+            created: Utc::now().timestamp(),
+        });
+    }
+
+    /// Mark a run as exited once its script says so
+    fn schedule_exit(&self, key: String, run: u64, script: &MockScript) {
+        let Some(exit_code) = script.exit_code else {
+            return;
+        };
+        let state = Arc::clone(&self.state);
+        let run_for = script.run_for;
+        tokio::spawn(async move {
+            tokio::time::sleep(run_for).await;
+            let mut state = state.lock().unwrap();
+            if let Some(container) = state.containers.get_mut(&key) {
+                if container.run == run && container.is_running() {
+                    container.finish(exit_code);
+                }
+            }
+        });
+    }
+
+    fn stop(&self, id: &str, exit_code: i32) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let container = state.container(id)?;
+        if container.is_running() {
+            container.finish(exit_code);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ContainerRuntime for MockRuntime {
+    async fn info(&self) -> Result<RuntimeInfo> {
+        Ok(RuntimeInfo {
+            runtime_type: RuntimeType::Mock,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            api_version: None,
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            root_dir: None,
+            cgroup_driver: None,
+        })
+    }
+
+    async fn is_available(&self) -> bool {
+        true
+    }
+
+    // ============ Container Operations ============
+
+    async fn create_container(&self, spec: &ContainerSpec) -> Result<String> {
+        let image = normalize(&spec.image);
+        let mut state = self.state.lock().unwrap();
+        if !state.images.contains_key(&image) {
+            return Err(RuntimeError::ImageNotFound(format!("{} (pull it first)", spec.image)));
+        }
+        if !spec.name.is_empty() && state.containers.values().any(|c| c.info.name == spec.name) {
+            return Err(RuntimeError::OperationFailed(format!("Container name {} is already in use", spec.name)));
+        }
+
+        let id = new_id();
+        let labels = spec.labels.clone().unwrap_or_default();
+        let script = state.scripts.get(&image).cloned().unwrap_or_default().with_labels(&labels);
+        let name = if spec.name.is_empty() { format!("mock-{}", &id[..12]) } else { spec.name.clone() };
+        let container = MockContainer {
+            info: ContainerInfo {
+                id: id.clone(),
+                name,
+                image: spec.image.clone(),
+                state: ContainerState::Created,
+                created: Utc::now().timestamp(),
+                started: None,
+                finished: None,
+                exit_code: None,
+                pid: None,
+                ports: spec.ports.clone().unwrap_or_default(),
+                mounts: spec.mounts.clone().unwrap_or_default(),
+                labels,
+                health: None,
+            },
+            script,
+            logs: Vec::new(),
+            run: 0,
+        };
+        state.containers.insert(id.clone(), container);
+        Ok(id)
+    }
+
+    async fn start_container(&self, id: &str) -> Result<()> {
+        let (key, run, script) = {
+            let mut state = self.state.lock().unwrap();
+            state.next_pid += 1;
+            let pid = state.next_pid;
+            let key = state.resolve(id)?;
+            let container = state.containers.get_mut(&key).expect("resolved container exists");
+            if container.is_running() {
+                return Ok(());
+            }
+            container.run += 1;
+            container.info.state = ContainerState::Running;
+            container.info.started = Some(Utc::now().timestamp());
+            container.info.finished = None;
+            container.info.exit_code = None;
+            container.info.pid = Some(pid);
+            let image = container.info.image.clone();
+            container.log(LogStream::Stdout, format!("mock: started {}", image));
+            for line in container.script.output.clone() {
+                container.log(LogStream::Stdout, line);
+            }
+            (key, container.run, container.script.clone())
+        };
+        self.schedule_exit(key, run, &script);
+        Ok(())
+    }
+
+    async fn stop_container(&self, id: &str, _timeout: Option<u32>) -> Result<()> {
+        // Like a process that exits on SIGTERM
+        self.stop(id, 143)
+    }
+
+    async fn kill_container(&self, id: &str, _signal: Option<&str>) -> Result<()> {
+        self.stop(id, 137)
+    }
+
+    async fn remove_container(&self, id: &str, force: bool) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let key = state.resolve(id)?;
+        if state.containers[&key].is_running() && !force {
+            return Err(RuntimeError::OperationFailed(format!(
+                "Container {} is running; stop it or remove it with force",
+                id
+            )));
+        }
+        state.containers.remove(&key);
+        for network in state.networks.values_mut() {
+            network.containers.retain(|c| *c != key);
+        }
+        Ok(())
+    }
+
+    async fn pause_container(&self, id: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let container = state.container(id)?;
+        if container.info.state != ContainerState::Running {
+            return Err(RuntimeError::OperationFailed(format!("Container {} is not running", id)));
+        }
+        container.info.state = ContainerState::Paused;
+        Ok(())
+    }
+
+    async fn unpause_container(&self, id: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let container = state.container(id)?;
+        if container.info.state != ContainerState::Paused {
+            return Err(RuntimeError::OperationFailed(format!("Container {} is not paused", id)));
+        }
+        container.info.state = ContainerState::Running;
+        Ok(())
+    }
+
+    async fn restart_container(&self, id: &str, timeout: Option<u32>) -> Result<()> {
+        self.stop_container(id, timeout).await?;
+        self.start_container(id).await
+    }
+
+    async fn rename_container(&self, id: &str, name: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let key = state.resolve(id)?;
+        if state.containers.iter().any(|(k, c)| *k != key && c.info.name == name) {
+            return Err(RuntimeError::OperationFailed(format!("Container name {} is already in use", name)));
+        }
+        state.containers.get_mut(&key).expect("resolved container exists").info.name = name.to_string();
+        Ok(())
+    }
+
+    async fn update_resources(&self, id: &str, _resources: &ResourceLimits) -> Result<()> {
+        self.state.lock().unwrap().resolve(id).map(|_| ())
+    }
+
+    async fn inspect_container(&self, id: &str) -> Result<ContainerInfo> {
+        let mut state = self.state.lock().unwrap();
+        Ok(state.container(id)?.info.clone())
+    }
+
+    async fn list_containers(&self, all: bool) -> Result<Vec<ContainerInfo>> {
+        let state = self.state.lock().unwrap();
+        let mut containers: Vec<ContainerInfo> = state
+            .containers
+            .values()
+            .filter(|c| all || c.is_running())
+            .map(|c| c.info.clone())
+            .collect();
+        containers.sort_by(|a, b| b.created.cmp(&a.created));
+        Ok(containers)
+    }
+
+    async fn logs(&self, id: &str, tail: Option<usize>, follow: bool) -> Result<String> {
+        if follow {
+            self.wait_container(id).await?;
+        }
+        let mut state = self.state.lock().unwrap();
+        let logs = &state.container(id)?.logs;
+        let skip = logs.len().saturating_sub(tail.unwrap_or(100));
+        Ok(logs[skip..].iter().map(|l| format!("{}\n", l.message)).collect())
+    }
+
+    async fn follow_logs(&self, id: &str, tail: Option<usize>, tx: mpsc::Sender<LogLine>) -> Result<()> {
+        let mut sent = {
+            let mut state = self.state.lock().unwrap();
+            let logs = &state.container(id)?.logs;
+            logs.len().saturating_sub(tail.unwrap_or(100))
+        };
+        loop {
+            let (lines, running) = {
+                let mut state = self.state.lock().unwrap();
+                let container = state.container(id)?;
+                (container.logs[sent..].to_vec(), container.is_running())
+            };
+            sent += lines.len();
+            for line in lines {
+                if tx.send(line).await.is_err() {
+                    return Ok(());
+                }
+            }
+            if !running || tx.is_closed() {
+                return Ok(());
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    async fn stats(&self, id: &str) -> Result<ContainerStats> {
+        let mut state = self.state.lock().unwrap();
+        let container = state.container(id)?;
+        if !container.is_running() {
+            return Err(RuntimeError::OperationFailed(format!("Container {} is not running", id)));
+        }
+        let uptime = Utc::now().timestamp() - container.info.started.unwrap_or_default();
+        Ok(ContainerStats {
+            cpu_usage_usec: uptime.max(0) as u64 * 1000,
+            pids: 1,
+            ..Default::default()
+        })
+    }
+
+    async fn exec(&self, id: &str, cmd: &[String], _tty: bool) -> Result<ExecOutput> {
+        let mut state = self.state.lock().unwrap();
+        let container = state.container(id)?;
+        if !container.is_running() {
+            return Err(RuntimeError::OperationFailed(format!("Container {} is not running", id)));
+        }
+        // `echo` answers with its arguments; anything else succeeds silently
+        let stdout = match cmd.split_first() {
+            Some((program, args)) if program == "echo" => format!("{}\n", args.join(" ")),
+            _ => String::new(),
+        };
+        Ok(ExecOutput { exit_code: 0, stdout, stderr: String::new() })
+    }
+
+    async fn wait_container(&self, id: &str) -> Result<i32> {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                let container = state.container(id)?;
+                if !container.is_running() {
+                    return Ok(container.info.exit_code.unwrap_or(0));
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    // ============ Image Operations ============
+
+    async fn pull_image(&self, reference: &str) -> Result<()> {
+        self.add_image(reference);
+        Ok(())
+    }
+
+    async fn list_images(&self) -> Result<Vec<ImageInfo>> {
+        Ok(self.state.lock().unwrap().images.values().cloned().collect())
+    }
+
+    async fn remove_image(&self, reference: &str, force: bool) -> Result<()> {
+        let reference = normalize(reference);
+        let mut state = self.state.lock().unwrap();
+        if !state.images.contains_key(&reference) {
+            return Err(RuntimeError::ImageNotFound(reference));
+        }
+        if !force && state.containers.values().any(|c| normalize(&c.info.image) == reference) {
+            return Err(RuntimeError::OperationFailed(format!("Image {} is used by a container", reference)));
+        }
+        state.images.remove(&reference);
+        Ok(())
+    }
+
+    async fn image_exists(&self, reference: &str) -> Result<bool> {
+        Ok(self.state.lock().unwrap().images.contains_key(&normalize(reference)))
+    }
+
+    async fn push_image(&self, reference: &str) -> Result<()> {
+        if self.image_exists(reference).await? {
+            Ok(())
+        } else {
+            Err(RuntimeError::ImageNotFound(reference.to_string()))
+        }
+    }
+
+    async fn build_image(&self, request: &BuildImageRequest, progress: Option<mpsc::Sender<String>>) -> Result<String> {
+        if let Some(tx) = progress {
+            let _ = tx.send(format!("mock: building {}", request.tag)).await;
+        }
+        self.add_image(&request.tag);
+        let state = self.state.lock().unwrap();
+        Ok(state.images[&normalize(&request.tag)].id.clone())
+    }
+
+    // ============ Maintenance ============
+
+    async fn disk_usage(&self) -> Result<DiskUsage> {
+        let state = self.state.lock().unwrap();
+        let images = state.images.len();
+        let stopped = state.containers.values().filter(|c| !c.is_running()).count();
+        Ok(DiskUsage {
+            images: UsageSummary {
+                count: images,
+                size: images as i64 * IMAGE_SIZE,
+                reclaimable: 0,
+            },
+            containers: UsageSummary {
+                count: state.containers.len(),
+                size: 0,
+                reclaimable: stopped as i64,
+            },
+            ..Default::default()
+        })
+    }
+
+    async fn prune(&self, request: &PruneRequest) -> Result<PruneResult> {
+        let mut result = PruneResult::default();
+        if request.containers {
+            let mut state = self.state.lock().unwrap();
+            let stopped: Vec<String> = state
+                .containers
+                .iter()
+                .filter(|(_, c)| {
+                    !c.is_running() && c.info.labels.get(MANAGED_BY.0).map(String::as_str) == Some(MANAGED_BY.1)
+                })
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in stopped {
+                state.containers.remove(&id);
+                result.containers_deleted.push(id);
+            }
+        }
+        Ok(result)
+    }
+
+    // ============ Network Operations ============
+
+    async fn create_network(&self, name: &str, internal: bool, labels: &HashMap<String, String>) -> Result<String> {
+        let mut state = self.state.lock().unwrap();
+        if state.networks.values().any(|n| n.name == name) {
+            return Err(RuntimeError::OperationFailed(format!("Network {} already exists", name)));
+        }
+        let id = new_id();
+        state.networks.insert(
+            id.clone(),
+            NetworkInfo {
+                id: id.clone(),
+                name: name.to_string(),
+                driver: "bridge".to_string(),
+                internal,
+                labels: labels.clone(),
+                containers: Vec::new(),
+            },
+        );
+        Ok(id)
+    }
+
+    async fn list_networks(&self) -> Result<Vec<NetworkInfo>> {
+        Ok(self.state.lock().unwrap().networks.values().cloned().collect())
+    }
+
+    async fn remove_network(&self, name: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let key = state.network_key(name)?;
+        state.networks.remove(&key);
+        Ok(())
+    }
+
+    async fn connect_network(&self, network: &str, container: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let container = state.resolve(container)?;
+        let key = state.network_key(network)?;
+        let network = state.networks.get_mut(&key).expect("resolved network exists");
+        if !network.containers.contains(&container) {
+            network.containers.push(container);
+        }
+        Ok(())
+    }
+
+    async fn disconnect_network(&self, network: &str, container: &str, _force: bool) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let container = state.resolve(container)?;
+        let key = state.network_key(network)?;
+        state.networks.get_mut(&key).expect("resolved network exists").containers.retain(|c| *c != container);
+        Ok(())
+    }
+}
@@ -1,9 +1,43 @@
 pub mod agent;
+pub mod agent_memory;
+pub mod agent_store;
+pub mod agent_templates;
+pub mod agent_tools;
+pub mod audit;
+pub mod backup;
+pub mod bandwidth;
 pub mod container;
 pub mod container_runtime;
+pub mod deployment;
+pub mod discovery;
+pub mod download;
+pub mod earnings;
+pub mod fleet;
+pub mod gpu_credentials;
+pub mod gpu_offers;
+pub mod gpu_provider;
+pub mod gpu_spend;
 pub mod hardware;
+pub mod identity;
+pub mod idle;
+pub mod image_policy;
 pub mod ipfs;
+pub mod ipfs_cluster;
+pub mod keychain;
+pub mod llm_provider;
+pub mod mesh;
 pub mod ollama;
+pub mod payments;
+pub mod pricing;
+pub mod pubsub;
+pub mod rag;
+pub mod redact;
+pub mod registry_auth;
+pub mod remote_compute;
+pub mod resources;
+pub mod state_store;
+pub mod storage;
+pub mod workspace;
 
 #[cfg(feature = "container-runtime")]
 pub mod docker_runtime;
@@ -11,9 +45,52 @@ pub mod docker_runtime;
 #[cfg(all(target_os = "linux", feature = "native-containers"))]
 pub mod native_runtime;
 
-pub use agent::{AgentManager, AgentExecution, CreateAgentRequest};
-pub use container::{ContainerManager, ContainerInfo, ContainerStatus, CreateContainerRequest, RuntimeInfo, ExecResult};
-pub use container_runtime::{ContainerRuntime, ContainerSpec, RuntimeSelector, RuntimeType};
+#[cfg(all(target_os = "linux", feature = "native-containers"))]
+pub mod native_logs;
+
+#[cfg(all(target_os = "linux", feature = "native-containers"))]
+pub mod native_network;
+
+#[cfg(all(target_os = "linux", feature = "native-containers"))]
+pub mod oci_image;
+
+#[cfg(feature = "embedded-ipfs")]
+pub mod embedded_ipfs;
+
+#[cfg(feature = "mock-runtime")]
+pub mod mock_runtime;
+
+pub use agent::{AgentManager, AgentExecution, AgentRole, CreateAgentRequest};
+pub use agent_memory::{AgentMemory, MemoryEntry};
+pub use agent_templates::AgentTemplate;
+pub use agent_store::{AgentStore, ExecutionQuery};
+pub use agent_tools::{SandboxConfig, ToolContext, ToolRegistry};
+pub use audit::{AuditKind, AuditLog, AuditQuery};
+pub use bandwidth::{BandwidthMeter, BandwidthReport};
+pub use container::{BuildImageRequest, ContainerManager, ContainerInfo, ContainerState, CreateContainerRequest, DiskUsage, PruneRequest, PruneResult, UpdateContainerRequest, LogLine, NetworkInfo, RuntimeInfo, ExecOutput};
+pub use container_runtime::{ContainerRuntime, ContainerSpec, HealthCheck, HealthStatus, RestartPolicy, RuntimeSelector, RuntimeType};
+pub use deployment::{AppDeployment, AppSpec, AppStatus};
+pub use discovery::{Discovery, Peer};
+pub use earnings::{EarningsLedger, EarningsQuery, EarningsSummary, ExportFormat, NewEarning};
+pub use fleet::{AddRemoteNodeRequest, Fleet, RemoteQuery};
+pub use gpu_provider::{CustomGpuProviderConfig, GpuInstance, GpuOffer, GpuProvider, GpuProviderInfo, RentRequest};
+pub use gpu_offers::{GpuOfferCache, OfferFilter, OfferSort};
+pub use gpu_spend::{GpuSpendTracker, SpendSummary};
 pub use hardware::HardwareDetector;
-pub use ipfs::IpfsManager;
+pub use identity::NodeIdentity;
+pub use idle::{Availability, IdleMonitor};
+pub use image_policy::ImagePolicy;
+pub use ipfs::{generate_swarm_key, parse_swarm_key, IpfsManager, MAX_CAT_SIZE, MAX_GET_SIZE};
+pub use ipfs_cluster::ClusterFollower;
+pub use llm_provider::{BackendStatus, ProviderConfig, ProviderInfo, ProviderPrice, ProviderRegistry};
+pub use mesh::{DispatchRequest, Mesh, MeshWork};
 pub use ollama::OllamaManager;
+pub use payments::{PaymentMonitor, Reconciliation};
+pub use pricing::{DemandSignal, Pricing, PricingStatus, Rates};
+pub use pubsub::{AnnouncedNode, JobOffer, Pubsub};
+pub use rag::{Document, DocumentIndex, IngestRequest, RagQuery, SearchDocumentsTool, SearchHit};
+pub use registry_auth::{RegistryCredential, RegistryInfo};
+pub use remote_compute::{ProvisionRequest, RemoteCompute, RemoteComputeManager};
+pub use state_store::{JobHistory, JobRecord, MeshJobRecord, MeshJobs, MeshUsage, RemoteNode, RemoteNodes, Settings, StateStore, StoragePin, StoragePins, BandwidthUsage};
+pub use storage::{Challenge, PinRequest, Proof, Storage, StorageStatus};
+pub use workspace::{CreateWorkspaceRequest, UpdateWorkspaceRequest, Workspace, WorkspaceManager};
@@ -0,0 +1,179 @@
+//! Native Container Logs
+//!
+//! A container's stdout and stderr are pipes read by the node and written,
+//! interleaved line by line, to `container.log` in the container directory.
+//! The file is rotated at `MAX_LOG_SIZE`, keeping `MAX_LOG_FILES` old ones
+//! as `container.log.1` (newest) and up.
+
+#![cfg(all(target_os = "linux", feature = "native-containers"))]
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::os::fd::OwnedFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::container_runtime::{Result, RuntimeError};
+
+/// Size at which the log is rotated
+const MAX_LOG_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Rotated files kept besides the current one
+const MAX_LOG_FILES: usize = 3;
+
+/// Lines returned when no tail is given, as with Docker
+const DEFAULT_TAIL: usize = 100;
+
+fn log_path(container_dir: &Path) -> PathBuf {
+    container_dir.join("container.log")
+}
+
+fn rotated_path(container_dir: &Path, n: usize) -> PathBuf {
+    container_dir.join(format!("container.log.{}", n))
+}
+
+struct LogFile {
+    container_dir: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(container_dir: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(log_path(container_dir))?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            container_dir: container_dir.to_path_buf(),
+            file,
+            size,
+        })
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        for n in (1..MAX_LOG_FILES).rev() {
+            let from = rotated_path(&self.container_dir, n);
+            if from.exists() {
+                std::fs::rename(&from, rotated_path(&self.container_dir, n + 1))?;
+            }
+        }
+        std::fs::rename(log_path(&self.container_dir), rotated_path(&self.container_dir, 1))?;
+        *self = Self::open(&self.container_dir)?;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > MAX_LOG_SIZE {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Copy lines from `pipe` into the log until every writer has closed it
+fn pump(pipe: OwnedFd, log: Arc<Mutex<LogFile>>) {
+    let mut reader = BufReader::new(File::from(pipe));
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                if !line.ends_with(b"\n") {
+                    line.push(b'\n');
+                }
+                if let Err(e) = log.lock().unwrap().write_line(&line) {
+                    log::warn!("Native runtime: failed to write container log: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Pipes to hand the container as stdout and stderr; what it writes to
+/// them ends up in its log
+pub fn capture(container_dir: &Path) -> Result<(OwnedFd, OwnedFd)> {
+    let log = Arc::new(Mutex::new(LogFile::open(container_dir)?));
+    let mut writers = Vec::with_capacity(2);
+    for stream in ["stdout", "stderr"] {
+        let (read, write) = nix::unistd::pipe()
+            .map_err(|e| RuntimeError::OperationFailed(format!("Failed to create {} pipe: {}", stream, e)))?;
+        let log = Arc::clone(&log);
+        std::thread::Builder::new()
+            .name(format!("container-{}", stream))
+            .spawn(move || pump(read, log))?;
+        writers.push(write);
+    }
+    let stderr = writers.pop().unwrap();
+    let stdout = writers.pop().unwrap();
+    Ok((stdout, stderr))
+}
+
+/// The last `tail` lines (100 by default) across the current and rotated logs
+pub fn read(container_dir: &Path, tail: Option<usize>) -> String {
+    let tail = tail.unwrap_or(DEFAULT_TAIL);
+    let mut lines: Vec<String> = Vec::new();
+    // Newest file first, until enough lines are collected
+    let files = std::iter::once(log_path(container_dir))
+        .chain((1..=MAX_LOG_FILES).map(|n| rotated_path(container_dir, n)));
+    for path in files {
+        if lines.len() >= tail {
+            break;
+        }
+        let Ok(data) = std::fs::read(&path) else {
+            continue;
+        };
+        let content = String::from_utf8_lossy(&data);
+        let mut older: Vec<String> = content.lines().map(|l| format!("{}\n", l)).collect();
+        let keep = older.len().min(tail - lines.len());
+        older.drain(..older.len() - keep);
+        older.append(&mut lines);
+        lines = older;
+    }
+    lines.concat()
+}
+
+/// Reads what is appended to the log after a given point, following it
+/// across rotations
+pub struct LogFollower {
+    container_dir: PathBuf,
+    offset: u64,
+}
+
+impl LogFollower {
+    /// Follow from the current end of the log
+    pub fn new(container_dir: &Path) -> Self {
+        let offset = std::fs::metadata(log_path(container_dir)).map(|m| m.len()).unwrap_or(0);
+        Self {
+            container_dir: container_dir.to_path_buf(),
+            offset,
+        }
+    }
+
+    fn read_from(path: &Path, offset: u64) -> std::io::Result<Vec<u8>> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Everything written since the last call
+    pub fn poll(&mut self) -> String {
+        let current = log_path(&self.container_dir);
+        let len = std::fs::metadata(&current).map(|m| m.len()).unwrap_or(0);
+        let mut data = Vec::new();
+        if len < self.offset {
+            // Rotated: finish the previous file, then start on the new one
+            let previous = rotated_path(&self.container_dir, 1);
+            data = Self::read_from(&previous, self.offset).unwrap_or_default();
+            self.offset = 0;
+        }
+        if let Ok(new) = Self::read_from(&current, self.offset) {
+            self.offset += new.len() as u64;
+            data.extend(new);
+        }
+        String::from_utf8_lossy(&data).into_owned()
+    }
+}
@@ -0,0 +1,459 @@
+//! Native Container Networking
+//!
+//! Gives native containers a way out and publishes their ports. As root,
+//! each container gets a veth pair on the `otherthing0` bridge with an
+//! address from 10.87.0.0/16, NAT to the outside and iptables DNAT rules
+//! for its ports. Without root, slirp4netns provides a user-mode network
+//! and forwards the ports through its API socket.
+//!
+//! The chosen mode and ports are saved in the container directory as
+//! `network.json`; what was set up for the running init process is kept in
+//! `attachment.json` so it can be torn down when that process exits.
+
+#![cfg(all(target_os = "linux", feature = "native-containers"))]
+
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::container_runtime::{ContainerSpec, PortMapping, Result, RuntimeError};
+
+const BRIDGE: &str = "otherthing0";
+const SUBNET: &str = "10.87.0.0/16";
+const GATEWAY: Ipv4Addr = Ipv4Addr::new(10, 87, 0, 1);
+const PREFIX_LEN: u8 = 16;
+
+/// nat chain holding the port forwarding rules
+const CHAIN: &str = "OTHERTHING";
+
+/// DNS server slirp4netns provides inside the container
+const SLIRP_DNS: &str = "10.0.2.3";
+
+/// How long slirp4netns gets to open its API socket
+const SLIRP_STARTUP: Duration = Duration::from_secs(5);
+
+/// Held while picking an address, so two containers never get the same one
+static ALLOCATION: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkMode {
+    /// veth pair on the node's bridge
+    Bridge,
+    /// User-mode networking for rootless containers
+    Slirp,
+    /// The host's network namespace
+    Host,
+    /// Loopback only
+    None,
+}
+
+/// How a container is networked, saved as `network.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    pub mode: NetworkMode,
+    #[serde(default)]
+    pub ports: Vec<PortMapping>,
+}
+
+/// What was set up for the current init process
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Attachment {
+    ip: Option<Ipv4Addr>,
+    host_veth: Option<String>,
+    slirp_pid: Option<i32>,
+    #[serde(default)]
+    ports: Vec<PortMapping>,
+}
+
+impl NetworkConfig {
+    /// Mode for a spec's `network_mode`; named networks are not supported
+    pub fn for_spec(spec: &ContainerSpec) -> Result<Self> {
+        let mode = match spec.network_mode.as_deref() {
+            None | Some("") | Some("bridge") | Some("default") => {
+                if nix::unistd::geteuid().is_root() {
+                    NetworkMode::Bridge
+                } else {
+                    NetworkMode::Slirp
+                }
+            }
+            Some("host") => NetworkMode::Host,
+            Some("none") => NetworkMode::None,
+            Some(other) => {
+                return Err(RuntimeError::Config(format!(
+                    "Network {} is not supported by the native runtime", other
+                )))
+            }
+        };
+        let ports = match mode {
+            NetworkMode::Bridge | NetworkMode::Slirp => spec.ports.clone().unwrap_or_default(),
+            NetworkMode::Host | NetworkMode::None => Vec::new(),
+        };
+        Ok(Self { mode, ports })
+    }
+
+    pub fn load(container_dir: &Path) -> Option<Self> {
+        let data = std::fs::read_to_string(container_dir.join("network.json")).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    /// Save the config, and the resolv.conf the container will see
+    pub fn save(&self, container_dir: &Path) -> Result<()> {
+        let data = serde_json::to_string_pretty(self)
+            .map_err(|e| RuntimeError::Config(e.to_string()))?;
+        std::fs::write(container_dir.join("network.json"), data)?;
+
+        let resolv_conf = match self.mode {
+            NetworkMode::Bridge => Some(bridge_resolv_conf()),
+            NetworkMode::Slirp => Some(format!("nameserver {}\n", SLIRP_DNS)),
+            NetworkMode::Host | NetworkMode::None => None,
+        };
+        if let Some(resolv_conf) = resolv_conf {
+            std::fs::write(container_dir.join("resolv.conf"), resolv_conf)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the container gets a network namespace of its own
+    pub fn isolated(&self) -> bool {
+        self.mode != NetworkMode::Host
+    }
+}
+
+/// The host's resolvers, minus loopback ones the container cannot reach
+/// (systemd-resolved's stub is swapped for its upstream list)
+fn bridge_resolv_conf() -> String {
+    let usable = |content: &str| -> Vec<String> {
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| match line.strip_prefix("nameserver") {
+                Some(server) => {
+                    let server = server.trim();
+                    !server.starts_with("127.") && server != "::1"
+                }
+                None => line.starts_with("search") || line.starts_with("options"),
+            })
+            .map(String::from)
+            .collect()
+    };
+
+    let mut lines = Vec::new();
+    for path in ["/etc/resolv.conf", "/run/systemd/resolve/resolv.conf"] {
+        lines = usable(&std::fs::read_to_string(path).unwrap_or_default());
+        if lines.iter().any(|l| l.starts_with("nameserver")) {
+            break;
+        }
+    }
+    if !lines.iter().any(|l| l.starts_with("nameserver")) {
+        lines.push("nameserver 1.1.1.1".to_string());
+        lines.push("nameserver 8.8.8.8".to_string());
+    }
+    lines.join("\n") + "\n"
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| RuntimeError::OperationFailed(format!("Failed to run {}: {}", program, e)))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(RuntimeError::OperationFailed(format!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+fn succeeds(program: &str, args: &[&str]) -> bool {
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Add an iptables rule unless it is already there
+fn ensure_rule(table: &str, chain: &str, rule: &[&str], insert: bool) -> Result<()> {
+    let check: Vec<&str> = ["-t", table, "-C", chain].iter().chain(rule).copied().collect();
+    if succeeds("iptables", &check) {
+        return Ok(());
+    }
+    let action = if insert { "-I" } else { "-A" };
+    let add: Vec<&str> = ["-t", table, action, chain].iter().chain(rule).copied().collect();
+    run("iptables", &add)
+}
+
+/// Create the bridge, NAT and the forwarding chain once per boot
+fn ensure_bridge() -> Result<()> {
+    if !Path::new("/sys/class/net").join(BRIDGE).exists() {
+        run("ip", &["link", "add", BRIDGE, "type", "bridge"])?;
+        let address = format!("{}/{}", GATEWAY, PREFIX_LEN);
+        run("ip", &["addr", "add", &address, "dev", BRIDGE])?;
+        run("ip", &["link", "set", BRIDGE, "up"])?;
+        log::info!("Native runtime: created bridge {} ({})", BRIDGE, SUBNET);
+    }
+
+    std::fs::write("/proc/sys/net/ipv4/ip_forward", "1")?;
+    // Lets ports published on 127.0.0.1 reach containers
+    let _ = std::fs::write(format!("/proc/sys/net/ipv4/conf/{}/route_localnet", BRIDGE), "1");
+
+    if !succeeds("iptables", &["-t", "nat", "-L", CHAIN, "-n"]) {
+        run("iptables", &["-t", "nat", "-N", CHAIN])?;
+    }
+    let local = ["-m", "addrtype", "--dst-type", "LOCAL", "-j", CHAIN];
+    ensure_rule("nat", "PREROUTING", &local, false)?;
+    ensure_rule("nat", "OUTPUT", &local, false)?;
+    ensure_rule("nat", "POSTROUTING", &["-s", SUBNET, "!", "-o", BRIDGE, "-j", "MASQUERADE"], false)?;
+    ensure_rule("nat", "POSTROUTING", &["-s", "127.0.0.0/8", "-o", BRIDGE, "-j", "MASQUERADE"], false)?;
+    // Inserted, since Docker leaves the FORWARD policy at DROP
+    ensure_rule("filter", "FORWARD", &["-i", BRIDGE, "-j", "ACCEPT"], true)?;
+    ensure_rule("filter", "FORWARD", &["-o", BRIDGE, "-j", "ACCEPT"], true)?;
+    Ok(())
+}
+
+fn protocol(port: &PortMapping) -> &str {
+    if port.protocol.is_empty() { "tcp" } else { &port.protocol }
+}
+
+fn host_ip(port: &PortMapping) -> Option<&str> {
+    port.host_ip.as_deref().filter(|ip| !ip.is_empty() && *ip != "0.0.0.0")
+}
+
+/// DNAT rule publishing one port, as passed after `-A`/`-D <chain>`
+fn dnat_rule(port: &PortMapping, ip: Ipv4Addr) -> Vec<String> {
+    let mut rule = vec!["-p".to_string(), protocol(port).to_string()];
+    if let Some(host_ip) = host_ip(port) {
+        rule.extend(["-d".to_string(), host_ip.to_string()]);
+    }
+    rule.extend([
+        "--dport".to_string(),
+        port.host_port.unwrap_or(port.container_port).to_string(),
+        "-j".to_string(),
+        "DNAT".to_string(),
+        "--to-destination".to_string(),
+        format!("{}:{}", ip, port.container_port),
+    ]);
+    rule
+}
+
+fn attachment_path(container_dir: &Path) -> std::path::PathBuf {
+    container_dir.join("attachment.json")
+}
+
+fn load_attachment(container_dir: &Path) -> Option<Attachment> {
+    let data = std::fs::read_to_string(attachment_path(container_dir)).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+fn save_attachment(container_dir: &Path, attachment: &Attachment) -> Result<()> {
+    let data = serde_json::to_string(attachment).map_err(|e| RuntimeError::Config(e.to_string()))?;
+    std::fs::write(attachment_path(container_dir), data)?;
+    Ok(())
+}
+
+/// Lowest free address on the bridge, reserved by saving it right away
+fn allocate_ip(container_dir: &Path) -> Result<Ipv4Addr> {
+    let _guard = ALLOCATION.lock().unwrap();
+    let used: Vec<Ipv4Addr> = container_dir
+        .parent()
+        .and_then(|root| std::fs::read_dir(root).ok())
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| load_attachment(&entry.path())?.ip)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let first = u32::from(GATEWAY) + 1;
+    let last = u32::from(GATEWAY) + (1 << (32 - PREFIX_LEN)) - 3;
+    let ip = (first..=last)
+        .map(Ipv4Addr::from)
+        .find(|ip| !used.contains(ip))
+        .ok_or_else(|| RuntimeError::OperationFailed("No free container addresses".to_string()))?;
+
+    save_attachment(container_dir, &Attachment { ip: Some(ip), ..Default::default() })?;
+    Ok(ip)
+}
+
+fn attach_bridge(container_dir: &Path, pid: i32, ports: &[PortMapping]) -> Result<()> {
+    ensure_bridge()?;
+    let ip = allocate_ip(container_dir)?;
+
+    let short_id: String = container_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().replace('-', ""))
+        .unwrap_or_default()
+        .chars()
+        .take(8)
+        .collect();
+    let host_veth = format!("veth{}", short_id);
+    let peer = format!("vc{}", short_id);
+    save_attachment(container_dir, &Attachment {
+        ip: Some(ip),
+        host_veth: Some(host_veth.clone()),
+        ..Default::default()
+    })?;
+
+    let pid = pid.to_string();
+    let address = format!("{}/{}", ip, PREFIX_LEN);
+    let gateway = GATEWAY.to_string();
+    run("ip", &["link", "add", &host_veth, "type", "veth", "peer", "name", &peer])?;
+    run("ip", &["link", "set", &host_veth, "master", BRIDGE, "up"])?;
+    run("ip", &["link", "set", &peer, "netns", &pid])?;
+
+    let in_container = |args: &[&str]| -> Result<()> {
+        let args: Vec<&str> = ["-t", pid.as_str(), "-n", "ip"].iter().chain(args).copied().collect();
+        run("nsenter", &args)
+    };
+    in_container(&["link", "set", "lo", "up"])?;
+    in_container(&["link", "set", &peer, "name", "eth0"])?;
+    in_container(&["addr", "add", &address, "dev", "eth0"])?;
+    in_container(&["link", "set", "eth0", "up"])?;
+    in_container(&["route", "add", "default", "via", &gateway])?;
+
+    let mut published = Vec::new();
+    for port in ports {
+        let rule = dnat_rule(port, ip);
+        let rule: Vec<&str> = rule.iter().map(String::as_str).collect();
+        let result = ensure_rule("nat", CHAIN, &rule, false);
+        // Record what was added before bailing, so detach removes it
+        if result.is_ok() {
+            published.push(port.clone());
+        }
+        save_attachment(container_dir, &Attachment {
+            ip: Some(ip),
+            host_veth: Some(host_veth.clone()),
+            slirp_pid: None,
+            ports: published.clone(),
+        })?;
+        result?;
+    }
+
+    log::info!("Native runtime: attached {} at {}", pid, ip);
+    Ok(())
+}
+
+/// Send one request to slirp4netns' API socket
+fn slirp_request(socket: &Path, request: serde_json::Value) -> Result<()> {
+    let mut stream = std::os::unix::net::UnixStream::connect(socket)?;
+    stream.write_all(request.to_string().as_bytes())?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap_or_default();
+    match response.get("error") {
+        Some(error) => Err(RuntimeError::OperationFailed(format!("slirp4netns: {}", error))),
+        None => Ok(()),
+    }
+}
+
+fn attach_slirp(container_dir: &Path, pid: i32, ports: &[PortMapping]) -> Result<()> {
+    let socket = container_dir.join("slirp.sock");
+    let _ = std::fs::remove_file(&socket);
+
+    let child = Command::new("slirp4netns")
+        .args(["--configure", "--mtu=65520", "--disable-host-loopback", "--api-socket"])
+        .arg(&socket)
+        .arg(pid.to_string())
+        .arg("tap0")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| RuntimeError::OperationFailed(format!(
+            "Rootless networking needs slirp4netns: {}", e
+        )))?;
+    save_attachment(container_dir, &Attachment {
+        slirp_pid: Some(child.id() as i32),
+        ..Default::default()
+    })?;
+
+    let started = std::time::Instant::now();
+    while !socket.exists() {
+        if started.elapsed() > SLIRP_STARTUP {
+            return Err(RuntimeError::OperationFailed("slirp4netns did not start".to_string()));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    for port in ports {
+        slirp_request(&socket, serde_json::json!({
+            "execute": "add_hostfwd",
+            "arguments": {
+                "proto": protocol(port),
+                "host_addr": host_ip(port).unwrap_or("0.0.0.0"),
+                "host_port": port.host_port.unwrap_or(port.container_port),
+                "guest_port": port.container_port,
+            }
+        }))?;
+    }
+
+    // slirp4netns is reaped by detach, not by us holding the handle
+    drop(child);
+    Ok(())
+}
+
+/// Connect the container whose init process is `pid`, before it starts
+pub fn attach(container_dir: &Path, pid: i32) -> Result<()> {
+    let Some(config) = NetworkConfig::load(container_dir) else {
+        return Ok(());
+    };
+    // Ports without a host port are only exposed, which needs no rule
+    let published: Vec<PortMapping> = config.ports.into_iter().filter(|p| p.host_port.is_some()).collect();
+    let attached = match config.mode {
+        NetworkMode::Bridge => attach_bridge(container_dir, pid, &published),
+        NetworkMode::Slirp => attach_slirp(container_dir, pid, &published),
+        NetworkMode::None => run("nsenter", &["-t", &pid.to_string(), "-n", "ip", "link", "set", "lo", "up"]),
+        NetworkMode::Host => Ok(()),
+    };
+    if attached.is_err() {
+        detach(container_dir);
+    }
+    attached
+}
+
+/// Undo `attach` once the init process is gone; safe to call repeatedly
+pub fn detach(container_dir: &Path) {
+    let Some(attachment) = load_attachment(container_dir) else {
+        return;
+    };
+
+    if let Some(ip) = attachment.ip {
+        for port in &attachment.ports {
+            let rule = dnat_rule(port, ip);
+            let args: Vec<&str> = ["-t", "nat", "-D", CHAIN]
+                .into_iter()
+                .chain(rule.iter().map(String::as_str))
+                .collect();
+            let _ = run("iptables", &args);
+        }
+    }
+    // The veth pair usually went away with the namespace
+    if let Some(veth) = &attachment.host_veth {
+        if Path::new("/sys/class/net").join(veth).exists() {
+            let _ = run("ip", &["link", "del", veth]);
+        }
+    }
+    if let Some(pid) = attachment.slirp_pid {
+        let pid = nix::unistd::Pid::from_raw(pid);
+        if nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGTERM).is_ok() {
+            let _ = nix::sys::wait::waitpid(pid, None);
+        }
+    }
+
+    let _ = std::fs::remove_file(attachment_path(container_dir));
+}
@@ -13,23 +13,110 @@ use oci_spec::runtime::{
     LinuxBuilder, LinuxNamespaceBuilder, LinuxNamespaceType, LinuxResourcesBuilder,
     MountBuilder, ProcessBuilder, RootBuilder, Spec, SpecBuilder, UserBuilder,
 };
-use std::collections::HashMap;
+use nix::mount::{mount, umount2, MntFlags, MsFlags};
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::Pid;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
 
 use super::container_runtime::{
-    ContainerInfo, ContainerRuntime, ContainerSpec, ContainerState, ExecOutput, ImageInfo, Mount,
-    MountType, PortMapping, Result, RuntimeError, RuntimeInfo, RuntimeType,
+    BuildImageRequest, ContainerInfo, ContainerRuntime, ContainerSpec, ContainerState, ContainerStats,
+    DiskUsage, ExecOutput, ImageInfo, LogLine, LogStream, MountType, NetworkInfo, PruneRequest,
+    PruneResult, ResourceLimits, RestartPolicy, Result, RuntimeError, RuntimeInfo, RuntimeType,
 };
+use super::native_logs::{self, LogFollower};
+use super::native_network::{self, NetworkConfig};
+use super::oci_image::{ImageStore, StoredImage};
 
 /// Root directory for container state
 const DEFAULT_ROOT_DIR: &str = "/var/lib/otherthing-node/containers";
 
+/// Root directory for pulled images and their blobs
+const DEFAULT_IMAGE_DIR: &str = "/var/lib/otherthing-node/images";
+
+/// Mount point of the unified (v2) cgroup hierarchy
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Longest wait between restarts; the delay doubles from 100ms up to this
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+
+/// Interval over which `stats` measures CPU use
+const CPU_SAMPLE: Duration = Duration::from_millis(250);
+
+/// Start and exit of the latest run, kept as `run.json` since libcontainer
+/// only records when the container was created
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RunState {
+    started: Option<i64>,
+    finished: Option<i64>,
+    exit_code: Option<i32>,
+}
+
+impl RunState {
+    fn load(container_dir: &Path) -> Self {
+        std::fs::read_to_string(container_dir.join("run.json"))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, container_dir: &Path) {
+        if let Ok(data) = serde_json::to_string(self) {
+            let _ = std::fs::write(container_dir.join("run.json"), data);
+        }
+    }
+}
+
+/// Name and labels given at creation, kept as `meta.json`; libcontainer
+/// only knows a container by its ID
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ContainerMeta {
+    name: String,
+    #[serde(default)]
+    labels: HashMap<String, String>,
+}
+
+impl ContainerMeta {
+    fn load(container_dir: &Path) -> Self {
+        std::fs::read_to_string(container_dir.join("meta.json"))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, container_dir: &Path) -> Result<()> {
+        let data = serde_json::to_string(self)
+            .map_err(|e| RuntimeError::Config(e.to_string()))?;
+        std::fs::write(container_dir.join("meta.json"), data)?;
+        Ok(())
+    }
+}
+
+/// Bytes used by the files under `path`, not following symlinks
+fn dir_size(path: &Path) -> i64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len() as i64;
+    }
+    std::fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|entry| dir_size(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
 /// Native container runtime using libcontainer
 pub struct NativeRuntime {
     root_dir: PathBuf,
+    images: ImageStore,
     containers: Arc<RwLock<HashMap<String, ContainerState>>>,
+    restart_policies: Arc<RwLock<HashMap<String, RestartPolicy>>>,
+    /// Containers being stopped or removed, which must not be restarted
+    stopping: Arc<RwLock<HashSet<String>>>,
 }
 
 impl NativeRuntime {
@@ -51,7 +138,10 @@ impl NativeRuntime {
 
         Some(Self {
             root_dir,
+            images: ImageStore::new(PathBuf::from(DEFAULT_IMAGE_DIR)),
             containers: Arc::new(RwLock::new(HashMap::new())),
+            restart_policies: Arc::new(RwLock::new(HashMap::new())),
+            stopping: Arc::new(RwLock::new(HashSet::new())),
         })
     }
 
@@ -75,30 +165,69 @@ impl NativeRuntime {
         self.root_dir.join(id)
     }
 
-    fn build_oci_spec(&self, spec: &ContainerSpec) -> Result<Spec> {
-        // Build process
+    /// Numeric `uid[:gid]` of an image's USER; names would need the
+    /// image's /etc/passwd, so they fall back to root
+    fn image_user(user: Option<&str>) -> (u32, u32) {
+        let Some(user) = user else {
+            return (0, 0);
+        };
+        let (uid, gid) = user.split_once(':').unwrap_or((user, ""));
+        match uid.parse::<u32>() {
+            Ok(uid) => (uid, gid.parse().unwrap_or(uid)),
+            Err(_) => {
+                log::warn!("Native runtime: user {} is not numeric, running as root", user);
+                (0, 0)
+            }
+        }
+    }
+
+    fn build_oci_spec(
+        &self,
+        id: &str,
+        spec: &ContainerSpec,
+        image: &StoredImage,
+        network: &NetworkConfig,
+    ) -> Result<Spec> {
+        // Build process; the spec overrides the image's defaults like
+        // `docker run` does: a command replaces the entrypoint, args
+        // replace the image's CMD
+        let (uid, gid) = Self::image_user(spec.user.as_deref().or(image.config.user.as_deref()));
         let mut process_builder = ProcessBuilder::default()
             .terminal(false)
-            .user(UserBuilder::default().uid(0u32).gid(0u32).build().unwrap());
+            .user(UserBuilder::default().uid(uid).gid(gid).build().unwrap());
 
-        if let Some(cmd) = &spec.command {
-            process_builder = process_builder.args(cmd.clone());
+        let entrypoint = image.config.entrypoint.clone().unwrap_or_default();
+        let args: Vec<String> = match (&spec.command, &spec.args) {
+            (Some(cmd), args) => cmd.iter().chain(args.iter().flatten()).cloned().collect(),
+            (None, Some(args)) => entrypoint.into_iter().chain(args.iter().cloned()).collect(),
+            (None, None) => entrypoint.into_iter().chain(image.config.cmd.clone().unwrap_or_default()).collect(),
+        };
+        if args.is_empty() {
+            return Err(RuntimeError::Config(format!("No command given and {} has none", spec.image)));
         }
-
-        if let Some(workdir) = &spec.workdir {
-            process_builder = process_builder.cwd(PathBuf::from(workdir));
-        }
-
-        if let Some(env) = &spec.env {
-            let env_vec: Vec<String> = env.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
-            process_builder = process_builder.env(env_vec);
+        process_builder = process_builder.args(args);
+
+        let workdir = spec.workdir.clone()
+            .or_else(|| image.config.working_dir.clone())
+            .unwrap_or_else(|| "/".to_string());
+        process_builder = process_builder.cwd(PathBuf::from(workdir));
+
+        // Image variables first so the spec's win
+        let mut env: Vec<String> = image.config.env.clone();
+        if let Some(spec_env) = &spec.env {
+            env.retain(|var| {
+                let name = var.split_once('=').map(|(k, _)| k).unwrap_or(var);
+                !spec_env.contains_key(name)
+            });
+            env.extend(spec_env.iter().map(|(k, v)| format!("{}={}", k, v)));
         }
+        process_builder = process_builder.env(env);
 
         let process = process_builder.build()
             .map_err(|e| RuntimeError::Config(e.to_string()))?;
 
         // Build root
-        let rootfs_path = self.container_dir(&spec.name).join("rootfs");
+        let rootfs_path = self.container_dir(id).join("rootfs");
         let root = RootBuilder::default()
             .path(rootfs_path)
             .readonly(spec.readonly_rootfs.unwrap_or(false))
@@ -130,6 +259,20 @@ impl NativeRuntime {
                 .unwrap(),
         ];
 
+        // DNS that works from inside the container's network
+        let resolv_conf = self.container_dir(id).join("resolv.conf");
+        if network.isolated() && resolv_conf.exists() {
+            mounts.push(
+                MountBuilder::default()
+                    .destination(PathBuf::from("/etc/resolv.conf"))
+                    .typ("bind")
+                    .source(resolv_conf)
+                    .options(vec!["rbind".to_string(), "ro".to_string()])
+                    .build()
+                    .unwrap(),
+            );
+        }
+
         // Add user mounts
         if let Some(user_mounts) = &spec.mounts {
             for m in user_mounts {
@@ -160,16 +303,12 @@ impl NativeRuntime {
             }
         }
 
-        // Build Linux config with namespaces
-        let namespaces = vec![
+        // Build Linux config with namespaces; host networking shares ours
+        let mut namespaces = vec![
             LinuxNamespaceBuilder::default()
                 .typ(LinuxNamespaceType::Pid)
                 .build()
                 .unwrap(),
-            LinuxNamespaceBuilder::default()
-                .typ(LinuxNamespaceType::Network)
-                .build()
-                .unwrap(),
             LinuxNamespaceBuilder::default()
                 .typ(LinuxNamespaceType::Ipc)
                 .build()
@@ -183,6 +322,14 @@ impl NativeRuntime {
                 .build()
                 .unwrap(),
         ];
+        if network.isolated() {
+            namespaces.push(
+                LinuxNamespaceBuilder::default()
+                    .typ(LinuxNamespaceType::Network)
+                    .build()
+                    .unwrap(),
+            );
+        }
 
         let mut linux_builder = LinuxBuilder::default()
             .namespaces(namespaces);
@@ -254,6 +401,277 @@ impl NativeRuntime {
         Ok(oci_spec)
     }
 
+    /// Create the container's init process from its bundle, connect its
+    /// network while it waits, then start it
+    fn launch(container_dir: &Path, id: &str) -> Result<Pid> {
+        let (stdout, stderr) = native_logs::capture(container_dir)?;
+        let syscall = SyscallType::default();
+        let mut container = ContainerBuilder::new(id.to_string(), syscall)
+            .with_stdout(stdout)
+            .with_stderr(stderr)
+            .with_root_path(container_dir.to_path_buf())
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?
+            .as_init(container_dir)
+            .with_systemd(false)
+            .build()
+            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+
+        let pid = container.pid()
+            .ok_or_else(|| RuntimeError::OperationFailed("Container has no init process".to_string()))?;
+
+        if let Err(e) = native_network::attach(container_dir, pid.as_raw()) {
+            let _ = container.delete(true);
+            return Err(e);
+        }
+
+        if let Err(e) = container.start() {
+            native_network::detach(container_dir);
+            return Err(RuntimeError::OperationFailed(e.to_string()));
+        }
+
+        RunState {
+            started: Some(chrono::Utc::now().timestamp()),
+            ..Default::default()
+        }
+        .save(container_dir);
+        Ok(pid)
+    }
+
+    /// Wait for the init process (our child) to exit and restart the
+    /// container as its restart policy says, backing off between attempts
+    fn supervise(&self, id: String, pid: Pid) {
+        let container_dir = self.container_dir(&id);
+        let containers = Arc::clone(&self.containers);
+        let policies = Arc::clone(&self.restart_policies);
+        let stopping = Arc::clone(&self.stopping);
+
+        tokio::spawn(async move {
+            let mut pid = pid;
+            let mut restarts = 0u32;
+
+            loop {
+                let exit_code = match tokio::task::spawn_blocking(move || waitpid(pid, None)).await {
+                    Ok(Ok(WaitStatus::Exited(_, code))) => code,
+                    Ok(Ok(WaitStatus::Signaled(_, signal, _))) => 128 + signal as i32,
+                    _ => -1,
+                };
+                native_network::detach(&container_dir);
+                let mut run = RunState::load(&container_dir);
+                run.finished = Some(chrono::Utc::now().timestamp());
+                run.exit_code = Some(exit_code);
+                run.save(&container_dir);
+                containers.write().await.insert(id.clone(), ContainerState::Exited);
+
+                let policy = policies.read().await.get(&id).cloned().unwrap_or_default();
+                if stopping.read().await.contains(&id) || !policy.should_restart(exit_code, restarts) {
+                    break;
+                }
+
+                let delay = Duration::from_millis(100 << restarts.min(10)).min(MAX_RESTART_DELAY);
+                log::info!(
+                    "Native runtime: container {} exited with {}, restarting in {:?}",
+                    id, exit_code, delay
+                );
+                tokio::time::sleep(delay).await;
+                if stopping.read().await.contains(&id) {
+                    break;
+                }
+
+                // A stopped libcontainer container cannot be started again;
+                // drop its state and launch a fresh one from the same bundle
+                if let Ok(mut old) = Container::load(container_dir.clone()) {
+                    let _ = old.delete(true);
+                }
+                match Self::launch(&container_dir, &id) {
+                    Ok(new_pid) => {
+                        pid = new_pid;
+                        restarts += 1;
+                        containers.write().await.insert(id.clone(), ContainerState::Running);
+                    }
+                    Err(e) => {
+                        log::warn!("Native runtime: failed to restart container {}: {}", id, e);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Layers (bottom first) a container's rootfs is stacked from
+    fn container_layers(container_dir: &Path) -> Vec<String> {
+        std::fs::read_to_string(container_dir.join("layers.json"))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn is_mounted(path: &Path) -> bool {
+        let Ok(mountinfo) = std::fs::read_to_string("/proc/self/mountinfo") else {
+            return false;
+        };
+        mountinfo
+            .lines()
+            .filter_map(|line| line.split(' ').nth(4))
+            .any(|mount_point| Path::new(mount_point) == path)
+    }
+
+    /// Mount the container's rootfs as an overlay of the image layers with
+    /// the container's own `upper` directory on top. Kernel overlayfs needs
+    /// privileges, so fuse-overlayfs is used when the mount is refused.
+    async fn mount_rootfs(&self, container_dir: &Path) -> Result<()> {
+        let rootfs = container_dir.join("rootfs");
+        if Self::is_mounted(&rootfs) {
+            return Ok(());
+        }
+
+        let mut lower = self.images.lower_dirs(&Self::container_layers(container_dir));
+        if lower.is_empty() {
+            // overlayfs needs at least one lower layer
+            let empty = container_dir.join("empty");
+            std::fs::create_dir_all(&empty)?;
+            lower.push(empty);
+        }
+        let lower: Vec<String> = lower.iter().map(|d| d.display().to_string()).collect();
+        let options = format!(
+            "lowerdir={},upperdir={},workdir={}",
+            lower.join(":"),
+            container_dir.join("upper").display(),
+            container_dir.join("work").display()
+        );
+
+        let mounted = mount(
+            Some("overlay"),
+            &rootfs,
+            Some("overlay"),
+            MsFlags::empty(),
+            Some(options.as_str()),
+        );
+        let Err(e) = mounted else {
+            return Ok(());
+        };
+        log::debug!("Native runtime: overlay mount failed ({}), trying fuse-overlayfs", e);
+
+        let status = tokio::process::Command::new("fuse-overlayfs")
+            .arg("-o")
+            .arg(&options)
+            .arg(&rootfs)
+            .status()
+            .await
+            .map_err(|fuse| RuntimeError::OperationFailed(format!(
+                "Failed to mount rootfs: {} (fuse-overlayfs: {})", e, fuse
+            )))?;
+        if !status.success() {
+            return Err(RuntimeError::OperationFailed(format!(
+                "Failed to mount rootfs: {} (fuse-overlayfs exited with {})", e, status
+            )));
+        }
+        Ok(())
+    }
+
+    async fn unmount_rootfs(container_dir: &Path) -> Result<()> {
+        let rootfs = container_dir.join("rootfs");
+        if !Self::is_mounted(&rootfs) {
+            return Ok(());
+        }
+        if umount2(&rootfs, MntFlags::MNT_DETACH).is_ok() {
+            return Ok(());
+        }
+        for fusermount in ["fusermount3", "fusermount"] {
+            let unmounted = tokio::process::Command::new(fusermount)
+                .arg("-u")
+                .arg(&rootfs)
+                .status()
+                .await
+                .is_ok_and(|s| s.success());
+            if unmounted {
+                return Ok(());
+            }
+        }
+        Err(RuntimeError::OperationFailed(format!("Failed to unmount {}", rootfs.display())))
+    }
+
+    /// Drop layers and blobs that no image or container uses any more
+    fn collect_garbage(&self) {
+        let in_use: HashSet<String> = std::fs::read_dir(&self.root_dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .flat_map(|entry| Self::container_layers(&entry.path()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.images.gc(&in_use);
+    }
+
+    /// Microseconds of CPU time from a cgroup's `cpu.stat`
+    fn cpu_usage_usec(dir: &Path) -> u64 {
+        std::fs::read_to_string(dir.join("cpu.stat"))
+            .unwrap_or_default()
+            .lines()
+            .find_map(|line| line.strip_prefix("usage_usec "))
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Total `rbytes` and `wbytes` over all devices in `io.stat`
+    fn io_bytes(dir: &Path) -> (u64, u64) {
+        let content = std::fs::read_to_string(dir.join("io.stat")).unwrap_or_default();
+        let mut read = 0;
+        let mut written = 0;
+        for field in content.split_whitespace() {
+            if let Some(v) = field.strip_prefix("rbytes=") {
+                read += v.parse::<u64>().unwrap_or(0);
+            } else if let Some(v) = field.strip_prefix("wbytes=") {
+                written += v.parse::<u64>().unwrap_or(0);
+            }
+        }
+        (read, written)
+    }
+
+    /// cgroup v2 directory of a running process, from `/proc/<pid>/cgroup`
+    fn cgroup_dir(pid: Pid) -> Result<PathBuf> {
+        let content = std::fs::read_to_string(format!("/proc/{}/cgroup", pid))
+            .map_err(RuntimeError::Io)?;
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .map(|path| Path::new(CGROUP_ROOT).join(path.trim_start_matches('/')))
+            .ok_or_else(|| RuntimeError::OperationFailed("Container is not in a cgroup v2 hierarchy".to_string()))
+    }
+
+    /// Images whose every reference has been pulled again since, the
+    /// native counterpart of Docker's dangling images
+    fn superseded_images(&self) -> Vec<StoredImage> {
+        let images = self.images.list();
+        images
+            .iter()
+            .filter(|image| {
+                image.references.iter().all(|reference| {
+                    images.iter().any(|newer| {
+                        newer.id != image.id
+                            && newer.created > image.created
+                            && newer.references.contains(reference)
+                    })
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// IDs of images some container was created from
+    fn images_in_use(&self) -> HashSet<String> {
+        std::fs::read_dir(&self.root_dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|entry| std::fs::read_to_string(entry.path().join("image")).ok())
+                    .filter_map(|image| self.images.find(&image))
+                    .map(|image| image.id)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     async fn get_container(&self, id: &str) -> Result<Container> {
         let container_dir = self.container_dir(id);
         if !container_dir.exists() {
@@ -294,13 +712,45 @@ impl ContainerRuntime for NativeRuntime {
         std::fs::create_dir_all(&container_dir)
             .map_err(|e| RuntimeError::Io(e))?;
 
-        // Create rootfs directory (would normally extract from image)
-        let rootfs_dir = container_dir.join("rootfs");
-        std::fs::create_dir_all(&rootfs_dir)
+        let image = self.images.find(&spec.image)
+            .ok_or_else(|| RuntimeError::ImageNotFound(format!("{} (pull it first)", spec.image)))?;
+
+        // The rootfs is the image's shared layers with a writable layer of
+        // the container's own on top
+        self.images.unpack(&image).await?;
+        for dir in ["rootfs", "upper", "work"] {
+            std::fs::create_dir_all(container_dir.join(dir))
+                .map_err(|e| RuntimeError::Io(e))?;
+        }
+        let layers: Vec<&str> = image.layers.iter().map(|l| l.digest.as_str()).collect();
+        let layers_json = serde_json::to_string(&layers)
+            .map_err(|e| RuntimeError::Config(e.to_string()))?;
+        std::fs::write(container_dir.join("layers.json"), layers_json)
+            .map_err(|e| RuntimeError::Io(e))?;
+        std::fs::write(container_dir.join("image"), &spec.image)
             .map_err(|e| RuntimeError::Io(e))?;
+        let mut labels = spec.labels.clone().unwrap_or_default();
+        labels.insert("managed_by".to_string(), "otherthing-node".to_string());
+        let meta = ContainerMeta {
+            name: if spec.name.is_empty() { container_id.clone() } else { spec.name.clone() },
+            labels,
+        };
+        meta.save(&container_dir)?;
+        let network = match NetworkConfig::for_spec(spec) {
+            Ok(network) => network,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&container_dir);
+                return Err(e);
+            }
+        };
+        network.save(&container_dir)?;
+        if let Err(e) = self.mount_rootfs(&container_dir).await {
+            let _ = std::fs::remove_dir_all(&container_dir);
+            return Err(e);
+        }
 
         // Build OCI spec
-        let oci_spec = self.build_oci_spec(spec)?;
+        let oci_spec = self.build_oci_spec(&container_id, spec, &image, &network)?;
 
         // Write config.json
         let config_path = container_dir.join("config.json");
@@ -314,6 +764,9 @@ impl ContainerRuntime for NativeRuntime {
             let mut containers = self.containers.write().await;
             containers.insert(container_id.clone(), ContainerState::Created);
         }
+        if let Some(policy) = &spec.restart_policy {
+            self.restart_policies.write().await.insert(container_id.clone(), policy.clone());
+        }
 
         log::info!("Native runtime: created container {}", container_id);
         Ok(container_id)
@@ -322,24 +775,19 @@ impl ContainerRuntime for NativeRuntime {
     async fn start_container(&self, id: &str) -> Result<()> {
         let container_dir = self.container_dir(id);
 
-        // Use ContainerBuilder to create and start
-        let syscall = SyscallType::default();
-        let mut container = ContainerBuilder::new(id.to_string(), syscall)
-            .with_root_path(container_dir.clone())
-            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?
-            .as_init(&container_dir)
-            .with_systemd(false)
-            .build()
-            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+        // The overlay does not survive a reboot
+        self.mount_rootfs(&container_dir).await?;
 
-        container.start()
-            .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+        // Use ContainerBuilder to create and start
+        let pid = Self::launch(&container_dir, id)?;
 
         // Update state
         {
             let mut containers = self.containers.write().await;
             containers.insert(id.to_string(), ContainerState::Running);
         }
+        self.stopping.write().await.remove(id);
+        self.supervise(id.to_string(), pid);
 
         log::info!("Native runtime: started container {}", id);
         Ok(())
@@ -347,6 +795,7 @@ impl ContainerRuntime for NativeRuntime {
 
     async fn stop_container(&self, id: &str, timeout: Option<u32>) -> Result<()> {
         let mut container = self.get_container(id).await?;
+        self.stopping.write().await.insert(id.to_string());
 
         // Send SIGTERM first
         container.kill(nix::sys::signal::Signal::SIGTERM, true)
@@ -393,6 +842,7 @@ impl ContainerRuntime for NativeRuntime {
     async fn remove_container(&self, id: &str, force: bool) -> Result<()> {
         let container_dir = self.container_dir(id);
 
+        self.stopping.write().await.insert(id.to_string());
         if force {
             // Try to kill first
             let _ = self.kill_container(id, None).await;
@@ -406,6 +856,8 @@ impl ContainerRuntime for NativeRuntime {
 
         // Remove directory
         if container_dir.exists() {
+            native_network::detach(&container_dir);
+            Self::unmount_rootfs(&container_dir).await?;
             std::fs::remove_dir_all(&container_dir)
                 .map_err(|e| RuntimeError::Io(e))?;
         }
@@ -415,6 +867,8 @@ impl ContainerRuntime for NativeRuntime {
             let mut containers = self.containers.write().await;
             containers.remove(id);
         }
+        self.restart_policies.write().await.remove(id);
+        self.collect_garbage();
 
         log::info!("Native runtime: removed container {}", id);
         Ok(())
@@ -446,10 +900,71 @@ impl ContainerRuntime for NativeRuntime {
         Ok(())
     }
 
+    async fn restart_container(&self, id: &str, timeout: Option<u32>) -> Result<()> {
+        self.stop_container(id, timeout).await?;
+
+        // The old init process is gone; clear its state before relaunching
+        if let Ok(mut container) = self.get_container(id).await {
+            container.delete(true)
+                .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+        }
+
+        self.start_container(id).await
+    }
+
+    async fn rename_container(&self, id: &str, name: &str) -> Result<()> {
+        // Containers are addressed by their ID directory; the name is
+        // only a label
+        let container_dir = self.container_dir(id);
+        if !container_dir.exists() {
+            return Err(RuntimeError::ContainerNotFound(id.to_string()));
+        }
+        let mut meta = ContainerMeta::load(&container_dir);
+        meta.name = name.to_string();
+        meta.save(&container_dir)
+    }
+
+    async fn update_resources(&self, id: &str, resources: &ResourceLimits) -> Result<()> {
+        let container = self.get_container(id).await?;
+        let pid = container.pid()
+            .ok_or_else(|| RuntimeError::OperationFailed(format!("Container {} is not running", id)))?;
+        let dir = Self::cgroup_dir(pid)?;
+        let write = |file: &str, value: String| {
+            std::fs::write(dir.join(file), value).map_err(RuntimeError::Io)
+        };
+
+        if let Some(memory) = resources.memory {
+            write("memory.max", memory.to_string())?;
+        }
+        if let (Some(memory), Some(total)) = (resources.memory, resources.memory_swap) {
+            // cgroup v2 limits swap alone, not memory + swap
+            write("memory.swap.max", (total - memory).max(0).to_string())?;
+        }
+        if let Some(shares) = resources.cpu_shares {
+            // Same shares -> weight mapping as runc
+            let weight = 1 + ((shares.clamp(2, 262_144) - 2) * 9_999) / 262_142;
+            write("cpu.weight", weight.to_string())?;
+        }
+        let period = resources.cpu_period.unwrap_or(100_000);
+        let quota = resources.cpu_quota
+            .or_else(|| resources.cpus.map(|cpus| (cpus * period as f64) as i64));
+        if let Some(quota) = quota {
+            write("cpu.max", format!("{} {}", quota, period))?;
+        }
+        if let Some(pids) = resources.pids_limit {
+            write("pids.max", pids.to_string())?;
+        }
+
+        log::info!("Native runtime: updated resources of container {}", id);
+        Ok(())
+    }
+
     async fn inspect_container(&self, id: &str) -> Result<ContainerInfo> {
         let container = self.get_container(id).await?;
         let state = container.state()
             .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+        let run = RunState::load(&self.container_dir(id));
+        let meta = ContainerMeta::load(&self.container_dir(id));
 
         let container_state = match state.status {
             libcontainer::container::ContainerStatus::Creating => ContainerState::Creating,
@@ -461,17 +976,18 @@ impl ContainerRuntime for NativeRuntime {
 
         Ok(ContainerInfo {
             id: id.to_string(),
-            name: id.to_string(),
-            image: "".to_string(), // Native runtime doesn't track image
+            name: if meta.name.is_empty() { id.to_string() } else { meta.name },
+            image: std::fs::read_to_string(self.container_dir(id).join("image")).unwrap_or_default(),
             state: container_state,
             created: state.created.map(|t| t.timestamp()).unwrap_or(0),
-            started: None,
-            finished: None,
-            exit_code: None,
+            started: run.started,
+            finished: run.finished,
+            exit_code: run.exit_code,
             pid: state.pid.map(|p| p.as_raw() as u32),
-            ports: vec![],
+            ports: NetworkConfig::load(&self.container_dir(id)).map(|n| n.ports).unwrap_or_default(),
             mounts: vec![],
-            labels: HashMap::new(),
+            labels: meta.labels,
+            health: None,
         })
     }
 
@@ -495,10 +1011,82 @@ impl ContainerRuntime for NativeRuntime {
         Ok(result)
     }
 
-    async fn logs(&self, _id: &str, _tail: Option<usize>, _follow: bool) -> Result<String> {
-        // Native runtime would need to implement log collection
-        // For now, return empty - logs would be in container's stdout/stderr files
-        Ok(String::new())
+    async fn logs(&self, id: &str, tail: Option<usize>, follow: bool) -> Result<String> {
+        let container_dir = self.container_dir(id);
+        if !container_dir.exists() {
+            return Err(RuntimeError::ContainerNotFound(id.to_string()));
+        }
+
+        let mut follower = LogFollower::new(&container_dir);
+        let mut output = native_logs::read(&container_dir, tail);
+        if !follow {
+            return Ok(output);
+        }
+
+        loop {
+            let running = self.containers.read().await.get(id) == Some(&ContainerState::Running);
+            output.push_str(&follower.poll());
+            if !running || !container_dir.exists() {
+                return Ok(output);
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    async fn follow_logs(&self, id: &str, tail: Option<usize>, tx: mpsc::Sender<LogLine>) -> Result<()> {
+        let container_dir = self.container_dir(id);
+        if !container_dir.exists() {
+            return Err(RuntimeError::ContainerNotFound(id.to_string()));
+        }
+
+        // stdout and stderr share one file, so every line reads as stdout
+        let mut follower = LogFollower::new(&container_dir);
+        let mut output = native_logs::read(&container_dir, tail);
+        loop {
+            for line in output.lines() {
+                let line = LogLine {
+                    stream: LogStream::Stdout,
+                    timestamp: None,
+                    message: line.to_string(),
+                };
+                if tx.send(line).await.is_err() {
+                    return Ok(());
+                }
+            }
+
+            let running = self.containers.read().await.get(id) == Some(&ContainerState::Running);
+            if !running || !container_dir.exists() {
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            output = follower.poll();
+        }
+    }
+
+    async fn stats(&self, id: &str) -> Result<ContainerStats> {
+        let container = self.get_container(id).await?;
+        let pid = container.pid()
+            .ok_or_else(|| RuntimeError::OperationFailed(format!("Container {} is not running", id)))?;
+        let dir = Self::cgroup_dir(pid)?;
+        let read = |file: &str| std::fs::read_to_string(dir.join(file)).unwrap_or_default();
+
+        let before = Self::cpu_usage_usec(&dir);
+        tokio::time::sleep(CPU_SAMPLE).await;
+        let cpu_usage_usec = Self::cpu_usage_usec(&dir);
+        let cpu_percent = cpu_usage_usec.saturating_sub(before) as f64
+            / CPU_SAMPLE.as_micros() as f64 * 100.0;
+
+        let (io_read_bytes, io_write_bytes) = Self::io_bytes(&dir);
+        Ok(ContainerStats {
+            cpu_usage_usec,
+            cpu_percent,
+            memory_usage: read("memory.current").trim().parse().unwrap_or(0),
+            // "max" when unlimited
+            memory_limit: read("memory.max").trim().parse().ok(),
+            io_read_bytes,
+            io_write_bytes,
+            pids: read("pids.current").trim().parse().unwrap_or(0),
+        })
     }
 
     async fn exec(&self, id: &str, cmd: &[String], _tty: bool) -> Result<ExecOutput> {
@@ -534,34 +1122,154 @@ impl ContainerRuntime for NativeRuntime {
                 .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
 
             if state.status == libcontainer::container::ContainerStatus::Stopped {
-                return Ok(0); // Would need to track actual exit code
+                // The supervisor records the code right after reaping
+                let run = RunState::load(&self.container_dir(id));
+                return Ok(run.exit_code.unwrap_or(-1));
             }
 
             tokio::time::sleep(std::time::Duration::from_millis(100)).await;
         }
     }
 
-    async fn pull_image(&self, _reference: &str) -> Result<()> {
-        // Native runtime would need image pulling implementation
-        // Could use skopeo or implement OCI registry client
+    async fn pull_image(&self, reference: &str) -> Result<()> {
+        self.images.pull(reference).await.map(|_| ())
+    }
+
+    async fn list_images(&self) -> Result<Vec<ImageInfo>> {
+        Ok(self.images.list().into_iter().map(|image| {
+            let repo_digests = image.references.iter()
+                .filter_map(|r| r.rsplit_once(':').map(|(repo, _)| format!("{}@{}", repo, image.manifest_digest)))
+                .collect();
+            ImageInfo {
+                size: image.size(),
+                created: image.created,
+                repo_digests,
+                repo_tags: image.references,
+                id: image.id,
+            }
+        }).collect())
+    }
+
+    async fn remove_image(&self, reference: &str, force: bool) -> Result<()> {
+        // Layers a container still uses are kept by gc, so only the
+        // bookkeeping would go stale; refuse unless forced like Docker does
+        if !force {
+            let containers = self.list_containers(true).await?;
+            if containers.iter().any(|c| c.image == reference) {
+                return Err(RuntimeError::OperationFailed(format!(
+                    "Image {} is used by a container", reference
+                )));
+            }
+        }
+        self.images.remove(reference)?;
+        self.collect_garbage();
+        Ok(())
+    }
+
+    async fn image_exists(&self, reference: &str) -> Result<bool> {
+        Ok(self.images.find(reference).is_some())
+    }
+
+    async fn push_image(&self, _reference: &str) -> Result<()> {
         Err(RuntimeError::OperationFailed(
-            "Image pulling not yet implemented for native runtime. Use Docker/Podman to pull images first.".to_string()
+            "Pushing images is not supported by the native runtime".to_string()
         ))
     }
 
-    async fn list_images(&self) -> Result<Vec<ImageInfo>> {
-        // Native runtime doesn't manage images directly
+    async fn build_image(&self, _request: &BuildImageRequest, _progress: Option<mpsc::Sender<String>>) -> Result<String> {
+        Err(RuntimeError::OperationFailed(
+            "Building images is not supported by the native runtime".to_string()
+        ))
+    }
+
+    async fn disk_usage(&self) -> Result<DiskUsage> {
+        let mut usage = DiskUsage::default();
+
+        let in_use = self.images_in_use();
+        for image in self.images.list() {
+            let size = image.size();
+            usage.images.count += 1;
+            usage.images.size += size;
+            if !in_use.contains(&image.id) {
+                usage.images.reclaimable += size;
+            }
+        }
+
+        for container in self.list_containers(true).await? {
+            let size = dir_size(&self.container_dir(&container.id).join("upper"));
+            usage.containers.count += 1;
+            usage.containers.size += size;
+            if container.state != ContainerState::Running {
+                usage.containers.reclaimable += size;
+            }
+        }
+
+        Ok(usage)
+    }
+
+    async fn prune(&self, request: &PruneRequest) -> Result<PruneResult> {
+        let mut result = PruneResult::default();
+        let image_dir = PathBuf::from(DEFAULT_IMAGE_DIR);
+        let images_before = dir_size(&image_dir);
+
+        if request.containers {
+            for container in self.list_containers(true).await? {
+                let stopped = matches!(
+                    container.state,
+                    ContainerState::Created | ContainerState::Stopped | ContainerState::Exited | ContainerState::Dead
+                );
+                if !stopped || container.labels.get("managed_by").map(String::as_str) != Some("otherthing-node") {
+                    continue;
+                }
+                let size = dir_size(&self.container_dir(&container.id).join("upper"));
+                self.remove_container(&container.id, false).await?;
+                result.containers_deleted.push(container.id);
+                result.space_reclaimed += size;
+            }
+        }
+
+        if request.images {
+            let in_use = self.images_in_use();
+            for image in self.superseded_images() {
+                if !in_use.contains(&image.id) {
+                    self.images.remove(&image.id)?;
+                    result.images_deleted.push(image.id);
+                }
+            }
+            self.collect_garbage();
+        }
+
+        // Volumes are plain bind mounts here, so there are none to prune
+        result.space_reclaimed += (images_before - dir_size(&image_dir)).max(0);
+        Ok(result)
+    }
+
+    async fn create_network(&self, _name: &str, _internal: bool, _labels: &HashMap<String, String>) -> Result<String> {
+        Err(RuntimeError::OperationFailed(
+            "Network management not implemented for native runtime".to_string()
+        ))
+    }
+
+    async fn list_networks(&self) -> Result<Vec<NetworkInfo>> {
+        // Containers each get their own network namespace
         Ok(vec![])
     }
 
-    async fn remove_image(&self, _reference: &str, _force: bool) -> Result<()> {
+    async fn remove_network(&self, _name: &str) -> Result<()> {
         Err(RuntimeError::OperationFailed(
-            "Image management not implemented for native runtime".to_string()
+            "Network management not implemented for native runtime".to_string()
         ))
     }
 
-    async fn image_exists(&self, _reference: &str) -> Result<bool> {
-        // Would need to check extracted rootfs or image store
-        Ok(false)
+    async fn connect_network(&self, _network: &str, _container: &str) -> Result<()> {
+        Err(RuntimeError::OperationFailed(
+            "Network management not implemented for native runtime".to_string()
+        ))
+    }
+
+    async fn disconnect_network(&self, _network: &str, _container: &str, _force: bool) -> Result<()> {
+        Err(RuntimeError::OperationFailed(
+            "Network management not implemented for native runtime".to_string()
+        ))
     }
 }
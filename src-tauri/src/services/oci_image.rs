@@ -0,0 +1,729 @@
+//! OCI Image Store
+//!
+//! Pulls images straight from OCI/Docker registries for the native runtime:
+//! resolves the manifest for this platform, downloads and verifies each
+//! blob against its digest, and unpacks each layer once into a directory of
+//! its own. Containers stack an image's layers with overlayfs, so images
+//! sharing a base share it on disk and a container only stores its changes.
+//! Saved registry logins are used when a registry asks for them.
+
+#![cfg(all(target_os = "linux", feature = "native-containers"))]
+
+use futures_util::StreamExt;
+use nix::sys::stat::{makedev, mknod, Mode, SFlag};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use super::container_runtime::{Result, RuntimeError};
+use super::registry_auth;
+
+const MANIFEST_ACCEPT: &str = "application/vnd.oci.image.index.v1+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json, \
+    application/vnd.oci.image.manifest.v1+json, \
+    application/vnd.docker.distribution.manifest.v2+json";
+
+/// Prefix of a whiteout entry, which deletes the named file of a lower layer
+const WHITEOUT_PREFIX: &str = ".wh.";
+/// Whiteout entry that hides everything lower layers put in its directory
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+/// A parsed image reference such as `ghcr.io/org/app:1.0`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageReference {
+    /// Registry host as credentials are saved under, e.g. `docker.io`
+    pub registry: String,
+    pub repository: String,
+    /// Tag or `sha256:` digest
+    pub reference: String,
+}
+
+impl ImageReference {
+    pub fn parse(image: &str) -> Result<Self> {
+        let image = image.trim();
+        if image.is_empty() {
+            return Err(RuntimeError::Config("Empty image reference".to_string()));
+        }
+
+        let registry = registry_auth::registry_of(image);
+        let rest = if registry == registry_auth::DOCKER_HUB && !image.starts_with("docker.io/") {
+            image
+        } else {
+            image.split_once('/').map(|(_, rest)| rest).unwrap_or(image)
+        };
+
+        let (name, reference) = if let Some((name, digest)) = rest.split_once('@') {
+            (name, digest.to_string())
+        } else {
+            match rest.rsplit_once(':') {
+                Some((name, tag)) if !tag.contains('/') => (name, tag.to_string()),
+                _ => (rest, "latest".to_string()),
+            }
+        };
+
+        // Official Docker Hub images live under library/
+        let repository = if registry == registry_auth::DOCKER_HUB && !name.contains('/') {
+            format!("library/{}", name)
+        } else {
+            name.to_string()
+        };
+
+        Ok(Self { registry, repository, reference })
+    }
+
+    /// Host serving the registry API
+    fn api_host(&self) -> &str {
+        if self.registry == registry_auth::DOCKER_HUB {
+            "registry-1.docker.io"
+        } else {
+            &self.registry
+        }
+    }
+
+    fn base_url(&self) -> String {
+        let host = self.api_host();
+        // Local registries are usually plain HTTP
+        let scheme = if host.starts_with("localhost") || host.starts_with("127.0.0.1") {
+            "http"
+        } else {
+            "https"
+        };
+        format!("{}://{}/v2/{}", scheme, host, self.repository)
+    }
+
+    /// Canonical `registry/repository:tag` form
+    pub fn canonical(&self) -> String {
+        let separator = if self.reference.starts_with("sha256:") { '@' } else { ':' };
+        format!("{}/{}{}{}", self.registry, self.repository, separator, self.reference)
+    }
+}
+
+/// Runtime defaults from the image config
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImageConfig {
+    #[serde(default)]
+    pub env: Vec<String>,
+    #[serde(default)]
+    pub entrypoint: Option<Vec<String>>,
+    #[serde(default)]
+    pub cmd: Option<Vec<String>>,
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    #[serde(default)]
+    pub user: Option<String>,
+}
+
+/// A layer blob of an image, bottom first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerRef {
+    pub digest: String,
+    pub media_type: String,
+    pub size: i64,
+}
+
+/// An image in the store, saved as its `image.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredImage {
+    /// `sha256:` digest of the image config
+    pub id: String,
+    /// Canonical references pulled as this image
+    pub references: Vec<String>,
+    pub manifest_digest: String,
+    pub layers: Vec<LayerRef>,
+    pub config: ImageConfig,
+    pub created: i64,
+}
+
+impl StoredImage {
+    pub fn size(&self) -> i64 {
+        self.layers.iter().map(|l| l.size).sum()
+    }
+}
+
+#[derive(Deserialize)]
+struct ConfigBlob {
+    #[serde(default)]
+    created: Option<String>,
+    #[serde(default)]
+    config: Option<ConfigSection>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConfigSection {
+    #[serde(default)]
+    env: Option<Vec<String>>,
+    #[serde(default)]
+    entrypoint: Option<Vec<String>>,
+    #[serde(default)]
+    cmd: Option<Vec<String>>,
+    #[serde(default)]
+    working_dir: Option<String>,
+    #[serde(default)]
+    user: Option<String>,
+}
+
+/// Registry API client for one repository
+struct RegistryClient {
+    http: reqwest::Client,
+    image: ImageReference,
+    token: Option<String>,
+    basic: Option<(String, String)>,
+}
+
+impl RegistryClient {
+    fn new(image: ImageReference) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .user_agent("otherthing-node")
+            .connect_timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| RuntimeError::OperationFailed(format!("Failed to create client: {}", e)))?;
+        Ok(Self { http, image, token: None, basic: None })
+    }
+
+    fn request(&self, url: &str, accept: Option<&str>) -> reqwest::RequestBuilder {
+        let mut request = self.http.get(url);
+        if let Some(accept) = accept {
+            request = request.header(reqwest::header::ACCEPT, accept);
+        }
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        } else if let Some((user, password)) = &self.basic {
+            request = request.basic_auth(user, Some(password));
+        }
+        request
+    }
+
+    /// GET with one retry after answering the registry's auth challenge
+    async fn get(&mut self, url: &str, accept: Option<&str>) -> Result<reqwest::Response> {
+        let response = self.request(url, accept).send().await
+            .map_err(|e| RuntimeError::OperationFailed(format!("Registry request failed: {}", e)))?;
+
+        let response = if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            let challenge = response
+                .headers()
+                .get(reqwest::header::WWW_AUTHENTICATE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            self.authenticate(&challenge).await?;
+            self.request(url, accept).send().await
+                .map_err(|e| RuntimeError::OperationFailed(format!("Registry request failed: {}", e)))?
+        } else {
+            response
+        };
+
+        match response.status() {
+            s if s.is_success() => Ok(response),
+            reqwest::StatusCode::NOT_FOUND => Err(RuntimeError::ImageNotFound(self.image.canonical())),
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+                Err(RuntimeError::OperationFailed(format!(
+                    "Access to {} denied; save a login for {} first",
+                    self.image.canonical(),
+                    self.image.registry
+                )))
+            }
+            s => Err(RuntimeError::OperationFailed(format!("Registry returned {} for {}", s, url))),
+        }
+    }
+
+    async fn authenticate(&mut self, challenge: &str) -> Result<()> {
+        let credential = registry_auth::get(&self.image.registry);
+        let login = credential
+            .as_ref()
+            .and_then(|c| Some((c.username.clone()?, c.password.clone()?)));
+
+        let Some(params) = challenge.strip_prefix("Bearer ") else {
+            // Basic auth straight against the registry
+            self.basic = Some(login.ok_or_else(|| {
+                RuntimeError::OperationFailed(format!("{} requires a login", self.image.registry))
+            })?);
+            return Ok(());
+        };
+
+        let params = parse_challenge(params);
+        let realm = params
+            .iter()
+            .find(|(k, _)| k == "realm")
+            .map(|(_, v)| v.clone())
+            .ok_or_else(|| RuntimeError::OperationFailed("Auth challenge without realm".to_string()))?;
+        let mut query: Vec<(String, String)> = params.into_iter().filter(|(k, _)| k != "realm").collect();
+        if !query.iter().any(|(k, _)| k == "scope") {
+            query.push(("scope".to_string(), format!("repository:{}:pull", self.image.repository)));
+        }
+
+        let mut request = self.http.get(&realm).query(&query);
+        if let Some(token) = credential.as_ref().and_then(|c| c.token.clone()) {
+            request = request.bearer_auth(token);
+        } else if let Some((user, password)) = &login {
+            request = request.basic_auth(user, Some(password));
+        }
+
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            token: Option<String>,
+            access_token: Option<String>,
+        }
+        let response: TokenResponse = request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| RuntimeError::OperationFailed(format!("Registry authentication failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| RuntimeError::OperationFailed(format!("Invalid token response: {}", e)))?;
+
+        self.token = Some(response.token.or(response.access_token).ok_or_else(|| {
+            RuntimeError::OperationFailed("Token response without a token".to_string())
+        })?);
+        Ok(())
+    }
+
+    async fn manifest(&mut self, reference: &str) -> Result<(String, serde_json::Value)> {
+        let url = format!("{}/manifests/{}", self.image.base_url(), reference);
+        let response = self.get(&url, Some(MANIFEST_ACCEPT)).await?;
+        let bytes = response.bytes().await
+            .map_err(|e| RuntimeError::OperationFailed(format!("Failed to read manifest: {}", e)))?;
+
+        let digest = format!("sha256:{:x}", Sha256::digest(&bytes));
+        if reference.starts_with("sha256:") && reference != digest {
+            return Err(RuntimeError::OperationFailed(format!(
+                "Manifest digest mismatch: expected {}, got {}", reference, digest
+            )));
+        }
+        let manifest = serde_json::from_slice(&bytes)
+            .map_err(|e| RuntimeError::OperationFailed(format!("Invalid manifest: {}", e)))?;
+        Ok((digest, manifest))
+    }
+
+    /// Stream a blob to `dest`, verifying it against its digest
+    async fn download_blob(&mut self, digest: &str, dest: &Path) -> Result<()> {
+        let expected = digest
+            .strip_prefix("sha256:")
+            .ok_or_else(|| RuntimeError::OperationFailed(format!("Unsupported digest: {}", digest)))?;
+
+        let url = format!("{}/blobs/{}", self.image.base_url(), digest);
+        let response = self.get(&url, None).await?;
+
+        let partial = dest.with_extension("partial");
+        let mut file = tokio::fs::File::create(&partial).await?;
+        let mut hasher = Sha256::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk
+                .map_err(|e| RuntimeError::OperationFailed(format!("Download of {} failed: {}", digest, e)))?;
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        drop(file);
+
+        let actual = format!("{:x}", hasher.finalize());
+        if actual != expected {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(RuntimeError::OperationFailed(format!(
+                "Digest mismatch for {}: got sha256:{}", digest, actual
+            )));
+        }
+        tokio::fs::rename(&partial, dest).await?;
+        Ok(())
+    }
+}
+
+/// `key="value",key2="value2"` pairs of a WWW-Authenticate header; values
+/// may contain commas inside the quotes
+fn parse_challenge(params: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut rest = params.trim();
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let (value, remaining) = if let Some(quoted) = after.strip_prefix('"') {
+            match quoted.split_once('"') {
+                Some((value, remaining)) => (value.to_string(), remaining),
+                None => (quoted.to_string(), ""),
+            }
+        } else {
+            match after.split_once(',') {
+                Some((value, remaining)) => (value.trim().to_string(), remaining),
+                None => (after.trim().to_string(), ""),
+            }
+        };
+        pairs.push((key, value));
+        rest = remaining.trim_start_matches(',').trim();
+    }
+    pairs
+}
+
+/// Refuse anything but a `sha256:` digest of 64 lowercase hex digits
+fn check_digest(digest: &str) -> Result<()> {
+    let valid = digest
+        .strip_prefix("sha256:")
+        .is_some_and(|hex| hex.len() == 64 && hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')));
+    if !valid {
+        return Err(RuntimeError::OperationFailed(format!("Invalid digest in manifest: {}", digest)));
+    }
+    Ok(())
+}
+
+/// Architecture name registries use for this build
+fn oci_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "arm" => "arm",
+        other => other,
+    }
+}
+
+/// Image storage under a root directory:
+/// `blobs/sha256/<hex>` for verified blobs and `images/<hex>/` holding
+/// `image.json`, and `layers/<hex>/diff` for each unpacked layer, which
+/// images and containers share
+pub struct ImageStore {
+    root: PathBuf,
+}
+
+impl ImageStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn blob_path(&self, digest: &str) -> PathBuf {
+        self.root.join("blobs").join(digest.replace(':', "/"))
+    }
+
+    fn image_dir(&self, id: &str) -> PathBuf {
+        self.root.join("images").join(id.trim_start_matches("sha256:"))
+    }
+
+    fn layer_dir(&self, digest: &str) -> PathBuf {
+        self.root.join("layers").join(digest.trim_start_matches("sha256:"))
+    }
+
+    /// overlayfs `lowerdir` entries for layers listed bottom first, as
+    /// images list them; overlayfs wants the topmost first
+    pub fn lower_dirs(&self, layers: &[String]) -> Vec<PathBuf> {
+        layers.iter().rev().map(|digest| self.layer_dir(digest).join("diff")).collect()
+    }
+
+    /// Unpack any of an image's layers that are not unpacked yet
+    pub async fn unpack(&self, image: &StoredImage) -> Result<()> {
+        for layer in &image.layers {
+            let dir = self.layer_dir(&layer.digest);
+            let complete = dir.join(".complete");
+            if complete.exists() {
+                continue;
+            }
+
+            let blob = self.blob_path(&layer.digest);
+            if !blob.exists() {
+                return Err(RuntimeError::ImageNotFound(format!(
+                    "layer {} of {} is missing, pull the image again", layer.digest, image.id
+                )));
+            }
+            let _ = std::fs::remove_dir_all(&dir);
+            let diff = dir.join("diff");
+            std::fs::create_dir_all(&diff)?;
+
+            let media_type = layer.media_type.clone();
+            let target = diff.clone();
+            let extracted = tokio::task::spawn_blocking(move || extract_layer(&blob, &media_type, &target))
+                .await
+                .map_err(|e| RuntimeError::OperationFailed(e.to_string()))?;
+            if let Err(e) = extracted {
+                let _ = std::fs::remove_dir_all(&dir);
+                return Err(e);
+            }
+            std::fs::write(&complete, "")?;
+        }
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<StoredImage> {
+        let Ok(entries) = std::fs::read_dir(self.root.join("images")) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter_map(|entry| std::fs::read_to_string(entry.path().join("image.json")).ok())
+            .filter_map(|data| serde_json::from_str(&data).ok())
+            .collect()
+    }
+
+    /// Image pulled as `image`, or with that ID
+    pub fn find(&self, image: &str) -> Option<StoredImage> {
+        let canonical = ImageReference::parse(image).ok().map(|r| r.canonical());
+        self.list().into_iter().find(|stored| {
+            stored.id == image
+                || stored.id.trim_start_matches("sha256:").starts_with(image)
+                || canonical.as_ref().is_some_and(|c| stored.references.contains(c))
+        })
+    }
+
+    fn save(&self, image: &StoredImage) -> Result<()> {
+        let data = serde_json::to_string_pretty(image)
+            .map_err(|e| RuntimeError::Config(e.to_string()))?;
+        std::fs::write(self.image_dir(&image.id).join("image.json"), data)?;
+        Ok(())
+    }
+
+    /// Pull an image and unpack it, unless it is already stored
+    pub async fn pull(&self, image: &str) -> Result<StoredImage> {
+        let reference = ImageReference::parse(image)?;
+        let mut client = RegistryClient::new(reference.clone())?;
+
+        let (mut digest, mut manifest) = client.manifest(&reference.reference).await?;
+
+        // A multi-platform index points at one manifest per platform
+        if let Some(manifests) = manifest.get("manifests").and_then(|m| m.as_array()) {
+            let arch = oci_arch();
+            let entry = manifests
+                .iter()
+                .find(|m| {
+                    let platform = &m["platform"];
+                    platform["os"] == "linux"
+                        && platform["architecture"] == arch
+                        && (arch != "arm64" || platform["variant"].is_null() || platform["variant"] == "v8")
+                })
+                .ok_or_else(|| {
+                    RuntimeError::OperationFailed(format!("{} has no linux/{} image", image, arch))
+                })?;
+            let platform_digest = entry["digest"]
+                .as_str()
+                .ok_or_else(|| RuntimeError::OperationFailed("Index entry without digest".to_string()))?
+                .to_string();
+            check_digest(&platform_digest)?;
+            (digest, manifest) = client.manifest(&platform_digest).await?;
+        }
+
+        let config_digest = manifest["config"]["digest"]
+            .as_str()
+            .ok_or_else(|| RuntimeError::OperationFailed("Manifest without config".to_string()))?
+            .to_string();
+        let layers: Vec<LayerRef> = manifest["layers"]
+            .as_array()
+            .map(|layers| {
+                layers
+                    .iter()
+                    .filter_map(|l| {
+                        Some(LayerRef {
+                            digest: l["digest"].as_str()?.to_string(),
+                            media_type: l["mediaType"].as_str().unwrap_or_default().to_string(),
+                            size: l["size"].as_i64().unwrap_or(0),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        // Digests name files under the store, so nothing but a digest may
+        // come from the (unverified) tag manifest
+        check_digest(&config_digest)?;
+        for layer in &layers {
+            check_digest(&layer.digest)?;
+        }
+
+        if let Some(mut existing) = self.list().into_iter().find(|i| i.id == config_digest) {
+            let canonical = reference.canonical();
+            if !existing.references.contains(&canonical) {
+                existing.references.push(canonical);
+                self.save(&existing)?;
+            }
+            return Ok(existing);
+        }
+
+        std::fs::create_dir_all(self.root.join("blobs").join("sha256"))?;
+        for digest in std::iter::once(&config_digest).chain(layers.iter().map(|l| &l.digest)) {
+            let path = self.blob_path(digest);
+            if !path.exists() {
+                log::info!("Native runtime: downloading {} for {}", digest, image);
+                client.download_blob(digest, &path).await?;
+            }
+        }
+
+        let config_blob: ConfigBlob = serde_json::from_slice(&std::fs::read(self.blob_path(&config_digest))?)
+            .map_err(|e| RuntimeError::OperationFailed(format!("Invalid image config: {}", e)))?;
+        let section = config_blob.config;
+        let config = ImageConfig {
+            env: section.as_ref().and_then(|c| c.env.clone()).unwrap_or_default(),
+            entrypoint: section.as_ref().and_then(|c| c.entrypoint.clone()),
+            cmd: section.as_ref().and_then(|c| c.cmd.clone()),
+            working_dir: section.as_ref().and_then(|c| c.working_dir.clone()).filter(|d| !d.is_empty()),
+            user: section.as_ref().and_then(|c| c.user.clone()).filter(|u| !u.is_empty()),
+        };
+
+        let stored = StoredImage {
+            id: config_digest,
+            references: vec![reference.canonical()],
+            manifest_digest: digest,
+            layers,
+            config,
+            created: config_blob
+                .created
+                .and_then(|c| chrono::DateTime::parse_from_rfc3339(&c).ok())
+                .map(|c| c.timestamp())
+                .unwrap_or(0),
+        };
+
+        self.unpack(&stored).await?;
+
+        let image_dir = self.image_dir(&stored.id);
+        let _ = std::fs::remove_dir_all(&image_dir);
+        std::fs::create_dir_all(&image_dir)?;
+        self.save(&stored)?;
+        log::info!("Native runtime: pulled {} ({})", image, stored.id);
+        Ok(stored)
+    }
+
+    /// Remove an image's record; its layers and blobs stay until `gc`
+    pub fn remove(&self, image: &str) -> Result<()> {
+        let stored = self.find(image).ok_or_else(|| RuntimeError::ImageNotFound(image.to_string()))?;
+        std::fs::remove_dir_all(self.image_dir(&stored.id))?;
+        Ok(())
+    }
+
+    /// Delete blobs no stored image refers to, and unpacked layers used
+    /// neither by a stored image nor by `containers`. Returns the number
+    /// of layers removed.
+    pub fn gc(&self, containers: &HashSet<String>) -> usize {
+        let images = self.list();
+        let blobs: HashSet<String> = images
+            .iter()
+            .flat_map(|i| std::iter::once(i.id.clone()).chain(i.layers.iter().map(|l| l.digest.clone())))
+            .map(|digest| digest.trim_start_matches("sha256:").to_string())
+            .collect();
+        let layers: HashSet<String> = blobs
+            .iter()
+            .cloned()
+            .chain(containers.iter().map(|d| d.trim_start_matches("sha256:").to_string()))
+            .collect();
+
+        if let Ok(entries) = std::fs::read_dir(self.root.join("blobs").join("sha256")) {
+            for entry in entries.flatten() {
+                if !blobs.contains(&*entry.file_name().to_string_lossy()) {
+                    let _ = std::fs::remove_file(entry.path());
+                }
+            }
+        }
+
+        let mut removed = 0;
+        if let Ok(entries) = std::fs::read_dir(self.root.join("layers")) {
+            for entry in entries.flatten() {
+                // Layers still being unpacked have no marker yet
+                let complete = entry.path().join(".complete").exists();
+                if complete
+                    && !layers.contains(&*entry.file_name().to_string_lossy())
+                    && std::fs::remove_dir_all(entry.path()).is_ok()
+                {
+                    removed += 1;
+                }
+            }
+        }
+        if removed > 0 {
+            log::info!("Native runtime: removed {} unused layers", removed);
+        }
+        removed
+    }
+}
+
+fn open_layer(blob: &Path, media_type: &str) -> Result<tar::Archive<Box<dyn Read>>> {
+    let file = std::fs::File::open(blob)?;
+    let reader: Box<dyn Read> = if media_type.ends_with("gzip") {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else if media_type.ends_with("zstd") {
+        return Err(RuntimeError::OperationFailed("zstd-compressed layers are not supported".to_string()));
+    } else {
+        Box::new(file)
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+fn is_relative(path: &Path) -> bool {
+    path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+
+/// Directory `path` under `root`, created as needed; refuses to go through
+/// a symlink so whiteouts can never land outside the layer
+fn layer_subdir(root: &Path, path: &Path) -> Option<PathBuf> {
+    if !is_relative(path) {
+        return None;
+    }
+    let mut dir = root.to_path_buf();
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            continue;
+        };
+        dir.push(name);
+        match dir.symlink_metadata() {
+            Ok(m) if m.is_dir() => {}
+            Ok(_) => return None,
+            Err(_) => std::fs::create_dir(&dir).ok()?,
+        }
+    }
+    Some(dir)
+}
+
+/// Mark a directory opaque to overlayfs, hiding lower layers' contents
+fn set_opaque(dir: &Path) -> std::io::Result<()> {
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes())?;
+    let name = c"trusted.overlay.opaque";
+    let ret = unsafe {
+        libc::setxattr(path.as_ptr(), name.as_ptr(), b"y".as_ptr().cast(), 1, 0)
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Unpack one layer into its own `diff` directory, turning OCI whiteouts
+/// into the overlayfs form (a 0/0 character device, or the opaque xattr).
+/// Without the privileges for that the `.wh.` files are kept as they are,
+/// which fuse-overlayfs understands too.
+fn extract_layer(blob: &Path, media_type: &str, diff: &Path) -> Result<()> {
+    let mut archive = open_layer(blob, media_type)?;
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_ownerships(true);
+    archive.set_overwrite(true);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+        let parent = path.parent().unwrap_or(Path::new(""));
+
+        if name == OPAQUE_WHITEOUT {
+            let Some(dir) = layer_subdir(diff, parent) else {
+                continue;
+            };
+            if set_opaque(&dir).is_err() {
+                std::fs::File::create(dir.join(OPAQUE_WHITEOUT))?;
+            }
+        } else if let Some(hidden) = name.strip_prefix(WHITEOUT_PREFIX) {
+            if hidden.is_empty() || hidden == "." || hidden == ".." {
+                continue;
+            }
+            let Some(dir) = layer_subdir(diff, parent) else {
+                continue;
+            };
+            let whiteout = mknod(
+                &dir.join(hidden),
+                SFlag::S_IFCHR,
+                Mode::empty(),
+                makedev(0, 0),
+            );
+            if whiteout.is_err() {
+                std::fs::File::create(dir.join(&name))?;
+            }
+        } else {
+            entry.unpack_in(diff)?;
+        }
+    }
+    Ok(())
+}
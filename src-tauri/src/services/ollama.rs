@@ -1,27 +1,116 @@
-use crate::models::{OllamaModel, OllamaStatus};
+use crate::config::OllamaConfig;
+use crate::error::NodeError;
+use crate::models::{
+    Hardware, ModelFit, OllamaHealth, OllamaHealthEvent, OllamaModel, OllamaStatus, PullProgress,
+    ResourceLimits,
+};
 use std::path::PathBuf;
 use std::process::{Child, Command, Stdio};
-use std::sync::Mutex;
-use tokio::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+
+use super::download;
+use super::resources;
+use super::hardware::HardwareDetector;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+/// How often the supervisor checks the server
+const SUPERVISOR_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Consecutive failed health checks before a live process counts as hung
+const HANG_THRESHOLD: u32 = 3;
+
+/// Bounds of the restart backoff
+const MIN_RESTART_DELAY: Duration = Duration::from_secs(2);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(120);
+
+/// Longest an embedding request may take
+const EMBED_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Models offered on first start, best first, with the memory they need
+const RECOMMENDED_MODELS: &[(u64, &str)] = &[
+    (16 * GIB, "llama3.1:8b"),
+    (8 * GIB, "llama3.2:3b"),
+    (4 * GIB, "llama3.2:1b"),
+    (0, "qwen2.5:0.5b"),
+];
 
 pub struct OllamaManager {
     process: Mutex<Option<Child>>,
     custom_path: Mutex<Option<PathBuf>>,
+    config: Mutex<OllamaConfig>,
+    /// CPU and memory the server is started with
+    resource_limits: Mutex<ResourceLimits>,
+    /// Detected once, for estimating whether models fit
+    hardware: OnceLock<Hardware>,
+    /// Set while the managed server should be kept alive
+    supervised: AtomicBool,
+    health_tx: broadcast::Sender<OllamaHealthEvent>,
 }
 
 impl OllamaManager {
     pub fn new() -> Self {
+        Self::with_config(OllamaConfig::default())
+    }
+
+    pub fn with_config(config: OllamaConfig) -> Self {
         Self {
             process: Mutex::new(None),
             custom_path: Mutex::new(None),
+            config: Mutex::new(config),
+            resource_limits: Mutex::new(ResourceLimits::default()),
+            hardware: OnceLock::new(),
+            supervised: AtomicBool::new(false),
+            health_tx: broadcast::channel(16).0,
         }
     }
 
+    pub fn config(&self) -> OllamaConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Limits for the managed server, applied when it next starts
+    pub fn set_resource_limits(&self, limits: ResourceLimits) {
+        *self.resource_limits.lock().unwrap() = limits;
+    }
+
+    /// Replace the settings. Spawn options take effect the next time the
+    /// managed server starts; returns whether a restart is needed for that.
+    pub fn set_config(&self, config: OllamaConfig) -> bool {
+        let mut current = self.config.lock().unwrap();
+        let needs_restart = self.process.lock().unwrap().is_some()
+            && (current.host != config.host
+                || current.port != config.port
+                || current.models_dir != config.models_dir
+                || current.keep_alive != config.keep_alive
+                || current.gpu_devices != config.gpu_devices
+                || current.env != config.env);
+        *current = config;
+        needs_restart
+    }
+
+    /// Per-request model options derived from the settings
+    pub fn request_options(&self) -> Option<serde_json::Value> {
+        self.config
+            .lock()
+            .unwrap()
+            .num_gpu
+            .map(|layers| serde_json::json!({ "num_gpu": layers }))
+    }
+
     pub fn get_ollama_path(&self) -> PathBuf {
         if let Some(path) = self.custom_path.lock().unwrap().as_ref() {
             return path.clone();
         }
 
+        // Installed by the app
+        if let Some(path) = Self::managed_binary() {
+            return path;
+        }
+
         // Default paths by platform
         #[cfg(target_os = "windows")]
         {
@@ -91,54 +180,181 @@ impl OllamaManager {
         }
 
         // Also check if ollama is running via API
-        Self::check_api_running()
+        self.check_api_running()
     }
 
-    fn check_api_running() -> bool {
+    fn check_api_running(&self) -> bool {
         // Sync check for ollama API
-        std::thread::spawn(|| {
-            reqwest::blocking::get("http://localhost:11434/api/tags").is_ok()
+        let url = format!("{}/api/tags", self.get_host());
+        std::thread::spawn(move || {
+            reqwest::blocking::get(url).is_ok()
         })
         .join()
         .unwrap_or(false)
     }
 
-    pub async fn start(&self) -> Result<(), String> {
+    pub async fn start(&self) -> Result<(), NodeError> {
         if self.is_running() {
             return Ok(());
         }
 
         let path = self.get_ollama_path();
+        let config = self.config();
+
+        let mut cmd = resources::command(&path, &self.resource_limits.lock().unwrap());
+        cmd.arg("serve")
+            .env("OLLAMA_HOST", format!("{}:{}", config.host, config.port));
+        if let Some(dir) = &config.models_dir {
+            cmd.env("OLLAMA_MODELS", dir);
+        }
+        if let Some(keep_alive) = &config.keep_alive {
+            cmd.env("OLLAMA_KEEP_ALIVE", keep_alive);
+        }
+        if let Some(devices) = &config.gpu_devices {
+            cmd.env("CUDA_VISIBLE_DEVICES", devices)
+                .env("HIP_VISIBLE_DEVICES", devices);
+        }
+        cmd.envs(&config.env);
 
-        let child = Command::new(&path)
-            .arg("serve")
+        let child = cmd
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .spawn()
-            .map_err(|e| format!("Failed to start Ollama: {}", e))?;
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => NodeError::NotInstalled(format!("Ollama not found at {}", path.display())),
+                _ => NodeError::Internal(format!("Failed to start Ollama: {}", e)),
+            })?;
 
         *self.process.lock().unwrap() = Some(child);
+        self.supervised.store(true, Ordering::SeqCst);
 
         // Wait for API to be ready
         for _ in 0..30 {
             tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-            if Self::check_api_running() {
+            if self.check_api_running() {
                 return Ok(());
             }
         }
 
-        Err("Ollama started but API not responding".to_string())
+        Err(NodeError::Timeout("Ollama started but API not responding".to_string()))
     }
 
-    pub async fn stop(&self) -> Result<(), String> {
+    pub async fn stop(&self) -> Result<(), NodeError> {
+        self.supervised.store(false, Ordering::SeqCst);
         if let Ok(mut guard) = self.process.lock() {
             if let Some(mut child) = guard.take() {
-                child.kill().map_err(|e| format!("Failed to stop Ollama: {}", e))?;
+                child.kill().map_err(|e| NodeError::Internal(format!("Failed to stop Ollama: {}", e)))?;
             }
         }
         Ok(())
     }
 
+    pub fn subscribe_health(&self) -> broadcast::Receiver<OllamaHealthEvent> {
+        self.health_tx.subscribe()
+    }
+
+    /// Watch the server until the app exits: emit health changes and
+    /// restart the managed process with backoff when it crashes or hangs.
+    /// Servers not started by the app are only reported on.
+    pub async fn supervise(&self) {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap_or_default();
+        let mut last = None;
+        let mut failures = 0;
+        let mut restarts = 0;
+        let mut delay = MIN_RESTART_DELAY;
+
+        loop {
+            tokio::time::sleep(SUPERVISOR_INTERVAL).await;
+
+            let exited = self.take_exited_process();
+            let responding = client
+                .get(format!("{}/api/version", self.get_host()))
+                .send()
+                .await
+                .map(|r| r.status().is_success())
+                .unwrap_or(false);
+
+            let problem = if responding {
+                failures = 0;
+                delay = MIN_RESTART_DELAY;
+                self.report(&mut last, OllamaHealth::Running, None, restarts);
+                continue;
+            } else if !self.supervised.load(Ordering::SeqCst) {
+                self.report(&mut last, OllamaHealth::Stopped, None, restarts);
+                continue;
+            } else if let Some(status) = exited {
+                (OllamaHealth::Crashed, format!("Ollama exited unexpectedly ({})", status))
+            } else {
+                failures += 1;
+                if failures < HANG_THRESHOLD {
+                    continue;
+                }
+                (OllamaHealth::Unresponsive, "Ollama stopped responding".to_string())
+            };
+
+            let (health, message) = problem;
+            log::warn!("{}, restarting in {:?}", message, delay);
+            self.report(&mut last, health, Some(message), restarts);
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RESTART_DELAY);
+
+            // stop() may have been called while backing off
+            if !self.supervised.load(Ordering::SeqCst) {
+                continue;
+            }
+            if let Some(mut child) = self.process.lock().unwrap().take() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+
+            restarts += 1;
+            failures = 0;
+            self.report(&mut last, OllamaHealth::Restarting, None, restarts);
+            match self.start().await {
+                Ok(()) => {
+                    log::info!("Ollama restarted");
+                    self.report(&mut last, OllamaHealth::Running, None, restarts);
+                }
+                Err(e) => {
+                    log::error!("Failed to restart Ollama: {}", e);
+                    self.report(&mut last, OllamaHealth::RestartFailed, Some(e.to_string()), restarts);
+                }
+            }
+        }
+    }
+
+    /// Reap the managed process if it has exited, returning its exit status
+    fn take_exited_process(&self) -> Option<std::process::ExitStatus> {
+        let mut guard = self.process.lock().unwrap();
+        let status = guard.as_mut()?.try_wait().ok()??;
+        *guard = None;
+        Some(status)
+    }
+
+    fn report(
+        &self,
+        last: &mut Option<OllamaHealth>,
+        health: OllamaHealth,
+        message: Option<String>,
+        restarts: u32,
+    ) {
+        // Failures are always reported, steady states only when they change
+        let is_failure = message.is_some();
+        if !is_failure && *last == Some(health) {
+            return;
+        }
+        *last = Some(health);
+        let _ = self.health_tx.send(OllamaHealthEvent {
+            health,
+            message,
+            restarts,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+
     pub async fn get_status(&self) -> OllamaStatus {
         let installed = self.is_installed();
         let running = self.is_running();
@@ -151,66 +367,149 @@ impl OllamaManager {
         OllamaStatus { installed, running, models }
     }
 
-    pub async fn list_models(&self) -> Result<Vec<OllamaModel>, String> {
+    pub async fn list_models(&self) -> Result<Vec<OllamaModel>, NodeError> {
         let client = reqwest::Client::new();
         let response = client
-            .get("http://localhost:11434/api/tags")
+            .get(format!("{}/api/tags", self.get_host()))
             .send()
             .await
-            .map_err(|e| format!("Failed to list models: {}", e))?;
+            .map_err(|e| NodeError::request("Failed to list models", e))?;
 
         let data: serde_json::Value = response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| NodeError::Upstream(format!("Failed to parse response: {}", e)))?;
 
-        let models = data["models"]
+        let hardware = self.hardware().await;
+        let models: Vec<OllamaModel> = data["models"]
             .as_array()
             .unwrap_or(&vec![])
             .iter()
             .filter_map(|m| {
+                let details = &m["details"];
+                let size = m["size"].as_u64().unwrap_or(0);
+                let estimated_memory = estimate_memory(size);
                 Some(OllamaModel {
                     name: m["name"].as_str()?.to_string(),
-                    size: m["size"].as_u64().unwrap_or(0),
+                    size,
                     modified_at: m["modified_at"].as_str().unwrap_or("").to_string(),
+                    digest: m["digest"].as_str().unwrap_or("").to_string(),
+                    family: details["family"].as_str().map(String::from),
+                    parameter_size: details["parameter_size"].as_str().map(String::from),
+                    quantization: details["quantization_level"].as_str().map(String::from),
+                    template: None,
+                    estimated_memory,
+                    fit: hardware
+                        .as_ref()
+                        .map_or(ModelFit::Unknown, |hw| model_fit(hw, estimated_memory)),
                 })
             })
             .collect();
 
-        Ok(models)
+        // Fill in what only /api/show reports
+        let shown = futures_util::future::join_all(
+            models.iter().map(|m| self.show_model(&client, &m.name)),
+        )
+        .await;
+
+        Ok(models
+            .into_iter()
+            .zip(shown)
+            .map(|(mut model, show)| {
+                if let Some(show) = show {
+                    let details = &show["details"];
+                    model.template = show["template"].as_str().map(String::from);
+                    model.family = model.family.or_else(|| details["family"].as_str().map(String::from));
+                    model.parameter_size = model
+                        .parameter_size
+                        .or_else(|| details["parameter_size"].as_str().map(String::from));
+                    model.quantization = model
+                        .quantization
+                        .or_else(|| details["quantization_level"].as_str().map(String::from));
+                }
+                model
+            })
+            .collect())
+    }
+
+    async fn show_model(&self, client: &reqwest::Client, name: &str) -> Option<serde_json::Value> {
+        let response = client
+            .post(format!("{}/api/show", self.get_host()))
+            .json(&serde_json::json!({ "model": name }))
+            .send()
+            .await
+            .ok()?;
+        if !response.status().is_success() {
+            log::debug!("No details for model {}: {}", name, response.status());
+            return None;
+        }
+        response.json().await.ok()
+    }
+
+    async fn hardware(&self) -> Option<Hardware> {
+        if let Some(hardware) = self.hardware.get() {
+            return Some(hardware.clone());
+        }
+        let detected = tokio::task::spawn_blocking(HardwareDetector::detect).await.ok()?;
+        Some(self.hardware.get_or_init(|| detected).clone())
     }
 
     pub async fn pull_model(
         &self,
         name: &str,
-        progress_tx: Option<mpsc::Sender<(String, Option<f64>)>>,
-    ) -> Result<(), String> {
+        progress_tx: Option<mpsc::Sender<PullProgress>>,
+    ) -> Result<(), NodeError> {
         let client = reqwest::Client::new();
         let response = client
-            .post("http://localhost:11434/api/pull")
+            .post(format!("{}/api/pull", self.get_host()))
             .json(&serde_json::json!({ "name": name, "stream": true }))
             .send()
             .await
-            .map_err(|e| format!("Failed to pull model: {}", e))?;
+            .map_err(|e| NodeError::request("Failed to pull model", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(NodeError::Upstream(format!("Ollama returned error {}: {}", status, text)));
+        }
 
         let mut stream = response.bytes_stream();
         use futures_util::StreamExt;
 
+        // Progress is newline-delimited JSON; a line, or a character in it,
+        // may span several chunks, so only whole lines are decoded
+        let mut buffer: Vec<u8> = Vec::new();
+
         while let Some(chunk) = stream.next().await {
-            if let Ok(bytes) = chunk {
-                if let Ok(text) = std::str::from_utf8(&bytes) {
-                    for line in text.lines() {
-                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(line) {
-                            let status = json["status"].as_str().unwrap_or("").to_string();
-                            let percent = json["completed"]
-                                .as_f64()
-                                .and_then(|c| json["total"].as_f64().map(|t| c / t * 100.0));
-
-                            if let Some(ref tx) = progress_tx {
-                                let _ = tx.send((status, percent)).await;
-                            }
-                        }
-                    }
+            let bytes = chunk.map_err(|e| NodeError::request("Pull stream interrupted", e))?;
+            buffer.extend_from_slice(&bytes);
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=pos).collect();
+                let json = match serde_json::from_slice::<serde_json::Value>(&line) {
+                    Ok(json) => json,
+                    Err(_) => continue,
+                };
+
+                if let Some(error) = json["error"].as_str() {
+                    return Err(NodeError::Upstream(format!("Failed to pull model: {}", error)));
+                }
+
+                let completed = json["completed"].as_u64();
+                let total = json["total"].as_u64();
+                let percent = match (completed, total) {
+                    (Some(c), Some(t)) if t > 0 => Some(c as f64 / t as f64 * 100.0),
+                    _ => None,
+                };
+
+                if let Some(ref tx) = progress_tx {
+                    let _ = tx.send(PullProgress {
+                        model: name.to_string(),
+                        status: json["status"].as_str().unwrap_or("").to_string(),
+                        percent,
+                        completed,
+                        total,
+                    }).await;
                 }
             }
         }
@@ -218,21 +517,223 @@ impl OllamaManager {
         Ok(())
     }
 
-    pub async fn delete_model(&self, name: &str) -> Result<(), String> {
+    pub async fn delete_model(&self, name: &str) -> Result<(), NodeError> {
         let client = reqwest::Client::new();
-        client
-            .delete("http://localhost:11434/api/delete")
+        let response = client
+            .delete(format!("{}/api/delete", self.get_host()))
             .json(&serde_json::json!({ "name": name }))
             .send()
             .await
-            .map_err(|e| format!("Failed to delete model: {}", e))?;
+            .map_err(|e| NodeError::request("Failed to delete model", e))?;
 
-        Ok(())
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            reqwest::StatusCode::NOT_FOUND => Err(NodeError::NotFound(format!("Model {} is not installed", name))),
+            status => Err(NodeError::Upstream(format!("Ollama returned error {} deleting {}", status, name))),
+        }
+    }
+
+    /// Embed `inputs` with the configured embedding model, one vector each
+    pub async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, NodeError> {
+        let model = self.config.lock().unwrap().embedding_model.clone();
+        let response = reqwest::Client::new()
+            .post(format!("{}/api/embed", self.get_host()))
+            .json(&serde_json::json!({ "model": model, "input": inputs }))
+            .timeout(EMBED_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| NodeError::request("Failed to embed text", e))?;
+
+        match response.status() {
+            status if status.is_success() => {}
+            reqwest::StatusCode::NOT_FOUND => {
+                return Err(NodeError::NotInstalled(format!("Embedding model {} is not installed", model)))
+            }
+            status => {
+                let text = response.text().await.unwrap_or_default();
+                return Err(NodeError::Upstream(format!("Ollama returned error {} embedding text: {}", status, text)));
+            }
+        }
+
+        #[derive(serde::Deserialize)]
+        struct EmbedResponse {
+            embeddings: Vec<Vec<f32>>,
+        }
+        let data: EmbedResponse = response
+            .json()
+            .await
+            .map_err(|e| NodeError::Upstream(format!("Failed to parse response: {}", e)))?;
+        if data.embeddings.len() != inputs.len() {
+            return Err(NodeError::Upstream(format!(
+                "Ollama returned {} embeddings for {} inputs",
+                data.embeddings.len(),
+                inputs.len()
+            )));
+        }
+        Ok(data.embeddings)
+    }
+
+    /// Pick the largest recommended model the machine can comfortably run.
+    /// A GPU with at least 4 GiB of VRAM is preferred over system RAM.
+    pub fn recommended_model(hardware: &Hardware) -> &'static str {
+        let vram = hardware.gpu.iter().filter_map(|g| g.vram).max().unwrap_or(0);
+        let budget = if vram >= 4 * GIB {
+            vram
+        } else {
+            // Leave room for the OS and the app itself
+            hardware.memory.total / 2
+        };
+
+        RECOMMENDED_MODELS
+            .iter()
+            .find(|(needed, _)| budget >= *needed)
+            .map(|(_, name)| *name)
+            .unwrap_or("qwen2.5:0.5b")
+    }
+
+    /// Pull the recommended model if no model is installed yet.
+    /// Returns the pulled model, or `None` when models were already present.
+    pub async fn bootstrap_model(
+        &self,
+        hardware: &Hardware,
+        progress_tx: Option<mpsc::Sender<PullProgress>>,
+    ) -> Result<Option<String>, NodeError> {
+        if !self.list_models().await?.is_empty() {
+            return Ok(None);
+        }
+
+        let model = Self::recommended_model(hardware);
+        log::info!("No Ollama models installed, pulling recommended model {}", model);
+        self.pull_model(model, progress_tx).await?;
+        Ok(Some(model.to_string()))
+    }
+
+    /// Directory the app installs Ollama into
+    pub fn managed_dir() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("otherthing-node")
+            .join("ollama")
+    }
+
+    /// The app-installed Ollama binary, if any
+    pub fn managed_binary() -> Option<PathBuf> {
+        let dir = Self::managed_dir();
+        ["bin/ollama", "ollama", "ollama.exe"]
+            .iter()
+            .map(|p| dir.join(p))
+            .find(|p| p.is_file())
+    }
+
+    /// Version reported by the Ollama binary in use
+    pub fn installed_version(&self) -> Option<String> {
+        let output = Command::new(self.get_ollama_path())
+            .arg("--version")
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        // e.g. "ollama version is 0.5.7"
+        String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .last()
+            .map(|v| v.trim_start_matches('v').to_string())
+    }
+
+    /// Latest released Ollama version
+    pub async fn latest_version() -> Result<String, NodeError> {
+        let release: serde_json::Value = reqwest::Client::new()
+            .get("https://api.github.com/repos/ollama/ollama/releases/latest")
+            .header("User-Agent", "otherthing-node")
+            .send()
+            .await
+            .map_err(|e| NodeError::request("Failed to check Ollama releases", e))?
+            .json()
+            .await
+            .map_err(|e| NodeError::Upstream(format!("Failed to parse release info: {}", e)))?;
+
+        release["tag_name"]
+            .as_str()
+            .map(|tag| tag.trim_start_matches('v').to_string())
+            .ok_or_else(|| NodeError::Upstream("Release info has no version".to_string()))
+    }
+
+    /// Download an Ollama release (latest when `version` is `None`) into the
+    /// managed directory, verify its checksum and use it from now on
+    pub async fn install(&self, version: Option<&str>) -> Result<String, NodeError> {
+        let version = match version {
+            Some(v) => v.trim_start_matches('v').to_string(),
+            None => Self::latest_version().await?,
+        };
+
+        #[cfg(target_os = "windows")]
+        let asset = format!("ollama-windows-{}.zip", if cfg!(target_arch = "aarch64") { "arm64" } else { "amd64" });
+        #[cfg(target_os = "macos")]
+        let asset = "ollama-darwin.tgz".to_string();
+        #[cfg(target_os = "linux")]
+        let asset = format!("ollama-linux-{}.tgz", if cfg!(target_arch = "aarch64") { "arm64" } else { "amd64" });
+
+        let base = format!("https://github.com/ollama/ollama/releases/download/v{}", version);
+        log::info!("Installing Ollama {} from {}/{}", version, base, asset);
+
+        let listing = download::fetch(&format!("{}/sha256sum.txt", base)).await?;
+        let expected = download::checksum_for(&String::from_utf8_lossy(&listing), &asset)
+            .ok_or_else(|| NodeError::NotFound(format!("No checksum published for {}", asset)))?;
+
+        let archive = download::fetch(&format!("{}/{}", base, asset)).await?;
+        download::verify_sha256(&archive, &expected)?;
+
+        // Unpack next to the current install, then swap
+        let dir = Self::managed_dir();
+        let parent = dir.parent().ok_or("Invalid install directory")?;
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+        let archive_path = parent.join(&asset);
+        let staging = parent.join("ollama.new");
+        std::fs::write(&archive_path, &archive)
+            .map_err(|e| format!("Failed to write archive: {}", e))?;
+        let _ = std::fs::remove_dir_all(&staging);
+        let extracted = download::extract_archive(&archive_path, &staging);
+        let _ = std::fs::remove_file(&archive_path);
+        extracted?;
+
+        // A managed server has to be stopped to replace its binary
+        let was_running = self.process.lock().unwrap().is_some();
+        if was_running {
+            self.stop().await?;
+        }
+
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)
+                .map_err(|e| format!("Failed to remove previous install: {}", e))?;
+        }
+        std::fs::rename(&staging, &dir)
+            .map_err(|e| format!("Failed to install Ollama: {}", e))?;
+
+        let binary = Self::managed_binary()
+            .ok_or_else(|| format!("Ollama binary not found in {:?} after extraction", dir))?;
+        download::make_executable(&binary)?;
+        *self.custom_path.lock().unwrap() = Some(binary.clone());
+        log::info!("Ollama {} installed at {:?}", version, binary);
+
+        if was_running {
+            self.start().await?;
+        }
+
+        Ok(version)
+    }
+
+    /// Install the latest release if it is newer than the one in use.
+    /// Returns the new version, or `None` when already up to date.
+    pub async fn upgrade(&self) -> Result<Option<String>, NodeError> {
+        let latest = Self::latest_version().await?;
+        if self.installed_version().as_deref() == Some(latest.as_str()) {
+            return Ok(None);
+        }
+        self.install(Some(&latest)).await.map(Some)
     }
 
     /// Get the Ollama API host URL
     pub fn get_host(&self) -> String {
-        std::env::var("OLLAMA_HOST").unwrap_or_else(|_| "http://localhost:11434".to_string())
+        self.config.lock().unwrap().base_url()
     }
 }
 
@@ -241,3 +742,23 @@ impl Default for OllamaManager {
         Self::new()
     }
 }
+
+/// Rough memory needed to run a model of `size` bytes on disk: the weights
+/// plus ~20% for the KV cache and runtime buffers at default context
+fn estimate_memory(size: u64) -> u64 {
+    size + size / 5
+}
+
+fn model_fit(hardware: &Hardware, needed: u64) -> ModelFit {
+    let vram = hardware.gpu.iter().filter_map(|g| g.vram).max().unwrap_or(0);
+    let ram = hardware.memory.total;
+    if needed == 0 || (vram == 0 && ram == 0) {
+        ModelFit::Unknown
+    } else if needed <= vram {
+        ModelFit::Gpu
+    } else if needed <= ram {
+        ModelFit::Cpu
+    } else {
+        ModelFit::TooLarge
+    }
+}
@@ -0,0 +1,562 @@
+//! On-chain Payments
+//!
+//! Watches the configured wallet for incoming USDC transfers through a
+//! chain JSON-RPC endpoint (`eth_getLogs` on the token's `Transfer` event),
+//! keeps them in `payments.db` under the config dir, and reconciles them
+//! against the earnings ledger:
+//!
+//! - a job whose payment reference is the hash of a received transfer is
+//!   paid by that transfer;
+//! - remaining funds cover the other USD/USDC jobs oldest first;
+//! - a job still uncovered once the grace period after completion is over
+//!   is flagged as unpaid and announced as a `PaymentsOverdue` node event.
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, RwLock};
+
+use super::earnings::{EarningsLedger, EarningsQuery};
+use crate::config::{NodeConfig, PaymentsConfig};
+use crate::models::NodeEvent;
+
+/// `keccak256("Transfer(address,address,uint256)")`
+const TRANSFER_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+/// USDC has 6 decimals; this many base units make a cent
+const UNITS_PER_CENT: u128 = 10_000;
+
+/// Ledger currencies settled in USDC
+const RECONCILED_CURRENCIES: &[&str] = &["USDC", "USD"];
+
+/// Blocks per `eth_getLogs` call; public RPCs reject wide ranges
+const MAX_BLOCK_RANGE: u64 = 2_000;
+
+/// Blocks scanned on first sync when no start block is set (a day on Base)
+const DEFAULT_LOOKBACK: u64 = 43_200;
+
+/// Blocks a transfer must be buried under before it counts
+const CONFIRMATIONS: u64 = 5;
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+const RPC_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// A USDC transfer into the wallet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceivedPayment {
+    pub tx_hash: String,
+    pub log_index: u64,
+    pub from: String,
+    /// Token base units, as a decimal string
+    pub amount_units: String,
+    pub amount_cents: i64,
+    pub block_number: u64,
+    pub seen_at: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobPaymentStatus {
+    /// Paid by the transfer named in its payment reference
+    Paid,
+    /// Covered by funds received without a matching reference
+    Covered,
+    /// Uncovered, but still within the grace period
+    Pending,
+    Unpaid,
+}
+
+impl JobPaymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Paid => "paid",
+            Self::Covered => "covered",
+            Self::Pending => "pending",
+            Self::Unpaid => "unpaid",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconciledJob {
+    pub job_id: String,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub completed_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment_ref: Option<String>,
+    pub status: JobPaymentStatus,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reconciliation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wallet_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_scanned_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synced_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub received_cents: i64,
+    pub earned_cents: i64,
+    /// Received but not matched to any job yet
+    pub unallocated_cents: i64,
+    pub paid_jobs: u64,
+    pub covered_jobs: u64,
+    pub pending_jobs: u64,
+    pub unpaid_cents: i64,
+    /// Jobs flagged as unpaid, oldest first
+    pub unpaid: Vec<ReconciledJob>,
+    /// Jobs still within the grace period, oldest first
+    pub pending: Vec<ReconciledJob>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct SyncStatus {
+    synced_at: Option<String>,
+    last_error: Option<String>,
+}
+
+/// Whether `value` is a 0x-prefixed 20-byte hex address
+pub fn is_address(value: &str) -> bool {
+    value.len() == 42 && value.starts_with("0x") && value[2..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn parse_quantity(value: &serde_json::Value) -> Option<u64> {
+    u64::from_str_radix(value.as_str()?.trim_start_matches("0x"), 16).ok()
+}
+
+/// A 256-bit amount; anything beyond 128 bits saturates
+fn parse_amount(data: &str) -> u128 {
+    let hex = data.trim_start_matches("0x").trim_start_matches('0');
+    if hex.is_empty() {
+        return 0;
+    }
+    if hex.len() > 32 {
+        return u128::MAX;
+    }
+    u128::from_str_radix(hex, 16).unwrap_or(0)
+}
+
+/// Last 20 bytes of a 32-byte topic
+fn topic_address(topic: &str) -> String {
+    let hex = topic.trim_start_matches("0x");
+    format!("0x{}", &hex[hex.len().saturating_sub(40)..])
+}
+
+async fn rpc(url: &str, method: &str, params: serde_json::Value) -> Result<serde_json::Value, String> {
+    let response: serde_json::Value = reqwest::Client::new()
+        .post(url)
+        .json(&serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+        .timeout(RPC_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach chain RPC: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid chain RPC response: {}", e))?;
+    if let Some(error) = response.get("error") {
+        return Err(format!("Chain RPC {} failed: {}", method, error));
+    }
+    Ok(response["result"].clone())
+}
+
+pub struct PaymentMonitor {
+    conn: Mutex<Connection>,
+    earnings: Arc<EarningsLedger>,
+    config: Arc<RwLock<NodeConfig>>,
+    events: broadcast::Sender<NodeEvent>,
+    status: RwLock<SyncStatus>,
+    /// Jobs already announced as unpaid
+    flagged: Mutex<HashSet<String>>,
+}
+
+impl PaymentMonitor {
+    /// Open the payment store at its default location under the config dir
+    pub fn open_default(
+        earnings: Arc<EarningsLedger>,
+        config: Arc<RwLock<NodeConfig>>,
+        events: broadcast::Sender<NodeEvent>,
+    ) -> Result<Self, String> {
+        let conn = Self::connect(&default_path())?;
+        Self::init(conn, earnings, config, events)
+    }
+
+    /// Store that lives only as long as the process (used when the file can't be opened)
+    pub fn in_memory(
+        earnings: Arc<EarningsLedger>,
+        config: Arc<RwLock<NodeConfig>>,
+        events: broadcast::Sender<NodeEvent>,
+    ) -> Result<Self, String> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open payment store: {}", e))?;
+        Self::init(conn, earnings, config, events)
+    }
+
+    fn connect(path: &Path) -> Result<Connection, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        Connection::open(path).map_err(|e| format!("Failed to open payment store: {}", e))
+    }
+
+    fn init(
+        conn: Connection,
+        earnings: Arc<EarningsLedger>,
+        config: Arc<RwLock<NodeConfig>>,
+        events: broadcast::Sender<NodeEvent>,
+    ) -> Result<Self, String> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS received_payments (
+                wallet TEXT NOT NULL,
+                contract TEXT NOT NULL,
+                tx_hash TEXT NOT NULL,
+                log_index INTEGER NOT NULL,
+                from_address TEXT NOT NULL,
+                amount_units TEXT NOT NULL,
+                amount_cents INTEGER NOT NULL,
+                block_number INTEGER NOT NULL,
+                seen_at TEXT NOT NULL,
+                PRIMARY KEY (tx_hash, log_index)
+            );
+            CREATE TABLE IF NOT EXISTS scan_state (
+                key TEXT PRIMARY KEY,
+                last_block INTEGER NOT NULL
+            );",
+        )
+        .map_err(|e| format!("Failed to initialize payment store: {}", e))?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            earnings,
+            config,
+            events,
+            status: RwLock::new(SyncStatus::default()),
+            flagged: Mutex::new(HashSet::new()),
+        })
+    }
+
+    async fn settings(&self) -> Result<Option<(PaymentsConfig, String, String)>, String> {
+        let config = self.config.read().await.payments.clone();
+        let Some(wallet) = config.wallet_address.as_deref().map(|w| w.trim().to_lowercase()) else {
+            return Ok(None);
+        };
+        if !is_address(&wallet) {
+            return Err(format!("{} is not a wallet address", wallet));
+        }
+        let contract = config.usdc_contract.trim().to_lowercase();
+        if !is_address(&contract) {
+            return Err(format!("{} is not a token contract address", contract));
+        }
+        Ok(Some((config, wallet, contract)))
+    }
+
+    fn scan_key(wallet: &str, contract: &str) -> String {
+        format!("{}:{}", wallet, contract)
+    }
+
+    fn last_block(&self, wallet: &str, contract: &str) -> Result<Option<u64>, String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT last_block FROM scan_state WHERE key = ?1",
+                params![Self::scan_key(wallet, contract)],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .map(|block| block.map(|b| b as u64))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Pull new transfers into the store
+    pub async fn sync(&self) -> Result<(), String> {
+        let result = self.scan().await;
+        let mut status = self.status.write().await;
+        match &result {
+            Ok(()) => {
+                status.synced_at = Some(Utc::now().to_rfc3339());
+                status.last_error = None;
+            }
+            Err(e) => status.last_error = Some(e.clone()),
+        }
+        drop(status);
+        result?;
+        self.flag_unpaid().await;
+        Ok(())
+    }
+
+    async fn scan(&self) -> Result<(), String> {
+        let Some((config, wallet, contract)) = self.settings().await? else {
+            return Ok(());
+        };
+        let latest = parse_quantity(&rpc(&config.rpc_url, "eth_blockNumber", serde_json::json!([])).await?)
+            .ok_or("Chain RPC returned no block number")?;
+        let safe = latest.saturating_sub(CONFIRMATIONS);
+        let mut from = match self.last_block(&wallet, &contract)? {
+            Some(last) => last + 1,
+            None => config.start_block.unwrap_or_else(|| safe.saturating_sub(DEFAULT_LOOKBACK)),
+        };
+        let recipient = format!("0x{:0>64}", wallet.trim_start_matches("0x"));
+
+        while from <= safe {
+            let to = (from + MAX_BLOCK_RANGE - 1).min(safe);
+            let logs = rpc(
+                &config.rpc_url,
+                "eth_getLogs",
+                serde_json::json!([{
+                    "fromBlock": format!("0x{:x}", from),
+                    "toBlock": format!("0x{:x}", to),
+                    "address": contract,
+                    "topics": [TRANSFER_TOPIC, null, recipient],
+                }]),
+            )
+            .await?;
+            self.store_logs(&wallet, &contract, &logs, to)?;
+            from = to + 1;
+        }
+        Ok(())
+    }
+
+    /// Save the transfers of one scanned range and where scanning got to
+    fn store_logs(&self, wallet: &str, contract: &str, logs: &serde_json::Value, to: u64) -> Result<(), String> {
+        let now = Utc::now().to_rfc3339();
+        let conn = self.conn.lock().unwrap();
+        for entry in logs.as_array().into_iter().flatten() {
+            let (Some(tx_hash), Some(log_index), Some(block)) = (
+                entry["transactionHash"].as_str(),
+                parse_quantity(&entry["logIndex"]),
+                parse_quantity(&entry["blockNumber"]),
+            ) else {
+                continue;
+            };
+            let from_address = entry["topics"][1].as_str().map(topic_address).unwrap_or_default();
+            let units = parse_amount(entry["data"].as_str().unwrap_or("0x0"));
+            let cents = i64::try_from(units / UNITS_PER_CENT).unwrap_or(i64::MAX);
+            let inserted = conn
+                .execute(
+                    "INSERT OR IGNORE INTO received_payments
+                     (wallet, contract, tx_hash, log_index, from_address, amount_units, amount_cents, block_number, seen_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        wallet,
+                        contract,
+                        tx_hash.to_lowercase(),
+                        log_index as i64,
+                        from_address,
+                        units.to_string(),
+                        cents,
+                        block as i64,
+                        now
+                    ],
+                )
+                .map_err(|e| e.to_string())?;
+            if inserted > 0 {
+                log::info!("Received {:.2} USDC from {} in {}", cents as f64 / 100.0, from_address, tx_hash);
+            }
+        }
+        conn.execute(
+            "INSERT OR REPLACE INTO scan_state (key, last_block) VALUES (?1, ?2)",
+            params![Self::scan_key(wallet, contract), to as i64],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Transfers received by the configured wallet, newest first
+    pub async fn received(&self) -> Result<Vec<ReceivedPayment>, String> {
+        let Some((_, wallet, contract)) = self.settings().await? else {
+            return Ok(Vec::new());
+        };
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT tx_hash, log_index, from_address, amount_units, amount_cents, block_number, seen_at
+                 FROM received_payments WHERE wallet = ?1 AND contract = ?2
+                 ORDER BY block_number DESC, log_index DESC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![wallet, contract], |row| {
+                Ok(ReceivedPayment {
+                    tx_hash: row.get(0)?,
+                    log_index: row.get::<_, i64>(1)? as u64,
+                    from: row.get(2)?,
+                    amount_units: row.get(3)?,
+                    amount_cents: row.get(4)?,
+                    block_number: row.get::<_, i64>(5)? as u64,
+                    seen_at: row.get(6)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    /// Match received funds against the ledger's USD/USDC jobs
+    pub async fn reconcile(&self) -> Result<Reconciliation, String> {
+        Ok(self.classify().await?.0)
+    }
+
+    /// Payment status of every reconciled job, by job id
+    pub async fn job_statuses(&self) -> Result<HashMap<String, JobPaymentStatus>, String> {
+        let (_, jobs) = self.classify().await?;
+        Ok(jobs.into_iter().map(|job| (job.job_id, job.status)).collect())
+    }
+
+    /// The reconciliation report, and the status of each job it covered
+    async fn classify(&self) -> Result<(Reconciliation, Vec<ReconciledJob>), String> {
+        let settings = self.settings().await?;
+        let status = self.status.read().await.clone();
+        let mut report = Reconciliation {
+            synced_at: status.synced_at,
+            last_error: status.last_error,
+            ..Default::default()
+        };
+        let Some((config, wallet, contract)) = settings else {
+            return Ok((report, Vec::new()));
+        };
+        report.last_scanned_block = self.last_block(&wallet, &contract)?;
+        report.wallet_address = Some(wallet);
+
+        let payments = self.received().await?;
+        report.received_cents = payments.iter().map(|p| p.amount_cents).sum();
+        // Funds left per transaction after paying the jobs that name it
+        let mut by_tx: HashMap<String, i64> = HashMap::new();
+        for payment in &payments {
+            *by_tx.entry(payment.tx_hash.clone()).or_default() += payment.amount_cents;
+        }
+
+        let mut jobs = Vec::new();
+        for currency in RECONCILED_CURRENCIES {
+            let query = EarningsQuery {
+                currency: Some(currency.to_string()),
+                ..Default::default()
+            };
+            jobs.extend(self.earnings.list(&query)?);
+        }
+        jobs.sort_by(|a, b| a.completed_at.cmp(&b.completed_at));
+        report.earned_cents = jobs.iter().map(|j| j.amount_cents).sum();
+
+        let mut statuses: Vec<Option<JobPaymentStatus>> = vec![None; jobs.len()];
+        for (job, status) in jobs.iter().zip(statuses.iter_mut()) {
+            if job.amount_cents == 0 {
+                *status = Some(JobPaymentStatus::Paid);
+                continue;
+            }
+            let reference = job.payment_ref.as_deref().map(str::to_lowercase);
+            if let Some(left) = reference.and_then(|r| by_tx.get_mut(&r)) {
+                if *left >= job.amount_cents {
+                    *left -= job.amount_cents;
+                    *status = Some(JobPaymentStatus::Paid);
+                }
+            }
+        }
+
+        let mut pool: i64 = by_tx.values().sum();
+        let grace = Duration::hours(i64::from(config.grace_hours));
+        let now = Utc::now();
+        for (job, status) in jobs.iter().zip(statuses.iter_mut()) {
+            if status.is_some() {
+                continue;
+            }
+            *status = Some(if pool >= job.amount_cents {
+                pool -= job.amount_cents;
+                JobPaymentStatus::Covered
+            } else if DateTime::parse_from_rfc3339(&job.completed_at)
+                .map(|t| t.with_timezone(&Utc) + grace > now)
+                .unwrap_or(false)
+            {
+                JobPaymentStatus::Pending
+            } else {
+                JobPaymentStatus::Unpaid
+            });
+        }
+        report.unallocated_cents = pool;
+
+        let mut all = Vec::with_capacity(jobs.len());
+        for (job, status) in jobs.into_iter().zip(statuses) {
+            let status = status.unwrap_or(JobPaymentStatus::Unpaid);
+            let reconciled = ReconciledJob {
+                job_id: job.job_id,
+                amount_cents: job.amount_cents,
+                currency: job.currency,
+                completed_at: job.completed_at,
+                payment_ref: job.payment_ref,
+                status,
+            };
+            match status {
+                JobPaymentStatus::Paid => report.paid_jobs += 1,
+                JobPaymentStatus::Covered => report.covered_jobs += 1,
+                JobPaymentStatus::Pending => {
+                    report.pending_jobs += 1;
+                    report.pending.push(reconciled.clone());
+                }
+                JobPaymentStatus::Unpaid => {
+                    report.unpaid_cents += reconciled.amount_cents;
+                    report.unpaid.push(reconciled.clone());
+                }
+            }
+            all.push(reconciled);
+        }
+        Ok((report, all))
+    }
+
+    /// Announce jobs that became unpaid since the last check
+    async fn flag_unpaid(&self) {
+        let report = match self.reconcile().await {
+            Ok(report) => report,
+            Err(e) => {
+                log::warn!("Payment reconciliation failed: {}", e);
+                return;
+            }
+        };
+        let jobs: Vec<String> = {
+            let mut flagged = self.flagged.lock().unwrap();
+            let unpaid: HashSet<String> = report.unpaid.iter().map(|j| j.job_id.clone()).collect();
+            // Jobs paid since are no longer flagged
+            flagged.retain(|id| unpaid.contains(id));
+            unpaid.into_iter().filter(|id| flagged.insert(id.clone())).collect()
+        };
+        if jobs.is_empty() {
+            return;
+        }
+        log::warn!(
+            "{} completed jobs are unpaid ({:.2} USDC outstanding)",
+            report.unpaid.len(),
+            report.unpaid_cents as f64 / 100.0
+        );
+        let _ = self.events.send(NodeEvent::PaymentsOverdue {
+            jobs,
+            unpaid_cents: report.unpaid_cents,
+        });
+    }
+
+    /// Poll forever while a wallet is configured
+    pub async fn watch(self: Arc<Self>) {
+        loop {
+            if self.config.read().await.payments.wallet_address.is_some() {
+                if let Err(e) = self.sync().await {
+                    log::warn!("Payment sync failed: {}", e);
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Location of the payment database
+pub fn default_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+        .join("payments.db")
+}
@@ -0,0 +1,243 @@
+//! Dynamic Pricing
+//!
+//! The node advertises its rates (`pricing`) to the orchestrator with every
+//! heartbeat. With `pricing.dynamic.enabled` they are the configured rates
+//! times a multiplier that moves every `intervalSecs`:
+//!
+//! - pressure weighs the orchestrator's latest demand signal (0 when no
+//!   work is waiting, 1 when there is far more work than nodes; 0.5 without
+//!   a current signal) with this machine's CPU utilization
+//! - the target maps pressure onto `floorPercent`..`ceilingPercent`, so an
+//!   idle node on a quiet network drops toward the floor to win work
+//! - the multiplier steps toward the target by at most `maxStepPercent`
+//!
+//! The multiplier survives restarts in the settings store. Each change is
+//! announced as `PricingChanged`, which also sends a heartbeat at once.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+
+use super::state_store::{self, Settings};
+use super::IdleMonitor;
+use crate::config::{NodeConfig, PricingConfig};
+use crate::error::NodeError;
+use crate::models::NodeEvent;
+
+/// Weight of the demand signal against local utilization
+const DEMAND_WEIGHT: f64 = 0.6;
+
+/// How long a demand signal without `expiresAt` counts
+const DEMAND_TTL: Duration = Duration::from_secs(900);
+
+/// Pressure assumed without a current demand signal
+const NEUTRAL_DEMAND: f64 = 0.5;
+
+/// Smallest change worth advertising
+const MIN_CHANGE: f64 = 0.001;
+
+/// How busy the network is, from the orchestrator
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DemandSignal {
+    /// 0 (no work waiting) to 1 (far more work than nodes)
+    pub level: f64,
+    #[serde(default)]
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Demand {
+    pub level: f64,
+    pub received_at: String,
+    pub expires_at: String,
+}
+
+/// Rates as advertised
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rates {
+    pub currency: String,
+    pub cpu_core_hour_cents: f64,
+    pub memory_gb_hour_cents: f64,
+    pub gpu_hour_cents: f64,
+    pub inference_1k_tokens_cents: f64,
+    /// Applied to the configured rates; 1 without dynamic pricing
+    pub multiplier: f64,
+    pub dynamic: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricingStatus {
+    pub rates: Rates,
+    /// Current demand signal, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub demand: Option<Demand>,
+    /// CPU utilization, 0 to 1
+    pub utilization: f64,
+    /// Multiplier the rates are moving toward
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<f64>,
+}
+
+pub struct Pricing {
+    config: Arc<RwLock<NodeConfig>>,
+    settings: Settings,
+    idle: Arc<IdleMonitor>,
+    events: broadcast::Sender<NodeEvent>,
+    multiplier: Mutex<f64>,
+    demand: Mutex<Option<Demand>>,
+}
+
+impl Pricing {
+    pub fn new(
+        config: Arc<RwLock<NodeConfig>>,
+        settings: Settings,
+        idle: Arc<IdleMonitor>,
+        events: broadcast::Sender<NodeEvent>,
+    ) -> Self {
+        let multiplier = settings
+            .get(state_store::PRICING_MULTIPLIER)
+            .ok()
+            .flatten()
+            .and_then(|value| value.parse::<f64>().ok())
+            .filter(|value| value.is_finite() && *value > 0.0)
+            .unwrap_or(1.0);
+        Self {
+            config,
+            settings,
+            idle,
+            events,
+            multiplier: Mutex::new(multiplier),
+            demand: Mutex::new(None),
+        }
+    }
+
+    /// Rates to advertise now
+    pub async fn rates(&self) -> Rates {
+        let pricing = self.config.read().await.pricing.clone();
+        let multiplier = if pricing.dynamic.enabled {
+            clamp(*self.multiplier.lock().unwrap(), &pricing)
+        } else {
+            1.0
+        };
+        Rates {
+            currency: pricing.currency,
+            cpu_core_hour_cents: pricing.cpu_core_hour_cents * multiplier,
+            memory_gb_hour_cents: pricing.memory_gb_hour_cents * multiplier,
+            gpu_hour_cents: pricing.gpu_hour_cents * multiplier,
+            inference_1k_tokens_cents: pricing.inference_1k_tokens_cents * multiplier,
+            multiplier,
+            dynamic: pricing.dynamic.enabled,
+        }
+    }
+
+    pub async fn status(&self) -> PricingStatus {
+        let pricing = self.config.read().await.pricing.clone();
+        let utilization = self.utilization().await;
+        PricingStatus {
+            rates: self.rates().await,
+            demand: self.demand(),
+            utilization,
+            target: pricing.dynamic.enabled.then(|| self.target(&pricing, utilization)),
+        }
+    }
+
+    /// Take a demand signal from the orchestrator
+    pub async fn set_demand(&self, signal: DemandSignal) -> Result<PricingStatus, NodeError> {
+        if !signal.level.is_finite() || !(0.0..=1.0).contains(&signal.level) {
+            return Err(NodeError::Invalid("level must be between 0 and 1".to_string()));
+        }
+        let now = Utc::now();
+        let expires_at = match &signal.expires_at {
+            Some(at) => DateTime::parse_from_rfc3339(at)
+                .map_err(|_| NodeError::Invalid("expiresAt must be an RFC 3339 timestamp".to_string()))?
+                .with_timezone(&Utc),
+            None => now + chrono::Duration::from_std(DEMAND_TTL).unwrap_or_default(),
+        };
+        *self.demand.lock().unwrap() = Some(Demand {
+            level: signal.level,
+            received_at: now.to_rfc3339(),
+            expires_at: expires_at.to_rfc3339(),
+        });
+        Ok(self.status().await)
+    }
+
+    /// Announce the rates again, e.g. after the configured ones changed
+    pub async fn readvertise(&self) {
+        let multiplier = self.rates().await.multiplier;
+        let _ = self.events.send(NodeEvent::PricingChanged { multiplier });
+    }
+
+    /// Step the rates toward their target every interval, for the life of the app
+    pub async fn watch(self: Arc<Self>) {
+        loop {
+            let pricing = self.config.read().await.pricing.clone();
+            tokio::time::sleep(Duration::from_secs(pricing.dynamic.interval_secs.max(30))).await;
+            if pricing.dynamic.enabled {
+                self.step(&pricing).await;
+            }
+        }
+    }
+
+    async fn step(&self, pricing: &PricingConfig) {
+        let target = self.target(pricing, self.utilization().await);
+        let max_step = pricing.dynamic.max_step_percent.max(0.0) / 100.0;
+        let next = {
+            let mut multiplier = self.multiplier.lock().unwrap();
+            let current = clamp(*multiplier, pricing);
+            let next = clamp(current + (target - current).clamp(-max_step, max_step), pricing);
+            if (next - *multiplier).abs() < MIN_CHANGE {
+                return;
+            }
+            *multiplier = next;
+            next
+        };
+        if let Err(e) = self.settings.set(state_store::PRICING_MULTIPLIER, &next.to_string()) {
+            log::warn!("{}", e);
+        }
+        log::info!("Rates now {:.0}% of the configured ones (target {:.0}%)", next * 100.0, target * 100.0);
+        let _ = self.events.send(NodeEvent::PricingChanged { multiplier: next });
+    }
+
+    /// Multiplier the current demand and utilization call for
+    fn target(&self, pricing: &PricingConfig, utilization: f64) -> f64 {
+        let demand = self.demand().map_or(NEUTRAL_DEMAND, |demand| demand.level);
+        let pressure = DEMAND_WEIGHT * demand + (1.0 - DEMAND_WEIGHT) * utilization;
+        let (floor, ceiling) = bounds(pricing);
+        floor + (ceiling - floor) * pressure
+    }
+
+    /// The demand signal, while it has not expired
+    fn demand(&self) -> Option<Demand> {
+        let mut demand = self.demand.lock().unwrap();
+        let expired = demand
+            .as_ref()
+            .and_then(|demand| DateTime::parse_from_rfc3339(&demand.expires_at).ok())
+            .is_some_and(|at| at <= Utc::now());
+        if expired {
+            *demand = None;
+        }
+        demand.clone()
+    }
+
+    async fn utilization(&self) -> f64 {
+        (f64::from(self.idle.status().await.cpu_percent) / 100.0).clamp(0.0, 1.0)
+    }
+}
+
+/// Floor and ceiling as multipliers
+fn bounds(pricing: &PricingConfig) -> (f64, f64) {
+    let floor = pricing.dynamic.floor_percent / 100.0;
+    let ceiling = pricing.dynamic.ceiling_percent / 100.0;
+    (floor, ceiling.max(floor))
+}
+
+fn clamp(multiplier: f64, pricing: &PricingConfig) -> f64 {
+    let (floor, ceiling) = bounds(pricing);
+    multiplier.clamp(floor, ceiling)
+}
@@ -0,0 +1,299 @@
+//! IPFS Pubsub
+//!
+//! With `ipfs.pubsub.enabled`, Kubo runs with pubsub and the node takes
+//! part in two topics, a decentralized channel next to the orchestrator
+//! WebSocket:
+//!
+//! - the announce topic, where it publishes a signed summary of itself
+//!   (capabilities and availability) every `announceIntervalSecs` and when
+//!   its availability changes, and reads the summaries of other nodes
+//! - the offer topic, where it reads jobs the orchestrator offers
+//!
+//! Messages are a JSON payload and a signature over `pubsub_message` (see
+//! `identity`). Announcements must be signed by the key they announce;
+//! offers by the orchestrator, so none are kept without
+//! `orchestratorPublicKey`. Messages older than `MAX_MESSAGE_AGE` are
+//! dropped as replays.
+
+use chrono::Utc;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+
+use super::identity::{self, NodeIdentity};
+use super::ipfs::multibase_decode;
+use super::IpfsManager;
+use crate::config::NodeConfig;
+use crate::error::NodeError;
+use crate::models::{NodeCapabilities, NodeEvent};
+
+const MAX_MESSAGE_AGE: Duration = Duration::from_secs(600);
+
+/// Wait before subscribing again once a subscription ends
+const RETRY_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Offers kept, newest first
+const MAX_OFFERS: usize = 200;
+
+/// Announcements missed before a node is dropped from the list
+const MISSED_ANNOUNCEMENTS: u64 = 3;
+
+/// A message as published
+#[derive(Serialize, Deserialize)]
+struct Signed {
+    payload: String,
+    signature: String,
+}
+
+/// A message as the pubsub API delivers it
+#[derive(Deserialize)]
+struct Delivered {
+    /// IPFS peer it was published from
+    from: String,
+    /// Multibase-encoded
+    data: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Announcement {
+    pub capabilities: NodeCapabilities,
+    pub timestamp: String,
+}
+
+/// Another node as it last announced itself
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnouncedNode {
+    #[serde(flatten)]
+    pub capabilities: NodeCapabilities,
+    pub peer_id: String,
+    pub announced_at: String,
+}
+
+/// A job the orchestrator offers to whichever node takes it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobOffer {
+    pub id: String,
+    /// e.g. `agent`, `container` or `inference`
+    pub kind: String,
+    /// What a node needs to take it, e.g. a model or GPU memory
+    #[serde(default)]
+    pub requirements: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reward_cents: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    pub timestamp: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+enum Topic {
+    Announce,
+    Offer,
+}
+
+pub struct Pubsub {
+    ipfs: Arc<IpfsManager>,
+    identity: Arc<NodeIdentity>,
+    config: Arc<RwLock<NodeConfig>>,
+    events: broadcast::Sender<NodeEvent>,
+    /// Other nodes by public key
+    nodes: RwLock<HashMap<String, AnnouncedNode>>,
+    offers: RwLock<Vec<JobOffer>>,
+}
+
+impl Pubsub {
+    pub fn new(
+        ipfs: Arc<IpfsManager>,
+        identity: Arc<NodeIdentity>,
+        config: Arc<RwLock<NodeConfig>>,
+        events: broadcast::Sender<NodeEvent>,
+    ) -> Self {
+        Self {
+            ipfs,
+            identity,
+            config,
+            events,
+            nodes: RwLock::new(HashMap::new()),
+            offers: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Nodes heard from recently, by node ID
+    pub async fn nodes(&self) -> Vec<AnnouncedNode> {
+        let interval = self.config.read().await.ipfs.pubsub.announce_interval_secs;
+        let max_age = Duration::from_secs(interval * MISSED_ANNOUNCEMENTS).max(MAX_MESSAGE_AGE);
+        let mut nodes: Vec<AnnouncedNode> = self
+            .nodes
+            .read()
+            .await
+            .values()
+            .filter(|node| age(&node.announced_at).is_some_and(|age| age <= max_age))
+            .cloned()
+            .collect();
+        nodes.sort_by(|a, b| a.capabilities.node_id.cmp(&b.capabilities.node_id));
+        nodes
+    }
+
+    /// Offers that have not expired, newest first
+    pub async fn offers(&self) -> Vec<JobOffer> {
+        let now = Utc::now();
+        self.offers
+            .read()
+            .await
+            .iter()
+            .filter(|offer| {
+                let expires = offer
+                    .expires_at
+                    .as_deref()
+                    .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok());
+                !matches!(expires, Some(at) if at <= now)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Publish a signed summary of this node on the announce topic
+    pub async fn announce(&self, capabilities: NodeCapabilities) -> Result<(), NodeError> {
+        let topic = self.config.read().await.ipfs.pubsub.announce_topic.clone();
+        let payload = serde_json::to_string(&Announcement {
+            capabilities,
+            timestamp: Utc::now().to_rfc3339(),
+        })
+        .map_err(|e| e.to_string())?;
+        let signature = self
+            .identity
+            .sign(&identity::pubsub_message("announcement", payload.as_bytes()));
+        let message = serde_json::to_vec(&Signed { payload, signature }).map_err(|e| e.to_string())?;
+        self.ipfs.pubsub_publish(&topic, &message).await
+    }
+
+    /// Read both topics for the life of the app, while pubsub is enabled
+    /// and the daemon runs
+    pub async fn listen(self: Arc<Self>) {
+        tokio::join!(self.listen_on(Topic::Announce), self.listen_on(Topic::Offer));
+    }
+
+    async fn listen_on(&self, topic: Topic) {
+        loop {
+            let pubsub = self.config.read().await.ipfs.pubsub.clone();
+            if pubsub.enabled && self.ipfs.is_running() {
+                let name = match topic {
+                    Topic::Announce => pubsub.announce_topic,
+                    Topic::Offer => pubsub.offer_topic,
+                };
+                match self.ipfs.pubsub_subscribe(&name).await {
+                    Ok(response) => {
+                        log::info!("Listening on IPFS pubsub topic {}", name);
+                        self.read(topic, response).await;
+                    }
+                    Err(e) => log::debug!("{}", e),
+                }
+            }
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+    }
+
+    /// Handle messages until the subscription ends or pubsub is switched off
+    async fn read(&self, topic: Topic, response: reqwest::Response) {
+        let mut stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        while let Some(Ok(chunk)) = stream.next().await {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let Ok(message) = serde_json::from_slice::<Delivered>(&line) else {
+                    continue;
+                };
+                let Some(data) = multibase_decode(&message.data) else {
+                    continue;
+                };
+                let Ok(signed) = serde_json::from_slice::<Signed>(&data) else {
+                    continue;
+                };
+                match topic {
+                    Topic::Announce => self.on_announcement(&message.from, signed).await,
+                    Topic::Offer => self.on_offer(signed).await,
+                }
+            }
+            if !self.config.read().await.ipfs.pubsub.enabled {
+                break;
+            }
+        }
+    }
+
+    async fn on_announcement(&self, from: &str, signed: Signed) {
+        let Ok(announcement) = serde_json::from_str::<Announcement>(&signed.payload) else {
+            return;
+        };
+        let capabilities = announcement.capabilities;
+        // Our own announcements come back to us
+        if capabilities.public_key == self.identity.public_key() {
+            return;
+        }
+        let message = identity::pubsub_message("announcement", signed.payload.as_bytes());
+        if !identity::verify(&capabilities.public_key, &message, &signed.signature) {
+            log::debug!("Dropped an announcement from {} with a bad signature", from);
+            return;
+        }
+        if !age(&announcement.timestamp).is_some_and(|age| age <= MAX_MESSAGE_AGE) {
+            return;
+        }
+
+        let mut nodes = self.nodes.write().await;
+        if !nodes.contains_key(&capabilities.public_key) {
+            log::info!("Node {} announced itself on IPFS pubsub", capabilities.node_id);
+        }
+        nodes.insert(
+            capabilities.public_key.clone(),
+            AnnouncedNode {
+                capabilities,
+                peer_id: from.to_string(),
+                announced_at: announcement.timestamp,
+            },
+        );
+    }
+
+    async fn on_offer(&self, signed: Signed) {
+        let Some(orchestrator_key) = self.config.read().await.orchestrator_public_key.clone() else {
+            return;
+        };
+        let message = identity::pubsub_message("offer", signed.payload.as_bytes());
+        if !identity::verify(&orchestrator_key, &message, &signed.signature) {
+            log::debug!("Dropped a job offer not signed by the orchestrator");
+            return;
+        }
+        let Ok(offer) = serde_json::from_str::<JobOffer>(&signed.payload) else {
+            return;
+        };
+        if !age(&offer.timestamp).is_some_and(|age| age <= MAX_MESSAGE_AGE) {
+            return;
+        }
+
+        let mut offers = self.offers.write().await;
+        if offers.iter().any(|known| known.id == offer.id) {
+            return;
+        }
+        let _ = self.events.send(NodeEvent::JobOffered {
+            offer_id: offer.id.clone(),
+            kind: offer.kind.clone(),
+        });
+        offers.insert(0, offer);
+        offers.truncate(MAX_OFFERS);
+    }
+}
+
+/// How long ago an RFC 3339 timestamp was, either way
+fn age(timestamp: &str) -> Option<Duration> {
+    let at = chrono::DateTime::parse_from_rfc3339(timestamp).ok()?;
+    Some(Duration::from_secs(
+        (Utc::now() - at.with_timezone(&Utc)).num_seconds().unsigned_abs(),
+    ))
+}
@@ -0,0 +1,453 @@
+//! Document Index
+//!
+//! Files and IPFS content ingested for retrieval: each document is split
+//! into overlapping chunks, embedded with Ollama's embedding model and kept
+//! with its vectors in the node's state store. A query is embedded the same
+//! way and answered with the closest chunks. The index is small enough per
+//! node that search scans the stored vectors exactly rather than keeping an
+//! approximate index alongside.
+//!
+//! Documents belong to a workspace or, without one, are shared by all;
+//! a search in a workspace covers both.
+
+use async_trait::async_trait;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use super::agent_memory::{cosine, vector_from_blob, vector_to_blob};
+use super::agent_tools::{required_str, workspace_dir, AgentTool, ToolContext};
+use super::{IpfsManager, OllamaManager, MAX_CAT_SIZE};
+use crate::error::NodeError;
+
+/// Target length of a chunk in bytes
+const CHUNK_SIZE: usize = 1_200;
+/// Bytes a chunk repeats from the end of the previous one
+const CHUNK_OVERLAP: usize = 200;
+/// Chunks embedded per Ollama request
+const EMBED_BATCH: usize = 16;
+/// Chunks returned by a query unless asked otherwise
+pub const DEFAULT_RESULTS: usize = 5;
+/// Most chunks a query may ask for
+const MAX_RESULTS: usize = 50;
+
+/// An ingested file or CID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Document {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace_id: Option<String>,
+    pub name: String,
+    /// `file` or `cid`
+    pub source: String,
+    /// Path of the file, or the CID
+    pub location: String,
+    pub size_bytes: u64,
+    pub chunks: u64,
+    pub created_at: String,
+}
+
+/// What to ingest: a file on this machine or content on IPFS
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestRequest {
+    #[serde(default)]
+    pub workspace_id: Option<String>,
+    /// Relative paths are taken inside the workspace
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub cid: Option<String>,
+    /// Shown in search results; defaults to the file name or CID
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RagQuery {
+    pub query: String,
+    #[serde(default)]
+    pub workspace_id: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// A chunk matching a query
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub document_id: String,
+    pub name: String,
+    /// Position of the chunk in its document
+    pub chunk: u64,
+    pub content: String,
+    /// Cosine similarity to the query
+    pub score: f64,
+}
+
+/// Ingested documents and their embedded chunks; tables of the node's state store
+pub struct Documents {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Documents {
+    pub(super) fn new(conn: Arc<Mutex<Connection>>) -> Self {
+        Self { conn }
+    }
+
+    /// Store a document with its chunks, replacing an earlier ingest of the
+    /// same location into the same workspace
+    pub fn save(&self, doc: &Document, chunks: &[(String, Vec<f32>)]) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM rag_chunks WHERE document_id IN (
+                SELECT id FROM rag_documents WHERE location = ?1 AND workspace_id IS ?2)",
+            params![doc.location, doc.workspace_id],
+        )
+        .and_then(|_| {
+            tx.execute(
+                "DELETE FROM rag_documents WHERE location = ?1 AND workspace_id IS ?2",
+                params![doc.location, doc.workspace_id],
+            )
+        })
+        .and_then(|_| {
+            tx.execute(
+                "INSERT INTO rag_documents (id, workspace_id, name, source, location, size_bytes, chunks, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    doc.id,
+                    doc.workspace_id,
+                    doc.name,
+                    doc.source,
+                    doc.location,
+                    doc.size_bytes as i64,
+                    doc.chunks as i64,
+                    doc.created_at
+                ],
+            )
+        })
+        .map_err(|e| format!("Failed to save document {}: {}", doc.name, e))?;
+        for (seq, (content, embedding)) in chunks.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO rag_chunks (document_id, seq, content, embedding) VALUES (?1, ?2, ?3, ?4)",
+                params![doc.id, seq as i64, content, vector_to_blob(embedding)],
+            )
+            .map_err(|e| format!("Failed to save document {}: {}", doc.name, e))?;
+        }
+        tx.commit().map_err(|e| format!("Failed to save document {}: {}", doc.name, e))
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<Document>, String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                &format!("SELECT {} FROM rag_documents WHERE id = ?1", COLUMNS),
+                params![id],
+                document_from_row,
+            )
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Documents visible from `workspace_id` (its own and shared ones), or
+    /// all documents; newest first
+    pub fn list(&self, workspace_id: Option<&str>) -> Result<Vec<Document>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM rag_documents
+                 WHERE ?1 IS NULL OR workspace_id IS NULL OR workspace_id = ?1
+                 ORDER BY created_at DESC",
+                COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![workspace_id], document_from_row)
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to read documents: {}", e))
+    }
+
+    pub fn remove(&self, id: &str) -> Result<bool, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM rag_chunks WHERE document_id = ?1", params![id])
+            .and_then(|_| conn.execute("DELETE FROM rag_documents WHERE id = ?1", params![id]))
+            .map(|removed| removed > 0)
+            .map_err(|e| format!("Failed to remove document {}: {}", id, e))
+    }
+
+    /// The `limit` chunks visible from `workspace_id` closest to `query`
+    pub fn search(&self, query: &[f32], workspace_id: Option<&str>, limit: usize) -> Result<Vec<SearchHit>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT c.document_id, d.name, c.seq, c.content, c.embedding
+                 FROM rag_chunks c JOIN rag_documents d ON d.id = c.document_id
+                 WHERE ?1 IS NULL OR d.workspace_id IS NULL OR d.workspace_id = ?1",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![workspace_id], |row| {
+                let embedding: Vec<u8> = row.get(4)?;
+                Ok((
+                    SearchHit {
+                        document_id: row.get(0)?,
+                        name: row.get(1)?,
+                        chunk: row.get::<_, i64>(2)?.max(0) as u64,
+                        content: row.get(3)?,
+                        score: 0.0,
+                    },
+                    embedding,
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut hits = Vec::new();
+        for row in rows {
+            let (mut hit, embedding) = row.map_err(|e| format!("Failed to read documents: {}", e))?;
+            let embedding = vector_from_blob(&embedding);
+            // Chunks embedded with a different model can't be compared
+            if embedding.len() != query.len() {
+                continue;
+            }
+            hit.score = cosine(query, &embedding);
+            hits.push(hit);
+        }
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+        Ok(hits)
+    }
+}
+
+const COLUMNS: &str = "id, workspace_id, name, source, location, size_bytes, chunks, created_at";
+
+fn document_from_row(row: &rusqlite::Row) -> rusqlite::Result<Document> {
+    Ok(Document {
+        id: row.get(0)?,
+        workspace_id: row.get(1)?,
+        name: row.get(2)?,
+        source: row.get(3)?,
+        location: row.get(4)?,
+        size_bytes: row.get::<_, i64>(5)?.max(0) as u64,
+        chunks: row.get::<_, i64>(6)?.max(0) as u64,
+        created_at: row.get(7)?,
+    })
+}
+
+/// Ingests documents and answers queries against them
+pub struct DocumentIndex {
+    documents: Documents,
+    ollama: Arc<OllamaManager>,
+    ipfs: Arc<IpfsManager>,
+}
+
+impl DocumentIndex {
+    pub fn new(documents: Documents, ollama: Arc<OllamaManager>, ipfs: Arc<IpfsManager>) -> Self {
+        Self { documents, ollama, ipfs }
+    }
+
+    /// Read, chunk and embed a file or CID, replacing an earlier ingest of it
+    pub async fn ingest(&self, req: IngestRequest) -> Result<Document, NodeError> {
+        let (source, location, text) = match (&req.path, &req.cid) {
+            (Some(path), None) => {
+                let path = resolve_path(path, req.workspace_id.as_deref())?;
+                let text = read_text_file(&path).await?;
+                ("file", path.to_string_lossy().to_string(), text)
+            }
+            (None, Some(cid)) => ("cid", cid.clone(), self.ipfs.cat(cid).await?),
+            _ => return Err(NodeError::Invalid("Give either a path or a CID to ingest".to_string())),
+        };
+        let name = req.name.clone().unwrap_or_else(|| {
+            Path::new(&location)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| location.clone())
+        });
+
+        let chunks = chunk(&text);
+        if chunks.is_empty() {
+            return Err(NodeError::Invalid(format!("{} has no text to index", name)));
+        }
+        let mut embedded = Vec::with_capacity(chunks.len());
+        for batch in chunks.chunks(EMBED_BATCH) {
+            let embeddings = self.ollama.embed(batch).await?;
+            embedded.extend(batch.iter().cloned().zip(embeddings));
+        }
+
+        let doc = Document {
+            id: Uuid::new_v4().to_string(),
+            workspace_id: req.workspace_id,
+            name,
+            source: source.to_string(),
+            location,
+            size_bytes: text.len() as u64,
+            chunks: embedded.len() as u64,
+            created_at: Utc::now().to_rfc3339(),
+        };
+        self.documents.save(&doc, &embedded)?;
+        log::info!("Indexed {} ({} chunks)", doc.name, doc.chunks);
+        Ok(doc)
+    }
+
+    pub fn list(&self, workspace_id: Option<&str>) -> Result<Vec<Document>, NodeError> {
+        Ok(self.documents.list(workspace_id)?)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Document, NodeError> {
+        self.documents
+            .get(id)?
+            .ok_or_else(|| NodeError::NotFound(format!("Document {} not found", id)))
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), NodeError> {
+        if self.documents.remove(id)? {
+            Ok(())
+        } else {
+            Err(NodeError::NotFound(format!("Document {} not found", id)))
+        }
+    }
+
+    /// Chunks closest to the query, best first
+    pub async fn query(&self, query: &RagQuery) -> Result<Vec<SearchHit>, NodeError> {
+        if query.query.trim().is_empty() {
+            return Err(NodeError::Invalid("Query is empty".to_string()));
+        }
+        let limit = query.limit.unwrap_or(DEFAULT_RESULTS).clamp(1, MAX_RESULTS);
+        let embedding = self
+            .ollama
+            .embed(std::slice::from_ref(&query.query))
+            .await?
+            .pop()
+            .ok_or_else(|| NodeError::Upstream("Ollama returned no embedding".to_string()))?;
+        Ok(self.documents.search(&embedding, query.workspace_id.as_deref(), limit)?)
+    }
+}
+
+/// Absolute paths as given; relative ones inside the workspace, which they
+/// may not escape
+fn resolve_path(path: &str, workspace_id: Option<&str>) -> Result<PathBuf, NodeError> {
+    let given = Path::new(path);
+    if given.is_absolute() {
+        return Ok(given.to_path_buf());
+    }
+    let Some(workspace_id) = workspace_id else {
+        return Err(NodeError::Invalid("Relative paths need a workspace".to_string()));
+    };
+    if given.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+        return Err(NodeError::Invalid(format!("Path must stay inside the workspace: {}", path)));
+    }
+    Ok(workspace_dir(workspace_id).join(given))
+}
+
+async fn read_text_file(path: &Path) -> Result<String, NodeError> {
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| NodeError::NotFound(format!("Failed to read {}: {}", path.display(), e)))?;
+    if metadata.len() > MAX_CAT_SIZE {
+        return Err(NodeError::Invalid(format!(
+            "{} is {} bytes, over the {} byte limit",
+            path.display(),
+            metadata.len(),
+            MAX_CAT_SIZE
+        )));
+    }
+    let data = tokio::fs::read(path)
+        .await
+        .map_err(|e| NodeError::Internal(format!("Failed to read {}: {}", path.display(), e)))?;
+    String::from_utf8(data).map_err(|_| NodeError::Invalid(format!("{} is not text", path.display())))
+}
+
+/// Split text into chunks of about `CHUNK_SIZE` bytes overlapping by
+/// `CHUNK_OVERLAP`, breaking at whitespace where possible
+fn chunk(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        let mut end = (start + CHUNK_SIZE).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end < text.len() {
+            // Prefer to break after whitespace in the second half of the chunk
+            if let Some(pos) = text[start..end].rfind(char::is_whitespace) {
+                if pos > CHUNK_SIZE / 2 {
+                    end = start + pos + 1;
+                }
+            }
+        }
+        let piece = text[start..end].trim();
+        if !piece.is_empty() {
+            chunks.push(piece.to_string());
+        }
+        if end >= text.len() {
+            break;
+        }
+        let mut next = end.saturating_sub(CHUNK_OVERLAP).max(start + 1);
+        while !text.is_char_boundary(next) {
+            next += 1;
+        }
+        start = next;
+    }
+    chunks
+}
+
+/// Let agents search the documents of their workspace
+pub struct SearchDocumentsTool {
+    index: Arc<DocumentIndex>,
+}
+
+impl SearchDocumentsTool {
+    pub fn new(index: Arc<DocumentIndex>) -> Self {
+        Self { index }
+    }
+}
+
+#[async_trait]
+impl AgentTool for SearchDocumentsTool {
+    fn name(&self) -> &'static str {
+        "search_documents"
+    }
+
+    fn description(&self) -> &'static str {
+        "Search the documents indexed for this workspace and return the most relevant passages"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "What to look for" },
+                "limit": { "type": "integer", "description": "Number of passages (default 5)" }
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn execute(&self, ctx: &ToolContext, input: &Value) -> Result<String, String> {
+        let query = required_str(input, "query")?;
+        let hits = self
+            .index
+            .query(&RagQuery {
+                query: query.to_string(),
+                workspace_id: Some(ctx.workspace_id.clone()),
+                limit: input["limit"].as_u64().map(|n| n as usize),
+            })
+            .await?;
+        if hits.is_empty() {
+            return Ok("No indexed documents match.".to_string());
+        }
+        Ok(hits
+            .iter()
+            .map(|hit| format!("[{} #{}, score {:.2}]\n{}", hit.name, hit.chunk, hit.score, hit.content))
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+}
@@ -0,0 +1,170 @@
+//! Container Registry Credentials
+//!
+//! Logins for private registries, used when pulling, building and pushing
+//! images. The list of registries lives in `registries.json` under the
+//! config dir; passwords and tokens are kept in the OS keychain.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use super::keychain;
+
+/// Registry of image references without a registry host
+pub const DOCKER_HUB: &str = "docker.io";
+
+/// A registry login as submitted by the user
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegistryCredential {
+    /// Registry host, e.g. `ghcr.io` or `localhost:5000`
+    pub registry: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Identity token, used instead of a username and password
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+/// A saved registry, without its secret
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryInfo {
+    pub registry: String,
+    pub username: Option<String>,
+    pub uses_token: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Secret {
+    password: Option<String>,
+    token: Option<String>,
+}
+
+fn config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+        .join("registries.json")
+}
+
+fn keychain_key(registry: &str) -> String {
+    format!("registry:{}", registry)
+}
+
+fn normalize(registry: &str) -> String {
+    let host = registry
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/')
+        .to_lowercase();
+    match host.as_str() {
+        "index.docker.io" | "registry-1.docker.io" | "index.docker.io/v1" => DOCKER_HUB.to_string(),
+        _ => host,
+    }
+}
+
+/// Registry host an image reference is pulled from. Like Docker, the first
+/// path component is a host only if it has a dot, a port or is `localhost`.
+pub fn registry_of(image: &str) -> String {
+    match image.split_once('/') {
+        Some((first, _)) if first.contains('.') || first.contains(':') || first == "localhost" => {
+            normalize(first)
+        }
+        _ => DOCKER_HUB.to_string(),
+    }
+}
+
+/// Saved registries
+pub fn list() -> Vec<RegistryInfo> {
+    std::fs::read_to_string(config_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn persist(registries: &[RegistryInfo]) -> Result<(), String> {
+    let path = config_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let data = serde_json::to_string_pretty(registries).map_err(|e| e.to_string())?;
+    std::fs::write(&path, data).map_err(|e| format!("Failed to save registries: {}", e))
+}
+
+/// Add or replace the login for a registry
+pub fn save(credential: RegistryCredential) -> Result<RegistryInfo, String> {
+    let registry = normalize(&credential.registry);
+    if registry.is_empty() {
+        return Err("Registry is required".to_string());
+    }
+    let uses_token = credential.token.as_deref().is_some_and(|t| !t.is_empty());
+    if !uses_token && (credential.username.is_none() || credential.password.is_none()) {
+        return Err("A username and password, or a token, is required".to_string());
+    }
+
+    let secret = Secret {
+        password: credential.password,
+        token: credential.token,
+    };
+    let secret = serde_json::to_string(&secret).map_err(|e| e.to_string())?;
+    keychain::set(&keychain_key(&registry), &secret)?;
+
+    let info = RegistryInfo {
+        registry: registry.clone(),
+        username: credential.username,
+        uses_token,
+    };
+    let mut registries = list();
+    registries.retain(|r| r.registry != registry);
+    registries.push(info.clone());
+    persist(&registries)?;
+
+    log::info!("Saved credentials for registry {}", registry);
+    Ok(info)
+}
+
+pub fn remove(registry: &str) -> Result<(), String> {
+    let registry = normalize(registry);
+    let mut registries = list();
+    let before = registries.len();
+    registries.retain(|r| r.registry != registry);
+    if registries.len() == before {
+        return Err(format!("Unknown registry: {}", registry));
+    }
+
+    keychain::delete(&keychain_key(&registry))?;
+    persist(&registries)
+}
+
+/// Full login for a saved registry
+pub fn get(registry: &str) -> Option<RegistryCredential> {
+    let registry = normalize(registry);
+    let info = list().into_iter().find(|r| r.registry == registry)?;
+    let secret = match keychain::get(&keychain_key(&registry)) {
+        Ok(Some(secret)) => secret,
+        Ok(None) => return None,
+        Err(e) => {
+            log::warn!("{}", e);
+            return None;
+        }
+    };
+    let secret: Secret = serde_json::from_str(&secret).ok()?;
+
+    Some(RegistryCredential {
+        registry,
+        username: info.username,
+        password: secret.password,
+        token: secret.token,
+    })
+}
+
+/// Login for the registry an image comes from, if one is saved
+pub fn credentials_for(image: &str) -> Option<RegistryCredential> {
+    get(&registry_of(image))
+}
+
+/// Every saved login, for builds whose base images may come from any of them
+pub fn all() -> Vec<RegistryCredential> {
+    list().iter().filter_map(|r| get(&r.registry)).collect()
+}
@@ -0,0 +1,468 @@
+//! Remote Compute
+//!
+//! One-click GPU compute for agents: rent an offer, wait for the instance
+//! and its Ollama to come up, pull the model on it and register it as an
+//! Ollama backend in the provider registry. Agents pick it like any other
+//! provider, by its `gpu-...` id, which also becomes their compute source.
+//! A rented machine nobody has used for the idle timeout is destroyed.
+//! Attachments are kept in `remote_compute.json` under the config dir so
+//! a restart does not lose track of what is being paid for.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::gpu_provider::{self, RentRequest};
+use super::llm_provider::{ProviderConfig, ProviderKind, ProviderPrice, ProviderRegistry};
+use crate::config::NodeConfig;
+
+/// Prefix of the provider ids rented machines are registered under
+pub const REMOTE_COMPUTE_PREFIX: &str = "gpu-";
+
+/// Idle time before a rented machine is destroyed unless configured
+pub const DEFAULT_IDLE_TIMEOUT_MINUTES: u64 = 30;
+
+/// How long a rented instance may take to start running
+const INSTANCE_TIMEOUT: Duration = Duration::from_secs(20 * 60);
+
+/// How long its Ollama may take to answer once running
+const OLLAMA_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How long pulling the model may take
+const PULL_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Between checks while provisioning
+const PROVISION_POLL: Duration = Duration::from_secs(10);
+
+/// Between idle and liveness checks of attached machines
+const WATCH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteComputeStatus {
+    Renting,
+    /// Rented, waiting for the instance to run
+    Starting,
+    /// Running, waiting for Ollama to answer
+    WaitingForOllama,
+    PullingModel,
+    /// Registered and selectable by agents
+    Ready,
+    /// Destroyed after being idle, on request or by the provider
+    Destroyed,
+    Failed,
+}
+
+/// A rented machine attached, or being attached, as agent compute
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteCompute {
+    /// Provider id in the registry once ready, `gpu-<uuid>`
+    pub id: String,
+    /// GPU marketplace
+    pub provider: String,
+    pub offer_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<String>,
+    pub model: String,
+    pub status: RemoteComputeStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ollama_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_per_hour: Option<f64>,
+    pub idle_timeout_minutes: u64,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ready_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destroyed_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RemoteCompute {
+    fn is_active(&self) -> bool {
+        !matches!(self.status, RemoteComputeStatus::Destroyed | RemoteComputeStatus::Failed)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvisionRequest {
+    /// GPU marketplace, `vast` unless given
+    #[serde(default)]
+    pub provider: Option<String>,
+    pub offer_id: String,
+    /// Ollama model to pull on the machine
+    pub model: String,
+    /// Minutes without agent use before the machine is destroyed; 0 keeps
+    /// it until detached
+    #[serde(default)]
+    pub idle_timeout_minutes: Option<u64>,
+    #[serde(default, flatten)]
+    pub rent: RentRequest,
+}
+
+fn store_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+        .join("remote_compute.json")
+}
+
+pub struct RemoteComputeManager {
+    computes: RwLock<HashMap<String, RemoteCompute>>,
+    /// When each ready machine became ready or was last seen in use by
+    /// this process; usage is not persisted, so a restart starts the clock
+    active_since: std::sync::Mutex<HashMap<String, Instant>>,
+    path: PathBuf,
+    providers: Arc<ProviderRegistry>,
+    config: Arc<RwLock<NodeConfig>>,
+}
+
+impl RemoteComputeManager {
+    pub fn load(providers: Arc<ProviderRegistry>, config: Arc<RwLock<NodeConfig>>) -> Self {
+        let path = store_path();
+        let mut computes: HashMap<String, RemoteCompute> = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+
+        // Provisioning does not resume; the instance, if rented, is left
+        // for the user to destroy from the GPU panel
+        for compute in computes.values_mut() {
+            if compute.is_active() && compute.status != RemoteComputeStatus::Ready {
+                log::warn!("[GPU] Provisioning of {} was interrupted", compute.id);
+                compute.status = RemoteComputeStatus::Failed;
+                compute.error = Some("Interrupted by a restart".to_string());
+            }
+        }
+        let active_since = computes
+            .values()
+            .filter(|c| c.status == RemoteComputeStatus::Ready)
+            .map(|c| (c.id.clone(), Instant::now()))
+            .collect();
+
+        Self {
+            computes: RwLock::new(computes),
+            active_since: std::sync::Mutex::new(active_since),
+            path,
+            providers,
+            config,
+        }
+    }
+
+    fn persist(&self, computes: &HashMap<String, RemoteCompute>) {
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .map_err(|e| e.to_string())
+            .and_then(|_| serde_json::to_string_pretty(computes).map_err(|e| e.to_string()))
+            .and_then(|data| std::fs::write(&self.path, data).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            log::warn!("Failed to save remote compute: {}", e);
+        }
+    }
+
+    async fn update(&self, id: &str, f: impl FnOnce(&mut RemoteCompute)) {
+        let mut computes = self.computes.write().await;
+        if let Some(compute) = computes.get_mut(id) {
+            f(compute);
+            self.persist(&computes);
+        }
+    }
+
+    /// Every attachment, newest first
+    pub async fn list(&self) -> Vec<RemoteCompute> {
+        let mut computes: Vec<RemoteCompute> = self.computes.read().await.values().cloned().collect();
+        computes.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        computes
+    }
+
+    pub async fn get(&self, id: &str) -> Option<RemoteCompute> {
+        self.computes.read().await.get(id).cloned()
+    }
+
+    /// Start renting and attaching an offer. Returns right away; progress
+    /// shows in the attachment's status.
+    pub async fn provision(self: &Arc<Self>, req: ProvisionRequest) -> Result<RemoteCompute, String> {
+        if req.model.trim().is_empty() {
+            return Err("A model to pull is required".to_string());
+        }
+        let gpu = self.config.read().await.gpu.clone();
+        let provider_id = req.provider.clone().unwrap_or_else(|| gpu_provider::DEFAULT_PROVIDER.to_string());
+        // Fail early on a provider without a key
+        gpu_provider::provider(&provider_id, &gpu.custom_providers)?;
+
+        let compute = RemoteCompute {
+            id: format!("{}{}", REMOTE_COMPUTE_PREFIX, Uuid::new_v4()),
+            provider: provider_id,
+            offer_id: req.offer_id.clone(),
+            instance_id: None,
+            model: req.model.trim().to_string(),
+            status: RemoteComputeStatus::Renting,
+            ollama_url: None,
+            gpu_name: None,
+            price_per_hour: None,
+            idle_timeout_minutes: req
+                .idle_timeout_minutes
+                .or(gpu.remote_idle_timeout_minutes)
+                .unwrap_or(DEFAULT_IDLE_TIMEOUT_MINUTES),
+            created_at: Utc::now(),
+            ready_at: None,
+            destroyed_at: None,
+            error: None,
+        };
+        {
+            let mut computes = self.computes.write().await;
+            computes.insert(compute.id.clone(), compute.clone());
+            self.persist(&computes);
+        }
+
+        let manager = Arc::clone(self);
+        let id = compute.id.clone();
+        tokio::spawn(async move {
+            if let Err(e) = manager.attach(&id, &req.rent).await {
+                log::error!("[GPU] Failed to attach {}: {}", id, e);
+                manager
+                    .update(&id, |c| {
+                        c.status = RemoteComputeStatus::Failed;
+                        c.error = Some(e);
+                    })
+                    .await;
+                // Do not keep paying for a machine that never became usable
+                manager.destroy_instance(&id).await;
+            }
+        });
+
+        Ok(compute)
+    }
+
+    async fn attach(&self, id: &str, rent: &RentRequest) -> Result<(), String> {
+        let compute = self.get(id).await.ok_or("Remote compute not found")?;
+        let custom = self.config.read().await.gpu.custom_providers.clone();
+        let provider = gpu_provider::provider(&compute.provider, &custom)?;
+
+        log::info!("[GPU] Renting offer {} on {} for {}", compute.offer_id, compute.provider, id);
+        let instance_id = provider.rent(&compute.offer_id, rent).await?;
+        self.update(id, |c| {
+            c.instance_id = Some(instance_id.clone());
+            c.status = RemoteComputeStatus::Starting;
+        })
+        .await;
+
+        // Wait for the instance to run with a reachable Ollama
+        let deadline = Instant::now() + INSTANCE_TIMEOUT;
+        let (instance, ollama_url) = loop {
+            let instances = provider.list_instances().await.unwrap_or_else(|e| {
+                log::warn!("[GPU] Waiting for {}: {}", instance_id, e);
+                Vec::new()
+            });
+            if let Some(instance) = instances.into_iter().find(|i| i.id == instance_id) {
+                if instance.status == "running" {
+                    if let Some(url) = instance.ollama_url.clone() {
+                        break (instance, url.trim_end_matches('/').to_string());
+                    }
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(format!("Instance {} did not start within {} minutes", instance_id, INSTANCE_TIMEOUT.as_secs() / 60));
+            }
+            tokio::time::sleep(PROVISION_POLL).await;
+        };
+        self.update(id, |c| {
+            c.status = RemoteComputeStatus::WaitingForOllama;
+            c.ollama_url = Some(ollama_url.clone());
+            c.gpu_name = Some(instance.gpu_name.clone());
+            c.price_per_hour = Some(instance.price_per_hour);
+        })
+        .await;
+
+        let client = reqwest::Client::new();
+        let deadline = Instant::now() + OLLAMA_TIMEOUT;
+        loop {
+            let healthy = client
+                .get(format!("{}/api/tags", ollama_url))
+                .timeout(Duration::from_secs(5))
+                .send()
+                .await
+                .is_ok_and(|r| r.status().is_success());
+            if healthy {
+                break;
+            }
+            if Instant::now() >= deadline {
+                return Err(format!("Ollama at {} did not answer", ollama_url));
+            }
+            tokio::time::sleep(PROVISION_POLL).await;
+        }
+
+        self.update(id, |c| c.status = RemoteComputeStatus::PullingModel).await;
+        log::info!("[GPU] Pulling {} on {}", compute.model, ollama_url);
+        let response = client
+            .post(format!("{}/api/pull", ollama_url))
+            .json(&serde_json::json!({ "name": compute.model, "stream": false }))
+            .timeout(PULL_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to pull {}: {}", compute.model, e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("Failed to pull {}: {} {}", compute.model, status, text));
+        }
+
+        self.providers
+            .save(ProviderConfig {
+                id: id.to_string(),
+                kind: ProviderKind::Ollama,
+                base_url: ollama_url,
+                api_key: None,
+                default_model: Some(compute.model.clone()),
+                // Rented by the hour, in dollars
+                price: ProviderPrice {
+                    per_1k_tokens_cents: 0.0,
+                    per_hour_cents: instance.price_per_hour * 100.0,
+                },
+            })
+            .await?;
+        self.active_since.lock().unwrap().insert(id.to_string(), Instant::now());
+        self.update(id, |c| {
+            c.status = RemoteComputeStatus::Ready;
+            c.ready_at = Some(Utc::now());
+        })
+        .await;
+        log::info!("[GPU] {} ready with {}", id, compute.model);
+        Ok(())
+    }
+
+    /// Destroy the instance behind an attachment, if one was rented
+    async fn destroy_instance(&self, id: &str) {
+        let Some(compute) = self.get(id).await else {
+            return;
+        };
+        let Some(instance_id) = compute.instance_id else {
+            return;
+        };
+        let custom = self.config.read().await.gpu.custom_providers.clone();
+        let result = match gpu_provider::provider(&compute.provider, &custom) {
+            Ok(provider) => provider.destroy(&instance_id).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            log::error!("[GPU] Failed to destroy instance {} of {}: {}", instance_id, id, e);
+        }
+    }
+
+    /// Unregister the backend, destroy the machine and mark it destroyed
+    pub async fn detach(&self, id: &str, reason: &str) -> Result<(), String> {
+        let compute = self.get(id).await.ok_or("Remote compute not found")?;
+        if compute.status == RemoteComputeStatus::Destroyed {
+            return Ok(());
+        }
+        log::info!("[GPU] Detaching {}: {}", id, reason);
+
+        if self.providers.get_config(id).await.is_some() {
+            self.providers.remove(id).await?;
+        }
+        self.destroy_instance(id).await;
+        self.active_since.lock().unwrap().remove(id);
+        self.update(id, |c| {
+            c.status = RemoteComputeStatus::Destroyed;
+            c.destroyed_at = Some(Utc::now());
+            c.error = Some(reason.to_string());
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Ready attachments unused for longer than their idle timeout
+    async fn idle(&self) -> Vec<String> {
+        let ready: Vec<RemoteCompute> = self
+            .computes
+            .read()
+            .await
+            .values()
+            .filter(|c| c.status == RemoteComputeStatus::Ready)
+            .cloned()
+            .collect();
+
+        let mut active_since = self.active_since.lock().unwrap();
+        let mut idle = Vec::new();
+        for compute in ready {
+            let since = active_since.entry(compute.id.clone()).or_insert_with(Instant::now);
+            if self.providers.is_busy(&compute.id) {
+                *since = Instant::now();
+                continue;
+            }
+            if let Some(used) = self.providers.last_used(&compute.id) {
+                *since = (*since).max(used);
+            }
+            let timeout = Duration::from_secs(compute.idle_timeout_minutes * 60);
+            if compute.idle_timeout_minutes > 0 && since.elapsed() >= timeout {
+                idle.push(compute.id);
+            }
+        }
+        idle
+    }
+
+    /// Drop attachments whose instance the provider no longer runs, e.g.
+    /// after the budget destroyed it
+    async fn reap_gone(&self) {
+        let ready: Vec<RemoteCompute> = self
+            .computes
+            .read()
+            .await
+            .values()
+            .filter(|c| c.status == RemoteComputeStatus::Ready)
+            .cloned()
+            .collect();
+        if ready.is_empty() {
+            return;
+        }
+
+        let custom = self.config.read().await.gpu.custom_providers.clone();
+        let mut listed = HashMap::new();
+        for compute in ready {
+            if !listed.contains_key(&compute.provider) {
+                let instances = match gpu_provider::provider(&compute.provider, &custom) {
+                    Ok(provider) => provider.list_instances().await,
+                    Err(e) => Err(e),
+                };
+                listed.insert(compute.provider.clone(), instances.ok());
+            }
+            // A provider that cannot be reached says nothing either way
+            let Some(Some(instances)) = listed.get(&compute.provider) else {
+                continue;
+            };
+            let running = instances
+                .iter()
+                .any(|i| Some(&i.id) == compute.instance_id.as_ref() && i.status == "running");
+            if !running {
+                if let Err(e) = self.detach(&compute.id, "Instance is no longer running").await {
+                    log::warn!("[GPU] {}", e);
+                }
+            }
+        }
+    }
+
+    /// Tear down idle machines and forget vanished ones, forever
+    pub async fn watch(self: Arc<Self>) {
+        loop {
+            tokio::time::sleep(WATCH_INTERVAL).await;
+            for id in self.idle().await {
+                if let Err(e) = self.detach(&id, "Idle timeout reached").await {
+                    log::warn!("[GPU] {}", e);
+                }
+            }
+            self.reap_gone().await;
+        }
+    }
+}
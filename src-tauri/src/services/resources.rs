@@ -0,0 +1,147 @@
+//! Shared Resource Limits
+//!
+//! The contributor caps how much of the machine the node may use
+//! (`limits.resources`): a share of the CPU, memory and storage. The caps
+//! hold for everything the node runs:
+//!
+//! - containers, agent sandboxes included, get them as Docker or cgroup
+//!   limits on create, lowering whatever the request asked for, and
+//!   running containers are brought under new limits when they change
+//! - Ollama, IPFS and host shell commands run in a transient systemd scope
+//!   with `CPUQuota` and `MemoryMax` on Linux, where a user systemd is
+//!   available; everywhere, the Go runtimes of Ollama and Kubo are told
+//!   their share with `GOMAXPROCS` and `GOMEMLIMIT`
+//! - storage caps the IPFS repo budget, and images are not pulled or
+//!   containers created once the container runtime uses that much disk
+//!
+//! New limits reach Ollama and IPFS the next time they start.
+
+use std::path::Path;
+use std::process::Command;
+
+use super::container_runtime;
+use crate::models::ResourceLimits;
+
+const MB: u64 = 1024 * 1024;
+const GB: u64 = 1024 * MB;
+
+/// Default CFS period Docker uses with a quota
+const CPU_PERIOD: i64 = 100_000;
+
+fn cpu_count() -> f64 {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1) as f64
+}
+
+/// CPUs the node may use, when capped
+pub fn cpus(limits: &ResourceLimits) -> Option<f64> {
+    (limits.max_cpu_percent > 0 && limits.max_cpu_percent < 100)
+        .then(|| (cpu_count() * limits.max_cpu_percent as f64 / 100.0).max(0.01))
+}
+
+pub fn memory_bytes(limits: &ResourceLimits) -> Option<i64> {
+    (limits.max_memory_mb > 0).then(|| (limits.max_memory_mb * MB) as i64)
+}
+
+pub fn storage_bytes(limits: &ResourceLimits) -> Option<u64> {
+    (limits.max_storage_gb > 0).then(|| limits.max_storage_gb * GB)
+}
+
+pub fn check(limits: &ResourceLimits) -> Result<(), String> {
+    if limits.max_cpu_percent > 100 {
+        return Err("CPU limit must be a percentage between 0 and 100".to_string());
+    }
+    Ok(())
+}
+
+/// Container limits for a request, lowered to the node's caps
+pub fn cap(
+    requested: Option<container_runtime::ResourceLimits>,
+    limits: &ResourceLimits,
+) -> Option<container_runtime::ResourceLimits> {
+    let (cpus, memory) = (cpus(limits), memory_bytes(limits));
+    if cpus.is_none() && memory.is_none() {
+        return requested;
+    }
+
+    let mut resources = requested.unwrap_or_default();
+    if let Some(max) = cpus {
+        // Docker takes either a CPU count or a quota, not both
+        match resources.cpu_quota {
+            Some(quota) => {
+                let period = resources.cpu_period.unwrap_or(CPU_PERIOD);
+                resources.cpu_quota = Some(quota.min((max * period as f64) as i64));
+            }
+            None => resources.cpus = Some(resources.cpus.map_or(max, |c| c.min(max))),
+        }
+    }
+    if let Some(max) = memory {
+        resources.memory = Some(resources.memory.map_or(max, |m| m.min(max)));
+        // Swap may not be below memory; -1 (unlimited swap) is kept
+        if let (Some(memory), Some(swap)) = (resources.memory, resources.memory_swap) {
+            if swap >= 0 && swap < memory {
+                resources.memory_swap = Some(memory);
+            }
+        }
+    }
+    Some(resources)
+}
+
+/// Limits to put on containers that are already running
+pub fn update(limits: &ResourceLimits) -> Option<container_runtime::ResourceLimits> {
+    let (cpus, memory) = (cpus(limits), memory_bytes(limits));
+    (cpus.is_some() || memory.is_some()).then(|| container_runtime::ResourceLimits {
+        cpus,
+        memory,
+        ..Default::default()
+    })
+}
+
+/// Whether commands can be put in a transient user systemd scope
+#[cfg(target_os = "linux")]
+fn systemd_scope_available() -> bool {
+    static AVAILABLE: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        let available = Command::new("systemd-run")
+            .args(["--user", "--scope", "--quiet", "--collect", "true"])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !available {
+            log::info!("No user systemd; CPU and memory limits of Ollama and IPFS are advisory");
+        }
+        available
+    })
+}
+
+/// A command running `program` within the limits
+pub fn command(program: &Path, limits: &ResourceLimits) -> Command {
+    let (cpus, memory) = (cpus(limits), memory_bytes(limits));
+
+    #[cfg(target_os = "linux")]
+    let mut cmd = if (cpus.is_some() || memory.is_some()) && systemd_scope_available() {
+        let mut cmd = Command::new("systemd-run");
+        // A scope runs the program in place, so its PID is the program's
+        cmd.args(["--user", "--scope", "--quiet", "--collect"]);
+        if let Some(cpus) = cpus {
+            cmd.arg("-p").arg(format!("CPUQuota={}%", (cpus * 100.0).round() as u64));
+        }
+        if let Some(memory) = memory {
+            cmd.arg("-p").arg(format!("MemoryMax={}", memory));
+        }
+        cmd.arg("--").arg(program);
+        cmd
+    } else {
+        Command::new(program)
+    };
+    #[cfg(not(target_os = "linux"))]
+    let mut cmd = Command::new(program);
+
+    if let Some(cpus) = cpus {
+        cmd.env("GOMAXPROCS", (cpus.ceil() as u64).max(1).to_string());
+    }
+    if let Some(memory) = memory {
+        cmd.env("GOMEMLIMIT", memory.to_string());
+    }
+    cmd
+}
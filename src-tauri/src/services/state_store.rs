@@ -0,0 +1,955 @@
+//! Node State Store
+//!
+//! One SQLite database, `node.db`, for the node's own state: its ID and
+//! share key, settings such as the Ollama path, agent executions and what
+//! agents remember per workspace, documents indexed for retrieval, the
+//! history of relayed jobs, the user's other nodes and the jobs exchanged
+//! with them, the data pinned as contributed storage and the bytes
+//! transferred each month. Each kind of state has a small accessor
+//! (`Settings`, `AgentStore`, `AgentMemory`, `Documents`, `JobHistory`,
+//! `RemoteNodes`, `MeshJobs`, `StoragePins`, `BandwidthUsage`) sharing the
+//! one connection.
+//!
+//! The schema is a list of numbered migrations applied in order when the
+//! store opens; `PRAGMA user_version` records how far a database has got.
+//! On first open, state kept in the loose files of earlier versions
+//! (`node_id`, `share_key`, `agents.db`) is imported; the files are left in
+//! place.
+
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::agent_memory::AgentMemory;
+use super::agent_store::AgentStore;
+use super::rag::Documents;
+use super::redact::Secret;
+use crate::error::NodeError;
+use crate::pagination::{ListQuery, Page};
+
+/// Settings keys
+pub const NODE_ID: &str = "node_id";
+pub const SHARE_KEY: &str = "share_key";
+pub const OLLAMA_PATH: &str = "ollama_path";
+/// Multiplier dynamic pricing last set the rates to
+pub const PRICING_MULTIPLIER: &str = "pricing_multiplier";
+/// Set once the files of earlier versions have been imported
+const LEGACY_IMPORTED: &str = "legacy_imported";
+
+/// Schema changes, oldest first. Never edit one that has shipped; add a new one.
+const MIGRATIONS: &[&str] = &[
+    // 1: settings, agent executions and relayed jobs
+    "CREATE TABLE IF NOT EXISTS settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS agent_executions (
+        id TEXT PRIMARY KEY,
+        workspace_id TEXT NOT NULL,
+        status TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_agent_executions_workspace
+        ON agent_executions (workspace_id, created_at);
+    CREATE TABLE IF NOT EXISTS jobs (
+        id TEXT PRIMARY KEY,
+        method TEXT NOT NULL,
+        path TEXT NOT NULL,
+        status INTEGER,
+        started_at TEXT NOT NULL,
+        finished_at TEXT,
+        duration_ms INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS idx_jobs_started ON jobs (started_at);",
+    // 2: other nodes of the user, watched from this one
+    "CREATE TABLE IF NOT EXISTS remote_nodes (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        address TEXT NOT NULL,
+        share_key TEXT NOT NULL,
+        added_at TEXT NOT NULL
+    );",
+    // 3: jobs sent to or run for other nodes on the LAN
+    "CREATE TABLE IF NOT EXISTS mesh_jobs (
+        id TEXT PRIMARY KEY,
+        direction TEXT NOT NULL,
+        peer_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        remote_id TEXT,
+        status TEXT NOT NULL,
+        tokens_used INTEGER NOT NULL DEFAULT 0,
+        result TEXT,
+        error TEXT,
+        started_at TEXT NOT NULL,
+        finished_at TEXT,
+        duration_ms INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS idx_mesh_jobs_started ON mesh_jobs (started_at);",
+    // 4: data pinned for the orchestrator as contributed storage
+    "CREATE TABLE IF NOT EXISTS storage_pins (
+        cid TEXT PRIMARY KEY,
+        request_id TEXT,
+        size_bytes INTEGER NOT NULL DEFAULT 0,
+        status TEXT NOT NULL,
+        error TEXT,
+        requested_at TEXT NOT NULL,
+        pinned_at TEXT,
+        expires_at TEXT,
+        last_proof_at TEXT,
+        proofs_passed INTEGER NOT NULL DEFAULT 0,
+        proofs_failed INTEGER NOT NULL DEFAULT 0,
+        accrued_until TEXT,
+        gb_hours REAL NOT NULL DEFAULT 0
+    );",
+    // 5: bytes transferred per month and subsystem
+    "CREATE TABLE IF NOT EXISTS bandwidth (
+        month TEXT NOT NULL,
+        subsystem TEXT NOT NULL,
+        rx_bytes INTEGER NOT NULL DEFAULT 0,
+        tx_bytes INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (month, subsystem)
+    );",
+    // 6: goals, observations and results agents remember per workspace
+    "CREATE TABLE IF NOT EXISTS agent_memory (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        workspace_id TEXT NOT NULL,
+        execution_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        content TEXT NOT NULL,
+        embedding BLOB,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_agent_memory_workspace ON agent_memory (workspace_id, id);",
+    // 7: documents indexed for retrieval, chunked and embedded
+    "CREATE TABLE IF NOT EXISTS rag_documents (
+        id TEXT PRIMARY KEY,
+        workspace_id TEXT,
+        name TEXT NOT NULL,
+        source TEXT NOT NULL,
+        location TEXT NOT NULL,
+        size_bytes INTEGER NOT NULL DEFAULT 0,
+        chunks INTEGER NOT NULL DEFAULT 0,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS rag_chunks (
+        document_id TEXT NOT NULL,
+        seq INTEGER NOT NULL,
+        content TEXT NOT NULL,
+        embedding BLOB NOT NULL,
+        PRIMARY KEY (document_id, seq)
+    );",
+];
+
+pub struct StateStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl StateStore {
+    /// Open the store at its default location under the config dir,
+    /// importing state from the files earlier versions kept there
+    pub fn open_default() -> Result<Self, String> {
+        let store = Self::open(&default_path())?;
+        if let Some(dir) = default_path().parent() {
+            store.import_legacy(dir)?;
+        }
+        Ok(store)
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open state store: {}", e))?;
+        Self::init(conn)
+    }
+
+    /// Store that lives only as long as the process (used when the file can't be opened)
+    pub fn in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory().map_err(|e| format!("Failed to open state store: {}", e))?;
+        Self::init(conn)
+    }
+
+    fn init(mut conn: Connection) -> Result<Self, String> {
+        migrate(&mut conn)?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    pub fn settings(&self) -> Settings {
+        Settings { conn: Arc::clone(&self.conn) }
+    }
+
+    pub fn agents(&self) -> AgentStore {
+        AgentStore::new(Arc::clone(&self.conn))
+    }
+
+    pub fn agent_memory(&self) -> AgentMemory {
+        AgentMemory::new(Arc::clone(&self.conn))
+    }
+
+    pub fn documents(&self) -> Documents {
+        Documents::new(Arc::clone(&self.conn))
+    }
+
+    pub fn jobs(&self) -> JobHistory {
+        JobHistory { conn: Arc::clone(&self.conn) }
+    }
+
+    pub fn remote_nodes(&self) -> RemoteNodes {
+        RemoteNodes { conn: Arc::clone(&self.conn) }
+    }
+
+    pub fn mesh_jobs(&self) -> MeshJobs {
+        MeshJobs { conn: Arc::clone(&self.conn) }
+    }
+
+    pub fn storage_pins(&self) -> StoragePins {
+        StoragePins { conn: Arc::clone(&self.conn) }
+    }
+
+    pub fn bandwidth(&self) -> BandwidthUsage {
+        BandwidthUsage { conn: Arc::clone(&self.conn) }
+    }
+
+    /// Copy the node ID, share key and agent history out of the files
+    /// earlier versions kept in `dir`
+    fn import_legacy(&self, dir: &Path) -> Result<(), String> {
+        let settings = self.settings();
+        if settings.get(LEGACY_IMPORTED)?.is_some() {
+            return Ok(());
+        }
+
+        for (file, key) in [("node_id", NODE_ID), ("share_key", SHARE_KEY)] {
+            let Ok(value) = std::fs::read_to_string(dir.join(file)) else {
+                continue;
+            };
+            let value = value.trim();
+            if !value.is_empty() && settings.get(key)?.is_none() {
+                settings.set(key, value)?;
+            }
+        }
+
+        let agents_db = dir.join("agents.db");
+        if agents_db.exists() {
+            let conn = self.conn.lock().unwrap();
+            conn.execute("ATTACH DATABASE ?1 AS legacy", params![agents_db.to_string_lossy()])
+                .map_err(|e| format!("Failed to open {}: {}", agents_db.display(), e))?;
+            let imported = conn.execute(
+                "INSERT OR IGNORE INTO agent_executions (id, workspace_id, status, created_at, data)
+                 SELECT id, workspace_id, status, created_at, data FROM legacy.agent_executions",
+                [],
+            );
+            let _ = conn.execute("DETACH DATABASE legacy", []);
+            match imported {
+                Ok(n) if n > 0 => log::info!("Imported {} agent executions from {}", n, agents_db.display()),
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to import agent executions from {}: {}", agents_db.display(), e),
+            }
+        }
+
+        settings.set(LEGACY_IMPORTED, &Utc::now().to_rfc3339())
+    }
+}
+
+/// Bring the schema up to the newest migration
+fn migrate(conn: &mut Connection) -> Result<(), String> {
+    let version: i64 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to read state store version: {}", e))?;
+    let version = version.max(0) as usize;
+    if version > MIGRATIONS.len() {
+        return Err(format!(
+            "State store is at version {}, newer than this build understands ({})",
+            version,
+            MIGRATIONS.len()
+        ));
+    }
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute_batch(migration)
+            .and_then(|_| tx.pragma_update(None, "user_version", (i + 1) as i64))
+            .and_then(|_| tx.commit())
+            .map_err(|e| format!("Failed to migrate state store to version {}: {}", i + 1, e))?;
+        log::info!("Migrated state store to version {}", i + 1);
+    }
+    Ok(())
+}
+
+/// Key-value settings of the node
+pub struct Settings {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Settings {
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT value FROM settings WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()
+            .map_err(|e| format!("Failed to read setting {}: {}", key, e))
+    }
+
+    pub fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES (?1, ?2, ?3)",
+                params![key, value, Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| format!("Failed to save setting {}: {}", key, e))?;
+        Ok(())
+    }
+
+    pub fn delete(&self, key: &str) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM settings WHERE key = ?1", params![key])
+            .map_err(|e| format!("Failed to delete setting {}: {}", key, e))?;
+        Ok(())
+    }
+}
+
+/// A job relayed from the orchestrator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobRecord {
+    pub id: String,
+    pub method: String,
+    pub path: String,
+    /// Response status; missing while the job runs, or if the node stopped first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub started_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+/// Job fields that can be sorted on, with their columns
+const JOB_SORT: &[(&str, &str)] = &[
+    ("startedAt", "started_at"),
+    ("method", "method"),
+    ("path", "path"),
+    ("status", "status"),
+    ("durationMs", "duration_ms"),
+];
+/// Job fields that can be filtered on
+const JOB_FILTER: &[(&str, &str)] = &[("method", "method"), ("path", "path"), ("status", "status")];
+
+/// History of relayed jobs
+pub struct JobHistory {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl JobHistory {
+    pub fn start(&self, id: &str, method: &str, path: &str) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO jobs (id, method, path, started_at) VALUES (?1, ?2, ?3, ?4)",
+                params![id, method, path, Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| format!("Failed to record job {}: {}", id, e))?;
+        Ok(())
+    }
+
+    pub fn finish(&self, id: &str, status: u16, duration_ms: u64) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE jobs SET status = ?2, finished_at = ?3, duration_ms = ?4 WHERE id = ?1",
+                params![id, status, Utc::now().to_rfc3339(), duration_ms as i64],
+            )
+            .map_err(|e| format!("Failed to record job {}: {}", id, e))?;
+        Ok(())
+    }
+
+    /// A page of jobs, newest first unless sorted otherwise
+    pub fn list(&self, query: &ListQuery) -> Result<Page<JobRecord>, NodeError> {
+        let order = query.order(JOB_SORT, "ORDER BY started_at DESC")?;
+        let (condition, filter) = query.condition(JOB_FILTER, 1)?;
+        let conn = self.conn.lock().unwrap();
+        let total: i64 = conn
+            .query_row(&format!("SELECT COUNT(*) FROM jobs WHERE {}", condition), params![filter], |row| {
+                row.get(0)
+            })
+            .map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT id, method, path, status, started_at, finished_at, duration_ms FROM jobs
+                 WHERE {} {} LIMIT ?2 OFFSET ?3",
+                condition, order
+            ))
+            .map_err(|e| e.to_string())?;
+        let (limit, offset) = (query.limit(), query.offset());
+        let rows = stmt
+            .query_map(params![filter, limit as i64, offset as i64], |row| {
+                Ok(JobRecord {
+                    id: row.get(0)?,
+                    method: row.get(1)?,
+                    path: row.get(2)?,
+                    status: row.get(3)?,
+                    started_at: row.get(4)?,
+                    finished_at: row.get(5)?,
+                    duration_ms: row.get::<_, Option<i64>>(6)?.map(|ms| ms.max(0) as u64),
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        let items = rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+        Ok(Page { items, total: total.max(0) as usize, offset, limit })
+    }
+}
+
+/// Another node of the user, reached at its API address
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteNode {
+    /// The remote node's own ID
+    pub id: String,
+    pub name: String,
+    /// Base URL of its API, e.g. `http://192.168.1.20:8080`
+    pub address: String,
+    #[serde(skip)]
+    pub share_key: Secret,
+    pub added_at: String,
+}
+
+/// Remote nodes registered on this one
+pub struct RemoteNodes {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl RemoteNodes {
+    pub fn list(&self) -> Result<Vec<RemoteNode>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT id, name, address, share_key, added_at FROM remote_nodes ORDER BY added_at")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([], remote_node).map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to read remote nodes: {}", e))
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<RemoteNode>, String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT id, name, address, share_key, added_at FROM remote_nodes WHERE id = ?1",
+                params![id],
+                remote_node,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read remote node {}: {}", id, e))
+    }
+
+    /// Add the node, or update its name, address and key if it is known
+    pub fn save(&self, node: &RemoteNode) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO remote_nodes (id, name, address, share_key, added_at) VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(id) DO UPDATE SET name = ?2, address = ?3, share_key = ?4",
+                params![node.id, node.name, node.address, node.share_key.expose(), node.added_at],
+            )
+            .map_err(|e| format!("Failed to save remote node {}: {}", node.id, e))?;
+        Ok(())
+    }
+
+    /// Returns whether the node was registered
+    pub fn remove(&self, id: &str) -> Result<bool, String> {
+        let removed = self
+            .conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM remote_nodes WHERE id = ?1", params![id])
+            .map_err(|e| format!("Failed to remove remote node {}: {}", id, e))?;
+        Ok(removed > 0)
+    }
+}
+
+fn remote_node(row: &rusqlite::Row) -> rusqlite::Result<RemoteNode> {
+    Ok(RemoteNode {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        address: row.get(2)?,
+        share_key: Secret::new(row.get::<_, String>(3)?),
+        added_at: row.get(4)?,
+    })
+}
+
+/// A job exchanged with another node on the LAN: `sent` to run there, or
+/// `received` and run here
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeshJobRecord {
+    pub id: String,
+    /// `sent` or `received`
+    pub direction: String,
+    /// The node the job went to or came from
+    pub peer_id: String,
+    /// `agent` or `container`
+    pub kind: String,
+    /// ID of a sent job on the node running it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_id: Option<String>,
+    /// `running`, `completed`, `failed` or `interrupted`
+    pub status: String,
+    pub tokens_used: u64,
+    /// Agent answer or container output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub started_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+/// Work exchanged with one peer in one direction
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeshUsage {
+    pub peer_id: String,
+    pub direction: String,
+    pub jobs: u64,
+    pub tokens_used: u64,
+    pub duration_ms: u64,
+}
+
+const MESH_JOB_SORT: &[(&str, &str)] = &[
+    ("startedAt", "started_at"),
+    ("peerId", "peer_id"),
+    ("kind", "kind"),
+    ("status", "status"),
+    ("tokensUsed", "tokens_used"),
+    ("durationMs", "duration_ms"),
+];
+const MESH_JOB_FILTER: &[(&str, &str)] = &[
+    ("direction", "direction"),
+    ("peerId", "peer_id"),
+    ("kind", "kind"),
+    ("status", "status"),
+];
+const MESH_JOB_COLUMNS: &str =
+    "id, direction, peer_id, kind, remote_id, status, tokens_used, result, error, started_at, finished_at, duration_ms";
+
+/// Jobs sent to and received from other nodes
+pub struct MeshJobs {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl MeshJobs {
+    pub fn start(&self, job: &MeshJobRecord) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO mesh_jobs (id, direction, peer_id, kind, remote_id, status, started_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![job.id, job.direction, job.peer_id, job.kind, job.remote_id, job.status, job.started_at],
+            )
+            .map_err(|e| format!("Failed to record mesh job {}: {}", job.id, e))?;
+        Ok(())
+    }
+
+    /// Record how a job ended, and what it used
+    pub fn finish(
+        &self,
+        id: &str,
+        status: &str,
+        tokens_used: u64,
+        result: Option<&str>,
+        error: Option<&str>,
+        duration_ms: u64,
+    ) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE mesh_jobs SET status = ?2, tokens_used = ?3, result = ?4, error = ?5,
+                 finished_at = ?6, duration_ms = ?7 WHERE id = ?1",
+                params![id, status, tokens_used as i64, result, error, Utc::now().to_rfc3339(), duration_ms as i64],
+            )
+            .map_err(|e| format!("Failed to record mesh job {}: {}", id, e))?;
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<MeshJobRecord>, String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                &format!("SELECT {} FROM mesh_jobs WHERE id = ?1", MESH_JOB_COLUMNS),
+                params![id],
+                mesh_job,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read mesh job {}: {}", id, e))
+    }
+
+    /// Jobs still running in one direction
+    pub fn running(&self, direction: &str) -> Result<Vec<MeshJobRecord>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM mesh_jobs WHERE direction = ?1 AND status = 'running'",
+                MESH_JOB_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt.query_map(params![direction], mesh_job).map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to read mesh jobs: {}", e))
+    }
+
+    /// A page of jobs, newest first unless sorted otherwise
+    pub fn list(&self, query: &ListQuery) -> Result<Page<MeshJobRecord>, NodeError> {
+        let order = query.order(MESH_JOB_SORT, "ORDER BY started_at DESC")?;
+        let (condition, filter) = query.condition(MESH_JOB_FILTER, 1)?;
+        let conn = self.conn.lock().unwrap();
+        let total: i64 = conn
+            .query_row(&format!("SELECT COUNT(*) FROM mesh_jobs WHERE {}", condition), params![filter], |row| {
+                row.get(0)
+            })
+            .map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM mesh_jobs WHERE {} {} LIMIT ?2 OFFSET ?3",
+                MESH_JOB_COLUMNS, condition, order
+            ))
+            .map_err(|e| e.to_string())?;
+        let (limit, offset) = (query.limit(), query.offset());
+        let rows = stmt
+            .query_map(params![filter, limit as i64, offset as i64], mesh_job)
+            .map_err(|e| e.to_string())?;
+        let items = rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+        Ok(Page { items, total: total.max(0) as usize, offset, limit })
+    }
+
+    /// Totals per peer and direction, over finished jobs
+    pub fn usage(&self) -> Result<Vec<MeshUsage>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT peer_id, direction, COUNT(*), SUM(tokens_used), SUM(COALESCE(duration_ms, 0))
+                 FROM mesh_jobs WHERE status != 'running'
+                 GROUP BY peer_id, direction ORDER BY peer_id, direction",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(MeshUsage {
+                    peer_id: row.get(0)?,
+                    direction: row.get(1)?,
+                    jobs: row.get::<_, i64>(2)?.max(0) as u64,
+                    tokens_used: row.get::<_, i64>(3)?.max(0) as u64,
+                    duration_ms: row.get::<_, i64>(4)?.max(0) as u64,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to read mesh usage: {}", e))
+    }
+}
+
+fn mesh_job(row: &rusqlite::Row) -> rusqlite::Result<MeshJobRecord> {
+    Ok(MeshJobRecord {
+        id: row.get(0)?,
+        direction: row.get(1)?,
+        peer_id: row.get(2)?,
+        kind: row.get(3)?,
+        remote_id: row.get(4)?,
+        status: row.get(5)?,
+        tokens_used: row.get::<_, i64>(6)?.max(0) as u64,
+        result: row.get(7)?,
+        error: row.get(8)?,
+        started_at: row.get(9)?,
+        finished_at: row.get(10)?,
+        duration_ms: row.get::<_, Option<i64>>(11)?.map(|ms| ms.max(0) as u64),
+    })
+}
+
+/// Data pinned for the orchestrator, and how well it has been kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoragePin {
+    pub cid: String,
+    /// ID of the orchestrator's pin request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub size_bytes: u64,
+    /// `pinning`, `pinned`, `missing` or `failed`
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub requested_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned_at: Option<String>,
+    /// When the orchestrator no longer needs it; unpinned after
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_proof_at: Option<String>,
+    pub proofs_passed: u64,
+    pub proofs_failed: u64,
+    /// Storage-hours are recorded up to here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accrued_until: Option<String>,
+    pub gb_hours: f64,
+}
+
+const STORAGE_PIN_SORT: &[(&str, &str)] = &[
+    ("requestedAt", "requested_at"),
+    ("sizeBytes", "size_bytes"),
+    ("status", "status"),
+    ("gbHours", "gb_hours"),
+    ("lastProofAt", "last_proof_at"),
+];
+const STORAGE_PIN_FILTER: &[(&str, &str)] = &[("status", "status"), ("requestId", "request_id")];
+const STORAGE_PIN_COLUMNS: &str = "cid, request_id, size_bytes, status, error, requested_at, pinned_at, expires_at, \
+     last_proof_at, proofs_passed, proofs_failed, accrued_until, gb_hours";
+
+/// Data pinned as contributed storage
+pub struct StoragePins {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl StoragePins {
+    /// Record a new request, or take up again one that failed
+    pub fn request(&self, pin: &StoragePin) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO storage_pins (cid, request_id, size_bytes, status, requested_at, expires_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT (cid) DO UPDATE SET request_id = ?2, size_bytes = ?3, status = ?4,
+                 error = NULL, requested_at = ?5, expires_at = ?6",
+                params![pin.cid, pin.request_id, pin.size_bytes as i64, pin.status, pin.requested_at, pin.expires_at],
+            )
+            .map_err(|e| format!("Failed to record pin {}: {}", pin.cid, e))?;
+        Ok(())
+    }
+
+    /// The pin is held; storage-hours count from now
+    pub fn pinned(&self, cid: &str, size_bytes: u64) -> Result<(), String> {
+        let now = Utc::now().to_rfc3339();
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE storage_pins SET status = 'pinned', error = NULL, size_bytes = ?2,
+                 pinned_at = COALESCE(pinned_at, ?3), accrued_until = ?3 WHERE cid = ?1",
+                params![cid, size_bytes as i64, now],
+            )
+            .map_err(|e| format!("Failed to record pin {}: {}", cid, e))?;
+        Ok(())
+    }
+
+    pub fn set_status(&self, cid: &str, status: &str, error: Option<&str>) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE storage_pins SET status = ?2, error = ?3 WHERE cid = ?1",
+                params![cid, status, error],
+            )
+            .map_err(|e| format!("Failed to record pin {}: {}", cid, e))?;
+        Ok(())
+    }
+
+    /// Record the outcome of a possession check
+    pub fn proof(&self, cid: &str, passed: bool) -> Result<(), String> {
+        let column = if passed { "proofs_passed" } else { "proofs_failed" };
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                &format!(
+                    "UPDATE storage_pins SET {0} = {0} + 1, last_proof_at = ?2 WHERE cid = ?1",
+                    column
+                ),
+                params![cid, Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| format!("Failed to record proof for {}: {}", cid, e))?;
+        Ok(())
+    }
+
+    /// Add storage-hours recorded up to `until`
+    pub fn accrue(&self, cid: &str, until: &str, gb_hours: f64) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE storage_pins SET gb_hours = gb_hours + ?3, accrued_until = ?2 WHERE cid = ?1",
+                params![cid, until, gb_hours],
+            )
+            .map_err(|e| format!("Failed to record storage-hours for {}: {}", cid, e))?;
+        Ok(())
+    }
+
+    pub fn get(&self, cid: &str) -> Result<Option<StoragePin>, String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                &format!("SELECT {} FROM storage_pins WHERE cid = ?1", STORAGE_PIN_COLUMNS),
+                params![cid],
+                storage_pin,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read pin {}: {}", cid, e))
+    }
+
+    /// Pins that are held or being fetched, oldest first
+    pub fn active(&self) -> Result<Vec<StoragePin>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM storage_pins WHERE status != 'failed' ORDER BY requested_at",
+                STORAGE_PIN_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([], storage_pin).map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to read pins: {}", e))
+    }
+
+    /// A page of pins, newest first unless sorted otherwise
+    pub fn list(&self, query: &ListQuery) -> Result<Page<StoragePin>, NodeError> {
+        let order = query.order(STORAGE_PIN_SORT, "ORDER BY requested_at DESC")?;
+        let (condition, filter) = query.condition(STORAGE_PIN_FILTER, 1)?;
+        let conn = self.conn.lock().unwrap();
+        let total: i64 = conn
+            .query_row(&format!("SELECT COUNT(*) FROM storage_pins WHERE {}", condition), params![filter], |row| {
+                row.get(0)
+            })
+            .map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM storage_pins WHERE {} {} LIMIT ?2 OFFSET ?3",
+                STORAGE_PIN_COLUMNS, condition, order
+            ))
+            .map_err(|e| e.to_string())?;
+        let (limit, offset) = (query.limit(), query.offset());
+        let rows = stmt
+            .query_map(params![filter, limit as i64, offset as i64], storage_pin)
+            .map_err(|e| e.to_string())?;
+        let items = rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+        Ok(Page { items, total: total.max(0) as usize, offset, limit })
+    }
+
+    /// Bytes taken by pins that are held or being fetched
+    pub fn used_bytes(&self) -> Result<u64, String> {
+        let used: i64 = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT COALESCE(SUM(size_bytes), 0) FROM storage_pins WHERE status != 'failed'",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to read pins: {}", e))?;
+        Ok(used.max(0) as u64)
+    }
+
+    /// Storage-hours recorded over the pins on record
+    pub fn total_gb_hours(&self) -> Result<f64, String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT COALESCE(SUM(gb_hours), 0) FROM storage_pins", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read pins: {}", e))
+    }
+
+    pub fn remove(&self, cid: &str) -> Result<bool, String> {
+        let removed = self
+            .conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM storage_pins WHERE cid = ?1", params![cid])
+            .map_err(|e| format!("Failed to remove pin {}: {}", cid, e))?;
+        Ok(removed > 0)
+    }
+}
+
+fn storage_pin(row: &rusqlite::Row) -> rusqlite::Result<StoragePin> {
+    Ok(StoragePin {
+        cid: row.get(0)?,
+        request_id: row.get(1)?,
+        size_bytes: row.get::<_, i64>(2)?.max(0) as u64,
+        status: row.get(3)?,
+        error: row.get(4)?,
+        requested_at: row.get(5)?,
+        pinned_at: row.get(6)?,
+        expires_at: row.get(7)?,
+        last_proof_at: row.get(8)?,
+        proofs_passed: row.get::<_, i64>(9)?.max(0) as u64,
+        proofs_failed: row.get::<_, i64>(10)?.max(0) as u64,
+        accrued_until: row.get(11)?,
+        gb_hours: row.get(12)?,
+    })
+}
+
+/// Bytes transferred, by month (`YYYY-MM`) and subsystem
+pub struct BandwidthUsage {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl BandwidthUsage {
+    /// Bytes received and sent in `month`, by subsystem
+    pub fn month(&self, month: &str) -> Result<Vec<(String, u64, u64)>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT subsystem, rx_bytes, tx_bytes FROM bandwidth WHERE month = ?1")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![month], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?.max(0) as u64,
+                    row.get::<_, i64>(2)?.max(0) as u64,
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to read bandwidth usage: {}", e))
+    }
+
+    /// Replace the totals of one subsystem for `month`
+    pub fn save(&self, month: &str, subsystem: &str, rx_bytes: u64, tx_bytes: u64) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO bandwidth (month, subsystem, rx_bytes, tx_bytes) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (month, subsystem) DO UPDATE SET rx_bytes = ?3, tx_bytes = ?4",
+                params![month, subsystem, rx_bytes as i64, tx_bytes as i64],
+            )
+            .map_err(|e| format!("Failed to record bandwidth usage: {}", e))?;
+        Ok(())
+    }
+}
+
+/// Location of the state database
+pub fn default_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+        .join("node.db")
+}
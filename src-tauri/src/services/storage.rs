@@ -0,0 +1,407 @@
+//! Storage Contribution
+//!
+//! With `storage.enabled`, the node rents out disk space: the orchestrator
+//! sends a `PinRequest` (through the relay, like any job) and the node pins
+//! the data in its IPFS repo, which `storage.drive` places on a chosen
+//! drive. Requests that would take the pins past `budgetGb` are refused.
+//! Pins are held: the repo budget never unpins them to make room.
+//!
+//! Every `proofIntervalSecs` each pin is checked to still be whole in the
+//! local repo, and fetched again if not. Pins that pass earn
+//! `storageGbHourCents` per GB-hour since the last check, recorded in the
+//! earnings ledger as one `storage` entry per round; fractions of a cent
+//! carry over to the next round. The orchestrator checks for itself with a
+//! `Challenge`: the SHA-256 of its nonce and a range of the content, which
+//! only a node holding the bytes can answer.
+//!
+//! Turning contribution off stops new requests; data already pinned is
+//! kept, checked and paid until it is released or expires.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use super::earnings::{EarningsLedger, NewEarning};
+use super::identity::{self, NodeIdentity};
+use super::state_store::{StoragePin, StoragePins};
+use super::IpfsManager;
+use crate::config::NodeConfig;
+use crate::error::NodeError;
+use crate::pagination::{ListQuery, Page};
+
+const GIB: f64 = (1u64 << 30) as f64;
+
+/// Largest range a challenge may ask for
+const MAX_CHALLENGE_LENGTH: u64 = 1024 * 1024;
+
+/// Shortest wait between checks, whatever the config says
+const MIN_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long the size of requested content may take to resolve
+const STAT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Data the orchestrator asks the node to keep
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinRequest {
+    /// The orchestrator's ID for the request
+    #[serde(default)]
+    pub id: Option<String>,
+    pub cid: String,
+    /// Size as the orchestrator knows it; resolved from the network otherwise
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+    /// Unpinned after this time (RFC 3339)
+    #[serde(default)]
+    pub expires_at: Option<String>,
+}
+
+/// Prove the node holds `cid` by hashing `nonce` with a range of it
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Challenge {
+    pub cid: String,
+    pub nonce: String,
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Proof {
+    pub cid: String,
+    pub nonce: String,
+    pub offset: u64,
+    pub length: u64,
+    /// Hex SHA-256 of the nonce followed by the bytes read
+    pub proof: String,
+    /// Node signature over `storage_proof_message`
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageStatus {
+    pub enabled: bool,
+    pub budget_bytes: u64,
+    pub used_bytes: u64,
+    pub pins: usize,
+    /// Storage-hours recorded over the pins on record
+    pub gb_hours: f64,
+    pub repo_path: String,
+}
+
+pub struct Storage {
+    ipfs: Arc<IpfsManager>,
+    pins: StoragePins,
+    earnings: Arc<EarningsLedger>,
+    identity: Arc<NodeIdentity>,
+    config: Arc<RwLock<NodeConfig>>,
+    /// Fraction of a cent earned but not yet recorded
+    carry: Mutex<f64>,
+}
+
+impl Storage {
+    pub fn new(
+        ipfs: Arc<IpfsManager>,
+        pins: StoragePins,
+        earnings: Arc<EarningsLedger>,
+        identity: Arc<NodeIdentity>,
+        config: Arc<RwLock<NodeConfig>>,
+    ) -> Self {
+        match pins.active() {
+            Ok(active) => {
+                for pin in active {
+                    ipfs.hold(&pin.cid, true);
+                }
+            }
+            Err(e) => log::warn!("{}", e),
+        }
+        Self {
+            ipfs,
+            pins,
+            earnings,
+            identity,
+            config,
+            carry: Mutex::new(0.0),
+        }
+    }
+
+    pub async fn status(&self) -> Result<StorageStatus, NodeError> {
+        let config = self.config.read().await.storage.clone();
+        let active = self.pins.active()?;
+        Ok(StorageStatus {
+            enabled: config.enabled,
+            budget_bytes: budget_bytes(config.budget_gb),
+            used_bytes: active.iter().map(|pin| pin.size_bytes).sum(),
+            pins: active.len(),
+            gb_hours: self.pins.total_gb_hours()?,
+            repo_path: self.ipfs.get_repo_path().display().to_string(),
+        })
+    }
+
+    pub fn list(&self, query: &ListQuery) -> Result<Page<StoragePin>, NodeError> {
+        self.pins.list(query)
+    }
+
+    pub fn get(&self, cid: &str) -> Result<StoragePin, NodeError> {
+        self.pins
+            .get(cid)?
+            .ok_or_else(|| NodeError::NotFound(format!("No pin for {}", cid)))
+    }
+
+    /// Take on a pin request within the budget. The data is fetched in the
+    /// background; the returned pin is `pinning` until it arrives.
+    pub async fn request(self: &Arc<Self>, request: PinRequest) -> Result<StoragePin, NodeError> {
+        let config = self.config.read().await.storage.clone();
+        if !config.enabled {
+            return Err(NodeError::Policy("Storage contribution is off".to_string()));
+        }
+        let cid = request.cid.trim().to_string();
+        if cid.is_empty() {
+            return Err(NodeError::Invalid("cid is required".to_string()));
+        }
+        if let Some(expires_at) = &request.expires_at {
+            DateTime::parse_from_rfc3339(expires_at)
+                .map_err(|_| NodeError::Invalid("expiresAt must be an RFC 3339 timestamp".to_string()))?;
+        }
+        if !self.ipfs.is_running() {
+            return Err(NodeError::NotRunning("IPFS is not running".to_string()));
+        }
+        if let Some(existing) = self.pins.get(&cid)? {
+            if existing.status != "failed" {
+                return Ok(existing);
+            }
+        }
+
+        let size = match request.size_bytes {
+            Some(size) => size,
+            None => tokio::time::timeout(STAT_TIMEOUT, self.ipfs.content_size(&cid))
+                .await
+                .map_err(|_| NodeError::Timeout(format!("Timed out resolving the size of {}", cid)))??,
+        };
+        let budget = budget_bytes(config.budget_gb);
+        let used = self.pins.used_bytes()?;
+        if used.saturating_add(size) > budget {
+            return Err(NodeError::Policy(format!(
+                "{} ({} bytes) would take storage past its {} GB budget ({} bytes free)",
+                cid,
+                size,
+                config.budget_gb,
+                budget.saturating_sub(used)
+            )));
+        }
+
+        let pin = StoragePin {
+            cid: cid.clone(),
+            request_id: request.id,
+            size_bytes: size,
+            status: "pinning".to_string(),
+            error: None,
+            requested_at: Utc::now().to_rfc3339(),
+            pinned_at: None,
+            expires_at: request.expires_at,
+            last_proof_at: None,
+            proofs_passed: 0,
+            proofs_failed: 0,
+            accrued_until: None,
+            gb_hours: 0.0,
+        };
+        self.pins.request(&pin)?;
+        self.ipfs.hold(&cid, true);
+        log::info!("Pinning {} ({} bytes) as contributed storage", cid, size);
+        tokio::spawn(Arc::clone(self).fetch(cid));
+        Ok(pin)
+    }
+
+    /// Pin `cid`, recording its size once it is all here
+    async fn fetch(self: Arc<Self>, cid: String) {
+        match self.ipfs.pin(&cid).await {
+            Ok(()) => {
+                let size = match self.ipfs.content_size(&cid).await {
+                    Ok(size) => size,
+                    Err(_) => self.pins.get(&cid).ok().flatten().map_or(0, |pin| pin.size_bytes),
+                };
+                if let Err(e) = self.pins.pinned(&cid, size) {
+                    log::warn!("{}", e);
+                }
+            }
+            Err(e) => {
+                log::warn!("Failed to pin {} for storage: {}", cid, e);
+                self.ipfs.hold(&cid, false);
+                if let Err(e) = self.pins.set_status(&cid, "failed", Some(e.to_string().as_str())) {
+                    log::warn!("{}", e);
+                }
+            }
+        }
+    }
+
+    /// Stop keeping `cid`. Time since its last check is not paid.
+    pub async fn release(&self, cid: &str) -> Result<(), NodeError> {
+        self.get(cid)?;
+        if self.ipfs.is_running() {
+            self.ipfs.unpin(cid).await?;
+        }
+        self.ipfs.hold(cid, false);
+        self.pins.remove(cid)?;
+        log::info!("Released {} from contributed storage", cid);
+        Ok(())
+    }
+
+    /// Answer a challenge from the local repo only
+    pub async fn respond(&self, challenge: Challenge) -> Result<Proof, NodeError> {
+        if challenge.nonce.is_empty() {
+            return Err(NodeError::Invalid("nonce is required".to_string()));
+        }
+        if challenge.length == 0 || challenge.length > MAX_CHALLENGE_LENGTH {
+            return Err(NodeError::Invalid(format!(
+                "length must be between 1 and {} bytes",
+                MAX_CHALLENGE_LENGTH
+            )));
+        }
+        let pin = self.get(&challenge.cid)?;
+        if pin.status != "pinned" {
+            return Err(NodeError::Conflict(format!("{} is {}", pin.cid, pin.status)));
+        }
+        if !self.ipfs.is_running() {
+            return Err(NodeError::NotRunning("IPFS is not running".to_string()));
+        }
+
+        let data = match self
+            .ipfs
+            .read_range(&challenge.cid, challenge.offset, challenge.length)
+            .await
+        {
+            Ok(data) => data,
+            Err(e) => {
+                let _ = self.pins.proof(&challenge.cid, false);
+                return Err(e);
+            }
+        };
+        let _ = self.pins.proof(&challenge.cid, true);
+
+        let proof = identity::possession_proof(&challenge.nonce, &data);
+        let signature = self.identity.sign(&identity::storage_proof_message(
+            &challenge.cid,
+            &challenge.nonce,
+            challenge.offset,
+            challenge.length,
+            &proof,
+        ));
+        Ok(Proof {
+            cid: challenge.cid,
+            nonce: challenge.nonce,
+            offset: challenge.offset,
+            length: challenge.length,
+            proof,
+            signature,
+        })
+    }
+
+    /// Check pins and record storage-hours for the life of the app
+    pub async fn watch(self: Arc<Self>) {
+        // Fetches cut short by the last shutdown
+        if let Ok(active) = self.pins.active() {
+            for pin in active.into_iter().filter(|pin| pin.status == "pinning") {
+                tokio::spawn(Arc::clone(&self).fetch(pin.cid));
+            }
+        }
+        loop {
+            let interval = self.config.read().await.storage.proof_interval_secs;
+            tokio::time::sleep(Duration::from_secs(interval).max(MIN_CHECK_INTERVAL)).await;
+            if !self.ipfs.is_running() {
+                continue;
+            }
+            if let Err(e) = self.check().await {
+                log::warn!("Storage check failed: {}", e);
+            }
+        }
+    }
+
+    /// One round: drop expired pins, check the rest are whole, and pay
+    /// for the time since the last round
+    async fn check(self: &Arc<Self>) -> Result<(), NodeError> {
+        let config = self.config.read().await.storage.clone();
+        let now = Utc::now();
+        let until = now.to_rfc3339();
+        let mut gb_hours = 0.0;
+        let mut since = now;
+        let mut paid = 0;
+
+        for pin in self.pins.active()? {
+            let expired = pin
+                .expires_at
+                .as_deref()
+                .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+                .is_some_and(|at| at <= now);
+            if expired {
+                if let Err(e) = self.release(&pin.cid).await {
+                    log::warn!("Failed to release expired pin {}: {}", pin.cid, e);
+                }
+                continue;
+            }
+            if pin.status == "pinning" {
+                continue;
+            }
+
+            let whole = self.ipfs.has_locally(&pin.cid).await.unwrap_or(false);
+            self.pins.proof(&pin.cid, whole)?;
+            if !whole {
+                log::warn!("Pinned {} is no longer whole, fetching it again", pin.cid);
+                self.pins.set_status(&pin.cid, "pinning", Some("Missing from the repo"))?;
+                tokio::spawn(Arc::clone(self).fetch(pin.cid));
+                continue;
+            }
+            if pin.status != "pinned" {
+                self.pins.pinned(&pin.cid, pin.size_bytes)?;
+                continue;
+            }
+
+            let Some(from) = pin
+                .accrued_until
+                .as_deref()
+                .or(pin.pinned_at.as_deref())
+                .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+                .map(|at| at.with_timezone(&Utc))
+            else {
+                continue;
+            };
+            let hours = (now - from).num_seconds().max(0) as f64 / 3600.0;
+            let earned = pin.size_bytes as f64 / GIB * hours;
+            self.pins.accrue(&pin.cid, &until, earned)?;
+            gb_hours += earned;
+            since = since.min(from);
+            paid += 1;
+        }
+
+        if gb_hours <= 0.0 || config.storage_gb_hour_cents <= 0.0 {
+            return Ok(());
+        }
+        let cents = {
+            let mut carry = self.carry.lock().unwrap();
+            let total = *carry + gb_hours * config.storage_gb_hour_cents;
+            *carry = total.fract();
+            total.trunc() as i64
+        };
+        if cents < 1 {
+            return Ok(());
+        }
+        self.earnings.record(NewEarning {
+            job_id: format!("storage-{}", now.timestamp_millis()),
+            job_kind: "storage".to_string(),
+            amount_cents: cents,
+            currency: config.currency,
+            started_at: since.to_rfc3339(),
+            completed_at: until,
+            payment_ref: None,
+            resources: Some(format!("{:.3} GB-hours over {} pins", gb_hours, paid)),
+        })?;
+        Ok(())
+    }
+}
+
+fn budget_bytes(budget_gb: u64) -> u64 {
+    budget_gb.saturating_mul(1 << 30)
+}
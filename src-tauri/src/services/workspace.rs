@@ -0,0 +1,172 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::agent_tools::workspace_dir;
+use crate::error::NodeError;
+use crate::models::ResourceLimits;
+
+/// A named group of agent executions and files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Model agents use when a request does not name one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_limits: Option<ResourceLimits>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateWorkspaceRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub default_model: Option<String>,
+    #[serde(default)]
+    pub resource_limits: Option<ResourceLimits>,
+}
+
+/// Fields to change; an empty `defaultModel` clears it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateWorkspaceRequest {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub default_model: Option<String>,
+    #[serde(default)]
+    pub resource_limits: Option<ResourceLimits>,
+}
+
+/// Workspaces persisted to `workspaces.json` under the config dir
+pub struct WorkspaceManager {
+    workspaces: RwLock<Vec<Workspace>>,
+    path: PathBuf,
+}
+
+impl WorkspaceManager {
+    pub fn load() -> Self {
+        let path = config_path();
+        let workspaces = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+
+        Self {
+            workspaces: RwLock::new(workspaces),
+            path,
+        }
+    }
+
+    pub async fn list(&self) -> Vec<Workspace> {
+        self.workspaces.read().await.clone()
+    }
+
+    pub async fn get(&self, id: &str) -> Option<Workspace> {
+        self.workspaces.read().await.iter().find(|w| w.id == id).cloned()
+    }
+
+    pub async fn create(&self, req: CreateWorkspaceRequest) -> Result<Workspace, NodeError> {
+        let name = req.name.trim();
+        if name.is_empty() {
+            return Err(NodeError::Invalid("Workspace name is required".to_string()));
+        }
+
+        let now = Utc::now().to_rfc3339();
+        let workspace = Workspace {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            description: req.description.unwrap_or_default(),
+            default_model: req.default_model.filter(|m| !m.is_empty()),
+            resource_limits: req.resource_limits,
+            created_at: now.clone(),
+            updated_at: now,
+        };
+
+        let mut workspaces = self.workspaces.write().await;
+        workspaces.push(workspace.clone());
+        self.persist(&workspaces)?;
+
+        log::info!("Created workspace {} ({})", workspace.name, workspace.id);
+        Ok(workspace)
+    }
+
+    pub async fn update(&self, id: &str, req: UpdateWorkspaceRequest) -> Result<Workspace, NodeError> {
+        let mut workspaces = self.workspaces.write().await;
+        let workspace = workspaces
+            .iter_mut()
+            .find(|w| w.id == id)
+            .ok_or_else(|| NodeError::NotFound("Workspace not found".to_string()))?;
+
+        if let Some(name) = req.name {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(NodeError::Invalid("Workspace name is required".to_string()));
+            }
+            workspace.name = name.to_string();
+        }
+        if let Some(description) = req.description {
+            workspace.description = description;
+        }
+        if let Some(model) = req.default_model {
+            workspace.default_model = Some(model).filter(|m| !m.is_empty());
+        }
+        if let Some(limits) = req.resource_limits {
+            workspace.resource_limits = Some(limits);
+        }
+        workspace.updated_at = Utc::now().to_rfc3339();
+
+        let updated = workspace.clone();
+        self.persist(&workspaces)?;
+        Ok(updated)
+    }
+
+    /// Delete a workspace together with its files
+    pub async fn delete(&self, id: &str) -> Result<(), NodeError> {
+        let mut workspaces = self.workspaces.write().await;
+        let before = workspaces.len();
+        workspaces.retain(|w| w.id != id);
+        if workspaces.len() == before {
+            return Err(NodeError::NotFound("Workspace not found".to_string()));
+        }
+        self.persist(&workspaces)?;
+
+        let dir = workspace_dir(id);
+        if dir.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&dir) {
+                log::warn!("Failed to remove files of workspace {}: {}", id, e);
+            }
+        }
+
+        log::info!("Deleted workspace {}", id);
+        Ok(())
+    }
+
+    fn persist(&self, workspaces: &[Workspace]) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let data = serde_json::to_string_pretty(workspaces).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, data).map_err(|e| format!("Failed to save workspaces: {}", e))
+    }
+}
+
+fn config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("otherthing-node")
+        .join("workspaces.json")
+}
@@ -0,0 +1,218 @@
+//! Tracing and Metrics Export
+//!
+//! Spans cover the stages of a job pipeline: the relayed request from the
+//! orchestrator (`relay.job`), the API handler it reaches (`HTTP <route>`),
+//! agent executions with their model calls and tool invocations, and
+//! container operations. With `telemetry.otlpEndpoint` set, spans and
+//! metrics are exported over OTLP/HTTP to a collector; otherwise the global
+//! tracer and meter are no-ops and nothing is recorded.
+//!
+//! Trace context travels in W3C `traceparent` headers, so a trace the
+//! orchestrator starts continues through the relay into the handler here.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::trace::{FutureExt, SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{global, Context, KeyValue};
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig, WithHttpConfig};
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+
+use crate::config::TelemetryConfig;
+
+/// Instrumentation scope of every span and instrument
+const SCOPE: &str = "otherthing-node";
+
+struct Providers {
+    tracer: TracerProvider,
+    meter: SdkMeterProvider,
+}
+
+fn providers() -> &'static Mutex<Option<Providers>> {
+    static PROVIDERS: OnceLock<Mutex<Option<Providers>>> = OnceLock::new();
+    PROVIDERS.get_or_init(|| Mutex::new(None))
+}
+
+/// Start exporting to the configured collector, if any. Must run inside
+/// the Tokio runtime, which the batch exporters are spawned on.
+pub fn init(config: &TelemetryConfig, node_id: &str) -> Result<(), String> {
+    let Some(endpoint) = config.otlp_endpoint.as_deref().map(|e| e.trim().trim_end_matches('/')) else {
+        return Ok(());
+    };
+    if endpoint.is_empty() {
+        return Ok(());
+    }
+    let headers: HashMap<String, String> = config
+        .headers
+        .iter()
+        .map(|(name, value)| (name.clone(), value.expose().to_string()))
+        .collect();
+    let resource = Resource::new(vec![
+        KeyValue::new("service.name", config.service_name.clone()),
+        KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+        KeyValue::new("service.instance.id", node_id.to_string()),
+    ]);
+
+    let span_exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint))
+        .with_headers(headers.clone())
+        .build()
+        .map_err(|e| format!("Failed to create OTLP span exporter: {}", e))?;
+    let tracer = TracerProvider::builder()
+        .with_batch_exporter(span_exporter, runtime::Tokio)
+        .with_resource(resource.clone())
+        .build();
+
+    let metric_exporter = MetricExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/metrics", endpoint))
+        .with_headers(headers)
+        .build()
+        .map_err(|e| format!("Failed to create OTLP metric exporter: {}", e))?;
+    let reader = PeriodicReader::builder(metric_exporter, runtime::Tokio)
+        .with_interval(Duration::from_secs(config.metrics_interval_secs.max(1)))
+        .build();
+    let meter = SdkMeterProvider::builder()
+        .with_reader(reader)
+        .with_resource(resource)
+        .build();
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(tracer.clone());
+    global::set_meter_provider(meter.clone());
+    *providers().lock().unwrap() = Some(Providers { tracer, meter });
+
+    log::info!("Exporting traces and metrics to {}", endpoint);
+    Ok(())
+}
+
+/// Flush what is still buffered and stop exporting
+pub async fn shutdown() {
+    let Some(providers) = providers().lock().unwrap().take() else {
+        return;
+    };
+    // Shutting down waits on the exporters' tasks, so keep it off the runtime's workers
+    let result = tokio::task::spawn_blocking(move || {
+        if let Err(e) = providers.tracer.shutdown() {
+            log::warn!("Failed to flush traces: {}", e);
+        }
+        if let Err(e) = providers.meter.shutdown() {
+            log::warn!("Failed to flush metrics: {}", e);
+        }
+    });
+    let _ = result.await;
+}
+
+/// Run `future` in a new span, a child of the span it is called from
+pub async fn in_span<F: Future>(
+    name: impl Into<String>,
+    kind: SpanKind,
+    attributes: Vec<KeyValue>,
+    future: F,
+) -> F::Output {
+    in_span_with_parent(name, kind, attributes, Context::current(), future).await
+}
+
+/// Run `future` in a new span under `parent`, e.g. a context extracted from
+/// an incoming request
+pub async fn in_span_with_parent<F: Future>(
+    name: impl Into<String>,
+    kind: SpanKind,
+    attributes: Vec<KeyValue>,
+    parent: Context,
+    future: F,
+) -> F::Output {
+    let tracer = global::tracer(SCOPE);
+    let span = tracer
+        .span_builder(name.into())
+        .with_kind(kind)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, &parent);
+    let cx = parent.with_span(span);
+    let output = future.with_context(cx.clone()).await;
+    cx.span().end();
+    output
+}
+
+/// Add an attribute to the current span
+pub fn set_attribute(attribute: KeyValue) {
+    Context::current().span().set_attribute(attribute);
+}
+
+/// Mark the current span as failed
+pub fn set_error(message: &str) {
+    Context::current().span().set_status(Status::error(crate::services::redact::redact(message)));
+}
+
+/// Record a duration, in seconds, in the named histogram
+pub fn record_duration(name: &'static str, elapsed: Duration, attributes: &[KeyValue]) {
+    global::meter(SCOPE)
+        .f64_histogram(name)
+        .with_unit("s")
+        .build()
+        .record(elapsed.as_secs_f64(), attributes);
+}
+
+/// Add one to the named counter
+pub fn count(name: &'static str, attributes: &[KeyValue]) {
+    global::meter(SCOPE).u64_counter(name).build().add(1, attributes);
+}
+
+/// Add `bytes` to the named byte counter
+pub fn count_bytes(name: &'static str, bytes: u64, attributes: &[KeyValue]) {
+    global::meter(SCOPE).u64_counter(name).with_unit("By").build().add(bytes, attributes);
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Trace context carried by request headers, or the current one without any
+pub fn context_from_headers(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+/// Trace context carried by relayed request headers
+pub fn context_from_map(headers: &HashMap<String, String>) -> Context {
+    let headers: HashMap<String, String> = headers
+        .iter()
+        .map(|(name, value)| (name.to_lowercase(), value.clone()))
+        .collect();
+    global::get_text_map_propagator(|propagator| propagator.extract(&headers))
+}
+
+struct MapInjector(Vec<(String, String)>);
+
+impl Injector for MapInjector {
+    fn set(&mut self, key: &str, value: String) {
+        self.0.push((key.to_string(), value));
+    }
+}
+
+/// Headers that carry the current trace context to the next hop
+pub fn current_context_headers() -> Vec<(String, String)> {
+    let mut injector = MapInjector(Vec::new());
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&Context::current(), &mut injector));
+    injector.0
+}
+
+/// Whether a header carries trace context, and is replaced at each hop
+pub fn is_context_header(name: &str) -> bool {
+    name.eq_ignore_ascii_case("traceparent") || name.eq_ignore_ascii_case("tracestate")
+}
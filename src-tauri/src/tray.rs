@@ -0,0 +1,203 @@
+//! System Tray
+//!
+//! The tray icon shows whether the node is earning, idle or paused, and its
+//! menu pauses and resumes the node, brings the window back, opens the log
+//! folder and quits. Closing the window only hides it, so the node keeps
+//! running in the background until it is quit from here.
+
+use std::time::Duration;
+
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Emitter, Manager, Wry};
+use tauri_plugin_shell::ShellExt;
+
+use crate::commands::AppState;
+use crate::models::NodeActivity;
+
+/// Tray declared in tauri.conf.json
+const TRAY_ID: &str = "main";
+
+/// How often the tray re-reads the node state
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Menu items whose text follows the node state
+#[derive(Clone)]
+struct StatusItems {
+    status: MenuItem<Wry>,
+    toggle: MenuItem<Wry>,
+}
+
+impl StatusItems {
+    fn show(&self, tray: &TrayIcon, activity: NodeActivity) {
+        let label = match activity {
+            NodeActivity::Earning => "Earning",
+            NodeActivity::Idle => "Idle",
+            NodeActivity::Paused => "Paused",
+        };
+        let _ = self.status.set_text(format!("Status: {}", label));
+        let _ = self.toggle.set_text(if activity == NodeActivity::Paused {
+            "Resume node"
+        } else {
+            "Pause node"
+        });
+        let _ = tray.set_tooltip(Some(format!("OtherThing Node - {}", label)));
+    }
+}
+
+/// Tracks whether the node did work since the last look
+#[derive(Default)]
+struct ActivityProbe {
+    tokens: Option<u64>,
+}
+
+impl ActivityProbe {
+    async fn activity(&mut self, state: &AppState) -> NodeActivity {
+        if !*state.node_running.read().await {
+            return NodeActivity::Paused;
+        }
+
+        let tokens: u64 = state
+            .api
+            .state()
+            .token_usage
+            .read()
+            .await
+            .values()
+            .map(|u| u.prompt_tokens + u.completion_tokens)
+            .sum();
+        let served = self.tokens.is_some_and(|seen| seen != tokens);
+        self.tokens = Some(tokens);
+
+        if served || state.agents.running_count().await > 0 {
+            NodeActivity::Earning
+        } else {
+            NodeActivity::Idle
+        }
+    }
+}
+
+/// Bring the main window back from the tray
+pub fn show_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+// Shell::open is deprecated in favour of the opener plugin, which the app does not use
+#[allow(deprecated)]
+fn open_logs(app: &AppHandle) {
+    let dir = match app.path().app_log_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            log::warn!("Failed to resolve log directory: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        log::warn!("Failed to create log directory: {}", e);
+    }
+    if let Err(e) = app.shell().open(dir.to_string_lossy(), None) {
+        log::warn!("Failed to open log directory: {}", e);
+    }
+}
+
+async fn toggle_node(app: AppHandle, items: StatusItems, tray: TrayIcon) {
+    let state = app.state::<AppState>().inner().clone();
+    let activity = if *state.node_running.read().await {
+        state.stop_node().await;
+        log::info!("Node paused from tray");
+        NodeActivity::Paused
+    } else {
+        if let Err(e) = state.start_node().await {
+            log::error!("{}", e);
+            return;
+        }
+        log::info!("Node resumed from tray");
+        NodeActivity::Idle
+    };
+    items.show(&tray, activity);
+    let _ = app.emit("node://activity", activity);
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent, items: &StatusItems, tray: &TrayIcon) {
+    match event.id().as_ref() {
+        "toggle" => {
+            let (app, items, tray) = (app.clone(), items.clone(), tray.clone());
+            tauri::async_runtime::spawn(toggle_node(app, items, tray));
+        }
+        "open" => show_window(app),
+        "logs" => open_logs(app),
+        // Goes through the exit handler, which shuts the node down first
+        "quit" => app.exit(0),
+        _ => {}
+    }
+}
+
+/// Attach the menu to the tray icon and keep its status current
+pub fn init(app: &AppHandle, state: AppState) -> tauri::Result<()> {
+    let status = MenuItem::with_id(app, "status", "Status: Starting", false, None::<&str>)?;
+    let toggle = MenuItem::with_id(app, "toggle", "Pause node", true, None::<&str>)?;
+    let open = MenuItem::with_id(app, "open", "Open OtherThing Node", true, None::<&str>)?;
+    let logs = MenuItem::with_id(app, "logs", "Open logs", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(
+        app,
+        &[
+            &status,
+            &PredefinedMenuItem::separator(app)?,
+            &toggle,
+            &open,
+            &logs,
+            &PredefinedMenuItem::separator(app)?,
+            &quit,
+        ],
+    )?;
+
+    let tray = match app.tray_by_id(TRAY_ID) {
+        Some(tray) => tray,
+        None => {
+            let mut builder = TrayIconBuilder::with_id(TRAY_ID);
+            if let Some(icon) = app.default_window_icon() {
+                builder = builder.icon(icon.clone());
+            }
+            builder.build(app)?
+        }
+    };
+    tray.set_menu(Some(menu))?;
+
+    let items = StatusItems { status, toggle };
+    {
+        let (items, tray_handle) = (items.clone(), tray.clone());
+        tray.on_menu_event(move |app, event| on_menu_event(app, event, &items, &tray_handle));
+    }
+    tray.on_tray_icon_event(|tray, event| {
+        if let TrayIconEvent::Click {
+            button: MouseButton::Left,
+            button_state: MouseButtonState::Up,
+            ..
+        } = event
+        {
+            show_window(tray.app_handle());
+        }
+    });
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut probe = ActivityProbe::default();
+        let mut shown = None;
+        loop {
+            let activity = probe.activity(&state).await;
+            if shown != Some(activity) {
+                items.show(&tray, activity);
+                let _ = handle.emit("node://activity", activity);
+                shown = Some(activity);
+            }
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+        }
+    });
+
+    Ok(())
+}
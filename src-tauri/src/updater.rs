@@ -0,0 +1,149 @@
+//! App Updates
+//!
+//! Checks the release manifest of the configured channel and installs
+//! signed updates. Stable follows the latest GitHub release; beta follows
+//! the manifest the release workflow republishes under the rolling `beta`
+//! tag for every release, pre-releases included. Before an update is
+//! applied the node stops taking work, running agents get time to finish,
+//! and everything is shut down as on quit.
+
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Url};
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+use crate::commands::AppState;
+use crate::config::ReleaseChannel;
+
+const STABLE_MANIFEST: &str = "https://github.com/server9-dev/otherthing-node/releases/latest/download/latest.json";
+const BETA_MANIFEST: &str = "https://github.com/server9-dev/otherthing-node/releases/download/beta/latest.json";
+
+/// How often the background check runs
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Time running agents get to finish before an update cancels them
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// An update offered by the channel's manifest
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub channel: ReleaseChannel,
+    pub notes: Option<String>,
+    pub date: Option<String>,
+}
+
+/// Download progress, emitted as `update://progress`
+#[derive(Debug, Clone, Serialize)]
+struct DownloadProgress {
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+async fn channel(state: &AppState) -> ReleaseChannel {
+    state.api.state().config.read().await.update.channel
+}
+
+async fn find_update(app: &AppHandle, channel: ReleaseChannel) -> Result<Option<Update>, String> {
+    let manifest = match channel {
+        ReleaseChannel::Stable => STABLE_MANIFEST,
+        ReleaseChannel::Beta => BETA_MANIFEST,
+    };
+    let endpoint = Url::parse(manifest).map_err(|e| e.to_string())?;
+    app.updater_builder()
+        .endpoints(vec![endpoint])
+        .and_then(|builder| builder.build())
+        .map_err(|e| format!("Failed to set up updater: {}", e))?
+        .check()
+        .await
+        .map_err(|e| format!("Failed to check for updates: {}", e))
+}
+
+/// The newer release on the configured channel, if any
+pub async fn check(app: &AppHandle, state: &AppState) -> Result<Option<UpdateInfo>, String> {
+    let channel = channel(state).await;
+    let update = find_update(app, channel).await?;
+    Ok(update.map(|u| UpdateInfo {
+        version: u.version.clone(),
+        current_version: u.current_version.clone(),
+        channel,
+        notes: u.body.clone(),
+        date: u.date.map(|d| d.to_string()),
+    }))
+}
+
+/// Stop taking work and give running agents time to finish
+async fn drain(state: &AppState) {
+    state.stop_node().await;
+    let deadline = tokio::time::Instant::now() + DRAIN_TIMEOUT;
+    loop {
+        let running = state.agents.running_count().await;
+        if running == 0 {
+            return;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            log::warn!("{} agent executions still running; cancelling them for the update", running);
+            return;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Download and verify the update, drain the node, install and restart.
+/// Does not return once the update is applied.
+pub async fn install(app: &AppHandle, state: &AppState) -> Result<(), String> {
+    let Some(update) = find_update(app, channel(state).await).await? else {
+        return Err("No update available".to_string());
+    };
+    log::info!("Downloading update {}", update.version);
+
+    // Download first so a failed download leaves the node running
+    let mut downloaded = 0u64;
+    let handle = app.clone();
+    let bytes = update
+        .download(
+            move |chunk, total| {
+                downloaded += chunk as u64;
+                let _ = handle.emit("update://progress", DownloadProgress { downloaded, total });
+            },
+            || {},
+        )
+        .await
+        .map_err(|e| format!("Failed to download update: {}", e))?;
+
+    log::info!("Draining the node before updating to {}", update.version);
+    drain(state).await;
+    if !crate::shutdown_once(state.clone()).await {
+        return Err("The app is already shutting down".to_string());
+    }
+
+    update
+        .install(bytes)
+        .map_err(|e| format!("Failed to install update: {}", e))?;
+    log::info!("Update {} installed, restarting", update.version);
+    app.restart();
+}
+
+/// Check periodically while auto-check is on and tell the frontend about
+/// new releases as `update://available`
+pub async fn watch(app: AppHandle) {
+    let state = app.state::<AppState>().inner().clone();
+    let mut announced: Option<String> = None;
+    loop {
+        if state.api.state().config.read().await.update.auto_check {
+            match check(&app, &state).await {
+                Ok(Some(info)) if announced.as_deref() != Some(info.version.as_str()) => {
+                    log::info!("Update available: {} ({:?})", info.version, info.channel);
+                    announced = Some(info.version.clone());
+                    let _ = app.emit("update://available", info);
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("{}", e),
+            }
+        }
+        tokio::time::sleep(CHECK_INTERVAL).await;
+    }
+}
@@ -5,8 +5,22 @@ import { useWeb3 } from '../context/Web3Context';
 
 const API_BASE = 'http://localhost:8080/api/v1/gpu';
 
+// Built-in providers plus any custom ones from the node config
+interface GPUProviderInfo {
+  id: string;
+  name: string;
+  apiKeyUrl?: string;
+  custom: boolean;
+  configured: boolean;
+}
+
+const DEFAULT_PROVIDERS: GPUProviderInfo[] = [
+  { id: 'vast', name: 'Vast.ai', apiKeyUrl: 'https://cloud.vast.ai/cli/', custom: false, configured: false },
+];
+
+// Offers and instances as normalized by the node, whatever the provider
 interface GPUOffer {
-  id: number;
+  id: string;
   gpuName: string;
   gpuCount: number;
   gpuMemoryMb: number;
@@ -17,24 +31,40 @@ interface GPUOffer {
   reliability: number;
   verified: boolean;
   dlperf: number;
+  tflops?: number | null;
+  pricePerTflop?: number | null;
+  cudaVersion?: number | null;
 }
 
 interface GPUInstance {
-  id: number;
+  id: string;
   status: string;
   gpuName: string;
   gpuCount: number;
   pricePerHour: number;
   totalCost: number;
-  startedAt: Date;
+  startedAt: number | null;
   sshHost?: string;
   sshPort?: number;
   publicIp?: string;
-  ollamaPort?: number;
+  ollamaUrl?: string;
+}
+
+// A rented GPU attached to agents as an Ollama backend
+interface RemoteCompute {
+  id: string;
+  provider: string;
+  instanceId?: string;
+  model: string;
+  status: 'renting' | 'starting' | 'waiting_for_ollama' | 'pulling_model' | 'ready' | 'destroyed' | 'failed';
+  gpuName?: string;
+  pricePerHour?: number;
+  idleTimeoutMinutes: number;
+  error?: string;
 }
 
 interface TunnelInfo {
-  instanceId: number;
+  instanceId: string;
   localPort: number;
   status: 'connecting' | 'connected' | 'disconnected' | 'error';
 }
@@ -43,51 +73,25 @@ interface CloudGPUPanelProps {
   onEndpointChange?: (endpoint: string | null) => void;
 }
 
-// Raw Vast API types
-interface RawVastOffer {
-  id: number;
-  machine_id: number;
-  gpu_name: string;
-  num_gpus: number;
-  gpu_ram: number;
-  cpu_cores_effective: number;
-  cpu_ram: number;
-  dph_total: number;
-  geolocation?: string;
-  reliability?: number;
-  verified?: boolean;
-  dlperf?: number;
-}
-
-interface RawVastInstance {
-  id: number;
-  actual_status: string;
-  gpu_name: string;
-  num_gpus: number;
-  dph_total: number;
-  total_cost?: number;
-  start_date: number;
-  ssh_host?: string;
-  ssh_port?: number;
-  public_ipaddr?: string;
-  direct_port_start?: number;
-}
-
 export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
   const { connected, address, connectWallet, isConnecting } = useWeb3();
 
+  const [providers, setProviders] = useState<GPUProviderInfo[]>(DEFAULT_PROVIDERS);
+  const [provider, setProvider] = useState('vast');
+  // Only the settings form holds a key; saved keys stay on the node
   const [apiKey, setApiKey] = useState('');
-  const [isConfigured, setIsConfigured] = useState(false);
   const [showSettings, setShowSettings] = useState(false);
 
   const [offers, setOffers] = useState<GPUOffer[]>([]);
   const [instances, setInstances] = useState<GPUInstance[]>([]);
-  const [tunnels, setTunnels] = useState<Map<number, TunnelInfo>>(new Map());
+  const [tunnels, setTunnels] = useState<Map<string, TunnelInfo>>(new Map());
   const [balance, setBalance] = useState<number | null>(null);
+  const [computes, setComputes] = useState<RemoteCompute[]>([]);
+  const [computeModel, setComputeModel] = useState('llama3.1:8b');
 
   const [loading, setLoading] = useState(false);
-  const [renting, setRenting] = useState<number | null>(null);
-  const [connecting, setConnecting] = useState<number | null>(null);
+  const [renting, setRenting] = useState<string | null>(null);
+  const [connecting, setConnecting] = useState<string | null>(null);
   const [error, setError] = useState<string | null>(null);
 
   const [filters, setFilters] = useState({
@@ -95,36 +99,75 @@ export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
     gpuType: 'any' as string,
   });
 
-  // Load API key tied to wallet address
+  const loadProviders = useCallback(async () => {
+    try {
+      const res = await fetch(`${API_BASE}/providers`);
+      const data: { providers: GPUProviderInfo[] } = await res.json();
+      setProviders(data.providers);
+    } catch {
+      setProviders(DEFAULT_PROVIDERS);
+    }
+  }, []);
+
+  useEffect(() => {
+    loadProviders();
+  }, [loadProviders]);
+
+  const saveCredentials = useCallback(async (providerId: string, key: string) => {
+    const response = await fetch(`${API_BASE}/credentials`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ provider: providerId, apiKey: key }),
+    });
+    if (!response.ok) {
+      const err = await response.json().catch(() => ({}));
+      throw new Error(err.error || `Failed to save API key (${response.status})`);
+    }
+  }, []);
+
+  // Load the provider tied to wallet address
   useEffect(() => {
     if (address) {
-      const saved = localStorage.getItem(`cloudgpu_api_key_${address}`);
-      if (saved) {
-        setApiKey(saved);
-        setIsConfigured(true);
-      } else {
-        setApiKey('');
-        setIsConfigured(false);
-      }
+      setProvider(localStorage.getItem(`cloudgpu_provider_${address}`) || 'vast');
     }
   }, [address]);
 
-  // Backend proxy API call (bypasses CORS)
+  const providerInfo = providers.find(p => p.id === provider);
+  const isConfigured = providerInfo?.configured ?? false;
+
+  // Keys used to be kept in localStorage; hand any left there to the node
+  useEffect(() => {
+    if (!address) return;
+    const legacyKey = `cloudgpu_api_key_${address}`;
+    const legacy = localStorage.getItem(legacyKey);
+    if (legacy) {
+      saveCredentials('vast', legacy)
+        .then(() => {
+          localStorage.removeItem(legacyKey);
+          return loadProviders();
+        })
+        .catch(err => console.error('Failed to migrate API key:', err));
+    }
+  }, [address, saveCredentials, loadProviders]);
+
+  // Backend proxy API call (bypasses CORS); the node adds the API key
   const gpuApi = useCallback(async <T,>(endpoint: string, params: Record<string, string> = {}): Promise<T> => {
-    const searchParams = new URLSearchParams({ api_key: apiKey, ...params });
+    const searchParams = new URLSearchParams({ provider, ...params });
     const response = await fetch(`${API_BASE}${endpoint}?${searchParams}`);
     if (!response.ok) {
       const errorText = await response.text();
       throw new Error(`API error (${response.status}): ${errorText}`);
     }
     return response.json() as Promise<T>;
-  }, [apiKey]);
+  }, [provider]);
 
   const handleSaveApiKey = async () => {
     if (!address) return;
     try {
-      localStorage.setItem(`cloudgpu_api_key_${address}`, apiKey);
-      setIsConfigured(true);
+      await saveCredentials(provider, apiKey);
+      localStorage.setItem(`cloudgpu_provider_${address}`, provider);
+      setApiKey('');
+      await loadProviders();
       setShowSettings(false);
       setError(null);
     } catch (err) {
@@ -133,11 +176,12 @@ export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
   };
 
   const refreshOffers = useCallback(async () => {
-    if (!isConfigured || !apiKey) return;
+    if (!isConfigured) return;
     setLoading(true);
     setError(null);
     try {
-      const params: Record<string, string> = {};
+      // The node ranks by price per TFLOP and keeps the best offer per GPU model
+      const params: Record<string, string> = { distinct: 'true', limit: '20' };
       if (filters.maxPrice < 10) {
         params.max_price = filters.maxPrice.toString();
       }
@@ -145,94 +189,94 @@ export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
         params.gpu_type = filters.gpuType;
       }
 
-      const result = await gpuApi<{ offers: RawVastOffer[] }>('/offers', params);
-
-      const allOffers: GPUOffer[] = (result.offers || []).map(o => ({
-        id: o.id,
-        gpuName: o.gpu_name,
-        gpuCount: o.num_gpus,
-        gpuMemoryMb: o.gpu_ram,
-        cpuCores: o.cpu_cores_effective,
-        ramMb: o.cpu_ram,
-        pricePerHour: o.dph_total,
-        location: o.geolocation || 'Unknown',
-        reliability: o.reliability || 0,
-        verified: o.verified || false,
-        dlperf: o.dlperf || 0,
-      }));
-
-      // Group by GPU type and take best from each, then fill with top overall
-      const gpuGroups = new Map<string, GPUOffer[]>();
-      allOffers.forEach(o => {
-        const group = gpuGroups.get(o.gpuName) || [];
-        group.push(o);
-        gpuGroups.set(o.gpuName, group);
-      });
-
-      // Get best (cheapest) from each GPU type
-      const diverse: GPUOffer[] = [];
-      gpuGroups.forEach(group => {
-        group.sort((a, b) => a.pricePerHour - b.pricePerHour);
-        if (group[0]) diverse.push(group[0]);
-      });
-
-      // Sort by dlperf descending to show most powerful first
-      diverse.sort((a, b) => b.dlperf - a.dlperf);
-
-      setOffers(diverse.slice(0, 20));
+      const result = await gpuApi<{ offers: GPUOffer[] }>('/offers', params);
+      setOffers(result.offers || []);
     } catch (err) {
       setError(err instanceof Error ? err.message : 'Failed to fetch');
     } finally {
       setLoading(false);
     }
-  }, [isConfigured, apiKey, filters, gpuApi]);
+  }, [isConfigured, filters, gpuApi]);
 
   const refreshInstances = useCallback(async () => {
-    if (!isConfigured || !apiKey) return;
+    if (!isConfigured) return;
     try {
       const [instancesRes, userRes] = await Promise.all([
-        gpuApi<{ instances: RawVastInstance[] }>('/instances'),
-        gpuApi<{ credit: number }>('/user'),
+        gpuApi<{ instances: GPUInstance[] }>('/instances'),
+        gpuApi<{ credit: number | null }>('/user'),
       ]);
 
-      const mappedInstances: GPUInstance[] = (instancesRes.instances || []).map(i => ({
-        id: i.id,
-        status: i.actual_status,
-        gpuName: i.gpu_name,
-        gpuCount: i.num_gpus,
-        pricePerHour: i.dph_total,
-        totalCost: i.total_cost || 0,
-        startedAt: new Date(i.start_date * 1000),
-        sshHost: i.ssh_host,
-        sshPort: i.ssh_port,
-        publicIp: i.public_ipaddr,
-        ollamaPort: i.direct_port_start ? i.direct_port_start + 11434 - 1 : undefined,
-      }));
-
-      setInstances(mappedInstances);
+      setInstances(instancesRes.instances || []);
       setBalance(userRes.credit);
     } catch (err) {
       console.error('Failed to fetch instances:', err);
     }
-  }, [isConfigured, apiKey, gpuApi]);
+  }, [isConfigured, gpuApi]);
+
+  const refreshComputes = useCallback(async () => {
+    try {
+      const res = await fetch(`${API_BASE}/compute`);
+      const data: { computes: RemoteCompute[] } = await res.json();
+      // Keep the ones still in use plus the latest failure for context
+      setComputes(data.computes.filter((c, i) => c.status !== 'destroyed' && (c.status !== 'failed' || i === 0)));
+    } catch (err) {
+      console.error('Failed to load remote compute:', err);
+    }
+  }, []);
 
   useEffect(() => {
-    if (isConfigured && apiKey) {
+    if (isConfigured) {
       refreshOffers();
       refreshInstances();
-      const interval = setInterval(refreshInstances, 30000);
+      refreshComputes();
+      const interval = setInterval(() => { refreshInstances(); refreshComputes(); }, 30000);
       return () => clearInterval(interval);
     }
-  }, [isConfigured, apiKey, refreshOffers, refreshInstances]);
+  }, [isConfigured, refreshOffers, refreshInstances, refreshComputes]);
 
-  const handleRent = async (offerId: number) => {
+  // Rent, wait for Ollama, pull the model and offer the machine to agents
+  const handleAttach = async (offerId: string) => {
     setRenting(offerId);
     setError(null);
     try {
-      const response = await fetch(`http://localhost:8080/api/v1/gpu/rent/${offerId}`, {
+      const response = await fetch(`${API_BASE}/compute`, {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify({ api_key: apiKey }),
+        body: JSON.stringify({ provider, offerId, model: computeModel }),
+      });
+      if (!response.ok) {
+        const err = await response.json().catch(() => ({}));
+        throw new Error(err.error || `Failed to attach (${response.status})`);
+      }
+      await refreshComputes();
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Failed to attach');
+    } finally {
+      setRenting(null);
+    }
+  };
+
+  const handleDetach = async (computeId: string) => {
+    if (!confirm('Detach this GPU from agents and stop it?')) return;
+    try {
+      const response = await fetch(`${API_BASE}/compute/${encodeURIComponent(computeId)}`, { method: 'DELETE' });
+      if (!response.ok) {
+        throw new Error(await response.text());
+      }
+      await Promise.all([refreshComputes(), refreshInstances()]);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Failed to detach');
+    }
+  };
+
+  const handleRent = async (offerId: string) => {
+    setRenting(offerId);
+    setError(null);
+    try {
+      const response = await fetch(`${API_BASE}/rent/${encodeURIComponent(offerId)}`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ provider }),
       });
       if (!response.ok) {
         const err = await response.text();
@@ -249,7 +293,7 @@ export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
     }
   };
 
-  const handleConnect = async (instanceId: number) => {
+  const handleConnect = async (instanceId: string) => {
     setConnecting(instanceId);
     try {
       // For now just show instructions - SSH tunnel needs backend support
@@ -259,16 +303,17 @@ export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
     }
   };
 
-  const handleDisconnect = async (instanceId: number) => {
+  const handleDisconnect = async (instanceId: string) => {
     setTunnels(prev => { const n = new Map(prev); n.delete(instanceId); return n; });
     onEndpointChange?.(null);
   };
 
-  const handleTerminate = async (instanceId: number) => {
+  const handleTerminate = async (instanceId: string) => {
     if (!confirm('Stop this GPU? You will be charged for time used.')) return;
     try {
+      const params = new URLSearchParams({ provider });
       const response = await fetch(
-        `http://localhost:8080/api/v1/gpu/destroy/${instanceId}?api_key=${apiKey}`,
+        `${API_BASE}/destroy/${encodeURIComponent(instanceId)}?${params}`,
         { method: 'DELETE' }
       );
       if (!response.ok) {
@@ -283,8 +328,9 @@ export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
 
   const formatVram = (mb: number) => `${Math.round(mb / 1024)}GB`;
   const formatPrice = (p: number) => `$${p.toFixed(2)}/hr`;
-  const formatRuntime = (d: Date) => {
-    const ms = Date.now() - new Date(d).getTime();
+  const formatRuntime = (startedAt: number | null) => {
+    if (!startedAt) return '-';
+    const ms = Date.now() - startedAt * 1000;
     return `${Math.floor(ms / 3600000)}h ${Math.floor((ms % 3600000) / 60000)}m`;
   };
 
@@ -319,6 +365,14 @@ export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
           <div className="wallet-info">
             <Wallet size={14} /> Connected: {address?.slice(0, 6)}...{address?.slice(-4)}
           </div>
+          <div className="input-group">
+            <label>Provider</label>
+            <select value={provider} onChange={(e) => setProvider(e.target.value)}>
+              {providers.map(p => (
+                <option key={p.id} value={p.id}>{p.name}</option>
+              ))}
+            </select>
+          </div>
           <div className="input-group">
             <label>API Key</label>
             <input
@@ -327,9 +381,11 @@ export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
               onChange={(e) => setApiKey(e.target.value)}
               placeholder="Enter your cloud GPU API key"
             />
-            <a href="https://cloud.vast.ai/cli/" target="_blank" rel="noopener" className="api-link">
-              Get API Key →
-            </a>
+            {providerInfo?.apiKeyUrl && (
+              <a href={providerInfo.apiKeyUrl} target="_blank" rel="noopener" className="api-link">
+                Get API Key →
+              </a>
+            )}
           </div>
           {error && <div className="error-msg"><AlertTriangle size={14} /> {error}</div>}
           <div className="button-row">
@@ -361,6 +417,34 @@ export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
 
       {error && <div className="error-banner"><AlertTriangle size={14} /> {error}</div>}
 
+      {computes.length > 0 && (
+        <div className="section">
+          <h3>Agent Compute</h3>
+          {computes.map(c => (
+            <div key={c.id} className={`instance-card ${c.status === 'ready' ? 'running' : c.status}`}>
+              <div className="inst-row">
+                <span className="gpu-label"><Cpu size={16} /> {c.gpuName || c.provider} · {c.model}</span>
+                <span className={`status ${c.status === 'ready' ? 'running' : c.status}`}>{c.status.replace(/_/g, ' ')}</span>
+                <span className="meta">
+                  {c.idleTimeoutMinutes > 0 ? `stops after ${c.idleTimeoutMinutes}m idle` : 'no idle stop'}
+                </span>
+              </div>
+              {c.error && <div className="ssh-hint">{c.error}</div>}
+              {c.status === 'ready' && (
+                <div className="tunnel-active">
+                  <Link size={12} /> Agents can use provider <code>{c.id}</code>
+                </div>
+              )}
+              <div className="inst-actions">
+                <CyberButton size="small" variant="danger" onClick={() => handleDetach(c.id)}>
+                  <Unlink size={14} /> Detach
+                </CyberButton>
+              </div>
+            </div>
+          ))}
+        </div>
+      )}
+
       {instances.length > 0 && (
         <div className="section">
           <h3>Active GPUs</h3>
@@ -400,6 +484,11 @@ export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
                     <div className="ssh-hint">Click to copy. Then use Ollama at localhost:11434</div>
                   </div>
                 )}
+                {inst.ollamaUrl && (
+                  <div className="tunnel-active">
+                    <Link size={12} /> Ollama at <code>{inst.ollamaUrl}</code>
+                  </div>
+                )}
                 {tunnel?.status === 'connected' && (
                   <div className="tunnel-active">
                     <Link size={12} /> Ollama at <code>localhost:{tunnel.localPort}</code>
@@ -428,6 +517,12 @@ export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
               <option value="5.0">Under $5/hr</option>
               <option value="50">Any price</option>
             </select>
+            <input
+              value={computeModel}
+              onChange={e => setComputeModel(e.target.value)}
+              placeholder="Model for agents"
+              title="Model pulled when attaching a GPU to agents"
+            />
           </div>
         </div>
 
@@ -443,12 +538,21 @@ export function CloudGPUPanel({ onEndpointChange }: CloudGPUPanelProps) {
                   <span className="vram">{formatVram(o.gpuMemoryMb)}</span>
                   {o.verified && <span className="verified">✓</span>}
                 </div>
-                <span className="offer-dlperf" title="Deep Learning Performance">{o.dlperf.toFixed(1)}</span>
+                <span className="offer-dlperf" title={o.tflops ? `${o.tflops.toFixed(1)} TFLOPS` : 'Unknown performance'}>
+                  {o.pricePerTflop ? `$${o.pricePerTflop.toFixed(3)}/TF` : '—'}
+                </span>
                 <span className="offer-loc">{o.location}</span>
                 <span className="offer-price">{formatPrice(o.pricePerHour)}</span>
                 <CyberButton size="small" onClick={() => handleRent(o.id)} disabled={renting !== null}>
                   {renting === o.id ? '...' : 'Rent'}
                 </CyberButton>
+                <CyberButton
+                  size="small"
+                  onClick={() => handleAttach(o.id)}
+                  disabled={renting !== null || !computeModel.trim()}
+                >
+                  Use for agents
+                </CyberButton>
               </div>
             ))}
           </div>
@@ -483,7 +587,7 @@ const styles = `
 .section h3 { margin: 0 0 12px; font-size: 14px; color: rgba(255,255,255,0.8); }
 .section-head { display: flex; justify-content: space-between; align-items: center; margin-bottom: 12px; }
 .filters { display: flex; gap: 8px; }
-.filters select {
+.filters select, .filters input {
   background: rgba(0,0,0,0.4); border: 1px solid rgba(255,255,255,0.2);
   border-radius: 4px; color: white; padding: 4px 8px; font-size: 12px;
 }
@@ -496,7 +600,7 @@ const styles = `
 }
 .input-group { display: flex; flex-direction: column; gap: 8px; margin-bottom: 16px; }
 .input-group label { font-size: 12px; color: rgba(255,255,255,0.6); }
-.input-group input {
+.input-group input, .input-group select {
   background: rgba(0,0,0,0.4); border: 1px solid rgba(255,255,255,0.2);
   border-radius: 4px; color: white; padding: 10px 12px; font-size: 14px;
 }
@@ -31,8 +31,10 @@ interface ContainersPanelProps {
 
 const STATUS_COLORS: Record<ContainerStatus, { bg: string; border: string; text: string }> = {
   running: { bg: 'rgba(0, 212, 255, 0.1)', border: 'var(--primary)', text: 'var(--primary)' },
+  creating: { bg: 'rgba(155, 89, 182, 0.1)', border: 'var(--secondary)', text: 'var(--secondary)' },
   created: { bg: 'rgba(155, 89, 182, 0.1)', border: 'var(--secondary)', text: 'var(--secondary)' },
   paused: { bg: 'rgba(255, 193, 7, 0.1)', border: 'var(--warning)', text: 'var(--warning)' },
+  stopped: { bg: 'rgba(108, 117, 125, 0.1)', border: 'var(--text-muted)', text: 'var(--text-muted)' },
   exited: { bg: 'rgba(108, 117, 125, 0.1)', border: 'var(--text-muted)', text: 'var(--text-muted)' },
   dead: { bg: 'rgba(255, 0, 128, 0.1)', border: 'var(--accent)', text: 'var(--accent)' },
   restarting: { bg: 'rgba(0, 212, 255, 0.1)', border: 'var(--primary)', text: 'var(--primary)' },
@@ -50,9 +50,28 @@ interface DriveInfo {
   used_percent: number;
 }
 
+// Stable error categories reported by the node alongside the message
+export type ErrorCode =
+  | 'not_installed'
+  | 'not_running'
+  | 'timeout'
+  | 'conflict'
+  | 'policy'
+  | 'upstream'
+  | 'not_found'
+  | 'invalid'
+  | 'internal';
+
+export class ApiError extends Error {
+  constructor(message: string, public code?: ErrorCode, public status?: number) {
+    super(message);
+  }
+}
+
 interface CommandResult {
   success: boolean;
   error?: string;
+  code?: ErrorCode;
 }
 
 interface NodeStatus {
@@ -82,12 +101,55 @@ async function fetchApi<T>(endpoint: string, options?: RequestInit): Promise<T>
     ...options,
   });
   if (!response.ok) {
-    const error = await response.text();
-    throw new Error(error || `HTTP ${response.status}`);
+    const text = await response.text();
+    try {
+      const body = JSON.parse(text);
+      const message = typeof body.error === 'string' ? body.error : body.error?.message;
+      throw new ApiError(message || text, body.code, response.status);
+    } catch (err) {
+      if (err instanceof ApiError) throw err;
+      throw new ApiError(text || `HTTP ${response.status}`, undefined, response.status);
+    }
   }
   return response.json();
 }
 
+// Node event stream (jobs, agent progress, container and service changes)
+const EVENTS_WS = 'ws://localhost:8080/ws/events';
+
+type NodeEventListener = (event: any) => void;
+const eventListeners = new Set<NodeEventListener>();
+let eventSocket: WebSocket | null = null;
+
+function connectEvents() {
+  if (eventSocket) return;
+  const socket = new WebSocket(EVENTS_WS);
+  eventSocket = socket;
+  socket.onmessage = (message) => {
+    try {
+      const event = JSON.parse(message.data);
+      eventListeners.forEach((listener) => listener(event));
+    } catch {}
+  };
+  socket.onclose = () => {
+    eventSocket = null;
+    // The API server may not be up yet; try again shortly
+    setTimeout(connectEvents, 5000);
+  };
+}
+
+/** Call `listener` with every node event; returns a function that unsubscribes */
+function onNodeEvent(listener: NodeEventListener): () => void {
+  eventListeners.add(listener);
+  connectEvents();
+  return () => {
+    eventListeners.delete(listener);
+  };
+}
+
+// Status is refreshed on events; this slow poll only catches missed ones
+const FALLBACK_POLL_MS = 30000;
+
 // The unified API
 export const api = {
   // Platform detection
@@ -217,7 +279,7 @@ export const api = {
       try {
         return await fetchApi('/ollama/start', { method: 'POST' });
       } catch (err: any) {
-        return { success: false, error: err.message };
+        return { success: false, error: err.message, code: err.code };
       }
     }
     return (window as any).electronAPI.startOllama();
@@ -228,7 +290,7 @@ export const api = {
       try {
         return await fetchApi('/ollama/stop', { method: 'POST' });
       } catch (err: any) {
-        return { success: false, error: err.message };
+        return { success: false, error: err.message, code: err.code };
       }
     }
     return (window as any).electronAPI.stopOllama();
@@ -253,7 +315,7 @@ export const api = {
           body: JSON.stringify({ model: modelName }),
         });
       } catch (err: any) {
-        return { success: false, error: err.message };
+        return { success: false, error: err.message, code: err.code };
       }
     }
     return (window as any).electronAPI.pullOllamaModel(modelName);
@@ -266,7 +328,7 @@ export const api = {
           method: 'DELETE',
         });
       } catch (err: any) {
-        return { success: false, error: err.message };
+        return { success: false, error: err.message, code: err.code };
       }
     }
     return (window as any).electronAPI.deleteOllamaModel(modelName);
@@ -345,7 +407,7 @@ export const api = {
       try {
         return await fetchApi('/ipfs/start', { method: 'POST' });
       } catch (err: any) {
-        return { success: false, error: err.message };
+        return { success: false, error: err.message, code: err.code };
       }
     }
     return (window as any).electronAPI.startIPFS();
@@ -356,7 +418,7 @@ export const api = {
       try {
         return await fetchApi('/ipfs/stop', { method: 'POST' });
       } catch (err: any) {
-        return { success: false, error: err.message };
+        return { success: false, error: err.message, code: err.code };
       }
     }
     return (window as any).electronAPI.stopIPFS();
@@ -520,11 +582,12 @@ export const api = {
   },
 
   // ============ Event Subscriptions ============
-  // For Tauri, we'll poll the API instead of using events
+  // For Tauri, status is re-fetched when the node's event stream reports a change
+
+  onNodeEvent,
 
   onNodeStatus(callback: (status: any) => void) {
     if (useRestApi) {
-      // Poll every 5 seconds
       const poll = async () => {
         try {
           const status = await api.getNodeStatus();
@@ -532,7 +595,12 @@ export const api = {
         } catch {}
       };
       poll();
-      setInterval(poll, 5000);
+      onNodeEvent((event) => {
+        if (event.type === 'service_status' && (event.service === 'node' || event.service === 'relay')) {
+          poll();
+        }
+      });
+      setInterval(poll, FALLBACK_POLL_MS);
     } else {
       (window as any).electronAPI.onNodeStatus(callback);
     }
@@ -547,7 +615,12 @@ export const api = {
         } catch {}
       };
       poll();
-      setInterval(poll, 5000);
+      onNodeEvent((event) => {
+        if (event.type === 'service_status' && event.service === 'ollama') {
+          poll();
+        }
+      });
+      setInterval(poll, FALLBACK_POLL_MS);
     } else {
       (window as any).electronAPI.onOllamaStatusChange(callback);
     }
@@ -6,8 +6,26 @@ interface Stats {
   completedJobs: number;
   totalCompute: number;
   earnings: number;
+  currency: string;
+  uptimeSecs: number;
 }
 
+// Shape of GET /api/v1/stats
+interface NodeStatsResponse {
+  activeJobs: number;
+  completedJobs: number;
+  uptimeSecs: number;
+  earnings: {
+    total: { currency: string; amountCents: number; jobs: number }[];
+  };
+}
+
+const formatUptime = (secs: number) => {
+  if (!secs) return '--:--:--';
+  const pad = (n: number) => String(n).padStart(2, '0');
+  return `${pad(Math.floor(secs / 3600))}:${pad(Math.floor((secs % 3600) / 60))}:${pad(secs % 60)}`;
+};
+
 interface ServiceStatus {
   ipfs: { running: boolean; peerId: string | null };
   ollama: { running: boolean; models: number };
@@ -19,6 +37,8 @@ export function Dashboard() {
     completedJobs: 0,
     totalCompute: 0,
     earnings: 0,
+    currency: 'USD',
+    uptimeSecs: 0,
   });
   const [nodeStatus, setNodeStatus] = useState<'offline' | 'idle' | 'working'>('offline');
   const [services, setServices] = useState<ServiceStatus>({
@@ -56,11 +76,33 @@ export function Dashboard() {
       }
     };
 
+    // Earnings come from the node's ledger; the largest currency is shown
+    const loadStats = async () => {
+      try {
+        const res = await fetch('http://localhost:8080/api/v1/stats');
+        if (!res.ok) return;
+        const data: NodeStatsResponse = await res.json();
+        const top = [...data.earnings.total].sort((a, b) => b.amountCents - a.amountCents)[0];
+        setStats(prev => ({
+          ...prev,
+          activeJobs: data.activeJobs,
+          completedJobs: data.completedJobs,
+          earnings: top ? top.amountCents / 100 : 0,
+          currency: top?.currency ?? prev.currency,
+          uptimeSecs: data.uptimeSecs,
+        }));
+      } catch {
+        // Node offline; the status banner says so
+      }
+    };
+
     checkStatus();
     checkServices();
+    loadStats();
     const interval = setInterval(() => {
       checkStatus();
       checkServices();
+      loadStats();
     }, 5000);
     return () => clearInterval(interval);
   }, [checkServices]);
@@ -156,7 +198,7 @@ export function Dashboard() {
         <div className="stat-card hover-lift">
           <div style={{ display: 'flex', alignItems: 'center', gap: 'var(--gap-sm)', marginBottom: 'var(--gap-sm)' }}>
             <ArrowUp size={20} style={{ color: 'var(--info)' }} />
-            <span className="stat-label">Earnings (OTT)</span>
+            <span className="stat-label">Earnings ({stats.currency})</span>
           </div>
           <div className="stat-value">{stats.earnings.toFixed(2)}</div>
         </div>
@@ -175,7 +217,7 @@ export function Dashboard() {
             <div style={{ display: 'flex', flexDirection: 'column', gap: 'var(--gap-md)' }}>
               <div style={{ display: 'flex', justifyContent: 'space-between' }}>
                 <span style={{ color: 'var(--text-muted)' }}>Uptime</span>
-                <span style={{ color: 'var(--text-primary)', fontFamily: 'var(--font-mono)' }}>--:--:--</span>
+                <span style={{ color: 'var(--text-primary)', fontFamily: 'var(--font-mono)' }}>{formatUptime(stats.uptimeSecs)}</span>
               </div>
               <div style={{ display: 'flex', justifyContent: 'space-between' }}>
                 <span style={{ color: 'var(--text-muted)' }}>Network Status</span>
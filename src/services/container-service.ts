@@ -7,7 +7,7 @@
  */
 
 // Container status
-export type ContainerStatus = 'created' | 'running' | 'paused' | 'restarting' | 'removing' | 'exited' | 'dead' | 'unknown';
+export type ContainerStatus = 'creating' | 'created' | 'running' | 'paused' | 'restarting' | 'removing' | 'stopped' | 'exited' | 'dead' | 'unknown';
 
 // Port mapping
 export interface PortMapping {